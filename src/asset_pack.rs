@@ -0,0 +1,95 @@
+//! Asset packs: folders of metatile libraries and source images, such as the tiles shared by the
+//! community, that are installed once and can then be added to any project
+//!
+//! A pack is just a folder. Every `.nesimg-tiles` library and every image in it, or in the folders
+//! below it, is part of the pack, and the name of the folder is the name of the pack.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+use crate::library::{MetatileLibrary, LIBRARY_EXTENSION};
+
+/// The file extensions of the images that can be used as source images
+pub const SOURCE_IMAGE_EXTENSIONS: &[&str] = &["png", "bmp", "gif"];
+
+/// How many folders deep to look for assets inside of a pack
+const PACK_SEARCH_DEPTH: usize = 4;
+
+/// The assets found in an asset pack folder
+#[derive(Debug, Clone)]
+pub struct AssetPack {
+    /// The name of the pack, which is the name of its folder
+    pub name: String,
+    /// The folder the pack was loaded from
+    pub dir: PathBuf,
+    /// The metatile libraries in the pack, and the files they were loaded from
+    pub libraries: Vec<(PathBuf, MetatileLibrary)>,
+    /// The source images in the pack
+    pub images: Vec<PathBuf>,
+    /// The libraries that couldn't be loaded, with the reason why
+    pub errors: Vec<String>,
+}
+
+impl AssetPack {
+    /// Find the assets in a pack folder. Assets are sorted by their path, so the pack is listed
+    /// the same way on every system.
+    pub fn load(dir: &Path) -> anyhow::Result<Self> {
+        if !dir.is_dir() {
+            anyhow::bail!("Asset pack folder `{}` doesn't exist", dir.display());
+        }
+
+        let mut files = Vec::new();
+        collect_files(dir, PACK_SEARCH_DEPTH, &mut files)
+            .with_context(|| format!("Could not read asset pack `{}`", dir.display()))?;
+        files.sort();
+
+        let mut pack = AssetPack {
+            name: dir
+                .file_name()
+                .map(|x| x.to_string_lossy().into_owned())
+                .unwrap_or_else(|| dir.display().to_string()),
+            dir: dir.to_owned(),
+            libraries: Vec::new(),
+            images: Vec::new(),
+            errors: Vec::new(),
+        };
+        for path in files {
+            let extension = path
+                .extension()
+                .map(|x| x.to_string_lossy().to_ascii_lowercase())
+                .unwrap_or_default();
+            if extension == LIBRARY_EXTENSION {
+                match MetatileLibrary::load(&path) {
+                    Ok(library) => pack.libraries.push((path, library)),
+                    Err(e) => pack.errors.push(format!("{}: {:#}", path.display(), e)),
+                }
+            } else if SOURCE_IMAGE_EXTENSIONS.contains(&extension.as_str()) {
+                pack.images.push(path);
+            }
+        }
+
+        Ok(pack)
+    }
+
+    /// The path of an asset relative to the pack folder, for display
+    pub fn relative_path<'a>(&self, path: &'a Path) -> &'a Path {
+        path.strip_prefix(&self.dir).unwrap_or(path)
+    }
+}
+
+/// Add the paths of the files in `dir`, and in the folders inside of it down to `depth` levels, to
+/// `files`
+fn collect_files(dir: &Path, depth: usize, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            if depth > 0 {
+                collect_files(&path, depth - 1, files)?;
+            }
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
@@ -1,40 +1,939 @@
-use std::path::PathBuf;
+use std::{
+    collections::HashSet,
+    ffi::{OsStr, OsString},
+    fmt::Display,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
-use structopt::StructOpt;
+use anyhow::Context;
+use serde::Serialize;
+use structopt::{clap::Shell, StructOpt};
 use tracing as trc;
 
+use crate::{
+    export,
+    info::ProjectInfo,
+    map_csv,
+    pallet::{decode_pal_file, encode_pal_file},
+    project::{ExportContent, Project, ProjectFormat, ProjectTemplate, BINARY_PROJECT_EXTENSION},
+    schema,
+};
+
+mod manpage;
+
 #[derive(Debug, structopt::StructOpt)]
 #[structopt(
     name = "NESImg",
     about = "A background, sprite, and map editor for making NES games."
 )]
-enum Args {
+struct Args {
+    #[structopt(
+        long,
+        global = true,
+        default_value = "text",
+        possible_values = &ErrorFormat::NAMES,
+        help = "How to print errors to stderr: `text` for people, or `json` for scripts"
+    )]
+    error_format: ErrorFormat,
+    #[structopt(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, structopt::StructOpt)]
+enum Command {
     #[structopt(about = "Start the GUI interface")]
     Gui(GuiArgs),
+    #[structopt(about = "Export project data for use in an NES game")]
+    Export(ExportArgs),
+    #[structopt(about = "Convert metatileset pallets to and from 32 byte .pal files")]
+    Pal(PalArgs),
+    #[structopt(about = "Convert level maps to and from CSV and TSV files of metatile indexes")]
+    Map(MapArgs),
+    #[structopt(about = "Print a summary of a project")]
+    Info(InfoArgs),
+    #[structopt(about = "Convert a project between the RON and binary project formats")]
+    Convert(ConvertArgs),
+    #[structopt(
+        about = "Print a JSON Schema of the project format, for tools that read or write \
+    project files"
+    )]
+    Schema(SchemaArgs),
+    #[structopt(about = "Print a shell completion script for the NESImg commands")]
+    Completions(CompletionsArgs),
+    #[structopt(about = "Print a man page describing the NESImg commands")]
+    Manpage,
 }
 
 #[derive(Debug, structopt::StructOpt)]
 pub struct GuiArgs {
     pub project: Option<PathBuf>,
+    #[structopt(
+        long,
+        conflicts_with = "project",
+        help = "Create a new project file at this path and open it"
+    )]
+    pub new: Option<PathBuf>,
+    #[structopt(
+        long,
+        requires = "new",
+        default_value = "blank",
+        possible_values = &ProjectTemplate::NAMES,
+        help = "The kind of project to create with `--new`"
+    )]
+    pub template: ProjectTemplate,
+    #[structopt(
+        long,
+        requires = "new",
+        help = "Only create the project with `--new`, without starting the GUI"
+    )]
+    pub no_gui: bool,
+    #[structopt(
+        long,
+        help = "Start a new window even if NESImg is already running, instead of opening the \
+        project in the running window"
+    )]
+    pub new_instance: bool,
+}
+
+#[derive(Debug, structopt::StructOpt)]
+pub struct ExportArgs {
+    #[structopt(help = "The project file to export, or `-` to read it from standard input")]
+    pub project: PathBuf,
+    #[structopt(
+        long,
+        short,
+        help = "The name of the export target to use, defaults to the first target in the project"
+    )]
+    pub target: Option<String>,
+    #[structopt(
+        long,
+        short,
+        help = "The directory to write the exported files to, defaults to the target's output directory or `export/<target>` next to the project. Use `-` to write a single exported file to standard output"
+    )]
+    pub output: Option<PathBuf>,
+    #[structopt(
+        long,
+        requires = "output",
+        help = "The name of the exported file to write when the output is `-`, such as \
+        `overworld_chr.bin`, which may be left out if the export only writes one file"
+    )]
+    pub file: Option<String>,
+    #[structopt(
+        long,
+        help = "Export a second time to a temporary directory and fail if any file differs"
+    )]
+    pub verify_reproducible: bool,
+    #[structopt(
+        long,
+        help = "Only export one level or metatileset, such as `map:3` or `metatileset:overworld`, \
+        by name or by its number in the project, starting from 0"
+    )]
+    pub only: Option<String>,
+    #[structopt(
+        long,
+        conflicts_with = "pallets-only",
+        help = "Only export the attribute tables of each level and its HUD, instead of the \
+        target's configured content"
+    )]
+    pub attributes_only: bool,
+    #[structopt(
+        long,
+        help = "Only export the metatileset pallets, instead of the target's configured content"
+    )]
+    pub pallets_only: bool,
+}
+
+#[derive(Debug, structopt::StructOpt)]
+pub enum PalArgs {
+    #[structopt(about = "Write the pallet of each metatileset to a .pal file")]
+    Export(PalExportArgs),
+    #[structopt(about = "Replace the pallet of a metatileset with the colors from a .pal file")]
+    Import(PalImportArgs),
+}
+
+#[derive(Debug, structopt::StructOpt)]
+pub struct PalExportArgs {
+    #[structopt(
+        help = "The project file to export the pallets from, or `-` to read it from standard input"
+    )]
+    pub project: PathBuf,
+    #[structopt(
+        long,
+        short,
+        help = "The name of the metatileset to export, defaults to all of them"
+    )]
+    pub metatileset: Option<String>,
+    #[structopt(
+        long,
+        short,
+        help = "The directory to write the .pal files to, defaults to the project's directory. Use \
+        `-` to write the pallet of a single metatileset to standard output"
+    )]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, structopt::StructOpt)]
+pub struct PalImportArgs {
+    #[structopt(
+        help = "The project file to import the pallet into, or `-` to read it from standard input \
+        and write the changed project to standard output"
+    )]
+    pub project: PathBuf,
+    #[structopt(help = "The .pal file to import, or `-` to read it from standard input")]
+    pub pal_file: PathBuf,
+    #[structopt(
+        long,
+        short,
+        help = "The name of the metatileset to import the pallet into, which may be left out if \
+        the project only has one"
+    )]
+    pub metatileset: Option<String>,
+}
+
+#[derive(Debug, structopt::StructOpt)]
+pub enum MapArgs {
+    #[structopt(about = "Write the map of each level to a CSV or TSV file")]
+    Export(MapExportArgs),
+    #[structopt(about = "Replace the map of a level with the cells from a CSV or TSV file")]
+    Import(MapImportArgs),
+}
+
+#[derive(Debug, structopt::StructOpt)]
+pub struct MapExportArgs {
+    #[structopt(
+        help = "The project file to export the maps from, or `-` to read it from standard input"
+    )]
+    pub project: PathBuf,
+    #[structopt(
+        long,
+        short,
+        help = "The name of the level to export, defaults to all of them"
+    )]
+    pub level: Option<String>,
+    #[structopt(
+        long,
+        short,
+        help = "The directory to write the map files to, defaults to the project's directory. Use \
+        `-` to write the map of a single level to standard output"
+    )]
+    pub output: Option<PathBuf>,
+    #[structopt(long, help = "Write tab-separated .tsv files instead of .csv files")]
+    pub tsv: bool,
+}
+
+#[derive(Debug, structopt::StructOpt)]
+pub struct MapImportArgs {
+    #[structopt(
+        help = "The project file to import the map into, or `-` to read it from standard input \
+        and write the changed project to standard output"
+    )]
+    pub project: PathBuf,
+    #[structopt(
+        help = "The .csv or .tsv file to import, where .tsv files are tab-separated, or `-` to \
+        read it from standard input"
+    )]
+    pub map_file: PathBuf,
+    #[structopt(
+        long,
+        short,
+        help = "The name of the level to import the map into, which may be left out if the \
+        project only has one"
+    )]
+    pub level: Option<String>,
+}
+
+#[derive(Debug, structopt::StructOpt)]
+pub struct InfoArgs {
+    #[structopt(help = "The project file to summarize, or `-` to read it from standard input")]
+    pub project: PathBuf,
+    #[structopt(long, help = "Print the summary as JSON")]
+    pub json: bool,
+}
+
+#[derive(Debug, structopt::StructOpt)]
+pub struct ConvertArgs {
+    #[structopt(help = "The project file to convert, or `-` to read it from standard input")]
+    pub project: PathBuf,
+    #[structopt(
+        help = "The file to write, in the binary format if it ends in `.nesimgb` and RON \
+        otherwise, or `-` to write RON to standard output. Source paths are relative to the \
+        project, so keep it in the same directory"
+    )]
+    pub output: PathBuf,
+}
+
+#[derive(Debug, structopt::StructOpt)]
+pub struct SchemaArgs {
+    #[structopt(
+        long,
+        short,
+        default_value = "-",
+        help = "The file to write the schema to, defaults to standard output"
+    )]
+    pub output: PathBuf,
+}
+
+#[derive(Debug, structopt::StructOpt)]
+pub struct CompletionsArgs {
+    #[structopt(
+        possible_values = &Shell::variants(),
+        case_insensitive = true,
+        help = "The shell to print the completion script for"
+    )]
+    pub shell: Shell,
+}
+
+/// How errors are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ErrorFormat {
+    /// A line of text for people to read
+    Text,
+    /// A JSON object for scripts to parse
+    Json,
+}
+
+impl ErrorFormat {
+    const NAMES: [&'static str; 2] = ["text", "json"];
+}
+
+impl FromStr for ErrorFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(format!(
+                "Unknown error format `{}`, expected one of: {}",
+                s,
+                Self::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+/// The kinds of failures, each with its own exit code so that build scripts can react to them
+///
+/// The exit codes are stable, so scripts can rely on them across versions of NESImg.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Failure {
+    /// The input was read, but it isn't valid, such as a project that can't be parsed, a map file
+    /// with a cell that isn't a metatile, or an export that isn't reproducible
+    Invalid = 1,
+    /// The commandline arguments are wrong, such as an unknown flag or a level that isn't in the
+    /// project
+    BadArguments = 2,
+    /// A file couldn't be read or written
+    Io = 3,
+}
+
+impl Failure {
+    /// The kind of failure an error is
+    fn of(error: &anyhow::Error) -> Self {
+        if error.is::<BadArguments>() {
+            Self::BadArguments
+        } else if error.chain().any(|x| x.is::<std::io::Error>()) {
+            Self::Io
+        } else {
+            Self::Invalid
+        }
+    }
+
+    /// The name of the failure in JSON errors
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Invalid => "invalid",
+            Self::BadArguments => "bad_arguments",
+            Self::Io => "io",
+        }
+    }
+}
+
+/// An error caused by the commandline arguments, rather than the files they point to
+#[derive(Debug)]
+struct BadArguments(String);
+
+impl Display for BadArguments {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for BadArguments {}
+
+/// Make an error caused by the commandline arguments
+fn bad_arguments(message: impl Display) -> anyhow::Error {
+    BadArguments(format!("{:#}", message)).into()
+}
+
+/// An error, as printed with `--error-format json`
+#[derive(Serialize)]
+struct JsonError {
+    /// The kind of failure: `invalid`, `bad_arguments`, or `io`
+    kind: &'static str,
+    /// The exit code NESImg exits with
+    exit_code: i32,
+    /// The whole error message
+    message: String,
+    /// The error and each of the errors that caused it, from the outermost to the root cause
+    causes: Vec<String>,
+}
+
+/// Print an error in the requested format and exit with the exit code of its kind of failure
+fn exit_with_error(
+    format: ErrorFormat,
+    failure: Failure,
+    message: String,
+    causes: Vec<String>,
+) -> ! {
+    match format {
+        ErrorFormat::Text => eprintln!("Error: {}", message),
+        ErrorFormat::Json => eprintln!(
+            "{}",
+            serde_json::to_string(&JsonError {
+                kind: failure.name(),
+                exit_code: failure as i32,
+                message,
+                causes,
+            })
+            .expect("Serialize error")
+        ),
+    }
+    std::process::exit(failure as i32);
+}
+
+/// Find the error format in arguments that clap couldn't parse
+fn error_format_in(args: &[OsString]) -> ErrorFormat {
+    let args = args.iter().map(|x| x.to_string_lossy()).collect::<Vec<_>>();
+    let json = args.iter().any(|x| x == "--error-format=json")
+        || args
+            .windows(2)
+            .any(|x| x[0] == "--error-format" && x[1] == "json");
+    if json {
+        ErrorFormat::Json
+    } else {
+        ErrorFormat::Text
+    }
 }
 
 pub fn run() {
-    // Log to stdout (if you run with `RUST_LOG=debug`).
+    // Log to stderr (if you run with `RUST_LOG=debug`).
     setup_tracing();
 
-    let args = Args::from_args();
+    let raw_args = gui_args_from_file_association(std::env::args_os()).collect::<Vec<_>>();
+    let args = match Args::from_iter_safe(&raw_args) {
+        Ok(args) => args,
+        Err(e) if e.use_stderr() => match error_format_in(&raw_args) {
+            // Keep clap's message, which includes the usage of the command
+            ErrorFormat::Text => {
+                eprintln!("{}", e.message);
+                std::process::exit(Failure::BadArguments as i32);
+            }
+            ErrorFormat::Json => {
+                let message = e.message.lines().next().unwrap_or_default();
+                let message = message.trim_start_matches("error: ").to_owned();
+                exit_with_error(
+                    ErrorFormat::Json,
+                    Failure::BadArguments,
+                    message.clone(),
+                    vec![message],
+                );
+            }
+        },
+        // Help and version information
+        Err(e) => e.exit(),
+    };
     trc::debug!(?args, "Parsed commandline arguments");
 
-    match args {
-        Args::Gui(args) => crate::gui::run_gui(args),
+    let result = match args.command {
+        Command::Gui(args) => run_gui(args),
+        Command::Export(args) => export_project(args),
+        Command::Pal(PalArgs::Export(args)) => export_pal_files(args),
+        Command::Pal(PalArgs::Import(args)) => import_pal_file(args),
+        Command::Map(MapArgs::Export(args)) => export_map_files(args),
+        Command::Map(MapArgs::Import(args)) => import_map_file(args),
+        Command::Info(args) => print_info(args),
+        Command::Convert(args) => convert_project(args),
+        Command::Schema(args) => write_schema(args),
+        Command::Completions(args) => print_completions(args),
+        Command::Manpage => print_manpage(),
+    };
+
+    if let Err(e) = result {
+        exit_with_error(
+            args.error_format,
+            Failure::of(&e),
+            format!("{:#}", e),
+            e.chain().map(|x| x.to_string()).collect(),
+        );
     }
 }
 
+/// Treat launching NESImg with only a project file, which is how file managers open files with
+/// it, with no arguments, which is how app launchers start it, or with one of the GUI's flags, as
+/// running the `gui` command
+fn gui_args_from_file_association(
+    args: impl Iterator<Item = OsString>,
+) -> impl Iterator<Item = OsString> {
+    // Older versions of macOS pass a process serial number to apps started from Finder
+    let mut args = args
+        .filter(|x| !x.to_string_lossy().starts_with("-psn_"))
+        .collect::<Vec<_>>();
+    let launched_with_file = match args.get(1) {
+        Some(arg) => {
+            matches!(
+                Path::new(arg).extension().and_then(OsStr::to_str),
+                Some("nesimg" | BINARY_PROJECT_EXTENSION)
+            ) || arg.to_string_lossy().starts_with("--new")
+        }
+        None => true,
+    };
+    if launched_with_file {
+        args.insert(1.min(args.len()), "gui".into());
+    }
+    args.into_iter()
+}
+
+fn run_gui(mut args: GuiArgs) -> anyhow::Result<()> {
+    if let Some(path) = args.new.take() {
+        if path.exists() {
+            return Err(bad_arguments(format!(
+                "`{}` already exists",
+                path.display()
+            )));
+        }
+        args.template.project().save(&path)?;
+        eprintln!(
+            "Created `{}` from the {} template",
+            path.display(),
+            args.template
+        );
+
+        if args.no_gui {
+            return Ok(());
+        }
+        args.project = Some(path);
+    }
+
+    crate::gui::run_gui(args);
+    Ok(())
+}
+
+/// The path that stands for standard input or standard output on the commandline
+const STDIO_PATH: &str = "-";
+
+/// Where projects read from standard input are treated as being saved, so that their sources and
+/// default export directory are found relative to the current directory
+const STDIN_PROJECT_PATH: &str = "project.nesimg";
+
+/// Whether a commandline path stands for standard input or standard output
+fn is_stdio(path: &Path) -> bool {
+    path == Path::new(STDIO_PATH)
+}
+
+/// Read a file, or standard input if the path is `-`
+fn read_input(path: &Path) -> anyhow::Result<Vec<u8>> {
+    if is_stdio(path) {
+        let mut bytes = Vec::new();
+        std::io::stdin()
+            .read_to_end(&mut bytes)
+            .context("Read standard input")?;
+        Ok(bytes)
+    } else {
+        std::fs::read(path).with_context(|| format!("Read {}", path.display()))
+    }
+}
+
+/// Write a file, or standard output if the path is `-`
+fn write_output(path: &Path, bytes: &[u8]) -> anyhow::Result<()> {
+    if is_stdio(path) {
+        let mut stdout = std::io::stdout();
+        stdout
+            .write_all(bytes)
+            .and_then(|_| stdout.flush())
+            .context("Write standard output")
+    } else {
+        std::fs::write(path, bytes).with_context(|| format!("Write {}", path.display()))
+    }
+}
+
+/// Load a project file, or read the project from standard input if the path is `-`, along with
+/// the path that its sources are relative to
+fn load_project(path: &Path) -> anyhow::Result<(Project, PathBuf)> {
+    if is_stdio(path) {
+        let bytes = read_input(path)?;
+        let project = Project::from_bytes(&bytes, ProjectFormat::from_contents(&bytes))?;
+        Ok((project, PathBuf::from(STDIN_PROJECT_PATH)))
+    } else {
+        Ok((Project::load(path)?, path.to_owned()))
+    }
+}
+
+/// Save a project file, or write the project to standard output as RON if the path is `-`
+fn save_project(project: &Project, path: &Path) -> anyhow::Result<()> {
+    if is_stdio(path) {
+        write_output(path, &project.to_bytes(ProjectFormat::Ron)?)
+    } else {
+        project.save(path)
+    }
+}
+
+/// Make sure that at most one of the inputs of a command is read from standard input
+fn check_one_stdin(inputs: &[&Path]) -> anyhow::Result<()> {
+    if inputs.iter().filter(|x| is_stdio(x)).count() > 1 {
+        return Err(bad_arguments(
+            "Only one of the input files can be read from standard input",
+        ));
+    }
+    Ok(())
+}
+
+fn export_project(args: ExportArgs) -> anyhow::Result<()> {
+    let (project, project_path) = load_project(&args.project)?;
+    let mut target =
+        export::find_target(&project, args.target.as_deref()).map_err(bad_arguments)?;
+    if args.attributes_only {
+        target.content = ExportContent::Attributes;
+    } else if args.pallets_only {
+        target.content = ExportContent::Pallets;
+    }
+    // Exports to standard output are written to a temporary directory first, and the file to
+    // stream is picked from it
+    let to_stdout = matches!(args.output.as_deref(), Some(path) if is_stdio(path));
+    let out_dir = match args.output {
+        _ if to_stdout => {
+            std::env::temp_dir().join(format!("nesimg-stdout-{}", std::process::id()))
+        }
+        Some(dir) => dir,
+        None => export::default_output_dir(&project_path, &target),
+    };
+
+    let only = args
+        .only
+        .map(|x| export::ExportOnly::parse(&project, &x))
+        .transpose()
+        .map_err(bad_arguments)?;
+    let result = (|| {
+        let report = export::export_project(&project, &project_path, &target, only, &out_dir)?;
+
+        for warning in &report.warnings {
+            eprintln!("Warning: {}", warning);
+        }
+        for stat in &report.stats {
+            eprintln!("{}", stat);
+        }
+
+        if args.verify_reproducible {
+            export::verify_reproducible(&project, &project_path, &target, only, &out_dir, &report)?;
+            eprintln!("Verified that the export is reproducible");
+        }
+
+        if to_stdout {
+            write_exported_file(&report.files, args.file.as_deref())?;
+        } else {
+            for file in &report.files {
+                println!("{}", file.display());
+            }
+        }
+
+        Ok(())
+    })();
+    if to_stdout {
+        std::fs::remove_dir_all(&out_dir).ok();
+    }
+
+    result
+}
+
+/// Write one of the exported files to standard output, picked by its file name, or the only
+/// exported file if no name is given
+fn write_exported_file(files: &[PathBuf], name: Option<&str>) -> anyhow::Result<()> {
+    let file_name = |path: &PathBuf| {
+        path.file_name()
+            .map(|x| x.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    };
+    let names = || files.iter().map(file_name).collect::<Vec<_>>().join(", ");
+    let path = match name {
+        Some(name) => files.iter().find(|x| file_name(x) == name).ok_or_else(|| {
+            bad_arguments(format!(
+                "The export has no file named `{}`, it has: {}",
+                name,
+                names()
+            ))
+        })?,
+        None if files.len() == 1 => &files[0],
+        None => {
+            return Err(bad_arguments(format!(
+            "The export wrote {} files, pick the one to write to standard output with --file: {}",
+            files.len(),
+            names()
+        )))
+        }
+    };
+
+    write_output(Path::new(STDIO_PATH), &read_input(path)?)
+}
+
+fn export_pal_files(args: PalExportArgs) -> anyhow::Result<()> {
+    let (project, project_path) = load_project(&args.project)?;
+    let out_dir = args.output.unwrap_or_else(|| {
+        project_path
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .to_owned()
+    });
+    let to_stdout = is_stdio(&out_dir);
+    if !to_stdout {
+        std::fs::create_dir_all(&out_dir).context("Create output directory")?;
+    }
+
+    let metatilesets = project
+        .metatilesets
+        .values()
+        .filter(|x| match &args.metatileset {
+            Some(name) => &x.name == name,
+            None => true,
+        })
+        .collect::<Vec<_>>();
+    if let Some(name) = &args.metatileset {
+        if metatilesets.is_empty() {
+            return Err(bad_arguments(format!(
+                "Project has no metatileset named `{}`",
+                name
+            )));
+        }
+    }
+    if to_stdout {
+        let metatileset = match metatilesets.as_slice() {
+            [metatileset] => metatileset,
+            _ => {
+                return Err(bad_arguments(format!(
+                    "Project has {} metatilesets, pick the one to write to standard output with \
+                --metatileset",
+                    metatilesets.len()
+                )))
+            }
+        };
+        return write_output(&out_dir, &encode_pal_file(&metatileset.pallet));
+    }
+
+    let mut names = HashSet::new();
+    for metatileset in metatilesets {
+        let name = export::unique_symbol_name(&mut names, &metatileset.name);
+        let path = out_dir.join(format!("{}.pal", name));
+        write_output(&path, &encode_pal_file(&metatileset.pallet))?;
+        println!("{}", path.display());
+    }
+
+    Ok(())
+}
+
+fn import_pal_file(args: PalImportArgs) -> anyhow::Result<()> {
+    check_one_stdin(&[&args.project, &args.pal_file])?;
+    let (mut project, _) = load_project(&args.project)?;
+    let bytes = read_input(&args.pal_file)?;
+    let pallet = decode_pal_file(&bytes)?;
+
+    let metatileset = match &args.metatileset {
+        Some(name) => project
+            .metatilesets
+            .values_mut()
+            .find(|x| &x.name == name)
+            .ok_or_else(|| bad_arguments(format!("Project has no metatileset named `{}`", name)))?,
+        None if project.metatilesets.len() == 1 => project
+            .metatilesets
+            .values_mut()
+            .next()
+            .expect("One metatileset"),
+        None => {
+            return Err(bad_arguments(format!(
+                "Project has {} metatilesets, pick one with --metatileset",
+                project.metatilesets.len()
+            )))
+        }
+    };
+    if metatileset.set_pallet(pallet) {
+        eprintln!("Kept the colors of the locked sub-pallets");
+    }
+    let name = metatileset.name.clone();
+
+    save_project(&project, &args.project)?;
+    eprintln!("Imported pallet into metatileset `{}`", name);
+
+    Ok(())
+}
+
+fn export_map_files(args: MapExportArgs) -> anyhow::Result<()> {
+    let (project, project_path) = load_project(&args.project)?;
+    let out_dir = args.output.unwrap_or_else(|| {
+        project_path
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .to_owned()
+    });
+    let to_stdout = is_stdio(&out_dir);
+    if !to_stdout {
+        std::fs::create_dir_all(&out_dir).context("Create output directory")?;
+    }
+
+    let levels = project
+        .levels
+        .values()
+        .filter(|x| match &args.level {
+            Some(name) => &x.name == name,
+            None => true,
+        })
+        .collect::<Vec<_>>();
+    if let Some(name) = &args.level {
+        if levels.is_empty() {
+            return Err(bad_arguments(format!(
+                "Project has no level named `{}`",
+                name
+            )));
+        }
+    }
+
+    let extension = if args.tsv { "tsv" } else { "csv" };
+    if to_stdout {
+        let level = match levels.as_slice() {
+            [level] => level,
+            _ => {
+                return Err(bad_arguments(format!(
+                    "Project has {} levels, pick the one to write to standard output with --level",
+                    levels.len()
+                )))
+            }
+        };
+        let delimiter = if args.tsv { '\t' } else { ',' };
+        let metatileset = project.metatilesets.get(&level.metatileset_id);
+        return write_output(
+            &out_dir,
+            map_csv::encode_map(level, metatileset, delimiter).as_bytes(),
+        );
+    }
+
+    let mut names = HashSet::new();
+    for level in levels {
+        let name = export::unique_symbol_name(&mut names, &level.name);
+        let path = out_dir.join(format!("{}.{}", name, extension));
+        let metatileset = project.metatilesets.get(&level.metatileset_id);
+        write_output(
+            &path,
+            map_csv::encode_map(level, metatileset, map_csv::delimiter(&path)).as_bytes(),
+        )?;
+        println!("{}", path.display());
+    }
+
+    Ok(())
+}
+
+fn import_map_file(args: MapImportArgs) -> anyhow::Result<()> {
+    check_one_stdin(&[&args.project, &args.map_file])?;
+    let (mut project, _) = load_project(&args.project)?;
+    let text = String::from_utf8(read_input(&args.map_file)?).context("Map file isn't text")?;
+    // Map files from standard input don't have an extension, so look for tabs instead
+    let delimiter = if is_stdio(&args.map_file) && text.contains('\t') {
+        '\t'
+    } else {
+        map_csv::delimiter(&args.map_file)
+    };
+    let cells = map_csv::decode_map(&text, delimiter)?;
+
+    let level = match &args.level {
+        Some(name) => project
+            .levels
+            .values_mut()
+            .find(|x| &x.name == name)
+            .ok_or_else(|| bad_arguments(format!("Project has no level named `{}`", name)))?,
+        None if project.levels.len() == 1 => project.levels.values_mut().next().expect("One level"),
+        None => {
+            return Err(bad_arguments(format!(
+                "Project has {} levels, pick one with --level",
+                project.levels.len()
+            )))
+        }
+    };
+    let metatileset = project
+        .metatilesets
+        .get(&level.metatileset_id)
+        .with_context(|| format!("Level `{}` doesn't have a metatileset", level.name))?;
+    map_csv::import_map(level, metatileset, &cells)?;
+    let name = level.name.clone();
+
+    save_project(&project, &args.project)?;
+    eprintln!(
+        "Imported a {}x{} map into level `{}`",
+        cells[0].len(),
+        cells.len(),
+        name
+    );
+
+    Ok(())
+}
+
+fn print_info(args: InfoArgs) -> anyhow::Result<()> {
+    let (project, project_path) = load_project(&args.project)?;
+    let info = ProjectInfo::new(&project, &project_path);
+
+    if args.json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&info).context("Serialize project info")?
+        );
+    } else {
+        print!("{}", info.to_text());
+    }
+
+    Ok(())
+}
+
+fn convert_project(args: ConvertArgs) -> anyhow::Result<()> {
+    let (project, _) = load_project(&args.project)?;
+    save_project(&project, &args.output)?;
+    if is_stdio(&args.project) || is_stdio(&args.output) {
+        return Ok(());
+    }
+
+    let size = |path: &Path| std::fs::metadata(path).map(|x| x.len()).unwrap_or_default();
+    eprintln!(
+        "Converted {} ({} bytes) to {} ({} bytes)",
+        args.project.display(),
+        size(&args.project),
+        args.output.display(),
+        size(&args.output)
+    );
+
+    Ok(())
+}
+
+fn write_schema(args: SchemaArgs) -> anyhow::Result<()> {
+    let schema = schema::project_schema()?;
+    let mut json = serde_json::to_string_pretty(&schema).context("Serialize schema")?;
+    json.push('\n');
+    write_output(&args.output, json.as_bytes())
+}
+
+fn print_completions(args: CompletionsArgs) -> anyhow::Result<()> {
+    Args::clap().gen_completions_to("nesimg", args.shell, &mut std::io::stdout());
+    Ok(())
+}
+
+fn print_manpage() -> anyhow::Result<()> {
+    write_output(
+        Path::new(STDIO_PATH),
+        manpage::manpage(Args::clap()).as_bytes(),
+    )
+}
+
 fn setup_tracing() {
     use tracing_subscriber::prelude::*;
     use tracing_subscriber::{fmt, EnvFilter};
 
-    let fmt_layer = fmt::layer();
+    // Logs go to stderr, so they don't mix with files written to stdout
+    let fmt_layer = fmt::layer().with_writer(std::io::stderr);
     let filter_layer =
         EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("nesimg_gui=debug"));
 
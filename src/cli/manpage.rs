@@ -0,0 +1,155 @@
+//! A man page generated from the commandline definitions
+//!
+//! The page holds the `--help` output of every command, so it always matches the commands that
+//! NESImg actually accepts. The commands are found by reading the `SUBCOMMANDS` section of the
+//! help too, since clap doesn't have a public way to list them.
+
+use std::fmt::Write;
+
+use structopt::clap::{App, AppSettings, ErrorKind};
+
+/// The width the help of each command is wrapped to
+const HELP_WIDTH: usize = 80;
+
+/// Write a man page in roff format for the commandline described by `app`
+pub fn manpage(app: App<'_, '_>) -> String {
+    let app = app
+        .setting(AppSettings::ColorNever)
+        .set_term_width(HELP_WIDTH);
+    let name = app.get_name().to_lowercase();
+    let help = command_help(&app, &[]);
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        ".TH {} 1 \"\" \"NESImg {}\" \"User Commands\"",
+        name.to_uppercase(),
+        env!("CARGO_PKG_VERSION")
+    )
+    .unwrap();
+    out.push_str(".SH NAME\n");
+    writeln!(out, "{} \\- {}", name, escape(&help_about(&help))).unwrap();
+    out.push_str(".SH SYNOPSIS\n");
+    writeln!(out, ".B {}\n[\\fIPROJECT\\fR]\n.br", name).unwrap();
+    writeln!(out, ".B {}\n\\fICOMMAND\\fR [\\fIOPTIONS\\fR]", name).unwrap();
+    out.push_str(".SH DESCRIPTION\n");
+    writeln!(
+        out,
+        "Running {} with only a project file, or with no arguments at all, starts the GUI. The \
+        other commands work on project files without opening a window, for use in build scripts.",
+        name
+    )
+    .unwrap();
+    out.push_str(".SH COMMANDS\n");
+    for path in command_paths(&app, &[]) {
+        writeln!(out, ".SS {} {}", name, path.join(" ")).unwrap();
+        out.push_str(".nf\n");
+        // Skip the name and version line, since the section title already names the command
+        for line in command_help(&app, &path).lines().skip(1) {
+            writeln!(out, "{}", escape(line)).unwrap();
+        }
+        out.push_str(".fi\n");
+    }
+
+    out
+}
+
+/// The subcommands of the command at `parent`, and their subcommands, depth first, skipping
+/// clap's `help`
+fn command_paths(app: &App<'_, '_>, parent: &[String]) -> Vec<Vec<String>> {
+    let mut paths = Vec::new();
+    for name in subcommand_names(&command_help(app, parent)) {
+        if name == "help" {
+            continue;
+        }
+        let mut path = parent.to_vec();
+        path.push(name);
+        let subcommands = command_paths(app, &path);
+        if subcommands.is_empty() {
+            paths.push(path);
+        } else {
+            paths.extend(subcommands);
+        }
+    }
+    paths
+}
+
+/// The names in the `SUBCOMMANDS` section of a command's `--help` output. Each one starts a line
+/// that is indented by four spaces, and descriptions that wrap are indented further.
+fn subcommand_names(help: &str) -> Vec<String> {
+    help.lines()
+        .skip_while(|x| x.trim_end() != "SUBCOMMANDS:")
+        .skip(1)
+        .take_while(|x| !x.trim().is_empty())
+        .filter_map(|x| x.strip_prefix("    "))
+        .filter(|x| !x.starts_with(' '))
+        .filter_map(|x| x.split_whitespace().next())
+        .map(str::to_owned)
+        .collect()
+}
+
+/// The description of a command, from the lines of its `--help` output between the name and
+/// version line and the usage
+fn help_about(help: &str) -> String {
+    help.lines()
+        .skip(1)
+        .take_while(|x| !x.trim().is_empty())
+        .map(str::trim)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The `--help` output of the command at `path`
+fn command_help(app: &App<'_, '_>, path: &[String]) -> String {
+    let args = std::iter::once(app.get_name().to_lowercase())
+        .chain(path.iter().cloned())
+        .chain(std::iter::once("--help".to_owned()));
+    match app.clone().get_matches_from_safe(args) {
+        Err(e) if e.kind == ErrorKind::HelpDisplayed => e.message,
+        _ => String::new(),
+    }
+}
+
+/// Escape text so that roff prints it as it is
+fn escape(text: &str) -> String {
+    let text = text.replace('\\', "\\e");
+    if text.starts_with('.') || text.starts_with('\'') {
+        format!("\\&{}", text)
+    } else {
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HELP: &str = "\
+NESImg 0.1.0
+A background, sprite, and map editor
+for making NES games.
+
+USAGE:
+    nesimg <SUBCOMMAND>
+
+SUBCOMMANDS:
+    export    Export project data for use in an NES game, with a description that wraps
+              onto the next line
+    help      Prints this message or the help of the given subcommand(s)
+    info      Print a summary of a project
+";
+
+    #[test]
+    fn reads_subcommands_from_help() {
+        assert_eq!(subcommand_names(HELP), ["export", "help", "info"]);
+        assert!(subcommand_names("NESImg 0.1.0\n\nUSAGE:\n    nesimg\n").is_empty());
+    }
+
+    #[test]
+    fn reads_about_from_help() {
+        assert_eq!(
+            help_about(HELP),
+            "A background, sprite, and map editor for making NES games."
+        );
+    }
+}
@@ -0,0 +1,183 @@
+//! Grouping metatiles into proposed metatilesets by the tiles and colors they share
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use indexmap::IndexMap;
+
+use crate::{
+    project::{Metatile, Metatileset, Project, Tile},
+    Uid,
+};
+
+/// The most 8x8 tiles a group can use, so that it fits in one pattern table
+pub const MAX_CLUSTER_TILES: usize = 256;
+
+/// The most colors, not counting the background color, that a group can use, so that it could
+/// fit in the four sub-pallets of one pallet
+pub const MAX_CLUSTER_COLORS: usize = 12;
+
+/// A group of metatiles that would make a good metatileset
+#[derive(Debug, Clone)]
+pub struct MetatileCluster {
+    /// The metatiles in the group, in the order they are in the project
+    pub metatiles: Vec<Uid<Metatile>>,
+    /// The existing metatileset that already has the most metatiles from the group, if any.
+    ///
+    /// Each metatileset is suggested for at most one group.
+    pub metatileset_id: Option<Uid<Metatileset>>,
+    /// The number of distinct 8x8 tiles used by the group
+    pub tile_count: usize,
+    /// The number of distinct colors, not counting the background color, that the group is
+    /// colored with in its current metatilesets
+    pub color_count: usize,
+}
+
+/// The tiles and colors used by a metatile, or by a group of them
+#[derive(Default, Clone)]
+struct Usage {
+    tiles: HashSet<Tile>,
+    colors: BTreeSet<u32>,
+}
+
+impl Usage {
+    /// How alike two usages are, from 0 when they share nothing to 2 when they are the same
+    fn similarity(&self, other: &Usage) -> f32 {
+        let jaccard = |shared: usize, a: usize, b: usize| match a + b - shared {
+            0 => 0.0,
+            total => shared as f32 / total as f32,
+        };
+        jaccard(
+            self.tiles.intersection(&other.tiles).count(),
+            self.tiles.len(),
+            other.tiles.len(),
+        ) + jaccard(
+            self.colors.intersection(&other.colors).count(),
+            self.colors.len(),
+            other.colors.len(),
+        )
+    }
+
+    /// Whether the combined usage of two groups still fits in a metatileset
+    fn fits_with(&self, other: &Usage) -> bool {
+        let tiles = self.tiles.len() + other.tiles.difference(&self.tiles).count();
+        let colors = self.colors.len() + other.colors.difference(&self.colors).count();
+        tiles <= MAX_CLUSTER_TILES && colors <= MAX_CLUSTER_COLORS
+    }
+}
+
+/// Group the metatiles of a project into proposed metatilesets.
+///
+/// Pairs of metatiles are merged into the same group from most to least alike, based on the 8x8
+/// tiles they share and the colors they are currently colored with, as long as the merged group
+/// still fits in one pattern table and one pallet. Metatiles that share nothing with any others
+/// are left in groups of their own. The groups are sorted from largest to smallest.
+pub fn cluster_metatiles(project: &Project) -> Vec<MetatileCluster> {
+    let ids = project.metatiles.keys().copied().collect::<Vec<_>>();
+    let indexes = ids
+        .iter()
+        .enumerate()
+        .map(|(i, id)| (*id, i))
+        .collect::<HashMap<_, _>>();
+
+    let mut usages = project
+        .metatiles
+        .values()
+        .map(|metatile| Usage {
+            tiles: metatile.tiles.iter().flatten().cloned().collect(),
+            colors: BTreeSet::new(),
+        })
+        .collect::<Vec<_>>();
+    for metatileset in project.metatilesets.values() {
+        for tile in metatileset.tiles.values() {
+            if let Some(&i) = indexes.get(&tile.metatile_id) {
+                let [background, colors @ ..] = metatileset.sub_pallet(tile.sub_pallet_idx);
+                usages[i]
+                    .colors
+                    .extend(colors.into_iter().filter(|&color| color != background));
+            }
+        }
+    }
+
+    let mut pairs = Vec::new();
+    for i in 0..usages.len() {
+        for j in (i + 1)..usages.len() {
+            let similarity = usages[i].similarity(&usages[j]);
+            if similarity > 0.0 {
+                pairs.push((similarity, i, j));
+            }
+        }
+    }
+    pairs.sort_by(|a, b| {
+        b.0.partial_cmp(&a.0)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then((a.1, a.2).cmp(&(b.1, b.2)))
+    });
+
+    // Merge the groups with a union-find, keeping the combined usage at each group's root
+    let mut parents = (0..usages.len()).collect::<Vec<_>>();
+    fn root(parents: &mut [usize], mut i: usize) -> usize {
+        while parents[i] != i {
+            parents[i] = parents[parents[i]];
+            i = parents[i];
+        }
+        i
+    }
+    for (_, i, j) in pairs {
+        let (a, b) = (root(&mut parents, i), root(&mut parents, j));
+        if a == b || !usages[a].fits_with(&usages[b]) {
+            continue;
+        }
+        let (into, from) = if usages[a].tiles.len() >= usages[b].tiles.len() {
+            (a, b)
+        } else {
+            (b, a)
+        };
+        let merged = std::mem::take(&mut usages[from]);
+        usages[into].tiles.extend(merged.tiles);
+        usages[into].colors.extend(merged.colors);
+        parents[from] = into;
+    }
+
+    let mut groups = IndexMap::<usize, Vec<Uid<Metatile>>>::new();
+    for (i, id) in ids.iter().enumerate() {
+        let group = root(&mut parents, i);
+        groups.entry(group).or_default().push(*id);
+    }
+    let mut clusters = groups
+        .into_iter()
+        .map(|(group, metatiles)| MetatileCluster {
+            metatiles,
+            metatileset_id: None,
+            tile_count: usages[group].tiles.len(),
+            color_count: usages[group].colors.len(),
+        })
+        .collect::<Vec<_>>();
+    clusters.sort_by_key(|cluster| std::cmp::Reverse(cluster.metatiles.len()));
+
+    // Suggest the existing metatilesets that the groups overlap the most with
+    let mut overlaps = Vec::new();
+    for (cluster_idx, cluster) in clusters.iter().enumerate() {
+        let members = cluster.metatiles.iter().collect::<HashSet<_>>();
+        for (metatileset_id, metatileset) in &project.metatilesets {
+            let overlap = metatileset
+                .tiles
+                .values()
+                .map(|tile| &tile.metatile_id)
+                .collect::<HashSet<_>>()
+                .intersection(&members)
+                .count();
+            if overlap > 0 {
+                overlaps.push((overlap, cluster_idx, *metatileset_id));
+            }
+        }
+    }
+    overlaps.sort_by_key(|(overlap, cluster_idx, _)| (std::cmp::Reverse(*overlap), *cluster_idx));
+    let mut claimed = HashSet::new();
+    for (_, cluster_idx, metatileset_id) in overlaps {
+        if clusters[cluster_idx].metatileset_id.is_none() && claimed.insert(metatileset_id) {
+            clusters[cluster_idx].metatileset_id = Some(metatileset_id);
+        }
+    }
+
+    clusters
+}
@@ -1,10 +1,16 @@
-use egui::{color::linear_f32_from_gamma_u8, Color32};
+use egui::Color32;
 use once_cell::sync::Lazy;
 
 // Uncomment if we need clipboard support later
 // pub(crate) static CLIPBOARD: Lazy<Mutex<arboard::Clipboard>> =
 //     Lazy::new(|| Mutex::new(arboard::Clipboard::new().expect("Access clipboard")));
 
+/// The width of the NES screen in metatiles
+pub const SCREEN_WIDTH_METATILES: usize = 16;
+
+/// The height of the NES screen in metatiles
+pub const SCREEN_HEIGHT_METATILES: usize = 15;
+
 /// NES color pallet
 pub static NES_PALLET: Lazy<[Color32; 64]> = Lazy::new(|| {
     [
@@ -75,24 +81,74 @@ pub static NES_PALLET: Lazy<[Color32; 64]> = Lazy::new(|| {
     ]
 });
 
-pub static NES_PALLET_SHADER_CONST: Lazy<String> = Lazy::new(|| {
-    let color_count = NES_PALLET.len();
-    let mut color_list = Vec::new();
-    for color in NES_PALLET.iter() {
-        color_list.push(format!(
-            "vec3<f32>({:.7}, {:.7}, {:.7})",
-            linear_f32_from_gamma_u8(color[0]),
-            linear_f32_from_gamma_u8(color[1]),
-            linear_f32_from_gamma_u8(color[2]),
-        ));
-    }
-    let color_list = color_list.join(",\n");
+/// The "blacker than black" NES color, which is darker than the video signal's black level and
+/// can make some TVs lose sync
+pub const FORBIDDEN_COLOR: u32 = 0x0D;
 
-    format!(
-        r#"var<private> NES_PALLET: array<vec3<f32>, {color_count}> = array<vec3<f32>, {color_count}>(
-            {color_list}
-        );"#,
-        color_list = color_list,
-        color_count = color_count
-    )
-});
+/// Common names for the colors in [`NES_PALLET`], for display in the GUI
+pub const NES_COLOR_NAMES: [&str; 64] = [
+    "Dark Gray",
+    "Navy",
+    "Dark Blue",
+    "Dark Violet",
+    "Dark Purple",
+    "Maroon",
+    "Dark Red",
+    "Brown",
+    "Dark Olive",
+    "Dark Green",
+    "Forest Green",
+    "Dark Emerald",
+    "Dark Teal",
+    "Blacker Than Black",
+    "Black",
+    "Black",
+    "Gray",
+    "Azure",
+    "Blue",
+    "Indigo",
+    "Purple",
+    "Crimson",
+    "Red",
+    "Rust",
+    "Olive",
+    "Green",
+    "Kelly Green",
+    "Emerald",
+    "Teal",
+    "Black",
+    "Black",
+    "Black",
+    "White",
+    "Sky Blue",
+    "Periwinkle",
+    "Lavender",
+    "Magenta",
+    "Pink",
+    "Salmon",
+    "Orange",
+    "Yellow",
+    "Lime",
+    "Light Green",
+    "Mint",
+    "Cyan",
+    "Dark Gray",
+    "Black",
+    "Black",
+    "White",
+    "Pale Blue",
+    "Pale Periwinkle",
+    "Pale Lavender",
+    "Pale Magenta",
+    "Pale Pink",
+    "Pale Salmon",
+    "Tan",
+    "Pale Yellow",
+    "Pale Lime",
+    "Pale Green",
+    "Pale Mint",
+    "Pale Cyan",
+    "Light Gray",
+    "Black",
+    "Black",
+];
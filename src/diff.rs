@@ -0,0 +1,180 @@
+//! Comparing two versions of a project
+
+use std::path::PathBuf;
+
+use indexmap::IndexMap;
+
+use crate::{
+    project::{Level, LevelMargin, Metatile, Metatileset, Pallet, Project},
+    Uid,
+};
+
+/// The differences between two versions of a project.
+///
+/// Everything is described as the changes needed to get from the `base` project to the `other`
+/// project, so "added" items are only in `other`, and "removed" items are only in `base`.
+#[derive(Debug, Clone, Default)]
+pub struct ProjectDiff {
+    pub sources: EntityDiff<PathBuf>,
+    pub metatiles: EntityDiff<Metatile>,
+    pub metatilesets: EntityDiff<Metatileset>,
+    /// The metatilesets, present in both projects, that have a different pallet
+    pub pallets: Vec<PalletChange>,
+    pub levels: EntityDiff<Level>,
+    /// The levels, present in both projects, that have different tiles or bounds
+    pub level_changes: Vec<LevelChange>,
+}
+
+/// The ids of the items that were added, removed, or changed in a collection
+#[derive(Debug, Clone)]
+pub struct EntityDiff<T> {
+    pub added: Vec<Uid<T>>,
+    pub removed: Vec<Uid<T>>,
+    pub changed: Vec<Uid<T>>,
+}
+
+// Derived `Default` would require `T: Default`
+impl<T> Default for EntityDiff<T> {
+    fn default() -> Self {
+        Self {
+            added: Vec::new(),
+            removed: Vec::new(),
+            changed: Vec::new(),
+        }
+    }
+}
+
+impl<T> EntityDiff<T> {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// A metatileset pallet that was changed
+#[derive(Debug, Clone)]
+pub struct PalletChange {
+    pub metatileset_id: Uid<Metatileset>,
+    pub before: Pallet,
+    pub after: Pallet,
+}
+
+/// The changes made to a level that is in both projects
+#[derive(Debug, Clone)]
+pub struct LevelChange {
+    pub level_id: Uid<Level>,
+    pub margin_before: LevelMargin,
+    pub margin_after: LevelMargin,
+    /// The map cells that were changed, and how
+    pub cells: IndexMap<(i32, i32), CellChange>,
+}
+
+/// How a level map cell was changed
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CellChange {
+    Added,
+    Removed,
+    Changed,
+}
+
+impl ProjectDiff {
+    /// Compare two projects
+    pub fn new(base: &Project, other: &Project) -> Self {
+        let metatilesets = diff_entities(&base.metatilesets, &other.metatilesets);
+        let pallets = metatilesets
+            .changed
+            .iter()
+            .filter_map(|id| {
+                let before = &base.metatilesets[id].pallet;
+                let after = &other.metatilesets[id].pallet;
+                (before != after).then(|| PalletChange {
+                    metatileset_id: *id,
+                    before: before.clone(),
+                    after: after.clone(),
+                })
+            })
+            .collect();
+
+        let levels = diff_entities(&base.levels, &other.levels);
+        let level_changes = levels
+            .changed
+            .iter()
+            .filter_map(|id| {
+                let change = diff_level(*id, &base.levels[id], &other.levels[id]);
+                if change.cells.is_empty() && change.margin_before == change.margin_after {
+                    None
+                } else {
+                    Some(change)
+                }
+            })
+            .collect();
+
+        Self {
+            sources: diff_entities(&base.sources, &other.sources),
+            metatiles: diff_entities(&base.metatiles, &other.metatiles),
+            metatilesets,
+            pallets,
+            levels,
+            level_changes,
+        }
+    }
+
+    /// Whether the projects have the same sources, metatiles, metatilesets, and levels
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+            && self.metatiles.is_empty()
+            && self.metatilesets.is_empty()
+            && self.levels.is_empty()
+    }
+}
+
+/// Compare two collections of project items by their ids
+fn diff_entities<T: PartialEq>(
+    base: &IndexMap<Uid<T>, T>,
+    other: &IndexMap<Uid<T>, T>,
+) -> EntityDiff<T> {
+    let mut diff = EntityDiff::default();
+
+    for (id, item) in base {
+        match other.get(id) {
+            None => diff.removed.push(*id),
+            Some(other_item) if other_item != item => diff.changed.push(*id),
+            Some(_) => (),
+        }
+    }
+    for id in other.keys() {
+        if !base.contains_key(id) {
+            diff.added.push(*id);
+        }
+    }
+
+    diff
+}
+
+/// Compare the map cells of two versions of a level
+fn diff_level(level_id: Uid<Level>, base: &Level, other: &Level) -> LevelChange {
+    let mut cells = IndexMap::new();
+
+    for (pos, tile) in base.tiles.iter() {
+        match other.tiles.get(pos) {
+            None => {
+                cells.insert(*pos, CellChange::Removed);
+            }
+            Some(other_tile) if other_tile != tile => {
+                cells.insert(*pos, CellChange::Changed);
+            }
+            Some(_) => (),
+        }
+    }
+    for pos in other.tiles.keys() {
+        if !base.tiles.contains_key(pos) {
+            cells.insert(*pos, CellChange::Added);
+        }
+    }
+
+    LevelChange {
+        level_id,
+        margin_before: base.margin,
+        margin_after: other.margin,
+        cells,
+    }
+}
@@ -0,0 +1,167 @@
+//! Ordered dithering between two colors of a source image, for drawing gradients with the few
+//! colors that a tile can use
+//!
+//! Ordered patterns repeat every few pixels, so a dithered area only needs a few unique tiles in
+//! the pattern table, and at 1x the regular grid blends into an even mix of the two colors, where
+//! random noise would look grainy. The patterns are aligned to the image instead of the region, so
+//! neighboring regions dithered separately line up.
+
+use crate::source::PixelRegion;
+
+/// The 2x2 ordered dithering matrix
+const BAYER_2X2: [[u8; 2]; 2] = [[0, 2], [3, 1]];
+
+/// The 4x4 ordered dithering matrix
+const BAYER_4X4: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// The order that the rows of the line pattern are filled in
+const LINE_ORDER: [u8; 4] = [0, 2, 1, 3];
+
+/// The arrangement of pixels used to mix the two colors
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DitherPattern {
+    /// A 2x2 pattern with 5 levels, where the middle level is a checkerboard
+    Bayer2x2,
+    /// A 4x4 pattern with 17 levels, for smoother gradients
+    Bayer4x4,
+    /// Horizontal lines, which read as scanlines on a TV
+    Lines,
+    /// Fill the region with whichever of the two colors it uses the most, to remove dithering
+    Solid,
+}
+
+impl DitherPattern {
+    pub const ALL: [DitherPattern; 4] = [
+        DitherPattern::Bayer2x2,
+        DitherPattern::Bayer4x4,
+        DitherPattern::Lines,
+        DitherPattern::Solid,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DitherPattern::Bayer2x2 => "2x2",
+            DitherPattern::Bayer4x4 => "4x4",
+            DitherPattern::Lines => "Lines",
+            DitherPattern::Solid => "Solid (De-dither)",
+        }
+    }
+
+    /// The density that a pixel of the image needs to be above to get the second color, from `0`
+    /// to `1`
+    fn threshold(&self, x: u32, y: u32) -> f32 {
+        match self {
+            DitherPattern::Bayer2x2 => {
+                (BAYER_2X2[y as usize % 2][x as usize % 2] as f32 + 0.5) / 4.0
+            }
+            DitherPattern::Bayer4x4 => {
+                (BAYER_4X4[y as usize % 4][x as usize % 4] as f32 + 0.5) / 16.0
+            }
+            DitherPattern::Lines => (LINE_ORDER[y as usize % 4] as f32 + 0.5) / 4.0,
+            DitherPattern::Solid => 0.5,
+        }
+    }
+}
+
+/// How the amount of the second color changes across the region
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DitherDirection {
+    /// The same density everywhere
+    Flat,
+    /// From all first color on the left to all second color on the right
+    LeftToRight,
+    /// From all first color at the top to all second color at the bottom
+    TopToBottom,
+}
+
+impl DitherDirection {
+    pub const ALL: [DitherDirection; 3] = [
+        DitherDirection::Flat,
+        DitherDirection::LeftToRight,
+        DitherDirection::TopToBottom,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DitherDirection::Flat => "Flat",
+            DitherDirection::LeftToRight => "Left to Right",
+            DitherDirection::TopToBottom => "Top to Bottom",
+        }
+    }
+}
+
+/// The settings for dithering a region between two color indexes
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Dither {
+    pub pattern: DitherPattern,
+    pub direction: DitherDirection,
+    /// The amount of the second color used by flat dithering, from `0` to `1`
+    pub density: f32,
+    /// The color indexes, `0`-`3`, that are mixed
+    pub colors: [u8; 2],
+    /// Whether pixels that use other colors, like outlines, are left alone
+    pub keep_other_colors: bool,
+}
+
+impl Default for Dither {
+    fn default() -> Self {
+        Self {
+            pattern: DitherPattern::Bayer2x2,
+            direction: DitherDirection::LeftToRight,
+            density: 0.5,
+            colors: [1, 2],
+            keep_other_colors: true,
+        }
+    }
+}
+
+impl Dither {
+    /// The new color indexes of the pixels of `region`, in row-major order, for an image with the
+    /// given width and color indexes
+    pub fn apply(&self, indexes: &[u8], image_width: u32, region: PixelRegion) -> Vec<u8> {
+        let index_at = |x: u32, y: u32| indexes[(y * image_width + x) as usize];
+        let is_mixed = |idx: u8| self.colors.contains(&idx);
+
+        // De-dithering fills with the color that the region is mostly made of
+        let solid = {
+            let second = region
+                .pixels()
+                .filter(|&(x, y)| index_at(x, y) == self.colors[1])
+                .count();
+            let first = region
+                .pixels()
+                .filter(|&(x, y)| index_at(x, y) == self.colors[0])
+                .count();
+            self.colors[(second > first) as usize]
+        };
+
+        region
+            .pixels()
+            .map(|(x, y)| {
+                let current = index_at(x, y);
+                if self.keep_other_colors && !is_mixed(current) {
+                    current
+                } else if self.pattern == DitherPattern::Solid {
+                    solid
+                } else if self.density(x, y, region) > self.pattern.threshold(x, y) {
+                    self.colors[1]
+                } else {
+                    self.colors[0]
+                }
+            })
+            .collect()
+    }
+
+    /// The amount of the second color at a pixel of the region, from `0` to `1`
+    fn density(&self, x: u32, y: u32, region: PixelRegion) -> f32 {
+        match self.direction {
+            DitherDirection::Flat => self.density,
+            DitherDirection::LeftToRight => {
+                (x - region.x) as f32 / (region.width.max(2) - 1) as f32
+            }
+            DitherDirection::TopToBottom => {
+                (y - region.y) as f32 / (region.height.max(2) - 1) as f32
+            }
+        }
+    }
+}
@@ -0,0 +1,1479 @@
+//! Exporting project data to formats that can be loaded by NES games
+//!
+//! Exports are reproducible: the same project and target always produce byte-identical files.
+//! Tables are built in project order, hash maps are only used for lookups, never iterated to
+//! produce output, and nothing that changes between runs, like the time, is written to files.
+
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use indexmap::{IndexMap, IndexSet};
+use rayon::prelude::*;
+
+use crate::{
+    constants::{
+        FORBIDDEN_COLOR, NES_COLOR_NAMES, SCREEN_HEIGHT_METATILES, SCREEN_WIDTH_METATILES,
+    },
+    project::{
+        ChrCompression, ExitSource, ExportContent, ExportFormat, ExportTarget, Level, MapEdge,
+        Metatileset, Project, ScreenFlags, Tile, ZoneKind,
+    },
+    source::IndexedImage,
+    Uid,
+};
+
+mod animation;
+mod attributes;
+mod binary;
+mod c;
+mod ca65;
+mod checksum;
+pub mod compression;
+mod dpcm;
+mod html;
+mod hud;
+mod javascript;
+mod metatile_layout;
+mod python;
+mod scrolling;
+pub mod streaming;
+mod symbols;
+mod tile_sources;
+
+/// The map table value used for level cells that don't have a metatile in them
+pub const EMPTY_METATILE: u8 = 0xFF;
+
+/// The maximum number of tiles that fit in one pattern table
+pub const PATTERN_TABLE_TILES: usize = 256;
+
+/// How long building the tables has to take, summed across threads, before the export report
+/// shows how much faster it was than using one thread
+const SPEEDUP_REPORT_MIN_WORK: Duration = Duration::from_millis(100);
+
+/// The exit kind used in the connections table for exits that start from a zone
+const EXIT_FROM_ZONE: u8 = 4;
+
+/// The zone index used in the connections table for exits that don't use a zone
+const NO_ZONE: u8 = 0xFF;
+
+/// All of the tables generated from a project, independent of the output format
+#[derive(Debug, Clone, Default)]
+pub struct ExportData {
+    pub tables: Vec<Table>,
+    /// Named values used in the tables, written by the text formats so that game code doesn't
+    /// need magic numbers
+    pub constants: Vec<ConstantGroup>,
+    /// The constant name and CRC-32 of each table, in the same order as the tables, when the
+    /// target asks for checksums
+    pub checksums: Vec<(String, u32)>,
+}
+
+/// A set of related named values, such as the zone kinds
+#[derive(Debug, Clone)]
+pub struct ConstantGroup {
+    /// A human-readable description of the group, written as a comment when enabled
+    pub description: String,
+    /// The upper-case symbol name and value of each constant
+    pub constants: Vec<(String, u8)>,
+}
+
+/// A named blob of bytes that will be written out by the exporter
+#[derive(Debug, Clone)]
+pub struct Table {
+    /// The kind of data in the table
+    pub kind: TableKind,
+    /// The symbol name of the table, which is also used for file names
+    pub name: String,
+    /// A human-readable description of the table, written as a comment when enabled
+    pub description: String,
+    /// The table contents
+    pub data: Vec<u8>,
+    /// The number of bytes to put on each line in text formats
+    pub row_len: usize,
+    /// Where the bytes on each row came from, written as comments by the assembly export when
+    /// the target asks for provenance comments. Empty for tables that don't have them.
+    pub row_comments: Vec<String>,
+    /// The assembler segment the table is placed in, or `None` for the target's default segment
+    pub segment: Option<String>,
+}
+
+/// The kinds of data that are exported
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TableKind {
+    /// A metatileset pattern table
+    Chr,
+    /// The tile indexes and sub-pallet of each metatile in a metatileset
+    Metatiles,
+    /// A metatileset pallet
+    Pallet,
+    /// The pallet swaps part way down the screen of a level
+    PalletRegions,
+    /// A level map
+    Map,
+    /// The tiles needed by each screen of a level, and the schedule for streaming them into CHR-RAM
+    Streaming,
+    /// The named rectangles drawn on a level map
+    Zones,
+    /// The flags of each screen of a level
+    Screens,
+    /// The exits linking levels together
+    Connections,
+    /// The screens of the levels placed in the world view
+    World,
+    /// Animated background tile definitions, frames, and banks
+    Animation,
+    /// The nametable of the static HUD rows of a level
+    Hud,
+    /// The tables tracing metatiles and pattern table tiles back to their source images
+    TileSources,
+    /// The attribute bytes of each screen of a level, or of a HUD section
+    Attributes,
+    /// The nametable columns or rows of a level and their attribute bits, for scrolling engines
+    Scrolling,
+}
+
+impl TableKind {
+    /// Whether tables of this kind are written when exporting `content`
+    pub fn is_exported(&self, content: ExportContent) -> bool {
+        match content {
+            ExportContent::Everything => true,
+            ExportContent::Attributes => *self == TableKind::Attributes,
+            ExportContent::Pallets => *self == TableKind::Pallet,
+        }
+    }
+}
+
+/// The result of a successful export
+#[derive(Debug, Clone, Default)]
+pub struct ExportReport {
+    /// The files that were written
+    pub files: Vec<PathBuf>,
+    /// Problems that didn't prevent the export, but will probably cause trouble on the NES
+    pub warnings: Vec<String>,
+    /// Information about the exported data, such as how well it was compressed
+    pub stats: Vec<String>,
+}
+
+/// Part of a project to export by itself, for iterating on one piece of a large game
+///
+/// Partial exports use the same table names as a full export. Formats that write every table to
+/// one file name the file after the exported part, so they don't replace the full export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportOnly {
+    /// One level, with its map, HUD, streaming, and zone tables
+    Level(Uid<Level>),
+    /// One metatileset, with its pattern table, metatiles, pallet, and animations
+    Metatileset(Uid<Metatileset>),
+}
+
+impl ExportOnly {
+    /// Parse a commandline filter such as `map:3` or `metatileset:overworld`, where the part
+    /// after the colon is either a name or the number of the level or metatileset in the project,
+    /// starting from 0
+    pub fn parse(project: &Project, filter: &str) -> anyhow::Result<Self> {
+        let (kind, value) = filter
+            .split_once(':')
+            .context("Expected a filter like `map:3` or `metatileset:overworld`")?;
+
+        match kind {
+            "map" | "level" => find_by_name(&project.levels, |x| &x.name, value)
+                .map(Self::Level)
+                .with_context(|| format!("Project has no level named or numbered `{}`", value)),
+            "metatileset" => find_by_name(&project.metatilesets, |x| &x.name, value)
+                .map(Self::Metatileset)
+                .with_context(|| {
+                    format!("Project has no metatileset named or numbered `{}`", value)
+                }),
+            _ => anyhow::bail!(
+                "Unknown export filter `{}`, expected `map` or `metatileset`",
+                kind
+            ),
+        }
+    }
+
+    /// The name of the level or metatileset, if it is still in the project
+    pub fn name<'a>(&self, project: &'a Project) -> Option<&'a str> {
+        match self {
+            Self::Level(id) => project.levels.get(id).map(|x| x.name.as_str()),
+            Self::Metatileset(id) => project.metatilesets.get(id).map(|x| x.name.as_str()),
+        }
+    }
+}
+
+/// Find an item by its name, or by its index if no item has that name
+fn find_by_name<T>(
+    items: &IndexMap<Uid<T>, T>,
+    name: impl Fn(&T) -> &str,
+    value: &str,
+) -> Option<Uid<T>> {
+    items
+        .iter()
+        .find(|(_, x)| name(x) == value)
+        .or_else(|| items.get_index(value.parse().ok()?))
+        .map(|(id, _)| *id)
+}
+
+/// Get the export target with the given name, or the first target in the project if no name is
+/// given.
+///
+/// If the project doesn't define any targets, the default target is used.
+pub fn find_target(project: &Project, name: Option<&str>) -> anyhow::Result<ExportTarget> {
+    match name {
+        Some(name) => project
+            .export_target(name)
+            .cloned()
+            .with_context(|| format!("Project has no export target named `{}`", name)),
+        None => Ok(project
+            .export_targets
+            .values()
+            .next()
+            .cloned()
+            .unwrap_or_default()),
+    }
+}
+
+/// The directory exports are written to when one isn't specified on the commandline: the
+/// target's output directory, or `export/<target>` next to the project file
+pub fn default_output_dir(project_path: &Path, target: &ExportTarget) -> PathBuf {
+    let project_dir = project_path.parent().unwrap_or_else(|| Path::new("."));
+    match &target.output_dir {
+        Some(dir) => project_dir.join(dir),
+        None => project_dir.join("export").join(symbol_name(&target.name)),
+    }
+}
+
+/// Export the project to `out_dir` using the settings from `target`, or only part of it if
+/// `only` is given
+pub fn export_project(
+    project: &Project,
+    project_path: &Path,
+    target: &ExportTarget,
+    only: Option<ExportOnly>,
+    out_dir: &Path,
+) -> anyhow::Result<ExportReport> {
+    if let Some(only) = only {
+        if only.name(project).is_none() {
+            anyhow::bail!("The level or metatileset to export isn't in the project anymore");
+        }
+    }
+
+    let mut report = ExportReport::default();
+    let sources = load_sources(project, project_path, &mut report.warnings);
+    let mut data = build_export_data(project, &sources, target, only, &mut report);
+    if let Some(origin) = target.data_origin {
+        report
+            .warnings
+            .extend(dpcm::overlap_warnings(&data, origin, target.dpcm_reserved));
+    }
+
+    std::fs::create_dir_all(out_dir).context("Create export directory")?;
+
+    let mut file_stem = project_path
+        .file_stem()
+        .map(|x| symbol_name(&x.to_string_lossy()))
+        .unwrap_or_else(|| "project".into());
+    if let Some(name) = only.and_then(|x| x.name(project)) {
+        file_stem = format!("{}_{}", file_stem, symbol_name(name));
+    }
+
+    report.files = match target.format {
+        ExportFormat::Binary => binary::write(&data, out_dir)?,
+        ExportFormat::Ca65 => ca65::write(&data, target, out_dir, &file_stem)?,
+        ExportFormat::C => c::write(&data, target, out_dir, &file_stem)?,
+        ExportFormat::Html => html::write(project, &sources, &data, out_dir, &file_stem)?,
+        ExportFormat::Python => python::write(&data, target, out_dir, &file_stem)?,
+        ExportFormat::JavaScript => javascript::write(&data, target, out_dir, &file_stem)?,
+    };
+
+    Ok(report)
+}
+
+/// Export the project a second time to a temporary directory and check that every file matches
+/// the files from `report`, which were written to `out_dir`
+pub fn verify_reproducible(
+    project: &Project,
+    project_path: &Path,
+    target: &ExportTarget,
+    only: Option<ExportOnly>,
+    out_dir: &Path,
+    report: &ExportReport,
+) -> anyhow::Result<()> {
+    let check_dir = std::env::temp_dir().join(format!(
+        "nesimg-verify-{}-{}",
+        std::process::id(),
+        symbol_name(&target.name)
+    ));
+    let result = (|| {
+        let check = export_project(project, project_path, target, only, &check_dir)?;
+        let relative = |files: &[PathBuf], dir: &Path| {
+            files
+                .iter()
+                .map(|x| x.strip_prefix(dir).unwrap_or(x).to_owned())
+                .collect::<Vec<_>>()
+        };
+        let files = relative(&report.files, out_dir);
+        if files != relative(&check.files, &check_dir) {
+            anyhow::bail!("The second export wrote a different set of files");
+        }
+
+        let mut different = Vec::new();
+        for file in &files {
+            let first = std::fs::read(out_dir.join(file))
+                .with_context(|| format!("Read {}", out_dir.join(file).display()))?;
+            let second = std::fs::read(check_dir.join(file))
+                .with_context(|| format!("Read {}", check_dir.join(file).display()))?;
+            if first != second {
+                different.push(file.display().to_string());
+            }
+        }
+        if !different.is_empty() {
+            anyhow::bail!(
+                "Exporting twice gave different output for: {}",
+                different.join(", ")
+            );
+        }
+
+        Ok(())
+    })();
+    std::fs::remove_dir_all(&check_dir).ok();
+
+    result
+}
+
+/// Load the project's source images, warning about the ones that can't be loaded
+pub fn load_sources(
+    project: &Project,
+    project_path: &Path,
+    warnings: &mut Vec<String>,
+) -> IndexMap<Uid<PathBuf>, IndexedImage> {
+    let mut sources = IndexMap::new();
+    for (id, path) in &project.sources {
+        let keep_pallet_order = project.pallet_order_sources.contains(id);
+        match IndexedImage::load(&Project::source_path(project_path, path), keep_pallet_order) {
+            Ok(image) => {
+                sources.insert(*id, image);
+            }
+            Err(e) => warnings.push(format!(
+                "Could not load source `{}`, its tiles will be blank: {:#}",
+                path.display(),
+                e
+            )),
+        }
+    }
+    sources
+}
+
+/// Generate the pattern tables, metatile definitions, pallets, and level maps for the project,
+/// along with the tile streaming tables if the target has them enabled
+///
+/// The metatilesets, and then the levels, are built in parallel, but their tables are always
+/// added in project order so the output doesn't depend on which thread finishes first. With
+/// `only`, just the tables for that level or metatileset are kept.
+pub fn build_export_data(
+    project: &Project,
+    sources: &IndexMap<Uid<PathBuf>, IndexedImage>,
+    target: &ExportTarget,
+    only: Option<ExportOnly>,
+    report: &mut ExportReport,
+) -> ExportData {
+    let started = Instant::now();
+    let mut work = Duration::ZERO;
+    let mut data = ExportData::default();
+    if TableKind::Metatiles.is_exported(target.content) {
+        report
+            .warnings
+            .extend(metatile_layout::warning(&target.metatile_layout));
+    }
+    if target.chr_streaming && target.chr_compression != ChrCompression::None {
+        report.warnings.push(format!(
+            "CHR streaming copies tiles from the uncompressed pattern tables, but they are \
+            exported compressed with {}, so the streaming tables don't line up with them",
+            target.chr_compression.name()
+        ));
+    }
+
+    // Pick the symbol names up front, in the same order as the tables
+    let mut names = HashSet::new();
+    let metatilesets = project
+        .metatilesets
+        .iter()
+        .map(|(id, x)| (unique_symbol_name(&mut names, &x.name), *id, x))
+        .collect::<Vec<_>>();
+    let levels = project
+        .levels
+        .iter()
+        .map(|(id, x)| (unique_symbol_name(&mut names, &x.name), *id, x))
+        .collect::<Vec<_>>();
+
+    // The pattern table indexes of each metatile, used to work out which tiles each screen needs
+    let mut metatile_chr = HashMap::<Uid<Metatileset>, Vec<[u8; 4]>>::new();
+    // A level export still needs the pattern table indexes of its metatileset
+    let only_metatileset = match only {
+        Some(ExportOnly::Level(id)) => project.levels.get(&id).map(|x| x.metatileset_id),
+        Some(ExportOnly::Metatileset(id)) => Some(id),
+        None => None,
+    };
+    let metatilesets = metatilesets
+        .into_iter()
+        .filter(|(_, id, _)| only_metatileset.map(|x| x == *id).unwrap_or(only.is_none()))
+        .collect::<Vec<_>>();
+    let levels = levels
+        .into_iter()
+        .filter(|(_, id, _)| match only {
+            Some(ExportOnly::Level(only)) => only == *id,
+            Some(ExportOnly::Metatileset(_)) => false,
+            None => true,
+        })
+        .collect::<Vec<_>>();
+
+    let metatileset_exports = metatilesets
+        .par_iter()
+        .map(|(name, _, metatileset)| {
+            timed(|| metatileset_export(name, metatileset, project, sources, target))
+        })
+        .collect::<Vec<_>>();
+    for ((_, id, _), ((partial, chr_indexes), time)) in metatilesets.iter().zip(metatileset_exports)
+    {
+        work += time;
+        metatile_chr.insert(*id, chr_indexes);
+        if !matches!(only, Some(ExportOnly::Level(_))) {
+            partial.add_to(&mut data, report, target.metatileset_segments.get(id));
+        }
+    }
+
+    let level_exports = levels
+        .par_iter()
+        .map(|(name, _, level)| timed(|| level_export(name, level, project, &metatile_chr, target)))
+        .collect::<Vec<_>>();
+    for ((_, id, _), (partial, time)) in levels.iter().zip(level_exports) {
+        work += time;
+        partial.add_to(&mut data, report, target.level_segments.get(id));
+    }
+
+    // Compare the time spent building each metatileset and level with the time the export took,
+    // which shows how much the threads helped
+    let elapsed = started.elapsed();
+    if work >= SPEEDUP_REPORT_MIN_WORK {
+        report.stats.push(format!(
+            "Built the tables in {:.0} ms using {} threads, {:.1}x faster than the {:.0} ms it \
+            would take on one thread",
+            elapsed.as_secs_f64() * 1000.0,
+            rayon::current_num_threads(),
+            work.as_secs_f64() / elapsed.as_secs_f64().max(f64::EPSILON),
+            work.as_secs_f64() * 1000.0,
+        ));
+    }
+
+    if only.is_none()
+        && project
+            .world_layout
+            .keys()
+            .any(|id| project.levels.contains_key(id))
+    {
+        let name = unique_symbol_name(&mut names, "world_rooms");
+        data.tables
+            .push(world_table(&name, project, &mut report.warnings));
+    }
+
+    if only.is_none() && project.levels.values().any(|x| !x.exits.is_empty()) {
+        let name = unique_symbol_name(&mut names, "connections");
+        data.tables
+            .push(connection_table(&name, project, &mut report.warnings));
+    }
+
+    data.tables.retain(|x| x.kind.is_exported(target.content));
+
+    if target.constants {
+        data.constants = constant_groups(project, target.content);
+    }
+
+    // Compressed tables are renamed, so they are compressed before the names are styled
+    let compressed = compress_chr_tables(&mut data, target.chr_compression);
+    if target.checksums {
+        data.checksums = data
+            .tables
+            .iter()
+            .map(|x| {
+                let name = checksum::constant_name(&x.name, &target.symbol_prefix);
+                (name, checksum::crc32(&x.data))
+            })
+            .collect();
+    }
+    symbols::apply_style(&mut data, &target.symbol_prefix, target.symbol_case);
+    for (i, size) in compressed {
+        let name = &data.tables[i].name;
+        match size {
+            Some(size) => report.stats.push(format!("`{}`: {}", name, size)),
+            None => report.warnings.push(format!(
+                "Pattern table `{}` can't be compressed with {}, so it was exported uncompressed",
+                name,
+                target.chr_compression.name()
+            )),
+        }
+    }
+
+    data
+}
+
+/// The tables, warnings, and stats for part of the project, built on its own thread
+#[derive(Default)]
+struct PartialExport {
+    tables: Vec<Table>,
+    warnings: Vec<String>,
+    stats: Vec<String>,
+}
+
+impl PartialExport {
+    /// Add the tables to the export, placing them in `segment` if one is assigned to the part of
+    /// the project they came from
+    fn add_to(self, data: &mut ExportData, report: &mut ExportReport, segment: Option<&String>) {
+        let segment = segment.filter(|x| !x.is_empty());
+        data.tables
+            .extend(self.tables.into_iter().map(|table| Table {
+                segment: segment.cloned(),
+                ..table
+            }));
+        report.warnings.extend(self.warnings);
+        report.stats.extend(self.stats);
+    }
+}
+
+/// Run `f`, returning its result along with how long it took
+fn timed<T>(f: impl FnOnce() -> T) -> (T, Duration) {
+    let started = Instant::now();
+    let result = f();
+    (result, started.elapsed())
+}
+
+/// Build the tables for one metatileset, along with the pattern table indexes of its metatiles
+fn metatileset_export(
+    name: &str,
+    metatileset: &Metatileset,
+    project: &Project,
+    sources: &IndexMap<Uid<PathBuf>, IndexedImage>,
+    target: &ExportTarget,
+) -> (PartialExport, Vec<[u8; 4]>) {
+    let mut partial = PartialExport::default();
+    let mut chr = IndexSet::<[u8; 16]>::new();
+    let layout = &target.metatile_layout;
+    let mut metatiles = Vec::with_capacity(metatileset.tiles.len() * layout.record_size());
+    let mut chr_indexes = Vec::with_capacity(metatileset.tiles.len());
+    let mut used_tiles = HashSet::new();
+
+    // Slice and encode the tiles of every metatile in parallel, then deduplicate them in order
+    let metatileset_tiles = metatileset.tiles.values().collect::<Vec<_>>();
+    let encoded = metatileset_tiles
+        .par_iter()
+        .map(|metatileset_tile| {
+            let metatile = project.metatiles.get(&metatileset_tile.metatile_id);
+            let tiles = [0, 1, 2, 3].map(|i| metatile.and_then(|x| x.tiles[i].as_ref()));
+            (
+                tiles,
+                tiles.map(|tile| encode_chr_tile(&tile_pixels(sources, tile))),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    // Where each pattern table tile first came from and the metatiles that use it, and where the
+    // tiles of each metatile came from, for the provenance comments
+    let mut chr_provenance = Vec::<(String, Vec<String>)>::new();
+    let mut metatile_provenance = Vec::new();
+
+    for (metatileset_tile, (tiles, encoded)) in metatileset_tiles.iter().zip(encoded) {
+        let metatile_name = project
+            .metatiles
+            .get_index_of(&metatileset_tile.metatile_id)
+            .map(|idx| format!("metatile {}", idx + 1))
+            .unwrap_or_else(|| "missing metatile".into());
+
+        let mut indexes = [0; 4];
+        for (i, index) in indexes.iter_mut().enumerate() {
+            used_tiles.extend(tiles[i].cloned());
+            let (idx, _) = chr.insert_full(encoded[i]);
+            *index = idx as u8;
+
+            if target.provenance {
+                if idx == chr_provenance.len() {
+                    chr_provenance.push((tile_provenance(project, tiles[i]), Vec::new()));
+                }
+                let users = &mut chr_provenance[idx].1;
+                if users.last() != Some(&metatile_name) {
+                    users.push(metatile_name.clone());
+                }
+            }
+        }
+        metatile_layout::write_record(layout, metatileset_tile, indexes, &mut metatiles);
+        chr_indexes.push(indexes);
+
+        if target.provenance {
+            metatile_provenance.push(format!(
+                "{}: {}",
+                metatile_name,
+                tiles
+                    .iter()
+                    .map(|tile| tile_provenance(project, *tile))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+    }
+
+    let animation_tables = animation::metatileset_tables(
+        name,
+        &metatileset.name,
+        project,
+        sources,
+        &used_tiles,
+        &chr,
+        &mut partial.warnings,
+    );
+
+    let extra_colored = metatileset
+        .tiles
+        .values()
+        .filter(|x| x.sub_pallet_idx >= 4)
+        .count();
+    if extra_colored > 0 {
+        partial.warnings.push(format!(
+            "Metatileset `{}` has {} metatiles colored with extra sub-pallets, which were \
+            exported with the last sub-pallet. Use the pallet solver to fit them into the pallet",
+            metatileset.name, extra_colored
+        ));
+    }
+
+    if metatileset.pallet.colors.contains(&FORBIDDEN_COLOR) {
+        partial.warnings.push(format!(
+            "The pallet for metatileset `{}` uses the \"blacker than black\" color $0D, which \
+            can make some TVs lose sync",
+            metatileset.name
+        ));
+    }
+
+    if chr.len() > PATTERN_TABLE_TILES {
+        partial.warnings.push(format!(
+            "Metatileset `{}` uses {} unique tiles, but only {} fit in a pattern table",
+            metatileset.name,
+            chr.len(),
+            PATTERN_TABLE_TILES
+        ));
+    } else if chr.len() > metatileset.max_tiles() {
+        partial.warnings.push(format!(
+            "Metatileset `{}` uses {} unique tiles, which is over its budget of {}",
+            metatileset.name,
+            chr.len(),
+            metatileset.max_tiles()
+        ));
+    }
+    if metatileset.tiles.len() > EMPTY_METATILE as usize {
+        partial.warnings.push(format!(
+            "Metatileset `{}` has {} metatiles, but maps can only reference {}",
+            metatileset.name,
+            metatileset.tiles.len(),
+            EMPTY_METATILE
+        ));
+    }
+
+    partial.tables.push(Table {
+        kind: TableKind::Chr,
+        description: format!(
+            "Pattern table for metatileset `{}`: {} tiles",
+            metatileset.name,
+            chr.len()
+        ),
+        name: format!("{}_chr", name),
+        data: chr.into_iter().flatten().collect(),
+        row_len: 16,
+        row_comments: chr_provenance
+            .into_iter()
+            .enumerate()
+            .map(|(idx, (source, users))| {
+                format!("${:02X}: {}, used by {}", idx, source, users.join(", "))
+            })
+            .collect(),
+        segment: None,
+    });
+    partial.tables.push(Table {
+        kind: TableKind::Metatiles,
+        name: format!("{}_metatiles", name),
+        description: format!(
+            "Metatiles for metatileset `{}`: {}",
+            metatileset.name,
+            metatile_layout::describe(layout)
+        ),
+        data: metatiles,
+        row_len: layout.record_size().max(1),
+        row_comments: metatile_provenance,
+        segment: None,
+    });
+    partial.tables.push(Table {
+        kind: TableKind::Pallet,
+        name: format!("{}_pallet", name),
+        description: format!("Pallet for metatileset `{}`", metatileset.name),
+        data: metatileset
+            .pallet
+            .get_sub_pallets()
+            .iter()
+            .flatten()
+            .map(|&x| x as u8)
+            .collect(),
+        row_len: 4,
+        row_comments: Vec::new(),
+        segment: None,
+    });
+    partial.tables.extend(animation_tables);
+    if target.tile_sources {
+        partial.tables.extend(tile_sources::metatileset_tables(
+            name,
+            metatileset,
+            project,
+            &chr_indexes,
+            &mut partial.warnings,
+        ));
+    }
+
+    (partial, chr_indexes)
+}
+
+/// Build the map, HUD, streaming, and zone tables for one level
+fn level_export(
+    name: &str,
+    level: &Level,
+    project: &Project,
+    metatile_chr: &HashMap<Uid<Metatileset>, Vec<[u8; 4]>>,
+    target: &ExportTarget,
+) -> PartialExport {
+    let mut partial = PartialExport::default();
+    let metatileset = project.metatilesets.get(&level.metatileset_id);
+    let margin = level.margin;
+
+    let mut map = Vec::with_capacity((margin.width() * margin.height()).max(0) as usize);
+    for y in -margin.top..margin.bottom {
+        for x in -margin.left..margin.right {
+            let idx = level
+                .tiles
+                .get(&(x, y))
+                .and_then(|tile| metatileset?.tiles.get_index_of(&tile.metatileset_tile_id))
+                .map(|idx| idx.min(EMPTY_METATILE as usize - 1) as u8)
+                .unwrap_or(EMPTY_METATILE);
+            map.push(idx);
+        }
+    }
+
+    let sub_pallets = metatileset
+        .map(|x| {
+            x.tiles
+                .values()
+                .map(|x| x.sub_pallet_idx.min(3) as u8)
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    let width = margin.width().max(0) as usize;
+    if TableKind::Attributes.is_exported(target.content) {
+        partial.tables.push(attributes::level_table(
+            name,
+            &level.name,
+            &map,
+            width,
+            &sub_pallets,
+        ));
+    }
+
+    // Split the HUD rows off of the playfield
+    let height = map.len() / width.max(1);
+    if let Some(metatiles) = metatile_chr.get(&level.metatileset_id) {
+        let limit = project.chr_banks.screen_tile_limit();
+        for ((x, y), count) in screen_tile_counts(&map, width, metatiles) {
+            if count > limit {
+                partial.warnings.push(format!(
+                    "Screen ({}, {}) of level `{}` uses {} unique tiles, but the CHR bank \
+                    settings only leave room for {}",
+                    x, y, level.name, count, limit
+                ));
+            }
+        }
+    }
+    let hud_top = (level.hud.top as usize).min(height);
+    let hud_bottom = (level.hud.bottom as usize).min(height - hud_top);
+    if hud_top + hud_bottom == height && height > 0 {
+        partial.warnings.push(format!(
+            "The HUD rows of level `{}` cover the whole map, so it has no playfield",
+            level.name
+        ));
+    }
+    let hud_bottom_map = map.split_off((height - hud_bottom) * width);
+    let hud_top_map = map.drain(..hud_top * width).collect::<Vec<_>>();
+    let level_chr = metatile_chr
+        .get(&level.metatileset_id)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+
+    let streaming = metatile_chr
+        .get(&level.metatileset_id)
+        .filter(|_| target.chr_streaming)
+        .map(|metatiles| {
+            streaming::level_tables(
+                name,
+                &level.name,
+                &map,
+                width,
+                metatiles,
+                target.nmi_transfer_budget,
+            )
+        });
+    let scrolling = metatile_chr
+        .get(&level.metatileset_id)
+        .map(|metatiles| {
+            scrolling::level_tables(
+                name,
+                &level.name,
+                &map,
+                width,
+                metatiles,
+                &sub_pallets,
+                target.map_streaming,
+            )
+        })
+        .unwrap_or_default();
+
+    partial.tables.push(Table {
+        kind: TableKind::Map,
+        name: format!("{}_map", name),
+        description: format!(
+            "Map for level `{}`: {}x{} metatiles from metatileset `{}`, ${:02X} is empty{}",
+            level.name,
+            width,
+            height - hud_top - hud_bottom,
+            metatileset.map(|x| x.name.as_str()).unwrap_or("None"),
+            EMPTY_METATILE,
+            if hud_top + hud_bottom > 0 {
+                ". The HUD rows are exported separately"
+            } else {
+                ""
+            }
+        ),
+        data: map,
+        row_len: width.max(1),
+        row_comments: Vec::new(),
+        segment: None,
+    });
+
+    for (section, hud_map) in [("top", hud_top_map), ("bottom", hud_bottom_map)] {
+        if !hud_map.is_empty() {
+            partial.tables.extend(hud::section_tables(
+                name,
+                &level.name,
+                section,
+                &hud_map,
+                width,
+                level_chr,
+                &sub_pallets,
+            ));
+        }
+    }
+
+    if let Some(streaming) = streaming {
+        partial.tables.extend(streaming.tables);
+        partial.stats.push(streaming.stats);
+        partial.warnings.extend(streaming.warnings);
+    }
+    partial.tables.extend(scrolling);
+
+    if !level.zones.is_empty() {
+        partial
+            .tables
+            .push(zone_table(name, level, &mut partial.warnings));
+    }
+
+    if !level.screens.is_empty() {
+        partial.tables.push(screen_table(name, level));
+    }
+
+    if !level.pallet_regions.is_empty() {
+        partial.tables.push(pallet_region_table(name, level));
+    }
+
+    partial
+}
+
+/// Build the named constants for the colors used in the project's pallets and the values used in
+/// the zone, connection, screen, and map tables
+///
+/// Attribute-only exports don't have any constants, and pallet-only exports only have the colors.
+fn constant_groups(project: &Project, content: ExportContent) -> Vec<ConstantGroup> {
+    let mut groups = Vec::new();
+    if content == ExportContent::Attributes {
+        return groups;
+    }
+
+    let colors = project
+        .metatilesets
+        .values()
+        .flat_map(|x| x.pallet.get_sub_pallets())
+        .flatten()
+        .filter(|&x| (x as usize) < NES_COLOR_NAMES.len())
+        .collect::<BTreeSet<_>>();
+    if !colors.is_empty() {
+        // Several colors share a name, so the later ones get their value added to the name
+        let mut names = HashSet::new();
+        let constants = colors
+            .into_iter()
+            .map(|color| {
+                let name = constant_name("COLOR", NES_COLOR_NAMES[color as usize]);
+                let name = if names.insert(name.clone()) {
+                    name
+                } else {
+                    format!("{}_{:02X}", name, color)
+                };
+                (name, color as u8)
+            })
+            .collect();
+        groups.push(ConstantGroup {
+            description: "NES colors used by the metatileset pallets".into(),
+            constants,
+        });
+    }
+    if content == ExportContent::Pallets {
+        return groups;
+    }
+
+    groups.push(ConstantGroup {
+        description: "The value used in map tables for cells without a metatile".into(),
+        constants: vec![("EMPTY_METATILE".into(), EMPTY_METATILE)],
+    });
+    groups.push(ConstantGroup {
+        description: "Zone kinds, the first byte of each zone in the zone tables".into(),
+        constants: ZoneKind::ALL
+            .iter()
+            .map(|x| (constant_name("ZONE", &format!("{:?}", x)), x.export_id()))
+            .collect(),
+    });
+    groups.push(ConstantGroup {
+        description: "Exit kinds in the connections table, and the zone index used for exits \
+            without a zone"
+            .into(),
+        constants: MapEdge::ALL
+            .iter()
+            .map(|x| (constant_name("EXIT", &format!("{:?}", x)), x.export_id()))
+            .chain([
+                ("EXIT_ZONE".into(), EXIT_FROM_ZONE),
+                ("NO_ZONE".into(), NO_ZONE),
+            ])
+            .collect(),
+    });
+
+    if project.levels.values().any(|x| !x.screens.is_empty()) {
+        groups.push(ConstantGroup {
+            description: "Bits of the bytes in the screen tables, and the shift of the room type"
+                .into(),
+            constants: MapEdge::ALL
+                .iter()
+                .map(|x| {
+                    (
+                        constant_name("SCREEN_EXIT", &format!("{:?}", x)),
+                        ScreenFlags::exit_bit(*x),
+                    )
+                })
+                .chain([
+                    ("SCREEN_SCROLL_LOCK".into(), ScreenFlags::SCROLL_LOCK_BIT),
+                    (
+                        "SCREEN_ROOM_TYPE_SHIFT".into(),
+                        ScreenFlags::ROOM_TYPE_SHIFT,
+                    ),
+                ])
+                .collect(),
+        });
+    }
+
+    let property_flags = project
+        .property_flags
+        .iter()
+        .enumerate()
+        .filter(|(_, name)| !name.trim().is_empty())
+        .map(|(bit, name)| (constant_name("PROPERTY", name), 1 << bit))
+        .collect::<Vec<_>>();
+    if !property_flags.is_empty() {
+        groups.push(ConstantGroup {
+            description: "Bits of the metatile property byte, as named in the project settings"
+                .into(),
+            constants: property_flags,
+        });
+    }
+
+    if !project.levels.is_empty() {
+        let mut names = HashSet::new();
+        groups.push(ConstantGroup {
+            description: "Level indexes, as used in the connections and world tables".into(),
+            constants: project
+                .levels
+                .values()
+                .enumerate()
+                .map(|(idx, level)| {
+                    let name = unique_symbol_name(&mut names, &level.name).to_ascii_uppercase();
+                    (format!("LEVEL_{}", name.trim_start_matches('_')), idx as u8)
+                })
+                .collect(),
+        });
+    }
+
+    groups
+}
+
+/// Count the unique pattern table tiles used by each screen of a level map, by the screen's
+/// column and row. Screens are 16x15 metatiles, starting from the top-left corner of the map.
+///
+/// `map` holds the metatile index of each cell, `width` cells per row, and `metatiles` holds the
+/// four pattern table indexes of each metatile.
+pub fn screen_tile_counts(
+    map: &[u8],
+    width: usize,
+    metatiles: &[[u8; 4]],
+) -> Vec<((usize, usize), usize)> {
+    let width = width.max(1);
+    let height = map.len() / width;
+
+    let mut counts = Vec::new();
+    for screen_y in (0..height).step_by(SCREEN_HEIGHT_METATILES) {
+        for screen_x in (0..width).step_by(SCREEN_WIDTH_METATILES) {
+            let mut tiles = HashSet::new();
+            for y in screen_y..(screen_y + SCREEN_HEIGHT_METATILES).min(height) {
+                for x in screen_x..(screen_x + SCREEN_WIDTH_METATILES).min(width) {
+                    let metatile = map[y * width + x];
+                    if metatile == EMPTY_METATILE {
+                        continue;
+                    }
+                    if let Some(chr) = metatiles.get(metatile as usize) {
+                        tiles.extend(chr.iter().copied());
+                    }
+                }
+            }
+            counts.push((
+                (
+                    screen_x / SCREEN_WIDTH_METATILES,
+                    screen_y / SCREEN_HEIGHT_METATILES,
+                ),
+                tiles.len(),
+            ));
+        }
+    }
+    counts
+}
+
+/// Get an upper-case constant name for `name`, with the given prefix
+fn constant_name(prefix: &str, name: &str) -> String {
+    format!(
+        "{}_{}",
+        prefix,
+        symbol_name(name)
+            .trim_start_matches('_')
+            .to_ascii_uppercase()
+    )
+}
+
+/// Build the table of zones drawn on a level, with coordinates relative to the top-left corner of
+/// the level's playfield, below any HUD rows
+fn zone_table(symbol: &str, level: &Level, warnings: &mut Vec<String>) -> Table {
+    let margin = level.margin;
+    let hud = level.hud;
+    let mut data = Vec::with_capacity(level.zones.len() * 5);
+
+    for zone in level.zones.values() {
+        let left = zone.x + margin.left;
+        let top = zone.y + margin.top - hud.top as i32;
+        let right = left + zone.width;
+        let bottom = top + zone.height;
+
+        // Map coordinates have to fit in a byte
+        let max_x = margin.width().min(256);
+        let max_y = (margin.height() - hud.top as i32 - hud.bottom as i32).min(256);
+        if left < 0 || top < 0 || right > max_x || bottom > max_y {
+            warnings.push(format!(
+                "Zone `{}` in level `{}` is outside of the level's playfield, so it was clipped",
+                zone.name, level.name
+            ));
+        }
+
+        let (left, top) = (left.clamp(0, 255), top.clamp(0, 255));
+        let (right, bottom) = (right.min(max_x).max(left), bottom.min(max_y).max(top));
+        data.extend([
+            zone.kind.export_id(),
+            left as u8,
+            top as u8,
+            (right - left).min(255) as u8,
+            (bottom - top).min(255) as u8,
+        ]);
+    }
+
+    let kinds = ZoneKind::ALL
+        .iter()
+        .map(|x| format!("{} {}", x.export_id(), x.name()))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let names = level
+        .zones
+        .values()
+        .map(|x| format!("`{}`", x.name))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    Table {
+        kind: TableKind::Zones,
+        name: format!("{}_zones", symbol),
+        description: format!(
+            "Zones for level `{}`: the kind ( {} ), followed by the x, y, width, and height in \
+            metatiles from the top-left corner of the map, below any HUD rows. Zones: {}",
+            level.name, kinds, names
+        ),
+        data,
+        row_len: 5,
+        row_comments: Vec::new(),
+        segment: None,
+    }
+}
+
+/// Build the table of flags for each screen of a level, in the same order as the level's screens
+/// are numbered in the maps tab
+fn screen_table(symbol: &str, level: &Level) -> Table {
+    let (width, height) = level.screen_size();
+    let mut data = Vec::with_capacity((width * height) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            data.push(level.screen_flags((x, y)).export_byte());
+        }
+    }
+
+    Table {
+        kind: TableKind::Screens,
+        name: format!("{}_screens", symbol),
+        description: format!(
+            "Screen flags for level `{}`: one byte for each of its {}x{} screens, left to right \
+            and then top to bottom, counting from the top-left corner of the map including any \
+            HUD rows. Bits 0-3 are exits through the top, right, bottom, and left edges, bit 4 \
+            is the scroll lock, and bits 5-7 are the room type",
+            level.name, width, height
+        ),
+        data,
+        row_len: width as usize,
+        row_comments: Vec::new(),
+        segment: None,
+    }
+}
+
+/// Build the table of pallet swaps part way down the screen of a level, sorted by scanline
+fn pallet_region_table(symbol: &str, level: &Level) -> Table {
+    let mut regions = level.pallet_regions.iter().collect::<Vec<_>>();
+    regions.sort_by_key(|x| x.scanline);
+
+    Table {
+        kind: TableKind::PalletRegions,
+        name: format!("{}_pallet_regions", symbol),
+        description: format!(
+            "Pallet regions for level `{}`: the first scanline of each region, followed by the 16 \
+            bytes of the background pallet to write before it is drawn. The attribute tables \
+            don't include these swaps",
+            level.name
+        ),
+        data: regions
+            .iter()
+            .flat_map(|region| {
+                let pallet = region.pallet.get_sub_pallets().into_iter().flatten();
+                std::iter::once(region.scanline).chain(pallet.map(|x| x as u8))
+            })
+            .collect(),
+        row_len: 17,
+        row_comments: Vec::new(),
+        segment: None,
+    }
+}
+
+/// Build the table of exits between levels
+fn connection_table(symbol: &str, project: &Project, warnings: &mut Vec<String>) -> Table {
+    let mut data = Vec::new();
+    for (level_idx, level) in project.levels.values().enumerate() {
+        for exit in level.exits.values() {
+            let (source_kind, source_zone) = match exit.source {
+                ExitSource::Edge(edge) => (edge.export_id(), NO_ZONE),
+                ExitSource::Zone(zone_id) => match level.zones.get_index_of(&zone_id) {
+                    Some(idx) => (EXIT_FROM_ZONE, idx as u8),
+                    None => {
+                        warnings.push(format!(
+                            "An exit from level `{}` uses a zone that was deleted, so it was \
+                            skipped",
+                            level.name
+                        ));
+                        continue;
+                    }
+                },
+            };
+
+            let (target_idx, target) = match project.levels.get_full(&exit.target_level_id) {
+                Some((idx, _, target)) => (idx, target),
+                None => {
+                    warnings.push(format!(
+                        "An exit from level `{}` leads to a level that was deleted, so it was \
+                        skipped",
+                        level.name
+                    ));
+                    continue;
+                }
+            };
+            let target_zone = exit
+                .target_zone_id
+                .and_then(|id| target.zones.get_index_of(&id))
+                .map(|idx| idx as u8)
+                .unwrap_or(NO_ZONE);
+
+            data.extend([
+                level_idx as u8,
+                source_kind,
+                source_zone,
+                target_idx as u8,
+                target_zone,
+            ]);
+        }
+    }
+
+    let edges = MapEdge::ALL
+        .iter()
+        .map(|x| format!("{} {}", x.export_id(), x.name()))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let levels = project
+        .levels
+        .values()
+        .map(|x| format!("`{}`", x.name))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    Table {
+        kind: TableKind::Connections,
+        name: symbol.into(),
+        description: format!(
+            "Level connections: the source level, the exit kind ( {}, {} Zone ), the source \
+            zone, the target level, and the zone entered in the target level, with ${:02X} for \
+            no zone. Zones are numbered in the order of the level's zone table. Levels: {}",
+            edges, EXIT_FROM_ZONE, NO_ZONE, levels
+        ),
+        data,
+        row_len: 5,
+        row_comments: Vec::new(),
+        segment: None,
+    }
+}
+
+/// Build the room grid of the world view, with the level and screen shown in each screen of the
+/// world
+fn world_table(symbol: &str, project: &Project, warnings: &mut Vec<String>) -> Table {
+    const EMPTY: [u8; 2] = [0xFF, 0xFF];
+    // $FF is reserved for empty cells, so the level and screen indexes have to be below it
+    const MAX_INDEX: usize = 0xFE;
+
+    let placements = project
+        .world_layout
+        .iter()
+        .filter_map(|(id, placement)| {
+            let (idx, _, level) = project.levels.get_full(id)?;
+            if idx > MAX_INDEX {
+                warnings.push(format!(
+                    "Level `{}` is level {}, but the room grid only has room for {} levels, so \
+                    it was left out of the room grid",
+                    level.name,
+                    idx,
+                    MAX_INDEX + 1
+                ));
+                return None;
+            }
+            Some((idx, level, *placement, level.screen_size()))
+        })
+        .collect::<Vec<_>>();
+
+    let left = placements.iter().map(|(_, _, p, _)| p.x).min().unwrap_or(0);
+    let top = placements.iter().map(|(_, _, p, _)| p.y).min().unwrap_or(0);
+    let right = placements
+        .iter()
+        .map(|(_, _, p, (w, _))| p.x + w)
+        .max()
+        .unwrap_or(0);
+    let bottom = placements
+        .iter()
+        .map(|(_, _, p, (_, h))| p.y + h)
+        .max()
+        .unwrap_or(0);
+    let (width, height) = ((right - left) as usize, (bottom - top) as usize);
+
+    let mut grid = vec![EMPTY; width * height];
+    for (level_idx, level, placement, (screens_wide, screens_high)) in &placements {
+        let mut overlaps = false;
+        let mut too_many_screens = false;
+        for y in 0..*screens_high {
+            for x in 0..*screens_wide {
+                let screen_idx = (y * screens_wide + x) as usize;
+                if screen_idx > MAX_INDEX {
+                    too_many_screens = true;
+                    continue;
+                }
+                let cell = &mut grid
+                    [(placement.y + y - top) as usize * width + (placement.x + x - left) as usize];
+                if *cell == EMPTY {
+                    *cell = [*level_idx as u8, screen_idx as u8];
+                } else {
+                    overlaps = true;
+                }
+            }
+        }
+        if too_many_screens {
+            warnings.push(format!(
+                "Level `{}` has {} screens, but the room grid only has room for {} screens per \
+                level, so the rest were left out of the room grid",
+                level.name,
+                screens_wide * screens_high,
+                MAX_INDEX + 1
+            ));
+        }
+        if overlaps {
+            warnings.push(format!(
+                "Level `{}` overlaps another level in the world view, so some of its screens \
+                were left out of the room grid",
+                level.name
+            ));
+        }
+    }
+
+    let levels = project
+        .levels
+        .values()
+        .map(|x| format!("`{}`", x.name))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    Table {
+        kind: TableKind::World,
+        name: symbol.into(),
+        description: format!(
+            "World room grid: {}x{} screens in row-major order, each a level index followed by \
+            the screen index in that level, or $FF, $FF for no level. Levels: {}",
+            width, height, levels
+        ),
+        data: grid.into_iter().flatten().collect(),
+        row_len: (width * 2).max(1),
+        row_comments: Vec::new(),
+        segment: None,
+    }
+}
+
+/// Compress the pattern tables with the target's codec, adding the codec name to the table names.
+/// Returns the index of each pattern table with a description of how well it was compressed, or
+/// `None` if the codec couldn't compress it.
+fn compress_chr_tables(
+    data: &mut ExportData,
+    compression: ChrCompression,
+) -> Vec<(usize, Option<String>)> {
+    if compression == ChrCompression::None {
+        return Vec::new();
+    }
+
+    let mut results = Vec::new();
+    for (i, table) in data.tables.iter_mut().enumerate() {
+        if table.kind != TableKind::Chr {
+            continue;
+        }
+        let (codec, compressed) = match compression::compress(compression, &table.data) {
+            Some(result) => result,
+            None => {
+                results.push((i, None));
+                continue;
+            }
+        };
+
+        let size = format!(
+            "{} compressed from {} to {} bytes ( {:.0}% )",
+            codec.name(),
+            table.data.len(),
+            compressed.len(),
+            compressed.len() as f32 / table.data.len().max(1) as f32 * 100.0
+        );
+        table.name = format!("{}_{}", table.name, symbol_name(codec.name()));
+        table.description = format!("{}, {}", table.description, size);
+        table.data = compressed;
+        // The rows of compressed data don't line up with tiles anymore
+        table.row_comments.clear();
+        results.push((i, Some(size)));
+    }
+
+    results
+}
+
+/// Describe where a tile came from for a provenance comment, such as `tiles.png (3, 1)`, using
+/// the tile coordinates in the source image
+fn tile_provenance(project: &Project, tile: Option<&Tile>) -> String {
+    match tile.and_then(|tile| Some((tile, project.sources.get(&tile.source_id)?))) {
+        Some((tile, path)) => format!(
+            "{} ({}, {})",
+            path.file_name()
+                .unwrap_or(path.as_os_str())
+                .to_string_lossy(),
+            tile.x,
+            tile.y
+        ),
+        None => "blank".into(),
+    }
+}
+
+/// Get the color indexes of a tile, falling back to a blank tile if it is missing
+pub fn tile_pixels(
+    sources: &IndexMap<Uid<PathBuf>, IndexedImage>,
+    tile: Option<&Tile>,
+) -> [u8; 64] {
+    tile.and_then(|tile| sources.get(&tile.source_id)?.tile(tile.x, tile.y))
+        .unwrap_or([0; 64])
+}
+
+/// Encode the color indexes of an 8x8 tile into the 16 byte, two bit-plane NES CHR format
+pub fn encode_chr_tile(pixels: &[u8; 64]) -> [u8; 16] {
+    let mut chr = [0; 16];
+    for row in 0..8 {
+        for col in 0..8 {
+            let idx = pixels[row * 8 + col];
+            let bit = 7 - col;
+            chr[row] |= (idx & 1) << bit;
+            chr[row + 8] |= ((idx >> 1) & 1) << bit;
+        }
+    }
+    chr
+}
+
+/// Convert a human-readable name into a valid assembler and C identifier
+pub fn symbol_name(name: &str) -> String {
+    let mut symbol = String::with_capacity(name.len());
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            symbol.push(c.to_ascii_lowercase());
+        } else if !symbol.ends_with('_') {
+            symbol.push('_');
+        }
+    }
+    let symbol = symbol.trim_matches('_');
+
+    if symbol.is_empty() {
+        "unnamed".into()
+    } else if symbol.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", symbol)
+    } else {
+        symbol.into()
+    }
+}
+
+/// Get a symbol name for `name` that hasn't been used yet
+pub fn unique_symbol_name(used: &mut HashSet<String>, name: &str) -> String {
+    let base = symbol_name(name);
+    let mut symbol = base.clone();
+    let mut i = 2;
+    while !used.insert(symbol.clone()) {
+        symbol = format!("{}_{}", base, i);
+        i += 1;
+    }
+    symbol
+}
@@ -0,0 +1,164 @@
+//! Animated background tile tables
+//!
+//! Animations are exported for each metatileset that uses one of their tiles, in three tables:
+//!
+//! # Animations
+//!
+//! One row per animation: the pattern table index of the animated tile, the number of frames,
+//! and the number of NES frames that each frame is shown for.
+//!
+//! # Animation Frames
+//!
+//! The CHR data for every frame of every animation, in the same order as the animations table,
+//! for games that copy the frames into CHR-RAM.
+//!
+//! # Animation Banks
+//!
+//! Copies of the whole pattern table, one for each step of the animation cycle, with the animated
+//! tiles replaced by the frame shown during that step. This is the layout used by games that
+//! animate tiles by switching CHR-ROM banks, so every animation advances at the same time, and the
+//! number of banks is the least common multiple of the animation frame counts.
+
+use std::{collections::HashSet, path::PathBuf};
+
+use indexmap::{IndexMap, IndexSet};
+
+use crate::{
+    project::{Project, Tile, TileAnimation},
+    source::IndexedImage,
+    Uid,
+};
+
+use super::{encode_chr_tile, tile_pixels, Table, TableKind};
+
+/// The most animation banks that will be exported for one metatileset
+const MAX_BANKS: usize = 64;
+
+/// Build the animation tables for a metatileset.
+///
+/// `used_tiles` are the source tiles used by the metatileset, and `chr` is its pattern table.
+/// Returns no tables if none of the animations are used by the metatileset.
+pub fn metatileset_tables(
+    symbol: &str,
+    metatileset_name: &str,
+    project: &Project,
+    sources: &IndexMap<Uid<PathBuf>, IndexedImage>,
+    used_tiles: &HashSet<Tile>,
+    chr: &IndexSet<[u8; 16]>,
+    warnings: &mut Vec<String>,
+) -> Vec<Table> {
+    // The animations used by the metatileset, with the pattern table index of the animated tile
+    let animations = project
+        .tile_animations
+        .values()
+        .filter_map(|animation| {
+            let first = animation.frames.first()?;
+            if !used_tiles.contains(first) {
+                return None;
+            }
+            let idx = chr.get_index_of(&encode_chr_tile(&tile_pixels(sources, Some(first))))?;
+            Some((idx, animation))
+        })
+        .collect::<Vec<(usize, &TileAnimation)>>();
+
+    if animations.is_empty() {
+        return Vec::new();
+    }
+
+    let frame_chr = |tile: &Tile| encode_chr_tile(&tile_pixels(sources, Some(tile)));
+
+    let mut definitions = Vec::with_capacity(animations.len() * 3);
+    let mut frames = Vec::new();
+    for (idx, animation) in &animations {
+        definitions.extend([
+            *idx as u8,
+            animation.frames.len().min(255) as u8,
+            animation.frame_duration.min(255) as u8,
+        ]);
+        frames.extend(animation.frames.iter().flat_map(frame_chr));
+    }
+
+    let mut bank_count = animations.iter().map(|(_, x)| x.frames.len()).fold(1, lcm);
+    if bank_count > MAX_BANKS {
+        warnings.push(format!(
+            "The animations in metatileset `{}` need {} banks to cycle together, so only the \
+            first {} were exported",
+            metatileset_name, bank_count, MAX_BANKS
+        ));
+        bank_count = MAX_BANKS;
+    }
+
+    let mut banks = Vec::with_capacity(bank_count * chr.len() * 16);
+    for step in 0..bank_count {
+        let mut bank = chr.iter().copied().collect::<Vec<_>>();
+        for (idx, animation) in &animations {
+            bank[*idx] = frame_chr(&animation.frames[step % animation.frames.len()]);
+        }
+        banks.extend(bank.into_iter().flatten());
+    }
+
+    let names = animations
+        .iter()
+        .map(|(_, x)| format!("`{}`", x.name))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    vec![
+        Table {
+            kind: TableKind::Animation,
+            name: format!("{}_animations", symbol),
+            description: format!(
+                "Tile animations for metatileset `{}`: the pattern table index of the animated \
+                tile, the frame count, and the frame duration in NES frames. Animations: {}",
+                metatileset_name, names
+            ),
+            data: definitions,
+            row_len: 3,
+            row_comments: Vec::new(),
+            segment: None,
+        },
+        Table {
+            kind: TableKind::Animation,
+            name: format!("{}_animation_frames", symbol),
+            description: format!(
+                "CHR data for each frame of the tile animations in metatileset `{}`",
+                metatileset_name
+            ),
+            data: frames,
+            row_len: 16,
+            row_comments: Vec::new(),
+            segment: None,
+        },
+        Table {
+            kind: TableKind::Animation,
+            name: format!("{}_animation_banks", symbol),
+            description: format!(
+                "Pattern tables for metatileset `{}` with the animated tiles at each of {} \
+                steps of the animation cycle, {} tiles each",
+                metatileset_name,
+                bank_count,
+                chr.len()
+            ),
+            data: banks,
+            row_len: 16,
+            row_comments: Vec::new(),
+            segment: None,
+        },
+    ]
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// The least common multiple, saturating instead of overflowing
+fn lcm(a: usize, b: usize) -> usize {
+    if a == 0 || b == 0 {
+        return a.max(b);
+    }
+    (a / gcd(a, b)).saturating_mul(b)
+}
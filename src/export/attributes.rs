@@ -0,0 +1,92 @@
+//! Attribute tables for level maps
+//!
+//! For games that build their own nametables, each level can get a table with the PPU attribute
+//! bytes for every screen of its map. The map, including any HUD rows, is split into screens of
+//! 16x15 metatiles in the same order as the screen flags table, and each screen gets 64 bytes
+//! laid out like the PPU's attribute table: 8 bytes per row, each covering 2x2 metatiles. The
+//! bottom half of the last row is past the bottom of the screen and uses sub-pallet 0.
+
+use crate::constants::{SCREEN_HEIGHT_METATILES, SCREEN_WIDTH_METATILES};
+
+use super::{Table, TableKind};
+
+/// The number of attribute bytes for one screen
+const SCREEN_ATTRIBUTE_BYTES: usize = 64;
+
+/// Pack the sub-pallets of 2x2 metatiles into an attribute byte, with the top-left metatile in
+/// the lowest two bits, followed by the top-right, bottom-left, and bottom-right
+pub fn attribute_byte(sub_pallets: [u8; 4]) -> u8 {
+    sub_pallets
+        .iter()
+        .enumerate()
+        .fold(0, |byte, (i, sub_pallet)| {
+            byte | (sub_pallet & 3) << (i * 2)
+        })
+}
+
+/// Build the attribute table for a level
+///
+/// `map` holds the metatile index of each cell, `width` cells per row, and `sub_pallets` holds the
+/// sub-pallet of each metatile.
+pub fn level_table(
+    symbol: &str,
+    level_name: &str,
+    map: &[u8],
+    width: usize,
+    sub_pallets: &[u8],
+) -> Table {
+    let width = width.max(1);
+    let height = map.len() / width;
+    // The sub-pallet of a cell, or 0 for empty cells and cells outside of the map
+    let sub_pallet = |x: usize, y: usize| {
+        if x < width && y < height {
+            sub_pallets
+                .get(map[y * width + x] as usize)
+                .copied()
+                .unwrap_or(0)
+        } else {
+            0
+        }
+    };
+
+    let screens_wide = (0..width).step_by(SCREEN_WIDTH_METATILES).len();
+    let screens_high = (0..height).step_by(SCREEN_HEIGHT_METATILES).len();
+    let mut data = Vec::with_capacity(screens_wide * screens_high * SCREEN_ATTRIBUTE_BYTES);
+    for screen_y in (0..height).step_by(SCREEN_HEIGHT_METATILES) {
+        for screen_x in (0..width).step_by(SCREEN_WIDTH_METATILES) {
+            for y in (0..SCREEN_HEIGHT_METATILES).step_by(2) {
+                for x in (0..SCREEN_WIDTH_METATILES).step_by(2) {
+                    // The bottom half of the last row is off of the screen
+                    let cell = |dx: usize, dy: usize| {
+                        if y + dy < SCREEN_HEIGHT_METATILES {
+                            sub_pallet(screen_x + x + dx, screen_y + y + dy)
+                        } else {
+                            0
+                        }
+                    };
+                    data.push(attribute_byte([
+                        cell(0, 0),
+                        cell(1, 0),
+                        cell(0, 1),
+                        cell(1, 1),
+                    ]));
+                }
+            }
+        }
+    }
+
+    Table {
+        kind: TableKind::Attributes,
+        name: format!("{}_attributes", symbol),
+        description: format!(
+            "Attributes for level `{}`: {} bytes for each of its {}x{} screens, left to right \
+            and then top to bottom, counting from the top-left corner of the map including any \
+            HUD rows. Each screen is laid out like a PPU attribute table",
+            level_name, SCREEN_ATTRIBUTE_BYTES, screens_wide, screens_high
+        ),
+        data,
+        row_len: SCREEN_WIDTH_METATILES / 2,
+        row_comments: Vec::new(),
+        segment: None,
+    }
+}
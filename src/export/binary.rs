@@ -0,0 +1,19 @@
+//! Raw binary export, with one file per table
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+use super::ExportData;
+
+pub fn write(data: &ExportData, out_dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = Vec::with_capacity(data.tables.len());
+
+    for table in &data.tables {
+        let path = out_dir.join(format!("{}.bin", table.name));
+        std::fs::write(&path, &table.data).with_context(|| format!("Write {}", path.display()))?;
+        files.push(path);
+    }
+
+    Ok(files)
+}
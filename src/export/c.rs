@@ -0,0 +1,96 @@
+//! C source and header export
+
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+use crate::project::ExportTarget;
+
+use super::ExportData;
+
+pub fn write(
+    data: &ExportData,
+    target: &ExportTarget,
+    out_dir: &Path,
+    file_stem: &str,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let guard = format!("{}_H", file_stem.to_ascii_uppercase());
+
+    let mut header = String::new();
+    let mut source = String::new();
+
+    if target.comments {
+        let comment = format!(
+            "/* Generated by NESImg, export target `{}` */\n\n",
+            target.name
+        );
+        header.push_str(&comment);
+        source.push_str(&comment);
+    }
+
+    writeln!(header, "#ifndef {}", guard)?;
+    writeln!(header, "#define {}", guard)?;
+    writeln!(source, "#include \"{}.h\"", file_stem)?;
+
+    for group in &data.constants {
+        writeln!(header)?;
+        if target.comments {
+            writeln!(header, "/* {} */", group.description)?;
+        }
+        for (name, value) in &group.constants {
+            writeln!(header, "#define {} 0x{:02X}", name, value)?;
+        }
+    }
+
+    for (i, table) in data.tables.iter().enumerate() {
+        writeln!(header)?;
+        writeln!(source)?;
+        if target.comments {
+            writeln!(header, "/* {} */", table.description)?;
+        }
+        writeln!(
+            header,
+            "extern const unsigned char {}[{}];",
+            table.name,
+            table.data.len().max(1)
+        )?;
+        if let Some((name, crc)) = data.checksums.get(i) {
+            writeln!(header, "#define {} 0x{:08X}UL", name, crc)?;
+        }
+
+        writeln!(
+            source,
+            "const unsigned char {}[{}] = {{",
+            table.name,
+            table.data.len().max(1)
+        )?;
+        // C doesn't allow zero-length arrays
+        if table.data.is_empty() {
+            writeln!(source, "    0x00,")?;
+        }
+        for row in table.data.chunks(table.row_len.max(1)) {
+            let bytes = row
+                .iter()
+                .map(|x| format!("0x{:02X}", x))
+                .collect::<Vec<_>>()
+                .join(", ");
+            writeln!(source, "    {},", bytes)?;
+        }
+        writeln!(source, "}};")?;
+    }
+
+    writeln!(header)?;
+    writeln!(header, "#endif")?;
+
+    let header_path = out_dir.join(format!("{}.h", file_stem));
+    let source_path = out_dir.join(format!("{}.c", file_stem));
+    std::fs::write(&header_path, header)
+        .with_context(|| format!("Write {}", header_path.display()))?;
+    std::fs::write(&source_path, source)
+        .with_context(|| format!("Write {}", source_path.display()))?;
+
+    Ok(vec![header_path, source_path])
+}
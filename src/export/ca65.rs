@@ -0,0 +1,95 @@
+//! ca65 assembly export
+
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+use crate::project::{ExportTarget, DEFAULT_SEGMENT};
+
+use super::ExportData;
+
+pub fn write(
+    data: &ExportData,
+    target: &ExportTarget,
+    out_dir: &Path,
+    file_stem: &str,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let mut out = String::new();
+
+    if target.comments {
+        writeln!(
+            out,
+            "; Generated by NESImg, export target `{}`",
+            target.name
+        )?;
+        writeln!(out)?;
+    }
+
+    for group in &data.constants {
+        if target.comments {
+            writeln!(out, "; {}", group.description)?;
+        }
+        for (name, value) in &group.constants {
+            writeln!(out, "{} = ${:02X}", name, value)?;
+            writeln!(out, ".exportzp {}", name)?;
+        }
+        writeln!(out)?;
+    }
+
+    if target.checksums {
+        if target.comments {
+            writeln!(out, "; CRC-32 of each table")?;
+        }
+        for (name, crc) in &data.checksums {
+            writeln!(out, "{} = ${:08X}", name, crc)?;
+            writeln!(out, ".export {}", name)?;
+        }
+        writeln!(out)?;
+    }
+
+    for table in &data.tables {
+        writeln!(out, ".export {}", table.name)?;
+    }
+
+    // Switch segments only when the next table is in a different one than the last, so tables
+    // keep their export order
+    let default_segment = if target.segment.is_empty() {
+        DEFAULT_SEGMENT
+    } else {
+        target.segment.as_str()
+    };
+    let mut current_segment = None;
+    for table in &data.tables {
+        let segment = table.segment.as_deref().unwrap_or(default_segment);
+        if current_segment != Some(segment) {
+            writeln!(out)?;
+            writeln!(out, ".segment \"{}\"", segment)?;
+            current_segment = Some(segment);
+        }
+
+        writeln!(out)?;
+        if target.comments {
+            writeln!(out, "; {}", table.description)?;
+        }
+        writeln!(out, "{}:", table.name)?;
+        for (i, row) in table.data.chunks(table.row_len.max(1)).enumerate() {
+            let bytes = row
+                .iter()
+                .map(|x| format!("${:02X}", x))
+                .collect::<Vec<_>>()
+                .join(",");
+            match table.row_comments.get(i).filter(|_| target.provenance) {
+                Some(comment) => writeln!(out, "    .byte {} ; {}", bytes, comment)?,
+                None => writeln!(out, "    .byte {}", bytes)?,
+            }
+        }
+    }
+
+    let path = out_dir.join(format!("{}.s", file_stem));
+    std::fs::write(&path, out).with_context(|| format!("Write {}", path.display()))?;
+
+    Ok(vec![path])
+}
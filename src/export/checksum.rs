@@ -0,0 +1,56 @@
+//! CRC-32 checksums of the exported tables, written as constants by the assembly and C exports
+//!
+//! Games can check their data against the constants at boot, and build systems can compare them
+//! to notice stale data without reading the tables. The checksum is the common CRC-32 used by zip
+//! and PNG: the reflected polynomial `$EDB88320`, starting from `$FFFFFFFF`, with the result
+//! inverted. It covers the bytes of the table exactly as they are exported, so compressed tables
+//! are checked in their compressed form.
+
+use crate::project::SymbolCase;
+
+use super::symbols::styled_symbol;
+
+/// The reflected CRC-32 polynomial
+const POLYNOMIAL: u32 = 0xEDB8_8320;
+
+/// The CRC-32 of `data`
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ POLYNOMIAL
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// The name of the constant holding the checksum of a table, with the target's prefix. Constants
+/// are always upper-case, so the name is built from the snake_case table name, before the table
+/// names are styled, to keep the words apart.
+pub fn constant_name(table_name: &str, prefix: &str) -> String {
+    styled_symbol(&format!("{}_crc32", table_name), prefix, SymbolCase::Upper)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn constant_name_keeps_words_apart() {
+        assert_eq!(constant_name("overworld_chr", ""), "OVERWORLD_CHR_CRC32");
+        assert_eq!(
+            constant_name("overworld_chr", "game"),
+            "GAME_OVERWORLD_CHR_CRC32"
+        );
+    }
+}
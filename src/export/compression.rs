@@ -0,0 +1,432 @@
+//! Compression codecs for exported pattern tables
+//!
+//! All of the codecs are simple enough to be decompressed by the NES straight into CHR-RAM.
+//!
+//! # RLE
+//!
+//! The first byte is a tag byte that doesn't appear anywhere in the uncompressed data. Every other
+//! byte is copied to the output, except for the tag, which is followed by a count byte. A count of
+//! 1-255 repeats the last byte that was output that many times, and a count of 0 ends the stream.
+//! This is the format read by `vram_unrle` in neslib.
+//!
+//! # PB8
+//!
+//! The data is split into 8 byte chunks, which is the size of one plane of a tile. Each chunk
+//! starts with a flag byte, where the most significant bit is for the first byte of the chunk. A
+//! set bit means that the byte is the same as the previous byte, and a clear bit means that the
+//! byte follows in the stream. The previous byte is $00 at the start of every chunk.
+//!
+//! # LZSS
+//!
+//! The stream is made of groups of up to 8 items, each preceded by a flag byte, where the most
+//! significant bit is for the first item. A clear bit is a literal byte that is copied to the
+//! output. A set bit is a match, made of a distance byte, 1-255, counting back from the end of the
+//! output, and a length byte holding the number of bytes to copy minus 3. Matches may overlap the
+//! bytes they are producing. A match with a distance of 0 and no length byte ends the stream.
+//!
+//! # Donut
+//!
+//! The data is split into 64 byte blocks of four tiles, with the last block padded with zeros,
+//! and each block into eight 8 byte planes. Each block starts with a header byte:
+//!
+//! - `$00`: every plane is $00
+//! - `$0C`: every plane is packed
+//! - `$02`: followed by a byte where the most significant bit is for the first plane, a set bit
+//!   for a packed plane and a clear bit for a plane of $00
+//! - `$2A`: followed by the 64 bytes of the block as they are
+//!
+//! A packed plane starts with a flag byte, where the most significant bit is for the first byte
+//! of the plane. A set bit means that the byte follows in the stream, and a clear bit means that
+//! the byte is the same as the previous byte, which is $00 at the start of every plane. There is
+//! no end marker, so the game has to know how many blocks to decode. These are a subset of the
+//! block types of jroatch's Donut format, so the data can be decoded with its 6502 decoder.
+
+use crate::project::ChrCompression;
+
+/// The codecs that [`ChrCompression::Smallest`] picks from
+const CODECS: [ChrCompression; 4] = [
+    ChrCompression::Rle,
+    ChrCompression::Pb8,
+    ChrCompression::Lzss,
+    ChrCompression::Donut,
+];
+
+/// The number of bytes in a Donut block
+const DONUT_BLOCK: usize = 64;
+
+/// Compress data, returning the codec that was actually used, which will be different than the
+/// requested codec for [`ChrCompression::Smallest`].
+///
+/// Returns `None` if the data can't be compressed with the requested codec.
+pub fn compress(compression: ChrCompression, data: &[u8]) -> Option<(ChrCompression, Vec<u8>)> {
+    match compression {
+        ChrCompression::None => Some((compression, data.to_vec())),
+        ChrCompression::Rle => Some((compression, rle(data)?)),
+        ChrCompression::Pb8 => Some((compression, pb8(data))),
+        ChrCompression::Lzss => Some((compression, lzss(data))),
+        ChrCompression::Donut => Some((compression, donut(data))),
+        ChrCompression::Smallest => CODECS
+            .into_iter()
+            .filter_map(|codec| compress(codec, data))
+            .min_by_key(|(_, compressed)| compressed.len()),
+    }
+}
+
+/// Compress data with RLE, or return `None` if every byte value is used, leaving none for the tag
+pub fn rle(data: &[u8]) -> Option<Vec<u8>> {
+    let mut used = [false; 256];
+    for &byte in data {
+        used[byte as usize] = true;
+    }
+    let tag = (0..=255).find(|&x| !used[x as usize])?;
+
+    let mut out = vec![tag];
+    let mut i = 0;
+    while i < data.len() {
+        let byte = data[i];
+        let run = data[i..].iter().take_while(|&&x| x == byte).count();
+        out.push(byte);
+
+        // A single repeat is cheaper as a literal than as a tag and count
+        let mut repeats = run - 1;
+        while repeats > 0 {
+            if repeats == 1 {
+                out.push(byte);
+                repeats = 0;
+            } else {
+                let count = repeats.min(255);
+                out.extend([tag, count as u8]);
+                repeats -= count;
+            }
+        }
+
+        i += run;
+    }
+    out.extend([tag, 0]);
+
+    Some(out)
+}
+
+/// Compress data with PB8, padding the last chunk with zeros
+pub fn pb8(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + data.len() / 8 + 1);
+
+    for chunk in data.chunks(8) {
+        let mut flags = 0u8;
+        let mut literals = Vec::with_capacity(8);
+        let mut previous = 0;
+
+        for i in 0..8 {
+            let byte = chunk.get(i).copied().unwrap_or(0);
+            flags <<= 1;
+            if byte == previous {
+                flags |= 1;
+            } else {
+                literals.push(byte);
+                previous = byte;
+            }
+        }
+
+        out.push(flags);
+        out.extend(literals);
+    }
+
+    out
+}
+
+/// Compress data with LZSS
+pub fn lzss(data: &[u8]) -> Vec<u8> {
+    const MIN_MATCH: usize = 3;
+    const MAX_MATCH: usize = 255 + MIN_MATCH;
+    const WINDOW: usize = 255;
+
+    let mut writer = FlagWriter::default();
+    let mut i = 0;
+    while i < data.len() {
+        let max_len = MAX_MATCH.min(data.len() - i);
+
+        let mut best = (0, 0);
+        for distance in 1..=WINDOW.min(i) {
+            let start = i - distance;
+            let len = (0..max_len)
+                .take_while(|&k| data[start + k] == data[i + k])
+                .count();
+            if len > best.0 {
+                best = (len, distance);
+            }
+        }
+
+        let (len, distance) = best;
+        if len >= MIN_MATCH {
+            writer.item(true, &[distance as u8, (len - MIN_MATCH) as u8]);
+            i += len;
+        } else {
+            writer.item(false, &[data[i]]);
+            i += 1;
+        }
+    }
+    writer.item(true, &[0]);
+
+    writer.out
+}
+
+/// Compress data with Donut, padding the last block with zeros
+pub fn donut(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + data.len() / DONUT_BLOCK + 1);
+
+    for chunk in data.chunks(DONUT_BLOCK) {
+        let mut block = [0; DONUT_BLOCK];
+        block[..chunk.len()].copy_from_slice(chunk);
+
+        let mut plane_flags = 0u8;
+        let mut planes = Vec::with_capacity(DONUT_BLOCK);
+        for plane in block.chunks(8) {
+            plane_flags <<= 1;
+            if plane.iter().any(|&x| x != 0) {
+                plane_flags |= 1;
+                planes.extend(donut_plane(plane));
+            }
+        }
+
+        // Pick the smallest of the block types, the raw block being the last resort
+        let packed_len = planes.len() + if plane_flags == 0xFF { 1 } else { 2 };
+        if plane_flags == 0 {
+            out.push(0x00);
+        } else if packed_len > DONUT_BLOCK + 1 {
+            out.push(0x2A);
+            out.extend(block);
+        } else if plane_flags == 0xFF {
+            out.push(0x0C);
+            out.extend(planes);
+        } else {
+            out.extend([0x02, plane_flags]);
+            out.extend(planes);
+        }
+    }
+
+    out
+}
+
+/// Pack one 8 byte plane of a Donut block
+fn donut_plane(plane: &[u8]) -> Vec<u8> {
+    let mut flags = 0u8;
+    let mut literals = Vec::with_capacity(8);
+    let mut previous = 0;
+
+    for &byte in plane {
+        flags <<= 1;
+        if byte != previous {
+            flags |= 1;
+            literals.push(byte);
+            previous = byte;
+        }
+    }
+
+    let mut out = vec![flags];
+    out.extend(literals);
+    out
+}
+
+/// Writes items that are grouped in eights behind a flag byte
+#[derive(Default)]
+struct FlagWriter {
+    out: Vec<u8>,
+    /// The position of the current flag byte in the output
+    flags_idx: usize,
+    /// The bit in the current flag byte for the next item, or 0 if a new flag byte is needed
+    bit: u8,
+}
+
+impl FlagWriter {
+    fn item(&mut self, flag: bool, bytes: &[u8]) {
+        if self.bit == 0 {
+            self.flags_idx = self.out.len();
+            self.out.push(0);
+            self.bit = 0x80;
+        }
+        if flag {
+            self.out[self.flags_idx] |= self.bit;
+        }
+        self.bit >>= 1;
+        self.out.extend_from_slice(bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pattern table-like test data: blank tiles, solid tiles, runs, and noise, with a length that
+    /// doesn't fill the last PB8 chunk or Donut block
+    fn sample_data() -> Vec<u8> {
+        let mut data = vec![0; 64];
+        data.extend([0xFF; 48]);
+        data.extend((0..200u32).map(|x| (x * 37 % 11) as u8));
+        let mut seed = 0x1234_5678u32;
+        data.extend((0..300).map(|_| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (seed >> 16) as u8
+        }));
+        data.extend([0x42; 70]);
+        data.extend([1, 2, 3, 1, 2, 3, 1, 2, 3, 1, 2]);
+        data
+    }
+
+    fn unrle(data: &[u8]) -> Vec<u8> {
+        let tag = data[0];
+        let mut out = Vec::new();
+        let mut i = 1;
+        loop {
+            if data[i] == tag {
+                let count = data[i + 1];
+                if count == 0 {
+                    return out;
+                }
+                let last = *out.last().unwrap();
+                out.extend(std::iter::repeat(last).take(count as usize));
+                i += 2;
+            } else {
+                out.push(data[i]);
+                i += 1;
+            }
+        }
+    }
+
+    fn unpb8(data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut i = 0;
+        while i < data.len() {
+            let flags = data[i];
+            i += 1;
+            let mut previous = 0;
+            for bit in (0..8).rev() {
+                if flags & 1 << bit == 0 {
+                    previous = data[i];
+                    i += 1;
+                }
+                out.push(previous);
+            }
+        }
+        out
+    }
+
+    fn unlzss(data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut i = 0;
+        loop {
+            let flags = data[i];
+            i += 1;
+            for bit in (0..8).rev() {
+                if flags & 1 << bit == 0 {
+                    out.push(data[i]);
+                    i += 1;
+                    continue;
+                }
+                let distance = data[i] as usize;
+                if distance == 0 {
+                    return out;
+                }
+                let len = data[i + 1] as usize + 3;
+                i += 2;
+                for _ in 0..len {
+                    out.push(out[out.len() - distance]);
+                }
+            }
+        }
+    }
+
+    fn undonut(data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let mut i = 0;
+        while i < data.len() {
+            let header = data[i];
+            i += 1;
+            let plane_flags = match header {
+                0x00 => 0x00,
+                0x0C => 0xFF,
+                0x02 => {
+                    i += 1;
+                    data[i - 1]
+                }
+                0x2A => {
+                    out.extend(&data[i..i + DONUT_BLOCK]);
+                    i += DONUT_BLOCK;
+                    continue;
+                }
+                _ => panic!("Unexpected block header ${:02X}", header),
+            };
+            for plane in (0..8).rev() {
+                if plane_flags & 1 << plane == 0 {
+                    out.extend([0; 8]);
+                    continue;
+                }
+                let flags = data[i];
+                i += 1;
+                let mut previous = 0;
+                for bit in (0..8).rev() {
+                    if flags & 1 << bit != 0 {
+                        previous = data[i];
+                        i += 1;
+                    }
+                    out.push(previous);
+                }
+            }
+        }
+        out
+    }
+
+    /// Check that the unpacked data is the original data, followed by no more than `padding` zeros
+    fn assert_unpacked(original: &[u8], unpacked: &[u8], padding: usize) {
+        assert_eq!(&unpacked[..original.len()], original);
+        assert!(unpacked.len() - original.len() < padding.max(1));
+        assert!(unpacked[original.len()..].iter().all(|&x| x == 0));
+    }
+
+    #[test]
+    fn rle_round_trip() {
+        let data = sample_data();
+        assert_eq!(unrle(&rle(&data).unwrap()), data);
+        assert_eq!(rle(&(0..=255).collect::<Vec<u8>>()), None);
+    }
+
+    #[test]
+    fn pb8_round_trip() {
+        let data = sample_data();
+        assert_unpacked(&data, &unpb8(&pb8(&data)), 8);
+    }
+
+    #[test]
+    fn lzss_round_trip() {
+        let data = sample_data();
+        assert_eq!(unlzss(&lzss(&data)), data);
+    }
+
+    #[test]
+    fn donut_round_trip() {
+        let data = sample_data();
+        let packed = donut(&data);
+        assert_unpacked(&data, &undonut(&packed), DONUT_BLOCK);
+        // The blank block packs to one byte
+        assert_eq!(packed[0], 0x00);
+    }
+
+    #[test]
+    fn smallest_picks_the_smallest_codec() {
+        let data = sample_data();
+        let (codec, packed) = compress(ChrCompression::Smallest, &data).unwrap();
+        assert_ne!(codec, ChrCompression::Smallest);
+        for codec in CODECS {
+            if let Some((_, other)) = compress(codec, &data) {
+                assert!(packed.len() <= other.len());
+            }
+        }
+    }
+
+    #[test]
+    fn empty_data_round_trips() {
+        assert_eq!(unrle(&rle(&[]).unwrap()), Vec::<u8>::new());
+        assert!(unpb8(&pb8(&[])).is_empty());
+        assert_eq!(unlzss(&lzss(&[])), Vec::<u8>::new());
+        assert!(undonut(&donut(&[])).is_empty());
+    }
+}
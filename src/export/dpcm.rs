@@ -0,0 +1,58 @@
+//! Checks for tables placed in the DPCM sample region
+//!
+//! The APU can only play DPCM samples from $C000-$FFFF, so games with a lot of samples keep the
+//! start of that range free for them. When an export target gives the address its tables are
+//! placed at in PRG-ROM, the tables in its default segment are laid out one after another from
+//! there, in the order they are exported, and the ones that land in the bytes reserved for samples
+//! are reported. Tables assigned to other segments are placed by the linker config, usually in
+//! switchable banks, so they aren't checked.
+
+use super::ExportData;
+
+/// The first address that DPCM samples can be played from
+pub const DPCM_START: u32 = 0xC000;
+
+/// The first address past the end of the CPU address space
+const ADDRESS_SPACE_END: u32 = 0x10000;
+
+/// Warn about the tables that overlap the `reserved` bytes starting at $C000 when they are placed
+/// one after another from `origin`
+pub fn overlap_warnings(data: &ExportData, origin: u16, reserved: u16) -> Vec<String> {
+    let region = DPCM_START..(DPCM_START + reserved as u32).min(ADDRESS_SPACE_END);
+    if region.is_empty() {
+        return Vec::new();
+    }
+
+    let mut address = origin as u32;
+    let mut overlapping = Vec::new();
+    for table in data.tables.iter().filter(|x| x.segment.is_none()) {
+        let start = address;
+        address += table.data.len() as u32;
+        if start < region.end && address > region.start {
+            overlapping.push(format!(
+                "`{}` ( ${:04X}-${:04X} )",
+                table.name,
+                start,
+                address - 1
+            ));
+        }
+    }
+
+    if overlapping.is_empty() {
+        return Vec::new();
+    }
+    vec![format!(
+        "{} placed from ${:04X} overlap the {} bytes reserved for DPCM samples at ${:04X}-${:04X}: \
+        {}",
+        if overlapping.len() == 1 {
+            "1 table".to_owned()
+        } else {
+            format!("{} tables", overlapping.len())
+        },
+        origin,
+        region.len(),
+        region.start,
+        region.end - 1,
+        overlapping.join(", ")
+    )]
+}
@@ -0,0 +1,456 @@
+//! Static HTML report export
+//!
+//! The report is a single HTML page documenting the project, with PNG images next to it for the
+//! metatile sheets and level maps. It is meant to be committed or shared along with the game so
+//! the whole team can see what is in the project without opening NESImg.
+
+use std::{
+    collections::HashSet,
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use egui::Color32;
+use image::{Rgb, RgbImage};
+use indexmap::IndexMap;
+
+use crate::{
+    constants::{NES_PALLET, SCREEN_HEIGHT_METATILES, SCREEN_WIDTH_METATILES},
+    project::{ExitSource, Level, Metatileset, MetatilesetTile, Project, Zone},
+    source::IndexedImage,
+    Uid,
+};
+
+use super::{tile_pixels, unique_symbol_name, ExportData, EMPTY_METATILE};
+
+/// The number of metatiles in each row of a metatile sheet
+const SHEET_COLUMNS: usize = 8;
+
+/// How much the images are scaled up in the report
+const SCALE: usize = 2;
+
+pub fn write(
+    project: &Project,
+    sources: &IndexMap<Uid<PathBuf>, IndexedImage>,
+    data: &ExportData,
+    out_dir: &Path,
+    file_stem: &str,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut names = HashSet::new();
+    let mut out = String::new();
+
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, "<html>")?;
+    writeln!(out, "<head>")?;
+    writeln!(out, "<meta charset=\"utf-8\">")?;
+    writeln!(out, "<title>{} - NESImg Report</title>", escape(file_stem))?;
+    writeln!(out, "<style>{}</style>", STYLE)?;
+    writeln!(out, "</head>")?;
+    writeln!(out, "<body>")?;
+    writeln!(out, "<h1>{}</h1>", escape(file_stem))?;
+    writeln!(
+        out,
+        "<p>Generated by NESImg. The images next to this file are regenerated on every export.</p>"
+    )?;
+
+    writeln!(out, "<h2>Metatilesets</h2>")?;
+    if project.metatilesets.is_empty() {
+        writeln!(out, "<p>No metatilesets.</p>")?;
+    }
+    for metatileset in project.metatilesets.values() {
+        writeln!(out, "<h3>{}</h3>", escape(&metatileset.name))?;
+        pallet_html(&mut out, metatileset)?;
+
+        if metatileset.tiles.is_empty() {
+            writeln!(out, "<p>No metatiles.</p>")?;
+            continue;
+        }
+
+        let file_name = format!(
+            "{}_{}_metatiles.png",
+            file_stem,
+            unique_symbol_name(&mut names, &metatileset.name)
+        );
+        let path = out_dir.join(&file_name);
+        metatile_sheet(project, sources, metatileset)
+            .save(&path)
+            .with_context(|| format!("Write {}", path.display()))?;
+        files.push(path);
+
+        let size = 16 * SCALE;
+        let sheet_width = SHEET_COLUMNS * size;
+        writeln!(out, "<div class=\"metatiles\">")?;
+        for (i, tile) in metatileset.tiles.values().enumerate() {
+            let (x, y) = ((i % SHEET_COLUMNS) * size, (i / SHEET_COLUMNS) * size);
+            writeln!(
+                out,
+                "<figure><div class=\"metatile\" style=\"background-image: url('{}'); \
+                background-size: {}px auto; background-position: -{}px -{}px\"></div>\
+                <figcaption>${:02X}<br><span class=\"dim\">sub-pallet {}</span></figcaption>\
+                </figure>",
+                escape(&file_name),
+                sheet_width,
+                x,
+                y,
+                i,
+                tile.sub_pallet_idx
+            )?;
+        }
+        writeln!(out, "</div>")?;
+    }
+
+    writeln!(out, "<h2>Levels</h2>")?;
+    if project.levels.is_empty() {
+        writeln!(out, "<p>No levels.</p>")?;
+    }
+    for level in project.levels.values() {
+        writeln!(out, "<h3>{}</h3>", escape(&level.name))?;
+        let metatileset = project.metatilesets.get(&level.metatileset_id);
+        let (screens_x, screens_y) = level.screen_size();
+        writeln!(out, "<table class=\"properties\">")?;
+        writeln!(
+            out,
+            "<tr><th>Size</th><td>{}x{} metatiles, {}x{} screens</td></tr>",
+            level.margin.width(),
+            level.margin.height(),
+            screens_x,
+            screens_y
+        )?;
+        writeln!(
+            out,
+            "<tr><th>Metatileset</th><td>{}</td></tr>",
+            escape(metatileset.map(|x| x.name.as_str()).unwrap_or("None"))
+        )?;
+        writeln!(
+            out,
+            "<tr><th>Placed Tiles</th><td>{}</td></tr>",
+            level.tiles.len()
+        )?;
+        if level.hud.top > 0 || level.hud.bottom > 0 {
+            writeln!(
+                out,
+                "<tr><th>HUD Rows</th><td>{} at the top, {} at the bottom</td></tr>",
+                level.hud.top, level.hud.bottom
+            )?;
+        }
+        writeln!(out, "</table>")?;
+
+        if level.margin.width() > 0 && level.margin.height() > 0 {
+            let file_name = format!(
+                "{}_{}_map.png",
+                file_stem,
+                unique_symbol_name(&mut names, &level.name)
+            );
+            let path = out_dir.join(&file_name);
+            let map = level_map(project, sources, level, metatileset);
+            map.save(&path)
+                .with_context(|| format!("Write {}", path.display()))?;
+            files.push(path);
+            writeln!(
+                out,
+                "<div class=\"map\" style=\"--screen-width: {}px; --screen-height: {}px\">\
+                <img src=\"{}\" width=\"{}\" height=\"{}\" alt=\"{}\"></div>",
+                SCREEN_WIDTH_METATILES * 16 * SCALE,
+                SCREEN_HEIGHT_METATILES * 16 * SCALE,
+                escape(&file_name),
+                map.width() as usize * SCALE,
+                map.height() as usize * SCALE,
+                escape(&level.name)
+            )?;
+        }
+
+        if !level.zones.is_empty() {
+            writeln!(out, "<h4>Zones</h4>")?;
+            writeln!(out, "<table>")?;
+            writeln!(
+                out,
+                "<tr><th>Name</th><th>Kind</th><th>X</th><th>Y</th><th>Width</th><th>Height</th></tr>"
+            )?;
+            for zone in level.zones.values() {
+                // Relative to the top-left of the map, like the exported zone tables
+                writeln!(
+                    out,
+                    "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                    escape(&zone.name),
+                    zone.kind.name(),
+                    zone.x + level.margin.left,
+                    zone.y + level.margin.top,
+                    zone.width,
+                    zone.height
+                )?;
+            }
+            writeln!(out, "</table>")?;
+        }
+
+        if !level.exits.is_empty() {
+            writeln!(out, "<h4>Exits</h4>")?;
+            writeln!(out, "<table>")?;
+            writeln!(out, "<tr><th>From</th><th>To</th></tr>")?;
+            for exit in level.exits.values() {
+                let source = match exit.source {
+                    ExitSource::Edge(edge) => edge.name().to_owned(),
+                    ExitSource::Zone(id) => format!("Zone {}", zone_name(level, id)),
+                };
+                let target = match project.levels.get(&exit.target_level_id) {
+                    Some(target) => match exit.target_zone_id {
+                        Some(id) => format!("{}, zone {}", target.name, zone_name(target, id)),
+                        None => target.name.clone(),
+                    },
+                    None => "Missing level".into(),
+                };
+                writeln!(
+                    out,
+                    "<tr><td>{}</td><td>{}</td></tr>",
+                    escape(&source),
+                    escape(&target)
+                )?;
+            }
+            writeln!(out, "</table>")?;
+        }
+    }
+
+    writeln!(out, "<h2>Export Targets</h2>")?;
+    writeln!(out, "<table>")?;
+    writeln!(
+        out,
+        "<tr><th>Name</th><th>Format</th><th>Comments</th><th>CHR Compression</th>\
+        <th>CHR Streaming</th></tr>"
+    )?;
+    for target in project.export_targets.values() {
+        writeln!(
+            out,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            escape(&target.name),
+            target.format.name(),
+            yes_no(target.comments),
+            target.chr_compression.name(),
+            if target.chr_streaming {
+                format!("NMI budget {} bytes", target.nmi_transfer_budget)
+            } else {
+                "No".into()
+            }
+        )?;
+    }
+    writeln!(out, "</table>")?;
+
+    writeln!(out, "<h2>Sources</h2>")?;
+    writeln!(out, "<table>")?;
+    writeln!(out, "<tr><th>Path</th><th>Size</th></tr>")?;
+    for (id, path) in &project.sources {
+        let size = match sources.get(id) {
+            Some(image) => format!("{}x{} pixels", image.width, image.height),
+            None => "Could not be loaded".into(),
+        };
+        writeln!(
+            out,
+            "<tr><td>{}</td><td>{}</td></tr>",
+            escape(&path.display().to_string()),
+            size
+        )?;
+    }
+    writeln!(out, "</table>")?;
+
+    writeln!(out, "<h2>Exported Tables</h2>")?;
+    writeln!(out, "<table>")?;
+    writeln!(
+        out,
+        "<tr><th>Name</th><th>Kind</th><th>Bytes</th><th>Description</th></tr>"
+    )?;
+    for table in &data.tables {
+        writeln!(
+            out,
+            "<tr><td><code>{}</code></td><td>{:?}</td><td>{}</td><td>{}</td></tr>",
+            escape(&table.name),
+            table.kind,
+            table.data.len(),
+            escape(&table.description)
+        )?;
+    }
+    writeln!(out, "</table>")?;
+
+    writeln!(out, "</body>")?;
+    writeln!(out, "</html>")?;
+
+    let path = out_dir.join(format!("{}_report.html", file_stem));
+    std::fs::write(&path, out).with_context(|| format!("Write {}", path.display()))?;
+    files.insert(0, path);
+
+    Ok(files)
+}
+
+const STYLE: &str = "
+body { font-family: sans-serif; background: #1b1b1b; color: #ddd; margin: 2em; }
+h2 { border-bottom: 1px solid #444; padding-bottom: 0.2em; margin-top: 2em; }
+table { border-collapse: collapse; margin: 0.5em 0 1em; }
+th, td { border: 1px solid #444; padding: 0.2em 0.6em; text-align: left; }
+th { background: #2a2a2a; }
+code { color: #9cdcfe; }
+.dim { color: #888; font-size: 0.8em; }
+.pallet { display: flex; gap: 1em; margin-bottom: 1em; flex-wrap: wrap; }
+.sub-pallet { display: flex; }
+.swatch { width: 3.2em; height: 3.2em; display: flex; align-items: flex-end; justify-content: center; \
+    font-size: 0.75em; font-family: monospace; border: 1px solid #000; }
+.metatiles { display: flex; flex-wrap: wrap; gap: 0.5em; }
+.metatiles figure { margin: 0; text-align: center; font-family: monospace; }
+.metatile { width: 32px; height: 32px; image-rendering: pixelated; margin: auto; }
+.map { position: relative; display: inline-block; }
+.map img { display: block; image-rendering: pixelated; }
+.map::after { content: ''; position: absolute; inset: 0; pointer-events: none; \
+    background-image: linear-gradient(to right, rgba(255,255,255,0.25) 1px, transparent 1px), \
+    linear-gradient(to bottom, rgba(255,255,255,0.25) 1px, transparent 1px); \
+    background-size: var(--screen-width) var(--screen-height); }
+";
+
+/// Write the swatches for the sub-pallets of a metatileset
+fn pallet_html(out: &mut String, metatileset: &Metatileset) -> std::fmt::Result {
+    writeln!(out, "<div class=\"pallet\">")?;
+    for i in 0..4 + metatileset.extra_sub_pallets.len() {
+        let title = if i < 4 {
+            format!("Sub-pallet {}", i)
+        } else {
+            format!("Extra sub-pallet {}", i - 4)
+        };
+        write!(out, "<div class=\"sub-pallet\" title=\"{}\">", title)?;
+        for idx in metatileset.sub_pallet(i) {
+            let color = nes_color(idx);
+            // Pick a readable label color for the swatch
+            let light = color.r() as u32 + color.g() as u32 + color.b() as u32 > 384;
+            write!(
+                out,
+                "<div class=\"swatch\" style=\"background: #{:02x}{:02x}{:02x}; color: {}\">${:02X}</div>",
+                color.r(),
+                color.g(),
+                color.b(),
+                if light { "#000" } else { "#fff" },
+                idx
+            )?;
+        }
+        writeln!(out, "</div>")?;
+    }
+    writeln!(out, "</div>")
+}
+
+/// Render every metatile in a metatileset into a sheet with [`SHEET_COLUMNS`] metatiles per row
+fn metatile_sheet(
+    project: &Project,
+    sources: &IndexMap<Uid<PathBuf>, IndexedImage>,
+    metatileset: &Metatileset,
+) -> RgbImage {
+    let rows = metatileset.tiles.len().max(1);
+    let rows = (0..rows).step_by(SHEET_COLUMNS).count();
+    let background = nes_color(metatileset.pallet.colors[0]);
+    let mut image = RgbImage::from_pixel(
+        (SHEET_COLUMNS * 16) as u32,
+        (rows * 16) as u32,
+        rgb(background),
+    );
+    for (i, tile) in metatileset.tiles.values().enumerate() {
+        let (x, y) = ((i % SHEET_COLUMNS) * 16, (i / SHEET_COLUMNS) * 16);
+        draw_metatile(&mut image, x, y, project, sources, metatileset, tile);
+    }
+    image
+}
+
+/// Render the whole level map, with empty cells in the background color
+fn level_map(
+    project: &Project,
+    sources: &IndexMap<Uid<PathBuf>, IndexedImage>,
+    level: &Level,
+    metatileset: Option<&Metatileset>,
+) -> RgbImage {
+    let margin = level.margin;
+    let background = metatileset
+        .map(|x| nes_color(x.pallet.colors[0]))
+        .unwrap_or(Color32::BLACK);
+    let mut image = RgbImage::from_pixel(
+        margin.width().max(0) as u32 * 16,
+        margin.height().max(0) as u32 * 16,
+        rgb(background),
+    );
+
+    let metatileset = match metatileset {
+        Some(x) => x,
+        None => return image,
+    };
+    for (&(x, y), level_tile) in level.tiles.iter() {
+        if x < -margin.left || x >= margin.right || y < -margin.top || y >= margin.bottom {
+            continue;
+        }
+        let tile = metatileset
+            .tiles
+            .get_full(&level_tile.metatileset_tile_id)
+            .filter(|(idx, _, _)| *idx < EMPTY_METATILE as usize);
+        if let Some((_, _, tile)) = tile {
+            let x = (x + margin.left) as usize * 16;
+            let y = (y + margin.top) as usize * 16;
+            draw_metatile(&mut image, x, y, project, sources, metatileset, tile);
+        }
+    }
+    image
+}
+
+fn draw_metatile(
+    image: &mut RgbImage,
+    x: usize,
+    y: usize,
+    project: &Project,
+    sources: &IndexMap<Uid<PathBuf>, IndexedImage>,
+    metatileset: &Metatileset,
+    tile: &MetatilesetTile,
+) {
+    let metatile = project.metatiles.get(&tile.metatile_id);
+    let colors = metatileset.sub_pallet(tile.sub_pallet_idx).map(nes_color);
+    for i in 0..4 {
+        let pixels = tile_pixels(sources, metatile.and_then(|x| x.tiles[i].as_ref()));
+        let (tile_x, tile_y) = (x + (i % 2) * 8, y + (i / 2) * 8);
+        for (j, color_idx) in pixels.iter().enumerate() {
+            image.put_pixel(
+                (tile_x + j % 8) as u32,
+                (tile_y + j / 8) as u32,
+                rgb(colors[*color_idx as usize & 3]),
+            );
+        }
+    }
+}
+
+fn zone_name(level: &Level, id: Uid<Zone>) -> &str {
+    level
+        .zones
+        .get(&id)
+        .map(|x| x.name.as_str())
+        .unwrap_or("(missing)")
+}
+
+fn nes_color(idx: u32) -> Color32 {
+    NES_PALLET[idx.min(63) as usize]
+}
+
+fn rgb(color: Color32) -> Rgb<u8> {
+    Rgb([color.r(), color.g(), color.b()])
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value {
+        "Yes"
+    } else {
+        "No"
+    }
+}
+
+/// Escape text for use in HTML content and attributes
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
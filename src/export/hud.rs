@@ -0,0 +1,106 @@
+//! Static HUD sections of level maps
+//!
+//! Levels can mark metatile rows at the top and bottom of their map as a HUD, which many engines
+//! draw once into its own part of a nametable instead of scrolling it with the playfield. Those rows
+//! are left out of the level's map table, and the first screen width of each section is exported
+//! as two tables:
+//!
+//! # Nametable
+//!
+//! The pattern table index of each 8x8 tile, 32 tiles per row, ready to be copied to the PPU.
+//! Empty cells use tile $00.
+//!
+//! # Attributes
+//!
+//! Attribute bytes for the section, 8 per row, each covering 2x2 metatiles starting from the
+//! top-left corner of the section. Like the PPU's attribute table, the sub-pallet of the top-left
+//! metatile is in the lowest two bits, followed by the top-right, bottom-left, and bottom-right.
+
+use crate::constants::SCREEN_WIDTH_METATILES as SCREEN_WIDTH;
+
+use super::{attributes::attribute_byte, Table, TableKind};
+
+/// Build the nametable and attribute tables for one HUD section of a level, where `section` is
+/// `top` or `bottom`
+///
+/// `map` holds the metatile index of each cell in the section, `width` cells per row.
+/// `metatiles` holds the four pattern table indexes of each metatile, and `sub_pallets` holds the
+/// sub-pallet of each metatile.
+pub fn section_tables(
+    symbol: &str,
+    level_name: &str,
+    section: &str,
+    map: &[u8],
+    width: usize,
+    metatiles: &[[u8; 4]],
+    sub_pallets: &[u8],
+) -> Vec<Table> {
+    let width = width.max(1);
+    let rows = map.len() / width;
+    // The metatile index of a cell, if it is in the section
+    let cell = |x: usize, y: usize| (x < width && y < rows).then(|| map[y * width + x] as usize);
+
+    let mut nametable = Vec::with_capacity(rows * SCREEN_WIDTH * 4);
+    for y in 0..rows {
+        for tile_row in 0..2 {
+            for x in 0..SCREEN_WIDTH {
+                let tiles = cell(x, y)
+                    .and_then(|idx| metatiles.get(idx))
+                    .copied()
+                    .unwrap_or_default();
+                nametable.extend([tiles[tile_row * 2], tiles[tile_row * 2 + 1]]);
+            }
+        }
+    }
+
+    let mut attributes = Vec::new();
+    for y in (0..rows).step_by(2) {
+        for x in (0..SCREEN_WIDTH).step_by(2) {
+            let sub_pallet = |dx: usize, dy: usize| {
+                cell(x + dx, y + dy)
+                    .and_then(|idx| sub_pallets.get(idx))
+                    .copied()
+                    .unwrap_or(0)
+            };
+            attributes.push(attribute_byte([
+                sub_pallet(0, 0),
+                sub_pallet(1, 0),
+                sub_pallet(0, 1),
+                sub_pallet(1, 1),
+            ]));
+        }
+    }
+
+    vec![
+        Table {
+            kind: TableKind::Hud,
+            name: format!("{}_hud_{}", symbol, section),
+            description: format!(
+                "Nametable for the {} HUD of level `{}`: {} rows of {} tiles, $00 is empty",
+                section,
+                level_name,
+                rows * 2,
+                SCREEN_WIDTH * 2
+            ),
+            data: nametable,
+            row_len: SCREEN_WIDTH * 2,
+            row_comments: Vec::new(),
+            segment: None,
+        },
+        Table {
+            kind: TableKind::Attributes,
+            name: format!("{}_hud_{}_attributes", symbol, section),
+            description: format!(
+                "Attributes for the {} HUD of level `{}`: {} bytes per row, each covering 2x2 \
+                metatiles",
+                section,
+                level_name,
+                SCREEN_WIDTH / 2
+            ),
+            data: attributes,
+            row_len: SCREEN_WIDTH / 2,
+            row_comments: Vec::new(),
+            segment: None,
+        },
+    ]
+}
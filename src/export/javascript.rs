@@ -0,0 +1,69 @@
+//! JavaScript module export, for web-based viewers and other tools
+
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+use crate::project::ExportTarget;
+
+use super::ExportData;
+
+pub fn write(
+    data: &ExportData,
+    target: &ExportTarget,
+    out_dir: &Path,
+    file_stem: &str,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let mut out = String::new();
+
+    if target.comments {
+        writeln!(
+            out,
+            "// Generated by NESImg, export target `{}`",
+            target.name
+        )?;
+        writeln!(out)?;
+    }
+
+    for group in &data.constants {
+        if target.comments {
+            writeln!(out, "// {}", group.description)?;
+        }
+        for (name, value) in &group.constants {
+            writeln!(out, "export const {} = 0x{:02X};", name, value)?;
+        }
+        writeln!(out)?;
+    }
+
+    for table in &data.tables {
+        if target.comments {
+            writeln!(out, "// {}", table.description)?;
+        }
+        writeln!(out, "export const {} = new Uint8Array([", table.name)?;
+        for row in table.data.chunks(table.row_len.max(1)) {
+            let bytes = row
+                .iter()
+                .map(|x| format!("0x{:02X}", x))
+                .collect::<Vec<_>>()
+                .join(", ");
+            writeln!(out, "  {},", bytes)?;
+        }
+        writeln!(out, "]);")?;
+        writeln!(out)?;
+    }
+
+    // A lookup of every table by name, for scripts that process all of them
+    writeln!(out, "export const tables = {{")?;
+    for table in &data.tables {
+        writeln!(out, "  {},", table.name)?;
+    }
+    writeln!(out, "}};")?;
+
+    let path = out_dir.join(format!("{}.js", file_stem));
+    std::fs::write(&path, out).with_context(|| format!("Write {}", path.display()))?;
+
+    Ok(vec![path])
+}
@@ -0,0 +1,73 @@
+//! The records of the metatile tables, laid out by the export target's [`MetatileLayout`]
+
+use crate::project::{MetatileField, MetatileLayout, MetatilesetTile};
+
+/// Add the record of one metatile to `out`, given the pattern table indexes of its top-left,
+/// top-right, bottom-left, and bottom-right tiles
+pub fn write_record(
+    layout: &MetatileLayout,
+    metatileset_tile: &MetatilesetTile,
+    indexes: [u8; 4],
+    out: &mut Vec<u8>,
+) {
+    let start = out.len();
+    let sub_pallet = metatileset_tile.sub_pallet_idx.min(3) as u8;
+    for field in &layout.fields {
+        match field {
+            MetatileField::TileIndexes => out.extend(indexes),
+            MetatileField::ColumnTileIndexes => {
+                out.extend([indexes[0], indexes[2], indexes[1], indexes[3]])
+            }
+            MetatileField::SubPallet => out.push(sub_pallet),
+            MetatileField::AttributeBits => out.push(sub_pallet * 0x55),
+            MetatileField::Properties => out.push(metatileset_tile.properties),
+            MetatileField::Padding => out.push(0),
+        }
+    }
+    out.resize(start + layout.record_size(), 0);
+}
+
+/// Describe the fields of the records, for the table comments
+pub fn describe(layout: &MetatileLayout) -> String {
+    let mut fields = layout.fields.iter().map(|field| match field {
+        MetatileField::TileIndexes => {
+            "top-left, top-right, bottom-left, and bottom-right tile indexes"
+        }
+        MetatileField::ColumnTileIndexes => {
+            "top-left, bottom-left, top-right, and bottom-right tile indexes"
+        }
+        MetatileField::SubPallet => "the sub-pallet index",
+        MetatileField::AttributeBits => "the sub-pallet index in every attribute bit pair",
+        MetatileField::Properties => "the property byte",
+        MetatileField::Padding => "a zero byte",
+    });
+
+    let mut description = match fields.next() {
+        Some(first) => first.to_owned(),
+        None => "empty records".to_owned(),
+    };
+    let rest = fields.collect::<Vec<_>>();
+    if !rest.is_empty() {
+        description = format!("{}, followed by {}", description, rest.join(", "));
+    }
+    if layout.record_size() > layout.fields_size() {
+        description = format!("{}, padded to {} bytes", description, layout.record_size());
+    }
+    description
+}
+
+/// A warning about a layout that probably isn't what was meant
+pub fn warning(layout: &MetatileLayout) -> Option<String> {
+    if layout.record_size() == 0 {
+        Some("The metatile records have no fields, so the metatile tables are empty".into())
+    } else if layout.record_size != 0 && (layout.record_size as usize) < layout.fields_size() {
+        Some(format!(
+            "The metatile record fields take {} bytes, which is more than the record size of {}, \
+            so the records weren't padded",
+            layout.fields_size(),
+            layout.record_size
+        ))
+    } else {
+        None
+    }
+}
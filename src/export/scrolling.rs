@@ -0,0 +1,208 @@
+//! Nametable columns and rows for scrolling engines
+//!
+//! Engines that scroll horizontally write a new column of tiles into the nametable just off of
+//! the edge of the screen as the camera moves, and engines that scroll vertically write a new row.
+//! Each level can be exported as those columns or rows, in the order the engine feeds them to the
+//! PPU, with the attribute bits that change along with them. Only the playfield is exported, so
+//! the HUD rows are left out, and empty cells use tile $00 and sub-pallet 0. Every record in a
+//! table is the same size, so the record for a column or row is found by multiplying its index.
+//!
+//! # Columns
+//!
+//! For each metatile column, left to right, its two tile columns, each with the pattern table
+//! index of every tile from the top of the playfield to the bottom. A playfield that is one
+//! screen high gives 30 tiles per column, ready to copy to the PPU with the +32 address increment.
+//!
+//! The attribute table has one record per metatile column, with 8 attribute bytes from the top
+//! of the screen to the bottom for each screen the playfield is high. Only the bits of the
+//! metatiles in the column are set: the column's sub-pallets are in bits 0-1 and 4-5 for even
+//! columns and bits 2-3 and 6-7 for odd columns. The game masks the old attribute byte with `$CC`
+//! for even columns or `$33` for odd columns and ORs in the new bits.
+//!
+//! # Rows
+//!
+//! For each metatile row, top to bottom, its two tile rows, each with the pattern table index of
+//! every tile from the left of the playfield to the right.
+//!
+//! The attribute table has one record per metatile row, with 8 attribute bytes from the left of
+//! the screen to the right for each screen the playfield is wide. Screens are 15 metatiles high,
+//! so the attribute rows start over with each screen. Only the bits of the metatiles in the row
+//! are set: bits 0-3 for rows at an even position in their screen, and bits 4-7 for odd rows. The
+//! game masks the old attribute byte with `$F0` for even rows or `$0F` for odd rows.
+
+use crate::{
+    constants::{SCREEN_HEIGHT_METATILES, SCREEN_WIDTH_METATILES},
+    project::MapStreaming,
+};
+
+use super::{Table, TableKind, EMPTY_METATILE};
+
+/// The number of attribute bytes across or down one screen
+const SCREEN_ATTRIBUTE_BYTES: usize = 8;
+
+/// Build the column or row tables for a level's playfield
+///
+/// `map` holds the metatile index of each cell, `width` cells per row. `metatiles` holds the four
+/// pattern table indexes of each metatile, and `sub_pallets` holds the sub-pallet of each
+/// metatile.
+pub fn level_tables(
+    symbol: &str,
+    level_name: &str,
+    map: &[u8],
+    width: usize,
+    metatiles: &[[u8; 4]],
+    sub_pallets: &[u8],
+    streaming: MapStreaming,
+) -> Vec<Table> {
+    let width = width.max(1);
+    let height = map.len() / width;
+    let cell = |x: usize, y: usize| {
+        let metatile = map[y * width + x];
+        (metatile != EMPTY_METATILE).then(|| metatile as usize)
+    };
+    // The pattern table indexes of a cell, in the order top-left, top-right, bottom-left,
+    // bottom-right
+    let tiles = |x: usize, y: usize| {
+        cell(x, y)
+            .and_then(|idx| metatiles.get(idx))
+            .copied()
+            .unwrap_or_default()
+    };
+    let sub_pallet = |x: usize, y: usize| {
+        cell(x, y)
+            .and_then(|idx| sub_pallets.get(idx))
+            .copied()
+            .unwrap_or(0)
+    };
+
+    match streaming {
+        MapStreaming::None => Vec::new(),
+        MapStreaming::Columns => {
+            let mut columns = Vec::with_capacity(width * height * 4);
+            for x in 0..width {
+                for tile_x in 0..2 {
+                    for y in 0..height {
+                        let tiles = tiles(x, y);
+                        columns.extend([tiles[tile_x], tiles[2 + tile_x]]);
+                    }
+                }
+            }
+
+            let screens_high = (0..height).step_by(SCREEN_HEIGHT_METATILES).len();
+            let mut attributes = Vec::new();
+            for x in 0..width {
+                for screen_y in (0..height).step_by(SCREEN_HEIGHT_METATILES) {
+                    for y in (0..SCREEN_HEIGHT_METATILES).step_by(2) {
+                        // The bottom half of the last row is off of the screen
+                        let bits = |dy: usize| {
+                            let y = screen_y + y + dy;
+                            if y - screen_y < SCREEN_HEIGHT_METATILES && y < height {
+                                sub_pallet(x, y) & 3
+                            } else {
+                                0
+                            }
+                        };
+                        let shift = (x % 2) * 2;
+                        attributes.push(bits(0) << shift | bits(1) << (shift + 4));
+                    }
+                }
+            }
+
+            vec![
+                Table {
+                    kind: TableKind::Scrolling,
+                    name: format!("{}_columns", symbol),
+                    description: format!(
+                        "Nametable columns for level `{}`: two columns of {} tiles for each of \
+                        its {} metatile columns, left to right, $00 is empty",
+                        level_name,
+                        height * 2,
+                        width
+                    ),
+                    data: columns,
+                    row_len: (height * 2).max(1),
+                    row_comments: Vec::new(),
+                    segment: None,
+                },
+                Table {
+                    kind: TableKind::Scrolling,
+                    name: format!("{}_column_attributes", symbol),
+                    description: format!(
+                        "Attribute bits for each metatile column of level `{}`: {} bytes, top to \
+                        bottom. Even columns use bits 0-1 and 4-5 and odd columns use bits 2-3 \
+                        and 6-7",
+                        level_name,
+                        screens_high * SCREEN_ATTRIBUTE_BYTES
+                    ),
+                    data: attributes,
+                    row_len: (screens_high * SCREEN_ATTRIBUTE_BYTES).max(1),
+                    row_comments: Vec::new(),
+                    segment: None,
+                },
+            ]
+        }
+        MapStreaming::Rows => {
+            let mut rows = Vec::with_capacity(width * height * 4);
+            for y in 0..height {
+                for tile_y in 0..2 {
+                    for x in 0..width {
+                        let tiles = tiles(x, y);
+                        rows.extend([tiles[tile_y * 2], tiles[tile_y * 2 + 1]]);
+                    }
+                }
+            }
+
+            let screens_wide = (0..width).step_by(SCREEN_WIDTH_METATILES).len();
+            let mut attributes = Vec::new();
+            for y in 0..height {
+                let shift = (y % SCREEN_HEIGHT_METATILES % 2) * 4;
+                for screen_x in (0..width).step_by(SCREEN_WIDTH_METATILES) {
+                    for x in (0..SCREEN_WIDTH_METATILES).step_by(2) {
+                        let bits = |dx: usize| {
+                            let x = screen_x + x + dx;
+                            if x < width {
+                                sub_pallet(x, y) & 3
+                            } else {
+                                0
+                            }
+                        };
+                        attributes.push((bits(0) | bits(1) << 2) << shift);
+                    }
+                }
+            }
+
+            vec![
+                Table {
+                    kind: TableKind::Scrolling,
+                    name: format!("{}_rows", symbol),
+                    description: format!(
+                        "Nametable rows for level `{}`: two rows of {} tiles for each of its {} \
+                        metatile rows, top to bottom, $00 is empty",
+                        level_name,
+                        width * 2,
+                        height
+                    ),
+                    data: rows,
+                    row_len: width * 2,
+                    row_comments: Vec::new(),
+                    segment: None,
+                },
+                Table {
+                    kind: TableKind::Scrolling,
+                    name: format!("{}_row_attributes", symbol),
+                    description: format!(
+                        "Attribute bits for each metatile row of level `{}`: {} bytes, left to \
+                        right. Rows at an even position in their screen use bits 0-3 and odd \
+                        rows use bits 4-7",
+                        level_name,
+                        screens_wide * SCREEN_ATTRIBUTE_BYTES
+                    ),
+                    data: attributes,
+                    row_len: screens_wide * SCREEN_ATTRIBUTE_BYTES,
+                    row_comments: Vec::new(),
+                    segment: None,
+                },
+            ]
+        }
+    }
+}
@@ -0,0 +1,192 @@
+//! Tile streaming schedules for games that copy tiles into CHR-RAM as the player moves between
+//! screens
+//!
+//! Levels are split into screens of 16x15 metatiles, starting from the top-left corner of the
+//! level, and the screens are numbered in row-major order. Every table has a matching `_offsets`
+//! table holding the little-endian, 16 bit offset of each screen's entry in the table.
+//!
+//! # Tiles Needed
+//!
+//! For each screen, a count byte followed by the pattern table indexes of the tiles that the
+//! screen uses, in ascending order.
+//!
+//! # Transfer Schedule
+//!
+//! For each screen, a frame count byte, followed by that many frames. Each frame is a count byte
+//! followed by the pattern table indexes of the tiles to copy during one NMI. The number of tiles
+//! in a frame is limited by the target's NMI transfer budget, 16 bytes per tile.
+//!
+//! Each tile is copied from the exported pattern table to the same index in CHR-RAM.
+
+use std::collections::BTreeSet;
+
+use crate::constants::{
+    SCREEN_HEIGHT_METATILES as SCREEN_HEIGHT, SCREEN_WIDTH_METATILES as SCREEN_WIDTH,
+};
+
+use super::{Table, TableKind, EMPTY_METATILE};
+
+/// The number of bytes in one tile of a pattern table
+const TILE_BYTES: u16 = 16;
+
+/// The most tiles a screen's entry can list, since the count is one byte
+const MAX_SCREEN_TILES: usize = 255;
+
+/// The streaming tables for one level
+pub struct LevelStreaming {
+    pub tables: Vec<Table>,
+    /// A summary of how much needs to be streamed for the level
+    pub stats: String,
+    /// Screens whose tiles didn't all fit in their entries
+    pub warnings: Vec<String>,
+}
+
+/// Build the tiles needed and transfer schedule tables for a level map
+///
+/// `map` holds the metatile index of each cell, `width` cells per row, and `metatiles` holds the
+/// four pattern table indexes of each metatile.
+pub fn level_tables(
+    symbol: &str,
+    level_name: &str,
+    map: &[u8],
+    width: usize,
+    metatiles: &[[u8; 4]],
+    nmi_budget: u16,
+) -> LevelStreaming {
+    let width = width.max(1);
+    let height = map.len() / width;
+    // Partial screens at the right and bottom edges count as whole screens
+    let screens_wide = (0..width).step_by(SCREEN_WIDTH).count();
+    let screens_high = (0..height).step_by(SCREEN_HEIGHT).count();
+    let tiles_per_frame = (nmi_budget / TILE_BYTES).max(1) as usize;
+
+    let mut screens = Vec::with_capacity(screens_wide * screens_high);
+    let mut warnings = Vec::new();
+    for screen_y in 0..screens_high {
+        for screen_x in 0..screens_wide {
+            let mut tiles = BTreeSet::new();
+            for y in screen_y * SCREEN_HEIGHT..((screen_y + 1) * SCREEN_HEIGHT).min(height) {
+                for x in screen_x * SCREEN_WIDTH..((screen_x + 1) * SCREEN_WIDTH).min(width) {
+                    let metatile = map[y * width + x];
+                    if metatile == EMPTY_METATILE {
+                        continue;
+                    }
+                    if let Some(chr) = metatiles.get(metatile as usize) {
+                        tiles.extend(chr.iter().copied());
+                    }
+                }
+            }
+            // A screen using every tile in the pattern table doesn't fit in the count byte
+            if tiles.len() > MAX_SCREEN_TILES {
+                warnings.push(format!(
+                    "Screen ({}, {}) of level `{}` uses all {} tiles of the pattern table, but \
+                    the streaming tables can only list {}, so tile ${:02X} isn't streamed for it",
+                    screen_x,
+                    screen_y,
+                    level_name,
+                    tiles.len(),
+                    MAX_SCREEN_TILES,
+                    tiles.iter().last().copied().unwrap_or_default()
+                ));
+            }
+            screens.push(
+                tiles
+                    .into_iter()
+                    .take(MAX_SCREEN_TILES)
+                    .collect::<Vec<u8>>(),
+            );
+        }
+    }
+
+    let mut needed = Vec::new();
+    let mut needed_offsets = Vec::with_capacity(screens.len() * 2);
+    let mut schedule = Vec::new();
+    let mut schedule_offsets = Vec::with_capacity(screens.len() * 2);
+    for tiles in &screens {
+        needed_offsets.extend((needed.len() as u16).to_le_bytes());
+        needed.push(tiles.len() as u8);
+        needed.extend(tiles);
+
+        schedule_offsets.extend((schedule.len() as u16).to_le_bytes());
+        let frames = tiles.chunks(tiles_per_frame).collect::<Vec<_>>();
+        schedule.push(frames.len() as u8);
+        for frame in frames {
+            schedule.push(frame.len() as u8);
+            schedule.extend(frame);
+        }
+    }
+
+    let layout = format!(
+        "{} screens, {} wide and {} high, in row-major order",
+        screens.len(),
+        screens_wide,
+        screens_high
+    );
+    let most_tiles = screens.iter().map(|x| x.len()).max().unwrap_or(0);
+    let most_frames = screens
+        .iter()
+        .map(|x| x.chunks(tiles_per_frame).count())
+        .max()
+        .unwrap_or(0);
+
+    LevelStreaming {
+        tables: vec![
+            Table {
+                kind: TableKind::Streaming,
+                name: format!("{}_screen_tiles", symbol),
+                description: format!(
+                    "Tiles needed by each screen of level `{}`: {}. Each screen is a tile count \
+                    followed by the pattern table indexes",
+                    level_name, layout
+                ),
+                data: needed,
+                row_len: 16,
+                row_comments: Vec::new(),
+                segment: None,
+            },
+            Table {
+                kind: TableKind::Streaming,
+                name: format!("{}_screen_tiles_offsets", symbol),
+                description: format!(
+                    "Little-endian offset of each screen in `{}_screen_tiles`",
+                    symbol
+                ),
+                data: needed_offsets,
+                row_len: 2,
+                row_comments: Vec::new(),
+                segment: None,
+            },
+            Table {
+                kind: TableKind::Streaming,
+                name: format!("{}_chr_schedule", symbol),
+                description: format!(
+                    "Tile transfer schedule for level `{}`: {}. Each screen is a frame count \
+                    followed by the frames, and each frame is a tile count followed by the \
+                    pattern table indexes to copy during one NMI, up to {} tiles",
+                    level_name, layout, tiles_per_frame
+                ),
+                data: schedule,
+                row_len: 16,
+                row_comments: Vec::new(),
+                segment: None,
+            },
+            Table {
+                kind: TableKind::Streaming,
+                name: format!("{}_chr_schedule_offsets", symbol),
+                description: format!(
+                    "Little-endian offset of each screen in `{}_chr_schedule`",
+                    symbol
+                ),
+                data: schedule_offsets,
+                row_len: 2,
+                row_comments: Vec::new(),
+                segment: None,
+            },
+        ],
+        stats: format!(
+            "Level `{}`: {}, needing up to {} tiles, streamed in up to {} frames of {} tiles",
+            level_name, layout, most_tiles, most_frames, tiles_per_frame
+        ),
+        warnings,
+    }
+}
@@ -0,0 +1,82 @@
+//! Prefixes and naming conventions for exported symbols
+//!
+//! Tables are named in snake_case and constants in UPPER_CASE while the export is built. Once
+//! every table is in place, the target's prefix is added to all of the names and the table names
+//! are rewritten in its naming convention. Constants stay upper-case, the way they are written in
+//! every format.
+
+use std::collections::HashSet;
+
+use crate::project::SymbolCase;
+
+use super::ExportData;
+
+/// Add `prefix` to the names of the tables and constants, and write the table names in `case`
+pub fn apply_style(data: &mut ExportData, prefix: &str, case: SymbolCase) {
+    let mut names = HashSet::new();
+    for table in &mut data.tables {
+        table.name = unique_styled(&mut names, &table.name, prefix, case);
+    }
+
+    if prefix.is_empty() {
+        return;
+    }
+    for group in &mut data.constants {
+        for (name, _) in &mut group.constants {
+            *name = styled_symbol(name, prefix, SymbolCase::Upper);
+        }
+    }
+}
+
+/// Style a snake_case or upper-case `symbol`, adding a number to it if the styled name has
+/// already been used. Different snake_case names can be the same in camelCase, like `level_1`
+/// and `level1`.
+fn unique_styled(
+    used: &mut HashSet<String>,
+    symbol: &str,
+    prefix: &str,
+    case: SymbolCase,
+) -> String {
+    let mut styled = styled_symbol(symbol, prefix, case);
+    let mut i = 2;
+    while !used.insert(styled.clone()) {
+        styled = styled_symbol(&format!("{}_{}", symbol, i), prefix, case);
+        i += 1;
+    }
+    styled
+}
+
+/// Join the words of `prefix` and `symbol` in the naming convention `case`
+pub fn styled_symbol(symbol: &str, prefix: &str, case: SymbolCase) -> String {
+    let words = prefix
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .chain(symbol.split('_'))
+        .filter(|x| !x.is_empty())
+        .map(|x| x.to_ascii_lowercase())
+        .collect::<Vec<_>>();
+
+    let styled = match case {
+        SymbolCase::Snake => words.join("_"),
+        SymbolCase::Upper => words.join("_").to_ascii_uppercase(),
+        SymbolCase::Camel => {
+            let mut styled = String::new();
+            for (i, word) in words.iter().enumerate() {
+                if i == 0 {
+                    styled.push_str(word);
+                } else {
+                    styled.push_str(&word[..1].to_ascii_uppercase());
+                    styled.push_str(&word[1..]);
+                }
+            }
+            styled
+        }
+    };
+
+    if styled.is_empty() {
+        "unnamed".into()
+    } else if styled.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", styled)
+    } else {
+        styled
+    }
+}
@@ -0,0 +1,127 @@
+//! Tables that trace exported tiles back to the source images they came from
+//!
+//! These tables aren't needed by games. They are for external tools and debuggers that need to
+//! find where a byte in a map or pattern table came from. Each metatileset gets two tables:
+//!
+//! # Metatile CHR
+//!
+//! Six bytes for each metatile in the metatileset, in the same order as the values in the level
+//! maps: the index of the metatile in the project's metatile list, as a little-endian 16-bit
+//! number, or $FFFF if it is missing or doesn't fit, followed by its top-left, top-right,
+//! bottom-left, and bottom-right pattern table indexes.
+//!
+//! # Tile sources
+//!
+//! Seven bytes for every source image tile used by the metatileset, sorted by source, then row,
+//! then column: the index of the source image in the project's source list, and the x and y pixel
+//! position of the tile in the image, all as little-endian 16-bit numbers, and the pattern table
+//! index the tile was exported to. Tiles with the same pixels share a pattern table index, so several
+//! source tiles may point to the same index.
+
+use std::collections::BTreeMap;
+
+use crate::project::{Metatileset, Project, Tile};
+
+use super::{Table, TableKind};
+
+/// The metatile index written for metatiles that are missing, or whose index doesn't fit
+const NO_METATILE: u16 = 0xFFFF;
+
+/// Build the source tracing tables for a metatileset
+///
+/// `chr_indexes` holds the four pattern table indexes of each metatile in the metatileset.
+pub fn metatileset_tables(
+    symbol: &str,
+    metatileset: &Metatileset,
+    project: &Project,
+    chr_indexes: &[[u8; 4]],
+    warnings: &mut Vec<String>,
+) -> Vec<Table> {
+    let mut metatile_chr = Vec::with_capacity(metatileset.tiles.len() * 6);
+    // The pattern table index of each source tile, by source index and pixel position
+    let mut tile_sources = BTreeMap::<(u16, u16, u16), u8>::new();
+    let mut unnumbered_metatiles = 0;
+
+    for (metatileset_tile, indexes) in metatileset.tiles.values().zip(chr_indexes) {
+        let metatile_idx = match project
+            .metatiles
+            .get_index_of(&metatileset_tile.metatile_id)
+        {
+            Some(idx) if idx < NO_METATILE as usize => idx as u16,
+            Some(_) => {
+                unnumbered_metatiles += 1;
+                NO_METATILE
+            }
+            None => NO_METATILE,
+        };
+        metatile_chr.extend(metatile_idx.to_le_bytes());
+        metatile_chr.extend(indexes);
+
+        if let Some(metatile) = project.metatiles.get(&metatileset_tile.metatile_id) {
+            for (tile, &chr_idx) in metatile.tiles.iter().zip(indexes) {
+                if let Some(key) = tile.as_ref().and_then(|x| source_key(project, x)) {
+                    tile_sources.entry(key).or_insert(chr_idx);
+                }
+            }
+        }
+    }
+
+    let sources = tile_sources
+        .into_iter()
+        .flat_map(|((source, y, x), chr_idx)| {
+            let [source_lo, source_hi] = source.to_le_bytes();
+            let [x_lo, x_hi] = x.to_le_bytes();
+            let [y_lo, y_hi] = y.to_le_bytes();
+            [source_lo, source_hi, x_lo, x_hi, y_lo, y_hi, chr_idx]
+        })
+        .collect::<Vec<_>>();
+
+    if unnumbered_metatiles > 0 {
+        warnings.push(format!(
+            "{} metatiles of metatileset `{}` come after the first {} metatiles of the project, \
+            so their index doesn't fit in the metatile CHR table and is written as ${:04X}",
+            unnumbered_metatiles, metatileset.name, NO_METATILE, NO_METATILE
+        ));
+    }
+
+    vec![
+        Table {
+            kind: TableKind::TileSources,
+            name: format!("{}_metatile_chr", symbol),
+            description: format!(
+                "Metatiles of metatileset `{}`: the 16-bit project metatile index, $FFFF if \
+                missing or too large, followed by the top-left, top-right, bottom-left, and bottom-right \
+                pattern table indexes",
+                metatileset.name
+            ),
+            data: metatile_chr,
+            row_len: 6,
+            row_comments: Vec::new(),
+            segment: None,
+        },
+        Table {
+            kind: TableKind::TileSources,
+            name: format!("{}_tile_sources", symbol),
+            description: format!(
+                "Source tiles of metatileset `{}`: the 16-bit project source index and x and y \
+                pixel position of the tile in the source, and its pattern table index",
+                metatileset.name
+            ),
+            data: sources,
+            row_len: 7,
+            row_comments: Vec::new(),
+            segment: None,
+        },
+    ]
+}
+
+/// Get the source index and pixel position of a tile, sorted by row before column
+fn source_key(project: &Project, tile: &Tile) -> Option<(u16, u16, u16)> {
+    let source = project.sources.get_index_of(&tile.source_id)?;
+
+    Some((
+        source.try_into().ok()?,
+        tile.y.saturating_mul(8),
+        tile.x.saturating_mul(8),
+    ))
+}
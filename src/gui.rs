@@ -6,22 +6,49 @@ use native_dialog::FileDialog;
 use once_cell::sync::Lazy;
 use path_absolutize::Absolutize;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, io::Read, path::Path, time::Instant};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Instant,
+};
 use watch::WatchReceiver;
 
 use tracing as trc;
 
+mod accessibility;
+mod asset_packs;
+mod background;
+mod compare;
 mod components;
+mod export;
+mod gamepad;
 mod keyboard_shortcuts;
+mod memory_usage;
+mod mockup_import;
+mod open_events;
+mod preview_pallet;
+mod problems;
+mod project_settings;
 mod project_state;
+mod rescue;
+mod savestate_import;
+mod single_instance;
+mod source_paths;
+mod source_texture;
 mod tabs;
+mod tour;
 mod util;
+mod vcs_status;
 
 use components::{send_error_notification, show_notifications};
 use keyboard_shortcuts::KeyboardShortcut;
-use tabs::NesimgGuiTab;
+use tabs::{NesimgGuiTab, ProjectLayout};
 
-use crate::{cli::GuiArgs, project::Project};
+use crate::{
+    cli::GuiArgs,
+    project::{Project, BINARY_PROJECT_EXTENSION},
+    vcs,
+};
 
 use self::{
     components::send_info_notification,
@@ -31,6 +58,16 @@ use self::{
 
 /// Run the GUI
 pub fn run_gui(args: GuiArgs) {
+    // Open the project in the window that is already running, if there is one
+    if let (false, Some(path)) = (args.new_instance, &args.project) {
+        if path.is_file() && single_instance::forward_to_running_instance(path) {
+            trc::debug!("Opened {} in the running NESImg window", path.display());
+            return;
+        }
+    }
+
+    rescue::install_panic_hook();
+
     let native_options = eframe::NativeOptions {
         renderer: eframe::Renderer::Wgpu,
         icon_data: Some(load_icon()),
@@ -55,6 +92,10 @@ pub struct NesimgGui {
     /// The list of tab implementations and their names
     #[serde(skip)]
     tabs: Vec<(String, Box<dyn NesimgGuiTab>)>,
+    /// The names of the tabs in the order they were arranged in, or empty for the default order
+    tab_order: Vec<String>,
+    /// The names of the tabs that are hidden from the tab list
+    hidden_tabs: Vec<String>,
 
     /// Dark mode enabled state
     dark_mode: bool,
@@ -65,6 +106,77 @@ pub struct NesimgGui {
     // The UI scale
     pixels_per_point: f32,
 
+    /// Whether or not to show the project settings window
+    #[serde(skip)]
+    show_project_settings: bool,
+    /// Whether to show the export history window
+    #[serde(skip)]
+    show_export_history: bool,
+
+    /// Whether or not to show the memory usage window
+    #[serde(skip)]
+    show_memory_usage: bool,
+
+    /// Whether to free the textures of source images that haven't been shown for a while
+    auto_trim_textures: bool,
+
+    /// Whether to smooth the source image previews when they are scaled up
+    smooth_scaling: bool,
+
+    /// Whether connected gamepads can be used to get around the maps
+    gamepad_navigation: bool,
+
+    /// Whether the widget that gets keyboard focus is read out loud
+    screen_reader: bool,
+
+    /// The colors used to show NES colors
+    preview_pallet: preview_pallet::PreviewPallet,
+
+    /// The window used to pick and tune the preview pallet
+    #[serde(skip)]
+    preview_pallet_window: preview_pallet::PreviewPalletWindow,
+    /// Whether to show the window listing pallet problems
+    #[serde(skip)]
+    show_problems: bool,
+
+    /// The window comparing the open project with another project file
+    #[serde(skip)]
+    compare_window: compare::CompareWindow,
+
+    /// The window that turns a mockup image into project data
+    #[serde(skip)]
+    mockup_import_window: mockup_import::MockupImportWindow,
+    /// The window that creates a source and metatileset from an emulator savestate
+    #[serde(skip)]
+    savestate_import_window: savestate_import::SavestateImportWindow,
+    /// The window offering to fix the source paths of the project
+    #[serde(skip)]
+    source_paths_window: source_paths::SourcePathsWindow,
+    /// The window listing the assets in the installed asset packs
+    #[serde(skip)]
+    asset_pack_browser: asset_packs::AssetPackBrowser,
+    /// The folders of the installed asset packs
+    asset_pack_dirs: Vec<PathBuf>,
+
+    /// Whether the tour has been started automatically on the first run
+    tour_offered: bool,
+
+    /// The editing context of each project that has been opened, by the path of the project file
+    project_layouts: HashMap<PathBuf, ProjectLayout>,
+
+    /// The guided tour for new users
+    #[serde(skip)]
+    tour: tour::Tour,
+
+    /// Whether the user asked to quit, and NESImg is waiting for background tasks to finish
+    #[serde(skip)]
+    quitting: bool,
+
+    /// The action waiting for the user to confirm that the project's unsaved changes can be
+    /// closed
+    #[serde(skip)]
+    confirm_close: Option<MainGuiAction>,
+
     /// The root GUI state, which will be shared with and allowed to be modified by tabs
     #[serde(skip)]
     state: RootState,
@@ -77,7 +189,29 @@ impl Default for NesimgGui {
             show_help: true,
             current_tab: "Sources".into(),
             pixels_per_point: 1.2,
+            show_project_settings: false,
+            show_export_history: false,
+            show_memory_usage: false,
+            auto_trim_textures: false,
+            smooth_scaling: false,
+            gamepad_navigation: true,
+            screen_reader: false,
+            preview_pallet: Default::default(),
+            preview_pallet_window: Default::default(),
+            show_problems: false,
+            compare_window: Default::default(),
+            mockup_import_window: Default::default(),
+            savestate_import_window: Default::default(),
+            source_paths_window: Default::default(),
+            asset_pack_browser: Default::default(),
+            asset_pack_dirs: Vec::new(),
+            tour_offered: false,
+            project_layouts: Default::default(),
+            tour: Default::default(),
+            tab_order: Vec::new(),
+            hidden_tabs: Vec::new(),
             tabs: vec![
+                ("World".into(), Box::new(tabs::world::WorldTab::default())),
                 ("Maps".into(), Box::new(tabs::maps::MapsTab::default())),
                 (
                     "Metatilesets".into(),
@@ -92,6 +226,8 @@ impl Default for NesimgGui {
                     Box::new(tabs::sources::SourcesTab::default()),
                 ),
             ],
+            quitting: false,
+            confirm_close: None,
             state: Default::default(),
         }
     }
@@ -108,6 +244,18 @@ pub struct RootState {
 
     /// Start time of the app, which can be used for calculating elapsed time for [`Undoer`]s
     start: Instant,
+
+    /// The git status of the project file
+    vcs: vcs_status::VcsIndicator,
+
+    /// The projects opened by other instances of NESImg, if this is the instance they forward to
+    forwarded_project: Option<WatchReceiver<Option<PathBuf>>>,
+
+    /// The project that was closed last, with any unsaved changes, so that it can be reopened
+    recently_closed: Option<LoadedProject>,
+
+    /// The recent exports of each project opened in this session
+    export_history: export::ExportHistory,
 }
 
 impl Default for RootState {
@@ -116,6 +264,22 @@ impl Default for RootState {
             project: None,
             loaded_project: watch::channel(None).1,
             start: Instant::now(),
+            vcs: Default::default(),
+            forwarded_project: None,
+            recently_closed: None,
+            export_history: Default::default(),
+        }
+    }
+}
+
+impl RootState {
+    /// Close the open project, keeping it as the recently closed project
+    fn close_project(&mut self) {
+        if let Some(project) = self.project.take() {
+            self.recently_closed = Some(LoadedProject {
+                data: project.data,
+                path: project.path,
+            });
         }
     }
 }
@@ -146,16 +310,142 @@ impl NesimgGui {
             }
         };
 
+        let tab_order = &gui.tab_order;
+        gui.tabs.sort_by_key(|(name, _)| {
+            tab_order
+                .iter()
+                .position(|x| x == name)
+                .unwrap_or(usize::MAX)
+        });
+
         cc.egui_ctx.set_pixels_per_point(gui.pixels_per_point);
+        preview_pallet::set_preview_pallet(&gui.preview_pallet);
+        source_texture::set_smooth_scaling(gui.smooth_scaling);
+        gamepad::set_enabled(gui.gamepad_navigation);
+        gamepad::start(&cc.egui_ctx);
+        #[cfg(feature = "screen_reader")]
+        accessibility::set_screen_reader(&cc.egui_ctx, gui.screen_reader);
+
+        if !args.new_instance {
+            gui.state.forwarded_project = single_instance::listen(&cc.egui_ctx);
+        }
+        open_events::install(&cc.egui_ctx);
 
         if let Some(path) = args.project {
             gui.state.loaded_project =
                 watch::channel(get_loaded_project(&cc.egui_ctx, &path, true)).1;
+        } else if !gui.tour_offered {
+            gui.tour.start(&cc.egui_ctx, &mut gui.state);
         }
+        gui.tour_offered = true;
 
         gui
     }
 
+    /// Whether a project is open and has changes that haven't been saved
+    fn has_unsaved_changes(&self) -> bool {
+        self.state
+            .project
+            .as_ref()
+            .map(|x| x.has_unsaved_changes())
+            .unwrap_or(false)
+    }
+
+    /// Record the editing context of the open project, so it can be restored when the project is
+    /// opened again
+    fn save_project_layout(&mut self) {
+        let project = match &self.state.project {
+            Some(project) => project,
+            None => return,
+        };
+        let layout = self
+            .project_layouts
+            .entry(project.path.clone())
+            .or_default();
+        layout.current_tab = self.current_tab.clone();
+        for (_, tab) in &self.tabs {
+            tab.save_layout(layout);
+        }
+    }
+
+    /// Restore the editing context of a project that was just loaded
+    fn load_project_layout(&mut self, path: &Path) {
+        let layout = self.project_layouts.get(path).cloned().unwrap_or_default();
+        if self
+            .tabs
+            .iter()
+            .any(|(name, _)| name == &layout.current_tab)
+        {
+            self.current_tab = layout.current_tab.clone();
+        }
+        for (_, tab) in &mut self.tabs {
+            tab.load_layout(&layout);
+        }
+    }
+
+    /// Render the list of tabs that aren't hidden, which can be dragged to rearrange them and
+    /// right-clicked to hide them
+    fn tab_list_gui(&mut self, ui: &mut Ui, tab_rects: &mut HashMap<String, egui::Rect>) {
+        // Switch away from the current tab if it was hidden
+        if self.hidden_tabs.contains(&self.current_tab) {
+            if let Some((name, _)) = self
+                .tabs
+                .iter()
+                .find(|(name, _)| !self.hidden_tabs.contains(name))
+            {
+                self.current_tab = name.clone();
+            }
+        }
+
+        let mut dragged = None;
+        let mut hide = None;
+        let shown_count = self.tabs.len() - self.hidden_tabs.len();
+        for (idx, (name, tab)) in self.tabs.iter().enumerate() {
+            if self.hidden_tabs.contains(name) {
+                continue;
+            }
+            let response = ui
+                .selectable_value(&mut self.current_tab, name.clone(), name)
+                .interact(egui::Sense::drag())
+                .on_hover_text(tab.tooltip())
+                .on_disabled_hover_text("Open project to edit")
+                .context_menu(|ui| {
+                    if ui
+                        .add_enabled(shown_count > 1, egui::Button::new("🗙 Hide Tab"))
+                        .on_hover_text("Bring it back from View ➡ Tabs")
+                        .clicked()
+                    {
+                        hide = Some(name.clone());
+                        ui.close_menu();
+                    }
+                });
+            if response.dragged() {
+                dragged = Some(idx);
+            }
+            tab_rects.insert(name.clone(), response.rect);
+        }
+
+        // Move the dragged tab to the spot of the tab under the pointer
+        if let (Some(from), Some(pos)) = (dragged, ui.input().pointer.interact_pos()) {
+            let to = self.tabs.iter().position(|(name, _)| {
+                tab_rects
+                    .get(name)
+                    .map(|rect| rect.x_range().contains(&pos.x))
+                    .unwrap_or(false)
+            });
+            if let Some(to) = to.filter(|to| *to != from) {
+                let tab = self.tabs.remove(from);
+                self.tabs.insert(to, tab);
+                self.tab_order = self.tabs.iter().map(|(name, _)| name.clone()).collect();
+            }
+            ui.output().cursor_icon = egui::CursorIcon::Grabbing;
+        }
+
+        if let Some(name) = hide {
+            self.hidden_tabs.push(name);
+        }
+    }
+
     fn toggle_dark_mode(&mut self, ui: &mut Ui) {
         if ui.visuals().dark_mode {
             self.dark_mode = false;
@@ -174,22 +464,49 @@ pub(crate) enum MainGuiAction {
     NewProject,
     OpenProject,
     SaveProject,
+    RevertProject,
+    /// Open the project at a path, such as one forwarded by another instance or opened from the
+    /// file manager
+    OpenPath(PathBuf),
+    /// Start the guided tour, which opens its example project
+    StartTour,
     Undo,
+    /// Set the UI scale to one of the [`UI_SCALE_PRESETS`], in percent
+    SetUiScale(u16),
 }
 
+/// The UI scales, in percent, that can be picked from the View menu or with a shortcut
+const UI_SCALE_PRESETS: [u16; 4] = [100, 125, 150, 200];
+
 impl MainGuiAction {
-    fn perform(&self, gui: &mut NesimgGui, ctx: &egui::Context, frame: &mut eframe::Frame) {
+    fn perform(&self, gui: &mut NesimgGui, ctx: &egui::Context) {
         #[allow(clippy::unit_arg)]
         if let Err(e) = match self {
-            MainGuiAction::Quit => Ok(frame.quit()),
-            MainGuiAction::NewProject => new_project(gui, ctx),
-            MainGuiAction::OpenProject => open_project(gui, ctx),
+            MainGuiAction::Quit
+            | MainGuiAction::NewProject
+            | MainGuiAction::OpenProject
+            | MainGuiAction::OpenPath(_)
+            | MainGuiAction::StartTour
+                if gui.has_unsaved_changes() =>
+            {
+                // Ask before closing the project, and do the action once it is confirmed
+                gui.confirm_close = Some(self.clone());
+                Ok(())
+            }
+            MainGuiAction::Quit
+            | MainGuiAction::NewProject
+            | MainGuiAction::OpenProject
+            | MainGuiAction::OpenPath(_)
+            | MainGuiAction::StartTour => self.close_project(gui, ctx),
             MainGuiAction::SaveProject => save_project(gui, ctx),
+            MainGuiAction::RevertProject => revert_project(gui, ctx),
             MainGuiAction::Undo => {
                 if let Some(project) = &mut gui.state.project {
                     if let Some(undone) = project.undoer.undo(&project.data) {
                         let mut needs_reload = false;
-                        if project.data.sources != undone.sources {
+                        if project.data.sources != undone.sources
+                            || project.data.pallet_order_sources != undone.pallet_order_sources
+                        {
                             needs_reload = true;
                         }
                         project.data = undone.clone();
@@ -201,6 +518,11 @@ impl MainGuiAction {
 
                 Ok(())
             }
+            MainGuiAction::SetUiScale(percent) => {
+                gui.pixels_per_point = *percent as f32 / 100.0;
+                ctx.set_pixels_per_point(gui.pixels_per_point);
+                Ok(())
+            }
         } {
             trc::error!("{}", e);
             send_error_notification(ctx, format!("{:#}", e));
@@ -226,22 +548,65 @@ static MAIN_GUI_SHORTCUTS: Lazy<HashMap<MainGuiAction, KeyboardShortcut>> = Lazy
         (Modifiers::COMMAND, Key::S).into(),
     );
     shortcuts.insert(MainGuiAction::Undo, (Modifiers::COMMAND, Key::Z).into());
+    // Ctrl+1 through Ctrl+9 are left for the map bookmarks
+    let preset_modifiers = Modifiers {
+        alt: true,
+        ..Modifiers::COMMAND
+    };
+    let preset_keys = [Key::Num1, Key::Num2, Key::Num3, Key::Num4];
+    for (percent, key) in UI_SCALE_PRESETS.into_iter().zip(preset_keys) {
+        shortcuts.insert(
+            MainGuiAction::SetUiScale(percent),
+            (preset_modifiers, key).into(),
+        );
+    }
 
     shortcuts
 });
 
 /// GUI implementation
 impl eframe::App for NesimgGui {
+    fn on_exit_event(&mut self) -> bool {
+        // Ask about unsaved changes first. Confirming quits through the quit window, which doesn't
+        // come back here.
+        if !self.quitting && self.has_unsaved_changes() {
+            self.confirm_close = Some(MainGuiAction::Quit);
+            return false;
+        }
+
+        // Wait for exports and dialogs to finish before closing the window
+        if background::running_tasks().is_empty() {
+            true
+        } else {
+            self.quitting = true;
+            false
+        }
+    }
+
     fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        self.save_project_layout();
         eframe::set_value(storage, eframe::APP_KEY, self);
     }
 
     fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
-        handle_keyboard_shortcuts(self, ctx, frame);
+        handle_keyboard_shortcuts(self, ctx);
+        gamepad::begin_frame(ctx);
 
         show_notifications(ctx);
 
+        let forwarded = self
+            .state
+            .forwarded_project
+            .as_mut()
+            .and_then(|x| x.get_if_new())
+            .flatten();
+        let opened = open_events::take_opened_files().pop();
+        if let Some(path) = forwarded.or(opened) {
+            MainGuiAction::OpenPath(path).perform(self, ctx);
+        }
+
         if let Some(loaded) = self.state.loaded_project.get_if_new() {
+            self.save_project_layout();
             if let Some(loaded) = loaded {
                 let data = loaded.data;
                 let mut undoer = Undoer::default();
@@ -252,15 +617,20 @@ impl eframe::App for NesimgGui {
                     path: loaded.path,
                     undoer,
                     source_images: Default::default(),
+                    export_history: self.state.export_history.clone(),
                 };
                 state.reload_source_images();
+                self.source_paths_window.check(&state);
+                self.load_project_layout(&state.path);
 
+                self.state.close_project();
                 self.state.project = Some(state);
-            } else {
-                self.state.project = None;
+                self.state.vcs.refresh();
             }
+            // A project that failed to load leaves the open project as it is
         }
 
+        let mut tab_rects = HashMap::new();
         egui::TopBottomPanel::top("menu_bar").show(ctx, |ui| {
             let default_visuals = ui.visuals().clone();
 
@@ -284,7 +654,7 @@ impl eframe::App for NesimgGui {
                         .button(format!("➕ New Project{}", new_shortcut))
                         .clicked()
                     {
-                        MainGuiAction::NewProject.perform(self, ctx, frame);
+                        MainGuiAction::NewProject.perform(self, ctx);
                         ui.close_menu();
                     }
 
@@ -292,7 +662,33 @@ impl eframe::App for NesimgGui {
                         .button(format!("🗁 Open Project{}", open_shortcut))
                         .clicked()
                     {
-                        MainGuiAction::OpenProject.perform(self, ctx, frame);
+                        MainGuiAction::OpenProject.perform(self, ctx);
+                        ui.close_menu();
+                    }
+
+                    let reopen_label = match &self.state.recently_closed {
+                        Some(closed) => format!(
+                            "⟲ Reopen {}",
+                            closed
+                                .path
+                                .file_name()
+                                .unwrap_or_default()
+                                .to_string_lossy()
+                        ),
+                        None => "⟲ Reopen Closed Project".into(),
+                    };
+                    let reopen_response = ui
+                        .add_enabled(
+                            self.state.recently_closed.is_some(),
+                            egui::Button::new(reopen_label),
+                        )
+                        .on_hover_text(
+                            "Open the project that was just closed again, with the changes it had \
+                            when it was closed",
+                        )
+                        .on_disabled_hover_text("No project has been closed yet");
+                    if reopen_response.clicked() {
+                        reopen_project(self);
                         ui.close_menu();
                     }
 
@@ -301,15 +697,99 @@ impl eframe::App for NesimgGui {
                             .button(format!("📩 Save Project{}", save_shortcut))
                             .clicked()
                         {
-                            MainGuiAction::SaveProject.perform(self, ctx, frame);
+                            MainGuiAction::SaveProject.perform(self, ctx);
+                            ui.close_menu();
+                        }
+
+                        if ui
+                            .button("⇄ Compare With…")
+                            .on_hover_text("Show the differences between this project and another")
+                            .clicked()
+                        {
+                            self.compare_window.pick_project();
+                            ui.close_menu();
+                        }
+
+                        if ui
+                            .button("🖼 Import Mockup…")
+                            .on_hover_text(
+                                "Create tiles, metatiles, and a level from a full-screen mockup image",
+                            )
+                            .clicked()
+                        {
+                            self.mockup_import_window.pick_mockup();
+                            ui.close_menu();
+                        }
+
+                        if ui
+                            .button("💾 Import Savestate…")
+                            .on_hover_text(
+                                "Create a source and metatileset from the pattern tables and \
+                                pallet in an FCEUX savestate or a PPU memory dump",
+                            )
+                            .clicked()
+                        {
+                            self.savestate_import_window.pick_savestate();
+                            ui.close_menu();
+                        }
+
+                        if ui
+                            .button("📚 Asset Packs…")
+                            .on_hover_text(
+                                "Browse the installed asset packs and add their metatile \
+                                libraries and source images to the project",
+                            )
+                            .clicked()
+                        {
+                            self.asset_pack_browser.open = true;
+                            ui.close_menu();
+                        }
+
+                        if ui
+                            .button("⚙ Project Settings…")
+                            .on_hover_text("Edit the overscan guides and export targets")
+                            .clicked()
+                        {
+                            self.show_project_settings = true;
+                            ui.close_menu();
+                        }
+
+                        if ui
+                            .button("🗀 Source Paths…")
+                            .on_hover_text(
+                                "Fix source paths that are absolute or outside of the project folder",
+                            )
+                            .clicked()
+                        {
+                            self.source_paths_window.open();
                             ui.close_menu();
                         }
                     });
 
+                    let modified = match &self.state.project {
+                        Some(project) => matches!(
+                            self.state.vcs.status(ctx, &project.path),
+                            vcs::VcsStatus::Modified { .. }
+                        ),
+                        None => false,
+                    };
+                    ui.add_enabled_ui(modified, |ui| {
+                        ui.menu_button("⟲ Revert to Last Commit", |ui| {
+                            ui.label("This will discard all unsaved and uncommitted changes.");
+                            if ui.button("⟲ Revert").clicked() {
+                                MainGuiAction::RevertProject.perform(self, ctx);
+                                ui.close_menu();
+                            }
+                        })
+                        .response
+                        .on_disabled_hover_text("The project file has no uncommitted changes");
+                    });
+
                     ui.separator();
 
                     if ui.button(format!("🗙 Quit{}", quit_shortcut)).clicked() {
-                        frame.quit();
+                        MainGuiAction::Quit.perform(self, ctx);
+                        ui.close_menu();
                     }
                 });
 
@@ -320,16 +800,78 @@ impl eframe::App for NesimgGui {
                             .map_or(String::new(), |x| format!("\t{}", x));
 
                         if ui.button(format!("⮪ Undo {}", undo_shortcut)).clicked() {
-                            MainGuiAction::Undo.perform(self, ctx, frame);
+                            MainGuiAction::Undo.perform(self, ctx);
                         }
                     });
                 });
 
+                ui.menu_button("Export", |ui| {
+                    if let Some(project) = &self.state.project {
+                        export::export_menu(
+                            ui,
+                            project,
+                            &mut self.show_project_settings,
+                            &mut self.show_export_history,
+                        );
+                    } else {
+                        ui.add_enabled(false, egui::Button::new("Open project to export"));
+                    }
+                });
+
                 ui.menu_button("View", |ui| {
                     if ui.checkbox(&mut self.dark_mode, "🌙 Dark Theme").clicked() {
                         self.toggle_dark_mode(ui);
                     }
                     ui.checkbox(&mut self.show_help, "ℹ Show Help Panel");
+                    if ui
+                        .button("🎨 Preview Pallet…")
+                        .on_hover_text(
+                            "Tune the NES colors to match your emulator or TV, or load them from \
+                            a pallet file",
+                        )
+                        .clicked()
+                    {
+                        self.preview_pallet_window.open = true;
+                        ui.close_menu();
+                    }
+                    if ui
+                        .checkbox(&mut self.smooth_scaling, "Smooth Scaling")
+                        .on_hover_text(
+                            "Blend the pixels of source images, metatiles, and maps when they are \
+                            zoomed in. Pixel art looks best without it, and blending shows colors \
+                            that aren't really there",
+                        )
+                        .changed()
+                    {
+                        source_texture::set_smooth_scaling(self.smooth_scaling);
+                        // Upload the textures again with the new filter
+                        if let Some(project) = &mut self.state.project {
+                            project.trim_source_textures(f64::INFINITY);
+                        }
+                    }
+                    if ui
+                        .checkbox(&mut self.gamepad_navigation, "🎮 Gamepad Navigation")
+                        .on_hover_text(
+                            "Scroll and zoom the maps, and switch between levels and screens, \
+                            with a connected gamepad",
+                        )
+                        .changed()
+                    {
+                        gamepad::set_enabled(self.gamepad_navigation);
+                    }
+                    #[cfg(feature = "screen_reader")]
+                    if ui
+                        .checkbox(&mut self.screen_reader, "🔊 Screen Reader")
+                        .on_hover_text(
+                            "Read out the button, tile, or canvas that gets keyboard focus. Press \
+                            Tab to move the focus, and Space or Enter to click",
+                        )
+                        .changed()
+                    {
+                        accessibility::set_screen_reader(ctx, self.screen_reader);
+                    }
+                    ui.checkbox(&mut self.show_problems, "⚠ Problems")
+                        .on_hover_text("List unused pallet colors and sub-pallets that can be merged");
 
                     let previous_ppp = ctx.pixels_per_point();
                     ui.horizontal(|ui| {
@@ -345,12 +887,84 @@ impl eframe::App for NesimgGui {
                             ctx.set_pixels_per_point(self.pixels_per_point);
                         }
                     });
+                    ui.horizontal(|ui| {
+                        ui.spacing_mut().item_spacing = egui::Vec2::splat(3.0);
+                        for percent in UI_SCALE_PRESETS {
+                            let action = MainGuiAction::SetUiScale(percent);
+                            let current =
+                                (self.pixels_per_point * 100.0).round() as u16 == percent;
+                            let mut response =
+                                ui.selectable_label(current, format!("{}%", percent));
+                            if let Some(shortcut) = MAIN_GUI_SHORTCUTS.get(&action) {
+                                response = response.on_hover_text(shortcut.to_string());
+                            }
+                            if response.clicked() {
+                                action.perform(self, ctx);
+                            }
+                        }
+                    });
 
-                    if cfg!(debug_assertions) {
+                    ui.menu_button("🗂 Tabs", |ui| {
+                        for (name, _) in &self.tabs {
+                            let mut shown = !self.hidden_tabs.contains(name);
+                            let last_shown =
+                                shown && self.hidden_tabs.len() + 1 == self.tabs.len();
+                            if ui
+                                .add_enabled(!last_shown, egui::Checkbox::new(&mut shown, name))
+                                .changed()
+                            {
+                                if shown {
+                                    self.hidden_tabs.retain(|x| x != name);
+                                } else {
+                                    self.hidden_tabs.push(name.clone());
+                                }
+                            }
+                        }
                         ui.separator();
-                        if ui.checkbox(&mut ctx.debug_on_hover(), "Debug UI").clicked() {
-                            ctx.set_debug_on_hover(!ctx.debug_on_hover());
+                        if ui.button("Show All Tabs").clicked() {
+                            self.hidden_tabs.clear();
+                            ui.close_menu();
                         }
+                        if ui
+                            .button("Reset Tab Order")
+                            .on_hover_text("Put the tabs back in their original order")
+                            .clicked()
+                        {
+                            self.tab_order.clear();
+                            let default_order = Self::default()
+                                .tabs
+                                .into_iter()
+                                .map(|(name, _)| name)
+                                .collect::<Vec<_>>();
+                            self.tabs.sort_by_key(|(name, _)| {
+                                default_order.iter().position(|x| x == name)
+                            });
+                            ui.close_menu();
+                        }
+                    });
+
+                    ui.separator();
+                    ui.checkbox(&mut self.show_memory_usage, "🐛 Memory Usage")
+                        .on_hover_text("Show the memory used by the source images");
+
+                    if cfg!(debug_assertions)
+                        && ui.checkbox(&mut ctx.debug_on_hover(), "Debug UI").clicked()
+                    {
+                        ctx.set_debug_on_hover(!ctx.debug_on_hover());
+                    }
+                });
+
+                ui.menu_button("Help", |ui| {
+                    if ui
+                        .button("🎓 Take the Tour")
+                        .on_hover_text(
+                            "Walk through making a map on an example project. This closes the \
+                            open project.",
+                        )
+                        .clicked()
+                    {
+                        MainGuiAction::StartTour.perform(self, ctx);
+                        ui.close_menu();
                     }
                 });
 
@@ -360,13 +974,13 @@ impl eframe::App for NesimgGui {
                         ui.set_enabled(false);
                     }
                     ui.horizontal(|ui| {
-                        for (name, tab) in &self.tabs {
-                            ui.selectable_value(&mut self.current_tab, name.clone(), name)
-                                .on_hover_text(tab.tooltip())
-                                .on_disabled_hover_text("Open project to edit");
-                        }
+                        self.tab_list_gui(ui, &mut tab_rects);
                     });
                     ui.separator();
+
+                    if let Some(project) = &self.state.project {
+                        self.state.vcs.show(ui, &project.path);
+                    }
                 });
             });
         });
@@ -417,6 +1031,34 @@ impl eframe::App for NesimgGui {
                     tab.show(project, ctx, frame);
                 }
             }
+            if let Some(target) = tabs::take_jump_target(ctx) {
+                self.current_tab = target.tab().into();
+                self.hidden_tabs.retain(|x| x != target.tab());
+                for (_, tab) in &mut self.tabs {
+                    tab.jump_to(&target);
+                }
+            }
+
+            project_settings::project_settings_window(
+                ctx,
+                project,
+                &mut self.show_project_settings,
+            );
+            memory_usage::memory_usage_window(
+                ctx,
+                project,
+                &mut self.show_memory_usage,
+                &mut self.auto_trim_textures,
+            );
+            export::export_history_window(ctx, project, &mut self.show_export_history);
+            problems::problems_window(ctx, project, &mut self.show_problems);
+            if self.auto_trim_textures {
+                project.trim_source_textures(ctx.input().time - memory_usage::AUTO_TRIM_SECONDS);
+            }
+            self.compare_window.show(ctx, project);
+            self.mockup_import_window.show(ctx, project);
+            self.savestate_import_window.show(ctx, project);
+            self.source_paths_window.show(ctx, project);
         } else {
             egui::CentralPanel::default().show(ctx, |ui| {
                 StripBuilder::new(ui)
@@ -431,15 +1073,14 @@ impl eframe::App for NesimgGui {
                                     strip.cell(|ui| {
                                         ui.centered_and_justified(|ui| {
                                             if ui.button("➕ New Project").clicked() {
-                                                MainGuiAction::NewProject.perform(self, ctx, frame);
+                                                MainGuiAction::NewProject.perform(self, ctx);
                                             }
                                         });
                                     });
                                     strip.cell(|ui| {
                                         ui.centered_and_justified(|ui| {
                                             if ui.button("🗁 Open Project").clicked() {
-                                                MainGuiAction::OpenProject
-                                                    .perform(self, ctx, frame);
+                                                MainGuiAction::OpenProject.perform(self, ctx);
                                             }
                                         });
                                     });
@@ -451,52 +1092,145 @@ impl eframe::App for NesimgGui {
             });
         }
 
+        self.preview_pallet_window
+            .show(ctx, &mut self.preview_pallet);
+        self.asset_pack_browser
+            .show(ctx, self.state.project.as_mut(), &mut self.asset_pack_dirs);
+        self.tour
+            .show(ctx, &mut self.state, &self.current_tab, &tab_rects);
+        background::quit_window(ctx, frame, &mut self.quitting);
+        confirm_close_window(self, ctx);
+
         // Update the undo state for the project, if one has been loaded
         if let Some(project) = &mut self.state.project {
             project
                 .undoer
                 .feed_state(self.state.start.elapsed().as_secs_f64(), &project.data);
         }
+        rescue::update_snapshot(self.state.project.as_ref());
     }
 }
 
-fn handle_keyboard_shortcuts(gui: &mut NesimgGui, ctx: &egui::Context, frame: &mut eframe::Frame) {
+fn handle_keyboard_shortcuts(gui: &mut NesimgGui, ctx: &egui::Context) {
     for (action, shortcut) in &*MAIN_GUI_SHORTCUTS {
         if ctx
             .input_mut()
             .consume_key(shortcut.modifiers, shortcut.key)
         {
-            action.perform(gui, ctx, frame);
+            action.perform(gui, ctx);
+        }
+    }
+}
+
+/// Ask whether to save the open project before doing the action that would close it
+fn confirm_close_window(gui: &mut NesimgGui, ctx: &egui::Context) {
+    let action = match &gui.confirm_close {
+        Some(action) => action.clone(),
+        None => return,
+    };
+    let name = match &gui.state.project {
+        Some(project) => project
+            .path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned(),
+        None => {
+            gui.confirm_close = None;
+            return;
+        }
+    };
+
+    let mut result = Ok(());
+    egui::Window::new("Close Project?")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .show(ctx, |ui| {
+            ui.label(format!("{} has unsaved changes.", name));
+            if action == MainGuiAction::Quit {
+                ui.label("If you don't save them, they will be lost.");
+            } else {
+                ui.label(
+                    "If you don't save them, you can still get them back with \"⟲ Reopen\" in \
+                    the File menu until another project is closed.",
+                );
+            }
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui.button("📩 Save and Continue").clicked() {
+                    gui.confirm_close = None;
+                    result = save_project(gui, ctx).and_then(|_| action.close_project(gui, ctx));
+                }
+                if ui.button("Continue Without Saving").clicked() {
+                    gui.confirm_close = None;
+                    result = action.close_project(gui, ctx);
+                }
+                if ui.button("Cancel").clicked() {
+                    gui.confirm_close = None;
+                }
+            });
+        });
+
+    if let Err(e) = result {
+        trc::error!("{}", e);
+        send_error_notification(ctx, format!("{:#}", e));
+    }
+}
+
+impl MainGuiAction {
+    /// Do an action that closes the open project, without asking about unsaved changes
+    fn close_project(&self, gui: &mut NesimgGui, ctx: &egui::Context) -> anyhow::Result<()> {
+        match self {
+            MainGuiAction::Quit => {
+                // The quit window quits once the background tasks are done
+                gui.quitting = true;
+                Ok(())
+            }
+            MainGuiAction::NewProject => new_project(gui, ctx),
+            MainGuiAction::OpenProject => open_project(gui, ctx),
+            MainGuiAction::OpenPath(path) => {
+                gui.state.loaded_project = watch::channel(get_loaded_project(ctx, path, false)).1;
+                Ok(())
+            }
+            MainGuiAction::StartTour => {
+                // The tour visits every tab
+                gui.hidden_tabs.clear();
+                gui.tour.start(ctx, &mut gui.state);
+                Ok(())
+            }
+            _ => Ok(()),
         }
     }
 }
 
+/// Open the project that was closed last again, closing the open project in its place
+fn reopen_project(gui: &mut NesimgGui) {
+    if let Some(closed) = gui.state.recently_closed.take() {
+        gui.state.loaded_project = watch::channel(Some(closed)).1;
+    }
+}
+
 fn new_project(gui: &mut NesimgGui, ctx: &egui::Context) -> anyhow::Result<()> {
-    let (sender, receiver) = watch::channel(None);
+    let (sender, mut receiver) = watch::channel(None);
+    // Keep the open project until the new one is created
+    receiver.get();
     gui.state.loaded_project = receiver;
 
     let ctx = ctx.clone();
     std::thread::spawn(move || {
+        let _task = background::BackgroundTask::start("Creating a new project");
         let save_path = FileDialog::new()
             .add_filter("NESImg Project", &["nesimg"])
+            .add_filter("NESImg Binary Project", &[BINARY_PROJECT_EXTENSION])
             .set_filename("project.nesimg")
             .show_save_single_file()
             .expect("Show save dialog");
 
         let inner = || -> anyhow::Result<()> {
             if let Some(path) = save_path {
-                let file = std::fs::OpenOptions::new()
-                    .read(true)
-                    .write(true)
-                    .truncate(true)
-                    .create(true)
-                    .open(&path)
-                    .context("Open file to save")?;
-
                 let data = Project::default();
-
-                ron::ser::to_writer_pretty(file, &data, Default::default())
-                    .context("Serialize project to JSON")?;
+                data.save(&path)?;
 
                 sender.send(Some(LoadedProject {
                     data,
@@ -517,13 +1251,16 @@ fn new_project(gui: &mut NesimgGui, ctx: &egui::Context) -> anyhow::Result<()> {
 
 fn open_project(gui: &mut NesimgGui, ctx: &egui::Context) -> anyhow::Result<()> {
     let ctx = ctx.clone();
-    gui.state.loaded_project = pick_file(
+    let mut receiver = pick_file(
         &[FileFilter {
             name: "NESImg Projects",
-            extensions: &["nesimg"],
+            extensions: &["nesimg", BINARY_PROJECT_EXTENSION],
         }],
         move |path| get_loaded_project(&ctx, path, false),
     );
+    // Keep the open project until another one is picked
+    receiver.get();
+    gui.state.loaded_project = receiver;
 
     Ok(())
 }
@@ -534,24 +1271,16 @@ fn get_loaded_project(
     create_if_not_exists: bool,
 ) -> Option<LoadedProject> {
     let inner = || -> anyhow::Result<_> {
-        let mut file = std::fs::OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open(path)
-            .context("Reading file to load")?;
-
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)
-            .context("Read project file")?;
+        let is_empty = std::fs::metadata(path)
+            .map(|x| x.len() == 0)
+            .unwrap_or(true);
 
         let data: Project;
-        if create_if_not_exists && contents.is_empty() {
+        if create_if_not_exists && is_empty {
             data = Project::default();
-            ron::ser::to_writer_pretty(file, &data, Default::default())
-                .context("Serialize project")?;
+            data.save(path)?;
         } else {
-            data = ron::de::from_str(&contents).context("Parsing JSON file")?;
+            data = Project::load(path)?;
         }
 
         Ok(Some(LoadedProject {
@@ -570,29 +1299,31 @@ fn get_loaded_project(
 }
 
 fn save_project(gui: &mut NesimgGui, ctx: &egui::Context) -> anyhow::Result<()> {
-    let project_path = if let Some(path) = gui.state.loaded_project.get().map(|x| x.path) {
-        path
+    let project = if let Some(project) = &gui.state.project {
+        project
     } else {
         return Ok(());
     };
-    let project_data = if let Some(data) = gui.state.project.as_ref().map(|x| x.data.clone()) {
-        data
+
+    project.data.save(&project.path)?;
+
+    send_info_notification(ctx, "Save successful");
+    gui.state.vcs.refresh();
+
+    Ok(())
+}
+
+fn revert_project(gui: &mut NesimgGui, ctx: &egui::Context) -> anyhow::Result<()> {
+    let project_path = if let Some(project) = &gui.state.project {
+        project.path.clone()
     } else {
         return Ok(());
     };
 
-    let file = std::fs::OpenOptions::new()
-        .read(true)
-        .write(true)
-        .truncate(true)
-        .create(true)
-        .open(project_path)
-        .context("Open file to save")?;
-
-    ron::ser::to_writer_pretty(file, &project_data, Default::default())
-        .context("Serialize project to RON")?;
+    vcs::revert_file(&project_path).context("Revert project file")?;
 
-    send_info_notification(ctx, "Save successful");
+    gui.state.loaded_project = watch::channel(get_loaded_project(ctx, &project_path, false)).1;
+    send_info_notification(ctx, "Reverted to the last commit");
 
     Ok(())
 }
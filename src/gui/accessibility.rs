@@ -0,0 +1,53 @@
+//! Descriptions of the hand painted widgets, so they can be used with a screen reader and the
+//! keyboard
+//!
+//! The tile pickers, color swatches, and canvases are painted by hand instead of being built out
+//! of egui widgets, so egui doesn't know what they show. These helpers describe them to egui,
+//! which reads out the widget that gets keyboard focus when the screen reader is turned on, and
+//! outline them while they have focus. Tab moves the focus between widgets in the order they are
+//! added, which in every tab is the toolbar, then the sidebars, then the canvas, and Space or
+//! Enter clicks the focused widget.
+//!
+//! The screen reader is egui's own text-to-speech, not a platform accessibility API like
+//! AccessKit, which this version of egui doesn't support: it speaks the focused widget through the
+//! system speech service, speech-dispatcher on Linux, and other screen readers can't see the
+//! widgets. Since it needs that service to build and run, it is only included with the
+//! `screen_reader` cargo feature. The descriptions and focus outlines are always there.
+
+use egui::{WidgetInfo, WidgetType};
+
+/// How wide the outline of the focused widget is
+const FOCUS_STROKE_WIDTH: f32 = 2.0;
+
+/// Set whether the widget that gets keyboard focus is read out loud
+#[cfg(feature = "screen_reader")]
+pub fn set_screen_reader(ctx: &egui::Context, enabled: bool) {
+    ctx.options().screen_reader = enabled;
+}
+
+/// Describe a hand painted button that shows a picture, like a tile in a tile picker. The label
+/// is only built when the screen reader asks for it.
+pub fn image_button(response: &egui::Response, selected: bool, label: impl Fn() -> String) {
+    response.widget_info(|| WidgetInfo::selected(WidgetType::ImageButton, selected, label()));
+}
+
+/// Describe a hand painted color button
+pub fn color_button(response: &egui::Response, selected: bool, label: impl Fn() -> String) {
+    response.widget_info(|| WidgetInfo::selected(WidgetType::ColorButton, selected, label()));
+}
+
+/// Describe a canvas, like the map canvas, that is painted by hand
+pub fn canvas(response: &egui::Response, label: impl Fn() -> String) {
+    response.widget_info(|| WidgetInfo::labeled(WidgetType::Other, label()));
+}
+
+/// Outline a hand painted widget while it has keyboard focus, so it can be found without a mouse
+pub fn paint_focus(ui: &egui::Ui, response: &egui::Response, rounding: f32) {
+    if response.has_focus() {
+        ui.painter().rect_stroke(
+            response.rect.shrink(FOCUS_STROKE_WIDTH / 2.0),
+            rounding,
+            (FOCUS_STROKE_WIDTH, ui.visuals().selection.stroke.color),
+        );
+    }
+}
@@ -0,0 +1,307 @@
+//! The window for browsing the installed asset packs and adding their assets to the project
+
+use std::path::{Path, PathBuf};
+
+use watch::WatchReceiver;
+
+use crate::{asset_pack::AssetPack, library::MetatileLibrary};
+
+use super::{
+    background::BackgroundTask,
+    components::{send_error_notification, send_info_notification},
+    project_state::ProjectState,
+};
+
+/// The state of the "Asset Packs" window
+pub struct AssetPackBrowser {
+    pub open: bool,
+    /// The installed packs, or the errors loading them, in the order they were installed
+    packs: Vec<Result<AssetPack, String>>,
+    /// The pack folders that [`Self::packs`] were loaded from, used to notice when the installed
+    /// packs change
+    loaded_dirs: Option<Vec<PathBuf>>,
+    /// The packs being loaded in the background
+    loading: WatchReceiver<Option<Vec<Result<AssetPack, String>>>>,
+    /// The pack folder picked to install
+    installing: WatchReceiver<Option<PathBuf>>,
+    /// The asset being dragged out of the window
+    dragged: Option<Asset>,
+}
+
+impl Default for AssetPackBrowser {
+    fn default() -> Self {
+        Self {
+            open: false,
+            packs: Vec::new(),
+            loaded_dirs: None,
+            loading: watch::channel(None).1,
+            installing: watch::channel(None).1,
+            dragged: None,
+        }
+    }
+}
+
+/// An asset that can be added to the project
+#[derive(Clone)]
+enum Asset {
+    Library(MetatileLibrary),
+    Image(PathBuf),
+}
+
+impl Asset {
+    fn label(&self) -> String {
+        match self {
+            Asset::Library(library) => library_label(library),
+            Asset::Image(path) => image_label(path),
+        }
+    }
+}
+
+fn library_label(library: &MetatileLibrary) -> String {
+    format!("▦ {}", library.name)
+}
+
+fn image_label(path: &Path) -> String {
+    format!(
+        "🖼 {}",
+        path.file_name().unwrap_or_default().to_string_lossy()
+    )
+}
+
+impl AssetPackBrowser {
+    /// Render the window, if it is open, loading the packs in `pack_dirs` the first time it is
+    /// shown
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        mut project: Option<&mut ProjectState>,
+        pack_dirs: &mut Vec<PathBuf>,
+    ) {
+        if let Some(dir) = self.installing.get_if_new().flatten() {
+            if !pack_dirs.contains(&dir) {
+                pack_dirs.push(dir);
+            }
+        }
+        if let Some(packs) = self.loading.get_if_new().flatten() {
+            self.packs = packs;
+        }
+        if !self.open {
+            return;
+        }
+        if self.loaded_dirs.as_ref() != Some(pack_dirs) {
+            self.reload(pack_dirs);
+        }
+
+        let mut added = None;
+        let mut uninstalled = None;
+        let mut open = self.open;
+        let window = egui::Window::new("📚 Asset Packs")
+            .open(&mut open)
+            .default_width(280.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if ui
+                        .button("➕ Install Pack…")
+                        .on_hover_text(
+                            "Pick a folder of metatile libraries and source images to use in \
+                            every project",
+                        )
+                        .clicked()
+                    {
+                        self.installing = pick_pack_dir();
+                    }
+                    if ui
+                        .button("⟲ Refresh")
+                        .on_hover_text("Look for new assets in the pack folders")
+                        .clicked()
+                    {
+                        self.reload(pack_dirs);
+                    }
+                });
+                ui.separator();
+
+                if project.is_none() {
+                    ui.label("Open a project to add assets to it.");
+                } else {
+                    ui.label("Drag an asset into the project, or click ➕ to add it.");
+                }
+                if pack_dirs.is_empty() {
+                    ui.label("No asset packs are installed.");
+                }
+
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    for (i, pack) in self.packs.iter().enumerate() {
+                        let pack = match pack {
+                            Ok(pack) => pack,
+                            Err(e) => {
+                                ui.horizontal(|ui| {
+                                    if ui.button("🗑").on_hover_text("Uninstall").clicked() {
+                                        uninstalled = Some(i);
+                                    }
+                                    ui.colored_label(egui::Color32::RED, e);
+                                });
+                                continue;
+                            }
+                        };
+
+                        egui::CollapsingHeader::new(&pack.name)
+                            .id_source(&pack.dir)
+                            .default_open(true)
+                            .show(ui, |ui| {
+                                ui.horizontal(|ui| {
+                                    if ui
+                                        .button("🗑 Uninstall")
+                                        .on_hover_text(
+                                            "Remove the pack from the list. Its folder is kept.",
+                                        )
+                                        .clicked()
+                                    {
+                                        uninstalled = Some(i);
+                                    }
+                                    ui.weak(pack.dir.display().to_string());
+                                });
+
+                                let enabled = project.is_some();
+                                for (path, library) in &pack.libraries {
+                                    let asset = || Asset::Library(library.clone());
+                                    let (add, drag) = asset_row(
+                                        ui,
+                                        library_label(library),
+                                        format!(
+                                            "{}\n{} metatiles",
+                                            pack.relative_path(path).display(),
+                                            library.metatiles.len()
+                                        ),
+                                        enabled,
+                                    );
+                                    if add {
+                                        added = Some(asset());
+                                    }
+                                    if drag {
+                                        self.dragged = Some(asset());
+                                    }
+                                }
+                                for path in &pack.images {
+                                    let asset = || Asset::Image(path.clone());
+                                    let (add, drag) = asset_row(
+                                        ui,
+                                        image_label(path),
+                                        format!(
+                                            "{}\nSource image",
+                                            pack.relative_path(path).display()
+                                        ),
+                                        enabled,
+                                    );
+                                    if add {
+                                        added = Some(asset());
+                                    }
+                                    if drag {
+                                        self.dragged = Some(asset());
+                                    }
+                                }
+                                if pack.libraries.is_empty() && pack.images.is_empty() {
+                                    ui.label("No libraries or images found.");
+                                }
+                                for e in &pack.errors {
+                                    ui.colored_label(egui::Color32::RED, e);
+                                }
+                            });
+                    }
+                });
+            });
+        self.open = open;
+
+        // Drop the dragged asset into the project when it is released outside of the window
+        if let Some(asset) = &self.dragged {
+            let pointer = ctx.input().pointer.interact_pos();
+            if ctx.input().pointer.any_released() {
+                let over_window = window
+                    .as_ref()
+                    .zip(pointer)
+                    .map_or(false, |(window, pos)| window.response.rect.contains(pos));
+                if !over_window {
+                    added = Some(asset.clone());
+                }
+                self.dragged = None;
+            } else {
+                egui::show_tooltip_at_pointer(ctx, egui::Id::new("dragged_asset"), |ui| {
+                    ui.label(asset.label());
+                });
+            }
+        }
+
+        if let Some(dir) = uninstalled.and_then(|i| self.loaded_dirs.as_ref()?.get(i)) {
+            pack_dirs.retain(|x| x != dir);
+        }
+        if let (Some(asset), Some(project)) = (added, project.as_deref_mut()) {
+            add_asset(ctx, project, asset);
+        }
+    }
+
+    /// Load the packs in `pack_dirs` again in the background
+    fn reload(&mut self, pack_dirs: &[PathBuf]) {
+        let dirs = pack_dirs.to_vec();
+        self.loaded_dirs = Some(dirs.clone());
+        let (sender, receiver) = watch::channel(None);
+        self.loading = receiver;
+
+        std::thread::spawn(move || {
+            let _task = BackgroundTask::start("Loading asset packs");
+            let packs = dirs
+                .iter()
+                .map(|dir| AssetPack::load(dir).map_err(|e| format!("{:#}", e)))
+                .collect();
+            sender.send(Some(packs));
+        });
+    }
+}
+
+/// Render an asset that can be added with a button or dragged out of the window, returning whether
+/// the button was clicked and whether the asset started being dragged
+fn asset_row(ui: &mut egui::Ui, label: String, hover_text: String, enabled: bool) -> (bool, bool) {
+    ui.horizontal(|ui| {
+        let add = ui
+            .add_enabled(enabled, egui::Button::new("➕"))
+            .on_hover_text("Add to the project")
+            .clicked();
+        let drag = ui
+            .add(egui::Label::new(label).sense(egui::Sense::drag()))
+            .on_hover_text(hover_text)
+            .drag_started();
+        (add, drag && enabled)
+    })
+    .inner
+}
+
+/// Ask the user for an asset pack folder to install
+fn pick_pack_dir() -> WatchReceiver<Option<PathBuf>> {
+    let (sender, receiver) = watch::channel(None);
+
+    std::thread::spawn(move || {
+        let _task = BackgroundTask::start("Installing an asset pack");
+        if let Some(dir) = native_dialog::FileDialog::new()
+            .show_open_single_dir()
+            .expect("File dialog")
+        {
+            sender.send(Some(dir));
+        }
+    });
+
+    receiver
+}
+
+/// Add an asset from a pack to the project, copying the files it needs into the project folder
+fn add_asset(ctx: &egui::Context, project: &mut ProjectState, asset: Asset) {
+    let result = match &asset {
+        Asset::Library(library) => project
+            .import_library(library)
+            .map(|_| format!("Added metatileset `{}`", library.name)),
+        Asset::Image(path) => project
+            .import_source(path)
+            .map(|_| format!("Added source image `{}`", path.display())),
+    };
+    match result {
+        Ok(message) => send_info_notification(ctx, &message),
+        Err(e) => send_error_notification(ctx, format!("{:#}", e)),
+    }
+}
@@ -0,0 +1,87 @@
+//! Tracking the work running on background threads, so that quitting can wait for it to finish
+//! instead of stopping it part way through writing a file
+
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Mutex,
+};
+
+use once_cell::sync::Lazy;
+
+/// The descriptions of the running tasks, by their id
+static TASKS: Lazy<Mutex<Vec<(u64, String)>>> = Lazy::new(Default::default);
+
+/// The id of the next task to start
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// A task running on a background thread, which is tracked until it is dropped
+pub struct BackgroundTask {
+    id: u64,
+}
+
+impl BackgroundTask {
+    /// Start tracking a task, with a description that is shown if the user tries to quit before it
+    /// is done
+    pub fn start(description: impl Into<String>) -> Self {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        TASKS.lock().unwrap().push((id, description.into()));
+        Self { id }
+    }
+}
+
+impl Drop for BackgroundTask {
+    fn drop(&mut self) {
+        TASKS.lock().unwrap().retain(|(id, _)| *id != self.id);
+    }
+}
+
+/// Get the descriptions of the tasks that are still running
+pub fn running_tasks() -> Vec<String> {
+    TASKS
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(_, description)| description.clone())
+        .collect()
+}
+
+/// Render the window shown while quitting is waiting for background tasks, and quit once they
+/// are done. Sets `quitting` to `false` if the user cancels.
+pub fn quit_window(ctx: &egui::Context, frame: &mut eframe::Frame, quitting: &mut bool) {
+    if !*quitting {
+        return;
+    }
+
+    let tasks = running_tasks();
+    if tasks.is_empty() {
+        frame.quit();
+        return;
+    }
+
+    // Keep checking whether the tasks are done
+    ctx.request_repaint();
+
+    egui::Window::new("Quit NESImg")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .show(ctx, |ui| {
+            ui.label("NESImg will quit once these are done:");
+            for task in &tasks {
+                ui.label(format!("⏳ {}", task));
+            }
+            ui.separator();
+            ui.horizontal(|ui| {
+                if ui
+                    .button("Quit Anyway")
+                    .on_hover_text("Quit now, which may leave files half written")
+                    .clicked()
+                {
+                    frame.quit();
+                }
+                if ui.button("Cancel").clicked() {
+                    *quitting = false;
+                }
+            });
+        });
+}
@@ -0,0 +1,318 @@
+//! The read-only window comparing the open project with another project file
+
+use std::path::PathBuf;
+
+use watch::WatchReceiver;
+
+use crate::{
+    diff::{CellChange, EntityDiff, LevelChange, ProjectDiff},
+    project::{Metatile, Pallet, Project, BINARY_PROJECT_EXTENSION},
+    Uid,
+};
+
+use super::{
+    components::{nes_color_swatch, send_error_notification},
+    project_state::ProjectState,
+    util::{pick_file, FileFilter},
+};
+
+const ADDED_COLOR: egui::Color32 = egui::Color32::from_rgb(80, 200, 80);
+const REMOVED_COLOR: egui::Color32 = egui::Color32::from_rgb(220, 70, 70);
+const CHANGED_COLOR: egui::Color32 = egui::Color32::from_rgb(230, 200, 60);
+
+/// The largest size, in points, that a level map diff will be drawn at
+const MAX_LEVEL_DIFF_SIZE: f32 = 320.0;
+
+/// The state of the "Compare With…" window
+pub struct CompareWindow {
+    /// The project file being loaded to compare with
+    loading: WatchReceiver<Option<Result<(PathBuf, Project), String>>>,
+    /// The project being compared with, if one has been loaded
+    comparison: Option<Comparison>,
+}
+
+impl Default for CompareWindow {
+    fn default() -> Self {
+        Self {
+            loading: watch::channel(None).1,
+            comparison: None,
+        }
+    }
+}
+
+struct Comparison {
+    path: PathBuf,
+    other: Project,
+    diff: ProjectDiff,
+}
+
+impl CompareWindow {
+    /// Ask the user for a project file to compare the open project with
+    pub fn pick_project(&mut self) {
+        self.loading = pick_file(
+            &[FileFilter {
+                name: "NESImg Projects",
+                extensions: &["nesimg", BINARY_PROJECT_EXTENSION],
+            }],
+            |path| {
+                Some(
+                    Project::load(path)
+                        .map(|project| (path.to_owned(), project))
+                        .map_err(|e| format!("{:#}", e)),
+                )
+            },
+        );
+    }
+
+    /// Render the window, if a project has been picked to compare with
+    pub fn show(&mut self, ctx: &egui::Context, project: &ProjectState) {
+        match self.loading.get_if_new().flatten() {
+            Some(Ok((path, other))) => {
+                self.comparison = Some(Comparison {
+                    diff: ProjectDiff::new(&project.data, &other),
+                    path,
+                    other,
+                });
+            }
+            Some(Err(e)) => send_error_notification(ctx, e),
+            None => (),
+        }
+
+        let comparison = if let Some(comparison) = &mut self.comparison {
+            comparison
+        } else {
+            return;
+        };
+
+        let mut open = true;
+        egui::Window::new("Compare Projects")
+            .open(&mut open)
+            .default_width(400.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(format!(
+                        "Changes from the open project to `{}`",
+                        comparison.path.display()
+                    ));
+                    if ui
+                        .button("⟲")
+                        .on_hover_text("Compare again with the latest changes")
+                        .clicked()
+                    {
+                        comparison.diff = ProjectDiff::new(&project.data, &comparison.other);
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.colored_label(ADDED_COLOR, "➕ Added");
+                    ui.colored_label(REMOVED_COLOR, "➖ Removed");
+                    ui.colored_label(CHANGED_COLOR, "✏ Changed");
+                });
+                ui.separator();
+
+                if comparison.diff.is_empty() {
+                    ui.label("The projects are the same.");
+                    return;
+                }
+
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    comparison_gui(ui, &project.data, comparison);
+                });
+            });
+
+        if !open {
+            self.comparison = None;
+        }
+    }
+}
+
+fn comparison_gui(ui: &mut egui::Ui, base: &Project, comparison: &Comparison) {
+    let other = &comparison.other;
+    let diff = &comparison.diff;
+
+    entity_section(ui, "Sources", &diff.sources, |id| {
+        base.sources
+            .get(id)
+            .or_else(|| other.sources.get(id))
+            .map(|x| x.display().to_string())
+            .unwrap_or_default()
+    });
+
+    entity_section(ui, "Metatiles", &diff.metatiles, |id| {
+        let describe = |project: &Project| {
+            let idx = project.metatiles.get_index_of(id)?;
+            Some(metatile_name(project, idx, &project.metatiles[idx]))
+        };
+        describe(base)
+            .or_else(|| describe(other))
+            .unwrap_or_default()
+    });
+
+    entity_section(ui, "Metatilesets", &diff.metatilesets, |id| {
+        base.metatilesets
+            .get(id)
+            .or_else(|| other.metatilesets.get(id))
+            .map(|x| x.name.clone())
+            .unwrap_or_default()
+    });
+
+    if !diff.pallets.is_empty() {
+        egui::CollapsingHeader::new(format!("Pallets ({})", diff.pallets.len()))
+            .default_open(true)
+            .show(ui, |ui| {
+                for change in &diff.pallets {
+                    let name = base
+                        .metatilesets
+                        .get(&change.metatileset_id)
+                        .map(|x| x.name.as_str())
+                        .unwrap_or_default();
+                    ui.label(name);
+                    pallet_change_gui(ui, &change.before, &change.after);
+                }
+            });
+    }
+
+    entity_section(ui, "Levels", &diff.levels, |id| {
+        base.levels
+            .get(id)
+            .or_else(|| other.levels.get(id))
+            .map(|x| x.name.clone())
+            .unwrap_or_default()
+    });
+
+    if !diff.level_changes.is_empty() {
+        egui::CollapsingHeader::new(format!("Level Maps ({})", diff.level_changes.len()))
+            .default_open(true)
+            .show(ui, |ui| {
+                for change in &diff.level_changes {
+                    let name = base
+                        .levels
+                        .get(&change.level_id)
+                        .map(|x| x.name.as_str())
+                        .unwrap_or_default();
+                    ui.label(format!("{}: {} cells changed", name, change.cells.len()));
+                    if change.margin_before != change.margin_after {
+                        ui.colored_label(CHANGED_COLOR, "The level bounds were changed");
+                    }
+                    level_change_gui(ui, base, change);
+                    ui.add_space(ui.spacing().item_spacing.y);
+                }
+            });
+    }
+}
+
+/// Render the list of added, removed, and changed items in a collection
+fn entity_section<T>(
+    ui: &mut egui::Ui,
+    title: &str,
+    diff: &EntityDiff<T>,
+    describe: impl Fn(&Uid<T>) -> String,
+) {
+    if diff.is_empty() {
+        return;
+    }
+
+    egui::CollapsingHeader::new(format!(
+        "{} (+{} -{} ~{})",
+        title,
+        diff.added.len(),
+        diff.removed.len(),
+        diff.changed.len()
+    ))
+    .default_open(true)
+    .show(ui, |ui| {
+        for id in &diff.added {
+            ui.colored_label(ADDED_COLOR, format!("➕ {}", describe(id)));
+        }
+        for id in &diff.removed {
+            ui.colored_label(REMOVED_COLOR, format!("➖ {}", describe(id)));
+        }
+        for id in &diff.changed {
+            ui.colored_label(CHANGED_COLOR, format!("✏ {}", describe(id)));
+        }
+    });
+}
+
+/// Describe a metatile by its position in the list and where its first tile comes from, because
+/// metatiles don't have names.
+fn metatile_name(project: &Project, idx: usize, metatile: &Metatile) -> String {
+    let first_tile = metatile.tiles.iter().flatten().next();
+    match first_tile.and_then(|tile| Some((tile, project.sources.get(&tile.source_id)?))) {
+        Some((tile, path)) => format!(
+            "Metatile {} ( {} at {}, {} )",
+            idx + 1,
+            path.display(),
+            tile.x,
+            tile.y
+        ),
+        None => format!("Metatile {} ( empty )", idx + 1),
+    }
+}
+
+/// Render the old and new colors of each sub-pallet that changed
+fn pallet_change_gui(ui: &mut egui::Ui, before: &Pallet, after: &Pallet) {
+    for (before, after) in before
+        .get_sub_pallets()
+        .into_iter()
+        .zip(after.get_sub_pallets())
+    {
+        if before == after {
+            continue;
+        }
+
+        ui.horizontal(|ui| {
+            for color in before {
+                nes_color_swatch(ui, color);
+            }
+            ui.label("➡");
+            for color in after {
+                nes_color_swatch(ui, color);
+            }
+        });
+    }
+}
+
+/// Draw a level map as a grid, highlighting the cells that changed
+fn level_change_gui(ui: &mut egui::Ui, base: &Project, change: &LevelChange) {
+    // Show the area covered by the level bounds in either project
+    let top = change.margin_before.top.max(change.margin_after.top);
+    let left = change.margin_before.left.max(change.margin_after.left);
+    let bottom = change.margin_before.bottom.max(change.margin_after.bottom);
+    let right = change.margin_before.right.max(change.margin_after.right);
+    let (width, height) = ((left + right).max(1), (top + bottom).max(1));
+
+    let cell_size = (MAX_LEVEL_DIFF_SIZE / width.max(height) as f32).clamp(2.0, 12.0);
+    let (rect, _) = ui.allocate_exact_size(
+        egui::Vec2::new(width as f32, height as f32) * cell_size,
+        egui::Sense::hover(),
+    );
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 0.0, ui.visuals().extreme_bg_color);
+
+    let cell_rect = |(x, y): (i32, i32)| {
+        egui::Rect::from_min_size(
+            rect.min + egui::Vec2::new((x + left) as f32, (y + top) as f32) * cell_size,
+            egui::Vec2::splat(cell_size),
+        )
+    };
+
+    // Paint the unchanged tiles so that the changes can be seen in context
+    if let Some(level) = base.levels.get(&change.level_id) {
+        let color = ui.visuals().widgets.inactive.bg_fill;
+        for pos in level.tiles.keys() {
+            if !change.cells.contains_key(pos) {
+                painter.rect_filled(cell_rect(*pos).shrink(0.5), 0.0, color);
+            }
+        }
+    }
+
+    for (pos, cell) in &change.cells {
+        let color = match cell {
+            CellChange::Added => ADDED_COLOR,
+            CellChange::Removed => REMOVED_COLOR,
+            CellChange::Changed => CHANGED_COLOR,
+        };
+        painter.rect_filled(cell_rect(*pos).shrink(0.5), 0.0, color);
+    }
+
+    painter.rect_stroke(rect, 0.0, ui.visuals().window_stroke());
+}
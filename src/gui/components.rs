@@ -7,6 +7,9 @@ pub use metatile::*;
 mod color_picker;
 pub use color_picker::*;
 
+mod ruler;
+pub use ruler::*;
+
 pub use popup::*;
 mod popup;
 
@@ -1,56 +1,92 @@
-use egui::{Color32, Response, Sense, Ui, Vec2};
+use egui::{Color32, Response, Sense, Stroke, Ui, Vec2};
 
-use crate::constants::NES_PALLET;
+use crate::{
+    constants::{FORBIDDEN_COLOR, NES_COLOR_NAMES},
+    gui::{accessibility, preview_pallet::preview_color},
+};
 
 use super::popup_under_widget;
 
 /// The border radius used for rendering
 const BORDER_RADIUS: f32 = 2.0;
 
+/// The color used to mark the forbidden NES color
+const WARNING_COLOR: Color32 = Color32::from_rgb(230, 80, 60);
+
 /// Allows you to select a color from the NES pallet
 pub fn nes_color_picker(ui: &mut Ui, nes_color_index: &mut u32) {
-    let i = (*nes_color_index).min(63) as usize;
-    let color = NES_PALLET[i];
-    let color = egui::Color32::from_rgb(color[0], color[1], color[2]);
+    let i = (*nes_color_index).min(63);
 
-    let response = color_button(ui, color);
+    let response = nes_color_button(ui, i, false);
 
     let popup_id = response.id.with("popup");
     if response.clicked() {
         ui.memory().toggle_popup(popup_id)
     }
-    let response = response.on_hover_ui(|ui| {
-        ui.label(format!("NES Pallet Index: ${:02X}", i));
-        ui.label(format!("srgb: ({}, {}, {})", color[0], color[1], color[2]));
-        ui.label(format!(
-            "srgb: #{:02X}{:02X}{:02X}",
-            color[0], color[1], color[2]
-        ));
-    });
+    let response = response.on_hover_ui(|ui| nes_color_info_ui(ui, i));
 
     popup_under_widget(ui, popup_id, &response, |ui| {
-        ui.horizontal_wrapped(|ui| {
-            ui.set_max_width(800.0);
-            for (i, color) in NES_PALLET.iter().enumerate() {
-                let color = egui::Color32::from_rgb(color[0], color[1], color[2]);
-                let resp = color_button(ui, color);
-
-                let resp = resp.on_hover_ui(|ui| {
-                    ui.label(format!("NES Pallet Index: ${:02X}", i));
-                    ui.label(format!("srgb: ({}, {}, {})", color[0], color[1], color[2]));
-                    ui.label(format!(
-                        "srgb: #{:02X}{:02X}{:02X}",
-                        color[0], color[1], color[2]
-                    ));
-                });
-
-                if resp.clicked() {
-                    *nes_color_index = i as u32;
-                    ui.memory().close_popup();
+        // Lay the colors out in the same 16 column grid as the NES pallet, one row per brightness
+        for row in 0..4 {
+            ui.horizontal(|ui| {
+                for i in row * 16..row * 16 + 16 {
+                    let resp = nes_color_button(ui, i, i == *nes_color_index)
+                        .on_hover_ui(|ui| nes_color_info_ui(ui, i));
+
+                    if resp.clicked() {
+                        *nes_color_index = i;
+                        ui.memory().close_popup();
+                    }
                 }
-            }
-        });
+            });
+        }
+        ui.separator();
+        nes_color_info_ui(ui, i);
+    });
+}
+
+/// Displays a NES color that can't be changed, with its pallet index in the tooltip
+pub fn nes_color_swatch(ui: &mut Ui, nes_color_index: u32) -> Response {
+    let i = nes_color_index.min(63);
+    nes_color_button(ui, i, false).on_hover_ui(|ui| nes_color_info_ui(ui, i))
+}
+
+/// Shows the name, pallet index, and RGB value of a NES color, warning about the forbidden color
+pub fn nes_color_info_ui(ui: &mut Ui, nes_color_index: u32) {
+    let i = nes_color_index.min(63) as usize;
+    let color = preview_color(i as u32);
+    ui.strong(NES_COLOR_NAMES[i]);
+    ui.label(format!("NES Pallet Index: ${:02X}", i));
+    ui.label(format!(
+        "srgb: #{:02X}{:02X}{:02X}",
+        color[0], color[1], color[2]
+    ));
+    if i as u32 == FORBIDDEN_COLOR {
+        ui.colored_label(
+            WARNING_COLOR,
+            "⚠ $0D is \"blacker than black\" and can make some TVs lose sync. Use $0F for black.",
+        );
+    }
+}
+
+/// A color button for a NES color, outlined if it is the forbidden color, and described by its
+/// name for screen readers
+fn nes_color_button(ui: &mut Ui, nes_color_index: u32, selected: bool) -> Response {
+    let response = color_button(ui, preview_color(nes_color_index));
+    accessibility::color_button(&response, selected, || {
+        format!(
+            "{} ${:02X}",
+            NES_COLOR_NAMES[nes_color_index as usize], nes_color_index
+        )
     });
+    if nes_color_index == FORBIDDEN_COLOR {
+        ui.painter().rect_stroke(
+            response.rect.shrink(1.0),
+            BORDER_RADIUS,
+            Stroke::new(2.0, WARNING_COLOR),
+        );
+    }
+    response
 }
 
 /// Displays a clickable color button
@@ -70,6 +106,7 @@ pub fn color_button(ui: &mut Ui, color: Color32) -> Response {
             ui.visuals().widgets.noninteractive.fg_stroke,
         );
     }
+    accessibility::paint_focus(ui, &response, BORDER_RADIUS);
 
     response
 }
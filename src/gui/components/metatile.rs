@@ -1,15 +1,18 @@
 use std::{collections::HashMap, sync::Arc};
 
 use eframe::{
-    egui,
+    egui::{self, color::linear_f32_from_gamma_u8},
     egui_wgpu::{renderer::CallbackFn, winit::RenderState},
     wgpu::{self, util::DeviceExt},
 };
 
 use crate::{
-    constants::NES_PALLET_SHADER_CONST,
-    gui::project_state::{ProjectState, SourceImageStatus},
-    project::{Metatile, Metatileset, MetatilesetTile},
+    gui::{
+        preview_pallet::preview_color,
+        project_state::{ProjectState, SourceImageStatus},
+        source_texture,
+    },
+    project::{Metatile, Metatileset, MetatilesetTile, Pallet},
     Uid,
 };
 
@@ -40,15 +43,187 @@ impl MetatileKind {
     }
 }
 
+/// How much the color emphasis bits darken the channels that aren't emphasized. This is an
+/// approximation, the real amount varies between PPU revisions.
+const EMPHASIS_ATTENUATION: f32 = 0.816;
+
+/// The PPU mask effects that change how colors are displayed, used to preview pallets the way
+/// games that use them will look
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PpuEffects {
+    /// The red, green, and blue color emphasis bits
+    pub emphasis: [bool; 3],
+    /// Whether greyscale mode is enabled
+    pub greyscale: bool,
+}
+
+impl PpuEffects {
+    /// Get the pallet index the PPU will actually display for a color
+    pub fn color_index(&self, idx: u32) -> u32 {
+        let idx = idx.min(63);
+        if self.greyscale {
+            idx & 0x30
+        } else {
+            idx
+        }
+    }
+
+    /// The amount each of the red, green, and blue channels is multiplied by, in gamma space
+    fn tint(&self) -> [f32; 3] {
+        let mut tint = [1.0; 3];
+        for (i, _) in self.emphasis.iter().enumerate().filter(|(_, x)| **x) {
+            for (j, channel) in tint.iter_mut().enumerate() {
+                if i != j {
+                    *channel *= EMPHASIS_ATTENUATION;
+                }
+            }
+        }
+        tint
+    }
+
+    /// Get the color that the PPU will display for a NES color index
+    pub fn apply(&self, idx: u32) -> egui::Color32 {
+        let idx = self.color_index(idx);
+        let color = preview_color(idx);
+        // The blacks at the end of each row aren't affected by emphasis
+        if idx & 0x0F >= 0x0E {
+            return color;
+        }
+        let [r, g, b] = self.tint();
+        egui::Color32::from_rgb(
+            (color.r() as f32 * r) as u8,
+            (color.g() as f32 * g) as u8,
+            (color.b() as f32 * b) as u8,
+        )
+    }
+}
+
+/// What is shown for the pixels that use color 0 of their sub-pallet, which games often treat as
+/// transparent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backdrop {
+    /// The backdrop color, the way the NES shows it
+    Color,
+    /// A checkerboard, like transparent pixels in paint programs
+    Checkerboard,
+    /// Magenta, which stands out from every NES color
+    Magenta,
+}
+
+impl Backdrop {
+    pub const ALL: [Backdrop; 3] = [Backdrop::Color, Backdrop::Checkerboard, Backdrop::Magenta];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Backdrop::Color => "Backdrop Color",
+            Backdrop::Checkerboard => "Checkerboard",
+            Backdrop::Magenta => "Magenta",
+        }
+    }
+
+    /// The value passed to the shader for the backdrop
+    fn shader_id(&self) -> u32 {
+        match self {
+            Backdrop::Color => 0,
+            Backdrop::Checkerboard => 1,
+            Backdrop::Magenta => 2,
+        }
+    }
+}
+
+/// Pick what is shown behind color 0, so that it can be told apart from a black color
+pub fn backdrop_gui(ui: &mut egui::Ui, id_source: &str, backdrop: &mut Backdrop) {
+    egui::ComboBox::from_id_source(id_source)
+        .selected_text(backdrop.name())
+        .show_ui(ui, |ui| {
+            for option in Backdrop::ALL {
+                ui.selectable_value(backdrop, option, option.name());
+            }
+        })
+        .response
+        .on_hover_text(
+            "What to show for the pixels that use color 0 of their sub-pallet, so they can be told \
+            apart from black pixels",
+        );
+    ui.label("Color 0:");
+}
+
 pub struct MetatileGui<'a> {
     tile: MetatileKind,
     project: &'a mut ProjectState,
+    /// The NES frame to show animated tiles at, or `None` to show their first frame
+    nes_frame: Option<u64>,
+    effects: PpuEffects,
+    /// The position of the metatile in NES pixels, if it should be shown with the composite video
+    /// filter
+    composite: Option<(i32, i32)>,
+    backdrop: Backdrop,
+    /// The sub-pallet to show a standalone metatile with, instead of greyscale
+    preview_colors: Option<[u32; 4]>,
+    /// The pallet to show a metatileset tile with, instead of its metatileset's pallet
+    pallet: Option<Pallet>,
 }
 
 impl<'a> MetatileGui<'a> {
     #[must_use = "Must call .show() to display"]
     pub fn new(project: &'a mut ProjectState, tile: MetatileKind) -> Self {
-        Self { tile, project }
+        Self {
+            tile,
+            project,
+            nes_frame: None,
+            effects: Default::default(),
+            composite: None,
+            backdrop: Backdrop::Color,
+            preview_colors: None,
+            pallet: None,
+        }
+    }
+
+    /// Show animated tiles as they would be at `time`, in seconds, or show their first frame if
+    /// `time` is `None`
+    #[must_use = "Must call .show() to display"]
+    pub fn animated(mut self, time: Option<f64>) -> Self {
+        self.nes_frame = time.map(|time| (time * 60.0) as u64);
+        self
+    }
+
+    /// Show the metatile with the PPU color emphasis and greyscale effects
+    #[must_use = "Must call .show() to display"]
+    pub fn effects(mut self, effects: PpuEffects) -> Self {
+        self.effects = effects;
+        self
+    }
+
+    /// Simulate the blur and color artifacts of composite video, for a metatile at `position` in
+    /// NES pixels, which sets the phase of the color artifacts. Does nothing if `position` is
+    /// `None`.
+    #[must_use = "Must call .show() to display"]
+    pub fn composite(mut self, position: Option<(i32, i32)>) -> Self {
+        self.composite = position;
+        self
+    }
+
+    /// Show the pixels that use color 0 with `backdrop` instead of the backdrop color
+    #[must_use = "Must call .show() to display"]
+    pub fn backdrop(mut self, backdrop: Backdrop) -> Self {
+        self.backdrop = backdrop;
+        self
+    }
+
+    /// Show a standalone metatile with the four colors of a sub-pallet, since it doesn't have
+    /// colors of its own. Does nothing for metatileset tiles, or if `colors` is `None`.
+    #[must_use = "Must call .show() to display"]
+    pub fn preview_colors(mut self, colors: Option<[u32; 4]>) -> Self {
+        self.preview_colors = colors;
+        self
+    }
+
+    /// Show a metatileset tile with `pallet` instead of its metatileset's pallet, such as a pallet
+    /// swapped in part way down the screen. Tiles that use an extra sub-pallet keep their colors.
+    #[must_use = "Must call .show() to display"]
+    pub fn pallet(mut self, pallet: Option<Pallet>) -> Self {
+        self.pallet = pallet;
+        self
     }
 
     // pub fn show(&mut self, size: egui::Vec2, ui: &mut egui::Ui, frame: &mut eframe::Frame) {
@@ -72,7 +247,11 @@ impl<'a> MetatileGui<'a> {
         for i in 0..4 {
             let mut get_tile = || {
                 let tile = self.tile.get_metatile(self.project).unwrap().tiles[i].clone()?;
-                let source_image = self.project.source_images.get_mut(&tile.source_id).unwrap();
+                let tile = match self.nes_frame {
+                    Some(nes_frame) => self.project.data.animated_tile(&tile, nes_frame).clone(),
+                    None => tile,
+                };
+                let source_image = self.project.source_images.get_mut(&tile.source_id)?;
 
                 let source_image_data =
                     if let SourceImageStatus::Found(image) = source_image.data.get() {
@@ -111,7 +290,9 @@ impl<'a> MetatileGui<'a> {
         ];
 
         let colors = match &self.tile {
-            MetatileKind::Standalone { .. } => [0x0f, 0x2d, 0x10, 0x30],
+            MetatileKind::Standalone { .. } => {
+                self.preview_colors.unwrap_or([0x0f, 0x2d, 0x10, 0x30])
+            }
             MetatileKind::Metatileset {
                 metatileset_id,
                 metatileset_tile_id,
@@ -123,11 +304,47 @@ impl<'a> MetatileGui<'a> {
                     .get(metatileset_tile_id)
                     .map(|metatileset_tile| metatileset_tile.sub_pallet_idx)
                     .unwrap();
-                metatileset.pallet.get_sub_pallets()[sub_pallet_idx]
+                match &self.pallet {
+                    Some(pallet) if sub_pallet_idx < 4 => pallet.get_sub_pallets()[sub_pallet_idx],
+                    _ => metatileset.sub_pallet(sub_pallet_idx),
+                }
             }
+        }
+        .map(|x| self.effects.color_index(x));
+        // The shader works with linear colors
+        let rgb = colors.map(|x| {
+            let color = preview_color(x);
+            [
+                linear_f32_from_gamma_u8(color.r()),
+                linear_f32_from_gamma_u8(color.g()),
+                linear_f32_from_gamma_u8(color.b()),
+                1.0,
+            ]
+        });
+        let [r, g, b] = self.effects.tint().map(|x| x.powf(2.2));
+        let params = ShaderParams {
+            colors,
+            rgb,
+            tint: [r, g, b, 1.0],
+            composite: self.composite.is_some(),
+            // The color artifacts crawl in a three frame cycle
+            frame: self.nes_frame.unwrap_or(0) % 3,
+            origin: self
+                .composite
+                .map(|(x, y)| [x as f32, y as f32])
+                .unwrap_or_default(),
+            backdrop: self.backdrop.shader_id(),
+            smoothing: source_texture::texture_filter() == egui::TextureFilter::Linear,
         };
 
-        let id = ui.id().with(&self.tile);
+        // Composite metatiles look different at each position, and swapped pallets change their
+        // colors, so they need their own resources
+        let id = ui
+            .id()
+            .with(&self.tile)
+            .with(self.composite)
+            .with(self.pallet.as_ref().map(|x| x.colors));
+        let time = ui.input().time;
 
         // Paint the image
         let image_painter = egui::PaintCallback {
@@ -137,7 +354,7 @@ impl<'a> MetatileGui<'a> {
                     .prepare(move |device, queue, resources| {
                         let renderer: &mut Renderer = resources.get_mut().unwrap();
 
-                        renderer.prepare(device, queue, id, &raw_tiles, colors);
+                        renderer.prepare(device, queue, id, &raw_tiles, &params, time);
                     })
                     .paint(move |_info, rpass, resources| {
                         let renderer: &Renderer = resources.get().unwrap();
@@ -150,6 +367,20 @@ impl<'a> MetatileGui<'a> {
     }
 }
 
+/// The metatile settings, other than the tiles, that are passed to the shader
+struct ShaderParams {
+    colors: [u32; 4],
+    /// The linear color of each of the four colors, from the preview pallet
+    rgb: [[f32; 4]; 4],
+    tint: [f32; 4],
+    composite: bool,
+    frame: u64,
+    origin: [f32; 2],
+    backdrop: u32,
+    /// Whether neighboring pixels are blended, see [`source_texture::texture_filter`]
+    smoothing: bool,
+}
+
 struct RawTile {
     texture_view: wgpu::TextureView,
     uv_start: [f32; 2],
@@ -159,12 +390,17 @@ struct RawTile {
 struct MetatileResources {
     bind_group: wgpu::BindGroup,
     uniform_buffer: wgpu::Buffer,
+    /// The egui time of the frame the metatile was last drawn in
+    last_drawn: f64,
 }
 
 struct Renderer {
     pipeline: wgpu::RenderPipeline,
     bind_group_layout: wgpu::BindGroupLayout,
     metatile_resources: HashMap<egui::Id, MetatileResources>,
+    /// The egui time of the frame being drawn, used to free the resources of metatiles that
+    /// weren't drawn in the last frame
+    frame_time: f64,
     sampler: wgpu::Sampler,
     empty_tile_texture_view: wgpu::TextureView,
 }
@@ -183,11 +419,7 @@ impl Renderer {
             .or_insert_with(|| {
                 let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
                     label,
-                    source: wgpu::ShaderSource::Wgsl(
-                        include_str!("./metatile/shader.wgsl")
-                            .replace("// #NES_PALLET", &NES_PALLET_SHADER_CONST)
-                            .into(),
-                    ),
+                    source: wgpu::ShaderSource::Wgsl(include_str!("./metatile/shader.wgsl").into()),
                 });
 
                 let bind_group_layout =
@@ -328,6 +560,7 @@ impl Renderer {
 
                 Renderer {
                     metatile_resources: Default::default(),
+                    frame_time: 0.0,
                     pipeline,
                     bind_group_layout,
                     sampler,
@@ -342,12 +575,29 @@ impl Renderer {
         queue: &wgpu::Queue,
         id: egui::Id,
         raw_tiles: &[Option<RawTile>; 4],
-        colors: [u32; 4],
+        params: &ShaderParams,
+        time: f64,
     ) {
+        // The first metatile of a new frame frees the metatiles that weren't drawn in the last
+        // one, such as the ones scrolled out of view or with a pallet that was changed
+        if time != self.frame_time {
+            let last_frame = self.frame_time;
+            self.metatile_resources
+                .retain(|_, resources| resources.last_drawn >= last_frame);
+            self.frame_time = time;
+        }
+
         #[derive(encase::ShaderType)]
         struct MetatileUniform {
             tiles: [MetatileTileUniform; 4],
             colors: glam::UVec4,
+            rgb: [glam::Vec4; 4],
+            tint: glam::Vec4,
+            composite: u32,
+            frame: u32,
+            origin: glam::Vec2,
+            backdrop: u32,
+            smoothing: u32,
         }
 
         #[derive(encase::ShaderType)]
@@ -386,7 +636,14 @@ impl Renderer {
                     uniform_tiles.remove(0),
                     uniform_tiles.remove(0),
                 ],
-                colors: colors.into(),
+                colors: params.colors.into(),
+                rgb: params.rgb.map(glam::Vec4::from),
+                tint: params.tint.into(),
+                composite: params.composite as u32,
+                frame: params.frame as u32,
+                origin: params.origin.into(),
+                backdrop: params.backdrop,
+                smoothing: params.smoothing as u32,
             })
             .expect("Format uniform buffer");
         let uniform_buffer_bytes = uniform_buffer_temp.into_inner();
@@ -450,6 +707,7 @@ impl Renderer {
             MetatileResources {
                 bind_group,
                 uniform_buffer,
+                last_drawn: time,
             },
         );
     }
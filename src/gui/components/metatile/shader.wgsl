@@ -7,6 +7,20 @@ struct Tile {
 struct Metatile {
     tiles: array<Tile, 4>;
     colors: vec4<u32>;
+    // The linear color of each of the colors, from the preview pallet
+    rgb: array<vec4<f32>, 4>;
+    // The color emphasis tint, which doesn't apply to the blacks at the end of each pallet row
+    tint: vec4<f32>;
+    // Whether to simulate composite video, and the frame and pixel position used for the phase of
+    // its color artifacts
+    composite: u32;
+    frame: u32;
+    origin: vec2<f32>;
+    // What to show for pixels that use color 0: 0 for the backdrop color, 1 for a checkerboard, and
+    // 2 for magenta
+    backdrop: u32;
+    // Whether to blend neighboring pixels when zoomed in, following the smooth scaling preference
+    smoothing: u32;
 };
 
 [[group(0), binding(0)]]
@@ -16,6 +30,10 @@ struct VertexOut {
     [[builtin(position)]] pos: vec4<f32>;
     [[location(0)]] tex_idx: u32;
     [[location(1)]] uv: vec2<f32>;
+    [[location(2)]] uv_start: vec2<f32>;
+    [[location(3)]] uv_size: vec2<f32>;
+    // The position of the tile in the metatile, in pixels
+    [[location(4)]] tile_offset: vec2<f32>;
 };
 
 var<private> v_positions: array<vec2<f32>, 6> = array<vec2<f32>, 6>(
@@ -53,6 +71,9 @@ fn vs_main([[builtin(vertex_index)]] vertex_idx: u32) -> VertexOut {
     out.pos = vec4<f32>(v_positions[vertex_idx_in_square] + offset, 0.0, 1.0);
     out.uv = tile.uv_start + tile.uv_size * v_uvs[vertex_idx_in_square];
     out.tex_idx = tile.tex_idx;
+    out.uv_start = tile.uv_start;
+    out.uv_size = tile.uv_size;
+    out.tile_offset = vec2<f32>(f32(offset_x), f32(offset_y)) * 8.0;
     return out;
 }
 
@@ -70,28 +91,135 @@ var texture_3: texture_2d<f32>;
 [[group(0), binding(6)]]
 var tex_sampler: sampler;
 
-// The below comment will be substituted with a local variable containing the NES color pallet
-// #NES_PALLET
-
-[[stage(fragment)]]
-fn fs_main(in: VertexOut) -> [[location(0)]] vec4<f32> {
+fn sample_tile(tex_idx: u32, uv: vec2<f32>) -> vec4<f32> {
     var out: vec4<f32>;
 
-    if (in.tex_idx == 0u) {
-        out = textureSampleLevel(dummy_texture, tex_sampler, in.uv, 0.0);
-    } else if (in.tex_idx == 1u) {
-        out = textureSampleLevel(texture_0, tex_sampler, in.uv, 0.0);
-    } else if (in.tex_idx == 2u) {
-        out = textureSampleLevel(texture_1, tex_sampler, in.uv, 0.0);
-    } else if (in.tex_idx == 3u) {
-        out = textureSampleLevel(texture_2, tex_sampler, in.uv, 0.0);
-    } else if (in.tex_idx == 4u) {
-        out = textureSampleLevel(texture_3, tex_sampler, in.uv, 0.0);
+    if (tex_idx == 0u) {
+        out = textureSampleLevel(dummy_texture, tex_sampler, uv, 0.0);
+    } else if (tex_idx == 1u) {
+        out = textureSampleLevel(texture_0, tex_sampler, uv, 0.0);
+    } else if (tex_idx == 2u) {
+        out = textureSampleLevel(texture_1, tex_sampler, uv, 0.0);
+    } else if (tex_idx == 3u) {
+        out = textureSampleLevel(texture_2, tex_sampler, uv, 0.0);
+    } else if (tex_idx == 4u) {
+        out = textureSampleLevel(texture_3, tex_sampler, uv, 0.0);
     }
 
-    let color_idx = u32(ceil(out.r * 3.0));
+    return out;
+}
+
+// Get the index, 0-3, of the color used by the tile pixel at `uv`
+fn color_index(tex_idx: u32, uv: vec2<f32>) -> u32 {
+    return u32(ceil(sample_tile(tex_idx, uv).r * 3.0));
+}
 
-    out = vec4<f32>(NES_PALLET[ metatile.colors[color_idx] ], 1.0);
+// Get the linear color of the tile pixel at `uv`
+fn nes_color(tex_idx: u32, uv: vec2<f32>) -> vec3<f32> {
+    let color_idx = color_index(tex_idx, uv);
+    let nes_color = metatile.colors[color_idx];
 
+    var out = metatile.rgb[color_idx].rgb;
+    // The color emphasis tint doesn't apply to the blacks at the end of each pallet row
+    if ((nes_color & 15u) < 14u) {
+        out = out * metatile.tint.rgb;
+    }
     return out;
-}
\ No newline at end of file
+}
+
+fn rgb_to_yiq(c: vec3<f32>) -> vec3<f32> {
+    return vec3<f32>(
+        dot(c, vec3<f32>(0.299, 0.587, 0.114)),
+        dot(c, vec3<f32>(0.596, -0.274, -0.322)),
+        dot(c, vec3<f32>(0.211, -0.523, 0.312)),
+    );
+}
+
+fn yiq_to_rgb(c: vec3<f32>) -> vec3<f32> {
+    return vec3<f32>(
+        dot(c, vec3<f32>(1.0, 0.956, 0.621)),
+        dot(c, vec3<f32>(1.0, -0.272, -0.647)),
+        dot(c, vec3<f32>(1.0, -1.106, 1.703)),
+    );
+}
+
+// The horizontal filters applied to the brightness and the color of the composite signal. The
+// color has much less bandwidth than the brightness, so it smears over more pixels.
+var<private> LUMA_FILTER: array<f32, 5> = array<f32, 5>(0.0, 0.25, 0.5, 0.25, 0.0);
+var<private> CHROMA_FILTER: array<f32, 5> = array<f32, 5>(0.1, 0.2, 0.4, 0.2, 0.1);
+
+// How much of the color signal leaks into the brightness, causing the crawling dot artifacts
+let CHROMA_CRAWL: f32 = 0.15;
+
+// Simulate composite video by filtering the pixels around the current one.
+//
+// Only the pixels in the same 8x8 tile are sampled, so the smearing stops at tile edges, but it
+// is close enough to show how fine dithering will blend together.
+fn composite(in: VertexOut) -> vec3<f32> {
+    let texel_size = in.uv_size / 8.0;
+    let local = floor((in.uv - in.uv_start) / texel_size);
+    let pixel = metatile.origin + in.tile_offset + local;
+
+    var luma = 0.0;
+    var chroma = vec2<f32>(0.0, 0.0);
+    for (var i: i32 = 0; i < 5; i = i + 1) {
+        let x = clamp(local.x + f32(i - 2), 0.0, 7.0);
+        let uv = in.uv_start + (vec2<f32>(x, local.y) + 0.5) * texel_size;
+        // Filter the gamma encoded signal, like the TV does
+        let yiq = rgb_to_yiq(pow(nes_color(in.tex_idx, uv), vec3<f32>(1.0 / 2.2)));
+        luma = luma + yiq.x * LUMA_FILTER[i];
+        chroma = chroma + yiq.yz * CHROMA_FILTER[i];
+    }
+
+    // The NES color subcarrier phase shifts by a third of a cycle each scanline and each frame
+    let phase = (pixel.x * 2.0 + pixel.y + f32(metatile.frame)) * 2.0943951;
+    luma = luma + dot(chroma, vec2<f32>(cos(phase), sin(phase))) * CHROMA_CRAWL;
+
+    let rgb = clamp(yiq_to_rgb(vec3<f32>(luma, chroma)), vec3<f32>(0.0), vec3<f32>(1.0));
+    return pow(rgb, vec3<f32>(2.2));
+}
+
+// Blend the colors of the four pixels around `uv`, like a linear texture filter. The textures hold
+// color indexes, which can't be blended, so each pixel is colored first. Only the pixels in the
+// same 8x8 tile are sampled, the same as for composite video.
+fn smooth_color(in: VertexOut) -> vec3<f32> {
+    let texel_size = in.uv_size / 8.0;
+    let texel_pos = (in.uv - in.uv_start) / texel_size - 0.5;
+    let base = floor(texel_pos);
+    let t = texel_pos - base;
+
+    var colors: array<vec3<f32>, 4>;
+    for (var i: i32 = 0; i < 4; i = i + 1) {
+        let texel = clamp(base + vec2<f32>(f32(i % 2), f32(i / 2)), vec2<f32>(0.0), vec2<f32>(7.0));
+        colors[i] = nes_color(in.tex_idx, in.uv_start + (texel + 0.5) * texel_size);
+    }
+    return mix(mix(colors[0], colors[1], t.x), mix(colors[2], colors[3], t.x), t.y);
+}
+
+// The size of the checkerboard squares shown behind color 0, in physical pixels
+let CHECKERBOARD_SIZE: f32 = 6.0;
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOut) -> [[location(0)]] vec4<f32> {
+    if (metatile.composite != 0u) {
+        return vec4<f32>(composite(in), 1.0);
+    }
+
+    if (metatile.backdrop != 0u && color_index(in.tex_idx, in.uv) == 0u) {
+        if (metatile.backdrop == 2u) {
+            return vec4<f32>(1.0, 0.0, 1.0, 1.0);
+        }
+        // The checkerboard squares are a fixed size on screen, so they don't look like pixels
+        let square = floor(in.pos.xy / CHECKERBOARD_SIZE);
+        if ((u32(square.x) + u32(square.y)) % 2u == 0u) {
+            return vec4<f32>(vec3<f32>(0.35), 1.0);
+        }
+        return vec4<f32>(vec3<f32>(0.15), 1.0);
+    }
+
+    if (metatile.smoothing != 0u) {
+        return vec4<f32>(smooth_color(in), 1.0);
+    }
+
+    return vec4<f32>(nes_color(in.tex_idx, in.uv), 1.0);
+}
@@ -108,6 +108,19 @@ pub fn send_error_notification(ctx: &egui::Context, message: String) {
     .send(ctx);
 }
 
+pub fn send_warning_notification(ctx: &egui::Context, message: String) {
+    Notification::new(move |ui| {
+        Some(
+            ui.horizontal(|ui| {
+                ui.colored_label(egui::Color32::YELLOW, format!("Warning: {}", message));
+                ui.button("x").clicked()
+            })
+            .inner,
+        )
+    })
+    .send(ctx);
+}
+
 pub fn send_info_notification(ctx: &egui::Context, message: &str) {
     let message = message.to_owned();
     Notification::new(move |ui| {
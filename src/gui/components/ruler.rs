@@ -0,0 +1,248 @@
+use crate::constants::{SCREEN_HEIGHT_METATILES, SCREEN_WIDTH_METATILES};
+
+/// The height of the ruler along the top of a canvas, in points
+pub const RULER_HEIGHT: f32 = 18.0;
+
+/// The width of the ruler along the left of a canvas, in points, wide enough for a nametable offset
+pub const RULER_WIDTH: f32 = 44.0;
+
+/// The closest that two labels on a ruler may be, in points
+const MIN_LABEL_SPACING: f32 = 48.0;
+
+/// The closest that two ticks on a ruler may be, in points
+const MIN_TICK_SPACING: f32 = 4.0;
+
+/// The color used to mark the pointer position on the rulers
+const POINTER_COLOR: egui::Color32 = egui::Color32::from_rgb(255, 220, 80);
+
+/// The unit that the rulers along a canvas are labeled in
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RulerUnit {
+    /// 8x8 pattern table tiles
+    Tiles,
+    /// 16x16 metatiles
+    Metatiles,
+    /// NES pixels
+    Pixels,
+    /// Nametable addresses, with the offset of each tile from the start of its screen
+    Nametable,
+}
+
+impl Default for RulerUnit {
+    fn default() -> Self {
+        Self::Tiles
+    }
+}
+
+impl RulerUnit {
+    pub const ALL: [RulerUnit; 4] = [
+        RulerUnit::Tiles,
+        RulerUnit::Metatiles,
+        RulerUnit::Pixels,
+        RulerUnit::Nametable,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RulerUnit::Tiles => "Tiles",
+            RulerUnit::Metatiles => "Metatiles",
+            RulerUnit::Pixels => "Pixels",
+            RulerUnit::Nametable => "Nametable",
+        }
+    }
+
+    /// The size of one step of the unit, in NES pixels
+    fn step(&self) -> i32 {
+        match self {
+            RulerUnit::Tiles | RulerUnit::Nametable => 8,
+            RulerUnit::Metatiles => 16,
+            RulerUnit::Pixels => 1,
+        }
+    }
+
+    /// The label for the NES pixel `pos` on the horizontal or vertical ruler
+    fn label(&self, pos: i32, horizontal: bool) -> String {
+        match self {
+            RulerUnit::Nametable => {
+                let (x, y) = if horizontal { (pos, 0) } else { (0, pos) };
+                let address = nametable_address((x, y));
+                if pos.rem_euclid(screen_len(horizontal)) == 0 {
+                    format!("${:04X}", address)
+                } else {
+                    format!("+${:X}", address & 0x3ff)
+                }
+            }
+            _ => pos.div_euclid(self.step()).to_string(),
+        }
+    }
+}
+
+/// The width or height of a screen in NES pixels
+fn screen_len(horizontal: bool) -> i32 {
+    if horizontal {
+        (SCREEN_WIDTH_METATILES * 16) as i32
+    } else {
+        (SCREEN_HEIGHT_METATILES * 16) as i32
+    }
+}
+
+/// The PPU address of the nametable entry for the 8x8 tile at a pixel of a map.
+///
+/// The screens of the map are counted from its top-left corner, and alternate between the
+/// nametables the way a scrolling engine writes them: `$2000` and `$2400` from left to right, and
+/// `$2000` and `$2800` from top to bottom.
+pub fn nametable_address(pixel: (i32, i32)) -> u16 {
+    let (screen_width, screen_height) = (screen_len(true), screen_len(false));
+    let screen = (
+        pixel.0.div_euclid(screen_width),
+        pixel.1.div_euclid(screen_height),
+    );
+    let tile = (
+        pixel.0.rem_euclid(screen_width) / 8,
+        pixel.1.rem_euclid(screen_height) / 8,
+    );
+    let nametable = (screen.0 & 1) | ((screen.1 & 1) << 1);
+    (0x2000 + nametable * 0x400 + tile.1 * 32 + tile.0) as u16
+}
+
+/// The PPU address of the attribute byte for the metatile at a pixel of a map, and the lowest of
+/// the two bits in it that hold the metatile's sub-pallet, with the screens counted like
+/// [`nametable_address`]
+pub fn attribute_address(pixel: (i32, i32)) -> (u16, u32) {
+    let nametable = nametable_address(pixel) & 0xfc00;
+    let metatile = (
+        pixel.0.rem_euclid(screen_len(true)) / 16,
+        pixel.1.rem_euclid(screen_len(false)) / 16,
+    );
+    let address = nametable + 0x3c0 + (metatile.1 / 2 * 8 + metatile.0 / 2) as u16;
+    let shift = ((metatile.1 % 2) * 2 + metatile.0 % 2) as u32 * 2;
+    (address, shift)
+}
+
+/// Describe a pixel of a map in each of the ruler units, one per line
+pub fn coordinate_readout(pixel: (i32, i32)) -> String {
+    format!(
+        "Pixel:     ({}, {})\nTile:      ({}, {})\nMetatile:  ({}, {})\nNametable: ${:04X}",
+        pixel.0,
+        pixel.1,
+        pixel.0.div_euclid(8),
+        pixel.1.div_euclid(8),
+        pixel.0.div_euclid(16),
+        pixel.1.div_euclid(16),
+        nametable_address(pixel),
+    )
+}
+
+/// Pick the unit the rulers are labeled in
+pub fn ruler_unit_gui(ui: &mut egui::Ui, id_source: &str, unit: &mut RulerUnit) {
+    egui::ComboBox::from_id_source(id_source)
+        .selected_text(unit.name())
+        .show_ui(ui, |ui| {
+            for option in RulerUnit::ALL {
+                ui.selectable_value(unit, option, option.name());
+            }
+        })
+        .response
+        .on_hover_text("The unit that the rulers along the top and left of the canvas count in");
+}
+
+/// Paint rulers along the top and left edges of `rect`, counting NES pixels from `origin` at
+/// `zoom` points per pixel, and mark the position of the pointer on them
+pub fn paint_rulers(
+    painter: &egui::Painter,
+    rect: egui::Rect,
+    origin: egui::Pos2,
+    zoom: f32,
+    unit: RulerUnit,
+    pointer: Option<egui::Pos2>,
+) {
+    let visuals = painter.ctx().style().visuals.clone();
+    let top = egui::Rect::from_min_max(rect.min, egui::pos2(rect.max.x, rect.min.y + RULER_HEIGHT));
+    let left = egui::Rect::from_min_max(rect.min, egui::pos2(rect.min.x + RULER_WIDTH, rect.max.y));
+
+    for (band, horizontal) in [(top, true), (left, false)] {
+        let painter = painter.with_clip_rect(band.intersect(painter.clip_rect()));
+        painter.rect_filled(band, 0.0, visuals.extreme_bg_color);
+
+        let (start, range) = if horizontal {
+            (origin.x, band.x_range())
+        } else {
+            (origin.y, band.y_range())
+        };
+        let to_points = |pixel: i32| start + pixel as f32 * zoom;
+        let first = ((range.start() - start) / zoom).floor() as i32;
+        let last = ((range.end() - start) / zoom).ceil() as i32;
+
+        // Label every nth step, skipping steps until the labels have room for their text
+        let step = unit.step();
+        let mut label_every = 1;
+        while (step * label_every) as f32 * zoom < MIN_LABEL_SPACING {
+            label_every *= 2;
+        }
+        let show_ticks = step as f32 * zoom >= MIN_TICK_SPACING;
+
+        // Once the ticks are hidden, only the labeled steps need to be visited, except for
+        // nametables, whose labels restart at the start of each screen
+        let stride = if show_ticks || unit == RulerUnit::Nametable {
+            step
+        } else {
+            step * label_every
+        };
+
+        let mut pos = first.div_euclid(stride) * stride;
+        while pos <= last {
+            let idx = match unit {
+                RulerUnit::Nametable => pos.rem_euclid(screen_len(horizontal)) / step,
+                _ => pos / step,
+            };
+            let labeled = idx % label_every == 0;
+            if labeled || show_ticks {
+                let at = to_points(pos);
+                let len = if labeled { 1.0 } else { 0.3 };
+                let stroke = (1.0, visuals.text_color().linear_multiply(0.6));
+                if horizontal {
+                    painter.vline(
+                        at,
+                        band.bottom() - RULER_HEIGHT * len..=band.bottom(),
+                        stroke,
+                    );
+                } else {
+                    painter.hline(band.right() - RULER_WIDTH * len..=band.right(), at, stroke);
+                }
+                if labeled {
+                    let anchor = if horizontal {
+                        egui::pos2(at + 2.0, band.top())
+                    } else {
+                        egui::pos2(band.left() + 2.0, at + 1.0)
+                    };
+                    painter.text(
+                        anchor,
+                        egui::Align2::LEFT_TOP,
+                        unit.label(pos, horizontal),
+                        egui::FontId::monospace(10.0),
+                        visuals.text_color(),
+                    );
+                }
+            }
+
+            pos += stride;
+        }
+
+        if let Some(pointer) = pointer.filter(|x| rect.contains(*x)) {
+            let stroke = (2.0, POINTER_COLOR);
+            if horizontal {
+                painter.vline(pointer.x, band.y_range(), stroke);
+            } else {
+                painter.hline(band.x_range(), pointer.y, stroke);
+            }
+        }
+        painter.rect_stroke(band, 0.0, visuals.window_stroke());
+    }
+
+    // Cover the corner where the rulers meet
+    painter.rect_filled(
+        egui::Rect::from_min_size(rect.min, egui::vec2(RULER_WIDTH, RULER_HEIGHT)),
+        0.0,
+        visuals.extreme_bg_color,
+    );
+}
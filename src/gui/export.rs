@@ -0,0 +1,657 @@
+//! The export menu, the export target editor, and the history of recent exports
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use indexmap::IndexMap;
+
+use crate::{
+    export::{self, ExportOnly},
+    project::{
+        ChrCompression, ExportContent, ExportFormat, ExportTarget, Level, MapStreaming,
+        MetatileField, MetatileLayout, Metatileset, SymbolCase, DEFAULT_SEGMENT,
+    },
+    Uid,
+};
+
+use super::{
+    background::BackgroundTask,
+    components::{send_error_notification, send_info_notification, send_warning_notification},
+    project_state::ProjectState,
+};
+
+/// The number of exports kept in the export history
+const HISTORY_LEN: usize = 20;
+
+/// The recent exports of each project from this session, by project file path, newest first
+///
+/// It is kept in the [`RootState`](super::RootState), and each [`ProjectState`] has a handle to
+/// it, so that exports running in the background can add to it.
+#[derive(Clone, Default)]
+pub struct ExportHistory(Arc<Mutex<HashMap<PathBuf, Vec<ExportRecord>>>>);
+
+impl ExportHistory {
+    /// Get the exports of the project at `project_path`, newest first
+    fn project(&self, project_path: &Path) -> Vec<ExportRecord> {
+        self.0
+            .lock()
+            .unwrap()
+            .get(project_path)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Add an export to the history of the project at `project_path`
+    fn push(&self, project_path: PathBuf, record: ExportRecord) {
+        let mut history = self.0.lock().unwrap();
+        let records = history.entry(project_path).or_default();
+        records.insert(0, record);
+        records.truncate(HISTORY_LEN);
+    }
+}
+
+/// An export in the export history
+#[derive(Clone)]
+struct ExportRecord {
+    target: ExportTarget,
+    /// The level or metatileset that was exported by itself, if any
+    only: Option<ExportOnly>,
+    out_dir: PathBuf,
+    time: SystemTime,
+    /// The warnings from the export, or the error that stopped it
+    result: Result<Vec<String>, String>,
+}
+
+/// Render the contents of the export menu
+pub fn export_menu(
+    ui: &mut egui::Ui,
+    project: &ProjectState,
+    show_project_settings: &mut bool,
+    show_history: &mut bool,
+) {
+    let mut export_target = None;
+
+    if project.data.export_targets.is_empty()
+        && ui
+            .button("⮫ Export")
+            .on_hover_text("Export using the default settings")
+            .clicked()
+    {
+        export_target = Some(ExportTarget::default());
+    }
+
+    for target in project.data.export_targets.values() {
+        if ui.button(format!("⮫ {}", target.name)).clicked() {
+            export_target = Some(target.clone());
+        }
+    }
+
+    if let Some(target) = export_target {
+        let out_dir = export::default_output_dir(&project.path, &target);
+        start_export(ui.ctx(), project, target, None, out_dir);
+        ui.close_menu();
+    }
+
+    ui.separator();
+
+    let last_export = project
+        .export_history
+        .project(&project.path)
+        .into_iter()
+        .next();
+    if let Some(record) = last_export {
+        if ui
+            .button(format!("⟲ Re-export `{}`", record.target.name))
+            .on_hover_text(format!(
+                "Export again with the same settings, to {}",
+                record.out_dir.display()
+            ))
+            .clicked()
+        {
+            start_export(
+                ui.ctx(),
+                project,
+                record.target,
+                record.only,
+                record.out_dir,
+            );
+            ui.close_menu();
+        }
+    }
+    if ui.button("🕘 Export History…").clicked() {
+        *show_history = true;
+        ui.close_menu();
+    }
+
+    if ui.button("⚙ Export Targets…").clicked() {
+        *show_project_settings = true;
+        ui.close_menu();
+    }
+}
+
+/// Render a button for each export target that exports only one level or metatileset, for use in
+/// context menus
+pub fn export_only_menu(ui: &mut egui::Ui, project: &ProjectState, only: ExportOnly) {
+    let mut targets = project
+        .data
+        .export_targets
+        .values()
+        .cloned()
+        .collect::<Vec<_>>();
+    if targets.is_empty() {
+        targets.push(ExportTarget::default());
+    }
+
+    for target in targets {
+        if ui.button(format!("⮫ {}", target.name)).clicked() {
+            let out_dir = export::default_output_dir(&project.path, &target);
+            start_export(ui.ctx(), project, target, Some(only), out_dir);
+            ui.close_menu();
+        }
+    }
+}
+
+/// Render the editor used to add, remove, and edit the project's export targets
+pub fn export_targets_gui(ui: &mut egui::Ui, project: &mut ProjectState) {
+    let mut removed = None;
+
+    for (id, target) in &mut project.data.export_targets {
+        ui.push_id(id, |ui| {
+            egui::Grid::new("export_target")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("Name: ");
+                    ui.text_edit_singleline(&mut target.name);
+                    ui.end_row();
+
+                    ui.label("Format: ");
+                    egui::ComboBox::from_id_source("format")
+                        .selected_text(target.format.name())
+                        .show_ui(ui, |ui| {
+                            for format in ExportFormat::ALL {
+                                ui.selectable_value(&mut target.format, format, format.name());
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label("Content: ");
+                    egui::ComboBox::from_id_source("content")
+                        .selected_text(target.content.name())
+                        .show_ui(ui, |ui| {
+                            for content in ExportContent::ALL {
+                                ui.selectable_value(&mut target.content, content, content.name());
+                            }
+                        })
+                        .response
+                        .on_hover_text(
+                            "Export every table, or only the attribute tables of the levels and \
+                            their HUDs, or only the metatileset pallets, for games that build \
+                            the rest of their data themselves",
+                        );
+                    ui.end_row();
+
+                    ui.label("Output Directory: ");
+                    let mut output_dir = target
+                        .output_dir
+                        .as_ref()
+                        .map(|x| x.display().to_string())
+                        .unwrap_or_default();
+                    if ui
+                        .add(
+                            egui::TextEdit::singleline(&mut output_dir)
+                                .hint_text(format!("export/{}", export::symbol_name(&target.name))),
+                        )
+                        .on_hover_text(
+                            "The directory to write the exported files to, relative to the \
+                            project file. This is saved in the project, so everyone exporting \
+                            the project gets the same output.",
+                        )
+                        .changed()
+                    {
+                        target.output_dir = if output_dir.is_empty() {
+                            None
+                        } else {
+                            Some(output_dir.into())
+                        };
+                    }
+                    ui.end_row();
+
+                    ui.label("Symbol Prefix: ");
+                    ui.add(egui::TextEdit::singleline(&mut target.symbol_prefix).hint_text("None"))
+                        .on_hover_text(
+                            "Prepended to the name of every exported table and constant, so \
+                            they don't collide with the names in the game's own code",
+                        );
+                    ui.end_row();
+
+                    ui.label("Symbol Case: ");
+                    egui::ComboBox::from_id_source("symbol_case")
+                        .selected_text(target.symbol_case.name())
+                        .show_ui(ui, |ui| {
+                            for case in SymbolCase::ALL {
+                                ui.selectable_value(&mut target.symbol_case, case, case.name());
+                            }
+                        })
+                        .response
+                        .on_hover_text(
+                            "The naming convention of the exported table names. Constants are \
+                            always upper-case.",
+                        );
+                    ui.end_row();
+
+                    ui.label("Comments: ");
+                    ui.checkbox(&mut target.comments, "").on_hover_text(
+                        "Annotate the exported files with comments describing the data",
+                    );
+                    ui.end_row();
+
+                    ui.label("Constants: ");
+                    ui.checkbox(&mut target.constants, "").on_hover_text(
+                        "Write named constants for the pallet colors, zone kinds, exit \
+                        kinds, and level indexes in the text formats",
+                    );
+                    ui.end_row();
+
+                    ui.label("Provenance: ");
+                    ui.add_enabled(
+                        target.format == ExportFormat::Ca65,
+                        egui::Checkbox::new(&mut target.provenance, ""),
+                    )
+                    .on_hover_text(
+                        "Comment each row of the pattern and metatile tables with the source \
+                        image, tile coordinates, and metatiles it came from",
+                    )
+                    .on_disabled_hover_text(
+                        "Provenance comments are only written in ca65 assembly",
+                    );
+                    ui.end_row();
+
+                    ui.label("Checksums: ");
+                    ui.add_enabled(
+                        matches!(target.format, ExportFormat::Ca65 | ExportFormat::C),
+                        egui::Checkbox::new(&mut target.checksums, ""),
+                    )
+                    .on_hover_text(
+                        "Write a CRC-32 constant for each table, so the game can check its data \
+                        at boot and build systems can notice stale data",
+                    )
+                    .on_disabled_hover_text("Checksums are only written in ca65 assembly and C");
+                    ui.end_row();
+
+                    ui.label("Tile Sources: ");
+                    ui.checkbox(&mut target.tile_sources, "").on_hover_text(
+                        "Export tables that trace each metatile and pattern table tile back to \
+                        the source image tile it came from, for external tools and debuggers",
+                    );
+                    ui.end_row();
+
+                    ui.label("Metatile Records: ").on_hover_text(
+                        "The bytes of each metatile in the metatile tables, for engines that \
+                        copy the tables straight into memory",
+                    );
+                    metatile_layout_gui(ui, &mut target.metatile_layout);
+                    ui.end_row();
+
+                    ui.label("CHR Compression: ");
+                    egui::ComboBox::from_id_source("chr_compression")
+                        .selected_text(target.chr_compression.name())
+                        .show_ui(ui, |ui| {
+                            for compression in ChrCompression::ALL {
+                                ui.selectable_value(
+                                    &mut target.chr_compression,
+                                    compression,
+                                    compression.name(),
+                                );
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label("CHR Streaming: ");
+                    ui.checkbox(&mut target.chr_streaming, "").on_hover_text(
+                        "Export the tiles needed by each screen of each level, with a \
+                        schedule for copying them into CHR-RAM during vblank",
+                    );
+                    ui.end_row();
+
+                    if target.chr_streaming {
+                        ui.label("NMI Budget: ");
+                        ui.add(
+                            egui::DragValue::new(&mut target.nmi_transfer_budget)
+                                .clamp_range(16..=2048)
+                                .speed(16)
+                                .suffix(" bytes"),
+                        )
+                        .on_hover_text("The number of CHR bytes the game can copy during one NMI");
+                        ui.end_row();
+                    }
+
+                    ui.label("Map Streaming: ");
+                    egui::ComboBox::from_id_source("map_streaming")
+                        .selected_text(target.map_streaming.name())
+                        .show_ui(ui, |ui| {
+                            for streaming in MapStreaming::ALL {
+                                ui.selectable_value(
+                                    &mut target.map_streaming,
+                                    streaming,
+                                    streaming.name(),
+                                );
+                            }
+                        })
+                        .response
+                        .on_hover_text(
+                            "Export each level as the nametable columns or rows that a scrolling \
+                            engine writes to the PPU, with the attribute bits that change with them",
+                        );
+                    ui.end_row();
+
+                    ui.label("DPCM Check: ");
+                    let mut dpcm_check = target.data_origin.is_some();
+                    if ui
+                        .checkbox(&mut dpcm_check, "")
+                        .on_hover_text(
+                            "Warn when the exported tables overlap the part of $C000-$FFFF that \
+                            is kept free for DPCM samples",
+                        )
+                        .changed()
+                    {
+                        target.data_origin = if dpcm_check { Some(0x8000) } else { None };
+                    }
+                    ui.end_row();
+
+                    if let Some(origin) = &mut target.data_origin {
+                        ui.label("Data Origin: ");
+                        ui.add(
+                            egui::DragValue::new(origin)
+                                .clamp_range(0x8000..=0xffff)
+                                .speed(64)
+                                .custom_formatter(|x, _| format!("${:04X}", x as u32)),
+                        )
+                        .on_hover_text(
+                            "The address in PRG-ROM that the tables are placed at, one after \
+                            another in the order they are exported",
+                        );
+                        ui.end_row();
+
+                        ui.label("DPCM Reserved: ");
+                        ui.add(
+                            egui::DragValue::new(&mut target.dpcm_reserved)
+                                .clamp_range(0..=0x4000)
+                                .speed(64)
+                                .suffix(" bytes"),
+                        )
+                        .on_hover_text(
+                            "The number of bytes from $C000 that are kept free for DPCM samples",
+                        );
+                        ui.end_row();
+                    }
+                });
+
+            if target.format == ExportFormat::Ca65 {
+                segments_gui(ui, target, &project.data.levels, &project.data.metatilesets);
+            }
+
+            if ui.button("🗑 Remove").clicked() {
+                removed = Some(*id);
+            }
+        });
+        ui.separator();
+    }
+
+    if let Some(id) = removed {
+        project.data.export_targets.shift_remove(&id);
+    }
+
+    if ui.button("➕ Add Target").clicked() {
+        let name = format!("target {}", project.data.export_targets.len() + 1);
+        project.data.export_targets.insert(
+            Uid::new(),
+            ExportTarget {
+                name,
+                ..Default::default()
+            },
+        );
+    }
+}
+
+/// Render the fields of the metatile records, which can be changed, reordered, added, and removed,
+/// and the size the records are padded to
+fn metatile_layout_gui(ui: &mut egui::Ui, layout: &mut MetatileLayout) {
+    ui.vertical(|ui| {
+        let mut moved_up = None;
+        let mut removed = None;
+        for (i, field) in layout.fields.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_source(("metatile_field", i))
+                    .selected_text(field.name())
+                    .show_ui(ui, |ui| {
+                        for option in MetatileField::ALL {
+                            ui.selectable_value(field, option, option.name());
+                        }
+                    });
+                if ui
+                    .add_enabled(i > 0, egui::Button::new("⏶"))
+                    .on_hover_text("Move the field up")
+                    .clicked()
+                {
+                    moved_up = Some(i);
+                }
+                if ui.button("🗙").on_hover_text("Remove the field").clicked() {
+                    removed = Some(i);
+                }
+            });
+        }
+        if let Some(i) = moved_up {
+            layout.fields.swap(i - 1, i);
+        }
+        if let Some(i) = removed {
+            layout.fields.remove(i);
+        }
+
+        ui.horizontal(|ui| {
+            if ui.button("➕ Add Field").clicked() {
+                layout.fields.push(MetatileField::Properties);
+            }
+            ui.label("Record Size: ");
+            ui.add(
+                egui::DragValue::new(&mut layout.record_size)
+                    .clamp_range(0..=64)
+                    .suffix(" bytes"),
+            )
+            .on_hover_text(
+                "Pad each record with zeros to this size, such as 8 so the game can find records \
+                with shifts. 0 leaves the records unpadded.",
+            );
+        });
+        ui.weak(format!("{} bytes per metatile", layout.record_size()));
+    });
+}
+
+/// Render the assembler segments that an export target places the tables of each metatileset and
+/// level in
+fn segments_gui(
+    ui: &mut egui::Ui,
+    target: &mut ExportTarget,
+    levels: &IndexMap<Uid<Level>, Level>,
+    metatilesets: &IndexMap<Uid<Metatileset>, Metatileset>,
+) {
+    egui::CollapsingHeader::new("Segments")
+        .id_source("segments")
+        .show(ui, |ui| {
+            ui.label(
+                "The ca65 segment each metatileset's and level's tables are placed in, such as \
+                BANK3, so the export fits a multi-bank linker config. Leave a segment empty to \
+                use the default.",
+            );
+            egui::Grid::new("segments").num_columns(2).show(ui, |ui| {
+                ui.label("Default: ");
+                ui.add(egui::TextEdit::singleline(&mut target.segment).hint_text(DEFAULT_SEGMENT))
+                    .on_hover_text(
+                        "The segment for the tables that aren't assigned to another one, \
+                        including the connection and world tables",
+                    );
+                ui.end_row();
+
+                for (id, metatileset) in metatilesets {
+                    ui.label(format!("Metatileset `{}`: ", metatileset.name));
+                    segment_edit(ui, &mut target.metatileset_segments, *id);
+                    ui.end_row();
+                }
+                for (id, level) in levels {
+                    ui.label(format!("Level `{}`: ", level.name));
+                    segment_edit(ui, &mut target.level_segments, *id);
+                    ui.end_row();
+                }
+            });
+        });
+}
+
+/// Render the segment assigned to one metatileset or level, removing the assignment when it is
+/// cleared
+fn segment_edit<T>(ui: &mut egui::Ui, segments: &mut IndexMap<Uid<T>, String>, id: Uid<T>) {
+    let mut segment = segments.get(&id).cloned().unwrap_or_default();
+    if ui
+        .add(egui::TextEdit::singleline(&mut segment).hint_text("Default"))
+        .changed()
+    {
+        if segment.is_empty() {
+            segments.shift_remove(&id);
+        } else {
+            segments.insert(id, segment);
+        }
+    }
+}
+
+/// Render the window listing the recent exports of the project, with buttons to run them again
+pub fn export_history_window(ctx: &egui::Context, project: &ProjectState, open: &mut bool) {
+    let mut rerun = None;
+
+    egui::Window::new("🕘 Export History")
+        .open(open)
+        .default_width(500.0)
+        .show(ctx, |ui| {
+            let history = project.export_history.project(&project.path);
+            if history.is_empty() {
+                ui.label("The project hasn't been exported yet");
+                return;
+            }
+
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                egui::Grid::new("export_history")
+                    .num_columns(5)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for heading in ["Target", "When", "Output", "Result", ""] {
+                            ui.strong(heading);
+                        }
+                        ui.end_row();
+
+                        for record in history {
+                            let name = match record.only.and_then(|x| x.name(&project.data)) {
+                                Some(only) => format!("{} ( only `{}` )", record.target.name, only),
+                                None => record.target.name.clone(),
+                            };
+                            ui.label(name)
+                                .on_hover_text(format!("{} format", record.target.format.name()));
+                            ui.label(time_ago(record.time));
+                            ui.label(record.out_dir.display().to_string());
+                            match &record.result {
+                                Ok(warnings) if warnings.is_empty() => {
+                                    ui.label("✔ Exported");
+                                }
+                                Ok(warnings) => {
+                                    ui.label(format!("⚠ {} warnings", warnings.len()))
+                                        .on_hover_text(warnings.join("\n"));
+                                }
+                                Err(e) => {
+                                    ui.label("❌ Failed").on_hover_text(e);
+                                }
+                            }
+                            if ui
+                                .button("⟲ Re-export")
+                                .on_hover_text("Export again with the same settings")
+                                .clicked()
+                            {
+                                rerun = Some(record);
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+        });
+
+    if let Some(record) = rerun {
+        start_export(ctx, project, record.target, record.only, record.out_dir);
+    }
+}
+
+/// Describe how long ago a time was, such as `5 min ago`
+fn time_ago(time: SystemTime) -> String {
+    let secs = time.elapsed().map(|x| x.as_secs()).unwrap_or_default();
+    match secs {
+        0..=59 => "Just now".into(),
+        60..=3599 => format!("{} min ago", secs / 60),
+        _ => format!("{} h ago", secs / 3600),
+    }
+}
+
+/// Export the project in a background thread, notifying the user when it's done
+fn start_export(
+    ctx: &egui::Context,
+    project: &ProjectState,
+    target: ExportTarget,
+    only: Option<ExportOnly>,
+    out_dir: PathBuf,
+) {
+    let data = project.data.clone();
+    let path = project.path.clone();
+    let history = project.export_history.clone();
+    let ctx = ctx.clone();
+
+    std::thread::spawn(move || {
+        let _task = BackgroundTask::start(format!("Exporting `{}`", target.name));
+        let result = export::export_project(&data, &path, &target, only, &out_dir);
+
+        history.push(
+            path,
+            ExportRecord {
+                target: target.clone(),
+                only,
+                out_dir: out_dir.clone(),
+                time: SystemTime::now(),
+                result: match &result {
+                    Ok(report) => Ok(report.warnings.clone()),
+                    Err(e) => Err(format!("{:#}", e)),
+                },
+            },
+        );
+
+        match result {
+            Ok(report) => {
+                for warning in report.warnings {
+                    send_warning_notification(&ctx, warning);
+                }
+                let mut message = match only.and_then(|x| x.name(&data)) {
+                    Some(only) => format!(
+                        "Exported `{}` with `{}` to {}",
+                        only,
+                        target.name,
+                        out_dir.display()
+                    ),
+                    None => format!("Exported `{}` to {}", target.name, out_dir.display()),
+                };
+                for stat in report.stats {
+                    message.push('\n');
+                    message.push_str(&stat);
+                }
+                send_info_notification(&ctx, &message);
+            }
+            Err(e) => send_error_notification(&ctx, format!("{:#}", e)),
+        }
+
+        ctx.request_repaint();
+    });
+}
@@ -0,0 +1,150 @@
+//! Gamepad input, for getting around the maps with a controller
+//!
+//! The gamepads are read on a background thread, which wakes the GUI up while they are being used.
+//! At the start of each frame the GUI takes what they did since the last frame and stores it in
+//! the egui context, where the maps tab picks it up.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use gilrs::{Axis, Button, EventType, Gilrs};
+use once_cell::sync::Lazy;
+
+use tracing as trc;
+
+/// How often the gamepads are read
+const POLL_INTERVAL: Duration = Duration::from_millis(16);
+
+/// How far the sticks have to be pushed before they do anything, from `0` to `1`
+const DEAD_ZONE: f32 = 0.2;
+
+/// Whether the gamepads are used to navigate the maps
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// What the gamepads did since the GUI last took it
+static PENDING: Lazy<Mutex<GamepadInput>> = Lazy::new(Default::default);
+
+/// What the gamepads did since the last frame
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct GamepadInput {
+    /// How fast to scroll the view, from the left sticks and D-pads, with each axis from `-1` to
+    /// `1`
+    pub scroll: egui::Vec2,
+    /// How fast to zoom in, or out when negative, from the triggers, from `-1` to `1`
+    pub zoom: f32,
+    /// The number of D-pad presses to the right and down, or left and up when negative, for
+    /// stepping between screens
+    pub step: (i32, i32),
+    /// The number of levels to switch forward, or back when negative, from the shoulder buttons
+    pub switch_level: i32,
+    /// Whether Select was pressed, to switch between screen mode and the open canvas
+    pub toggle_screens: bool,
+    /// Whether Start was pressed, to reset the view
+    pub reset_view: bool,
+}
+
+/// Set whether the gamepads are used to navigate the maps
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Start reading the gamepads in the background
+pub fn start(ctx: &egui::Context) {
+    let ctx = ctx.clone();
+    std::thread::spawn(move || {
+        let mut gilrs = match Gilrs::new() {
+            Ok(gilrs) => gilrs,
+            Err(e) => {
+                trc::warn!("Gamepads aren't supported: {}", e);
+                return;
+            }
+        };
+
+        loop {
+            let mut presses = GamepadInput::default();
+            while let Some(event) = gilrs.next_event() {
+                if let EventType::ButtonPressed(button, _) = event.event {
+                    match button {
+                        Button::DPadLeft => presses.step.0 -= 1,
+                        Button::DPadRight => presses.step.0 += 1,
+                        Button::DPadUp => presses.step.1 -= 1,
+                        Button::DPadDown => presses.step.1 += 1,
+                        Button::LeftTrigger => presses.switch_level -= 1,
+                        Button::RightTrigger => presses.switch_level += 1,
+                        Button::Select => presses.toggle_screens = true,
+                        Button::Start => presses.reset_view = true,
+                        _ => (),
+                    }
+                }
+            }
+
+            let mut scroll = egui::Vec2::ZERO;
+            let mut zoom = 0.0;
+            for (_, gamepad) in gilrs.gamepads() {
+                let stick = egui::Vec2::new(
+                    gamepad.value(Axis::LeftStickX),
+                    -gamepad.value(Axis::LeftStickY),
+                );
+                if stick.length() > DEAD_ZONE {
+                    scroll += stick;
+                }
+                let button = |button| gamepad.is_pressed(button) as i32 as f32;
+                scroll += egui::Vec2::new(
+                    button(Button::DPadRight) - button(Button::DPadLeft),
+                    button(Button::DPadDown) - button(Button::DPadUp),
+                );
+                let trigger = |button| gamepad.button_data(button).map_or(0.0, |x| x.value());
+                zoom += trigger(Button::RightTrigger2) - trigger(Button::LeftTrigger2);
+            }
+            if zoom.abs() < DEAD_ZONE {
+                zoom = 0.0;
+            }
+
+            if ENABLED.load(Ordering::Relaxed) {
+                let mut pending = PENDING.lock().unwrap();
+                pending.scroll = scroll.clamp(egui::Vec2::splat(-1.0), egui::Vec2::splat(1.0));
+                pending.zoom = zoom.clamp(-1.0, 1.0);
+                pending.step.0 += presses.step.0;
+                pending.step.1 += presses.step.1;
+                pending.switch_level += presses.switch_level;
+                pending.toggle_screens |= presses.toggle_screens;
+                pending.reset_view |= presses.reset_view;
+                if *pending != GamepadInput::default() {
+                    ctx.request_repaint();
+                }
+            }
+
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    });
+}
+
+fn input_id() -> egui::Id {
+    egui::Id::new("gamepad_input")
+}
+
+/// Take what the gamepads did since the last frame, and store it for this frame
+pub fn begin_frame(ctx: &egui::Context) {
+    let input = {
+        let mut pending = PENDING.lock().unwrap();
+        let input = *pending;
+        // The sticks and triggers keep their position until they are read again
+        *pending = GamepadInput {
+            scroll: pending.scroll,
+            zoom: pending.zoom,
+            ..Default::default()
+        };
+        input
+    };
+    ctx.data().insert_temp(input_id(), input);
+}
+
+/// What the gamepads did since the last frame
+pub fn input(ctx: &egui::Context) -> GamepadInput {
+    ctx.data().get_temp(input_id()).unwrap_or_default()
+}
@@ -0,0 +1,101 @@
+//! The debug window showing how much memory the loaded source images use
+
+use super::{
+    components::send_info_notification,
+    project_state::{ProjectState, SourceImageStatus},
+};
+
+/// How long a source texture can go without being drawn before it is trimmed automatically
+pub const AUTO_TRIM_SECONDS: f64 = 30.0;
+
+/// Render the memory usage window
+pub fn memory_usage_window(
+    ctx: &egui::Context,
+    project: &mut ProjectState,
+    open: &mut bool,
+    auto_trim: &mut bool,
+) {
+    let now = ctx.input().time;
+    let mut trim = false;
+
+    egui::Window::new("🐛 Memory Usage")
+        .open(open)
+        .resizable(false)
+        .show(ctx, |ui| {
+            let (mut cpu_total, mut gpu_total) = (0, 0);
+            egui::Grid::new("memory_usage")
+                .num_columns(5)
+                .striped(true)
+                .show(ui, |ui| {
+                    for heading in ["Source", "Size", "RAM", "Texture", "Last Drawn"] {
+                        ui.strong(heading);
+                    }
+                    ui.end_row();
+
+                    for image in project.source_images.values_mut() {
+                        ui.label(image.path.display().to_string());
+                        match image.data.get() {
+                            SourceImageStatus::Found(data) => {
+                                let texture = &data.texture;
+                                let [width, height] = texture.size();
+                                cpu_total += texture.cpu_bytes();
+                                gpu_total += texture.gpu_bytes();
+
+                                ui.label(format!("{}x{}", width, height));
+                                ui.label(format_bytes(texture.cpu_bytes()));
+                                ui.label(if texture.is_loaded() {
+                                    format_bytes(texture.gpu_bytes())
+                                } else {
+                                    "Trimmed".into()
+                                });
+                                ui.label(match texture.last_used() {
+                                    Some(time) if now - time < 1.0 => "Now".into(),
+                                    Some(time) => format!("{:.0}s ago", now - time),
+                                    None => "Never".into(),
+                                });
+                            }
+                            SourceImageStatus::Loading => {
+                                ui.label("Loading…");
+                            }
+                            SourceImageStatus::Error(_) => {
+                                ui.label("Not loaded");
+                            }
+                        }
+                        ui.end_row();
+                    }
+                });
+
+            ui.separator();
+            ui.label(format!(
+                "Total: {} of RAM and {} of textures",
+                format_bytes(cpu_total),
+                format_bytes(gpu_total)
+            ));
+            ui.label("These are estimates of the image data, not counting the rest of NESImg.");
+
+            ui.separator();
+            trim = ui
+                .button("✂ Trim Textures")
+                .on_hover_text("Free the textures of the sources that aren't shown in this tab")
+                .clicked();
+            ui.checkbox(auto_trim, "Trim automatically")
+                .on_hover_text(format!(
+                    "Free the textures of sources that haven't been shown for {} seconds",
+                    AUTO_TRIM_SECONDS
+                ));
+        });
+
+    if trim {
+        let trimmed = project.trim_source_textures(now);
+        send_info_notification(ctx, &format!("Trimmed {} source textures", trimmed));
+    }
+}
+
+/// Format a number of bytes with a binary unit
+fn format_bytes(bytes: usize) -> String {
+    match bytes {
+        0..=1023 => format!("{} B", bytes),
+        1024..=1048575 => format!("{:.1} KiB", bytes as f32 / 1024.0),
+        _ => format!("{:.1} MiB", bytes as f32 / 1048576.0),
+    }
+}
@@ -0,0 +1,278 @@
+//! The window for turning a mockup image into a tile sheet, metatileset, and level
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use indexmap::IndexMap;
+use watch::WatchReceiver;
+
+use crate::{
+    mockup::{convert_mockup, tile_sheet, MockupConversion},
+    project::{
+        Level, LevelMargin, LevelTile, Metatile, Metatileset, MetatilesetTile, Project, Tile,
+    },
+    Uid,
+};
+
+use super::{
+    components::{nes_color_swatch, send_error_notification, send_info_notification},
+    project_state::ProjectState,
+    util::{pick_file, FileFilter},
+};
+
+const WARNING_COLOR: egui::Color32 = egui::Color32::from_rgb(230, 200, 60);
+
+/// The state of the "Import Mockup…" window
+pub struct MockupImportWindow {
+    /// The mockup being loaded and sliced up
+    loading: WatchReceiver<Option<Result<(PathBuf, MockupConversion), String>>>,
+    /// The mockup waiting to be imported, if one has been loaded
+    mockup: Option<Mockup>,
+}
+
+impl Default for MockupImportWindow {
+    fn default() -> Self {
+        Self {
+            loading: watch::channel(None).1,
+            mockup: None,
+        }
+    }
+}
+
+struct Mockup {
+    path: PathBuf,
+    /// The name used for the new source image, metatileset, and level
+    name: String,
+    conversion: MockupConversion,
+}
+
+impl MockupImportWindow {
+    /// Ask the user for a mockup image to import
+    pub fn pick_mockup(&mut self) {
+        self.loading = pick_file(
+            &[FileFilter {
+                name: "Image",
+                extensions: &["png", "bmp", "gif"],
+            }],
+            |path| {
+                Some(
+                    convert_mockup(path)
+                        .map(|conversion| (path.to_owned(), conversion))
+                        .map_err(|e| format!("{:#}", e)),
+                )
+            },
+        );
+    }
+
+    /// Render the window, if a mockup has been picked
+    pub fn show(&mut self, ctx: &egui::Context, project: &mut ProjectState) {
+        match self.loading.get_if_new().flatten() {
+            Some(Ok((path, conversion))) => {
+                self.mockup = Some(Mockup {
+                    name: path
+                        .file_stem()
+                        .map(|x| x.to_string_lossy().into_owned())
+                        .unwrap_or_else(|| "Mockup".into()),
+                    path,
+                    conversion,
+                });
+            }
+            Some(Err(e)) => send_error_notification(ctx, e),
+            None => (),
+        }
+
+        let mockup = if let Some(mockup) = &mut self.mockup {
+            mockup
+        } else {
+            return;
+        };
+
+        let mut open = true;
+        let mut import = false;
+        egui::Window::new("Import Mockup")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                import = mockup_gui(ui, mockup);
+            });
+
+        if import {
+            match import_mockup(project, mockup) {
+                Ok(()) => send_info_notification(
+                    ctx,
+                    &format!("Imported mockup as level `{}`", mockup.name.trim()),
+                ),
+                Err(e) => send_error_notification(ctx, format!("{:#}", e)),
+            }
+        }
+        if !open || import {
+            self.mockup = None;
+        }
+    }
+}
+
+/// Show the summary of a mockup, returning whether the import button was clicked
+fn mockup_gui(ui: &mut egui::Ui, mockup: &mut Mockup) -> bool {
+    let conversion = &mockup.conversion;
+    let unique_blocks = conversion.unique_blocks().len();
+
+    ui.label(format!("`{}`", mockup.path.display()));
+    ui.separator();
+
+    egui::Grid::new("mockup_summary")
+        .num_columns(2)
+        .show(ui, |ui| {
+            ui.label("Name");
+            ui.text_edit_singleline(&mut mockup.name);
+            ui.end_row();
+
+            ui.label("Size");
+            ui.label(format!(
+                "{}x{} metatiles",
+                conversion.width, conversion.height
+            ));
+            ui.end_row();
+
+            ui.label("Colors");
+            ui.label(conversion.color_count.to_string());
+            ui.end_row();
+
+            ui.label("Unique Tiles");
+            count_label(ui, conversion.tiles.len(), 256, "pattern table");
+            ui.end_row();
+
+            ui.label("Metatiles");
+            count_label(ui, unique_blocks, 256, "metatileset");
+            ui.end_row();
+
+            ui.label("Pallet");
+            ui.vertical(|ui| {
+                for sub_pallet in conversion.pallet.get_sub_pallets() {
+                    ui.horizontal(|ui| {
+                        for color in sub_pallet {
+                            nes_color_swatch(ui, color);
+                        }
+                    });
+                }
+            });
+            ui.end_row();
+        });
+
+    if conversion.unfit_block_count > 0 {
+        ui.colored_label(
+            WARNING_COLOR,
+            format!(
+                "⚠ {} blocks use more colors than fit in one sub-pallet and will be recolored with \
+                the closest colors available",
+                conversion.unfit_block_count
+            ),
+        );
+    }
+
+    ui.separator();
+    ui.label(
+        "This will create a source image with the unique tiles, a metatileset with the suggested \
+        pallet, and a level with a map matching the mockup.",
+    );
+    ui.add_enabled(
+        !mockup.name.trim().is_empty(),
+        egui::Button::new("🖼 Import"),
+    )
+    .clicked()
+}
+
+/// Show a count, with a warning if it goes over what fits in the NES hardware or export format
+fn count_label(ui: &mut egui::Ui, count: usize, max: usize, what: &str) {
+    if count > max {
+        ui.colored_label(WARNING_COLOR, format!("⚠ {}", count))
+            .on_hover_text(format!("Only {} fit in a {}", max, what));
+    } else {
+        ui.label(count.to_string());
+    }
+}
+
+/// Save the tile sheet of a mockup next to the project and add the source, metatiles,
+/// metatileset, and level for it
+fn import_mockup(project: &mut ProjectState, mockup: &Mockup) -> anyhow::Result<()> {
+    let conversion = &mockup.conversion;
+    let name = mockup.name.trim();
+
+    let (sheet, positions) = tile_sheet(&conversion.tiles);
+    let sheet_path = unused_path(
+        project.path.parent().unwrap_or_else(|| Path::new(".")),
+        &format!("{}_tiles", name),
+        "png",
+    );
+    sheet
+        .save(&sheet_path)
+        .with_context(|| format!("Could not save tile sheet `{}`", sheet_path.display()))?;
+    let source_id = project.add_source(sheet_path);
+
+    let data: &mut Project = &mut project.data;
+    let mut metatileset = Metatileset {
+        name: name.to_owned(),
+        pallet: conversion.pallet.clone(),
+        ..Default::default()
+    };
+    let mut metatileset_tiles = IndexMap::new();
+    for block in conversion.unique_blocks() {
+        let metatile_id = Uid::new();
+        data.metatiles.insert(
+            metatile_id,
+            Metatile {
+                tiles: block.tiles.map(|idx| {
+                    let (x, y) = positions[idx];
+                    Some(Tile { source_id, x, y })
+                }),
+            },
+        );
+
+        let tile_id = Uid::new();
+        metatileset.tiles.insert(
+            tile_id,
+            MetatilesetTile {
+                metatile_id,
+                sub_pallet_idx: block.sub_pallet,
+                properties: 0,
+            },
+        );
+        metatileset_tiles.insert(block, tile_id);
+    }
+    let metatileset_id = Uid::new();
+    data.metatilesets.insert(metatileset_id, metatileset);
+
+    let mut level = Level {
+        name: name.to_owned(),
+        metatileset_id,
+        margin: LevelMargin {
+            top: 0,
+            right: conversion.width as i32,
+            bottom: conversion.height as i32,
+            left: 0,
+        },
+        ..Default::default()
+    };
+    for (i, block) in conversion.blocks.iter().enumerate() {
+        level.tiles.insert(
+            ((i % conversion.width) as i32, (i / conversion.width) as i32),
+            LevelTile {
+                metatileset_tile_id: metatileset_tiles[block],
+            },
+        );
+    }
+    data.levels.insert(Uid::new(), level);
+
+    Ok(())
+}
+
+/// Find a path in `dir` for a file named `stem` with `extension` that doesn't exist yet, adding a
+/// number to the name if needed
+pub fn unused_path(dir: &Path, stem: &str, extension: &str) -> PathBuf {
+    (1..)
+        .map(|i| match i {
+            1 => dir.join(format!("{}.{}", stem, extension)),
+            i => dir.join(format!("{}_{}.{}", stem, i, extension)),
+        })
+        .find(|path| !path.exists())
+        .expect("Infinite candidates")
+}
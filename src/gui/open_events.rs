@@ -0,0 +1,86 @@
+//! Receiving the projects the OS asks NESImg to open while it's running
+//!
+//! On Linux and Windows, opening a `.nesimg` file with NESImg passes its path on the commandline,
+//! which is handled like `nesimg gui <path>`. macOS instead sends an "open documents" event to the
+//! app, which is received here by adding `application:openFiles:` to the app delegate.
+
+use std::{path::PathBuf, sync::Mutex};
+
+use once_cell::sync::{Lazy, OnceCell};
+
+/// The files the OS asked to open, waiting to be picked up by the GUI
+static OPENED_FILES: Lazy<Mutex<Vec<PathBuf>>> = Lazy::new(Default::default);
+
+/// The GUI context, used to wake the GUI up when a file is opened
+static CONTEXT: OnceCell<egui::Context> = OnceCell::new();
+
+/// Start receiving open file events from the OS
+pub fn install(ctx: &egui::Context) {
+    CONTEXT.set(ctx.clone()).ok();
+
+    #[cfg(target_os = "macos")]
+    macos::install();
+}
+
+/// Take the files the OS has asked to open since the last call
+pub fn take_opened_files() -> Vec<PathBuf> {
+    std::mem::take(&mut *OPENED_FILES.lock().unwrap())
+}
+
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn file_opened(path: PathBuf) {
+    OPENED_FILES.lock().unwrap().push(path);
+    if let Some(ctx) = CONTEXT.get() {
+        ctx.request_repaint();
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use std::{ffi::CStr, os::raw::c_char, path::PathBuf};
+
+    use objc::{
+        class, msg_send,
+        runtime::{class_addMethod, object_getClass, Class, Imp, Object, Sel},
+        sel, sel_impl,
+    };
+
+    /// `NSApplicationDelegateReplySuccess`
+    const REPLY_SUCCESS: usize = 0;
+
+    /// Add `application:openFiles:` to the class of the app delegate created by the windowing
+    /// library, which doesn't handle it. This must be called before the app finishes launching,
+    /// so the files that launched the app are received too.
+    pub fn install() {
+        unsafe {
+            let app: *mut Object = msg_send![class!(NSApplication), sharedApplication];
+            let delegate: *mut Object = msg_send![app, delegate];
+            if delegate.is_null() {
+                return;
+            }
+
+            let open_files: extern "C" fn(&Object, Sel, *mut Object, *mut Object) = open_files;
+            class_addMethod(
+                object_getClass(delegate) as *mut Class,
+                sel!(application:openFiles:),
+                std::mem::transmute::<_, Imp>(open_files),
+                b"v@:@@\0".as_ptr() as *const c_char,
+            );
+        }
+    }
+
+    extern "C" fn open_files(_this: &Object, _sel: Sel, app: *mut Object, files: *mut Object) {
+        unsafe {
+            let count: usize = msg_send![files, count];
+            for i in 0..count {
+                let file: *mut Object = msg_send![files, objectAtIndex: i];
+                let utf8: *const c_char = msg_send![file, UTF8String];
+                if !utf8.is_null() {
+                    let path = CStr::from_ptr(utf8).to_string_lossy().into_owned();
+                    super::file_opened(PathBuf::from(path));
+                }
+            }
+            let _: () = msg_send![app, replyToOpenOrPrint: REPLY_SUCCESS];
+        }
+    }
+}
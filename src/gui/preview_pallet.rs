@@ -0,0 +1,255 @@
+//! The colors used to show NES colors in the GUI, which can be generated from NTSC settings to
+//! match the emulator or TV the game is played on, or loaded from an emulator pallet file
+
+use std::{path::Path, sync::RwLock};
+
+use egui::Color32;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use watch::WatchReceiver;
+
+use crate::{
+    constants::{NES_COLOR_NAMES, NES_PALLET},
+    ntsc::{generate_pallet, NtscParams},
+};
+
+use super::{
+    accessibility,
+    components::{color_button, send_error_notification},
+    util::{pick_file, FileFilter},
+};
+
+/// The length of an emulator pallet file with one RGB color for each of the 64 NES colors
+const PALLET_FILE_LEN: usize = 64 * 3;
+
+/// The length of an emulator pallet file that also has the colors for each of the 8 combinations
+/// of the color emphasis bits
+const EMPHASIS_PALLET_FILE_LEN: usize = PALLET_FILE_LEN * 8;
+
+/// The emulator pallet files that custom colors are loaded from
+const PALLET_FILE_FILTERS: &[FileFilter] = &[FileFilter {
+    name: "Emulator Pallet",
+    extensions: &["pal"],
+}];
+
+/// Where the colors shown in the GUI come from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum PreviewPallet {
+    /// The built-in NES pallet
+    Builtin,
+    /// A pallet generated by simulating the NTSC signal
+    Ntsc(NtscParams),
+    /// A pallet loaded from an emulator pallet file
+    Custom(CustomPallet),
+}
+
+/// The colors loaded from an emulator pallet file, kept with the preferences so the file can be
+/// moved or deleted afterwards
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CustomPallet {
+    /// The name of the file the colors were loaded from
+    pub name: String,
+    /// The RGB color of each of the 64 NES colors
+    pub colors: Vec<[u8; 3]>,
+}
+
+impl CustomPallet {
+    /// Load the colors from an emulator pallet file
+    fn load(path: &Path) -> anyhow::Result<Self> {
+        let bytes = std::fs::read(path)?;
+        Ok(Self {
+            name: path
+                .file_name()
+                .map(|x| x.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            colors: decode_pallet_file(&bytes)?,
+        })
+    }
+}
+
+/// Decode the 64 colors of an emulator pallet file, with 3 bytes of RGB for each color. Files
+/// with the colors for every combination of the emphasis bits only use the first 64, since the
+/// emphasis is previewed separately.
+fn decode_pallet_file(bytes: &[u8]) -> anyhow::Result<Vec<[u8; 3]>> {
+    if bytes.len() != PALLET_FILE_LEN && bytes.len() != EMPHASIS_PALLET_FILE_LEN {
+        anyhow::bail!(
+            "Pallet file is {} bytes, but should be {} or {} bytes",
+            bytes.len(),
+            PALLET_FILE_LEN,
+            EMPHASIS_PALLET_FILE_LEN
+        );
+    }
+    Ok(bytes[..PALLET_FILE_LEN]
+        .chunks_exact(3)
+        .map(|x| [x[0], x[1], x[2]])
+        .collect())
+}
+
+impl Default for PreviewPallet {
+    fn default() -> Self {
+        PreviewPallet::Builtin
+    }
+}
+
+/// The colors currently used to show NES colors in the GUI
+static PREVIEW_COLORS: Lazy<RwLock<[Color32; 64]>> = Lazy::new(|| RwLock::new(*NES_PALLET));
+
+/// Get the color used to show a NES color in the GUI
+pub fn preview_color(idx: u32) -> Color32 {
+    PREVIEW_COLORS.read().unwrap()[idx.min(63) as usize]
+}
+
+/// Switch the colors used to show NES colors in the GUI
+pub fn set_preview_pallet(pallet: &PreviewPallet) {
+    *PREVIEW_COLORS.write().unwrap() = match pallet {
+        PreviewPallet::Builtin => *NES_PALLET,
+        PreviewPallet::Ntsc(params) => generate_pallet(params),
+        PreviewPallet::Custom(custom) => {
+            let mut colors = *NES_PALLET;
+            for (color, [r, g, b]) in colors.iter_mut().zip(&custom.colors) {
+                *color = Color32::from_rgb(*r, *g, *b);
+            }
+            colors
+        }
+    };
+}
+
+/// The window used to pick and tune the preview pallet
+pub struct PreviewPalletWindow {
+    pub open: bool,
+    /// The emulator pallet file being loaded, or the error loading it
+    loading: WatchReceiver<Option<Result<CustomPallet, String>>>,
+}
+
+impl Default for PreviewPalletWindow {
+    fn default() -> Self {
+        Self {
+            open: false,
+            loading: watch::channel(None).1,
+        }
+    }
+}
+
+impl PreviewPalletWindow {
+    /// Render the window, if it is open
+    pub fn show(&mut self, ctx: &egui::Context, pallet: &mut PreviewPallet) {
+        let before = pallet.clone();
+
+        match self.loading.get_if_new().flatten() {
+            Some(Ok(custom)) => *pallet = PreviewPallet::Custom(custom),
+            Some(Err(e)) => send_error_notification(ctx, e),
+            None => (),
+        }
+
+        let mut load_file = false;
+        egui::Window::new("🎨 Preview Pallet")
+            .open(&mut self.open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(
+                    "The colors used to show the NES pallet in NESImg. Exports aren't affected, \
+                    they only use the pallet indexes.",
+                );
+                ui.horizontal(|ui| {
+                    ui.radio_value(pallet, PreviewPallet::Builtin, "Built-in");
+                    if ui
+                        .radio(matches!(pallet, PreviewPallet::Ntsc(_)), "NTSC Generator")
+                        .on_hover_text("Generate the colors by simulating the NES video signal")
+                        .clicked()
+                        && !matches!(pallet, PreviewPallet::Ntsc(_))
+                    {
+                        *pallet = PreviewPallet::Ntsc(Default::default());
+                    }
+                    if ui
+                        .radio(matches!(pallet, PreviewPallet::Custom(_)), "Custom File")
+                        .on_hover_text(
+                            "Use the colors from an emulator pallet file, such as the one your \
+                            team tests with",
+                        )
+                        .clicked()
+                        && !matches!(pallet, PreviewPallet::Custom(_))
+                    {
+                        load_file = true;
+                    }
+                });
+
+                if let PreviewPallet::Custom(custom) = pallet {
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        ui.label(format!("Loaded from {}", custom.name));
+                        if ui
+                            .button("📂 Load…")
+                            .on_hover_text("Load the colors from a different pallet file")
+                            .clicked()
+                        {
+                            load_file = true;
+                        }
+                    });
+                }
+
+                if let PreviewPallet::Ntsc(params) = pallet {
+                    ui.separator();
+                    egui::Grid::new("ntsc_params")
+                        .num_columns(2)
+                        .show(ui, |ui| {
+                            ui.label("Hue: ");
+                            ui.add(egui::Slider::new(&mut params.hue, -30.0..=30.0).suffix("°"));
+                            ui.end_row();
+
+                            ui.label("Saturation: ");
+                            ui.add(egui::Slider::new(&mut params.saturation, 0.0..=2.0));
+                            ui.end_row();
+
+                            ui.label("Contrast: ");
+                            ui.add(egui::Slider::new(&mut params.contrast, 0.5..=1.5));
+                            ui.end_row();
+
+                            ui.label("Brightness: ");
+                            ui.add(egui::Slider::new(&mut params.brightness, -0.5..=0.5));
+                            ui.end_row();
+
+                            ui.label("Gamma: ");
+                            ui.add(egui::Slider::new(&mut params.gamma, 1.0..=3.0))
+                                .on_hover_text("The gamma of the TV being matched");
+                            ui.end_row();
+                        });
+                    if ui.button("⟲ Reset").clicked() {
+                        *params = Default::default();
+                    }
+                }
+
+                ui.separator();
+                if ui
+                    .add_enabled(
+                        *pallet != PreviewPallet::Builtin,
+                        egui::Button::new("⟲ Reset to Built-in"),
+                    )
+                    .clicked()
+                {
+                    *pallet = PreviewPallet::Builtin;
+                }
+                ui.spacing_mut().item_spacing = egui::Vec2::splat(1.0);
+                for row in 0..4 {
+                    ui.horizontal(|ui| {
+                        for i in row * 16..row * 16 + 16 {
+                            let response = color_button(ui, preview_color(i));
+                            accessibility::color_button(&response, false, || {
+                                format!("{} ${:02X}", NES_COLOR_NAMES[i as usize], i)
+                            });
+                            response.on_hover_text(format!("${:02X}", i));
+                        }
+                    });
+                }
+            });
+
+        if load_file {
+            self.loading = pick_file(PALLET_FILE_FILTERS, |path| {
+                Some(CustomPallet::load(path).map_err(|e| format!("{:#}", e)))
+            });
+        }
+
+        if *pallet != before {
+            set_preview_pallet(pallet);
+        }
+    }
+}
@@ -0,0 +1,345 @@
+//! The problems window, which lists pallet style lints with quick fixes for them, and screens
+//! that use more tiles than fit in the pattern table
+
+use std::collections::HashMap;
+
+use indexmap::IndexSet;
+
+use crate::{
+    export::{screen_tile_counts, EMPTY_METATILE},
+    project::{Metatile, Metatileset},
+    Uid,
+};
+
+use super::project_state::{ProjectState, SourceImageStatus};
+
+/// A problem found in the project
+struct Problem {
+    message: String,
+    /// The change that resolves the problem, if it can be fixed automatically
+    fix: Option<(Uid<Metatileset>, QuickFix)>,
+}
+
+/// A change to a metatileset that resolves a problem
+#[derive(Copy, Clone, Debug)]
+enum QuickFix {
+    /// Replace a sub-pallet color with the backdrop color, marking it as free
+    ClearColor { sub_pallet_idx: usize, slot: usize },
+    /// Replace all three colors of a sub-pallet with the backdrop color
+    ClearSubPallet { sub_pallet_idx: usize },
+    /// Move the tiles using one sub-pallet to another sub-pallet that looks the same for them
+    MergeSubPallets { from: usize, into: usize },
+}
+
+impl QuickFix {
+    fn label(&self) -> &'static str {
+        match self {
+            QuickFix::ClearColor { .. } => "Clear Color",
+            QuickFix::ClearSubPallet { .. } => "Clear Sub-Pallet",
+            QuickFix::MergeSubPallets { .. } => "Merge",
+        }
+    }
+
+    fn hover_text(&self) -> String {
+        match self {
+            QuickFix::ClearColor { .. } => {
+                "Replace the color with the backdrop color, to free it up for something else".into()
+            }
+            QuickFix::ClearSubPallet { .. } => {
+                "Replace the sub-pallet's colors with the backdrop color, to free it up for \
+                something else"
+                    .into()
+            }
+            QuickFix::MergeSubPallets { from, into } => format!(
+                "Switch the metatiles using sub-pallet {} to sub-pallet {}",
+                from, into
+            ),
+        }
+    }
+
+    fn apply(&self, metatileset: &mut Metatileset) {
+        let backdrop = metatileset.pallet.colors[0];
+        match *self {
+            QuickFix::ClearColor {
+                sub_pallet_idx,
+                slot,
+            } => metatileset.pallet.colors[sub_pallet_idx * 3 + slot] = backdrop,
+            QuickFix::ClearSubPallet { sub_pallet_idx } => {
+                for slot in 1..4 {
+                    metatileset.pallet.colors[sub_pallet_idx * 3 + slot] = backdrop;
+                }
+            }
+            QuickFix::MergeSubPallets { from, into } => {
+                for tile in metatileset.tiles.values_mut() {
+                    if tile.sub_pallet_idx == from {
+                        tile.sub_pallet_idx = into;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Render the problems window
+pub fn problems_window(ctx: &egui::Context, project: &mut ProjectState, open: &mut bool) {
+    if !*open {
+        return;
+    }
+
+    let loading = project
+        .source_images
+        .values_mut()
+        .any(|x| matches!(x.data.get(), SourceImageStatus::Loading));
+    let problems = if loading {
+        Vec::new()
+    } else {
+        find_problems(project)
+    };
+
+    let mut fix = None;
+    egui::Window::new("⚠ Problems")
+        .open(open)
+        .default_width(400.0)
+        .show(ctx, |ui| {
+            if loading {
+                ui.label("Loading source images…");
+                return;
+            }
+            if problems.is_empty() {
+                ui.label("No problems found");
+                return;
+            }
+
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                egui::Grid::new("problems")
+                    .num_columns(2)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for problem in &problems {
+                            ui.label(&problem.message);
+                            match problem.fix {
+                                Some((metatileset_id, quick_fix)) => {
+                                    if ui
+                                        .button(quick_fix.label())
+                                        .on_hover_text(quick_fix.hover_text())
+                                        .clicked()
+                                    {
+                                        fix = Some((metatileset_id, quick_fix));
+                                    }
+                                }
+                                None => {
+                                    ui.label("");
+                                }
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+        });
+
+    if let Some((metatileset_id, fix)) = fix {
+        if let Some(metatileset) = project.data.metatilesets.get_mut(&metatileset_id) {
+            fix.apply(metatileset);
+        }
+    }
+}
+
+/// Find the unused pallet colors, the sub-pallets that could be merged in every metatileset, and
+/// the screens that use too many tiles
+fn find_problems(project: &mut ProjectState) -> Vec<Problem> {
+    let mut problems = find_pallet_problems(project);
+    problems.extend(find_screen_problems(project));
+    problems
+}
+
+/// Find the unused pallet colors and the sub-pallets that could be merged in every metatileset
+fn find_pallet_problems(project: &mut ProjectState) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let mut metatile_slots = HashMap::new();
+
+    let metatileset_ids = project
+        .data
+        .metatilesets
+        .keys()
+        .cloned()
+        .collect::<Vec<_>>();
+    for metatileset_id in metatileset_ids {
+        let tiles = project.data.metatilesets[&metatileset_id]
+            .tiles
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+
+        // Find which of the colors of each sub-pallet are drawn by its metatiles
+        let mut used = [[false; 4]; 4];
+        let mut tile_counts = [0; 4];
+        for tile in tiles {
+            if tile.sub_pallet_idx >= 4 {
+                continue;
+            }
+            let slots = *metatile_slots
+                .entry(tile.metatile_id)
+                .or_insert_with(|| used_slots(project, tile.metatile_id));
+            for (used, slot_used) in used[tile.sub_pallet_idx].iter_mut().zip(slots) {
+                *used |= slot_used;
+            }
+            tile_counts[tile.sub_pallet_idx] += 1;
+        }
+
+        let metatileset = &project.data.metatilesets[&metatileset_id];
+        let sub_pallets = metatileset.pallet.get_sub_pallets();
+        let backdrop = metatileset.pallet.colors[0];
+        let mut push = |message: String, fix: QuickFix| {
+            problems.push(Problem {
+                message: format!("{}: {}", metatileset.name, message),
+                fix: Some((metatileset_id, fix)),
+            })
+        };
+
+        for (idx, colors) in sub_pallets.iter().enumerate() {
+            // The game relies on the colors of locked sub-pallets, used or not
+            if metatileset.locked_sub_pallets[idx] {
+                continue;
+            }
+            if tile_counts[idx] == 0 {
+                if colors[1..].iter().any(|&x| x != backdrop) {
+                    push(
+                        format!("Sub-pallet {} isn't used by any metatiles", idx),
+                        QuickFix::ClearSubPallet {
+                            sub_pallet_idx: idx,
+                        },
+                    );
+                }
+                continue;
+            }
+
+            for slot in 1..4 {
+                if !used[idx][slot] && colors[slot] != backdrop {
+                    push(
+                        format!(
+                            "Color {} (${:02X}) of sub-pallet {} isn't used by any of its metatiles",
+                            slot, colors[slot], idx
+                        ),
+                        QuickFix::ClearColor {
+                            sub_pallet_idx: idx,
+                            slot,
+                        },
+                    );
+                }
+            }
+        }
+
+        for a in 0..4 {
+            for b in a + 1..4 {
+                if tile_counts[a] == 0 || tile_counts[b] == 0 {
+                    continue;
+                }
+                let differing = (1..4)
+                    .filter(|&slot| sub_pallets[a][slot] != sub_pallets[b][slot])
+                    .collect::<Vec<_>>();
+
+                // The sub-pallets can be merged if the tiles of one of them don't draw the color
+                // that is different
+                let (from, into) = match differing[..] {
+                    [] => (b, a),
+                    [slot] if !used[b][slot] => (b, a),
+                    [slot] if !used[a][slot] => (a, b),
+                    _ => continue,
+                };
+                push(
+                    if differing.is_empty() {
+                        format!("Sub-pallets {} and {} are the same", a, b)
+                    } else {
+                        format!(
+                            "Sub-pallets {} and {} only differ by one color, and sub-pallet {} \
+                            doesn't use it",
+                            a, b, from
+                        )
+                    },
+                    QuickFix::MergeSubPallets { from, into },
+                );
+            }
+        }
+    }
+
+    problems
+}
+
+/// Find the screens of each level that use more unique tiles than the CHR bank profile has room
+/// for
+fn find_screen_problems(project: &mut ProjectState) -> Vec<Problem> {
+    let limit = project.data.chr_banks.screen_tile_limit();
+    let mut problems = Vec::new();
+
+    // The unique tile index of each quadrant of each metatileset tile, by their pixels, like the
+    // pattern table that is exported for the metatileset
+    let mut metatileset_chr = HashMap::new();
+    let levels = project.data.levels.values().cloned().collect::<Vec<_>>();
+    for level in levels {
+        let metatileset = match project.data.metatilesets.get(&level.metatileset_id) {
+            Some(metatileset) => metatileset.clone(),
+            None => continue,
+        };
+        let chr = metatileset_chr
+            .entry(level.metatileset_id)
+            .or_insert_with(|| {
+                let mut tiles = IndexSet::new();
+                let mut chr = Vec::with_capacity(metatileset.tiles.len());
+                for tile in metatileset.tiles.values() {
+                    let quadrants = project
+                        .data
+                        .metatiles
+                        .get(&tile.metatile_id)
+                        .map(|x| x.tiles.clone())
+                        .unwrap_or_default();
+                    let mut indexes = [0; 4];
+                    for (index, quadrant) in indexes.iter_mut().zip(quadrants) {
+                        let pixels = quadrant
+                            .and_then(|x| project.tile_pixels(&x))
+                            .unwrap_or([0; 64]);
+                        *index = tiles.insert_full(pixels).0.min(255) as u8;
+                    }
+                    chr.push(indexes);
+                }
+                chr
+            });
+
+        let margin = level.margin;
+        let mut map = Vec::with_capacity((margin.width() * margin.height()).max(0) as usize);
+        for y in -margin.top..margin.bottom {
+            for x in -margin.left..margin.right {
+                map.push(
+                    level
+                        .tiles
+                        .get(&(x, y))
+                        .and_then(|tile| metatileset.tiles.get_index_of(&tile.metatileset_tile_id))
+                        .map(|idx| idx.min(EMPTY_METATILE as usize - 1) as u8)
+                        .unwrap_or(EMPTY_METATILE),
+                );
+            }
+        }
+
+        for ((x, y), count) in screen_tile_counts(&map, margin.width().max(0) as usize, chr) {
+            if count > limit {
+                problems.push(Problem {
+                    message: format!(
+                        "Screen ({}, {}) of level `{}` uses {} unique tiles, but only {} fit",
+                        x, y, level.name, count, limit
+                    ),
+                    fix: None,
+                });
+            }
+        }
+    }
+
+    problems
+}
+
+/// Get which of the four sub-pallet colors are drawn by a metatile
+fn used_slots(project: &mut ProjectState, metatile_id: Uid<Metatile>) -> [bool; 4] {
+    let mut slots = [false; 4];
+    for idx in project.metatile_pixels(metatile_id).into_iter().flatten() {
+        slots[idx as usize % 4] = true;
+    }
+    slots
+}
@@ -0,0 +1,156 @@
+//! The window that gathers the settings that apply to the whole project
+
+use egui::DragValue;
+
+use crate::project::{ChrBankProfile, Overscan};
+
+use super::{export::export_targets_gui, project_state::ProjectState};
+
+/// Render the project settings window
+pub fn project_settings_window(ctx: &egui::Context, project: &mut ProjectState, open: &mut bool) {
+    egui::Window::new("Project Settings")
+        .open(open)
+        .resizable(false)
+        .show(ctx, |ui| {
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                egui::CollapsingHeader::new("📁 Project")
+                    .default_open(true)
+                    .show(ui, |ui| project_gui(ui, project));
+
+                egui::CollapsingHeader::new("▭ Overscan")
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        ui.label(
+                            "The pixels at each edge of the screen that TVs may crop, shown as \
+                            guides in the Maps tab.",
+                        );
+                        overscan_grid(ui, &mut project.data.overscan);
+                    });
+
+                egui::CollapsingHeader::new("🧱 CHR Banks")
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        ui.label(
+                            "How the game banks background tiles, used to check that each screen \
+                            of a level fits in the pattern table.",
+                        );
+                        chr_banks_grid(ui, &mut project.data.chr_banks);
+                    });
+
+                egui::CollapsingHeader::new("🏷 Metatile Properties")
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        ui.label(
+                            "Names for the bits of the property byte of each metatile, such as \
+                            \"solid\". Named bits are shown as checkboxes in the Metatilesets \
+                            tab, and exported as constants.",
+                        );
+                        property_flags_grid(ui, &mut project.data.property_flags);
+                    });
+
+                egui::CollapsingHeader::new("⮫ Export Targets")
+                    .default_open(true)
+                    .show(ui, |ui| export_targets_gui(ui, project));
+            });
+        });
+}
+
+/// Show where the project is saved and how much is in it
+fn project_gui(ui: &mut egui::Ui, project: &ProjectState) {
+    let data = &project.data;
+    egui::Grid::new("project_summary")
+        .num_columns(2)
+        .show(ui, |ui| {
+            ui.label("File: ");
+            ui.label(project.path.display().to_string());
+            ui.end_row();
+
+            for (label, count) in [
+                ("Sources: ", data.sources.len()),
+                ("Metatiles: ", data.metatiles.len()),
+                ("Metatilesets: ", data.metatilesets.len()),
+                ("Levels: ", data.levels.len()),
+                ("Tile Animations: ", data.tile_animations.len()),
+            ] {
+                ui.label(label);
+                ui.label(count.to_string());
+                ui.end_row();
+            }
+        });
+}
+
+/// Render the editor for the overscan margins
+pub fn overscan_grid(ui: &mut egui::Ui, overscan: &mut Overscan) {
+    egui::Grid::new("overscan_grid").show(ui, |ui| {
+        for (label, value) in [
+            ("Top", &mut overscan.top),
+            ("Bottom", &mut overscan.bottom),
+            ("Left", &mut overscan.left),
+            ("Right", &mut overscan.right),
+        ] {
+            ui.label(label);
+            ui.add(DragValue::new(value).clamp_range(0..=64).suffix(" px"));
+            ui.end_row();
+        }
+    });
+}
+
+/// Render the editor for the CHR bank profile
+fn chr_banks_grid(ui: &mut egui::Ui, chr_banks: &mut ChrBankProfile) {
+    egui::Grid::new("chr_banks_grid").show(ui, |ui| {
+        ui.label("Mapper");
+        egui::ComboBox::from_id_source("chr_bank_mapper")
+            .selected_text(chr_banks.mapper_name().unwrap_or("Custom"))
+            .show_ui(ui, |ui| {
+                for &(name, bank_tiles, banks) in ChrBankProfile::MAPPER_PRESETS {
+                    let selected = chr_banks.mapper_name() == Some(name);
+                    if ui.selectable_label(selected, name).clicked() {
+                        chr_banks.bank_tiles = bank_tiles;
+                        chr_banks.banks = banks;
+                    }
+                }
+            })
+            .response
+            .on_hover_text("Set the bank size and count to the way a mapper banks backgrounds");
+        ui.end_row();
+
+        ui.label("Bank Size");
+        egui::ComboBox::from_id_source("chr_bank_size")
+            .selected_text(format!("{} KB", chr_banks.bank_tiles / 64))
+            .show_ui(ui, |ui| {
+                for tiles in [64, 128, 256] {
+                    ui.selectable_value(
+                        &mut chr_banks.bank_tiles,
+                        tiles,
+                        format!("{} KB ({} tiles)", tiles / 64, tiles),
+                    );
+                }
+            });
+        ui.end_row();
+
+        ui.label("Background Banks");
+        ui.add(DragValue::new(&mut chr_banks.banks).clamp_range(1..=4))
+            .on_hover_text("The number of banks used for the background at the same time");
+        ui.end_row();
+
+        ui.label("Reserved Tiles");
+        ui.add(DragValue::new(&mut chr_banks.reserved_tiles).clamp_range(0..=256))
+            .on_hover_text("Tiles set aside for other things, such as a font or the HUD");
+        ui.end_row();
+    });
+    ui.label(format!(
+        "Each screen can use {} unique tiles",
+        chr_banks.screen_tile_limit()
+    ));
+}
+
+/// Render the editor for the names of the metatile property bits
+fn property_flags_grid(ui: &mut egui::Ui, property_flags: &mut [String; 8]) {
+    egui::Grid::new("property_flags_grid").show(ui, |ui| {
+        for (bit, name) in property_flags.iter_mut().enumerate() {
+            ui.label(format!("Bit {} (${:02X})", bit, 1 << bit));
+            ui.text_edit_singleline(name);
+            ui.end_row();
+        }
+    });
+}
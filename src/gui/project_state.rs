@@ -1,14 +1,27 @@
-use std::{path::PathBuf, sync::Arc};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
+use anyhow::Context;
 use egui::util::undoer::Undoer;
-use egui_extras::RetainedImage;
 use indexmap::IndexMap;
 use path_absolutize::Absolutize;
 use watch::WatchReceiver;
 
-use crate::{project::Project, Uid};
+use crate::{
+    export::symbol_name,
+    library::MetatileLibrary,
+    mockup::tile_sheet,
+    project::{MapTransform, Metatile, Metatileset, MetatilesetTile, Project, Tile},
+    Uid,
+};
 
-use super::util::load_and_watch_image;
+use super::{
+    export::ExportHistory, mockup_import::unused_path, source_texture::SourceTexture,
+    util::load_and_watch_image,
+};
 
 #[derive(Clone)]
 pub struct LoadedProject {
@@ -20,10 +33,10 @@ pub struct LoadedProject {
 pub struct SourceImageData {
     /// The image texture for the source, which can be displayed by Egui and contains the image size
     /// info
-    pub texture: Arc<RetainedImage>,
+    pub texture: Arc<SourceTexture>,
     /// The vector of pixels in the image, but instead of a color, the pixels contain the color
     /// index, 0-4
-    pub indexes: Vec<u8>,
+    pub indexes: Arc<Vec<u8>>,
 }
 
 #[derive(Clone)]
@@ -43,10 +56,19 @@ pub struct ProjectState {
     pub path: PathBuf,
     pub undoer: Undoer<Project>,
     pub source_images: IndexMap<Uid<PathBuf>, SourceImage>,
+    /// The recent exports of every project, shared with the [`RootState`](super::RootState)
+    pub export_history: ExportHistory,
 }
 
 impl ProjectState {
-    pub fn add_source(&mut self, path: PathBuf) {
+    /// Whether the project differs from the project file, or the file can't be read
+    pub fn has_unsaved_changes(&self) -> bool {
+        Project::load(&self.path)
+            .map(|saved| saved != self.data)
+            .unwrap_or(true)
+    }
+
+    pub fn add_source(&mut self, path: PathBuf) -> Uid<PathBuf> {
         let id = Uid::new();
         let absolute_path = path.absolutize().unwrap().to_path_buf();
         let relative_path = pathdiff::diff_paths(absolute_path, &self.path.absolutize().unwrap())
@@ -55,10 +77,53 @@ impl ProjectState {
         self.source_images.insert(
             id,
             SourceImage {
-                data: load_and_watch_image(&path),
+                data: load_and_watch_image(&path, false),
                 path: relative_path,
             },
         );
+        id
+    }
+
+    /// Copy an image from outside of the project into the project folder, and add it as a source
+    pub fn import_source(&mut self, path: &Path) -> anyhow::Result<Uid<PathBuf>> {
+        let stem = path
+            .file_stem()
+            .map(|x| x.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "source".into());
+        let extension = path
+            .extension()
+            .map(|x| x.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "png".into());
+        let copy_path = unused_path(self.project_dir(), &stem, &extension);
+        std::fs::copy(path, &copy_path)
+            .with_context(|| format!("Could not copy `{}` into the project", path.display()))?;
+
+        Ok(self.add_source(copy_path))
+    }
+
+    /// Save the tiles of a metatile library in a tile sheet next to the project, and add the
+    /// library's metatiles to the project as a new metatileset
+    pub fn import_library(
+        &mut self,
+        library: &MetatileLibrary,
+    ) -> anyhow::Result<Uid<Metatileset>> {
+        let (sheet, positions) = tile_sheet(&library.tile_pixels()?);
+        let sheet_path = unused_path(
+            self.project_dir(),
+            &format!("{}_tiles", symbol_name(&library.name)),
+            "png",
+        );
+        sheet
+            .save(&sheet_path)
+            .with_context(|| format!("Could not save tile sheet `{}`", sheet_path.display()))?;
+        let source_id = self.add_source(sheet_path);
+
+        Ok(library.add_to_project(&mut self.data, source_id, &positions))
+    }
+
+    /// The folder containing the project file
+    fn project_dir(&self) -> &Path {
+        self.path.parent().unwrap_or_else(|| Path::new("."))
     }
 
     pub fn update_source(&mut self, id: Uid<PathBuf>, path: PathBuf) {
@@ -67,11 +132,28 @@ impl ProjectState {
             .expect("Same filesystem");
         *self.data.sources.get_mut(&id).expect("missing source") = relative_path.clone();
         *self.source_images.get_mut(&id).expect("missing source") = SourceImage {
-            data: load_and_watch_image(&path),
+            data: load_and_watch_image(&path, self.data.pallet_order_sources.contains(&id)),
             path: relative_path,
         }
     }
 
+    /// Choose whether a source keeps the pallet order of its indexed image, and load it again with
+    /// its new color indexes
+    pub fn set_keep_pallet_order(&mut self, id: Uid<PathBuf>, keep: bool) {
+        if keep {
+            self.data.pallet_order_sources.insert(id);
+        } else {
+            self.data.pallet_order_sources.shift_remove(&id);
+        }
+        if let Some(path) = self.data.sources.get(&id) {
+            let image = SourceImage {
+                data: load_and_watch_image(&Project::source_path(&self.path, path), keep),
+                path: path.clone(),
+            };
+            self.source_images.insert(id, image);
+        }
+    }
+
     /// Reloads all the source images from the current project source list
     pub fn reload_source_images(&mut self) {
         self.source_images = self
@@ -83,13 +165,8 @@ impl ProjectState {
                     *id,
                     SourceImage {
                         data: load_and_watch_image(
-                            &self
-                                .path
-                                .absolutize()
-                                .unwrap()
-                                .join(&path)
-                                .absolutize()
-                                .expect("Absoluteize"),
+                            &Project::source_path(&self.path, path),
+                            self.data.pallet_order_sources.contains(id),
                         ),
                         path: path.clone(),
                     },
@@ -98,6 +175,138 @@ impl ProjectState {
             .collect();
     }
 
+    /// Get the color index, 0-3, of a pixel in a 16x16 metatile, or `None` if the tile is empty or
+    /// its source image isn't loaded.
+    pub fn metatile_pixel(&mut self, metatile_id: Uid<Metatile>, x: u32, y: u32) -> Option<u8> {
+        let tile =
+            self.data.metatiles.get(&metatile_id)?.tiles[(y / 8 * 2 + x / 8) as usize].clone()?;
+        let image = match self.source_images.get_mut(&tile.source_id)?.data.get() {
+            SourceImageStatus::Found(image) => image,
+            _ => return None,
+        };
+        let width = image.texture.size()[0];
+
+        let x = tile.x as usize * 8 + (x % 8) as usize;
+        let y = tile.y as usize * 8 + (y % 8) as usize;
+        image.indexes.get(y * width + x).copied()
+    }
+
+    /// Get the color indexes of the pixels in an 8x8 source tile in row-major order, or `None` if
+    /// its source image isn't loaded
+    pub fn tile_pixels(&mut self, tile: &Tile) -> Option<[u8; 64]> {
+        let image = match self.source_images.get_mut(&tile.source_id)?.data.get() {
+            SourceImageStatus::Found(image) => image,
+            _ => return None,
+        };
+        let width = image.texture.size()[0];
+
+        let mut pixels = [0; 64];
+        for y in 0..8 {
+            for x in 0..8 {
+                let idx = (tile.y as usize * 8 + y) * width + tile.x as usize * 8 + x;
+                pixels[y * 8 + x] = *image.indexes.get(idx)?;
+            }
+        }
+        Some(pixels)
+    }
+
+    /// Get the color indexes of all of the pixels in a 16x16 metatile in row-major order, with
+    /// `None` for pixels in empty tiles or tiles whose source image isn't loaded.
+    pub fn metatile_pixels(&mut self, metatile_id: Uid<Metatile>) -> [Option<u8>; 256] {
+        let mut pixels = [None; 256];
+        let tiles = match self.data.metatiles.get(&metatile_id) {
+            Some(metatile) => metatile.tiles.clone(),
+            None => return pixels,
+        };
+
+        for (i, tile) in tiles.iter().enumerate() {
+            let tile = if let Some(tile) = tile {
+                tile
+            } else {
+                continue;
+            };
+            let image = match self
+                .source_images
+                .get_mut(&tile.source_id)
+                .map(|x| x.data.get())
+            {
+                Some(SourceImageStatus::Found(image)) => image,
+                _ => continue,
+            };
+            let width = image.texture.size()[0];
+
+            for y in 0..8 {
+                for x in 0..8 {
+                    let idx = (tile.y as usize * 8 + y) * width + tile.x as usize * 8 + x;
+                    pixels[(i / 2 * 8 + y) * 16 + i % 2 * 8 + x] = image.indexes.get(idx).copied();
+                }
+            }
+        }
+
+        pixels
+    }
+
+    /// For each of the given tiles of a metatileset, find the tile that looks like it after a
+    /// mirror or rotation, using the same sub-pallet. Each tile maps to itself if it is
+    /// symmetrical, or to `None` if the metatileset has no such tile.
+    pub fn transformed_variants(
+        &mut self,
+        metatileset_id: Uid<Metatileset>,
+        tile_ids: &[Uid<MetatilesetTile>],
+        transform: MapTransform,
+    ) -> HashMap<Uid<MetatilesetTile>, Option<Uid<MetatilesetTile>>> {
+        let tiles = match self.data.metatilesets.get(&metatileset_id) {
+            Some(metatileset) => metatileset.tiles.clone(),
+            None => return tile_ids.iter().map(|id| (*id, None)).collect(),
+        };
+        let mut pixels = HashMap::new();
+        for tile in tiles.values() {
+            pixels
+                .entry(tile.metatile_id)
+                .or_insert_with(|| self.metatile_pixels(tile.metatile_id));
+        }
+
+        tile_ids
+            .iter()
+            .map(|tile_id| {
+                let tile = if let Some(tile) = tiles.get(tile_id) {
+                    tile
+                } else {
+                    return (*tile_id, None);
+                };
+                let original = &pixels[&tile.metatile_id];
+                let mut wanted = [None; 256];
+                for (i, pixel) in wanted.iter_mut().enumerate() {
+                    let (x, y) =
+                        transform.apply(((i % 16) as i32, (i / 16) as i32), (0, 0), (15, 15));
+                    *pixel = original[y as usize * 16 + x as usize];
+                }
+
+                // Prefer the tile itself when it is symmetrical
+                let variant = std::iter::once((tile_id, tile))
+                    .chain(tiles.iter().filter(|(id, _)| *id != tile_id))
+                    .find(|(_, x)| {
+                        x.sub_pallet_idx == tile.sub_pallet_idx && pixels[&x.metatile_id] == wanted
+                    })
+                    .map(|(id, _)| *id);
+                (*tile_id, variant)
+            })
+            .collect()
+    }
+
+    /// Free the textures of the source images that haven't been drawn since `time`, in egui
+    /// seconds, returning how many were freed
+    pub fn trim_source_textures(&mut self, time: f64) -> usize {
+        self.source_images
+            .values_mut()
+            .map(|image| match image.data.get() {
+                SourceImageStatus::Found(data) => data.texture.trim_unused_since(time),
+                _ => false,
+            })
+            .filter(|trimmed| *trimmed)
+            .count()
+    }
+
     /// Cleans up items with UID's pointing to non-existent objects. This happens when, for
     /// instance, we delete a metatile that is contained in a metatileset or other similar
     /// scenarios.
@@ -0,0 +1,124 @@
+//! Saving a rescue copy of the open project when the GUI crashes
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+    thread::ThreadId,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+use native_dialog::{MessageDialog, MessageType};
+use once_cell::sync::{Lazy, OnceCell};
+
+use crate::project::Project;
+
+use super::project_state::ProjectState;
+
+/// The last state of the open project that was shown in the GUI, and the path it was loaded from
+static SNAPSHOT: Lazy<Mutex<Option<(PathBuf, Project)>>> = Lazy::new(Default::default);
+
+/// The thread running the GUI, which is the only one that can take down the editing session
+static GUI_THREAD: OnceCell<ThreadId> = OnceCell::new();
+
+/// Install a panic hook that saves the open project to a rescue file and tells the user where to
+/// find it, when the GUI thread panics
+pub fn install_panic_hook() {
+    if GUI_THREAD.set(std::thread::current().id()).is_err() {
+        return;
+    }
+
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+
+        if GUI_THREAD.get() != Some(&std::thread::current().id()) {
+            return;
+        }
+
+        let mut message = format!("NESImg crashed: {}", info);
+        match write_rescue_file() {
+            Ok(Some(path)) => message.push_str(&format!(
+                "\n\nThe open project was saved to:\n{}\n\nRename it to replace the project file \
+                once you've checked that it looks right.",
+                path.display()
+            )),
+            Ok(None) => (),
+            Err(e) => message.push_str(&format!(
+                "\n\nThe open project could not be rescued: {:#}",
+                e
+            )),
+        }
+
+        MessageDialog::new()
+            .set_type(MessageType::Error)
+            .set_title("NESImg Crashed")
+            .set_text(&message)
+            .show_alert()
+            .ok();
+    }));
+}
+
+/// Remember the state of the open project, so that it can be rescued if the GUI crashes later.
+///
+/// This is called at the end of every frame, and only copies the project when it has changed.
+pub fn update_snapshot(project: Option<&ProjectState>) {
+    let mut snapshot = match SNAPSHOT.lock() {
+        Ok(snapshot) => snapshot,
+        Err(_) => return,
+    };
+    match project {
+        Some(project) => {
+            let changed = match &*snapshot {
+                Some((path, data)) => path != &project.path || data != &project.data,
+                None => true,
+            };
+            if changed {
+                *snapshot = Some((project.path.clone(), project.data.clone()));
+            }
+        }
+        None => *snapshot = None,
+    }
+}
+
+/// Write the last snapshot of the project next to the project file, returning the path it was
+/// written to, or `None` if no project was open
+fn write_rescue_file() -> anyhow::Result<Option<PathBuf>> {
+    // The panic may have happened while the snapshot was being taken, so don't wait for the lock
+    let snapshot = match SNAPSHOT.try_lock() {
+        Ok(snapshot) => snapshot,
+        Err(_) => anyhow::bail!("The project was being copied when the crash happened"),
+    };
+    let (project_path, data) = match &*snapshot {
+        Some(snapshot) => snapshot,
+        None => return Ok(None),
+    };
+
+    let contents =
+        ron::ser::to_string_pretty(data, Default::default()).context("Serialize project")?;
+
+    // Source paths are relative to the project file, so the rescue file works best next to it
+    let path = rescue_path(project_path);
+    if std::fs::write(&path, &contents).is_ok() {
+        return Ok(Some(path));
+    }
+    let path = std::env::temp_dir().join(path.file_name().expect("Rescue file name"));
+    std::fs::write(&path, &contents)
+        .with_context(|| format!("Could not write `{}`", path.display()))?;
+
+    Ok(Some(path))
+}
+
+/// Get the path of the rescue file for a project, such as `mygame.rescue-1660000000.nesimg`
+fn rescue_path(project_path: &Path) -> PathBuf {
+    let stem = project_path
+        .file_stem()
+        .map(|x| x.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "project".into());
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|x| x.as_secs())
+        .unwrap_or_default();
+
+    project_path.with_file_name(format!("{}.rescue-{}.nesimg", stem, timestamp))
+}
@@ -0,0 +1,203 @@
+//! The window for creating a source image and metatileset from an emulator savestate
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use watch::WatchReceiver;
+
+use crate::{
+    mockup::tile_sheet,
+    project::Metatileset,
+    savestate::{load_ppu_snapshot, PpuSnapshot},
+    Uid,
+};
+
+use super::{
+    components::{nes_color_swatch, send_error_notification, send_info_notification},
+    mockup_import::unused_path,
+    project_state::ProjectState,
+    util::{pick_file, FileFilter},
+};
+
+/// The state of the "Import Savestate…" window
+pub struct SavestateImportWindow {
+    /// The savestate being loaded
+    loading: WatchReceiver<Option<Result<(PathBuf, PpuSnapshot), String>>>,
+    /// The savestate waiting to be imported, if one has been loaded
+    savestate: Option<Savestate>,
+}
+
+impl Default for SavestateImportWindow {
+    fn default() -> Self {
+        Self {
+            loading: watch::channel(None).1,
+            savestate: None,
+        }
+    }
+}
+
+struct Savestate {
+    path: PathBuf,
+    /// The name used for the new source image and metatileset
+    name: String,
+    snapshot: PpuSnapshot,
+}
+
+impl SavestateImportWindow {
+    /// Ask the user for a savestate to import
+    pub fn pick_savestate(&mut self) {
+        self.loading = pick_file(
+            &[
+                FileFilter {
+                    name: "FCEUX Savestate",
+                    extensions: &[
+                        "fcs", "fc0", "fc1", "fc2", "fc3", "fc4", "fc5", "fc6", "fc7", "fc8", "fc9",
+                    ],
+                },
+                FileFilter {
+                    name: "PPU Memory Dump",
+                    extensions: &["dmp", "bin"],
+                },
+            ],
+            |path| {
+                Some(
+                    load_ppu_snapshot(path)
+                        .map(|snapshot| (path.to_owned(), snapshot))
+                        .map_err(|e| format!("{:#}", e)),
+                )
+            },
+        );
+    }
+
+    /// Render the window, if a savestate has been picked
+    pub fn show(&mut self, ctx: &egui::Context, project: &mut ProjectState) {
+        match self.loading.get_if_new().flatten() {
+            Some(Ok((path, snapshot))) => {
+                self.savestate = Some(Savestate {
+                    name: path
+                        .file_stem()
+                        .map(|x| x.to_string_lossy().into_owned())
+                        .unwrap_or_else(|| "Savestate".into()),
+                    path,
+                    snapshot,
+                });
+            }
+            Some(Err(e)) => send_error_notification(ctx, e),
+            None => (),
+        }
+
+        let savestate = if let Some(savestate) = &mut self.savestate {
+            savestate
+        } else {
+            return;
+        };
+
+        let mut open = true;
+        let mut import = false;
+        egui::Window::new("Import Savestate")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                import = savestate_gui(ui, savestate);
+            });
+
+        if import {
+            match import_savestate(project, savestate) {
+                Ok(()) => send_info_notification(
+                    ctx,
+                    &format!(
+                        "Imported savestate as metatileset `{}`",
+                        savestate.name.trim()
+                    ),
+                ),
+                Err(e) => send_error_notification(ctx, format!("{:#}", e)),
+            }
+        }
+        if !open || import {
+            self.savestate = None;
+        }
+    }
+}
+
+/// Show the summary of a savestate, returning whether the import button was clicked
+fn savestate_gui(ui: &mut egui::Ui, savestate: &mut Savestate) -> bool {
+    let snapshot = &savestate.snapshot;
+
+    ui.label(format!("`{}`", savestate.path.display()));
+    ui.separator();
+
+    egui::Grid::new("savestate_summary")
+        .num_columns(2)
+        .show(ui, |ui| {
+            ui.label("Name");
+            ui.text_edit_singleline(&mut savestate.name);
+            ui.end_row();
+
+            ui.label("Read From");
+            ui.label(snapshot.kind);
+            ui.end_row();
+
+            ui.label("Tiles");
+            match &snapshot.tiles {
+                Some(tiles) => ui.label(format!("{} tiles from both pattern tables", tiles.len())),
+                None => ui.label("None, the game uses CHR-ROM"),
+            };
+            ui.end_row();
+
+            ui.label("Pallet");
+            ui.vertical(|ui| {
+                for sub_pallet in snapshot.pallet.get_sub_pallets() {
+                    ui.horizontal(|ui| {
+                        for color in sub_pallet {
+                            nes_color_swatch(ui, color);
+                        }
+                    });
+                }
+            });
+            ui.end_row();
+        });
+
+    ui.separator();
+    ui.label(if snapshot.tiles.is_some() {
+        "This will create a source image with the pattern tables, the $0000 table on top and the \
+        $1000 table below it, and a metatileset with the background pallet."
+    } else {
+        "CHR-ROM isn't stored in savestates, so this will only create a metatileset with the \
+        background pallet."
+    });
+    ui.add_enabled(
+        !savestate.name.trim().is_empty(),
+        egui::Button::new("💾 Import"),
+    )
+    .clicked()
+}
+
+/// Save the pattern tables of a savestate next to the project and add the source and metatileset
+/// for it
+fn import_savestate(project: &mut ProjectState, savestate: &Savestate) -> anyhow::Result<()> {
+    let name = savestate.name.trim();
+
+    if let Some(tiles) = &savestate.snapshot.tiles {
+        let (sheet, _) = tile_sheet(tiles);
+        let sheet_path = unused_path(
+            project.path.parent().unwrap_or_else(|| Path::new(".")),
+            &format!("{}_chr", name),
+            "png",
+        );
+        sheet
+            .save(&sheet_path)
+            .with_context(|| format!("Could not save tile sheet `{}`", sheet_path.display()))?;
+        project.add_source(sheet_path);
+    }
+
+    project.data.metatilesets.insert(
+        Uid::new(),
+        Metatileset {
+            name: name.to_owned(),
+            pallet: savestate.snapshot.pallet.clone(),
+            ..Default::default()
+        },
+    );
+
+    Ok(())
+}
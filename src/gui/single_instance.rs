@@ -0,0 +1,214 @@
+//! Forwarding projects opened from the commandline or a file manager to the NESImg window that is
+//! already running, instead of starting a second one
+//!
+//! The first instance listens on a local socket that only the same user can reach: a Unix socket
+//! in a folder only the user can open on Unix, and elsewhere a TCP port on 127.0.0.1, written to a
+//! file in the user's local app data with a random token that every request has to include. Later
+//! instances connect to it and send a line with the token and the absolute path of the project to
+//! open, and the first instance replies with `OK`. Each user gets their own socket, so the
+//! instances of different users don't see each other.
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::Context;
+use path_absolutize::Absolutize;
+use watch::WatchReceiver;
+
+use tracing as trc;
+
+/// The start of each request, so that other programs using the socket aren't mistaken for NESImg
+const REQUEST_PREFIX: &str = "NESIMG-OPEN ";
+
+/// The reply sent once a project has been accepted
+const REPLY_OK: &str = "OK";
+
+/// Ask the running instance to open a project. Returns `false` if there is no running instance
+/// or it didn't accept the project.
+pub fn forward_to_running_instance(path: &Path) -> bool {
+    let inner = || -> anyhow::Result<bool> {
+        let (mut stream, token) = platform::connect()?;
+        stream.set_read_timeout(Some(Duration::from_secs(2)))?;
+
+        let path = path.absolutize()?;
+        writeln!(stream, "{}{} {}", REQUEST_PREFIX, token, path.display())?;
+
+        let mut reply = String::new();
+        BufReader::new(stream).read_line(&mut reply)?;
+        Ok(reply.trim_end() == REPLY_OK)
+    };
+
+    match inner() {
+        Ok(accepted) => accepted,
+        Err(e) => {
+            trc::debug!("No running instance to forward to: {:#}", e);
+            false
+        }
+    }
+}
+
+/// Listen for projects forwarded by other instances in the background. Returns `None` if another
+/// instance is already listening.
+pub fn listen(ctx: &egui::Context) -> Option<WatchReceiver<Option<PathBuf>>> {
+    let (listener, token) = match platform::bind() {
+        Ok(listener) => listener,
+        Err(e) => {
+            trc::debug!("Not listening for other instances: {:#}", e);
+            return None;
+        }
+    };
+    let (sender, receiver) = watch::channel(None);
+    let ctx = ctx.clone();
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+            stream.set_read_timeout(Some(Duration::from_secs(2))).ok();
+
+            let mut request = String::new();
+            if BufReader::new(&stream).read_line(&mut request).is_err() {
+                continue;
+            }
+            let path = match request
+                .trim_end()
+                .strip_prefix(REQUEST_PREFIX)
+                .and_then(|x| x.split_once(' '))
+            {
+                Some((request_token, path))
+                    if request_token == token && Path::new(path).is_file() =>
+                {
+                    PathBuf::from(path)
+                }
+                _ => continue,
+            };
+
+            writeln!(stream, "{}", REPLY_OK).ok();
+            sender.send(Some(path));
+            ctx.request_repaint();
+        }
+    });
+
+    Some(receiver)
+}
+
+/// The folder that the running instance is found through, which is created if it doesn't exist
+fn instance_dir() -> anyhow::Result<PathBuf> {
+    let dir = platform::user_dir().context("Couldn't find a folder for the current user")?;
+    let dir = dir.join("nesimg");
+    platform::create_private_dir(&dir)
+        .with_context(|| format!("Create instance folder `{}`", dir.display()))?;
+    Ok(dir)
+}
+
+#[cfg(unix)]
+mod platform {
+    use std::{
+        fs::DirBuilder,
+        os::unix::{
+            fs::{DirBuilderExt, PermissionsExt},
+            net::{UnixListener, UnixStream},
+        },
+        path::{Path, PathBuf},
+    };
+
+    use super::instance_dir;
+
+    /// The name of the socket in the instance folder
+    const SOCKET_NAME: &str = "instance.sock";
+
+    /// The token sent with requests. Only the user can open the instance folder, so the socket
+    /// doesn't need a secret.
+    const TOKEN: &str = "-";
+
+    /// The user's runtime folder, or their cache folder if there isn't one
+    pub fn user_dir() -> Option<PathBuf> {
+        std::env::var_os("XDG_RUNTIME_DIR")
+            .map(PathBuf::from)
+            .or_else(|| Some(PathBuf::from(std::env::var_os("HOME")?).join(".cache")))
+    }
+
+    /// Create a folder that only the user can open
+    pub fn create_private_dir(dir: &Path) -> std::io::Result<()> {
+        DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+        std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))
+    }
+
+    pub fn connect() -> anyhow::Result<(UnixStream, String)> {
+        let stream = UnixStream::connect(instance_dir()?.join(SOCKET_NAME))?;
+        Ok((stream, TOKEN.into()))
+    }
+
+    pub fn bind() -> anyhow::Result<(UnixListener, String)> {
+        let path = instance_dir()?.join(SOCKET_NAME);
+        let listener = match UnixListener::bind(&path) {
+            Ok(listener) => listener,
+            // The socket is left behind when an instance doesn't exit cleanly, so it is only in
+            // use if something answers on it
+            Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => {
+                if UnixStream::connect(&path).is_ok() {
+                    anyhow::bail!("Another instance is listening on `{}`", path.display());
+                }
+                std::fs::remove_file(&path)?;
+                UnixListener::bind(&path)?
+            }
+            Err(e) => return Err(e.into()),
+        };
+        Ok((listener, TOKEN.into()))
+    }
+}
+
+#[cfg(not(unix))]
+mod platform {
+    use std::{
+        net::{SocketAddr, TcpListener, TcpStream},
+        path::{Path, PathBuf},
+        time::Duration,
+    };
+
+    use anyhow::Context;
+
+    use super::instance_dir;
+
+    /// The name of the file in the instance folder holding the port and token
+    const PORT_FILE_NAME: &str = "instance";
+
+    /// The user's local app data folder
+    pub fn user_dir() -> Option<PathBuf> {
+        std::env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    }
+
+    /// Create a folder in the user's local app data, which only the user can open
+    pub fn create_private_dir(dir: &Path) -> std::io::Result<()> {
+        std::fs::create_dir_all(dir)
+    }
+
+    pub fn connect() -> anyhow::Result<(TcpStream, String)> {
+        let contents = std::fs::read_to_string(instance_dir()?.join(PORT_FILE_NAME))?;
+        let (port, token) = contents
+            .trim()
+            .split_once(' ')
+            .context("Instance file is invalid")?;
+        let addr = SocketAddr::from(([127, 0, 0, 1], port.parse()?));
+        let stream = TcpStream::connect_timeout(&addr, Duration::from_millis(250))?;
+        Ok((stream, token.into()))
+    }
+
+    pub fn bind() -> anyhow::Result<(TcpListener, String)> {
+        if connect().is_ok() {
+            anyhow::bail!("Another instance is already listening");
+        }
+        let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))?;
+        let token = format!("{:032x}", rand::random::<u128>());
+        std::fs::write(
+            instance_dir()?.join(PORT_FILE_NAME),
+            format!("{} {}", listener.local_addr()?.port(), token),
+        )?;
+        Ok((listener, token))
+    }
+}
@@ -0,0 +1,257 @@
+//! The window offering to fix source paths that are absolute, outside of the project folder, or
+//! missing, which is checked when a project is loaded
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use path_absolutize::Absolutize;
+
+use crate::{project::Project, Uid};
+
+use super::{
+    components::{send_error_notification, send_info_notification},
+    project_state::ProjectState,
+};
+
+/// The name of the folder next to the project file that outside sources are copied into
+const ASSETS_DIR: &str = "assets";
+
+/// The state of the "Source Paths" window
+#[derive(Default)]
+pub struct SourcePathsWindow {
+    open: bool,
+}
+
+/// A source whose path could cause trouble when the project is shared
+struct SourcePathIssue {
+    id: Uid<PathBuf>,
+    /// The path stored in the project
+    path: PathBuf,
+    /// The absolute path the source resolves to
+    resolved: PathBuf,
+    kind: IssueKind,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum IssueKind {
+    /// The source file doesn't exist
+    Missing,
+    /// The source is stored as an absolute path
+    Absolute,
+    /// The source is outside of the folder containing the project file
+    Outside,
+}
+
+impl IssueKind {
+    fn description(&self) -> &'static str {
+        match self {
+            IssueKind::Missing => "Missing",
+            IssueKind::Absolute => "Absolute path",
+            IssueKind::Outside => "Outside of the project folder",
+        }
+    }
+}
+
+impl SourcePathsWindow {
+    /// Open the window if any of the project's sources have path problems. This is called when a
+    /// project is loaded.
+    pub fn check(&mut self, project: &ProjectState) {
+        self.open = !find_issues(project).is_empty();
+    }
+
+    /// Open the window, even if there are no problems
+    pub fn open(&mut self) {
+        self.open = true;
+    }
+
+    /// Render the window, if it is open
+    pub fn show(&mut self, ctx: &egui::Context, project: &mut ProjectState) {
+        if !self.open {
+            return;
+        }
+
+        let issues = find_issues(project);
+        let mut make_relative = false;
+        let mut copy_to_assets = false;
+
+        egui::Window::new("🗀 Source Paths")
+            .open(&mut self.open)
+            .default_width(500.0)
+            .show(ctx, |ui| {
+                if issues.is_empty() {
+                    ui.label(
+                        "All sources are stored relative to the project, inside of its folder",
+                    );
+                    return;
+                }
+
+                ui.label(
+                    "These sources may not be found when the project is opened on another \
+                    computer:",
+                );
+                egui::ScrollArea::vertical()
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("source_path_issues")
+                            .num_columns(2)
+                            .striped(true)
+                            .show(ui, |ui| {
+                                for issue in &issues {
+                                    ui.label(issue.path.display().to_string())
+                                        .on_hover_text(issue.resolved.display().to_string());
+                                    ui.label(issue.kind.description());
+                                    ui.end_row();
+                                }
+                            });
+                    });
+                ui.separator();
+
+                let count = |kind| issues.iter().filter(|x| x.kind == kind).count();
+                let (absolute, missing) = (count(IssueKind::Absolute), count(IssueKind::Missing));
+                let outside = issues
+                    .iter()
+                    .filter(|x| {
+                        x.kind != IssueKind::Missing
+                            && is_outside_project(&project.path, &x.resolved)
+                    })
+                    .count();
+                ui.horizontal(|ui| {
+                    make_relative = ui
+                        .add_enabled(absolute > 0, egui::Button::new("Make Relative"))
+                        .on_hover_text(
+                            "Store the absolute paths relative to the project file, without \
+                            moving any files",
+                        )
+                        .clicked();
+                    copy_to_assets = ui
+                        .add_enabled(
+                            outside > 0,
+                            egui::Button::new(format!("Copy Into `{}/`", ASSETS_DIR)),
+                        )
+                        .on_hover_text(format!(
+                            "Copy the sources that are outside of the project folder into an \
+                            `{}` folder next to the project file, and use the copies",
+                            ASSETS_DIR
+                        ))
+                        .clicked();
+                });
+                if missing > 0 {
+                    ui.label(
+                        "Missing sources can be found with \"🔗 Relink Sources…\" in the Sources \
+                        tab.",
+                    );
+                }
+            });
+
+        if make_relative {
+            let mut fixed = 0;
+            for issue in issues.iter().filter(|x| x.kind == IssueKind::Absolute) {
+                project.update_source(issue.id, issue.resolved.clone());
+                fixed += 1;
+            }
+            send_info_notification(ctx, &format!("Made {} source paths relative", fixed));
+        } else if copy_to_assets {
+            let mut copied = 0;
+            for issue in issues.iter().filter(|x| x.kind != IssueKind::Missing) {
+                if !is_outside_project(&project.path, &issue.resolved) {
+                    continue;
+                }
+                match copy_to_assets_dir(&project.path, &issue.resolved) {
+                    Ok(path) => {
+                        project.update_source(issue.id, path);
+                        copied += 1;
+                    }
+                    Err(e) => send_error_notification(ctx, format!("{:#}", e)),
+                }
+            }
+            send_info_notification(
+                ctx,
+                &format!("Copied {} sources into `{}/`", copied, ASSETS_DIR),
+            );
+        }
+    }
+}
+
+/// Find the sources that are missing, absolute, or outside of the project folder
+fn find_issues(project: &ProjectState) -> Vec<SourcePathIssue> {
+    project
+        .data
+        .sources
+        .iter()
+        .filter_map(|(id, path)| {
+            let resolved = Project::source_path(&project.path, path);
+            let kind = if !resolved.exists() {
+                IssueKind::Missing
+            } else if path.is_absolute() {
+                IssueKind::Absolute
+            } else if is_outside_project(&project.path, &resolved) {
+                IssueKind::Outside
+            } else {
+                return None;
+            };
+            Some(SourcePathIssue {
+                id: *id,
+                path: path.clone(),
+                resolved,
+                kind,
+            })
+        })
+        .collect()
+}
+
+/// Whether a file isn't inside of the folder containing the project file
+fn is_outside_project(project_path: &Path, path: &Path) -> bool {
+    match project_path
+        .absolutize()
+        .ok()
+        .as_deref()
+        .and_then(Path::parent)
+    {
+        Some(project_dir) => !path.starts_with(project_dir),
+        None => false,
+    }
+}
+
+/// Copy a file into the assets folder next to the project file, returning the path of the copy.
+///
+/// If the assets folder already has a different file with the same name, a number is added to the
+/// name of the copy.
+fn copy_to_assets_dir(project_path: &Path, path: &Path) -> anyhow::Result<PathBuf> {
+    let assets_dir = project_path
+        .absolutize()?
+        .parent()
+        .context("Project file has no parent folder")?
+        .join(ASSETS_DIR);
+    std::fs::create_dir_all(&assets_dir).context("Create assets folder")?;
+
+    let contents =
+        std::fs::read(path).with_context(|| format!("Could not read `{}`", path.display()))?;
+    let stem = path
+        .file_stem()
+        .map(|x| x.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "source".into());
+    let extension = path
+        .extension()
+        .map(|x| format!(".{}", x.to_string_lossy()))
+        .unwrap_or_default();
+
+    for i in 1.. {
+        let name = if i == 1 {
+            format!("{}{}", stem, extension)
+        } else {
+            format!("{}-{}{}", stem, i, extension)
+        };
+        let new_path = assets_dir.join(name);
+        match std::fs::read(&new_path) {
+            // The same file has already been copied
+            Ok(existing) if existing == contents => return Ok(new_path),
+            Ok(_) => continue,
+            Err(_) => {
+                std::fs::write(&new_path, &contents)
+                    .with_context(|| format!("Could not write `{}`", new_path.display()))?;
+                return Ok(new_path);
+            }
+        }
+    }
+    unreachable!()
+}
@@ -0,0 +1,126 @@
+//! Source image textures that can be evicted from the GPU while they aren't being shown
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+
+use egui::Color32;
+use egui_extras::RetainedImage;
+
+/// The four colors used to represent the different pallets internally in the source image
+pub static GRAYSCALE_COLORS: [Color32; 4] = [
+    Color32::from_rgb(0, 0, 0),
+    Color32::from_rgb(85, 85, 85),
+    Color32::from_rgb(170, 170, 170),
+    Color32::from_rgb(255, 255, 255),
+];
+
+/// Whether textures are smoothed when they are scaled, instead of drawing each pixel as a sharp
+/// square. Off by default, since smoothing blends in colors that aren't in the pixel art.
+static SMOOTH_SCALING: AtomicBool = AtomicBool::new(false);
+
+/// The filter that textures are uploaded with, following the smooth scaling preference
+pub fn texture_filter() -> egui::TextureFilter {
+    if SMOOTH_SCALING.load(Ordering::Relaxed) {
+        egui::TextureFilter::Linear
+    } else {
+        egui::TextureFilter::Nearest
+    }
+}
+
+/// Set whether textures are smoothed when they are scaled. Textures that are already uploaded
+/// keep their filter until they are uploaded again.
+pub fn set_smooth_scaling(smooth: bool) {
+    SMOOTH_SCALING.store(smooth, Ordering::Relaxed);
+}
+
+/// The grayscale texture of a source image, which is built from the color indexes the first time
+/// it is drawn, and can be trimmed to free its memory until it is drawn again
+pub struct SourceTexture {
+    size: [usize; 2],
+    indexes: Arc<Vec<u8>>,
+    /// The texture, if it has been drawn since it was last trimmed
+    image: Mutex<Option<RetainedImage>>,
+    /// The egui time, in seconds, that the texture was last drawn
+    last_used: Mutex<Option<f64>>,
+}
+
+impl SourceTexture {
+    pub fn new(size: [usize; 2], indexes: Arc<Vec<u8>>) -> Self {
+        Self {
+            size,
+            indexes,
+            image: Mutex::new(None),
+            last_used: Mutex::new(None),
+        }
+    }
+
+    pub fn size(&self) -> [usize; 2] {
+        self.size
+    }
+
+    pub fn size_vec2(&self) -> egui::Vec2 {
+        egui::Vec2::new(self.size[0] as f32, self.size[1] as f32)
+    }
+
+    /// Get the texture to draw, uploading it if it isn't loaded
+    pub fn texture_id(&self, ctx: &egui::Context) -> egui::TextureId {
+        *self.last_used.lock().unwrap() = Some(ctx.input().time);
+
+        self.image
+            .lock()
+            .unwrap()
+            .get_or_insert_with(|| {
+                RetainedImage::from_color_image(
+                    "source_image",
+                    egui::ColorImage {
+                        size: self.size,
+                        pixels: self
+                            .indexes
+                            .iter()
+                            .map(|&idx| GRAYSCALE_COLORS[idx as usize % 4])
+                            .collect(),
+                    },
+                )
+                .with_texture_filter(texture_filter())
+            })
+            .texture_id(ctx)
+    }
+
+    /// Whether the texture is loaded
+    pub fn is_loaded(&self) -> bool {
+        self.image.lock().unwrap().is_some()
+    }
+
+    /// The egui time, in seconds, that the texture was last drawn, if it has been drawn
+    pub fn last_used(&self) -> Option<f64> {
+        *self.last_used.lock().unwrap()
+    }
+
+    /// Free the texture if it hasn't been drawn since `time`, returning whether it was freed
+    pub fn trim_unused_since(&self, time: f64) -> bool {
+        let mut image = self.image.lock().unwrap();
+        if image.is_some() && !matches!(self.last_used(), Some(used) if used >= time) {
+            *image = None;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// The estimated bytes of RAM used by the color indexes and the texture's pixels
+    pub fn cpu_bytes(&self) -> usize {
+        let pixels = self.size[0] * self.size[1];
+        self.indexes.len() + if self.is_loaded() { pixels * 4 } else { 0 }
+    }
+
+    /// The estimated bytes of video memory used by the texture
+    pub fn gpu_bytes(&self) -> usize {
+        if self.is_loaded() {
+            self.size[0] * self.size[1] * 4
+        } else {
+            0
+        }
+    }
+}
@@ -1,3 +1,12 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    project::{Level, Metatile, Metatileset, Tile},
+    Uid,
+};
+
 use super::ProjectState;
 
 pub trait NesimgGuiTab {
@@ -8,9 +17,99 @@ pub trait NesimgGuiTab {
     }
 
     fn tooltip(&self) -> &'static str;
+
+    /// Record the tab's editing context in the project's layout
+    fn save_layout(&self, _layout: &mut ProjectLayout) {}
+
+    /// Restore the tab's editing context from the project's layout, after the project is loaded
+    fn load_layout(&mut self, _layout: &ProjectLayout) {}
+
+    /// Show a jump target that was requested with [`jump_to`], if this tab is the one that shows it
+    fn jump_to(&mut self, _target: &JumpTarget) {}
+}
+
+/// A part of the project that one tab can ask to have shown in the tab that edits it
+#[derive(Debug, Clone)]
+pub enum JumpTarget {
+    /// A metatile, shown in the metatiles tab
+    Metatile(Uid<Metatile>),
+    /// An 8x8 tile of a source image, shown in the sources tab
+    SourceTile(Tile),
+}
+
+impl JumpTarget {
+    /// The name of the tab that shows the target
+    pub fn tab(&self) -> &'static str {
+        match self {
+            JumpTarget::Metatile(_) => "Metatiles",
+            JumpTarget::SourceTile(_) => "Sources",
+        }
+    }
+}
+
+/// The egui memory ID of the jump target waiting to be shown
+fn jump_target_id() -> egui::Id {
+    egui::Id::new("jump_target")
+}
+
+/// Switch to the tab that shows `target` at the end of the frame
+pub fn jump_to(ctx: &egui::Context, target: JumpTarget) {
+    ctx.data().insert_temp(jump_target_id(), Some(target));
+}
+
+/// Take the jump target requested this frame, if any
+pub fn take_jump_target(ctx: &egui::Context) -> Option<JumpTarget> {
+    ctx.data()
+        .get_temp_mut_or_default::<Option<JumpTarget>>(jump_target_id())
+        .take()
+}
+
+/// The editing context of a project, which is saved with the GUI settings for each project file
+/// so that switching projects brings back where you left off
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProjectLayout {
+    /// The tab that was open
+    pub current_tab: String,
+    /// The view of the maps tab
+    pub maps_view: Option<CanvasView>,
+    /// The width of the level sidebar in the maps tab
+    pub maps_sidebar_width: Option<f32>,
+    /// The level selected in the maps tab
+    pub maps_level: Option<Uid<Level>>,
+    /// The screen edited in the maps tab, if it was in screen mode
+    pub maps_screen: Option<(i32, i32)>,
+    /// The view of the world tab
+    pub world_view: Option<CanvasView>,
+    /// The level selected in the world tab
+    pub world_level: Option<Uid<Level>>,
+    /// The metatileset selected in the metatilesets tab
+    pub metatileset: Option<Uid<Metatileset>>,
+    /// The sub-pallet used to paint in the metatilesets tab
+    pub sub_pallet: usize,
+    /// The source image selected in the metatiles tab
+    pub metatiles_source: Option<Uid<PathBuf>>,
+    /// The metatile selected in the metatiles tab
+    pub metatile: Option<Uid<Metatile>>,
+    /// The metatileset whose pallet the metatiles tab previews metatiles with
+    pub metatiles_preview: Option<Uid<Metatileset>>,
+    /// The view of the metatile editor in the metatiles tab
+    pub metatile_editor_view: Option<CanvasView>,
+    /// The view of the source image in the metatiles tab
+    pub metatiles_source_view: Option<CanvasView>,
+    /// The zoom of the source preview in the sources tab
+    pub sources_zoom: Option<f32>,
+}
+
+/// The zoom and pan of a tab's canvas
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct CanvasView {
+    pub zoom: f32,
+    pub pan: [f32; 2],
 }
 
 pub mod maps;
 pub mod metatiles;
 pub mod metatilesets;
 pub mod sources;
+pub mod world;
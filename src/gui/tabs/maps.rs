@@ -1,35 +1,347 @@
+use std::collections::HashMap;
+
+use anyhow::Context;
 use egui::DragValue;
+use indexmap::IndexSet;
+use watch::WatchReceiver;
 
 use crate::{
-    constants::NES_PALLET,
+    constants::{SCREEN_HEIGHT_METATILES, SCREEN_WIDTH_METATILES},
+    export::{symbol_name, ExportOnly},
     gui::{
-        components::{MetatileGui, MetatileKind},
+        accessibility,
+        background::BackgroundTask,
+        components::{
+            attribute_address, backdrop_gui, coordinate_readout, nametable_address,
+            nes_color_picker, paint_rulers, ruler_unit_gui, send_error_notification,
+            send_info_notification, send_warning_notification, Backdrop, MetatileGui, MetatileKind,
+            PpuEffects, RulerUnit, RULER_WIDTH,
+        },
+        export::export_only_menu,
+        gamepad::{self, GamepadInput},
+        project_settings::overscan_grid,
+        util::{pick_file, FileFilter},
         ProjectState,
     },
-    project::{Level, LevelTile, MetatilesetTile},
+    map_csv,
+    map_generator::{self, BuildingBlock, MapGenerator, Terrain},
+    project::{
+        ExitSource, Level, LevelExit, LevelMargin, LevelTile, MapBookmark, MapEdge, MapTransform,
+        Metatile, Metatileset, MetatilesetTile, Overscan, Pallet, PalletRegion, ScreenFlags,
+        ScrollSplit, Zone, ZoneKind,
+    },
     Uid,
 };
 
-use super::NesimgGuiTab;
+use super::{jump_to, CanvasView, JumpTarget, NesimgGuiTab, ProjectLayout};
 
 pub struct MapsTab {
     zoom: f32,
     pan: egui::Vec2,
     dragging_level: Option<Uid<Level>>,
+    /// The level whose name was right-clicked, which the open context menu is for
+    context_menu_level: Option<Uid<Level>>,
     current_level: Option<Uid<Level>>,
     tile_list_col_count: u8,
     current_metatileset_tile: Option<Uid<MetatilesetTile>>,
+    /// The tiles painted at once when a block of tiles is selected in the tile picker, by their
+    /// offset from the cursor. Empty when only the current tile is painted.
+    brush: Vec<((i32, i32), Uid<MetatilesetTile>)>,
+    /// Whether to show the magnifier lens around the cursor
+    magnifier: bool,
+    /// Whether each kind of zone is shown on the map, indexed by [`ZoneKind::export_id`]
+    zone_visibility: [bool; ZoneKind::ALL.len()],
+    /// Whether dragging on the map draws a zone instead of placing tiles
+    zone_tool: bool,
+    /// The kind of zone that the zone tool draws
+    new_zone_kind: ZoneKind,
+    /// The first and last metatile of the zone being drawn
+    zone_drag: Option<((i32, i32), (i32, i32))>,
+    /// Whether to draw arrows between the levels that are connected by exits
+    show_exits: bool,
+    /// Whether to play the tile animations
+    animate: bool,
+    /// The PPU color effects to preview the map with
+    effects: PpuEffects,
+    /// Whether to simulate composite video on the map
+    composite: bool,
+    /// What to show for the pixels that use color 0
+    backdrop: Backdrop,
+    /// Whether to show the screen edges that may be cropped by TVs
+    show_overscan: bool,
+    /// Whether to show the window previewing the current level's screen with its scroll split
+    show_screen_preview: bool,
+    /// The size of the levels created from the canvas context menu
+    new_level_size: MapSize,
+    /// The level being resized in the "Resize Map" window, if it is open
+    resize_map: Option<ResizeMap>,
+    /// The settings of the "Generate Map" window, which are kept after the window is closed
+    generate_map: GenerateMap,
+    /// The number of metatiles the shift buttons move the map by
+    shift_amount: i32,
+    /// Whether shifting the map wraps tiles around to the opposite edge
+    shift_wrap: bool,
+    /// Whether shifting the map fills the cells left behind with the selected tile
+    shift_fill: bool,
+    /// Whether dragging on the map selects an area instead of placing tiles
+    select_tool: bool,
+    /// The selected area of a map
+    selection: Option<MapSelection>,
+    /// Whether the selection is being dragged out
+    selecting: bool,
+    /// Whether to color each map cell by how many times its metatile is used in the project
+    show_heatmap: bool,
+    /// Whether to show rulers along the edges of the canvas, counting from the current level
+    show_rulers: bool,
+    /// The unit the rulers are labeled in
+    ruler_unit: RulerUnit,
+    /// Whether hovering a map cell shows the PPU addresses it is exported to
+    show_ppu_addresses: bool,
+    /// The tiles that were placed most recently, newest first
+    recent_tiles: Vec<Uid<MetatilesetTile>>,
+    /// The width of the level sidebar the last time it was shown
+    sidebar_width: Option<f32>,
+    /// The width to restore the level sidebar to the next time it is shown
+    restore_sidebar_width: Option<f32>,
+    /// Whether the map is edited one screen at a time instead of on the open canvas
+    screen_mode: bool,
+    /// The column and row of the screen of the current level that is edited in screen mode
+    current_screen: (i32, i32),
+    /// The map file being read for a level, or the error reading it
+    map_import: WatchReceiver<MapImport>,
+    /// Whether the current level is painted with the keyboard, using a cursor moved by the arrow
+    /// keys instead of the mouse
+    keyboard_mode: bool,
+    /// The map cell of the current level under the keyboard cursor
+    keyboard_cursor: (i32, i32),
+    /// Whether the view needs to scroll to show the keyboard cursor
+    follow_keyboard_cursor: bool,
+}
+
+/// A level and the cells read from a CSV or TSV file to replace its map with
+type MapImport = Option<(Uid<Level>, Result<Vec<Vec<Option<usize>>>, String>)>;
+
+/// The file types that maps are imported from and exported to
+const MAP_FILE_FILTERS: &[FileFilter] = &[
+    FileFilter {
+        name: "Comma-Separated Values",
+        extensions: &["csv"],
+    },
+    FileFilter {
+        name: "Tab-Separated Values",
+        extensions: &["tsv", "tab"],
+    },
+];
+
+/// The units that map sizes are entered in
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum SizeUnit {
+    Metatiles,
+    Screens,
+}
+
+/// A map size entered by the user
+#[derive(Copy, Clone, Debug)]
+struct MapSize {
+    width: i32,
+    height: i32,
+    unit: SizeUnit,
+}
+
+impl MapSize {
+    /// The width and height in metatiles
+    fn metatiles(&self) -> (i32, i32) {
+        match self.unit {
+            SizeUnit::Metatiles => (self.width, self.height),
+            SizeUnit::Screens => (
+                self.width * SCREEN_WIDTH_METATILES as i32,
+                self.height * SCREEN_HEIGHT_METATILES as i32,
+            ),
+        }
+    }
+}
+
+/// A rectangular area of a level's map selected with the selection tool
+#[derive(Copy, Clone, Debug)]
+struct MapSelection {
+    level_id: Uid<Level>,
+    /// The metatile the selection was started from
+    start: (i32, i32),
+    /// The metatile the selection was dragged to
+    end: (i32, i32),
 }
 
+impl MapSelection {
+    /// Get the top-left and bottom-right metatiles of the selection
+    fn bounds(&self) -> ((i32, i32), (i32, i32)) {
+        let (start, end) = (self.start, self.end);
+        (
+            (start.0.min(end.0), start.1.min(end.1)),
+            (start.0.max(end.0), start.1.max(end.1)),
+        )
+    }
+}
+
+/// The state of the "Generate Map" window
+struct GenerateMap {
+    /// The level being generated, if the window is open
+    level_id: Option<Uid<Level>>,
+    generators: Vec<Box<dyn MapGenerator>>,
+    /// The index of the generator to use
+    current: usize,
+    /// The metatiles that each kind of terrain is filled with, indexed like [`Terrain::ALL`]
+    blocks: [Vec<BuildingBlock>; 2],
+    seed: u64,
+    /// Whether to only fill the selected area instead of the whole map
+    selection_only: bool,
+}
+
+impl Default for GenerateMap {
+    fn default() -> Self {
+        Self {
+            level_id: None,
+            generators: map_generator::generators(),
+            current: 0,
+            blocks: Default::default(),
+            seed: rand::random::<u32>() as u64,
+            selection_only: true,
+        }
+    }
+}
+
+/// The state of the "Resize Map" window
+struct ResizeMap {
+    level_id: Uid<Level>,
+    size: MapSize,
+    /// The part of the map that stays in place, from `-1` to `1` on each axis, see
+    /// [`Level::resize`]
+    anchor: (i32, i32),
+}
+
+/// A level that an exit can lead to, collected before editing the current level
+struct ExitTarget {
+    level_id: Uid<Level>,
+    name: String,
+    zones: Vec<(Uid<Zone>, String)>,
+}
+
+/// The color used to shade the HUD rows of a level
+const HUD_COLOR: egui::Color32 = egui::Color32::from_rgb(90, 200, 230);
+
+/// The color used to draw the scroll split
+const SPLIT_COLOR: egui::Color32 = egui::Color32::from_rgb(240, 200, 60);
+
+/// The color used to draw the first scanline of each pallet region
+const PALLET_REGION_COLOR: egui::Color32 = egui::Color32::from_rgb(230, 120, 200);
+
+/// The scale the screen preview is shown at
+const SCREEN_PREVIEW_ZOOM: f32 = 2.0;
+
+/// The color used to outline the selected area of a map
+const SELECTION_COLOR: egui::Color32 = egui::Color32::from_rgb(120, 220, 255);
+
+/// The color used to outline the tile under the eyedropper
+const EYEDROPPER_COLOR: egui::Color32 = egui::Color32::from_rgb(255, 255, 255);
+
+/// The color used to draw the overscan guides
+const OVERSCAN_COLOR: egui::Color32 = egui::Color32::from_rgb(230, 70, 70);
+
+/// The color used to draw the links between levels
+const EXIT_COLOR: egui::Color32 = egui::Color32::from_rgb(230, 110, 230);
+
+/// The color of the star marking favorite tiles in the tile picker
+const FAVORITE_COLOR: egui::Color32 = egui::Color32::from_rgb(250, 210, 60);
+
+/// The color used to outline the keyboard cursor
+const KEYBOARD_CURSOR_COLOR: egui::Color32 = egui::Color32::from_rgb(255, 140, 40);
+
+/// The number of recently placed tiles to remember for the tile picker
+const RECENT_TILES_LEN: usize = 16;
+
+/// The width and height, in NES pixels, of the area shown in the magnifier lens
+const MAGNIFIER_PIXELS: i32 = 24;
+
+/// The number of screen pixels used for each NES pixel in the magnifier lens
+const MAGNIFIER_ZOOM: f32 = 8.0;
+
+/// The space left around the screen in screen mode, in points
+const SCREEN_MODE_MARGIN: f32 = 16.0;
+
+/// How close to the edge of the canvas, in points, dragging starts scrolling the view
+const AUTOSCROLL_EDGE_SIZE: f32 = 48.0;
+
+/// How fast the view scrolls when dragging right at the edge of the canvas, in points per second
+const AUTOSCROLL_MAX_SPEED: f32 = 800.0;
+
+/// How fast a gamepad stick pushed all the way scrolls the view, in points per second
+const GAMEPAD_SCROLL_SPEED: f32 = 600.0;
+
+/// How fast a gamepad trigger pulled all the way zooms the view, as a fraction of the zoom per
+/// second
+const GAMEPAD_ZOOM_SPEED: f32 = 1.5;
+
+/// The keys used to set and jump to the map bookmarks, 1-9
+const BOOKMARK_KEYS: [egui::Key; 9] = [
+    egui::Key::Num1,
+    egui::Key::Num2,
+    egui::Key::Num3,
+    egui::Key::Num4,
+    egui::Key::Num5,
+    egui::Key::Num6,
+    egui::Key::Num7,
+    egui::Key::Num8,
+    egui::Key::Num9,
+];
+
 impl Default for MapsTab {
     fn default() -> Self {
         Self {
             zoom: 1.0,
             pan: egui::Vec2::ZERO,
             dragging_level: None,
+            context_menu_level: None,
             current_level: None,
             tile_list_col_count: 5,
             current_metatileset_tile: None,
+            brush: Vec::new(),
+            magnifier: false,
+            zone_visibility: [true; ZoneKind::ALL.len()],
+            zone_tool: false,
+            new_zone_kind: Default::default(),
+            zone_drag: None,
+            show_exits: false,
+            animate: true,
+            effects: Default::default(),
+            composite: false,
+            backdrop: Backdrop::Color,
+            show_overscan: false,
+            show_screen_preview: false,
+            new_level_size: MapSize {
+                width: 1,
+                height: 1,
+                unit: SizeUnit::Screens,
+            },
+            resize_map: None,
+            generate_map: Default::default(),
+            shift_amount: 1,
+            shift_wrap: false,
+            shift_fill: false,
+            select_tool: false,
+            selection: None,
+            selecting: false,
+            show_heatmap: false,
+            show_rulers: false,
+            ruler_unit: Default::default(),
+            show_ppu_addresses: false,
+            recent_tiles: Vec::new(),
+            sidebar_width: None,
+            restore_sidebar_width: None,
+            screen_mode: false,
+            current_screen: (0, 0),
+            map_import: watch::channel(None).1,
+            keyboard_mode: false,
+            keyboard_cursor: (0, 0),
+            follow_keyboard_cursor: false,
         }
     }
 }
@@ -44,6 +356,66 @@ impl NesimgGuiTab for MapsTab {
             }
         }
 
+        if let Some((level_id, cells)) = self.map_import.get_if_new().flatten() {
+            self.import_map(project, ctx, level_id, cells);
+        }
+
+        // Keep the current screen inside of the level, in case it was resized
+        if let Some(level) = self
+            .current_level
+            .and_then(|id| project.data.levels.get(&id))
+        {
+            let (width, height) = level.screen_size();
+            self.current_screen = (
+                self.current_screen.0.clamp(0, width - 1),
+                self.current_screen.1.clamp(0, height - 1),
+            );
+        }
+
+        // Keep redrawing while tile animations or composite color artifacts are playing
+        if self.animate && (self.composite || !project.data.tile_animations.is_empty()) {
+            ctx.request_repaint();
+        }
+
+        // Handle bookmark shortcuts
+        if !ctx.wants_keyboard_input() {
+            if ctx
+                .input_mut()
+                .consume_key(egui::Modifiers::NONE, egui::Key::Escape)
+            {
+                self.selection = None;
+            }
+
+            if self.screen_mode {
+                if ctx
+                    .input_mut()
+                    .consume_key(egui::Modifiers::NONE, egui::Key::PageDown)
+                {
+                    self.step_screen(project, 1);
+                } else if ctx
+                    .input_mut()
+                    .consume_key(egui::Modifiers::NONE, egui::Key::PageUp)
+                {
+                    self.step_screen(project, -1);
+                }
+            }
+
+            // Keyboard painting takes the number keys from the bookmarks
+            if self.keyboard_mode {
+                self.keyboard_paint(project, ctx);
+            }
+
+            for (slot, key) in (1..).zip(BOOKMARK_KEYS) {
+                if ctx.input_mut().consume_key(egui::Modifiers::COMMAND, key) {
+                    self.set_bookmark(project, slot);
+                } else if ctx.input_mut().consume_key(egui::Modifiers::NONE, key) {
+                    self.jump_to_bookmark(project, slot);
+                }
+            }
+        }
+
+        self.gamepad_navigation(project, ctx);
+
         let central_frame = egui::Frame {
             fill: ctx.style().visuals.window_fill(),
             ..Default::default()
@@ -60,31 +432,132 @@ impl NesimgGuiTab for MapsTab {
                     ui.spacing_mut().item_spacing = default_spacing;
 
                     ui.with_layout(egui::Layout::right_to_left(), |ui| {
-                        if ui.button("Reset View").clicked() {
+                        if ui
+                            .add_enabled(!self.screen_mode, egui::Button::new("Reset View"))
+                            .clicked()
+                        {
                             self.zoom = 1.0;
                             self.pan = egui::Vec2::ZERO;
                         }
                         ui.add_space(10.0);
+                        if ui
+                            .toggle_value(&mut self.keyboard_mode, "⌨ Keyboard")
+                            .on_hover_text(
+                                "Paint with the keyboard: the arrow keys move a cursor over the \
+                                current level, Space places the selected tile, Delete erases, \
+                                1-9 pick the recently used tiles, and Q and E step through the \
+                                tiles of the metatileset",
+                            )
+                            .changed()
+                        {
+                            self.follow_keyboard_cursor = true;
+                        }
+                        if self.keyboard_mode {
+                            ui.monospace(format!(
+                                "Cursor: ({}, {})",
+                                self.keyboard_cursor.0, self.keyboard_cursor.1
+                            ));
+                        }
+                        ui.add_space(10.0);
+                        ui.toggle_value(&mut self.screen_mode, "▦ Screens")
+                            .on_hover_text(
+                                "Edit the current level one screen at a time, for engines that \
+                                store levels as a list of screens",
+                            );
+                        ui.add_space(10.0);
                         ui.monospace(format!("Zoom: {:>5.1}", self.zoom));
                         ui.add_space(10.0);
                         ui.monospace(format!("Pan: {:>15}", format!("{:?}", self.pan)));
+                        ui.add_space(10.0);
+                        ui.toggle_value(&mut self.magnifier, "🔍 Magnifier")
+                            .on_hover_text(
+                                "Show a zoomed in view around the cursor, with a pixel grid and \
+                                the color under the cursor",
+                            );
+                        ui.add_space(10.0);
+                        if self.show_rulers {
+                            ruler_unit_gui(ui, "maps_ruler_unit", &mut self.ruler_unit);
+                        }
+                        ui.toggle_value(&mut self.show_ppu_addresses, "🖥 PPU Addresses")
+                            .on_hover_text(
+                                "Show the nametable and attribute addresses of the map cell under \
+                                the cursor, for comparing against an emulator's memory viewer",
+                            );
+                        ui.add_space(10.0);
+                        ui.toggle_value(&mut self.show_rulers, "📏 Rulers")
+                            .on_hover_text(
+                                "Show rulers along the edges of the canvas, counting from the \
+                                top-left corner of the current level, and the coordinates under \
+                                the cursor",
+                            );
+                        ui.add_space(10.0);
+                        ui.toggle_value(&mut self.animate, "▶ Animate")
+                            .on_hover_text("Play the tile animations");
+                        ui.add_space(10.0);
+                        ui.toggle_value(&mut self.show_heatmap, "🔥 Usage Heatmap")
+                            .on_hover_text(
+                                "Color each tile by how many times its metatile is used in all \
+                                of the levels: red for once, orange for 2-3 times, yellow for 4-9 \
+                                times, and green for more",
+                            );
+                        ui.add_space(10.0);
+                        overscan_gui(&mut self.show_overscan, &mut project.data.overscan, ui);
+                        ui.toggle_value(&mut self.composite, "📺 Composite")
+                            .on_hover_text(
+                                "Simulate the blur and color artifacts of composite video, to see \
+                                how dithering will blend together on a CRT",
+                            );
+                        ppu_effects_gui(&mut self.effects, ui);
+                        ui.add_space(10.0);
+                        backdrop_gui(ui, "maps_backdrop", &mut self.backdrop);
+                        ui.add_space(10.0);
+                        ui.toggle_value(
+                            &mut self.show_exits,
+                            egui::RichText::new("🔗 Exits").color(EXIT_COLOR),
+                        )
+                        .on_hover_text("Show how the levels are connected by their exits");
+                        for kind in ZoneKind::ALL.into_iter().rev() {
+                            ui.toggle_value(
+                                &mut self.zone_visibility[kind.export_id() as usize],
+                                egui::RichText::new(format!("⬛ {}", kind.name()))
+                                    .color(zone_color(kind)),
+                            )
+                            .on_hover_text(format!("Show {} zones", kind.name()));
+                        }
                     });
                 });
 
+                if self.screen_mode {
+                    egui::TopBottomPanel::top("screen_navigation").show_inside(ui, |ui| {
+                        ui.spacing_mut().item_spacing = default_spacing;
+                        self.screen_navigation_gui(project, ui);
+                    });
+                }
+
                 let sidebar_frame = egui::Frame {
                     inner_margin: egui::style::Margin::symmetric(8.0, 0.0),
                     fill: ctx.style().visuals.window_fill(),
                     stroke: ctx.style().visuals.window_stroke(),
                     ..Default::default()
                 };
-                egui::SidePanel::left("level_sidebar")
+                let mut sidebar = egui::SidePanel::left("level_sidebar")
                     .frame(sidebar_frame)
                     .min_width(150.0)
-                    .max_width(400.0)
+                    .max_width(400.0);
+                if let Some(width) = self.restore_sidebar_width.take() {
+                    // Pin the sidebar to the restored width for one frame, after which it is
+                    // remembered by egui
+                    let width = width.clamp(150.0, 400.0);
+                    sidebar = sidebar.width_range(width..=width);
+                }
+                let sidebar_rect = sidebar
                     .show_inside(ui, |ui| {
                         ui.spacing_mut().item_spacing = default_spacing;
                         self.level_sidebar_gui(project, ui, frame);
-                    });
+                    })
+                    .response
+                    .rect;
+                self.sidebar_width = Some(sidebar_rect.width());
 
                 // Reset the spacing to the default after adding the panels
                 ui.spacing_mut().item_spacing = default_spacing;
@@ -95,6 +568,10 @@ impl NesimgGuiTab for MapsTab {
                         self.map_canvas_gui(project, ui, frame);
                     });
             });
+
+        self.screen_preview_gui(project, ctx, frame);
+        self.resize_map_gui(project, ctx);
+        self.generate_map_gui(project, ctx, frame);
     }
 
     fn help_text(&self) -> &'static str {
@@ -104,6 +581,31 @@ impl NesimgGuiTab for MapsTab {
     fn tooltip(&self) -> &'static str {
         "Create maps and levels from metatiles"
     }
+
+    fn save_layout(&self, layout: &mut ProjectLayout) {
+        layout.maps_view = Some(CanvasView {
+            zoom: self.zoom,
+            pan: [self.pan.x, self.pan.y],
+        });
+        layout.maps_sidebar_width = self.sidebar_width.or(layout.maps_sidebar_width);
+        layout.maps_level = self.current_level;
+        layout.maps_screen = Some(self.current_screen).filter(|_| self.screen_mode);
+    }
+
+    fn load_layout(&mut self, layout: &ProjectLayout) {
+        if let Some(view) = layout.maps_view {
+            self.zoom = view.zoom;
+            self.pan = view.pan.into();
+        }
+        self.restore_sidebar_width = layout.maps_sidebar_width;
+        self.current_level = layout.maps_level;
+        self.screen_mode = layout.maps_screen.is_some();
+        self.current_screen = layout.maps_screen.unwrap_or_default();
+        self.current_metatileset_tile = None;
+        self.brush.clear();
+        self.recent_tiles.clear();
+        self.selection = None;
+    }
 }
 
 impl MapsTab {
@@ -114,6 +616,9 @@ impl MapsTab {
         frame: &mut eframe::Frame,
     ) {
         ui.add_space(ui.spacing().window_margin.top);
+        self.bookmarks_gui(project, ui);
+        ui.separator();
+
         ui.horizontal(|ui| {
             ui.horizontal(|ui| {
                 ui.set_height(ui.spacing().interact_size.y);
@@ -138,6 +643,25 @@ impl MapsTab {
             return;
         };
 
+        // The levels and zones that exits can lead to
+        let exit_targets = project
+            .data
+            .levels
+            .iter()
+            .map(|(id, level)| {
+                let zones = level
+                    .zones
+                    .iter()
+                    .map(|(id, zone)| (*id, zone.name.clone()))
+                    .collect::<Vec<_>>();
+                ExitTarget {
+                    level_id: *id,
+                    name: level.name.clone(),
+                    zones,
+                }
+            })
+            .collect::<Vec<_>>();
+
         let level = project.data.levels.get_mut(&level_id).unwrap();
 
         ui.horizontal(|ui| {
@@ -163,7 +687,33 @@ impl MapsTab {
 
         ui.separator();
 
-        ui.label("Margins:");
+        ui.horizontal(|ui| {
+            ui.label("Margins:");
+            ui.with_layout(egui::Layout::right_to_left(), |ui| {
+                if ui
+                    .button("⤢ Resize Map…")
+                    .on_hover_text("Change the size of the map, keeping the tiles that still fit")
+                    .clicked()
+                {
+                    self.resize_map = Some(ResizeMap {
+                        level_id,
+                        size: MapSize {
+                            width: level.margin.width(),
+                            height: level.margin.height(),
+                            unit: SizeUnit::Metatiles,
+                        },
+                        anchor: (-1, -1),
+                    });
+                }
+                if ui
+                    .button("🎲 Generate…")
+                    .on_hover_text("Fill the map or the selection with a maze, caves, or noise")
+                    .clicked()
+                {
+                    self.generate_map.level_id = Some(level_id);
+                }
+            });
+        });
         ui.indent("margin", |ui| {
             ui.horizontal(|ui| {
                 ui.label("Top");
@@ -192,7 +742,52 @@ impl MapsTab {
         });
         ui.separator();
 
+        self.shift_gui(level, ui);
+        ui.separator();
+
+        ui.label("HUD Rows:").on_hover_text(
+            "Metatile rows at the top and bottom of the map that make up a status bar, which are \
+            exported separately from the scrolling playfield",
+        );
+        ui.indent("hud", |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Top");
+                ui.with_layout(egui::Layout::right_to_left(), |ui| {
+                    ui.add(DragValue::new(&mut level.hud.top));
+                });
+            });
+            ui.horizontal(|ui| {
+                ui.label("Bottom");
+                ui.with_layout(egui::Layout::right_to_left(), |ui| {
+                    ui.add(DragValue::new(&mut level.hud.bottom));
+                });
+            });
+        });
+        ui.separator();
+
+        self.zones_gui(level, ui);
+        ui.separator();
+
+        exits_gui(level_id, level, &exit_targets, ui);
+        ui.separator();
+
+        split_gui(level, &exit_targets, &mut self.show_screen_preview, ui);
+        ui.separator();
+
+        let base_pallet = project
+            .data
+            .metatilesets
+            .get(&level.metatileset_id)
+            .map(|x| x.pallet.clone())
+            .unwrap_or_default();
+        pallet_regions_gui(level, &base_pallet, &mut self.show_screen_preview, ui);
+        ui.separator();
+
         let metatileset_id = level.metatileset_id;
+
+        self.selection_gui(project, ui);
+        ui.separator();
+
         let metatileset = if let Some(metatileset) = project.data.metatilesets.get(&metatileset_id)
         {
             metatileset
@@ -208,57 +803,215 @@ impl MapsTab {
 
         let item_spacing = egui::Vec2::splat(ui.spacing().item_spacing.x);
         ui.spacing_mut().item_spacing = item_spacing;
-        let hovered_stroke_color = ui.visuals().widgets.hovered.fg_stroke.color;
-        let tile_rounding = 2.0;
 
         let tile_ids = metatileset.tiles.keys().cloned().collect::<Vec<_>>();
+        let favorite_ids = metatileset
+            .favorite_tiles
+            .iter()
+            .filter(|&id| metatileset.tiles.contains_key(id))
+            .cloned()
+            .collect::<Vec<_>>();
+        let recent_ids = self
+            .recent_tiles
+            .iter()
+            .filter(|&id| metatileset.tiles.contains_key(id))
+            .take(self.tile_list_col_count as usize)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        // The tile to pin or unpin from the favorites
+        let mut toggle_favorite = None;
+
+        if !favorite_ids.is_empty() {
+            ui.label("★ Favorites")
+                .on_hover_text("Right-click a tile to pin or unpin it");
+            ui.horizontal_wrapped(|ui| {
+                for &id in &favorite_ids {
+                    self.tile_picker_button(project, metatileset_id, id, true, ui, frame)
+                        .context_menu(|ui| {
+                            if ui.button("☆ Unpin From Favorites").clicked() {
+                                toggle_favorite = Some(id);
+                                ui.close_menu();
+                            }
+                        });
+                }
+            });
+        }
+        if !recent_ids.is_empty() {
+            ui.label("🕘 Recently Used");
+            ui.horizontal_wrapped(|ui| {
+                for &id in &recent_ids {
+                    let favorite = favorite_ids.contains(&id);
+                    self.tile_picker_button(project, metatileset_id, id, favorite, ui, frame);
+                }
+            });
+        }
+        if !favorite_ids.is_empty() || !recent_ids.is_empty() {
+            ui.separator();
+        }
+
         ui.scope(|ui| {
             egui::ScrollArea::new([false, true]).show(ui, |ui| {
                 ui.add_space(ui.spacing().item_spacing.y);
                 ui.horizontal_wrapped(|ui| {
-                    for id in tile_ids {
-                        let tile_region_display_size = egui::Vec2::splat(
-                            ui.available_width() / self.tile_list_col_count as f32,
-                        ) - item_spacing;
-
-                        let (rect, mut response) =
-                            ui.allocate_exact_size(tile_region_display_size, egui::Sense::click());
-
-                        if response.clicked() {
-                            response.mark_changed();
-                        }
-
-                        MetatileGui::new(
+                    for &id in &tile_ids {
+                        let favorite = favorite_ids.contains(&id);
+                        let response = self.tile_picker_button(
                             project,
-                            MetatileKind::Metatileset {
-                                metatileset_id,
-                                metatileset_tile_id: id,
-                            },
-                        )
-                        .paint_at(rect, ui, frame);
-
-                        if self.current_metatileset_tile == Some(id) {
-                            ui.painter().rect_stroke(
-                                rect,
-                                tile_rounding,
-                                (2.0, egui::Color32::GREEN),
-                            );
-                        } else if response.hovered() {
-                            ui.painter().rect_stroke(
-                                rect,
-                                tile_rounding,
-                                (2.0, hovered_stroke_color),
-                            );
-                        }
-
-                        if response.clicked() {
-                            self.current_metatileset_tile = Some(id);
+                            metatileset_id,
+                            id,
+                            favorite,
+                            ui,
+                            frame,
+                        );
+                        if response.clicked() && ui.input().modifiers.shift {
+                            self.select_brush(&tile_ids, id);
                         }
+                        response.context_menu(|ui| {
+                            let label = if favorite {
+                                "☆ Unpin From Favorites"
+                            } else {
+                                "★ Pin to Favorites"
+                            };
+                            if ui.button(label).clicked() {
+                                toggle_favorite = Some(id);
+                                ui.close_menu();
+                            }
+                        });
                     }
                 });
                 ui.add_space(ui.spacing().item_spacing.y);
             });
         });
+
+        if let Some(id) = toggle_favorite {
+            let favorites = &mut project
+                .data
+                .metatilesets
+                .get_mut(&metatileset_id)
+                .unwrap()
+                .favorite_tiles;
+            match favorites.iter().position(|x| *x == id) {
+                Some(idx) => {
+                    favorites.remove(idx);
+                }
+                None => favorites.push(id),
+            }
+        }
+    }
+
+    /// Select the block of tiles in the tile picker between the current tile and `end` as the brush
+    fn select_brush(&mut self, tile_ids: &[Uid<MetatilesetTile>], end: Uid<MetatilesetTile>) {
+        let cols = self.tile_list_col_count.max(1) as usize;
+        let grid_pos = |id| {
+            tile_ids
+                .iter()
+                .position(|x| *x == id)
+                .map(|idx| (idx % cols, idx / cols))
+        };
+        let (start, end) = match (
+            self.current_metatileset_tile.and_then(grid_pos),
+            grid_pos(end),
+        ) {
+            (Some(start), Some(end)) => (start, end),
+            _ => {
+                self.current_metatileset_tile = Some(end);
+                return;
+            }
+        };
+
+        let (min_col, max_col) = (start.0.min(end.0), start.0.max(end.0));
+        let (min_row, max_row) = (start.1.min(end.1), start.1.max(end.1));
+        self.brush.clear();
+        for row in min_row..=max_row {
+            for col in min_col..=max_col {
+                if let Some(&id) = tile_ids.get(row * cols + col) {
+                    self.brush
+                        .push((((col - min_col) as i32, (row - min_row) as i32), id));
+                }
+            }
+        }
+        if self.brush.len() == 1 {
+            self.brush.clear();
+        }
+    }
+
+    /// Render a tile in the tile picker, selecting it when it is clicked, unless Shift is held to
+    /// select a block of tiles
+    fn tile_picker_button(
+        &mut self,
+        project: &mut ProjectState,
+        metatileset_id: Uid<Metatileset>,
+        id: Uid<MetatilesetTile>,
+        favorite: bool,
+        ui: &mut egui::Ui,
+        frame: &mut eframe::Frame,
+    ) -> egui::Response {
+        let tile_rounding = 2.0;
+        let item_spacing = ui.spacing().item_spacing;
+        let tile_region_display_size =
+            egui::Vec2::splat(ui.available_width() / self.tile_list_col_count as f32)
+                - item_spacing;
+
+        let (rect, mut response) =
+            ui.allocate_exact_size(tile_region_display_size, egui::Sense::click());
+
+        if response.clicked() {
+            response.mark_changed();
+        }
+
+        MetatileGui::new(
+            project,
+            MetatileKind::Metatileset {
+                metatileset_id,
+                metatileset_tile_id: id,
+            },
+        )
+        .effects(self.effects)
+        .backdrop(self.backdrop)
+        .paint_at(rect, ui, frame);
+
+        if favorite {
+            ui.painter().text(
+                rect.right_top() + egui::vec2(-2.0, 1.0),
+                egui::Align2::RIGHT_TOP,
+                "★",
+                egui::FontId::proportional(10.0),
+                FAVORITE_COLOR,
+            );
+        }
+
+        let selected =
+            self.current_metatileset_tile == Some(id) || self.brush.iter().any(|x| x.1 == id);
+        if selected {
+            ui.painter()
+                .rect_stroke(rect, tile_rounding, (2.0, egui::Color32::GREEN));
+        } else if response.hovered() {
+            let hovered_stroke_color = ui.visuals().widgets.hovered.fg_stroke.color;
+            ui.painter()
+                .rect_stroke(rect, tile_rounding, (2.0, hovered_stroke_color));
+        }
+        accessibility::paint_focus(ui, &response, tile_rounding);
+        accessibility::image_button(&response, selected, || {
+            let number = project
+                .data
+                .metatilesets
+                .get(&metatileset_id)
+                .and_then(|x| x.tiles.get_index_of(&id))
+                .map_or(0, |x| x + 1);
+            format!(
+                "Metatile {}{}",
+                number,
+                if favorite { ", favorite" } else { "" }
+            )
+        });
+
+        if response.clicked() && !ui.input().modifiers.shift {
+            self.current_metatileset_tile = Some(id);
+            self.brush.clear();
+        }
+
+        response
     }
 
     fn map_canvas_gui(
@@ -271,14 +1024,42 @@ impl MapsTab {
         let canvas_center = canvas_rect.center();
         ui.set_clip_rect(canvas_rect);
 
+        // In screen mode, swap in a view that fits the current screen to the canvas for this
+        // frame, and clip everything to it
+        let screen_view = self
+            .current_level
+            .filter(|_| self.screen_mode)
+            .and_then(|id| project.data.levels.get(&id))
+            .map(|level| self.screen_view(level, canvas_rect));
+        let free_view = (self.zoom, self.pan);
+        if let Some((zoom, pan, screen_rect)) = screen_view {
+            self.zoom = zoom;
+            self.pan = pan;
+            ui.set_clip_rect(screen_rect.intersect(canvas_rect));
+        }
+
         let mut response = ui.interact(
             canvas_rect,
             egui::Id::new("map_canvas"),
             egui::Sense::click_and_drag(),
         );
+        accessibility::canvas(&response, || {
+            let level = self
+                .current_level
+                .and_then(|id| project.data.levels.get(&id))
+                .map_or("no level selected", |x| x.name.as_str());
+            if self.keyboard_mode {
+                format!(
+                    "Map canvas, {}, keyboard cursor at {}, {}",
+                    level, self.keyboard_cursor.0, self.keyboard_cursor.1
+                )
+            } else {
+                format!("Map canvas, {}", level)
+            }
+        });
 
         // Handle zoom
-        if response.hovered() {
+        if response.hovered() && screen_view.is_none() {
             self.zoom *= 1000.0;
             self.zoom += ui.input().scroll_delta.y * 5.0;
             self.zoom /= 1000.0;
@@ -288,10 +1069,32 @@ impl MapsTab {
         // Handle pan
         let panning_map_view =
             response.dragged_by(egui::PointerButton::Middle) || ui.input().modifiers.command;
-        if response.dragged_by(egui::PointerButton::Middle) || ui.input().modifiers.command {
+        if panning_map_view && screen_view.is_none() {
             self.pan += response.drag_delta();
         }
 
+        // Scroll the view while painting or dragging near the edge of the canvas, faster the
+        // closer the pointer is to the edge
+        if response.dragged_by(egui::PointerButton::Primary)
+            && !panning_map_view
+            && screen_view.is_none()
+        {
+            if let Some(pos) = ui.input().pointer.interact_pos() {
+                let speed = |dist_to_min: f32, dist_to_max: f32| {
+                    let proximity = |dist: f32| (1.0 - dist / AUTOSCROLL_EDGE_SIZE).clamp(0.0, 1.0);
+                    (proximity(dist_to_min) - proximity(dist_to_max)) * AUTOSCROLL_MAX_SPEED
+                };
+                let scroll = egui::Vec2::new(
+                    speed(pos.x - canvas_rect.min.x, canvas_rect.max.x - pos.x),
+                    speed(pos.y - canvas_rect.min.y, canvas_rect.max.y - pos.y),
+                );
+                if scroll != egui::Vec2::ZERO {
+                    self.pan += scroll * ui.input().predicted_dt;
+                    ui.ctx().request_repaint();
+                }
+            }
+        }
+
         // Handle cursor
         if response.dragged_by(egui::PointerButton::Middle)
             || (ui.input().modifiers.command && response.dragged_by(egui::PointerButton::Primary))
@@ -301,14 +1104,18 @@ impl MapsTab {
             response = response.on_hover_cursor(egui::CursorIcon::Grab);
         }
 
-        let pointer_pos = ui.input().pointer.interact_pos();
+        let pointer_pos = ui
+            .input()
+            .pointer
+            .interact_pos()
+            .filter(|pos| screen_view.is_none() || ui.clip_rect().contains(*pos));
+        let animation_time = self.animation_time(ui);
 
         enum TileAction {
             None,
-            AddTile {
+            AddTiles {
                 level_id: Uid<Level>,
-                pos: (i32, i32),
-                tile: LevelTile,
+                tiles: Vec<((i32, i32), LevelTile)>,
             },
             EraseTile {
                 level_id: Uid<Level>,
@@ -317,25 +1124,25 @@ impl MapsTab {
         }
         let mut tile_action = TileAction::None;
         let level_ids = project.data.levels.keys().cloned().collect::<Vec<_>>();
+        let metatile_uses = if self.show_heatmap {
+            metatile_use_counts(project)
+        } else {
+            HashMap::default()
+        };
 
         let mut mouse_over_level = false;
+        let mut hovered_level = None;
+        let mut hovered_label = false;
         for id in level_ids {
+            // Only the current level is edited in screen mode
+            if screen_view.is_some() && self.current_level != Some(id) {
+                continue;
+            }
+
             let level = project.data.levels.get(&id).unwrap();
             let level_margin = level.margin;
 
-            let tile_size = 16_f32;
-            let canvas_pos = canvas_center + level.world_offset * self.zoom + self.pan;
-            let min = canvas_pos
-                - egui::Vec2::new(
-                    level_margin.left as f32 * tile_size * self.zoom,
-                    level_margin.top as f32 * tile_size * self.zoom,
-                );
-            let max = canvas_pos
-                + egui::Vec2::new(
-                    level_margin.right as f32 * tile_size * self.zoom,
-                    level_margin.bottom as f32 * tile_size * self.zoom,
-                );
-            let level_rect = egui::Rect { min, max };
+            let level_rect = self.level_rect(canvas_center, level);
 
             // Render the map label
             let label_pos = level_rect.center_top();
@@ -355,52 +1162,228 @@ impl MapsTab {
             let metatileset_id = level.metatileset_id;
             if let Some(metatileset) = project.data.metatilesets.get(&metatileset_id) {
                 // Paint the background color
-                let background_color = NES_PALLET[metatileset.pallet.colors[0] as usize];
+                let background_color = self.effects.apply(metatileset.pallet.colors[0]);
                 ui.painter().rect_filled(level_rect, 2.0, background_color);
 
+                // Only paint the tiles that are scrolled into view, so that huge maps stay fast
+                let tile_size = 16.0 * self.zoom;
+                let visible = ui.clip_rect().intersect(level_rect);
+                let visible_min = (
+                    ((visible.min.x - level_rect.min.x) / tile_size).floor() as i32
+                        - level_margin.left,
+                    ((visible.min.y - level_rect.min.y) / tile_size).floor() as i32
+                        - level_margin.top,
+                );
+                let visible_max = (
+                    ((visible.max.x - level_rect.min.x) / tile_size).ceil() as i32
+                        - level_margin.left,
+                    ((visible.max.y - level_rect.min.y) / tile_size).ceil() as i32
+                        - level_margin.top,
+                );
                 let tiles = level
                     .tiles
-                    .iter()
-                    .map(|(pos, tile)| (*pos, tile.clone()))
+                    .in_rect(
+                        (
+                            visible_min.0.max(-level_margin.left),
+                            visible_min.1.max(-level_margin.top),
+                        ),
+                        (
+                            visible_max.0.min(level_margin.right),
+                            visible_max.1.min(level_margin.bottom),
+                        ),
+                    )
+                    .map(|(pos, tile)| (*pos, tile.metatileset_tile_id))
                     .collect::<Vec<_>>();
 
-                for ((level_x, level_y), tile) in tiles {
-                    let metatileset_tile_id = tile.metatileset_tile_id;
-                    if level_x > -level_margin.left - 1
-                        && level_x < level_margin.right
-                        && level_y > -level_margin.top - 1
-                        && level_y < level_margin.bottom
-                    {
-                        let x = level_x + level_margin.left;
-                        let y = level_y + level_margin.top;
-                        let tile_size = 16.0 * self.zoom;
-                        let tile_rect = egui::Rect::from_min_size(
-                            level_rect.min
-                                + egui::Vec2::new(x as f32 * tile_size, y as f32 * tile_size),
-                            egui::Vec2::splat(tile_size),
-                        );
+                for ((level_x, level_y), metatileset_tile_id) in tiles {
+                    let x = level_x + level_margin.left;
+                    let y = level_y + level_margin.top;
+                    let tile_rect = egui::Rect::from_min_size(
+                        level_rect.min
+                            + egui::Vec2::new(x as f32 * tile_size, y as f32 * tile_size),
+                        egui::Vec2::splat(tile_size),
+                    );
 
-                        MetatileGui::new(
-                            project,
-                            MetatileKind::Metatileset {
-                                metatileset_id,
-                                metatileset_tile_id,
-                            },
-                        )
-                        .paint_at(tile_rect, ui, frame);
+                    MetatileGui::new(
+                        project,
+                        MetatileKind::Metatileset {
+                            metatileset_id,
+                            metatileset_tile_id,
+                        },
+                    )
+                    .animated(animation_time)
+                    .effects(self.effects)
+                    .backdrop(self.backdrop)
+                    .composite(Some((x * 16, y * 16)).filter(|_| self.composite))
+                    .paint_at(tile_rect, ui, frame);
+
+                    if self.show_heatmap {
+                        let uses = project.data.metatilesets[&metatileset_id]
+                            .tiles
+                            .get(&metatileset_tile_id)
+                            .and_then(|x| metatile_uses.get(&x.metatile_id))
+                            .copied()
+                            .unwrap_or_default();
+                        ui.painter().rect_filled(
+                            tile_rect,
+                            0.0,
+                            heatmap_color(uses).linear_multiply(0.45),
+                        );
+                        if pointer_pos.map(|x| tile_rect.contains(x)).unwrap_or(false) {
+                            egui::show_tooltip_at_pointer(
+                                ui.ctx(),
+                                egui::Id::new("heatmap_tooltip"),
+                                |ui| {
+                                    ui.label(if uses == 1 {
+                                        "Metatile used once".into()
+                                    } else {
+                                        format!("Metatile used {} times", uses)
+                                    });
+                                },
+                            );
+                        }
                     }
                 }
             }
 
-            let pointer_within_label = pointer_pos.map(|x| label_rect.contains(x)).unwrap_or(false);
-            let pointer_within_level = pointer_pos.map(|x| level_rect.contains(x)).unwrap_or(false);
-
-            if pointer_within_level {
-                mouse_over_level = true;
+            // Render the overscan guides
+            let level = &project.data.levels[&id];
+            if self.show_overscan {
+                paint_overscan(ui, level_rect, level, project.data.overscan, self.zoom);
             }
 
-            // Check drag state and update cursor
-            if response.dragged_by(egui::PointerButton::Primary)
+            // Shade the HUD rows
+            let tile_size = 16.0 * self.zoom;
+            for (rows, top) in [
+                (level.hud.top, level_rect.top()),
+                (
+                    level.hud.bottom,
+                    level_rect.bottom() - level.hud.bottom as f32 * tile_size,
+                ),
+            ] {
+                if rows == 0 {
+                    continue;
+                }
+                let hud_rect = egui::Rect::from_x_y_ranges(
+                    level_rect.x_range(),
+                    top.max(level_rect.top())
+                        ..=(top + rows as f32 * tile_size).min(level_rect.bottom()),
+                );
+                ui.painter()
+                    .rect_filled(hud_rect, 0.0, HUD_COLOR.linear_multiply(0.2));
+                ui.painter().text(
+                    hud_rect.left_top() + egui::Vec2::splat(4.0),
+                    egui::Align2::LEFT_TOP,
+                    "HUD",
+                    egui::FontId::monospace(12.0),
+                    HUD_COLOR,
+                );
+            }
+
+            // Render the scroll split across the screen it splits
+            if let Some(split) = &level.split {
+                let y = level_rect.top() + split.scanline as f32 * self.zoom;
+                let width = (SCREEN_WIDTH_METATILES * 16) as f32 * self.zoom;
+                let right = (level_rect.left() + width).min(level_rect.right());
+                if y < level_rect.bottom() {
+                    ui.painter().extend(egui::Shape::dashed_line(
+                        &[egui::pos2(level_rect.left(), y), egui::pos2(right, y)],
+                        egui::Stroke::new(2.0, SPLIT_COLOR),
+                        6.0,
+                        4.0,
+                    ));
+                }
+            }
+
+            // Render the first scanline of each pallet region across the screen it changes
+            for (idx, region) in level.pallet_regions.iter().enumerate() {
+                let y = level_rect.top() + region.scanline as f32 * self.zoom;
+                let width = (SCREEN_WIDTH_METATILES * 16) as f32 * self.zoom;
+                let right = (level_rect.left() + width).min(level_rect.right());
+                if y < level_rect.bottom() {
+                    ui.painter().extend(egui::Shape::dashed_line(
+                        &[egui::pos2(level_rect.left(), y), egui::pos2(right, y)],
+                        egui::Stroke::new(2.0, PALLET_REGION_COLOR),
+                        2.0,
+                        4.0,
+                    ));
+                    ui.painter().text(
+                        egui::pos2(right - 4.0, y + 2.0),
+                        egui::Align2::RIGHT_TOP,
+                        format!("Pallet {}", idx + 1),
+                        egui::FontId::monospace(12.0),
+                        PALLET_REGION_COLOR,
+                    );
+                }
+            }
+
+            // Render the zones
+            for zone in level.zones.values() {
+                if self.zone_visibility[zone.kind.export_id() as usize] {
+                    paint_zone(
+                        ui,
+                        zone_rect(level_rect, level_margin, self.zoom, zone),
+                        zone,
+                    );
+                }
+            }
+            if let (Some((start, end)), Some(current_level)) = (self.zone_drag, self.current_level)
+            {
+                if current_level == id {
+                    let zone = zone_from_drag(start, end, self.new_zone_kind);
+                    paint_zone(
+                        ui,
+                        zone_rect(level_rect, level_margin, self.zoom, &zone),
+                        &zone,
+                    );
+                }
+            }
+
+            // Render the selection
+            if let Some(selection) = self.selection.filter(|x| x.level_id == id) {
+                let (min, max) = selection.bounds();
+                let tile_size = 16.0 * self.zoom;
+                let rect = egui::Rect::from_min_max(
+                    level_rect.min
+                        + egui::vec2(
+                            (min.0 + level_margin.left) as f32,
+                            (min.1 + level_margin.top) as f32,
+                        ) * tile_size,
+                    level_rect.min
+                        + egui::vec2(
+                            (max.0 + 1 + level_margin.left) as f32,
+                            (max.1 + 1 + level_margin.top) as f32,
+                        ) * tile_size,
+                );
+                ui.painter()
+                    .rect_filled(rect, 0.0, SELECTION_COLOR.linear_multiply(0.15));
+                ui.painter().extend(egui::Shape::dashed_line(
+                    &[
+                        rect.left_top(),
+                        rect.right_top(),
+                        rect.right_bottom(),
+                        rect.left_bottom(),
+                        rect.left_top(),
+                    ],
+                    egui::Stroke::new(2.0, SELECTION_COLOR),
+                    6.0,
+                    4.0,
+                ));
+            }
+
+            let pointer_within_label = pointer_pos.map(|x| label_rect.contains(x)).unwrap_or(false);
+            let pointer_within_level = pointer_pos.map(|x| level_rect.contains(x)).unwrap_or(false);
+
+            if pointer_within_level {
+                mouse_over_level = true;
+                hovered_level = Some((id, level_rect));
+            }
+            if pointer_within_label {
+                hovered_label = true;
+            }
+
+            // Check drag state and update cursor
+            if response.dragged_by(egui::PointerButton::Primary)
                 && response.drag_started()
                 && pointer_within_label
                 && !panning_map_view
@@ -412,6 +1395,12 @@ impl MapsTab {
                 response = response.on_hover_cursor(egui::CursorIcon::Grab);
             }
 
+            // Remember which level's name opened the context menu, since the pointer leaves it
+            // to use the menu
+            if response.secondary_clicked() && pointer_within_label {
+                self.context_menu_level = Some(id);
+            }
+
             // Focus level if clicked
             if response.clicked_by(egui::PointerButton::Primary)
                 && (pointer_within_level || pointer_within_label)
@@ -438,7 +1427,94 @@ impl MapsTab {
                 let level_x_idx = -level_margin.left + tile_xy_idx.x as i32;
                 let level_y_idx = -level_margin.top + tile_xy_idx.y as i32;
 
-                if ui
+                if self.show_ppu_addresses {
+                    let text = ppu_address_text(
+                        &project.data.levels[&id],
+                        (tile_xy_idx.x as i32, tile_xy_idx.y as i32),
+                    );
+                    egui::show_tooltip_at_pointer(
+                        ui.ctx(),
+                        egui::Id::new("ppu_address_tooltip"),
+                        |ui| {
+                            ui.monospace(text);
+                        },
+                    );
+                }
+
+                if self.zone_tool {
+                    let pos = (level_x_idx, level_y_idx);
+                    if ui.input().pointer.any_pressed()
+                        && ui.input().pointer.primary_down()
+                        && !panning_map_view
+                    {
+                        self.zone_drag = Some((pos, pos));
+                    } else if let Some((_, end)) = &mut self.zone_drag {
+                        *end = pos;
+                    }
+                    ui.painter()
+                        .rect_stroke(tile_rect, 0.0, (1.0, zone_color(self.new_zone_kind)));
+                } else if self.select_tool {
+                    let pos = (level_x_idx, level_y_idx);
+                    if ui.input().pointer.any_pressed()
+                        && ui.input().pointer.primary_down()
+                        && !panning_map_view
+                    {
+                        self.selection = Some(MapSelection {
+                            level_id: id,
+                            start: pos,
+                            end: pos,
+                        });
+                        self.selecting = true;
+                    } else if let (true, Some(selection)) = (self.selecting, &mut self.selection) {
+                        selection.end = pos;
+                    }
+                    ui.painter()
+                        .rect_stroke(tile_rect, 0.0, (1.0, SELECTION_COLOR));
+                } else if ui.input().modifiers.alt {
+                    // Pick the tile under the cursor as the brush
+                    response = response.on_hover_cursor(egui::CursorIcon::Crosshair);
+                    ui.painter()
+                        .rect_stroke(tile_rect, 0.0, (1.0, EYEDROPPER_COLOR));
+                    if ui.input().pointer.primary_down() && !panning_map_view {
+                        let picked = project.data.levels[&id]
+                            .tiles
+                            .get(&(level_x_idx, level_y_idx))
+                            .map(|x| x.metatileset_tile_id);
+                        if let Some(picked) = picked {
+                            self.current_metatileset_tile = Some(picked);
+                            self.brush.clear();
+                        }
+                    }
+
+                    // Jump to the metatile under the cursor, or with Shift, to the source tile
+                    // of the quadrant under the cursor. This is only done with Alt held, since a
+                    // plain double-click paints.
+                    if response.double_clicked() {
+                        let metatile = project.data.levels[&id]
+                            .tiles
+                            .get(&(level_x_idx, level_y_idx))
+                            .and_then(|x| {
+                                let metatileset = project.data.metatilesets.get(&metatileset_id)?;
+                                Some(metatileset.tiles.get(&x.metatileset_tile_id)?.metatile_id)
+                            });
+                        let quadrant = ((pointer_pos.to_pos2() - tile_rect.min) / tile_size * 2.0)
+                            .floor()
+                            .clamp(egui::Vec2::ZERO, egui::Vec2::splat(1.0));
+                        let target = if ui.input().modifiers.shift {
+                            metatile
+                                .and_then(|x| project.data.metatiles.get(&x))
+                                .and_then(|x| {
+                                    x.tiles[quadrant.y as usize * 2 + quadrant.x as usize].clone()
+                                })
+                                .map(JumpTarget::SourceTile)
+                        } else {
+                            metatile.map(JumpTarget::Metatile)
+                        };
+                        if let Some(target) = target {
+                            jump_to(ui.ctx(), target);
+                        }
+                    }
+                } else if ui
                     .input()
                     .pointer
                     .button_down(egui::PointerButton::Secondary)
@@ -447,31 +1523,111 @@ impl MapsTab {
                         level_id: id,
                         pos: (level_x_idx, level_y_idx),
                     };
-                } else if let Some(metatileset_tile_id) = self.current_metatileset_tile {
-                    MetatileGui::new(
-                        project,
-                        MetatileKind::Metatileset {
-                            metatileset_id,
-                            metatileset_tile_id,
-                        },
-                    )
-                    .paint_at(tile_rect, ui, frame);
+                } else if self.current_metatileset_tile.is_some() {
+                    let brush = self.current_brush();
+                    let in_metatileset = project
+                        .data
+                        .metatilesets
+                        .get(&metatileset_id)
+                        .map(|x| brush.iter().all(|(_, id)| x.tiles.contains_key(id)))
+                        .unwrap_or(false);
+                    if !in_metatileset {
+                        // The brush is from another metatileset, so it can't be placed here
+                        ui.painter()
+                            .rect_stroke(tile_rect, 0.0, (1.0, egui::Color32::RED));
+                    } else {
+                        // Preview the tiles the way they will look once they're placed, skipping
+                        // the ones that fall off of the map
+                        let mut tiles = Vec::with_capacity(brush.len());
+                        for ((offset_x, offset_y), metatileset_tile_id) in brush {
+                            let pos = (level_x_idx + offset_x, level_y_idx + offset_y);
+                            if pos.0 >= level_margin.right || pos.1 >= level_margin.bottom {
+                                continue;
+                            }
+                            let (x, y) = (pos.0 + level_margin.left, pos.1 + level_margin.top);
+                            let rect = tile_rect.translate(
+                                egui::vec2(offset_x as f32, offset_y as f32) * tile_size,
+                            );
+                            MetatileGui::new(
+                                project,
+                                MetatileKind::Metatileset {
+                                    metatileset_id,
+                                    metatileset_tile_id,
+                                },
+                            )
+                            .animated(animation_time)
+                            .effects(self.effects)
+                            .backdrop(self.backdrop)
+                            .composite(Some((x * 16, y * 16)).filter(|_| self.composite))
+                            .paint_at(rect, ui, frame);
+                            tiles.push((
+                                pos,
+                                LevelTile {
+                                    metatileset_tile_id,
+                                },
+                            ));
+                        }
+                        let brush_rect = tiles.iter().fold(tile_rect, |rect, ((x, y), _)| {
+                            rect.union(tile_rect.translate(
+                                egui::vec2((x - level_x_idx) as f32, (y - level_y_idx) as f32)
+                                    * tile_size,
+                            ))
+                        });
+                        ui.painter().rect_stroke(
+                            brush_rect,
+                            0.0,
+                            (1.0, egui::Color32::WHITE.linear_multiply(0.5)),
+                        );
 
-                    let tile_xy_idx = (pointer_uv * level_size_in_tiles).floor();
-                    let level_x_idx = -level_margin.left + tile_xy_idx.x as i32;
-                    let level_y_idx = -level_margin.top + tile_xy_idx.y as i32;
-                    if ui.input().pointer.button_down(egui::PointerButton::Primary) {
-                        tile_action = TileAction::AddTile {
-                            level_id: id,
-                            pos: (level_x_idx, level_y_idx),
-                            tile: LevelTile {
-                                metatileset_tile_id,
-                            },
-                        };
+                        if ui.input().pointer.button_down(egui::PointerButton::Primary) {
+                            tile_action = TileAction::AddTiles {
+                                level_id: id,
+                                tiles,
+                            };
+                        }
                     }
                 }
             }
 
+            // Render the keyboard cursor, with the size of the brush
+            if self.keyboard_mode && self.current_level == Some(id) {
+                let tile_size = 16.0 * self.zoom;
+                let (x, y) = self.keyboard_cursor;
+                let (width, height) = self
+                    .current_brush()
+                    .iter()
+                    .fold((1, 1), |(w, h), ((x, y), _)| (w.max(x + 1), h.max(y + 1)));
+                let cursor_rect = egui::Rect::from_min_size(
+                    level_rect.min
+                        + egui::vec2(
+                            (x + level_margin.left) as f32,
+                            (y + level_margin.top) as f32,
+                        ) * tile_size,
+                    egui::vec2(width as f32, height as f32) * tile_size,
+                );
+                ui.painter()
+                    .rect_stroke(cursor_rect, 0.0, (2.0, KEYBOARD_CURSOR_COLOR));
+
+                // Scroll just far enough to bring the cursor into view
+                if self.follow_keyboard_cursor && screen_view.is_none() {
+                    let view = canvas_rect.shrink(tile_size.min(canvas_rect.width() / 4.0));
+                    let offset = |min: f32, max: f32, view_min: f32, view_max: f32| {
+                        if min < view_min {
+                            view_min - min
+                        } else if max > view_max {
+                            (view_max - max).max(view_min - min)
+                        } else {
+                            0.0
+                        }
+                    };
+                    self.pan += egui::vec2(
+                        offset(cursor_rect.min.x, cursor_rect.max.x, view.min.x, view.max.x),
+                        offset(cursor_rect.min.y, cursor_rect.max.y, view.min.y, view.max.y),
+                    );
+                }
+                self.follow_keyboard_cursor = false;
+            }
+
             // Render the level stroke
             ui.painter().rect_stroke(
                 level_rect,
@@ -484,14 +1640,97 @@ impl MapsTab {
             );
         }
 
-        if !mouse_over_level {
+        if self.show_exits {
+            self.exits_overlay_gui(project, ui, canvas_center);
+        }
+
+        // Outline the screen being edited, and mark the edges that it can be left through
+        if let Some((_, _, screen_rect)) = screen_view {
+            let painter = ui.painter().with_clip_rect(canvas_rect);
+            painter.rect_stroke(
+                screen_rect,
+                0.0,
+                ui.style().visuals.widgets.active.fg_stroke,
+            );
+
+            let flags = self
+                .current_level
+                .map(|id| project.data.levels[&id].screen_flags(self.current_screen))
+                .unwrap_or_default();
+            let rect = screen_rect.expand(3.0);
+            for edge in MapEdge::ALL {
+                if flags.exits[edge.export_id() as usize] {
+                    let points = match edge {
+                        MapEdge::Top => [rect.left_top(), rect.right_top()],
+                        MapEdge::Right => [rect.right_top(), rect.right_bottom()],
+                        MapEdge::Bottom => [rect.left_bottom(), rect.right_bottom()],
+                        MapEdge::Left => [rect.left_top(), rect.left_bottom()],
+                    };
+                    painter.line_segment(points, (4.0, EXIT_COLOR));
+                }
+            }
+        }
+
+        accessibility::paint_focus(ui, &response, 0.0);
+
+        if self.show_rulers {
+            self.rulers_gui(project, ui, canvas_rect, hovered_level, pointer_pos);
+        }
+
+        if let (true, Some((id, level_rect)), Some(pointer_pos)) =
+            (self.magnifier, hovered_level, pointer_pos)
+        {
+            self.magnifier_gui(project, ui, frame, id, level_rect, pointer_pos);
+        }
+
+        if response.secondary_clicked() && !hovered_label {
+            self.context_menu_level = None;
+        }
+        if let Some(level_id) = self.context_menu_level {
+            response = response.context_menu(|ui| {
+                ui.menu_button("⮫ Export Only This Level", |ui| {
+                    export_only_menu(ui, project, ExportOnly::Level(level_id));
+                });
+                ui.separator();
+                if ui
+                    .button("⮫ Export Map as CSV…")
+                    .on_hover_text(
+                        "Save the metatile index of each cell, for editing in a spreadsheet or \
+                        a script. Empty cells are -1",
+                    )
+                    .clicked()
+                {
+                    ui.close_menu();
+                    export_map_file(ui.ctx(), project, level_id);
+                }
+                if ui
+                    .button("⮪ Import Map from CSV…")
+                    .on_hover_text(
+                        "Replace the map with the metatile indexes in a CSV or TSV file, \
+                        resizing it to fit",
+                    )
+                    .clicked()
+                {
+                    ui.close_menu();
+                    self.map_import = pick_file(MAP_FILE_FILTERS, move |path| {
+                        let cells = std::fs::read_to_string(path)
+                            .map_err(anyhow::Error::from)
+                            .and_then(|text| map_csv::decode_map(&text, map_csv::delimiter(path)))
+                            .map_err(|e| format!("{:#}", e));
+                        Some((level_id, cells))
+                    });
+                }
+            });
+        } else if !mouse_over_level && screen_view.is_none() {
             response = response.context_menu(|ui| {
+                map_size_gui(ui, &mut self.new_level_size);
                 if ui.button("➕ Create Level").clicked() {
                     ui.close_menu();
 
                     if let Some(pos) = pointer_pos {
                         let world_pos =
                             (pos.to_vec2() - canvas_center.to_vec2() - self.pan) / self.zoom;
+                        let (width, height) = self.new_level_size.metatiles();
 
                         // Add new level
                         let id = Uid::new();
@@ -499,6 +1738,12 @@ impl MapsTab {
                             id,
                             Level {
                                 world_offset: world_pos,
+                                margin: LevelMargin {
+                                    top: 0,
+                                    right: width,
+                                    bottom: height,
+                                    left: 0,
+                                },
                                 ..Default::default()
                             },
                         );
@@ -508,15 +1753,31 @@ impl MapsTab {
             });
         }
 
+        // Add the zone that was drawn when the mouse is released
+        if let Some((start, end)) = self.zone_drag {
+            if !ui.input().pointer.primary_down() {
+                self.zone_drag = None;
+                if let Some(level) = self
+                    .current_level
+                    .and_then(|id| project.data.levels.get_mut(&id))
+                {
+                    let mut zone = zone_from_drag(start, end, self.new_zone_kind);
+                    zone.name = format!("{} {}", zone.kind.name(), level.zones.len() + 1);
+                    level.zones.insert(Uid::new(), zone);
+                }
+            }
+        }
+
+        if self.selecting && !ui.input().pointer.primary_down() {
+            self.selecting = false;
+        }
+
         // Add a new tile if one was place
         match tile_action {
-            TileAction::AddTile {
-                level_id,
-                pos,
-                tile,
-            } => {
+            TileAction::AddTiles { level_id, tiles } => {
                 let level = project.data.levels.get_mut(&level_id).unwrap();
-                level.tiles.insert(pos, tile);
+                self.remember_current_tile();
+                level.tiles.extend(tiles);
             }
             TileAction::EraseTile { level_id, pos } => {
                 let level = project.data.levels.get_mut(&level_id).unwrap();
@@ -546,5 +1807,2033 @@ impl MapsTab {
 
             level.world_offset += response.drag_delta() / self.zoom;
         }
+
+        if screen_view.is_some() {
+            (self.zoom, self.pan) = free_view;
+        }
+    }
+
+    /// Replace the map of a level with the cells read from a map file
+    fn import_map(
+        &mut self,
+        project: &mut ProjectState,
+        ctx: &egui::Context,
+        level_id: Uid<Level>,
+        cells: Result<Vec<Vec<Option<usize>>>, String>,
+    ) {
+        let result = cells.map_err(anyhow::Error::msg).and_then(|cells| {
+            let level = project
+                .data
+                .levels
+                .get_mut(&level_id)
+                .context("The level isn't in the project anymore")?;
+            let metatileset = project
+                .data
+                .metatilesets
+                .get(&level.metatileset_id)
+                .with_context(|| format!("Level `{}` doesn't have a metatileset", level.name))?;
+            map_csv::import_map(level, metatileset, &cells)?;
+            Ok(format!(
+                "Imported a {}x{} map into level `{}`",
+                cells[0].len(),
+                cells.len(),
+                level.name
+            ))
+        });
+
+        match result {
+            Ok(message) => {
+                self.current_level = Some(level_id);
+                send_info_notification(ctx, &message);
+            }
+            Err(e) => send_error_notification(ctx, format!("{:#}", e)),
+        }
+    }
+
+    /// The zoom and pan that fit the current screen of `level` in the middle of the canvas, and
+    /// the rect that the screen is drawn in
+    fn screen_view(&self, level: &Level, canvas_rect: egui::Rect) -> (f32, egui::Vec2, egui::Rect) {
+        let screen_size = egui::Vec2::new(
+            (SCREEN_WIDTH_METATILES * 16) as f32,
+            (SCREEN_HEIGHT_METATILES * 16) as f32,
+        );
+        let zoom = ((canvas_rect.size() - egui::Vec2::splat(2.0 * SCREEN_MODE_MARGIN))
+            / screen_size)
+            .min_elem()
+            .max(0.1);
+
+        // The top-left corner of the screen relative to the level's world offset, in pixels
+        let (x, y) = self.current_screen;
+        let screen_min = egui::Vec2::new(
+            ((x * SCREEN_WIDTH_METATILES as i32 - level.margin.left) * 16) as f32,
+            ((y * SCREEN_HEIGHT_METATILES as i32 - level.margin.top) * 16) as f32,
+        );
+        let pan = -(level.world_offset + screen_min + screen_size / 2.0) * zoom;
+
+        (
+            zoom,
+            pan,
+            egui::Rect::from_center_size(canvas_rect.center(), screen_size * zoom),
+        )
+    }
+
+    /// Move `by` screens forward or back through the current level, in the order that screen
+    /// lists are stored in: left to right, then top to bottom
+    fn step_screen(&mut self, project: &ProjectState, by: i32) {
+        if let Some(level) = self
+            .current_level
+            .and_then(|id| project.data.levels.get(&id))
+        {
+            let (width, height) = level.screen_size();
+            let index = (self.current_screen.1 * width + self.current_screen.0 + by)
+                .clamp(0, width * height - 1);
+            self.current_screen = (index % width, index / width);
+        }
+    }
+
+    /// Scroll and zoom the view, and switch between levels and screens, with the gamepads
+    fn gamepad_navigation(&mut self, project: &ProjectState, ctx: &egui::Context) {
+        let input = gamepad::input(ctx);
+        if input == GamepadInput::default() {
+            return;
+        }
+
+        if input.toggle_screens {
+            self.screen_mode = !self.screen_mode;
+        }
+        if input.reset_view {
+            self.zoom = 1.0;
+            self.pan = egui::Vec2::ZERO;
+        }
+
+        // Step through the levels in the order they are laid out on the canvas, since the current
+        // level is always moved to the end of the list, and center the view on the new level
+        if input.switch_level != 0 && !project.data.levels.is_empty() {
+            let mut levels = project
+                .data
+                .levels
+                .iter()
+                .map(|(id, level)| (*id, level.world_offset))
+                .collect::<Vec<_>>();
+            levels.sort_by(|(_, a), (_, b)| {
+                (a.y, a.x)
+                    .partial_cmp(&(b.y, b.x))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+            let idx = self
+                .current_level
+                .and_then(|id| levels.iter().position(|(x, _)| *x == id))
+                .map_or(0, |idx| {
+                    (idx as i32 + input.switch_level).rem_euclid(levels.len() as i32) as usize
+                });
+            let (id, _) = levels[idx];
+            let level = &project.data.levels[&id];
+            let margin = level.margin;
+            let center = level.world_offset
+                + egui::vec2(
+                    (margin.right - margin.left) as f32,
+                    (margin.bottom - margin.top) as f32,
+                ) * 8.0;
+            self.current_level = Some(id);
+            self.current_screen = (0, 0);
+            self.pan = -center * self.zoom;
+        }
+
+        if self.screen_mode {
+            if let Some(level) = self
+                .current_level
+                .and_then(|id| project.data.levels.get(&id))
+            {
+                let (width, height) = level.screen_size();
+                self.current_screen = (
+                    (self.current_screen.0 + input.step.0).clamp(0, width - 1),
+                    (self.current_screen.1 + input.step.1).clamp(0, height - 1),
+                );
+            }
+        } else {
+            let dt = ctx.input().predicted_dt;
+            self.pan -= input.scroll * GAMEPAD_SCROLL_SPEED * dt;
+
+            // Zoom around the center of the canvas
+            let zoom = (self.zoom * (1.0 + input.zoom * GAMEPAD_ZOOM_SPEED * dt)).max(0.1);
+            self.pan *= zoom / self.zoom;
+            self.zoom = zoom;
+        }
+    }
+
+    /// The tiles painted at once, by their offset from the cursor
+    fn current_brush(&self) -> Vec<((i32, i32), Uid<MetatilesetTile>)> {
+        match self.current_metatileset_tile {
+            Some(id) if self.brush.is_empty() => vec![((0, 0), id)],
+            Some(_) => self.brush.clone(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Move the current tile to the front of the recently used tiles
+    fn remember_current_tile(&mut self) {
+        if let Some(current) = self.current_metatileset_tile {
+            if self.recent_tiles.first() != Some(&current) {
+                self.recent_tiles.retain(|x| *x != current);
+                self.recent_tiles.insert(0, current);
+                self.recent_tiles.truncate(RECENT_TILES_LEN);
+            }
+        }
+    }
+
+    /// Handle the keys used to paint the current level with the keyboard. The arrow keys move the
+    /// cursor, or a whole screen with Shift, Space places the brush at the cursor, Delete and
+    /// Backspace erase the tile under it, 1-9 pick the recently used tiles, and Q and E step back
+    /// and forward through the tiles of the level's metatileset.
+    fn keyboard_paint(&mut self, project: &mut ProjectState, ctx: &egui::Context) {
+        let level_id = match self.current_level {
+            Some(id) => id,
+            None => return,
+        };
+        let level = match project.data.levels.get(&level_id) {
+            Some(level) => level,
+            None => return,
+        };
+        let margin = level.margin;
+        let metatileset_id = level.metatileset_id;
+        let pressed = |modifiers, key| ctx.input_mut().consume_key(modifiers, key);
+
+        // Move the cursor
+        let mut step = (0, 0);
+        for (key, (x, y)) in [
+            (egui::Key::ArrowLeft, (-1, 0)),
+            (egui::Key::ArrowRight, (1, 0)),
+            (egui::Key::ArrowUp, (0, -1)),
+            (egui::Key::ArrowDown, (0, 1)),
+        ] {
+            if pressed(egui::Modifiers::NONE, key) {
+                step = (step.0 + x, step.1 + y);
+            } else if pressed(egui::Modifiers::SHIFT, key) {
+                step = (
+                    step.0 + x * SCREEN_WIDTH_METATILES as i32,
+                    step.1 + y * SCREEN_HEIGHT_METATILES as i32,
+                );
+            }
+        }
+        let cursor = (
+            (self.keyboard_cursor.0 + step.0)
+                .min(margin.right - 1)
+                .max(-margin.left),
+            (self.keyboard_cursor.1 + step.1)
+                .min(margin.bottom - 1)
+                .max(-margin.top),
+        );
+        if cursor != self.keyboard_cursor {
+            self.keyboard_cursor = cursor;
+            self.follow_keyboard_cursor = true;
+            if self.screen_mode {
+                self.current_screen = (
+                    (cursor.0 + margin.left) / SCREEN_WIDTH_METATILES as i32,
+                    (cursor.1 + margin.top) / SCREEN_HEIGHT_METATILES as i32,
+                );
+            }
+        }
+
+        // Pick the brush
+        let metatileset_tiles = project
+            .data
+            .metatilesets
+            .get(&metatileset_id)
+            .map(|x| x.tiles.keys().copied().collect::<Vec<_>>())
+            .unwrap_or_default();
+        let recent = self
+            .recent_tiles
+            .iter()
+            .filter(|x| metatileset_tiles.contains(x))
+            .copied()
+            .collect::<Vec<_>>();
+        for (idx, key) in BOOKMARK_KEYS.into_iter().enumerate() {
+            if pressed(egui::Modifiers::NONE, key) {
+                if let Some(&id) = recent.get(idx) {
+                    self.current_metatileset_tile = Some(id);
+                    self.brush.clear();
+                }
+            }
+        }
+        for (key, by) in [(egui::Key::Q, -1), (egui::Key::E, 1)] {
+            if pressed(egui::Modifiers::NONE, key) && !metatileset_tiles.is_empty() {
+                let len = metatileset_tiles.len() as i32;
+                let idx = self
+                    .current_metatileset_tile
+                    .and_then(|id| metatileset_tiles.iter().position(|x| *x == id))
+                    .map_or(if by > 0 { 0 } else { len - 1 }, |idx| {
+                        (idx as i32 + by).rem_euclid(len)
+                    });
+                self.current_metatileset_tile = Some(metatileset_tiles[idx as usize]);
+                self.brush.clear();
+            }
+        }
+
+        // Paint
+        if pressed(egui::Modifiers::NONE, egui::Key::Space) {
+            let brush = self.current_brush();
+            if brush.is_empty() {
+                send_warning_notification(ctx, "Pick a tile to paint with first".into());
+            } else if !brush.iter().all(|(_, id)| metatileset_tiles.contains(id)) {
+                send_warning_notification(
+                    ctx,
+                    "The brush is from a different metatileset than the level uses".into(),
+                );
+            } else {
+                self.remember_current_tile();
+                let tiles = brush
+                    .into_iter()
+                    .map(|((x, y), metatileset_tile_id)| {
+                        (
+                            (cursor.0 + x, cursor.1 + y),
+                            LevelTile {
+                                metatileset_tile_id,
+                            },
+                        )
+                    })
+                    .filter(|((x, y), _)| *x < margin.right && *y < margin.bottom)
+                    .collect::<Vec<_>>();
+                let level = project.data.levels.get_mut(&level_id).unwrap();
+                level.tiles.extend(tiles);
+            }
+        }
+        if pressed(egui::Modifiers::NONE, egui::Key::Delete)
+            || pressed(egui::Modifiers::NONE, egui::Key::Backspace)
+        {
+            let level = project.data.levels.get_mut(&level_id).unwrap();
+            level.tiles.remove(&cursor);
+        }
+    }
+
+    /// The bar for picking the level and screen that is edited in screen mode, and editing the
+    /// screen's flags
+    fn screen_navigation_gui(&mut self, project: &mut ProjectState, ui: &mut egui::Ui) {
+        let (mut previous, mut next) = (false, false);
+        ui.horizontal(|ui| {
+            ui.label("Level");
+            let selected = self
+                .current_level
+                .and_then(|id| project.data.levels.get(&id))
+                .map(|level| level.name.as_str())
+                .unwrap_or("None");
+            let mut current_level = self.current_level;
+            egui::ComboBox::from_id_source("screen_level")
+                .selected_text(selected)
+                .show_ui(ui, |ui| {
+                    for (id, level) in &project.data.levels {
+                        ui.selectable_value(&mut current_level, Some(*id), &level.name);
+                    }
+                });
+            if current_level != self.current_level {
+                self.current_level = current_level;
+                self.current_screen = (0, 0);
+            }
+
+            let level = match self
+                .current_level
+                .and_then(|id| project.data.levels.get_mut(&id))
+            {
+                Some(level) => level,
+                None => return,
+            };
+            let (width, height) = level.screen_size();
+            let (x, y) = self.current_screen;
+            let index = y * width + x;
+
+            ui.add_space(10.0);
+            if ui
+                .add_enabled(index > 0, egui::Button::new("◀ Prev"))
+                .on_hover_text("Go to the previous screen (Page Up)")
+                .clicked()
+            {
+                previous = true;
+            }
+            ui.monospace(format!(
+                "Screen {:>3} of {:<3} (column {}, row {})",
+                index + 1,
+                width * height,
+                x,
+                y
+            ));
+            if ui
+                .add_enabled(index < width * height - 1, egui::Button::new("Next ▶"))
+                .on_hover_text("Go to the next screen (Page Down)")
+                .clicked()
+            {
+                next = true;
+            }
+
+            ui.add_space(10.0);
+            ui.separator();
+            let mut flags = level.screen_flags((x, y));
+            ui.label("Exits");
+            for (edge, icon) in MapEdge::ALL.into_iter().zip(["⬆", "➡", "⬇", "⬅"]) {
+                ui.toggle_value(&mut flags.exits[edge.export_id() as usize], icon)
+                    .on_hover_text(format!(
+                        "The player can leave the screen through its {}",
+                        edge.name().to_lowercase()
+                    ));
+            }
+            ui.add_space(10.0);
+            ui.toggle_value(&mut flags.scroll_lock, "🔒 Scroll Lock")
+                .on_hover_text("The camera stops scrolling while it is on this screen");
+            ui.add_space(10.0);
+            ui.label("Room Type");
+            ui.add(
+                DragValue::new(&mut flags.room_type).clamp_range(0..=ScreenFlags::MAX_ROOM_TYPE),
+            )
+            .on_hover_text("A kind of room defined by the game, exported in the screen table");
+            level.set_screen_flags((x, y), flags);
+        });
+        if previous {
+            self.step_screen(project, -1);
+        } else if next {
+            self.step_screen(project, 1);
+        }
+    }
+
+    /// Show the top-left screen of the current level the way the NES would draw it, with the
+    /// lower region of its scroll split and its pallet regions
+    fn screen_preview_gui(
+        &mut self,
+        project: &mut ProjectState,
+        ctx: &egui::Context,
+        frame: &mut eframe::Frame,
+    ) {
+        let level_id = match self.current_level {
+            Some(id) if self.show_screen_preview => id,
+            _ => return,
+        };
+
+        let mut open = true;
+        egui::Window::new("📺 Screen Preview")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let level = &project.data.levels[&level_id];
+                let split = level.split.clone();
+                let region_scanlines = level
+                    .pallet_regions
+                    .iter()
+                    .map(|x| x.scanline as i32)
+                    .collect::<Vec<_>>();
+                let screen_size = egui::Vec2::new(
+                    (SCREEN_WIDTH_METATILES * 16) as f32,
+                    (SCREEN_HEIGHT_METATILES * 16) as f32,
+                );
+                let (screen_rect, _) =
+                    ui.allocate_exact_size(screen_size * SCREEN_PREVIEW_ZOOM, egui::Sense::hover());
+                ui.painter()
+                    .rect_filled(screen_rect, 0.0, egui::Color32::BLACK);
+
+                let split_y = split
+                    .as_ref()
+                    .map(|x| x.scanline as i32)
+                    .unwrap_or(screen_size.y as i32);
+                let lower_level_id = split
+                    .as_ref()
+                    .and_then(|x| x.level_id)
+                    .filter(|id| project.data.levels.contains_key(id))
+                    .unwrap_or(level_id);
+
+                // Paint the screen in bands between the split and the pallet regions, since each
+                // band can show a different level or pallet
+                let mut bounds = vec![0, split_y, screen_size.y as i32];
+                bounds.extend(region_scanlines.iter().copied());
+                bounds.sort_unstable();
+                bounds.dedup();
+                for band in bounds.windows(2) {
+                    let (top, bottom) = (band[0], band[1]);
+                    let (band_level_id, scroll) = match &split {
+                        Some(split) if top >= split_y => {
+                            (lower_level_id, (split.scroll_x, split.scroll_y))
+                        }
+                        _ => (level_id, (0, 0)),
+                    };
+                    let pallet = project.data.levels[&level_id].region_pallet(top).cloned();
+                    let clip = egui::Rect::from_x_y_ranges(
+                        screen_rect.x_range(),
+                        screen_rect.top() + top as f32 * SCREEN_PREVIEW_ZOOM
+                            ..=screen_rect.top() + bottom as f32 * SCREEN_PREVIEW_ZOOM,
+                    );
+                    self.paint_screen_region(
+                        project,
+                        band_level_id,
+                        scroll,
+                        clip,
+                        screen_rect.min,
+                        pallet.as_ref(),
+                        ui,
+                        frame,
+                    );
+                }
+
+                for scanline in region_scanlines {
+                    ui.painter().hline(
+                        screen_rect.x_range(),
+                        screen_rect.top() + scanline as f32 * SCREEN_PREVIEW_ZOOM,
+                        egui::Stroke::new(1.0, PALLET_REGION_COLOR),
+                    );
+                }
+
+                let split = match split {
+                    Some(split) => split,
+                    None => {
+                        ui.label("This level has no scroll split. Add one in the sidebar.");
+                        return;
+                    }
+                };
+                ui.painter().hline(
+                    screen_rect.x_range(),
+                    screen_rect.top() + split_y as f32 * SCREEN_PREVIEW_ZOOM,
+                    egui::Stroke::new(1.0, SPLIT_COLOR),
+                );
+
+                match sprite_zero_hit_pos(project, level_id, split.scanline) {
+                    Some((x, y)) => {
+                        let pos =
+                            screen_rect.min + egui::vec2(x as f32, y as f32) * SCREEN_PREVIEW_ZOOM;
+                        ui.painter().rect_stroke(
+                            egui::Rect::from_min_size(pos, egui::Vec2::splat(SCREEN_PREVIEW_ZOOM))
+                                .expand(2.0),
+                            0.0,
+                            (1.5, egui::Color32::YELLOW),
+                        );
+                        ui.label(format!(
+                            "The closest opaque background pixel above the split is at ({}, {}). \
+                            Sprite zero can overlap it to trigger the split.",
+                            x, y
+                        ));
+                    }
+                    None => {
+                        ui.colored_label(
+                            SPLIT_COLOR,
+                            "⚠ There are no opaque background pixels above the split, so a \
+                            sprite zero hit can't be used to time it.",
+                        );
+                    }
+                }
+            });
+
+        self.show_screen_preview = open;
+    }
+
+    /// Paint the part of a level that is visible in `clip` when scrolled to `scroll`, in NES
+    /// pixels, with the top-left of the screen at `screen_min`. A `pallet` replaces the pallet of
+    /// the level's metatileset.
+    #[allow(clippy::too_many_arguments)]
+    fn paint_screen_region(
+        &self,
+        project: &mut ProjectState,
+        level_id: Uid<Level>,
+        scroll: (i32, i32),
+        clip: egui::Rect,
+        screen_min: egui::Pos2,
+        pallet: Option<&Pallet>,
+        ui: &mut egui::Ui,
+        frame: &mut eframe::Frame,
+    ) {
+        if !clip.is_positive() {
+            return;
+        }
+        let level = &project.data.levels[&level_id];
+        let margin = level.margin;
+        let metatileset_id = level.metatileset_id;
+        let background = match project.data.metatilesets.get(&metatileset_id) {
+            Some(metatileset) => pallet.unwrap_or(&metatileset.pallet).colors[0],
+            None => return,
+        };
+
+        let mut region_ui = ui.child_ui(clip, *ui.layout());
+        region_ui.set_clip_rect(clip.intersect(ui.clip_rect()));
+        region_ui
+            .painter()
+            .rect_filled(clip, 0.0, self.effects.apply(background));
+
+        let tile_size = 16.0 * SCREEN_PREVIEW_ZOOM;
+        let pixel_min = (clip.min - screen_min) / SCREEN_PREVIEW_ZOOM;
+        let pixel_max = (clip.max - screen_min) / SCREEN_PREVIEW_ZOOM;
+        let tiles = level
+            .tiles
+            .in_rect(
+                (
+                    ((scroll.0 + pixel_min.x.floor() as i32).div_euclid(16) - margin.left)
+                        .max(-margin.left),
+                    ((scroll.1 + pixel_min.y.floor() as i32).div_euclid(16) - margin.top)
+                        .max(-margin.top),
+                ),
+                (
+                    ((scroll.0 + pixel_max.x.ceil() as i32 + 15).div_euclid(16) - margin.left)
+                        .min(margin.right),
+                    ((scroll.1 + pixel_max.y.ceil() as i32 + 15).div_euclid(16) - margin.top)
+                        .min(margin.bottom),
+                ),
+            )
+            .map(|((x, y), tile)| {
+                (
+                    (x + margin.left) * 16,
+                    (y + margin.top) * 16,
+                    tile.metatileset_tile_id,
+                )
+            })
+            .collect::<Vec<_>>();
+        for (x, y, metatileset_tile_id) in tiles {
+            let tile_rect = egui::Rect::from_min_size(
+                screen_min
+                    + egui::vec2((x - scroll.0) as f32, (y - scroll.1) as f32)
+                        * SCREEN_PREVIEW_ZOOM,
+                egui::Vec2::splat(tile_size),
+            );
+            if !tile_rect.intersects(clip) {
+                continue;
+            }
+            MetatileGui::new(
+                project,
+                MetatileKind::Metatileset {
+                    metatileset_id,
+                    metatileset_tile_id,
+                },
+            )
+            .animated(self.animation_time(ui))
+            .effects(self.effects)
+            .composite(Some((x, y)).filter(|_| self.composite))
+            .pallet(pallet.cloned())
+            .paint_at(tile_rect, &mut region_ui, frame);
+        }
     }
+
+    /// Render the "Resize Map" window, if it is open
+    fn resize_map_gui(&mut self, project: &mut ProjectState, ctx: &egui::Context) {
+        let resize = if let Some(resize) = &mut self.resize_map {
+            resize
+        } else {
+            return;
+        };
+        let level = if let Some(level) = project.data.levels.get_mut(&resize.level_id) {
+            level
+        } else {
+            self.resize_map = None;
+            return;
+        };
+
+        let mut open = true;
+        let mut close = false;
+        egui::Window::new(format!("Resize Map: {}", level.name))
+            .id(egui::Id::new("resize_map"))
+            .open(&mut open)
+            .resizable(false)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "Current size: {}x{} metatiles",
+                    level.margin.width(),
+                    level.margin.height()
+                ));
+                map_size_gui(ui, &mut resize.size);
+                ui.separator();
+
+                ui.label("Anchor:").on_hover_text(
+                    "The part of the map that stays in place while the map grows or shrinks",
+                );
+                egui::Grid::new("resize_map_anchor").show(ui, |ui| {
+                    for (y, icons) in
+                        (-1..=1).zip([["↖", "⬆", "↗"], ["⬅", "⏺", "➡"], ["↙", "⬇", "↘"]])
+                    {
+                        for (x, icon) in (-1..=1).zip(icons) {
+                            ui.selectable_value(&mut resize.anchor, (x, y), icon);
+                        }
+                        ui.end_row();
+                    }
+                });
+                ui.separator();
+
+                let mut resized = level.clone();
+                let (width, height) = resize.size.metatiles();
+                resized.resize(width, height, resize.anchor);
+                let removed_tiles = level.tiles.len() - resized.tiles.len();
+                let removed_zones = level.zones.len() - resized.zones.len();
+                if removed_tiles > 0 || removed_zones > 0 {
+                    ui.colored_label(
+                        egui::Color32::YELLOW,
+                        format!(
+                            "⚠ {} tiles and {} zones are outside of the new size and will be \
+                            removed",
+                            removed_tiles, removed_zones
+                        ),
+                    );
+                }
+                let removed_screens = level.screens.len() - resized.screens.len();
+                if removed_screens > 0 {
+                    ui.colored_label(
+                        egui::Color32::YELLOW,
+                        format!(
+                            "⚠ The flags of {} screens will be removed. Grow or shrink the left \
+                            and top edges by whole screens to keep them.",
+                            removed_screens
+                        ),
+                    );
+                }
+
+                ui.horizontal(|ui| {
+                    if ui.button("✔ Resize").clicked() {
+                        *level = resized;
+                        close = true;
+                    }
+                    if ui.button("🗙 Cancel").clicked() {
+                        close = true;
+                    }
+                });
+            });
+
+        if !open || close {
+            self.resize_map = None;
+        }
+    }
+
+    /// Render the "Generate Map" window, if it is open
+    fn generate_map_gui(
+        &mut self,
+        project: &mut ProjectState,
+        ctx: &egui::Context,
+        frame: &mut eframe::Frame,
+    ) {
+        let level_id = if let Some(id) = self.generate_map.level_id {
+            id
+        } else {
+            return;
+        };
+        let metatileset_id = if let Some(level) = project.data.levels.get(&level_id) {
+            level.metatileset_id
+        } else {
+            self.generate_map.level_id = None;
+            return;
+        };
+        let selection = self
+            .selection
+            .filter(|x| x.level_id == level_id)
+            .map(|x| x.bounds());
+
+        // Forget the building blocks that aren't in the level's metatileset
+        let tiles = project
+            .data
+            .metatilesets
+            .get(&metatileset_id)
+            .map(|x| &x.tiles);
+        for blocks in &mut self.generate_map.blocks {
+            blocks.retain(|x| tiles.map(|tiles| tiles.contains_key(&x.tile)) == Some(true));
+        }
+        let current_tile = self
+            .current_metatileset_tile
+            .filter(|id| tiles.map(|tiles| tiles.contains_key(id)) == Some(true));
+
+        let settings = &mut self.generate_map;
+        let mut open = true;
+        let mut generate = false;
+        egui::Window::new(format!(
+            "Generate Map: {}",
+            project.data.levels[&level_id].name
+        ))
+        .id(egui::Id::new("generate_map"))
+        .open(&mut open)
+        .resizable(false)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            egui::ComboBox::from_label("Generator")
+                .selected_text(settings.generators[settings.current].name())
+                .show_ui(ui, |ui| {
+                    for (idx, generator) in settings.generators.iter().enumerate() {
+                        ui.selectable_value(&mut settings.current, idx, generator.name())
+                            .on_hover_text(generator.description());
+                    }
+                });
+            let generator = &mut settings.generators[settings.current];
+            ui.label(generator.description());
+            egui::Grid::new("generator_params")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    for param in generator.params() {
+                        ui.label(format!("{}:", param.name))
+                            .on_hover_text(param.help);
+                        ui.add(egui::Slider::new(param.value, param.range).suffix(param.suffix))
+                            .on_hover_text(param.help);
+                        ui.end_row();
+                    }
+
+                    ui.label("Seed:")
+                        .on_hover_text("The same seed and settings always generate the same map");
+                    ui.horizontal(|ui| {
+                        ui.add(DragValue::new(&mut settings.seed));
+                        if ui
+                            .button("🎲")
+                            .on_hover_text("Pick a new random seed")
+                            .clicked()
+                        {
+                            settings.seed = rand::random::<u32>() as u64;
+                        }
+                    });
+                    ui.end_row();
+                });
+            ui.separator();
+
+            ui.label("Building Blocks").on_hover_text(
+                "The metatiles each kind of terrain is filled with, picked at random by their \
+                weights. Terrain without any metatiles is left empty",
+            );
+            for (terrain, blocks) in Terrain::ALL.iter().zip(&mut settings.blocks) {
+                ui.horizontal_wrapped(|ui| {
+                    ui.label(format!("{}:", terrain.name()));
+                    let mut removed = None;
+                    for (idx, block) in blocks.iter_mut().enumerate() {
+                        ui.group(|ui| {
+                            let (rect, _) = ui.allocate_exact_size(
+                                egui::Vec2::splat(ui.spacing().interact_size.y),
+                                egui::Sense::hover(),
+                            );
+                            MetatileGui::new(
+                                project,
+                                MetatileKind::Metatileset {
+                                    metatileset_id,
+                                    metatileset_tile_id: block.tile,
+                                },
+                            )
+                            .effects(self.effects)
+                            .paint_at(rect, ui, frame);
+                            ui.add(DragValue::new(&mut block.weight).clamp_range(0..=100))
+                                .on_hover_text("How often this metatile is picked");
+                            if ui.small_button("🗑").clicked() {
+                                removed = Some(idx);
+                            }
+                        });
+                    }
+                    if let Some(idx) = removed {
+                        blocks.remove(idx);
+                    }
+
+                    let can_add = current_tile.filter(|id| !blocks.iter().any(|x| x.tile == *id));
+                    if ui
+                        .add_enabled(can_add.is_some(), egui::Button::new("➕"))
+                        .on_hover_text("Add the tile selected in the tile list")
+                        .on_disabled_hover_text(
+                            "Select a tile in the tile list that isn't already used here",
+                        )
+                        .clicked()
+                    {
+                        if let Some(tile) = can_add {
+                            blocks.push(BuildingBlock { tile, weight: 1 });
+                        }
+                    }
+                });
+            }
+            ui.separator();
+
+            ui.add_enabled(
+                selection.is_some(),
+                egui::Checkbox::new(&mut settings.selection_only, "Only Fill the Selection"),
+            )
+            .on_disabled_hover_text("Select part of the map to fill only that area");
+            ui.horizontal(|ui| {
+                generate = ui
+                    .button("✔ Generate")
+                    .on_hover_text("Replace the tiles in the area, which can be undone")
+                    .clicked();
+                if ui.button("🗙 Close").clicked() {
+                    settings.level_id = None;
+                }
+            });
+        });
+
+        if generate {
+            let level = project.data.levels.get_mut(&level_id).unwrap();
+            let margin = level.margin;
+            let (min, max) = selection.filter(|_| settings.selection_only).unwrap_or((
+                (-margin.left, -margin.top),
+                (margin.right - 1, margin.bottom - 1),
+            ));
+            map_generator::generate_map(
+                level,
+                min,
+                max,
+                settings.generators[settings.current].as_ref(),
+                &settings.blocks,
+                settings.seed,
+            );
+        }
+        if !open {
+            settings.level_id = None;
+        }
+    }
+
+    /// The time to show the tile animations at, if they are playing
+    fn animation_time(&self, ui: &egui::Ui) -> Option<f64> {
+        self.animate.then(|| ui.input().time)
+    }
+
+    /// Get the screen rect of a level on the map canvas
+    fn level_rect(&self, canvas_center: egui::Pos2, level: &Level) -> egui::Rect {
+        let tile_size = 16.0 * self.zoom;
+        let canvas_pos = canvas_center + level.world_offset * self.zoom + self.pan;
+        egui::Rect {
+            min: canvas_pos
+                - egui::Vec2::new(level.margin.left as f32, level.margin.top as f32) * tile_size,
+            max: canvas_pos
+                + egui::Vec2::new(level.margin.right as f32, level.margin.bottom as f32)
+                    * tile_size,
+        }
+    }
+
+    /// Paint the rulers along the edges of the canvas, counting from the top-left corner of the
+    /// current level's map, and the coordinates of the pixel under the cursor
+    fn rulers_gui(
+        &self,
+        project: &ProjectState,
+        ui: &egui::Ui,
+        canvas_rect: egui::Rect,
+        hovered_level: Option<(Uid<Level>, egui::Rect)>,
+        pointer_pos: Option<egui::Pos2>,
+    ) {
+        let level_rect = match self
+            .current_level
+            .and_then(|id| project.data.levels.get(&id))
+        {
+            Some(level) => self.level_rect(canvas_rect.center(), level),
+            None => return,
+        };
+        let painter = ui.painter().with_clip_rect(canvas_rect);
+        paint_rulers(
+            &painter,
+            canvas_rect,
+            level_rect.min,
+            self.zoom,
+            self.ruler_unit,
+            pointer_pos,
+        );
+
+        let pointer_pos = match (hovered_level, pointer_pos) {
+            (Some((id, _)), Some(pos)) if self.current_level == Some(id) => pos,
+            _ => return,
+        };
+        let pixel = ((pointer_pos - level_rect.min) / self.zoom).floor();
+        let galley = painter.layout_no_wrap(
+            coordinate_readout((pixel.x as i32, pixel.y as i32)),
+            egui::FontId::monospace(12.0),
+            ui.visuals().text_color(),
+        );
+        let readout_rect = egui::Rect::from_min_size(
+            canvas_rect.left_bottom() + egui::vec2(RULER_WIDTH, -galley.size().y - 8.0),
+            galley.size() + egui::Vec2::splat(8.0),
+        );
+        painter.rect_filled(readout_rect, 0.0, ui.visuals().extreme_bg_color);
+        painter.rect_stroke(readout_rect, 0.0, ui.visuals().window_stroke());
+        painter.galley(readout_rect.min + egui::Vec2::splat(4.0), galley);
+    }
+
+    /// Draw an arrow for each level exit, from where the player leaves the level to where they
+    /// arrive in the next one
+    fn exits_overlay_gui(
+        &self,
+        project: &ProjectState,
+        ui: &mut egui::Ui,
+        canvas_center: egui::Pos2,
+    ) {
+        let zone_center = |level: &Level, level_rect: egui::Rect, zone_id: &Uid<Zone>| {
+            let zone = level.zones.get(zone_id)?;
+            Some(zone_rect(level_rect, level.margin, self.zoom, zone).center())
+        };
+
+        for level in project.data.levels.values() {
+            let level_rect = self.level_rect(canvas_center, level);
+
+            for exit in level.exits.values() {
+                let from = match exit.source {
+                    ExitSource::Edge(MapEdge::Top) => Some(level_rect.center_top()),
+                    ExitSource::Edge(MapEdge::Right) => Some(level_rect.right_center()),
+                    ExitSource::Edge(MapEdge::Bottom) => Some(level_rect.center_bottom()),
+                    ExitSource::Edge(MapEdge::Left) => Some(level_rect.left_center()),
+                    ExitSource::Zone(zone_id) => zone_center(level, level_rect, &zone_id),
+                };
+                let target = project.data.levels.get(&exit.target_level_id);
+                let to = target.and_then(|target| {
+                    let target_rect = self.level_rect(canvas_center, target);
+                    match &exit.target_zone_id {
+                        Some(zone_id) => zone_center(target, target_rect, zone_id),
+                        None => Some(target_rect.center()),
+                    }
+                });
+
+                if let (Some(from), Some(to)) = (from, to) {
+                    paint_link(ui.painter(), from, to);
+                }
+            }
+        }
+    }
+
+    /// Render the zone tool and the list of zones in a level
+    fn zones_gui(&mut self, level: &mut Level, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new(format!("Zones ({})", level.zones.len()))
+            .default_open(false)
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    if ui
+                        .toggle_value(&mut self.zone_tool, "✏ Draw")
+                        .on_hover_text("Drag on the map to draw a zone instead of placing tiles")
+                        .changed()
+                        && self.zone_tool
+                    {
+                        self.select_tool = false;
+                    }
+                    egui::ComboBox::from_id_source("new_zone_kind")
+                        .selected_text(self.new_zone_kind.name())
+                        .show_ui(ui, |ui| {
+                            for kind in ZoneKind::ALL {
+                                ui.selectable_value(&mut self.new_zone_kind, kind, kind.name());
+                            }
+                        });
+                });
+
+                let mut removed = None;
+                for (zone_id, zone) in &mut level.zones {
+                    ui.separator();
+                    ui.push_id(zone_id, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.colored_label(zone_color(zone.kind), "⬛");
+                            ui.with_layout(egui::Layout::right_to_left(), |ui| {
+                                if ui.button("🗑").on_hover_text("Delete zone.").clicked() {
+                                    removed = Some(*zone_id);
+                                }
+                                ui.text_edit_singleline(&mut zone.name);
+                            });
+                        });
+                        egui::ComboBox::from_id_source("zone_kind")
+                            .selected_text(zone.kind.name())
+                            .show_ui(ui, |ui| {
+                                for kind in ZoneKind::ALL {
+                                    ui.selectable_value(&mut zone.kind, kind, kind.name());
+                                }
+                            });
+                        ui.horizontal(|ui| {
+                            ui.label("X");
+                            ui.add(DragValue::new(&mut zone.x).speed(0.25));
+                            ui.label("Y");
+                            ui.add(DragValue::new(&mut zone.y).speed(0.25));
+                            ui.label("W");
+                            ui.add(
+                                DragValue::new(&mut zone.width)
+                                    .speed(0.25)
+                                    .clamp_range(1..=256),
+                            );
+                            ui.label("H");
+                            ui.add(
+                                DragValue::new(&mut zone.height)
+                                    .speed(0.25)
+                                    .clamp_range(1..=256),
+                            );
+                        });
+                    });
+                }
+
+                if let Some(id) = removed {
+                    level.zones.shift_remove(&id);
+                }
+            });
+    }
+
+    /// Render the buttons for shifting everything on the map
+    fn shift_gui(&mut self, level: &mut Level, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Shift Map")
+            .default_open(false)
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("By");
+                    ui.add(
+                        DragValue::new(&mut self.shift_amount)
+                            .clamp_range(1..=256)
+                            .suffix(" metatiles"),
+                    );
+                });
+                ui.checkbox(&mut self.shift_wrap, "Wrap around")
+                    .on_hover_text(
+                    "Bring tiles that move off of one edge of the map back on the opposite edge",
+                );
+                ui.add_enabled_ui(!self.shift_wrap, |ui| {
+                    ui.checkbox(&mut self.shift_fill, "Fill with selected tile")
+                        .on_hover_text(
+                            "Fill the cells that are left behind with the tile selected below, \
+                            instead of leaving them empty",
+                        );
+                });
+
+                let amount = self.shift_amount;
+                let mut shift = None;
+                ui.horizontal(|ui| {
+                    for (icon, direction, dx, dy) in [
+                        ("⬅", "left", -amount, 0),
+                        ("⬆", "up", 0, -amount),
+                        ("⬇", "down", 0, amount),
+                        ("➡", "right", amount, 0),
+                    ] {
+                        if ui
+                            .button(icon)
+                            .on_hover_text(format!("Shift the map {}", direction))
+                            .clicked()
+                        {
+                            shift = Some((dx, dy));
+                        }
+                    }
+                });
+
+                if let Some((dx, dy)) = shift {
+                    let fill = self
+                        .current_metatileset_tile
+                        .filter(|_| self.shift_fill)
+                        .map(|metatileset_tile_id| LevelTile {
+                            metatileset_tile_id,
+                        });
+                    level.shift(dx, dy, self.shift_wrap, fill);
+                }
+            });
+    }
+
+    /// Render the selection tool and the operations on the selected area
+    fn selection_gui(&mut self, project: &mut ProjectState, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Selection")
+            .default_open(false)
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    if ui
+                        .toggle_value(&mut self.select_tool, "⬚ Select")
+                        .on_hover_text("Drag on the map to select an area instead of placing tiles")
+                        .changed()
+                        && self.select_tool
+                    {
+                        self.zone_tool = false;
+                    }
+                    if let Some(selection) = self.selection {
+                        let (min, max) = selection.bounds();
+                        ui.label(format!(
+                            "{}x{} metatiles",
+                            max.0 - min.0 + 1,
+                            max.1 - min.1 + 1
+                        ));
+                    }
+                });
+
+                ui.add_enabled_ui(self.selection.is_some(), |ui| {
+                    for transform in MapTransform::ALL {
+                        let icon = match transform {
+                            MapTransform::MirrorHorizontal => "⇔",
+                            MapTransform::MirrorVertical => "⇕",
+                            MapTransform::Rotate180 => "⟲",
+                        };
+                        if ui
+                            .button(format!("{} {}", icon, transform.name()))
+                            .on_hover_text(
+                                "Uses the metatiles that look like mirrored or rotated versions \
+                                of the selected tiles, if the metatileset has them",
+                            )
+                            .clicked()
+                        {
+                            self.transform_selection(project, transform, ui.ctx());
+                        }
+                    }
+                    if ui
+                        .button("🗙 Clear Selection")
+                        .on_hover_text("Shortcut: Esc")
+                        .clicked()
+                    {
+                        self.selection = None;
+                    }
+                });
+            });
+    }
+
+    /// Mirror or rotate the tiles in the selected area, swapping each tile for the one in the
+    /// metatileset that looks like its mirrored or rotated version
+    fn transform_selection(
+        &mut self,
+        project: &mut ProjectState,
+        transform: MapTransform,
+        ctx: &egui::Context,
+    ) {
+        let selection = if let Some(selection) = self.selection {
+            selection
+        } else {
+            return;
+        };
+        let level_id = selection.level_id;
+        let (min, max) = selection.bounds();
+        let in_selection =
+            |(x, y): (i32, i32)| (min.0..=max.0).contains(&x) && (min.1..=max.1).contains(&y);
+
+        let level = if let Some(level) = project.data.levels.get_mut(&level_id) {
+            level
+        } else {
+            return;
+        };
+        let metatileset_id = level.metatileset_id;
+        let selected = level
+            .tiles
+            .iter()
+            .filter(|(pos, _)| in_selection(**pos))
+            .map(|(pos, tile)| (*pos, tile.metatileset_tile_id))
+            .collect::<Vec<_>>();
+        level.tiles.retain(|pos, _| !in_selection(*pos));
+
+        let tile_ids = selected
+            .iter()
+            .map(|(_, id)| *id)
+            .collect::<IndexSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        let variants = project.transformed_variants(metatileset_id, &tile_ids, transform);
+
+        let level = project.data.levels.get_mut(&level_id).unwrap();
+        let mut missing = 0;
+        for (pos, tile_id) in selected {
+            let variant = variants.get(&tile_id).copied().flatten();
+            if variant.is_none() {
+                missing += 1;
+            }
+            level.tiles.insert(
+                transform.apply(pos, min, max),
+                LevelTile {
+                    metatileset_tile_id: variant.unwrap_or(tile_id),
+                },
+            );
+        }
+
+        if missing > 0 {
+            send_warning_notification(
+                ctx,
+                format!(
+                    "{}: {} tiles have no matching variant in the metatileset, so they were moved \
+                    without being changed",
+                    transform.name(),
+                    missing
+                ),
+            );
+        }
+    }
+
+    /// Render the list of map bookmarks
+    fn bookmarks_gui(&mut self, project: &mut ProjectState, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Bookmarks")
+            .default_open(false)
+            .show(ui, |ui| {
+                if project.data.map_bookmarks.is_empty() {
+                    ui.label("Press Ctrl+1 through Ctrl+9 to bookmark the center of the view.");
+                    return;
+                }
+
+                let mut jump_to = None;
+                let mut removed = None;
+                for (slot, bookmark) in &mut project.data.map_bookmarks {
+                    ui.horizontal(|ui| {
+                        if ui
+                            .button(format!("{}", slot))
+                            .on_hover_text(format!("Jump to bookmark\nShortcut: {}", slot))
+                            .clicked()
+                        {
+                            jump_to = Some(*slot);
+                        }
+                        ui.with_layout(egui::Layout::right_to_left(), |ui| {
+                            if ui.button("🗑").on_hover_text("Delete bookmark.").clicked() {
+                                removed = Some(*slot);
+                            }
+                            ui.text_edit_singleline(&mut bookmark.name);
+                        });
+                    });
+                }
+
+                if let Some(slot) = jump_to {
+                    self.jump_to_bookmark(project, slot);
+                }
+                if let Some(slot) = removed {
+                    project.data.map_bookmarks.remove(&slot);
+                }
+            });
+    }
+
+    /// Bookmark the center of the map view
+    fn set_bookmark(&self, project: &mut ProjectState, slot: u8) {
+        let bookmark = project
+            .data
+            .map_bookmarks
+            .entry(slot)
+            .or_insert_with(|| MapBookmark {
+                name: format!("Bookmark {}", slot),
+                ..Default::default()
+            });
+        bookmark.level_id = self.current_level;
+        bookmark.position = -self.pan / self.zoom;
+    }
+
+    /// Center the map view on a bookmark and select the level it was placed in
+    fn jump_to_bookmark(&mut self, project: &ProjectState, slot: u8) {
+        if let Some(bookmark) = project.data.map_bookmarks.get(&slot) {
+            self.pan = -bookmark.position * self.zoom;
+
+            if let Some(id) = bookmark
+                .level_id
+                .filter(|id| project.data.levels.contains_key(id))
+            {
+                self.current_level = Some(id);
+            }
+        }
+    }
+
+    /// Render the magnifier lens, showing the pixels of a level around the cursor
+    fn magnifier_gui(
+        &mut self,
+        project: &mut ProjectState,
+        ui: &mut egui::Ui,
+        frame: &mut eframe::Frame,
+        level_id: Uid<Level>,
+        level_rect: egui::Rect,
+        pointer_pos: egui::Pos2,
+    ) {
+        let level = project.data.levels.get(&level_id).unwrap();
+        let margin = level.margin;
+        let metatileset_id = level.metatileset_id;
+        let metatileset = if let Some(metatileset) = project.data.metatilesets.get(&metatileset_id)
+        {
+            metatileset
+        } else {
+            return;
+        };
+        let background_color = metatileset.pallet.colors[0];
+
+        // The NES pixel under the cursor, counted from the top-left corner of the level rect
+        let pixel = ((pointer_pos - level_rect.min) / self.zoom).floor();
+        let pixel = (pixel.x as i32, pixel.y as i32);
+
+        // Place the lens next to the cursor, flipping it to the other side near the canvas edges
+        let canvas_rect = ui.clip_rect();
+        let lens_size = egui::Vec2::splat(MAGNIFIER_PIXELS as f32 * MAGNIFIER_ZOOM);
+        let offset = 24.0;
+        let mut lens_min = pointer_pos + egui::Vec2::splat(offset);
+        if lens_min.x + lens_size.x > canvas_rect.max.x {
+            lens_min.x = pointer_pos.x - offset - lens_size.x;
+        }
+        if lens_min.y + lens_size.y > canvas_rect.max.y {
+            lens_min.y = pointer_pos.y - offset - lens_size.y;
+        }
+        let lens_rect = egui::Rect::from_min_size(lens_min, lens_size);
+
+        // The NES pixel shown in the top-left corner of the lens
+        let origin = (
+            pixel.0 - MAGNIFIER_PIXELS / 2,
+            pixel.1 - MAGNIFIER_PIXELS / 2,
+        );
+        let to_screen = |x: i32, y: i32| {
+            lens_rect.min
+                + egui::Vec2::new((x - origin.0) as f32, (y - origin.1) as f32) * MAGNIFIER_ZOOM
+        };
+
+        let mut lens_ui = ui.child_ui(lens_rect, *ui.layout());
+        lens_ui.set_clip_rect(lens_rect.intersect(canvas_rect));
+        lens_ui
+            .painter()
+            .rect_filled(lens_rect, 0.0, ui.visuals().extreme_bg_color);
+        lens_ui.painter().rect_filled(
+            egui::Rect::from_min_max(
+                to_screen(0, 0),
+                to_screen(margin.width() * 16, margin.height() * 16),
+            ),
+            0.0,
+            self.effects.apply(background_color),
+        );
+
+        // The metatile under the cursor, used to find the color under the cursor
+        let level_tile = (
+            pixel.0.div_euclid(16) - margin.left,
+            pixel.1.div_euclid(16) - margin.top,
+        );
+        let hovered_tile = level
+            .tiles
+            .get(&level_tile)
+            .and_then(|tile| metatileset.tiles.get(&tile.metatileset_tile_id))
+            .cloned();
+
+        // Paint the metatiles that overlap the lens
+        let tiles = level
+            .tiles
+            .in_rect(
+                (
+                    (origin.0.div_euclid(16) - margin.left).max(-margin.left),
+                    (origin.1.div_euclid(16) - margin.top).max(-margin.top),
+                ),
+                (
+                    ((origin.0 + MAGNIFIER_PIXELS + 15).div_euclid(16) - margin.left)
+                        .min(margin.right),
+                    ((origin.1 + MAGNIFIER_PIXELS + 15).div_euclid(16) - margin.top)
+                        .min(margin.bottom),
+                ),
+            )
+            .map(|((x, y), tile)| {
+                (
+                    (x + margin.left) * 16,
+                    (y + margin.top) * 16,
+                    tile.metatileset_tile_id,
+                )
+            })
+            .filter(|(x, y, _)| {
+                *x + 16 > origin.0
+                    && *x < origin.0 + MAGNIFIER_PIXELS
+                    && *y + 16 > origin.1
+                    && *y < origin.1 + MAGNIFIER_PIXELS
+            })
+            .collect::<Vec<_>>();
+        for (x, y, metatileset_tile_id) in tiles {
+            let tile_rect = egui::Rect::from_min_size(
+                to_screen(x, y),
+                egui::Vec2::splat(16.0 * MAGNIFIER_ZOOM),
+            );
+            MetatileGui::new(
+                project,
+                MetatileKind::Metatileset {
+                    metatileset_id,
+                    metatileset_tile_id,
+                },
+            )
+            .animated(self.animation_time(ui))
+            .effects(self.effects)
+            .backdrop(self.backdrop)
+            .composite(Some((x, y)).filter(|_| self.composite))
+            .paint_at(tile_rect, &mut lens_ui, frame);
+        }
+
+        // Paint the pixel and tile grids
+        let painter = lens_ui.painter();
+        let pixel_stroke = egui::Stroke::new(1.0, egui::Color32::from_black_alpha(60));
+        let tile_stroke = egui::Stroke::new(1.0, egui::Color32::from_white_alpha(90));
+        for i in 0..=MAGNIFIER_PIXELS {
+            let x_stroke = if (origin.0 + i).rem_euclid(8) == 0 {
+                tile_stroke
+            } else {
+                pixel_stroke
+            };
+            let y_stroke = if (origin.1 + i).rem_euclid(8) == 0 {
+                tile_stroke
+            } else {
+                pixel_stroke
+            };
+            let pos = lens_rect.min + egui::Vec2::splat(i as f32 * MAGNIFIER_ZOOM);
+            painter.vline(pos.x, lens_rect.y_range(), x_stroke);
+            painter.hline(lens_rect.x_range(), pos.y, y_stroke);
+        }
+        painter.rect_stroke(
+            egui::Rect::from_min_size(
+                to_screen(pixel.0, pixel.1),
+                egui::Vec2::splat(MAGNIFIER_ZOOM),
+            ),
+            0.0,
+            (2.0, egui::Color32::YELLOW),
+        );
+        painter.rect_stroke(lens_rect, 0.0, ui.visuals().window_stroke());
+
+        // Find the color under the cursor
+        let color = hovered_tile.and_then(|tile| {
+            let color_idx = project.metatile_pixel(
+                tile.metatile_id,
+                pixel.0.rem_euclid(16) as u32,
+                pixel.1.rem_euclid(16) as u32,
+            )?;
+            let metatileset = project.data.metatilesets.get(&metatileset_id)?;
+            Some((
+                color_idx,
+                metatileset.sub_pallet(tile.sub_pallet_idx)[color_idx as usize],
+            ))
+        });
+
+        let level_pixel = (pixel.0 - margin.left * 16, pixel.1 - margin.top * 16);
+        let mut readout = format!(
+            "Pixel: ({}, {})\nTile:  ({}, {})",
+            level_pixel.0,
+            level_pixel.1,
+            level_pixel.0.div_euclid(8),
+            level_pixel.1.div_euclid(8),
+        );
+        match color {
+            Some((color_idx, nes_color)) => {
+                readout.push_str(&format!("\nColor: {} (${:02X})", color_idx, nes_color))
+            }
+            None => readout.push_str(&format!("\nColor: 0 (${:02X})", background_color)),
+        }
+
+        let painter = ui.painter();
+        let galley = painter.layout_no_wrap(
+            readout,
+            egui::FontId::monospace(14.0),
+            ui.visuals().text_color(),
+        );
+        let readout_rect = egui::Rect::from_min_size(
+            lens_rect.left_bottom(),
+            egui::Vec2::new(lens_rect.width(), galley.size().y + 8.0),
+        );
+        painter.rect_filled(readout_rect, 0.0, ui.visuals().extreme_bg_color);
+        painter.rect_stroke(readout_rect, 0.0, ui.visuals().window_stroke());
+        painter.galley(readout_rect.min + egui::Vec2::splat(4.0), galley);
+    }
+}
+
+/// The sidebar section for editing the level's scroll split
+fn split_gui(
+    level: &mut Level,
+    levels: &[ExitTarget],
+    show_screen_preview: &mut bool,
+    ui: &mut egui::Ui,
+) {
+    egui::CollapsingHeader::new("Scroll Split")
+        .default_open(false)
+        .show(ui, |ui| {
+            let mut enabled = level.split.is_some();
+            if ui
+                .checkbox(&mut enabled, "Split the screen")
+                .on_hover_text(
+                    "Change the scroll part way down the screen, like games that keep a status \
+                    bar still while the playfield scrolls",
+                )
+                .changed()
+            {
+                level.split = enabled.then(ScrollSplit::default);
+            }
+
+            if let Some(split) = &mut level.split {
+                egui::Grid::new("split_grid").show(ui, |ui| {
+                    ui.label("Scanline");
+                    ui.add(DragValue::new(&mut split.scanline).clamp_range(1..=239))
+                        .on_hover_text("The first scanline of the lower region");
+                    ui.end_row();
+
+                    ui.label("Lower Level");
+                    let selected = split
+                        .level_id
+                        .and_then(|id| levels.iter().find(|x| x.level_id == id))
+                        .map(|x| x.name.as_str())
+                        .unwrap_or("This Level");
+                    egui::ComboBox::from_id_source("split_level")
+                        .selected_text(selected)
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut split.level_id, None, "This Level");
+                            for target in levels {
+                                ui.selectable_value(
+                                    &mut split.level_id,
+                                    Some(target.level_id),
+                                    &target.name,
+                                );
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label("Scroll X");
+                    ui.add(DragValue::new(&mut split.scroll_x).suffix(" px"));
+                    ui.end_row();
+
+                    ui.label("Scroll Y");
+                    ui.add(DragValue::new(&mut split.scroll_y).suffix(" px"));
+                    ui.end_row();
+                });
+            }
+
+            ui.toggle_value(show_screen_preview, "📺 Screen Preview")
+                .on_hover_text("Show the level's first screen with the split applied");
+        });
+}
+
+/// The sidebar section for editing the level's pallet regions
+fn pallet_regions_gui(
+    level: &mut Level,
+    base_pallet: &Pallet,
+    show_screen_preview: &mut bool,
+    ui: &mut egui::Ui,
+) {
+    egui::CollapsingHeader::new(format!("Pallet Regions ({})", level.pallet_regions.len()))
+        .default_open(false)
+        .show(ui, |ui| {
+            ui.label(
+                "Advanced: swap the pallet part way down the screen, for games that rewrite the \
+                pallet during rendering. The regions are exported as their own table, since the \
+                attribute tables can't describe them.",
+            );
+
+            let mut remove = None;
+            for (idx, region) in level.pallet_regions.iter_mut().enumerate() {
+                ui.push_id(idx, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.colored_label(PALLET_REGION_COLOR, format!("Pallet {}", idx + 1));
+                        ui.label("from scanline");
+                        ui.add(DragValue::new(&mut region.scanline).clamp_range(1..=239))
+                            .on_hover_text("The first scanline that uses the pallet");
+                        if ui
+                            .small_button("🗑")
+                            .on_hover_text("Delete pallet region")
+                            .clicked()
+                        {
+                            remove = Some(idx);
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.spacing_mut().item_spacing.x = 1.0;
+                        nes_color_picker(ui, &mut region.pallet.colors[0]);
+                        for sub_pallet in region.pallet.colors[1..].chunks_mut(3) {
+                            ui.add_space(4.0);
+                            for color in sub_pallet {
+                                nes_color_picker(ui, color);
+                            }
+                        }
+                    });
+                });
+            }
+            if let Some(idx) = remove {
+                level.pallet_regions.remove(idx);
+            }
+
+            ui.horizontal(|ui| {
+                if ui
+                    .button("➕ Add Region")
+                    .on_hover_text("Add a region starting with the metatileset's pallet")
+                    .clicked()
+                {
+                    let scanline = level
+                        .pallet_regions
+                        .iter()
+                        .map(|x| x.scanline.saturating_add(32).min(239))
+                        .max()
+                        .unwrap_or(PalletRegion::default().scanline);
+                    level.pallet_regions.push(PalletRegion {
+                        scanline,
+                        pallet: base_pallet.clone(),
+                    });
+                }
+                ui.toggle_value(show_screen_preview, "📺 Screen Preview")
+                    .on_hover_text("Show the level's first screen with the pallet regions applied");
+            });
+        });
+}
+
+/// Find the opaque background pixel closest to the split in the top-left screen of a level, which
+/// sprite zero can overlap to time the split
+fn sprite_zero_hit_pos(
+    project: &mut ProjectState,
+    level_id: Uid<Level>,
+    scanline: u8,
+) -> Option<(i32, i32)> {
+    let level = project.data.levels.get(&level_id)?;
+    let metatileset = project.data.metatilesets.get(&level.metatileset_id)?;
+    let margin = level.margin;
+    let screen_width = (SCREEN_WIDTH_METATILES * 16) as i32;
+
+    // The metatile at each metatile position of the upper region
+    let metatiles = (0..scanline as i32)
+        .step_by(16)
+        .flat_map(|y| (0..screen_width).step_by(16).map(move |x| (x, y)))
+        .filter_map(|(x, y)| {
+            let tile = level
+                .tiles
+                .get(&(x / 16 - margin.left, y / 16 - margin.top))?;
+            let metatile_id = metatileset
+                .tiles
+                .get(&tile.metatileset_tile_id)?
+                .metatile_id;
+            Some(((x / 16, y / 16), metatile_id))
+        })
+        .collect::<HashMap<_, _>>();
+
+    // Sprite zero hits can't happen on the last pixel of a scanline
+    for y in (0..scanline as i32).rev() {
+        for x in 0..screen_width - 1 {
+            let metatile_id = match metatiles.get(&(x / 16, y / 16)) {
+                Some(id) => *id,
+                None => continue,
+            };
+            let pixel = project.metatile_pixel(metatile_id, (x % 16) as u32, (y % 16) as u32);
+            if pixel.unwrap_or(0) != 0 {
+                return Some((x, y));
+            }
+        }
+    }
+    None
+}
+
+/// Toolbar menu for showing and configuring the overscan guides
+fn overscan_gui(show_overscan: &mut bool, overscan: &mut Overscan, ui: &mut egui::Ui) {
+    ui.menu_button("▭ Overscan ⏷", |ui| {
+        ui.checkbox(show_overscan, "Show Guides")
+            .on_hover_text("Shade the edges of each screen that many TVs crop");
+        ui.separator();
+        overscan_grid(ui, overscan);
+    });
+}
+
+/// Shade the overscan area of each screen in a level, and outline the screens
+fn paint_overscan(
+    ui: &egui::Ui,
+    level_rect: egui::Rect,
+    level: &Level,
+    overscan: Overscan,
+    zoom: f32,
+) {
+    let painter = ui
+        .painter()
+        .with_clip_rect(level_rect.intersect(ui.clip_rect()));
+    let screen_size = egui::Vec2::new(
+        (SCREEN_WIDTH_METATILES * 16) as f32,
+        (SCREEN_HEIGHT_METATILES * 16) as f32,
+    ) * zoom;
+    let fill = OVERSCAN_COLOR.linear_multiply(0.25);
+    let (screens_x, screens_y) = level.screen_size();
+    for y in 0..screens_y {
+        for x in 0..screens_x {
+            let screen = egui::Rect::from_min_size(
+                level_rect.min + egui::Vec2::new(x as f32, y as f32) * screen_size,
+                screen_size,
+            );
+            let safe = egui::Rect::from_min_max(
+                screen.min + egui::Vec2::new(overscan.left as f32, overscan.top as f32) * zoom,
+                screen.max - egui::Vec2::new(overscan.right as f32, overscan.bottom as f32) * zoom,
+            );
+            for unsafe_rect in [
+                egui::Rect::from_x_y_ranges(screen.x_range(), screen.top()..=safe.top()),
+                egui::Rect::from_x_y_ranges(screen.x_range(), safe.bottom()..=screen.bottom()),
+                egui::Rect::from_x_y_ranges(screen.left()..=safe.left(), safe.y_range()),
+                egui::Rect::from_x_y_ranges(safe.right()..=screen.right(), safe.y_range()),
+            ] {
+                if unsafe_rect.is_positive() {
+                    painter.rect_filled(unsafe_rect, 0.0, fill);
+                }
+            }
+            painter.rect_stroke(safe, 0.0, egui::Stroke::new(1.0, OVERSCAN_COLOR));
+            painter.rect_stroke(screen, 0.0, ui.visuals().window_stroke());
+        }
+    }
+}
+
+/// Edit a map size, converting it when the units are changed
+fn map_size_gui(ui: &mut egui::Ui, size: &mut MapSize) {
+    let unit = size.unit;
+    ui.horizontal(|ui| {
+        ui.label("Width");
+        ui.add(DragValue::new(&mut size.width).clamp_range(1..=256));
+        ui.label("Height");
+        ui.add(DragValue::new(&mut size.height).clamp_range(1..=256));
+    });
+    ui.horizontal(|ui| {
+        ui.radio_value(&mut size.unit, SizeUnit::Metatiles, "Metatiles");
+        ui.radio_value(&mut size.unit, SizeUnit::Screens, "Screens");
+    });
+
+    if size.unit != unit {
+        let (width, height) = MapSize { unit, ..*size }.metatiles();
+        *size = match size.unit {
+            SizeUnit::Metatiles => MapSize {
+                width,
+                height,
+                unit: SizeUnit::Metatiles,
+            },
+            SizeUnit::Screens => {
+                let screens =
+                    |len: i32, screen_len: usize| (0..len.max(1)).step_by(screen_len).count();
+                MapSize {
+                    width: screens(width, SCREEN_WIDTH_METATILES) as i32,
+                    height: screens(height, SCREEN_HEIGHT_METATILES) as i32,
+                    unit: SizeUnit::Screens,
+                }
+            }
+        };
+    }
+}
+
+/// Toolbar toggles for the PPU color emphasis bits and greyscale mode
+fn ppu_effects_gui(effects: &mut PpuEffects, ui: &mut egui::Ui) {
+    ui.toggle_value(&mut effects.greyscale, "Greyscale")
+        .on_hover_text(
+            "Preview the map with the PPU's greyscale mode, which only keeps the grey column of \
+            the NES pallet",
+        );
+    let channels = [
+        ("R", "red", egui::Color32::from_rgb(255, 90, 90)),
+        ("G", "green", egui::Color32::from_rgb(80, 220, 80)),
+        ("B", "blue", egui::Color32::from_rgb(90, 120, 255)),
+    ];
+    // The toolbar is laid out right to left, so add the channels backwards
+    for (i, (label, name, color)) in channels.into_iter().enumerate().rev() {
+        ui.toggle_value(
+            &mut effects.emphasis[i],
+            egui::RichText::new(label).color(color),
+        )
+        .on_hover_text(format!(
+            "Preview the map with the {} color emphasis bit, which darkens the other colors",
+            name
+        ));
+    }
+    ui.label("Emphasis:");
+}
+
+/// Count how many map cells use each metatile, across all of the levels
+fn metatile_use_counts(project: &ProjectState) -> HashMap<Uid<Metatile>, usize> {
+    let mut uses = HashMap::default();
+    for level in project.data.levels.values() {
+        let metatileset = match project.data.metatilesets.get(&level.metatileset_id) {
+            Some(metatileset) => metatileset,
+            None => continue,
+        };
+        for tile in level.tiles.values() {
+            if let Some(tile) = metatileset.tiles.get(&tile.metatileset_tile_id) {
+                *uses.entry(tile.metatile_id).or_default() += 1;
+            }
+        }
+    }
+    uses
+}
+
+/// Describe where the map cell at `cell`, counted from the top-left corner of the map, ends up in
+/// the PPU: the nametable bytes of its four tiles, and the bits of the attribute byte that hold its
+/// sub-pallet
+fn ppu_address_text(level: &Level, cell: (i32, i32)) -> String {
+    let pixel = (cell.0 * 16, cell.1 * 16);
+    let nametable = nametable_address(pixel);
+    let (attribute, shift) = attribute_address(pixel);
+    let screen = (
+        cell.0.div_euclid(SCREEN_WIDTH_METATILES as i32),
+        cell.1.div_euclid(SCREEN_HEIGHT_METATILES as i32),
+    );
+    // The same byte in the exported attribute table, which has 64 bytes for each screen
+    let screens_wide = level.screen_size().0;
+    let offset = (screen.1 * screens_wide + screen.0) * 64 + ((attribute & 0x3ff) - 0x3c0) as i32;
+    format!(
+        "Screen:    ({}, {})\nNametable: ${:04X} ${:04X}\n           ${:04X} ${:04X}\n\
+        Attribute: ${:04X}, bits {}-{}\n           byte {} of the attributes table",
+        screen.0,
+        screen.1,
+        nametable,
+        nametable + 1,
+        nametable + 32,
+        nametable + 33,
+        attribute,
+        shift,
+        shift + 1,
+        offset,
+    )
+}
+
+/// The color used to shade a map cell in the usage heatmap, by how many times its metatile is used
+fn heatmap_color(uses: usize) -> egui::Color32 {
+    match uses {
+        0..=1 => egui::Color32::from_rgb(230, 60, 60),
+        2..=3 => egui::Color32::from_rgb(240, 150, 50),
+        4..=9 => egui::Color32::from_rgb(230, 220, 70),
+        _ => egui::Color32::from_rgb(70, 200, 110),
+    }
+}
+
+/// The color used to draw each kind of zone
+fn zone_color(kind: ZoneKind) -> egui::Color32 {
+    match kind {
+        ZoneKind::Camera => egui::Color32::from_rgb(80, 160, 255),
+        ZoneKind::Room => egui::Color32::from_rgb(80, 220, 120),
+        ZoneKind::Trigger => egui::Color32::from_rgb(255, 140, 40),
+    }
+}
+
+/// Get the zone covering the metatiles between two corners
+fn zone_from_drag(start: (i32, i32), end: (i32, i32), kind: ZoneKind) -> Zone {
+    Zone {
+        kind,
+        x: start.0.min(end.0),
+        y: start.1.min(end.1),
+        width: (start.0 - end.0).abs() + 1,
+        height: (start.1 - end.1).abs() + 1,
+        ..Default::default()
+    }
+}
+
+/// Get the screen rect of a zone in a level
+fn zone_rect(level_rect: egui::Rect, margin: LevelMargin, zoom: f32, zone: &Zone) -> egui::Rect {
+    let tile_size = 16.0 * zoom;
+    egui::Rect::from_min_size(
+        level_rect.min
+            + egui::Vec2::new((zone.x + margin.left) as f32, (zone.y + margin.top) as f32)
+                * tile_size,
+        egui::Vec2::new(zone.width as f32, zone.height as f32) * tile_size,
+    )
+}
+
+/// Paint a zone as a tinted rectangle with its name in the corner
+fn paint_zone(ui: &egui::Ui, rect: egui::Rect, zone: &Zone) {
+    let color = zone_color(zone.kind);
+    let painter = ui.painter();
+    painter.rect_filled(rect, 0.0, color.linear_multiply(0.15));
+    painter.rect_stroke(rect, 0.0, (2.0, color));
+    painter.text(
+        rect.left_top() + egui::Vec2::splat(4.0),
+        egui::Align2::LEFT_TOP,
+        &zone.name,
+        egui::FontId::proportional(14.0),
+        color,
+    );
+}
+
+/// Render the list of exits from a level
+fn exits_gui(
+    level_id: Uid<Level>,
+    level: &mut Level,
+    exit_targets: &[ExitTarget],
+    ui: &mut egui::Ui,
+) {
+    egui::CollapsingHeader::new(format!("Exits ({})", level.exits.len()))
+        .default_open(false)
+        .show(ui, |ui| {
+            if ui.button("➕ Add Exit").clicked() {
+                let target_level_id = exit_targets
+                    .iter()
+                    .map(|x| x.level_id)
+                    .find(|id| *id != level_id)
+                    .unwrap_or(level_id);
+                level.exits.insert(
+                    Uid::new(),
+                    LevelExit {
+                        target_level_id,
+                        ..Default::default()
+                    },
+                );
+            }
+
+            let mut removed = None;
+            for (exit_id, exit) in &mut level.exits {
+                ui.separator();
+                ui.push_id(exit_id, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("From");
+                        let source_name = match exit.source {
+                            ExitSource::Edge(edge) => edge.name(),
+                            ExitSource::Zone(zone_id) => level
+                                .zones
+                                .get(&zone_id)
+                                .map(|x| x.name.as_str())
+                                .unwrap_or("Deleted Zone"),
+                        };
+                        egui::ComboBox::from_id_source("source")
+                            .selected_text(source_name)
+                            .show_ui(ui, |ui| {
+                                for edge in MapEdge::ALL {
+                                    ui.selectable_value(
+                                        &mut exit.source,
+                                        ExitSource::Edge(edge),
+                                        edge.name(),
+                                    );
+                                }
+                                for (zone_id, zone) in &level.zones {
+                                    ui.selectable_value(
+                                        &mut exit.source,
+                                        ExitSource::Zone(*zone_id),
+                                        &zone.name,
+                                    );
+                                }
+                            });
+                        ui.with_layout(egui::Layout::right_to_left(), |ui| {
+                            if ui.button("🗑").on_hover_text("Delete exit.").clicked() {
+                                removed = Some(*exit_id);
+                            }
+                        });
+                    });
+
+                    let target = exit_targets
+                        .iter()
+                        .find(|x| x.level_id == exit.target_level_id);
+                    ui.horizontal(|ui| {
+                        ui.label("To");
+                        egui::ComboBox::from_id_source("target_level")
+                            .selected_text(target.map(|x| x.name.as_str()).unwrap_or("None"))
+                            .show_ui(ui, |ui| {
+                                for choice in exit_targets {
+                                    if ui
+                                        .selectable_value(
+                                            &mut exit.target_level_id,
+                                            choice.level_id,
+                                            &choice.name,
+                                        )
+                                        .changed()
+                                    {
+                                        exit.target_zone_id = None;
+                                    }
+                                }
+                            });
+                    });
+
+                    let target_zones = target.map(|x| x.zones.as_slice()).unwrap_or(&[]);
+                    ui.horizontal(|ui| {
+                        ui.label("At");
+                        let zone_name = exit
+                            .target_zone_id
+                            .and_then(|id| target_zones.iter().find(|(zone_id, _)| *zone_id == id))
+                            .map(|(_, name)| name.as_str())
+                            .unwrap_or("Any");
+                        egui::ComboBox::from_id_source("target_zone")
+                            .selected_text(zone_name)
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut exit.target_zone_id, None, "Any")
+                                    .on_hover_text("Let the game decide where the player arrives");
+                                for (id, name) in target_zones {
+                                    ui.selectable_value(&mut exit.target_zone_id, Some(*id), name);
+                                }
+                            });
+                    });
+                });
+            }
+
+            if let Some(id) = removed {
+                level.exits.shift_remove(&id);
+            }
+        });
+}
+
+/// Paint an arrow linking two points, with a head that stays the same size at any zoom level
+fn paint_link(painter: &egui::Painter, from: egui::Pos2, to: egui::Pos2) {
+    let stroke = egui::Stroke::new(2.0, EXIT_COLOR);
+    painter.circle_filled(from, 4.0, EXIT_COLOR);
+    painter.line_segment([from, to], stroke);
+
+    let dir = (to - from).normalized();
+    if dir.x.is_finite() && dir.y.is_finite() {
+        let rot = egui::emath::Rot2::from_angle(std::f32::consts::TAU / 12.0);
+        let head_length = 12.0;
+        painter.line_segment([to, to - head_length * (rot * dir)], stroke);
+        painter.line_segment([to, to - head_length * (rot.inverse() * dir)], stroke);
+    }
+}
+
+/// Ask where to save the map of a level as a CSV or TSV file, and write it there
+fn export_map_file(ctx: &egui::Context, project: &ProjectState, level_id: Uid<Level>) {
+    let level = match project.data.levels.get(&level_id) {
+        Some(level) => level.clone(),
+        None => return,
+    };
+    let metatileset = project
+        .data
+        .metatilesets
+        .get(&level.metatileset_id)
+        .cloned();
+
+    let ctx = ctx.clone();
+    std::thread::spawn(move || {
+        let _task = BackgroundTask::start("Exporting a map");
+        let mut dialog = native_dialog::FileDialog::new();
+        for filter in MAP_FILE_FILTERS {
+            dialog = dialog.add_filter(filter.name, filter.extensions);
+        }
+        let path = match dialog
+            .set_filename(&format!("{}.csv", symbol_name(&level.name)))
+            .show_save_single_file()
+            .expect("Show save dialog")
+        {
+            Some(path) => path,
+            None => return,
+        };
+
+        let text = map_csv::encode_map(&level, metatileset.as_ref(), map_csv::delimiter(&path));
+        match std::fs::write(&path, text) {
+            Ok(()) => send_info_notification(&ctx, &format!("Exported map to {}", path.display())),
+            Err(e) => send_error_notification(&ctx, format!("Write {}: {}", path.display(), e)),
+        }
+    });
 }
@@ -1,26 +1,95 @@
-use std::path::PathBuf;
+use std::{collections::HashMap, path::PathBuf};
 
 use egui::{Color32, ComboBox, Layout};
+use indexmap::IndexMap;
+use watch::WatchReceiver;
 
 use crate::{
+    dither::{Dither, DitherDirection, DitherPattern},
     gui::{
-        components::{MetatileGui, MetatileKind},
-        project_state::SourceImageData,
+        accessibility,
+        background::BackgroundTask,
+        components::{
+            backdrop_gui, send_error_notification, send_info_notification, Backdrop, MetatileGui,
+            MetatileKind,
+        },
+        project_state::{SourceImage, SourceImageData, SourceImageStatus},
+        source_texture::GRAYSCALE_COLORS,
         ProjectState,
     },
-    project::{Metatile, Tile},
+    project::{Metatile, Metatileset, Project, Tile, TileAnimation},
+    source::{paint_indexes, PixelRegion},
     Uid,
 };
 
-use super::NesimgGuiTab;
+use super::{CanvasView, JumpTarget, NesimgGuiTab, ProjectLayout};
 
 pub struct MetatilesTab {
     current_source_image: Option<Uid<PathBuf>>,
     current_source_image_tile: Option<Tile>,
     current_metatile: Option<Uid<Metatile>>,
     metatile_list_col_count: u32,
+    /// The similar tile search, if its window is open
+    similar_tiles: Option<SimilarTiles>,
+    /// What to show for the pixels that use color 0
+    backdrop: Backdrop,
+    /// The metatileset whose pallet the metatiles are previewed with, or `None` for greyscale
+    preview_metatileset: Option<Uid<Metatileset>>,
+    /// The sub-pallet used to preview the metatiles that aren't in the preview metatileset
+    preview_sub_pallet: usize,
+    /// Whether to show the number of colors used by each tile of the source image
+    show_color_counts: bool,
+    /// Whether the current metatile needs to be scrolled into view in the metatile list
+    scroll_to_metatile: bool,
+    /// The dithering tool, if its window is open
+    dither_tool: Option<DitherTool>,
+    /// The dithering being written to disk, and the number of pixels it changed
+    dithering: WatchReceiver<Option<Result<usize, String>>>,
+    /// The zoom and pan of the metatile editor
+    editor_view: MetatileEditorState,
+    /// The zoom and pan of the source image viewer
+    source_view: SourceImageViewerState,
 }
 
+/// The state of the "Dither" window
+struct DitherTool {
+    source_id: Uid<PathBuf>,
+    /// The pixels of the source image that are dithered
+    region: PixelRegion,
+    dither: Dither,
+}
+
+/// The state of the "Similar Tiles" window
+struct SimilarTiles {
+    /// The most pixels that can differ between two tiles for them to be listed
+    max_difference: u32,
+    pairs: Vec<SimilarTilePair>,
+}
+
+/// Two tiles used by metatiles that only differ by a few pixels
+struct SimilarTilePair {
+    tiles: [Tile; 2],
+    /// The number of metatile quadrants using each tile
+    uses: [usize; 2],
+    /// The number of pixels that differ between the tiles
+    difference: u32,
+}
+
+/// The most similar tile pairs shown in the "Similar Tiles" window
+const MAX_SIMILAR_TILE_PAIRS: usize = 200;
+
+/// The largest width and height of the region dithered at once, in pixels
+const MAX_DITHER_SIZE: u32 = 64;
+
+/// The number of pixels of the surrounding image shown around the region in the dithering preview
+const DITHER_PREVIEW_MARGIN: u32 = 8;
+
+/// The size of each pixel in the zoomed dithering preview, in points
+const DITHER_PREVIEW_ZOOM: f32 = 4.0;
+
+/// The color used to outline the region being dithered
+const DITHER_REGION_COLOR: egui::Color32 = egui::Color32::from_rgb(255, 160, 0);
+
 impl Default for MetatilesTab {
     fn default() -> Self {
         Self {
@@ -28,6 +97,16 @@ impl Default for MetatilesTab {
             current_source_image_tile: None,
             current_metatile: Default::default(),
             metatile_list_col_count: 4,
+            similar_tiles: None,
+            backdrop: Backdrop::Color,
+            preview_metatileset: None,
+            preview_sub_pallet: 0,
+            show_color_counts: true,
+            scroll_to_metatile: false,
+            dither_tool: None,
+            dithering: watch::channel(None).1,
+            editor_view: Default::default(),
+            source_view: Default::default(),
         }
     }
 }
@@ -48,6 +127,13 @@ impl NesimgGuiTab for MetatilesTab {
             }
         }
 
+        if let Some(id) = &self.preview_metatileset {
+            if !project.data.metatilesets.contains_key(id) {
+                self.preview_metatileset = None;
+            }
+        }
+        let (preview_colors, default_preview_colors) = self.preview_colors(project);
+
         egui::SidePanel::right("metatiles_sidebar")
             .min_width(190.0)
             .show(ctx, |ui| {
@@ -170,6 +256,13 @@ impl NesimgGuiTab for MetatilesTab {
                                     }
 
                                     MetatileGui::new(project, MetatileKind::Standalone(id))
+                                        .backdrop(self.backdrop)
+                                        .preview_colors(
+                                            preview_colors
+                                                .get(&id)
+                                                .copied()
+                                                .or(default_preview_colors),
+                                        )
                                         .paint_at(rect, ui, frame);
 
                                     if self.current_metatile == Some(id) {
@@ -178,6 +271,10 @@ impl NesimgGuiTab for MetatilesTab {
                                             tile_rounding,
                                             (2.0, active_stroke_color),
                                         );
+                                        if self.scroll_to_metatile {
+                                            ui.scroll_to_rect(rect, Some(egui::Align::Center));
+                                            self.scroll_to_metatile = false;
+                                        }
                                     } else if response.hovered() {
                                         ui.painter().rect_stroke(
                                             rect,
@@ -185,6 +282,22 @@ impl NesimgGuiTab for MetatilesTab {
                                             (2.0, hovered_stroke_color),
                                         );
                                     }
+                                    accessibility::paint_focus(ui, &response, tile_rounding);
+                                    accessibility::image_button(
+                                        &response,
+                                        self.current_metatile == Some(id),
+                                        || {
+                                            format!(
+                                                "Metatile {}",
+                                                project
+                                                    .data
+                                                    .metatiles
+                                                    .get_index_of(&id)
+                                                    .unwrap_or(0)
+                                                    + 1
+                                            )
+                                        },
+                                    );
                                 }
                             });
                             ui.add_space(ui.spacing().item_spacing.y);
@@ -201,7 +314,7 @@ impl NesimgGuiTab for MetatilesTab {
                 ui.horizontal(|ui| {
                     ui.label("Source Image: ");
                     ComboBox::from_id_source("source_image")
-                        .width(ui.available_width() - ui.spacing().item_spacing.x)
+                        .width(ui.available_width() - ui.spacing().item_spacing.x * 2.0 - 200.0)
                         .selected_text(
                             self.current_source_image
                                 .map(|id| {
@@ -224,6 +337,29 @@ impl NesimgGuiTab for MetatilesTab {
                                 );
                             }
                         });
+                    ui.checkbox(&mut self.show_color_counts, "Color Counts")
+                        .on_hover_text(
+                            "Show how many of the 3 colors besides color 0 each tile uses, when \
+                            zoomed in far enough to read them",
+                        );
+                    if ui
+                        .add_enabled(
+                            self.current_source_image_tile.is_some(),
+                            egui::Button::new("▦ Dither"),
+                        )
+                        .on_hover_text(
+                            "Mix two colors in the selected tile with an ordered dithering pattern",
+                        )
+                        .clicked()
+                    {
+                        if let Some(tile) = &self.current_source_image_tile {
+                            self.dither_tool = Some(DitherTool {
+                                source_id: tile.source_id,
+                                region: PixelRegion::tile(tile.x, tile.y),
+                                dither: Dither::default(),
+                            });
+                        }
+                    }
                 });
 
                 ui.separator();
@@ -240,11 +376,19 @@ impl NesimgGuiTab for MetatilesTab {
                                 ui.colored_label(Color32::RED, e);
                             }
                             crate::gui::project_state::SourceImageStatus::Found(image) => {
+                                let dither_region = self
+                                    .dither_tool
+                                    .as_ref()
+                                    .filter(|x| x.source_id == id)
+                                    .map(|x| x.region);
                                 source_image_viewer(
                                     id,
                                     &image,
                                     project,
                                     &mut self.current_source_image_tile,
+                                    self.show_color_counts,
+                                    dither_region,
+                                    &mut self.source_view,
                                     ui,
                                 );
                             }
@@ -255,21 +399,60 @@ impl NesimgGuiTab for MetatilesTab {
                 });
             });
 
+        egui::TopBottomPanel::bottom("tile_animations")
+            .resizable(true)
+            .default_height(120.0)
+            .show(ctx, |ui| {
+                tile_animations_gui(project, &self.current_source_image_tile, ui);
+            });
+
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.horizontal(|ui| {
                 ui.set_height(ui.spacing().interact_size.y);
                 ui.label("Edit Metatile");
+                ui.with_layout(Layout::right_to_left(), |ui| {
+                    if ui
+                        .button("🔎 Similar Tiles")
+                        .on_hover_text(
+                            "Find tiles that only differ by a few pixels, which could be merged \
+                            to save pattern table space",
+                        )
+                        .clicked()
+                    {
+                        let max_difference = 4;
+                        self.similar_tiles = Some(SimilarTiles {
+                            max_difference,
+                            pairs: find_similar_tiles(project, max_difference),
+                        });
+                    }
+                    ui.separator();
+                    backdrop_gui(ui, "metatiles_backdrop", &mut self.backdrop);
+                    ui.separator();
+                    self.preview_pallet_gui(project, ui);
+                });
             });
             ui.separator();
 
             ui.centered_and_justified(|ui| {
                 if let Some(id) = self.current_metatile {
-                    metatile_editor(id, project, &self.current_source_image_tile, ui, frame);
+                    metatile_editor(
+                        id,
+                        project,
+                        &self.current_source_image_tile,
+                        self.backdrop,
+                        preview_colors.get(&id).copied().or(default_preview_colors),
+                        &mut self.editor_view,
+                        ui,
+                        frame,
+                    );
                 } else {
                     ui.label("No metatile selected");
                 }
             });
         });
+
+        self.similar_tiles_window(project, ctx);
+        self.dither_window(project, ctx);
     }
 
     fn help_text(&self) -> &'static str {
@@ -279,6 +462,625 @@ impl NesimgGuiTab for MetatilesTab {
     fn tooltip(&self) -> &'static str {
         "Create metatiles from source images"
     }
+
+    fn save_layout(&self, layout: &mut ProjectLayout) {
+        layout.metatiles_source = self.current_source_image;
+        layout.metatile = self.current_metatile;
+        layout.metatiles_preview = self.preview_metatileset;
+        layout.metatile_editor_view = Some(CanvasView {
+            zoom: self.editor_view.zoom,
+            pan: [self.editor_view.pan.x, self.editor_view.pan.y],
+        });
+        layout.metatiles_source_view = Some(CanvasView {
+            zoom: self.source_view.zoom,
+            pan: [self.source_view.pan.x, self.source_view.pan.y],
+        });
+    }
+
+    fn load_layout(&mut self, layout: &ProjectLayout) {
+        self.current_source_image = layout.metatiles_source;
+        self.current_source_image_tile = None;
+        self.current_metatile = layout.metatile;
+        self.similar_tiles = None;
+        self.dither_tool = None;
+        // Preview with the metatileset used last, in this tab or in the metatilesets tab
+        self.preview_metatileset = layout.metatiles_preview.or(layout.metatileset);
+        self.editor_view = layout
+            .metatile_editor_view
+            .map(|view| MetatileEditorState {
+                zoom: view.zoom,
+                pan: view.pan.into(),
+            })
+            .unwrap_or_default();
+        self.source_view = layout
+            .metatiles_source_view
+            .map(|view| SourceImageViewerState {
+                zoom: view.zoom,
+                pan: view.pan.into(),
+                drag_start: None,
+            })
+            .unwrap_or_default();
+    }
+
+    fn jump_to(&mut self, target: &JumpTarget) {
+        if let JumpTarget::Metatile(id) = target {
+            self.current_metatile = Some(*id);
+            self.scroll_to_metatile = true;
+        }
+    }
+}
+
+/// The colors each metatile is previewed with
+type PreviewColors = HashMap<Uid<Metatile>, [u32; 4]>;
+
+impl MetatilesTab {
+    /// The colors to preview each metatile in the preview metatileset with, and the colors for
+    /// the metatiles that aren't in it. Everything is shown in greyscale without a preview
+    /// metatileset.
+    fn preview_colors(&self, project: &ProjectState) -> (PreviewColors, Option<[u32; 4]>) {
+        let metatileset = match self
+            .preview_metatileset
+            .and_then(|id| project.data.metatilesets.get(&id))
+        {
+            Some(metatileset) => metatileset,
+            None => return (HashMap::new(), None),
+        };
+
+        let colors = metatileset
+            .tiles
+            .values()
+            .map(|x| (x.metatile_id, metatileset.sub_pallet(x.sub_pallet_idx)))
+            .collect();
+        (
+            colors,
+            Some(metatileset.sub_pallet(self.preview_sub_pallet)),
+        )
+    }
+
+    /// Pick the pallet that the metatiles are previewed with
+    fn preview_pallet_gui(&mut self, project: &ProjectState, ui: &mut egui::Ui) {
+        // The bar is laid out right to left
+        ui.add_enabled_ui(self.preview_metatileset.is_some(), |ui| {
+            ComboBox::from_id_source("preview_sub_pallet")
+                .width(40.0)
+                .selected_text(self.preview_sub_pallet.to_string())
+                .show_ui(ui, |ui| {
+                    for idx in 0..4 {
+                        ui.selectable_value(&mut self.preview_sub_pallet, idx, idx.to_string());
+                    }
+                })
+                .response
+                .on_hover_text(
+                    "The sub-pallet used for metatiles that aren't in the metatileset. The \
+                    others are shown with the sub-pallet they use in it",
+                );
+        });
+
+        let selected = self
+            .preview_metatileset
+            .and_then(|id| project.data.metatilesets.get(&id))
+            .map(|x| x.name.as_str())
+            .unwrap_or("Greyscale");
+        ComboBox::from_id_source("preview_metatileset")
+            .selected_text(selected)
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut self.preview_metatileset, None, "Greyscale");
+                for (id, metatileset) in &project.data.metatilesets {
+                    ui.selectable_value(
+                        &mut self.preview_metatileset,
+                        Some(*id),
+                        &metatileset.name,
+                    );
+                }
+            })
+            .response
+            .on_hover_text(
+                "Preview the metatiles with the pallet of a metatileset, since metatiles don't \
+                have colors until they are added to one",
+            );
+        ui.label("Colors:");
+    }
+    /// Render the window listing the similar tiles, if it is open
+    fn similar_tiles_window(&mut self, project: &mut ProjectState, ctx: &egui::Context) {
+        let similar_tiles = if let Some(similar_tiles) = &mut self.similar_tiles {
+            similar_tiles
+        } else {
+            return;
+        };
+
+        let mut open = true;
+        let mut replace = None;
+        let mut search = false;
+        egui::Window::new("🔎 Similar Tiles")
+            .open(&mut open)
+            .default_height(400.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Differing pixels: ");
+                    search = ui
+                        .add(egui::Slider::new(&mut similar_tiles.max_difference, 1..=16))
+                        .changed();
+                });
+                ui.label(
+                    "Tiles used by the metatiles that differ by this many pixels or less. Click \
+                    \"Use This\" to replace the other tile with it in every metatile.",
+                );
+                ui.separator();
+
+                if similar_tiles.pairs.is_empty() {
+                    ui.label("No similar tiles found");
+                    return;
+                }
+
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    for (i, pair) in similar_tiles.pairs.iter().enumerate() {
+                        ui.push_id(i, |ui| {
+                            ui.horizontal(|ui| {
+                                for side in 0..2 {
+                                    ui.vertical(|ui| {
+                                        source_tile_image(
+                                            ui,
+                                            &mut project.source_images,
+                                            &pair.tiles[side],
+                                        )
+                                        .on_hover_text(
+                                            format!("Used {} times in metatiles", pair.uses[side]),
+                                        );
+                                        if ui
+                                            .small_button("Use This")
+                                            .on_hover_text(
+                                                "Replace the other tile with this one in every \
+                                                metatile",
+                                            )
+                                            .clicked()
+                                        {
+                                            replace = Some((
+                                                pair.tiles[1 - side].clone(),
+                                                pair.tiles[side].clone(),
+                                            ));
+                                        }
+                                    });
+                                    if side == 0 {
+                                        ui.label(format!("{} px", pair.difference));
+                                    }
+                                }
+                            });
+                        });
+                        ui.separator();
+                    }
+                });
+            });
+
+        if let Some((from, to)) = replace {
+            for metatile in project.data.metatiles.values_mut() {
+                for tile in metatile.tiles.iter_mut().flatten() {
+                    if *tile == from {
+                        *tile = to.clone();
+                    }
+                }
+            }
+            search = true;
+        }
+        if search {
+            similar_tiles.pairs = find_similar_tiles(project, similar_tiles.max_difference);
+        }
+        if !open {
+            self.similar_tiles = None;
+        }
+    }
+
+    /// Render the dithering window, if it is open
+    fn dither_window(&mut self, project: &mut ProjectState, ctx: &egui::Context) {
+        match self.dithering.get_if_new().flatten() {
+            Some(Ok(count)) => send_info_notification(ctx, &format!("Dithered {} pixels", count)),
+            Some(Err(e)) => send_error_notification(ctx, e),
+            None => (),
+        }
+
+        let tool = if let Some(tool) = &mut self.dither_tool {
+            tool
+        } else {
+            return;
+        };
+        let image = match project
+            .source_images
+            .get_mut(&tool.source_id)
+            .map(|x| x.data.get())
+        {
+            Some(SourceImageStatus::Found(image)) => Some(image),
+            _ => None,
+        };
+        let path = project
+            .data
+            .sources
+            .get(&tool.source_id)
+            .map(|x| Project::source_path(&project.path, x));
+        let keep_pallet_order = project.data.pallet_order_sources.contains(&tool.source_id);
+
+        let mut open = true;
+        let mut apply = None;
+        egui::Window::new("▦ Dither")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let image = if let Some(image) = &image {
+                    image
+                } else {
+                    ui.label("The source image isn't loaded");
+                    return;
+                };
+                let [width, height] = image.texture.size().map(|x| x as u32);
+
+                ui.horizontal(|ui| {
+                    ui.label("Region:");
+                    let region = &mut tool.region;
+                    ui.add(egui::DragValue::new(&mut region.x).prefix("x: "));
+                    ui.add(egui::DragValue::new(&mut region.y).prefix("y: "));
+                    ui.add(
+                        egui::DragValue::new(&mut region.width)
+                            .prefix("w: ")
+                            .clamp_range(1..=MAX_DITHER_SIZE),
+                    );
+                    ui.add(
+                        egui::DragValue::new(&mut region.height)
+                            .prefix("h: ")
+                            .clamp_range(1..=MAX_DITHER_SIZE),
+                    );
+                    if ui
+                        .add_enabled(
+                            self.current_source_image_tile.is_some(),
+                            egui::Button::new("Selected Tile"),
+                        )
+                        .on_hover_text("Dither the tile selected in the source image")
+                        .clicked()
+                    {
+                        if let Some(tile) = &self.current_source_image_tile {
+                            tool.source_id = tile.source_id;
+                            *region = PixelRegion::tile(tile.x, tile.y);
+                        }
+                    }
+                });
+                tool.region = tool.region.clamp_to(width, height);
+
+                let dither = &mut tool.dither;
+                ui.horizontal(|ui| {
+                    ui.label("Colors:");
+                    color_index_gui(ui, "dither_color_0", &mut dither.colors[0]);
+                    if ui.button("⇄").on_hover_text("Swap the colors").clicked() {
+                        dither.colors.swap(0, 1);
+                    }
+                    color_index_gui(ui, "dither_color_1", &mut dither.colors[1]);
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Pattern:");
+                    ComboBox::from_id_source("dither_pattern")
+                        .selected_text(dither.pattern.name())
+                        .show_ui(ui, |ui| {
+                            for pattern in DitherPattern::ALL {
+                                ui.selectable_value(&mut dither.pattern, pattern, pattern.name());
+                            }
+                        });
+                    ui.add_enabled_ui(dither.pattern != DitherPattern::Solid, |ui| {
+                        ComboBox::from_id_source("dither_direction")
+                            .selected_text(dither.direction.name())
+                            .show_ui(ui, |ui| {
+                                for direction in DitherDirection::ALL {
+                                    ui.selectable_value(
+                                        &mut dither.direction,
+                                        direction,
+                                        direction.name(),
+                                    );
+                                }
+                            })
+                            .response
+                            .on_hover_text(
+                                "Blend from the first color to the second across the region, or \
+                                use the same mix everywhere",
+                            );
+                    });
+                });
+                ui.add_enabled_ui(
+                    dither.pattern != DitherPattern::Solid
+                        && dither.direction == DitherDirection::Flat,
+                    |ui| {
+                        ui.add(
+                            egui::Slider::new(&mut dither.density, 0.0..=1.0).text("Second color"),
+                        );
+                    },
+                );
+                ui.checkbox(&mut dither.keep_other_colors, "Keep other colors")
+                    .on_hover_text(
+                        "Only change the pixels that use one of the two colors, so outlines and \
+                        details stay",
+                    );
+                ui.separator();
+
+                let region = tool.region;
+                let dithered = dither.apply(&image.indexes, width, region);
+                let area = region.expand(DITHER_PREVIEW_MARGIN, width, height);
+                let before = |x: u32, y: u32| image.indexes[(y * width + x) as usize];
+                let after = |x: u32, y: u32| {
+                    if region.contains(x, y) {
+                        dithered[((y - region.y) * region.width + x - region.x) as usize]
+                    } else {
+                        before(x, y)
+                    }
+                };
+                let actual_size = 1.0 / ui.ctx().pixels_per_point();
+                egui::Grid::new("dither_preview").show(ui, |ui| {
+                    ui.label("Before");
+                    ui.label("After");
+                    ui.label("");
+                    ui.end_row();
+
+                    index_preview(ui, area, actual_size, None, before);
+                    index_preview(ui, area, actual_size, None, after);
+                    ui.label("1x");
+                    ui.end_row();
+
+                    index_preview(ui, area, DITHER_PREVIEW_ZOOM, Some(region), before);
+                    index_preview(ui, area, DITHER_PREVIEW_ZOOM, Some(region), after);
+                    ui.label(format!("{}x", DITHER_PREVIEW_ZOOM));
+                    ui.end_row();
+                });
+                ui.separator();
+
+                ui.label("The change is written to the image file.");
+                if ui
+                    .add_enabled(path.is_some(), egui::Button::new("▦ Apply"))
+                    .clicked()
+                {
+                    apply = path.clone().map(|path| (path, region, dithered));
+                }
+            });
+
+        if let Some((path, region, indexes)) = apply {
+            let (sender, receiver) = watch::channel(None);
+            self.dithering = receiver;
+            std::thread::spawn(move || {
+                let _task = BackgroundTask::start("Dithering a source image");
+                let result = paint_indexes(&path, region, &indexes, keep_pallet_order)
+                    .map_err(|e| format!("{:#}", e));
+                sender.send(Some(result));
+            });
+        }
+        if !open {
+            self.dither_tool = None;
+        }
+    }
+}
+
+/// Pick one of the four color indexes of a source image
+fn color_index_gui(ui: &mut egui::Ui, id_source: &str, idx: &mut u8) {
+    let (rect, _) = ui.allocate_exact_size(
+        egui::Vec2::splat(ui.spacing().interact_size.y),
+        egui::Sense::hover(),
+    );
+    ui.painter()
+        .rect_filled(rect, 2.0, GRAYSCALE_COLORS[*idx as usize]);
+    ComboBox::from_id_source(id_source)
+        .width(30.0)
+        .selected_text(idx.to_string())
+        .show_ui(ui, |ui| {
+            for option in 0..4 {
+                ui.selectable_value(idx, option, option.to_string());
+            }
+        });
+}
+
+/// Paint the color indexes of a region of a source image in grayscale, like the source image
+/// viewer, with each pixel `scale` points wide, and outline `highlight` inside of it
+fn index_preview(
+    ui: &mut egui::Ui,
+    area: PixelRegion,
+    scale: f32,
+    highlight: Option<PixelRegion>,
+    index_at: impl Fn(u32, u32) -> u8,
+) {
+    let size = egui::Vec2::new(area.width as f32, area.height as f32) * scale;
+    let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+    let pixel_rect = |x: u32, y: u32, width: u32, height: u32| {
+        let min = egui::Vec2::new((x - area.x) as f32, (y - area.y) as f32) * scale;
+        egui::Rect::from_min_size(
+            rect.min + min,
+            egui::Vec2::new(width as f32, height as f32) * scale,
+        )
+    };
+
+    for (x, y) in area.pixels() {
+        ui.painter().rect_filled(
+            pixel_rect(x, y, 1, 1),
+            0.0,
+            GRAYSCALE_COLORS[index_at(x, y) as usize % 4],
+        );
+    }
+    if let Some(region) = highlight {
+        ui.painter().rect_stroke(
+            pixel_rect(region.x, region.y, region.width, region.height),
+            0.0,
+            (1.0, DITHER_REGION_COLOR),
+        );
+    }
+}
+
+/// Find the pairs of tiles used by metatiles that differ by `max_difference` pixels or less, most
+/// similar first. Identical tiles aren't listed, because they are only stored once when exporting,
+/// and neither are animated tiles.
+fn find_similar_tiles(project: &mut ProjectState, max_difference: u32) -> Vec<SimilarTilePair> {
+    let mut uses = IndexMap::<Tile, usize>::new();
+    for metatile in project.data.metatiles.values() {
+        for tile in metatile.tiles.iter().flatten() {
+            if project.data.tile_animation(tile).is_none() {
+                *uses.entry(tile.clone()).or_default() += 1;
+            }
+        }
+    }
+
+    let tiles = uses
+        .into_iter()
+        .filter_map(|(tile, uses)| Some((project.tile_pixels(&tile)?, tile, uses)))
+        .collect::<Vec<_>>();
+
+    let mut pairs = Vec::new();
+    for (i, (a_pixels, a, a_uses)) in tiles.iter().enumerate() {
+        for (b_pixels, b, b_uses) in &tiles[i + 1..] {
+            let difference = a_pixels
+                .iter()
+                .zip(b_pixels)
+                .filter(|(a, b)| a != b)
+                .count() as u32;
+            if difference > 0 && difference <= max_difference {
+                pairs.push(SimilarTilePair {
+                    tiles: [a.clone(), b.clone()],
+                    uses: [*a_uses, *b_uses],
+                    difference,
+                });
+            }
+        }
+    }
+
+    pairs.sort_by_key(|x| x.difference);
+    pairs.truncate(MAX_SIMILAR_TILE_PAIRS);
+    pairs
+}
+
+/// The size that animation frames are shown at in the tile animation list
+const ANIMATION_FRAME_SIZE: egui::Vec2 = egui::Vec2::splat(32.0);
+
+/// Render the list of tile animations, with buttons to add the selected source tile as a frame
+fn tile_animations_gui(
+    project: &mut ProjectState,
+    current_source_image_tile: &Option<Tile>,
+    ui: &mut egui::Ui,
+) {
+    ui.add_space(ui.spacing().window_margin.top);
+    ui.horizontal(|ui| {
+        ui.label("Tile Animations");
+        ui.with_layout(Layout::right_to_left(), |ui| {
+            ui.add_enabled_ui(current_source_image_tile.is_some(), |ui| {
+                if ui
+                    .button("➕")
+                    .on_hover_text("Create an animation starting with the selected source tile")
+                    .clicked()
+                {
+                    project.data.tile_animations.insert(
+                        Uid::new(),
+                        TileAnimation {
+                            frames: current_source_image_tile.iter().cloned().collect(),
+                            ..Default::default()
+                        },
+                    );
+                }
+            });
+        });
+    });
+    ui.separator();
+
+    if project.data.tile_animations.is_empty() {
+        ui.label(
+            "Select a source tile and click ➕ to animate it. Metatiles using the tile will cycle \
+            through the animation frames.",
+        );
+        return;
+    }
+
+    let time = ui.input().time;
+    let mut removed = None;
+    egui::ScrollArea::vertical().show(ui, |ui| {
+        for (animation_id, animation) in &mut project.data.tile_animations {
+            ui.push_id(animation_id, |ui| {
+                ui.horizontal(|ui| {
+                    // Preview the animation
+                    if !animation.frames.is_empty() {
+                        let frame = animation.frame_at((time * 60.0) as u64);
+                        source_tile_image(ui, &mut project.source_images, frame)
+                            .on_hover_text("Preview");
+                        ui.ctx().request_repaint();
+                    }
+
+                    ui.add(egui::TextEdit::singleline(&mut animation.name).desired_width(120.0));
+                    ui.add(
+                        egui::DragValue::new(&mut animation.frame_duration)
+                            .clamp_range(1..=255)
+                            .suffix(" frames"),
+                    )
+                    .on_hover_text("How long each frame is shown, at 60 frames per second");
+                    ui.separator();
+
+                    let mut removed_frame = None;
+                    for (i, frame) in animation.frames.iter().enumerate() {
+                        source_tile_image(ui, &mut project.source_images, frame)
+                            .on_hover_text(if i == 0 {
+                                "Frame 1: metatiles using this tile are animated"
+                            } else {
+                                "Right-click to remove the frame"
+                            })
+                            .context_menu(|ui| {
+                                if ui.button("🗙 Remove Frame").clicked() {
+                                    removed_frame = Some(i);
+                                    ui.close_menu();
+                                }
+                            });
+                    }
+                    if let Some(i) = removed_frame {
+                        animation.frames.remove(i);
+                    }
+
+                    ui.add_enabled_ui(current_source_image_tile.is_some(), |ui| {
+                        if ui
+                            .button("➕")
+                            .on_hover_text("Add the selected source tile as a frame")
+                            .clicked()
+                        {
+                            animation.frames.extend(current_source_image_tile.clone());
+                        }
+                    });
+
+                    ui.with_layout(Layout::right_to_left(), |ui| {
+                        if ui.button("🗑").on_hover_text("Delete animation.").clicked() {
+                            removed = Some(*animation_id);
+                        }
+                    });
+                });
+            });
+        }
+    });
+
+    if let Some(id) = removed {
+        project.data.tile_animations.shift_remove(&id);
+    }
+}
+
+/// Show a single tile from a source image
+fn source_tile_image(
+    ui: &mut egui::Ui,
+    source_images: &mut IndexMap<Uid<PathBuf>, SourceImage>,
+    tile: &Tile,
+) -> egui::Response {
+    let image = source_images.get_mut(&tile.source_id).map(|x| x.data.get());
+    let response = match image {
+        Some(SourceImageStatus::Found(image)) => {
+            let size = image.texture.size_vec2();
+            let uv = egui::Rect::from_min_size(
+                egui::Pos2::new(tile.x as f32 * 8.0 / size.x, tile.y as f32 * 8.0 / size.y),
+                egui::Vec2::new(8.0 / size.x, 8.0 / size.y),
+            );
+            ui.add(
+                egui::Image::new(image.texture.texture_id(ui.ctx()), ANIMATION_FRAME_SIZE)
+                    .uv(uv)
+                    .sense(egui::Sense::click()),
+            )
+        }
+        _ => ui.add_sized(
+            ANIMATION_FRAME_SIZE,
+            egui::Label::new("?").sense(egui::Sense::click()),
+        ),
+    };
+    accessibility::image_button(&response, false, || {
+        format!("Source tile {}, {}", tile.x, tile.y)
+    });
+    accessibility::paint_focus(ui, &response, 0.0);
+    response
 }
 
 #[derive(Copy, Clone)]
@@ -303,18 +1105,27 @@ fn metatile_editor(
     metatile_id: Uid<Metatile>,
     project: &mut ProjectState,
     current_source_image_tile: &Option<Tile>,
+    backdrop: Backdrop,
+    preview_colors: Option<[u32; 4]>,
+    state: &mut MetatileEditorState,
     ui: &mut egui::Ui,
     frame: &mut eframe::Frame,
 ) {
-    let id = ui.id();
     let (rect, response) =
         ui.allocate_exact_size(ui.available_size(), egui::Sense::click_and_drag());
     let is_clicked = response.clicked_by(egui::PointerButton::Primary);
+    accessibility::canvas(&response, || {
+        let number = project
+            .data
+            .metatiles
+            .get_index_of(&metatile_id)
+            .unwrap_or(0)
+            + 1;
+        format!("Metatile {} editor", number)
+    });
 
     ui.set_clip_rect(rect);
 
-    let mut state: MetatileEditorState = *ui.data().get_temp_mut_or_default(id);
-
     // Handle zoom
     if response.hovered() {
         state.zoom += ui.input().scroll_delta.y * 0.01;
@@ -327,15 +1138,15 @@ fn metatile_editor(
     }
 
     // Handle cursor
-    if response.dragged_by(egui::PointerButton::Middle)
+    let response = if response.dragged_by(egui::PointerButton::Middle)
         || (ui.input().modifiers.command && response.dragged_by(egui::PointerButton::Primary))
     {
-        response.on_hover_cursor(egui::CursorIcon::Grabbing);
+        response.on_hover_cursor(egui::CursorIcon::Grabbing)
     } else if ui.input().modifiers.command {
-        response.on_hover_cursor(egui::CursorIcon::Grab);
+        response.on_hover_cursor(egui::CursorIcon::Grab)
     } else {
-        response.on_hover_cursor(egui::CursorIcon::Crosshair);
-    }
+        response.on_hover_cursor(egui::CursorIcon::Crosshair)
+    };
 
     // Calculate image rect render
     let min = rect.center() - METATILE_SIZE / 2.0 * state.zoom;
@@ -344,6 +1155,8 @@ fn metatile_editor(
 
     // Render metatile
     MetatileGui::new(project, MetatileKind::Standalone(metatile_id))
+        .backdrop(backdrop)
+        .preview_colors(preview_colors)
         .paint_at(image_rect, ui, frame);
 
     /// How wide a metatile is in tiles
@@ -378,7 +1191,7 @@ fn metatile_editor(
         }
     }
 
-    *ui.data().get_temp_mut_or_default(id) = state;
+    accessibility::paint_focus(ui, &response, 0.0);
 }
 
 #[derive(Copy, Clone)]
@@ -398,14 +1211,32 @@ impl Default for SourceImageViewerState {
     }
 }
 
+/// The smallest size on screen, in points, that tiles have to be for their color counts to be shown
+const COLOR_COUNT_MIN_TILE_SIZE: f32 = 32.0;
+
+/// The number of colors besides color 0 used by the 8x8 tile at a tile position in a source image
+fn tile_color_count(image: &SourceImageData, x: usize, y: usize) -> usize {
+    let width = image.texture.size()[0];
+    let mut used = [false; 4];
+    for row in y * 8..y * 8 + 8 {
+        let start = row * width + x * 8;
+        for &idx in image.indexes.get(start..start + 8).unwrap_or_default() {
+            used[idx as usize & 3] = true;
+        }
+    }
+    used[1..].iter().filter(|x| **x).count()
+}
+
 fn source_image_viewer(
     source_image_id: Uid<PathBuf>,
     source_image_data: &SourceImageData,
     project: &mut ProjectState,
     current_source_image_tile: &mut Option<Tile>,
+    show_color_counts: bool,
+    dither_region: Option<PixelRegion>,
+    state: &mut SourceImageViewerState,
     ui: &mut egui::Ui,
 ) {
-    let id = ui.id();
     let (rect, response) =
         ui.allocate_exact_size(ui.available_size(), egui::Sense::click_and_drag());
 
@@ -413,13 +1244,27 @@ fn source_image_viewer(
     let is_dragged_by_primary = response.dragged_by(egui::PointerButton::Primary);
     let select_drag_started = response.drag_started() && is_dragged_by_primary;
     let drag_released = response.drag_released() || !response.dragged();
+    accessibility::canvas(&response, || {
+        let name = project
+            .data
+            .sources
+            .get(&source_image_id)
+            .and_then(|x| x.file_name())
+            .map(|x| x.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        match current_source_image_tile.as_ref() {
+            Some(tile) => format!(
+                "Source image {}, tile {}, {} selected",
+                name, tile.x, tile.y
+            ),
+            None => format!("Source image {}", name),
+        }
+    });
 
     let image_size = source_image_data.texture.size_vec2();
 
     ui.set_clip_rect(rect);
 
-    let mut state: SourceImageViewerState = *ui.data().get_temp_mut_or_default(id);
-
     // Make sure selected tile is within image bounds and for the current source image
     if let Some(tile) = current_source_image_tile {
         tile.source_id = source_image_id;
@@ -439,15 +1284,15 @@ fn source_image_viewer(
     }
 
     // Handle cursor
-    if response.dragged_by(egui::PointerButton::Middle)
+    let response = if response.dragged_by(egui::PointerButton::Middle)
         || (ui.input().modifiers.command && response.dragged_by(egui::PointerButton::Primary))
     {
-        response.on_hover_cursor(egui::CursorIcon::Grabbing);
+        response.on_hover_cursor(egui::CursorIcon::Grabbing)
     } else if ui.input().modifiers.command {
-        response.on_hover_cursor(egui::CursorIcon::Grab);
+        response.on_hover_cursor(egui::CursorIcon::Grab)
     } else {
-        response.on_hover_cursor(egui::CursorIcon::Crosshair);
-    }
+        response.on_hover_cursor(egui::CursorIcon::Crosshair)
+    };
 
     // Calculate image rect render
     let min = rect.center() - image_size / 2.0 * state.zoom;
@@ -464,6 +1309,38 @@ fn source_image_viewer(
     let mut drag_selecting = false;
 
     let tile_size = TILE_SIZE * state.zoom;
+
+    // Render the color count of each visible tile, once the tiles are big enough to fit the badge
+    if show_color_counts && tile_size.x >= COLOR_COUNT_MIN_TILE_SIZE {
+        let visible = image_rect.intersect(rect);
+        let first = ((visible.min - image_rect.min) / tile_size).floor();
+        let last = ((visible.max - image_rect.min) / tile_size).ceil();
+        let font = egui::FontId::monospace((tile_size.x / 4.0).min(14.0));
+        for y in first.y as usize..(last.y as usize).min(image_size.y as usize / 8) {
+            for x in first.x as usize..(last.x as usize).min(image_size.x as usize / 8) {
+                let count = tile_color_count(source_image_data, x, y);
+                let color = if count >= 3 {
+                    egui::Color32::from_rgb(255, 160, 0)
+                } else {
+                    egui::Color32::WHITE
+                };
+                let corner =
+                    image_rect.min + egui::Vec2::new(x as f32 + 1.0, y as f32 + 1.0) * tile_size;
+                let text = ui
+                    .painter()
+                    .layout_no_wrap(format!("{}/3", count), font.clone(), color);
+                let badge = egui::Rect::from_min_size(
+                    corner - text.size() - egui::Vec2::splat(2.0),
+                    text.size(),
+                )
+                .expand(1.0);
+                ui.painter()
+                    .rect_filled(badge, 2.0, egui::Color32::from_black_alpha(180));
+                ui.painter()
+                    .galley(badge.min + egui::Vec2::splat(1.0), text);
+            }
+        }
+    }
     let mouse_pos = ui.input().pointer.interact_pos();
     if let Some(mouse_pos) = mouse_pos {
         if image_rect.contains(mouse_pos) {
@@ -617,5 +1494,14 @@ fn source_image_viewer(
         }
     }
 
-    *ui.data().get_temp_mut_or_default(id) = state;
+    // Render the region being dithered
+    if let Some(region) = dither_region {
+        let min = egui::Vec2::new(region.x as f32, region.y as f32) * state.zoom;
+        let size = egui::Vec2::new(region.width as f32, region.height as f32) * state.zoom;
+        let region_rect = egui::Rect::from_min_size(image_rect.min + min, size);
+        ui.painter()
+            .rect_stroke(region_rect, 0.0, (1.0, DITHER_REGION_COLOR));
+    }
+
+    accessibility::paint_focus(ui, &response, 0.0);
 }
@@ -1,23 +1,71 @@
-use indexmap::IndexSet;
+use std::collections::HashMap;
+
+use indexmap::{IndexMap, IndexSet};
+use watch::WatchReceiver;
 
 use crate::{
+    cluster::{cluster_metatiles, MetatileCluster, MAX_CLUSTER_TILES},
+    export::{symbol_name, ExportOnly},
     gui::{
-        components::{nes_color_picker, MetatileGui, MetatileKind},
+        accessibility,
+        background::BackgroundTask,
+        components::{
+            backdrop_gui, nes_color_picker, nes_color_swatch, send_error_notification,
+            send_info_notification, send_warning_notification, Backdrop, MetatileGui, MetatileKind,
+        },
+        export::export_only_menu,
         project_state::SourceImageStatus,
+        util::{pick_file, FileFilter},
         ProjectState,
     },
-    project::Metatileset,
+    library::{MetatileLibrary, LIBRARY_EXTENSION},
+    pallet::{
+        derive_pallet_from_image, format_pallet_text, parse_pallet_text, solve_sub_pallets,
+        ColorMerge, PalletProposal, SlotColors,
+    },
+    project::{Metatile, Metatileset, MetatilesetTile, Pallet},
     Uid,
 };
 
-use super::NesimgGuiTab;
+use super::{NesimgGuiTab, ProjectLayout};
 
 pub struct MetatilesetsTab {
     current_metatileset_id: Option<Uid<Metatileset>>,
     side_metatile_list_col_count: u8,
     central_metatile_list_col_count: u8,
-    /// The currently selected pallet, 0-3 that will be used for painting on metatiles
+    /// The currently selected pallet, 0-3, or higher for extra pallets, that will be used for
+    /// painting on metatiles
     current_subpallet_pallet: usize,
+    /// The pallet being derived from an image picked by the user
+    derived_pallet: WatchReceiver<Option<Result<PalletProposal, String>>>,
+    /// The derived pallet waiting to be accepted or discarded by the user
+    pallet_proposal: Option<PalletProposal>,
+    /// The pallet solver result waiting to be accepted or discarded by the user
+    pallet_solution: Option<SolvedPallet>,
+    /// The proposed grouping of metatiles waiting to be applied or discarded by the user
+    arrangement: Option<Vec<ArrangedGroup>>,
+    /// The pallet text in the paste menu, which starts out as the last copied pallet
+    pallet_text: String,
+    /// What to show for the pixels that use color 0
+    backdrop: Backdrop,
+    /// The metatile library being loaded to import into the project
+    library_import: WatchReceiver<Option<Result<MetatileLibrary, String>>>,
+}
+
+/// A group of similar metatiles proposed by the "Arrange" tool
+struct ArrangedGroup {
+    cluster: MetatileCluster,
+    /// The metatileset to add the group's metatiles to, or `None` to create a new one
+    target: Option<Uid<Metatileset>>,
+}
+
+/// A pallet computed by the pallet solver for the current metatileset
+struct SolvedPallet {
+    pallet: Pallet,
+    /// The sub-pallet index to assign to each of the metatileset tiles
+    tile_sub_pallets: Vec<(Uid<MetatilesetTile>, usize)>,
+    /// The color changes needed to fit the pallet, with the number of metatiles they affect
+    merges: Vec<(ColorMerge, usize)>,
 }
 
 impl Default for MetatilesetsTab {
@@ -27,10 +75,31 @@ impl Default for MetatilesetsTab {
             side_metatile_list_col_count: 5,
             central_metatile_list_col_count: 10,
             current_subpallet_pallet: 0,
+            derived_pallet: watch::channel(None).1,
+            pallet_proposal: None,
+            pallet_solution: None,
+            arrangement: None,
+            pallet_text: String::new(),
+            backdrop: Backdrop::Color,
+            library_import: watch::channel(None).1,
         }
     }
 }
 
+/// The space left for the rulers along the top and left of the pattern table, in points
+const PATTERN_RULER_SIZE: f32 = 16.0;
+
+/// The images that can be used to derive a pallet
+const PALLET_IMAGE_FILTERS: &[FileFilter] = &[FileFilter {
+    name: "Image",
+    extensions: &["png", "bmp", "gif"],
+}];
+
+const LIBRARY_FILTERS: &[FileFilter] = &[FileFilter {
+    name: "NESImg Metatile Library",
+    extensions: &[LIBRARY_EXTENSION],
+}];
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 enum SidebarTab {
     Tiles,
@@ -130,6 +199,8 @@ impl NesimgGuiTab for MetatilesetsTab {
             });
 
         ctx.data().insert_persisted(sidebar_tab_id, sidebar_tab);
+
+        self.arrange_window(project, ctx, frame);
     }
 
     fn help_text(&self) -> &'static str {
@@ -139,12 +210,84 @@ impl NesimgGuiTab for MetatilesetsTab {
     fn tooltip(&self) -> &'static str {
         "Color and group metatiles"
     }
+
+    fn save_layout(&self, layout: &mut ProjectLayout) {
+        layout.metatileset = self.current_metatileset_id;
+        layout.sub_pallet = self.current_subpallet_pallet;
+    }
+
+    fn load_layout(&mut self, layout: &ProjectLayout) {
+        self.current_metatileset_id = layout.metatileset;
+        self.current_subpallet_pallet = layout.sub_pallet;
+    }
 }
 
 impl MetatilesetsTab {
     fn top_panel(&mut self, ui: &mut egui::Ui, project: &mut ProjectState) {
         ui.add_space(1.0);
         ui.with_layout(egui::Layout::right_to_left(), |ui| {
+            if ui
+                .button("🗂 Arrange")
+                .on_hover_text(
+                    "Group the metatiles by the tiles and colors they share and suggest which \
+                    metatileset each group belongs in",
+                )
+                .clicked()
+            {
+                self.arrangement = Some(
+                    cluster_metatiles(&project.data)
+                        .into_iter()
+                        .map(|cluster| ArrangedGroup {
+                            target: cluster.metatileset_id,
+                            cluster,
+                        })
+                        .collect(),
+                );
+            }
+            ui.separator();
+            backdrop_gui(ui, "metatilesets_backdrop", &mut self.backdrop);
+            ui.separator();
+
+            match self.library_import.get_if_new().flatten() {
+                Some(Ok(library)) => match project.import_library(&library) {
+                    Ok(id) => {
+                        self.current_metatileset_id = Some(id);
+                        send_info_notification(
+                            ui.ctx(),
+                            &format!(
+                                "Imported {} metatiles from library `{}`",
+                                library.metatiles.len(),
+                                library.name
+                            ),
+                        );
+                    }
+                    Err(e) => send_error_notification(ui.ctx(), format!("{:#}", e)),
+                },
+                Some(Err(e)) => send_error_notification(ui.ctx(), e),
+                None => (),
+            }
+            if ui
+                .button("📥")
+                .on_hover_text("Import a metatile library as a new metatileset")
+                .clicked()
+            {
+                self.library_import = pick_file(LIBRARY_FILTERS, |path| {
+                    Some(MetatileLibrary::load(path).map_err(|e| format!("{:#}", e)))
+                });
+            }
+            ui.add_enabled_ui(self.current_metatileset_id.is_some(), |ui| {
+                if ui
+                    .button("📤")
+                    .on_hover_text(
+                        "Save the metatileset and the tiles it uses as a metatile library that \
+                        can be imported into other projects",
+                    )
+                    .clicked()
+                {
+                    save_library(ui.ctx(), project, self.current_metatileset_id.unwrap());
+                }
+            });
+
             ui.add_enabled_ui(self.current_metatileset_id.is_some(), |ui| {
                 if ui.button("🗑").on_hover_text("Delete Metatileset").clicked() {
                     project
@@ -191,6 +334,15 @@ impl MetatilesetsTab {
                             &metatileset.name,
                         );
                     }
+                })
+                .response
+                .context_menu(|ui| match self.current_metatileset_id {
+                    Some(id) => {
+                        ui.menu_button("⮫ Export Only This Metatileset", |ui| {
+                            export_only_menu(ui, project, ExportOnly::Metatileset(id));
+                        });
+                    }
+                    None => ui.close_menu(),
                 });
             ui.label("Metatileset: ");
 
@@ -248,6 +400,7 @@ impl MetatilesetsTab {
                         }
 
                         MetatileGui::new(project, MetatileKind::Standalone(id))
+                            .backdrop(self.backdrop)
                             .paint_at(rect, ui, frame);
 
                         if response.hovered() {
@@ -257,17 +410,27 @@ impl MetatilesetsTab {
                                 (2.0, hovered_stroke_color),
                             );
                         }
+                        accessibility::paint_focus(ui, &response, tile_rounding);
+                        accessibility::image_button(&response, false, || {
+                            format!(
+                                "Metatile {}, add to the metatileset",
+                                project.data.metatiles.get_index_of(&id).unwrap_or(0) + 1
+                            )
+                        });
 
                         if response.clicked() {
+                            let before = tile_counts(project);
                             if let Some(metatileset) = self.current_metatileset(project) {
                                 metatileset.tiles.insert(
                                     Uid::new(),
                                     crate::project::MetatilesetTile {
                                         metatile_id: id,
                                         sub_pallet_idx: 0,
+                                        properties: 0,
                                     },
                                 );
                             }
+                            warn_over_budget(ui.ctx(), project, &before);
                         }
 
                         response.on_hover_text("Click to add to metatileset");
@@ -284,57 +447,252 @@ impl MetatilesetsTab {
         ui: &mut egui::Ui,
         _frame: &mut eframe::Frame,
     ) {
+        let other_pallets = project
+            .data
+            .metatilesets
+            .iter()
+            .filter(|(id, _)| Some(**id) != self.current_metatileset_id)
+            .map(|(_, x)| (x.name.clone(), x.pallet.clone()))
+            .collect::<Vec<_>>();
         let metatileset = if let Some(metatileset) = self.current_metatileset(project) {
             metatileset
         } else {
             return;
         };
 
-        ui.horizontal(|ui| {
-            ui.radio_value(&mut self.current_subpallet_pallet, 0, "")
-                .on_hover_ui(|ui| {
-                    ui.label("Select pallet");
-                    ui.label("Shortcut: 1");
-                });
+        for i in 0..4 {
+            let background_locked = metatileset.background_locked();
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut self.current_subpallet_pallet, i, "")
+                    .on_hover_ui(|ui| {
+                        ui.label("Select pallet");
+                        ui.label(format!("Shortcut: {}", i + 1));
+                    });
 
-            nes_color_picker(ui, &mut metatileset.pallet.colors[0]);
-            nes_color_picker(ui, &mut metatileset.pallet.colors[1]);
-            nes_color_picker(ui, &mut metatileset.pallet.colors[2]);
-            nes_color_picker(ui, &mut metatileset.pallet.colors[3]);
-        });
-        ui.horizontal(|ui| {
-            ui.radio_value(&mut self.current_subpallet_pallet, 1, "")
-                .on_hover_ui(|ui| {
-                    ui.label("Select pallet");
-                    ui.label("Shortcut: 2");
+                ui.add_enabled_ui(!background_locked, |ui| {
+                    nes_color_picker(ui, &mut metatileset.pallet.colors[0]);
+                })
+                .response
+                .on_disabled_hover_text(
+                    "The background color is locked because it is part of a locked sub-pallet",
+                );
+                let locked = &mut metatileset.locked_sub_pallets[i];
+                ui.add_enabled_ui(!*locked, |ui| {
+                    for color in &mut metatileset.pallet.colors[1 + i * 3..4 + i * 3] {
+                        nes_color_picker(ui, color);
+                    }
                 });
-            nes_color_picker(ui, &mut metatileset.pallet.colors[0]);
-            nes_color_picker(ui, &mut metatileset.pallet.colors[4]);
-            nes_color_picker(ui, &mut metatileset.pallet.colors[5]);
-            nes_color_picker(ui, &mut metatileset.pallet.colors[6]);
-        });
+                if ui
+                    .selectable_label(*locked, if *locked { "🔒" } else { "🔓" })
+                    .on_hover_text(
+                        "Lock the sub-pallet's colors, and the background color, so they can't \
+                        be changed by accident or by the pallet tools, such as when the game's \
+                        code hardcodes them",
+                    )
+                    .clicked()
+                {
+                    *locked = !*locked;
+                }
+            });
+        }
+
         ui.horizontal(|ui| {
-            ui.radio_value(&mut self.current_subpallet_pallet, 2, "")
-                .on_hover_ui(|ui| {
-                    ui.label("Select pallet");
-                    ui.label("Shortcut: 3");
-                });
-            nes_color_picker(ui, &mut metatileset.pallet.colors[0]);
-            nes_color_picker(ui, &mut metatileset.pallet.colors[7]);
-            nes_color_picker(ui, &mut metatileset.pallet.colors[8]);
-            nes_color_picker(ui, &mut metatileset.pallet.colors[9]);
+            if ui
+                .button("📋 Copy")
+                .on_hover_text("Copy the four sub-pallets to the clipboard as hex colors")
+                .clicked()
+            {
+                self.pallet_text = format_pallet_text(&metatileset.pallet);
+                ui.output().copied_text = self.pallet_text.clone();
+                send_info_notification(ui.ctx(), "Copied pallet");
+            }
+
+            ui.menu_button("📥 Paste", |ui| {
+                ui.label("Paste pallet colors:");
+                ui.add(
+                    egui::TextEdit::multiline(&mut self.pallet_text)
+                        .hint_text("0F 21 11 01 0F 2A 1A 0A …")
+                        .desired_rows(2),
+                );
+                if ui
+                    .button("✔ Apply")
+                    .on_hover_text("Replace the pallet with the pasted colors")
+                    .clicked()
+                {
+                    match parse_pallet_text(&self.pallet_text) {
+                        Ok(pallet) => set_pallet(ui.ctx(), metatileset, pallet),
+                        Err(e) => send_error_notification(ui.ctx(), format!("{:#}", e)),
+                    }
+                    ui.close_menu();
+                }
+
+                if !other_pallets.is_empty() {
+                    ui.separator();
+                    ui.label("Copy from metatileset:");
+                    for (name, pallet) in &other_pallets {
+                        if ui
+                            .button(if name.is_empty() { "Untitled" } else { name })
+                            .on_hover_ui(|ui| pallet_preview(ui, pallet))
+                            .clicked()
+                        {
+                            set_pallet(ui.ctx(), metatileset, pallet.clone());
+                            ui.close_menu();
+                        }
+                    }
+                }
+            });
         });
+
+        let background = metatileset.pallet.colors[0];
+        let mut removed_extra = None;
+        for (i, colors) in metatileset.extra_sub_pallets.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut self.current_subpallet_pallet, 4 + i, "")
+                    .on_hover_text("Select extra pallet");
+                nes_color_swatch(ui, background);
+                for color in colors {
+                    nes_color_picker(ui, color);
+                }
+                if ui
+                    .small_button("🗑")
+                    .on_hover_text("Remove extra pallet")
+                    .clicked()
+                {
+                    removed_extra = Some(i);
+                }
+            });
+        }
+        if let Some(i) = removed_extra {
+            metatileset.remove_extra_sub_pallet(i);
+            if self.current_subpallet_pallet >= 4 + i {
+                self.current_subpallet_pallet = 0;
+            }
+        }
+
+        let mut solve = false;
         ui.horizontal(|ui| {
-            ui.radio_value(&mut self.current_subpallet_pallet, 3, "")
-                .on_hover_ui(|ui| {
-                    ui.label("Select pallet");
-                    ui.label("Shortcut: 4");
-                });
-            nes_color_picker(ui, &mut metatileset.pallet.colors[0]);
-            nes_color_picker(ui, &mut metatileset.pallet.colors[10]);
-            nes_color_picker(ui, &mut metatileset.pallet.colors[11]);
-            nes_color_picker(ui, &mut metatileset.pallet.colors[12]);
+            if ui
+                .button("➕ Extra Pallet")
+                .on_hover_text(
+                    "Add a pallet beyond the four supported by the NES, for sketching out colors \
+                    before using the pallet solver",
+                )
+                .clicked()
+            {
+                let [_, a, b, c] = metatileset.sub_pallet(self.current_subpallet_pallet);
+                metatileset.extra_sub_pallets.push([a, b, c]);
+                self.current_subpallet_pallet = 3 + metatileset.extra_sub_pallets.len();
+            }
+
+            solve = ui
+                .button("🧩 Solve Pallets")
+                .on_hover_text(
+                    "Fit the colors used by the metatiles into the four NES pallets, suggesting \
+                    color changes where they don't fit",
+                )
+                .clicked();
         });
+
+        ui.separator();
+
+        match self.derived_pallet.get_if_new().flatten() {
+            Some(Ok(proposal)) => self.pallet_proposal = Some(proposal),
+            Some(Err(e)) => send_error_notification(ui.ctx(), e),
+            None => (),
+        }
+
+        if ui
+            .button("🖼 Derive From Image…")
+            .on_hover_text(
+                "Suggest a pallet by snapping the colors of a mockup image to the NES pallet",
+            )
+            .clicked()
+        {
+            self.derived_pallet = pick_file(PALLET_IMAGE_FILTERS, |path| {
+                Some(derive_pallet_from_image(path).map_err(|e| format!("{:#}", e)))
+            });
+        }
+
+        let mut close_proposal = false;
+        if let Some(proposal) = &self.pallet_proposal {
+            ui.add_space(ui.spacing().item_spacing.y);
+            ui.label("Suggested Pallet:");
+            pallet_preview(ui, &proposal.pallet);
+
+            ui.label(format!(
+                "{} colors found in the image",
+                proposal.color_count
+            ));
+            if proposal.unfit_block_count > 0 {
+                ui.colored_label(
+                    egui::Color32::YELLOW,
+                    format!(
+                        "{} of {} 16x16 blocks can't be colored with these pallets",
+                        proposal.unfit_block_count, proposal.block_count
+                    ),
+                );
+            }
+
+            ui.horizontal(|ui| {
+                if ui.button("✔ Accept").clicked() {
+                    set_pallet(ui.ctx(), metatileset, proposal.pallet.clone());
+                    close_proposal = true;
+                }
+                if ui.button("🗙 Discard").clicked() {
+                    close_proposal = true;
+                }
+            });
+        }
+        if close_proposal {
+            self.pallet_proposal = None;
+        }
+
+        let mut close_solution = false;
+        if let Some(solution) = &self.pallet_solution {
+            ui.add_space(ui.spacing().item_spacing.y);
+            ui.label("Solved Pallet:");
+            pallet_preview(ui, &solution.pallet);
+
+            if solution.merges.is_empty() {
+                ui.label("All metatiles fit without changing colors");
+            } else {
+                ui.colored_label(egui::Color32::YELLOW, "Some colors have to change to fit:");
+                for (merge, count) in &solution.merges {
+                    ui.horizontal(|ui| {
+                        nes_color_swatch(ui, merge.from);
+                        ui.label("➡");
+                        nes_color_swatch(ui, merge.to);
+                        ui.label(format!("in {} metatiles", count));
+                    });
+                }
+            }
+
+            ui.horizontal(|ui| {
+                if ui.button("✔ Accept").clicked() {
+                    set_pallet(ui.ctx(), metatileset, solution.pallet.clone());
+                    for (id, sub_pallet_idx) in &solution.tile_sub_pallets {
+                        if let Some(tile) = metatileset.tiles.get_mut(id) {
+                            tile.sub_pallet_idx = *sub_pallet_idx;
+                        }
+                    }
+                    metatileset.extra_sub_pallets.clear();
+                    close_solution = true;
+                }
+                if ui.button("🗙 Discard").clicked() {
+                    close_solution = true;
+                }
+            });
+        }
+        if close_solution {
+            self.pallet_solution = None;
+            self.current_subpallet_pallet = self.current_subpallet_pallet.min(3);
+        }
+
+        if solve {
+            self.pallet_solution = self
+                .current_metatileset_id
+                .and_then(|id| solve_pallet(project, id));
+        }
     }
 
     fn pattern_table_sidebar(
@@ -352,6 +710,34 @@ impl MetatilesetsTab {
         ui.separator();
 
         let max_tiles = 16 * 16;
+        let budget = if let Some(metatileset) = self.current_metatileset(project) {
+            ui.horizontal(|ui| {
+                ui.label("Budget:");
+                let mut budget = metatileset.max_tiles();
+                if ui
+                    .add(
+                        egui::DragValue::new(&mut budget)
+                            .clamp_range(1..=max_tiles)
+                            .suffix(" tiles"),
+                    )
+                    .on_hover_text(
+                        "The number of unique tiles this metatileset may use, such as 128 if the \
+                        game switches half of the pattern table",
+                    )
+                    .changed()
+                {
+                    metatileset.tile_budget = if budget == max_tiles {
+                        None
+                    } else {
+                        Some(budget)
+                    };
+                }
+            });
+            metatileset.max_tiles()
+        } else {
+            return;
+        };
+
         let mut tiles = if let Some(metatileset) = self.current_metatileset(project) {
             let mut tiles = IndexSet::with_capacity(max_tiles);
 
@@ -375,7 +761,7 @@ impl MetatilesetsTab {
             return;
         };
 
-        let progress = tiles.len() as f32 / max_tiles as f32;
+        let progress = tiles.len() as f32 / budget as f32;
         if progress > 1.0 {
             let dark = ui.style().visuals.dark_mode;
             ui.style_mut().visuals.selection.bg_fill = if dark {
@@ -388,14 +774,17 @@ impl MetatilesetsTab {
             egui::ProgressBar::new(progress)
                 .show_percentage()
                 .text(format!(
-                    "{}Percentage Used: {:.1}%",
+                    "{}{} / {} Tiles Used",
                     if progress > 1.0 { "⚠ " } else { "" },
-                    progress * 100.0
+                    tiles.len(),
+                    budget
                 ))
                 .desired_width(ui.available_width()),
         );
-        if progress > 1.0 {
+        if tiles.len() > max_tiles {
             progress_resp.on_hover_text("⚠ Too many tiles to fit into NES pattern table!");
+        } else if progress > 1.0 {
+            progress_resp.on_hover_text("⚠ Too many tiles for the metatileset's budget!");
         }
 
         ui.separator();
@@ -404,9 +793,14 @@ impl MetatilesetsTab {
 
         let size = ui.available_width().min(ui.available_height());
 
-        let (rect, _response) =
+        let (outer_rect, response) =
             ui.allocate_exact_size(egui::Vec2::splat(size), egui::Sense::hover());
 
+        // Leave room for the rulers along the top and left of the pattern table
+        let rect = egui::Rect::from_min_max(
+            outer_rect.min + egui::Vec2::splat(PATTERN_RULER_SIZE),
+            outer_rect.max,
+        );
         ui.painter().rect_filled(rect, 0.0, egui::Color32::BLACK);
 
         let pattern_table_width = 128;
@@ -416,6 +810,41 @@ impl MetatilesetsTab {
         let tiles_high = pattern_table_height / tile_size;
         let physical_tile_size = egui::Vec2::splat(rect.width() / tiles_wide as f32 + 0.1);
 
+        // Label the columns with the low digit of the tile index and the rows with the high digit,
+        // the way the pattern table is laid out in memory
+        let text_color = ui.visuals().text_color();
+        for i in 0..tiles_wide {
+            let offset = (i as f32 + 0.5) * rect.width() / tiles_wide as f32;
+            ui.painter().text(
+                egui::pos2(rect.left() + offset, outer_rect.top()),
+                egui::Align2::CENTER_TOP,
+                format!("{:X}", i),
+                egui::FontId::monospace(10.0),
+                text_color,
+            );
+            ui.painter().text(
+                egui::pos2(outer_rect.left(), rect.top() + offset),
+                egui::Align2::LEFT_CENTER,
+                format!("{:X}0", i),
+                egui::FontId::monospace(10.0),
+                text_color,
+            );
+        }
+
+        if let Some(pos) = response.hover_pos().filter(|x| rect.contains(*x)) {
+            let tile = ((pos - rect.min) / rect.width() * tiles_wide as f32).floor();
+            let (x, y) = (tile.x as usize, tile.y as usize);
+            let index = y * tiles_wide + x;
+            response.on_hover_text(format!(
+                "Tile ${:02X} (column {}, row {})\nAddress: ${:04X} or ${:04X}",
+                index,
+                x,
+                y,
+                index * 16,
+                0x1000 + index * 16,
+            ));
+        }
+
         for y in 0..tiles_high {
             for x in 0..tiles_wide {
                 let min = rect.min + egui::Vec2::new(x as f32, y as f32) * physical_tile_size;
@@ -524,6 +953,7 @@ impl MetatilesetsTab {
                                 metatileset_tile_id: id,
                             },
                         )
+                        .backdrop(self.backdrop)
                         .paint_at(rect, ui, frame);
 
                         if response.hovered() {
@@ -533,6 +963,17 @@ impl MetatilesetsTab {
                                 (2.0, hovered_stroke_color),
                             );
                         }
+                        accessibility::paint_focus(ui, &response, tile_rounding);
+                        accessibility::image_button(&response, false, || {
+                            let tile = project.data.metatilesets[&metatileset_id]
+                                .tiles
+                                .get_full(&id);
+                            format!(
+                                "Metatile {}, sub-pallet {}",
+                                tile.map_or(0, |x| x.0 + 1),
+                                tile.map_or(0, |x| x.2.sub_pallet_idx)
+                            )
+                        });
 
                         // Paint the active pallet onto the tile
                         if sidebar_tab == &SidebarTab::Colors {
@@ -550,6 +991,33 @@ impl MetatilesetsTab {
                         }
 
                         response.context_menu(|ui| {
+                            let property_flags = project.data.property_flags.clone();
+                            if let Some(tile) = self
+                                .current_metatileset(project)
+                                .and_then(|x| x.tiles.get_mut(&id))
+                            {
+                                for (bit, name) in property_flags.iter().enumerate() {
+                                    if name.trim().is_empty() {
+                                        continue;
+                                    }
+                                    let mut set = tile.properties & 1 << bit != 0;
+                                    if ui.checkbox(&mut set, name.trim()).changed() {
+                                        tile.properties ^= 1 << bit;
+                                    }
+                                }
+                                ui.horizontal(|ui| {
+                                    ui.label("Properties: ");
+                                    ui.add(
+                                        egui::DragValue::new(&mut tile.properties)
+                                            .custom_formatter(|x, _| format!("${:02X}", x as u8)),
+                                    )
+                                    .on_hover_text(
+                                        "Flags that the game gives the metatile, such as whether \
+                                        it is solid, exported as the property byte of the \
+                                        metatile records",
+                                    );
+                                });
+                            }
                             if ui.button("🗑 Remove").clicked() {
                                 self.current_metatileset(project).unwrap().tiles.remove(&id);
                                 ui.close_menu();
@@ -563,6 +1031,401 @@ impl MetatilesetsTab {
     }
 }
 
+impl MetatilesetsTab {
+    /// Show the groups proposed by the "Arrange" tool, if there are any
+    fn arrange_window(
+        &mut self,
+        project: &mut ProjectState,
+        ctx: &egui::Context,
+        frame: &mut eframe::Frame,
+    ) {
+        let groups = if let Some(groups) = &mut self.arrangement {
+            groups
+        } else {
+            return;
+        };
+
+        let mut open = true;
+        let mut apply = false;
+        let mut discard = false;
+        egui::Window::new("Arrange Metatiles")
+            .open(&mut open)
+            .default_width(400.0)
+            .show(ctx, |ui| {
+                ui.label(
+                    "Metatiles grouped by the tiles and colors they share. Each group fits in one \
+                    pattern table and one pallet.",
+                );
+                ui.separator();
+
+                egui::ScrollArea::vertical()
+                    .max_height(400.0)
+                    .show(ui, |ui| {
+                        for (i, group) in groups.iter_mut().enumerate() {
+                            arranged_group_gui(ui, project, frame, i, group);
+                            ui.separator();
+                        }
+                    });
+
+                ui.horizontal(|ui| {
+                    apply = ui
+                        .button("✔ Apply")
+                        .on_hover_text(
+                            "Add each group's metatiles to its metatileset. Metatiles are not \
+                            removed from other metatilesets, so existing levels keep working.",
+                        )
+                        .clicked();
+                    discard = ui.button("🗙 Discard").clicked();
+                });
+            });
+
+        if apply {
+            let before = tile_counts(project);
+            apply_arrangement(project, groups);
+            warn_over_budget(ctx, project, &before);
+        }
+        if !open || apply || discard {
+            self.arrangement = None;
+        }
+    }
+}
+
+/// Show one group proposed by the "Arrange" tool, with a selector for its metatileset
+fn arranged_group_gui(
+    ui: &mut egui::Ui,
+    project: &mut ProjectState,
+    frame: &mut eframe::Frame,
+    idx: usize,
+    group: &mut ArrangedGroup,
+) {
+    let cluster = &group.cluster;
+    ui.horizontal(|ui| {
+        ui.strong(format!("Group {}", idx + 1));
+        ui.label(format!(
+            "{} metatiles, {}/{} tiles, {} colors",
+            cluster.metatiles.len(),
+            cluster.tile_count,
+            MAX_CLUSTER_TILES,
+            cluster.color_count
+        ));
+    });
+
+    ui.horizontal(|ui| {
+        ui.label("Metatileset:");
+        let metatilesets = &project.data.metatilesets;
+        egui::ComboBox::from_id_source(("arrange_target", idx))
+            .selected_text(
+                group
+                    .target
+                    .and_then(|id| metatilesets.get(&id))
+                    .map(|x| x.name.as_str())
+                    .unwrap_or("➕ New Metatileset"),
+            )
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut group.target, None, "➕ New Metatileset");
+                for (id, metatileset) in metatilesets {
+                    ui.selectable_value(&mut group.target, Some(*id), &metatileset.name);
+                }
+            });
+
+        let missing = match group.target.and_then(|id| metatilesets.get(&id)) {
+            Some(metatileset) => group
+                .cluster
+                .metatiles
+                .iter()
+                .filter(|id| !metatileset.tiles.values().any(|x| x.metatile_id == **id))
+                .count(),
+            None => group.cluster.metatiles.len(),
+        };
+        if missing > 0 {
+            ui.label(format!("{} to add", missing));
+        } else {
+            ui.label("Already in place");
+        }
+    });
+
+    ui.horizontal_wrapped(|ui| {
+        for id in &group.cluster.metatiles {
+            let (rect, _) = ui.allocate_exact_size(egui::Vec2::splat(24.0), egui::Sense::hover());
+            MetatileGui::new(project, MetatileKind::Standalone(*id)).paint_at(rect, ui, frame);
+        }
+    });
+}
+
+/// Add the metatiles of each arranged group to its metatileset, creating new metatilesets for
+/// groups that don't have one.
+///
+/// Metatiles keep their colors if the metatileset has a sub-pallet with the same colors as the one
+/// they use in the metatileset they came from.
+fn apply_arrangement(project: &mut ProjectState, groups: &[ArrangedGroup]) {
+    // The colors each metatile is currently drawn with, and the metatileset it came from
+    let mut homes = IndexMap::<Uid<Metatile>, ([u32; 4], Uid<Metatileset>)>::new();
+    for (metatileset_id, metatileset) in &project.data.metatilesets {
+        for tile in metatileset.tiles.values() {
+            homes
+                .entry(tile.metatile_id)
+                .or_insert((metatileset.sub_pallet(tile.sub_pallet_idx), *metatileset_id));
+        }
+    }
+
+    for (i, group) in groups.iter().enumerate() {
+        let target_id = match group.target {
+            Some(id) if project.data.metatilesets.contains_key(&id) => id,
+            _ => {
+                // Start from the pallet of the metatileset most of the group came from
+                let mut counts = IndexMap::<Uid<Metatileset>, usize>::new();
+                for (_, home) in group
+                    .cluster
+                    .metatiles
+                    .iter()
+                    .filter_map(|id| homes.get(id))
+                {
+                    *counts.entry(*home).or_default() += 1;
+                }
+                let pallet = counts
+                    .iter()
+                    .max_by_key(|(_, count)| **count)
+                    .and_then(|(id, _)| project.data.metatilesets.get(id))
+                    .map(|x| x.pallet.clone())
+                    .unwrap_or_default();
+
+                let id = Uid::new();
+                project.data.metatilesets.insert(
+                    id,
+                    Metatileset {
+                        name: format!("Group {}", i + 1),
+                        pallet,
+                        ..Default::default()
+                    },
+                );
+                id
+            }
+        };
+
+        let metatileset = project.data.metatilesets.get_mut(&target_id).unwrap();
+        let sub_pallets = metatileset.pallet.get_sub_pallets();
+        for metatile_id in &group.cluster.metatiles {
+            if metatileset
+                .tiles
+                .values()
+                .any(|x| x.metatile_id == *metatile_id)
+            {
+                continue;
+            }
+            let sub_pallet_idx = homes
+                .get(metatile_id)
+                .and_then(|(colors, _)| sub_pallets.iter().position(|x| x == colors))
+                .unwrap_or(0);
+            metatileset.tiles.insert(
+                Uid::new(),
+                MetatilesetTile {
+                    metatile_id: *metatile_id,
+                    sub_pallet_idx,
+                    properties: 0,
+                },
+            );
+        }
+    }
+}
+
+/// Ask where to save a metatileset as a metatile library, and write it there
+fn save_library(ctx: &egui::Context, project: &mut ProjectState, id: Uid<Metatileset>) {
+    let data = project.data.clone();
+    let metatileset = match data.metatilesets.get(&id) {
+        Some(metatileset) => metatileset,
+        None => return,
+    };
+    let library =
+        match MetatileLibrary::from_metatileset(&data, metatileset, |x| project.tile_pixels(x)) {
+            Ok(library) => library,
+            Err(e) => {
+                send_error_notification(ctx, format!("{:#}", e));
+                return;
+            }
+        };
+
+    let ctx = ctx.clone();
+    std::thread::spawn(move || {
+        let _task = BackgroundTask::start("Saving a metatile library");
+        let mut dialog = native_dialog::FileDialog::new();
+        for filter in LIBRARY_FILTERS {
+            dialog = dialog.add_filter(filter.name, filter.extensions);
+        }
+        let path = match dialog
+            .set_filename(&format!(
+                "{}.{}",
+                symbol_name(&library.name),
+                LIBRARY_EXTENSION
+            ))
+            .show_save_single_file()
+            .expect("Show save dialog")
+        {
+            Some(path) => path,
+            None => return,
+        };
+
+        match library.save(&path) {
+            Ok(()) => send_info_notification(
+                &ctx,
+                &format!(
+                    "Saved {} metatiles and {} tiles to {}",
+                    library.metatiles.len(),
+                    library.tiles.len(),
+                    path.display()
+                ),
+            ),
+            Err(e) => send_error_notification(&ctx, format!("{:#}", e)),
+        }
+    });
+}
+
+/// The number of unique tiles used by each metatileset, for [`warn_over_budget`]
+fn tile_counts(project: &ProjectState) -> HashMap<Uid<Metatileset>, usize> {
+    project
+        .data
+        .metatilesets
+        .iter()
+        .map(|(id, x)| (*id, x.unique_tile_count(&project.data.metatiles)))
+        .collect()
+}
+
+/// Warn about the metatilesets that went over their tile budget since the `before` counts were
+/// taken
+fn warn_over_budget(
+    ctx: &egui::Context,
+    project: &ProjectState,
+    before: &HashMap<Uid<Metatileset>, usize>,
+) {
+    for (id, metatileset) in &project.data.metatilesets {
+        let count = metatileset.unique_tile_count(&project.data.metatiles);
+        let budget = metatileset.max_tiles();
+        if count > budget && before.get(id).map(|&x| x <= budget) != Some(false) {
+            send_warning_notification(
+                ctx,
+                format!(
+                    "Metatileset `{}` now uses {} unique tiles, which is over its budget of {}",
+                    metatileset.name, count, budget
+                ),
+            );
+        }
+    }
+}
+
+/// Replace the pallet of a metatileset, letting the user know if any colors were kept because
+/// their sub-pallets are locked
+fn set_pallet(ctx: &egui::Context, metatileset: &mut Metatileset, pallet: Pallet) {
+    if metatileset.set_pallet(pallet) {
+        send_info_notification(ctx, "Kept the colors of the locked sub-pallets");
+    }
+}
+
+/// Display the four sub-pallets of a pallet without allowing them to be edited
+fn pallet_preview(ui: &mut egui::Ui, pallet: &Pallet) {
+    for sub_pallet in pallet.get_sub_pallets() {
+        ui.horizontal(|ui| {
+            for color in sub_pallet {
+                nes_color_swatch(ui, color);
+            }
+        });
+    }
+}
+
+/// Fit the colors used by the tiles of a metatileset into the four sub-pallets of its pallet
+fn solve_pallet(project: &mut ProjectState, id: Uid<Metatileset>) -> Option<SolvedPallet> {
+    let metatileset = project.data.metatilesets.get(&id)?.clone();
+
+    // Group the tiles by the colors they actually use
+    let mut requests = IndexMap::<SlotColors, usize>::new();
+    let mut tile_requests = Vec::with_capacity(metatileset.tiles.len());
+    for (tile_id, tile) in &metatileset.tiles {
+        let used = used_color_slots(project, tile.metatile_id);
+        let colors = metatileset.sub_pallet(tile.sub_pallet_idx);
+
+        let mut request = [None; 3];
+        for slot in 0..3 {
+            if used[slot] {
+                request[slot] = Some(colors[slot + 1]);
+            }
+        }
+
+        let entry = requests.entry(request);
+        tile_requests.push((*tile_id, entry.index()));
+        *entry.or_default() += 1;
+    }
+    let requests = requests.into_iter().collect::<Vec<_>>();
+
+    let locked = [0, 1, 2, 3].map(|idx| {
+        let [_, a, b, c] = metatileset.sub_pallet(idx);
+        if metatileset.locked_sub_pallets[idx] {
+            Some([a, b, c])
+        } else {
+            None
+        }
+    });
+    let solution = solve_sub_pallets(&requests, &locked);
+
+    // Slots that no tile uses keep their current color
+    let mut pallet = metatileset.pallet.clone();
+    for (i, sub_pallet) in solution.sub_pallets.iter().enumerate() {
+        for (slot, color) in sub_pallet.iter().enumerate() {
+            if let Some(color) = color {
+                pallet.colors[1 + i * 3 + slot] = *color;
+            }
+        }
+    }
+
+    Some(SolvedPallet {
+        pallet,
+        tile_sub_pallets: tile_requests
+            .into_iter()
+            .map(|(tile_id, request)| (tile_id, solution.assignments[request]))
+            .collect(),
+        merges: solution
+            .merges
+            .into_iter()
+            .map(|merge| {
+                let count = requests[merge.request].1;
+                (merge, count)
+            })
+            .collect(),
+    })
+}
+
+/// Find which of the three colors after the background color are used by a metatile.
+///
+/// If the source image for one of the tiles isn't loaded, all of the colors are assumed to be used.
+fn used_color_slots(project: &mut ProjectState, metatile_id: Uid<Metatile>) -> [bool; 3] {
+    let mut used = [false; 3];
+    let metatile = if let Some(metatile) = project.data.metatiles.get(&metatile_id) {
+        metatile
+    } else {
+        return used;
+    };
+
+    for tile in metatile.tiles.iter().flatten() {
+        let image = match project
+            .source_images
+            .get_mut(&tile.source_id)
+            .map(|x| x.data.get())
+        {
+            Some(SourceImageStatus::Found(image)) => image,
+            _ => return [true; 3],
+        };
+        let width = image.texture.size()[0];
+
+        for y in 0..8 {
+            let start = (tile.y as usize * 8 + y) * width + tile.x as usize * 8;
+            for &idx in image.indexes.iter().skip(start).take(8) {
+                if idx > 0 {
+                    used[idx as usize - 1] = true;
+                }
+            }
+        }
+    }
+
+    used
+}
+
 /// Sort the metatileset based on the order the tiles are in in the corresponding sources
 fn sort_project_metatileset(project: &mut ProjectState, id: Uid<Metatileset>) {
     let metatileset = project.data.metatilesets.get_mut(&id).unwrap();
@@ -1,32 +1,144 @@
-use std::path::PathBuf;
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsString,
+    path::{Component, Path, PathBuf},
+};
 
 use egui::Vec2;
-use egui_extras::{Size, TableBuilder};
+use egui_extras::{RetainedImage, Size, TableBuilder};
 use watch::WatchReceiver;
 
 use crate::{
-    gui::{project_state::SourceImageStatus, ProjectState},
+    constants::NES_PALLET,
+    gui::{
+        background::BackgroundTask,
+        components::{nes_color_picker, send_error_notification, send_info_notification},
+        project_state::SourceImageStatus,
+        source_texture, ProjectState,
+    },
+    pallet::nearest_nes_color,
+    project::{Project, Tile},
+    source::{color_report, image_colors, replace_color, ColorReport, TileColorViolation},
     Uid,
 };
 
-use super::NesimgGuiTab;
+use super::{JumpTarget, NesimgGuiTab, ProjectLayout};
 
 pub struct SourcesTab {
     new_source: WatchReceiver<Option<PathBuf>>,
     update_source: (Uid<PathBuf>, WatchReceiver<Option<PathBuf>>),
     preview_zoom: f32,
+    /// The color report being generated for a source
+    report_loading: WatchReceiver<Option<Result<SourceReport, String>>>,
+    /// The color report being shown, if any
+    report: Option<SourceReport>,
+    /// The new locations being searched for the missing sources
+    relink_loading: WatchReceiver<Option<Relink>>,
+    /// The new locations found for the missing sources, waiting to be applied or discarded
+    relink: Option<Relink>,
+    /// The source tile jumped to from another tab, if its window is open
+    located_tile: Option<LocatedTile>,
+    /// The colors of the source image being loaded for the "Replace Color" window
+    color_replace_loading: WatchReceiver<Option<Result<ColorReplace, String>>>,
+    /// The source image whose colors are being replaced, if the window is open
+    color_replace: Option<ColorReplace>,
+    /// The color replacement being written to disk, and the number of pixels it changed
+    color_replacing: WatchReceiver<Option<Result<usize, String>>>,
+    /// The sources checked in the list, which the bulk actions apply to
+    selected: HashSet<Uid<PathBuf>>,
+    /// The sources being moved, and the folder picked to move them to
+    rebase: (Vec<Uid<PathBuf>>, WatchReceiver<Option<PathBuf>>),
+}
+
+/// A source image shown in the "Replace Color" window
+struct ColorReplace {
+    path: PathBuf,
+    /// The distinct colors of the image, with the number of pixels that use each
+    colors: Vec<([u8; 4], usize)>,
+    /// The color picked to be replaced
+    from: Option<[u8; 4]>,
+    /// The color to replace it with
+    to: [u8; 3],
 }
 
+/// A source tile shown in the "Source Tile" window
+struct LocatedTile {
+    tile: Tile,
+    /// Whether the tile needs to be scrolled into view
+    scroll_to: bool,
+    zoom: f32,
+}
+
+/// The new locations found for missing sources in a directory picked by the user
+#[derive(Clone)]
+struct Relink {
+    base_dir: PathBuf,
+    /// The missing sources that were found, with their old path and their new absolute path
+    found: Vec<(Uid<PathBuf>, PathBuf, PathBuf)>,
+    /// The paths of the missing sources that weren't found
+    not_found: Vec<PathBuf>,
+}
+
+/// How many folders deep to search for missing sources
+const RELINK_SEARCH_DEPTH: usize = 8;
+
 impl Default for SourcesTab {
     fn default() -> Self {
         Self {
             update_source: (Uid::default(), watch::channel(None).1),
             new_source: watch::channel(None).1,
             preview_zoom: 3.0,
+            report_loading: watch::channel(None).1,
+            report: None,
+            relink_loading: watch::channel(None).1,
+            relink: None,
+            located_tile: None,
+            color_replace_loading: watch::channel(None).1,
+            color_replace: None,
+            color_replacing: watch::channel(None).1,
+            selected: HashSet::new(),
+            rebase: (Vec::new(), watch::channel(None).1),
         }
     }
 }
 
+/// The color used to mark tiles that break the color rules in the color report
+const VIOLATION_COLOR: egui::Color32 = egui::Color32::from_rgb(230, 70, 60);
+/// The color used to mark the selected tile in the color report
+const SELECTED_VIOLATION_COLOR: egui::Color32 = egui::Color32::from_rgb(240, 220, 60);
+/// The color used to mark the tile in the "Source Tile" window
+const LOCATED_TILE_COLOR: egui::Color32 = egui::Color32::from_rgb(80, 230, 120);
+
+/// The color report for a source image, along with the image it was made from
+#[derive(Clone)]
+struct SourceReport {
+    path: PathBuf,
+    report: ColorReport,
+    image: std::sync::Arc<RetainedImage>,
+    /// The index in the report of the violation that was last jumped to
+    selected: Option<usize>,
+    /// Whether the selected violation needs to be scrolled into view
+    scroll_to_selected: bool,
+    /// Whether tiles with colors that aren't in the NES pallet are reported
+    check_nes_colors: bool,
+    zoom: f32,
+}
+
+impl SourceReport {
+    /// The indexes of the violations that are shown in the report
+    fn shown_violations(&self) -> Vec<usize> {
+        self.report
+            .violations
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| {
+                x.too_many_colors() || (self.check_nes_colors && x.non_nes_color_count > 0)
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+}
+
 impl NesimgGuiTab for SourcesTab {
     fn show(
         &mut self,
@@ -40,11 +152,81 @@ impl NesimgGuiTab for SourcesTab {
         if let Some(path) = self.update_source.1.get_if_new().flatten() {
             project.update_source(self.update_source.0, path);
         }
+        if let Some(dir) = self.rebase.1.get_if_new().flatten() {
+            rebase_sources(ctx, project, &self.rebase.0, &dir);
+        }
+        self.selected
+            .retain(|id| project.data.sources.contains_key(id));
+
+        let missing = project
+            .data
+            .sources
+            .iter()
+            .filter(|(_, path)| !Project::source_path(&project.path, path).exists())
+            .map(|(id, path)| (*id, path.clone()))
+            .collect::<Vec<_>>();
 
         egui::CentralPanel::default().show(ctx, |ui| {
-            if ui.button("➕ Add Source").clicked() {
-                self.new_source = browse_for_image_path();
-            }
+            ui.horizontal(|ui| {
+                if ui.button("➕ Add Source").clicked() {
+                    self.new_source = browse_for_image_path();
+                }
+
+                if !missing.is_empty()
+                    && ui
+                        .button("🔗 Relink Sources…")
+                        .on_hover_text(format!(
+                            "{} source files are missing. Pick a folder to find them in by their \
+                            file names.",
+                            missing.len()
+                        ))
+                        .clicked()
+                {
+                    self.relink_loading = find_missing_sources(missing);
+                }
+
+                if !self.selected.is_empty() {
+                    ui.separator();
+                    ui.label(format!("{} selected:", self.selected.len()));
+                    if ui
+                        .button("🗀 Move Selected…")
+                        .on_hover_text(
+                            "Pick the folder the selected images are in now. Their paths are \
+                            changed to point into it, keeping the subfolders they have between \
+                            them.",
+                        )
+                        .clicked()
+                    {
+                        self.rebase = (
+                            project
+                                .data
+                                .sources
+                                .keys()
+                                .filter(|x| self.selected.contains(x))
+                                .copied()
+                                .collect(),
+                            browse_for_folder(),
+                        );
+                    }
+                    if ui
+                        .button("🗑 Remove Selected")
+                        .on_hover_text("Remove the selected sources from the project")
+                        .clicked()
+                    {
+                        let selected = std::mem::take(&mut self.selected);
+                        project.data.sources.retain(|id, _| !selected.contains(id));
+                        project
+                            .data
+                            .pallet_order_sources
+                            .retain(|id| !selected.contains(id));
+                        project.source_images.retain(|id, _| !selected.contains(id));
+                        send_info_notification(ctx, &format!("Removed {} sources", selected.len()));
+                    }
+                    if ui.button("Clear Selection").clicked() {
+                        self.selected.clear();
+                    }
+                }
+            });
 
             ui.separator();
 
@@ -60,10 +242,25 @@ impl NesimgGuiTab for SourcesTab {
                     .cell_layout(
                         egui::Layout::left_to_right().with_cross_align(egui::Align::Center),
                     )
+                    .column(Size::exact(24.0)) // Selection checkbox
                     .column(Size::remainder()) // Source path
                     .column(Size::exact(ROW_HEIGHT * 2.0)) // Image
+                    .column(Size::exact(ROW_HEIGHT * 1.5)) // Color buttons
                     .column(Size::exact(ROW_HEIGHT)) // Delete button
                     .header(20.0, |mut header| {
+                        header.col(|ui| {
+                            let all = self.selected.len() == project.data.sources.len();
+                            let mut checked = all;
+                            ui.checkbox(&mut checked, "")
+                                .on_hover_text("Select all sources");
+                            if checked != all {
+                                self.selected = if checked {
+                                    project.data.sources.keys().copied().collect()
+                                } else {
+                                    HashSet::new()
+                                };
+                            }
+                        });
                         header.col(|ui| {
                             ui.horizontal(|ui| {
                                 ui.label("Source Path");
@@ -75,15 +272,29 @@ impl NesimgGuiTab for SourcesTab {
                         header.col(|ui| {
                             ui.label("Image");
                         });
+                        header.col(|ui| {
+                            ui.label("Colors");
+                        });
                         header.col(|ui| {
                             ui.label("Remove");
                         });
                     })
                     .body(|mut body| {
+                        let mut pallet_order_change = None;
                         project.source_images.retain(|id, image| {
                             let mut keep = true;
 
                             body.row(ROW_HEIGHT, |mut row| {
+                                row.col(|ui| {
+                                    let mut checked = self.selected.contains(id);
+                                    if ui.checkbox(&mut checked, "").changed() {
+                                        if checked {
+                                            self.selected.insert(*id);
+                                        } else {
+                                            self.selected.remove(id);
+                                        }
+                                    }
+                                });
                                 row.col(|ui| {
                                     ui.horizontal(|ui| {
                                         ui.add_space(7.0);
@@ -129,19 +340,67 @@ impl NesimgGuiTab for SourcesTab {
                                             });
                                     }
                                 });
+                                row.col(|ui| {
+                                    if ui
+                                        .button("🔍")
+                                        .on_hover_text(
+                                            "Show the tiles that break the NES color rules",
+                                        )
+                                        .clicked()
+                                    {
+                                        self.report_loading = load_report(
+                                            &Project::source_path(&project.path, &image.path),
+                                            false,
+                                        );
+                                    }
+                                    if ui
+                                        .button("🎨")
+                                        .on_hover_text(
+                                            "Replace one color with another across the image",
+                                        )
+                                        .clicked()
+                                    {
+                                        self.color_replace_loading = load_color_replace(
+                                            Project::source_path(&project.path, &image.path),
+                                            None,
+                                        );
+                                    }
+                                    let pallet_order =
+                                        project.data.pallet_order_sources.contains(id);
+                                    if ui
+                                        .selectable_label(pallet_order, "🔢")
+                                        .on_hover_text(
+                                            "Number the colors of an indexed PNG or GIF in the \
+                                            order of its pallet, instead of from darkest to \
+                                            brightest",
+                                        )
+                                        .clicked()
+                                    {
+                                        pallet_order_change = Some((*id, !pallet_order));
+                                    }
+                                });
                                 row.col(|ui| {
                                     if ui.button("🗙").clicked() {
                                         keep = false;
                                         project.data.sources.remove(id);
+                                        project.data.pallet_order_sources.shift_remove(id);
                                     };
                                 });
                             });
 
                             keep
                         });
+                        if let Some((id, keep)) = pallet_order_change {
+                            project.set_keep_pallet_order(id, keep);
+                        }
                     });
             }
         });
+
+        self.report_window(ctx);
+        self.color_replace_window(ctx);
+        self.relink_window(project, ctx);
+        self.located_tile_window(project, ctx);
     }
 
     fn help_text(&self) -> &'static str {
@@ -151,14 +410,720 @@ impl NesimgGuiTab for SourcesTab {
     fn tooltip(&self) -> &'static str {
         "Select source images"
     }
+
+    fn save_layout(&self, layout: &mut ProjectLayout) {
+        layout.sources_zoom = Some(self.preview_zoom);
+    }
+
+    fn load_layout(&mut self, layout: &ProjectLayout) {
+        if let Some(zoom) = layout.sources_zoom {
+            self.preview_zoom = zoom;
+        }
+        self.located_tile = None;
+    }
+
+    fn jump_to(&mut self, target: &JumpTarget) {
+        if let JumpTarget::SourceTile(tile) = target {
+            self.located_tile = Some(LocatedTile {
+                tile: tile.clone(),
+                scroll_to: true,
+                zoom: 4.0,
+            });
+        }
+    }
+}
+
+impl SourcesTab {
+    /// Show the window marking the source tile that was jumped to, if there is one
+    fn located_tile_window(&mut self, project: &mut ProjectState, ctx: &egui::Context) {
+        let located = if let Some(located) = &mut self.located_tile {
+            located
+        } else {
+            return;
+        };
+        let tile = &located.tile;
+
+        let mut open = true;
+        egui::Window::new("Source Tile")
+            .open(&mut open)
+            .default_width(500.0)
+            .default_height(400.0)
+            .show(ctx, |ui| {
+                let source = match project.source_images.get_mut(&tile.source_id) {
+                    Some(source) => source,
+                    None => {
+                        ui.label("The source of this tile has been removed.");
+                        return;
+                    }
+                };
+                ui.horizontal(|ui| {
+                    ui.label(format!(
+                        "`{}`: tile {}, {} at pixel {}, {}",
+                        source.path.display(),
+                        tile.x,
+                        tile.y,
+                        tile.x as u32 * 8,
+                        tile.y as u32 * 8,
+                    ));
+                    ui.add(
+                        egui::Slider::new(&mut located.zoom, 1.0..=8.0)
+                            .text("Zoom")
+                            .show_value(false),
+                    );
+                });
+                ui.separator();
+
+                let image = match source.data.get() {
+                    SourceImageStatus::Found(image) => image,
+                    SourceImageStatus::Loading => {
+                        ui.spinner();
+                        return;
+                    }
+                    SourceImageStatus::Error(e) => {
+                        ui.colored_label(egui::Color32::RED, e);
+                        return;
+                    }
+                };
+                egui::ScrollArea::both().show(ui, |ui| {
+                    let size = image.texture.size_vec2() * located.zoom;
+                    let rect = ui.image(image.texture.texture_id(ctx), size).rect;
+                    let tile_size = 8.0 * located.zoom;
+                    let tile_rect = egui::Rect::from_min_size(
+                        rect.min + egui::vec2(tile.x as f32, tile.y as f32) * tile_size,
+                        Vec2::splat(tile_size),
+                    );
+                    ui.painter_at(rect).rect_stroke(
+                        tile_rect.expand(1.0),
+                        0.0,
+                        (2.0, LOCATED_TILE_COLOR),
+                    );
+                    if located.scroll_to {
+                        ui.scroll_to_rect(tile_rect, Some(egui::Align::Center));
+                        located.scroll_to = false;
+                    }
+                });
+            });
+
+        if !open {
+            self.located_tile = None;
+        }
+    }
+
+    /// Show the window listing the new locations found for the missing sources, if a search has
+    /// finished
+    fn relink_window(&mut self, project: &mut ProjectState, ctx: &egui::Context) {
+        if let Some(relink) = self.relink_loading.get_if_new().flatten() {
+            if relink.found.is_empty() {
+                send_error_notification(
+                    ctx,
+                    format!(
+                        "None of the missing sources were found in {}",
+                        relink.base_dir.display()
+                    ),
+                );
+            } else {
+                self.relink = Some(relink);
+            }
+        }
+
+        let relink = if let Some(relink) = &self.relink {
+            relink
+        } else {
+            return;
+        };
+
+        let mut open = true;
+        let mut close = false;
+        egui::Window::new("🔗 Relink Sources")
+            .open(&mut open)
+            .default_width(500.0)
+            .show(ctx, |ui| {
+                ui.label(format!("Found in {}:", relink.base_dir.display()));
+                egui::ScrollArea::vertical()
+                    .max_height(300.0)
+                    .show(ui, |ui| {
+                        egui::Grid::new("relinked_sources")
+                            .num_columns(3)
+                            .striped(true)
+                            .show(ui, |ui| {
+                                for (_, old_path, new_path) in &relink.found {
+                                    ui.label(old_path.display().to_string());
+                                    ui.label("➡");
+                                    ui.label(new_path.display().to_string());
+                                    ui.end_row();
+                                }
+                            });
+                    });
+
+                if !relink.not_found.is_empty() {
+                    ui.separator();
+                    ui.colored_label(
+                        egui::Color32::YELLOW,
+                        format!("{} sources weren't found:", relink.not_found.len()),
+                    );
+                    for path in &relink.not_found {
+                        ui.label(path.display().to_string());
+                    }
+                }
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button("✔ Relink").clicked() {
+                        for (id, _, new_path) in &relink.found {
+                            project.update_source(*id, new_path.clone());
+                        }
+                        send_info_notification(
+                            ui.ctx(),
+                            &format!("Relinked {} sources", relink.found.len()),
+                        );
+                        close = true;
+                    }
+                    if ui.button("🗙 Cancel").clicked() {
+                        close = true;
+                    }
+                });
+            });
+
+        if !open || close {
+            self.relink = None;
+        }
+    }
+
+    /// Show the window for replacing a color across a source image, if it is open
+    fn color_replace_window(&mut self, ctx: &egui::Context) {
+        match self.color_replace_loading.get_if_new().flatten() {
+            Some(Ok(replace)) => self.color_replace = Some(replace),
+            Some(Err(e)) => send_error_notification(ctx, e),
+            None => (),
+        }
+        let replaced = self.color_replacing.get_if_new().flatten();
+        match &replaced {
+            Some(Ok(count)) => {
+                send_info_notification(ctx, &format!("Replaced the color of {} pixels", count))
+            }
+            Some(Err(e)) => send_error_notification(ctx, e),
+            None => (),
+        }
+
+        let replace = if let Some(replace) = &mut self.color_replace {
+            replace
+        } else {
+            return;
+        };
+        if replaced.is_some() {
+            // Load the colors again so the list shows the new color
+            self.color_replace_loading = load_color_replace(replace.path.clone(), Some(replace.to));
+        }
+
+        let mut open = true;
+        let mut apply = false;
+        egui::Window::new("Replace Color")
+            .open(&mut open)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!("`{}`", replace.path.display()));
+                ui.separator();
+
+                ui.label("Replace:");
+                egui::ScrollArea::vertical()
+                    .max_height(200.0)
+                    .show(ui, |ui| {
+                        for (color, count) in &replace.colors {
+                            ui.horizontal(|ui| {
+                                color_swatch(ui, [color[0], color[1], color[2]]);
+                                let label = format!(
+                                    "#{:02X}{:02X}{:02X}{}: {} pixels",
+                                    color[0],
+                                    color[1],
+                                    color[2],
+                                    if color[3] == 255 {
+                                        String::new()
+                                    } else {
+                                        format!(" alpha {}", color[3])
+                                    },
+                                    count
+                                );
+                                ui.selectable_value(&mut replace.from, Some(*color), label);
+                            });
+                        }
+                    });
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    ui.label("With:");
+                    egui::color_picker::color_edit_button_srgb(ui, &mut replace.to);
+                    ui.label(format!(
+                        "#{:02X}{:02X}{:02X}",
+                        replace.to[0], replace.to[1], replace.to[2]
+                    ));
+                    ui.separator();
+                    ui.label("NES Color:");
+                    let mut nes_color = nearest_nes_color(replace.to);
+                    let before = nes_color;
+                    nes_color_picker(ui, &mut nes_color);
+                    if nes_color != before {
+                        let color = NES_PALLET[nes_color as usize];
+                        replace.to = [color.r(), color.g(), color.b()];
+                    }
+                });
+                ui.separator();
+
+                ui.label("The change is written to the image file.");
+                let can_apply = replace.from.map_or(false, |from| {
+                    from != [replace.to[0], replace.to[1], replace.to[2], 255]
+                });
+                apply = ui
+                    .add_enabled(can_apply, egui::Button::new("🎨 Replace"))
+                    .clicked();
+            });
+
+        if apply {
+            if let Some(from) = replace.from {
+                let (path, to) = (replace.path.clone(), replace.to);
+                let (sender, receiver) = watch::channel(None);
+                self.color_replacing = receiver;
+                std::thread::spawn(move || {
+                    let _task = BackgroundTask::start("Replacing a color");
+                    let result = replace_color(&path, from, to).map_err(|e| format!("{:#}", e));
+                    sender.send(Some(result));
+                });
+            }
+        }
+        if !open {
+            self.color_replace = None;
+        }
+    }
+
+    /// Show the color report window, if a report has been generated
+    fn report_window(&mut self, ctx: &egui::Context) {
+        match self.report_loading.get_if_new().flatten() {
+            Some(Ok(report)) => self.report = Some(report),
+            Some(Err(e)) => send_error_notification(ctx, e),
+            None => (),
+        }
+
+        let source_report = if let Some(report) = &mut self.report {
+            report
+        } else {
+            return;
+        };
+
+        let mut open = true;
+        let mut refresh = false;
+        egui::Window::new("Color Report")
+            .open(&mut open)
+            .default_width(600.0)
+            .default_height(400.0)
+            .show(ctx, |ui| {
+                let shown = source_report.shown_violations();
+                let report = &source_report.report;
+
+                ui.horizontal(|ui| {
+                    ui.label(format!("`{}`", source_report.path.display()));
+                    if ui
+                        .button("⟲")
+                        .on_hover_text("Check the image again with the latest changes")
+                        .clicked()
+                    {
+                        refresh = true;
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label(format!("{} colors, backdrop", report.color_count));
+                    let [r, g, b, _] = report.backdrop;
+                    color_swatch(ui, [r, g, b]);
+                    ui.separator();
+                    ui.checkbox(&mut source_report.check_nes_colors, "Flag non-NES colors")
+                        .on_hover_text(
+                            "Also report tiles with colors that aren't exactly in the NES pallet. \
+                            Only useful for sources drawn with real NES colors instead of four \
+                            shades.",
+                        );
+                });
+                ui.horizontal(|ui| {
+                    ui.label(format!("{} tiles break the color rules", shown.len()));
+                    let position = source_report
+                        .selected
+                        .and_then(|selected| shown.iter().position(|x| *x == selected));
+                    ui.add_enabled_ui(!shown.is_empty(), |ui| {
+                        if ui.button("⏶ Previous").clicked() {
+                            let idx = match position {
+                                Some(0) | None => shown.len() - 1,
+                                Some(i) => i - 1,
+                            };
+                            source_report.selected = Some(shown[idx]);
+                            source_report.scroll_to_selected = true;
+                        }
+                        if ui.button("⏷ Next").clicked() {
+                            let idx = position.map(|i| (i + 1) % shown.len()).unwrap_or(0);
+                            source_report.selected = Some(shown[idx]);
+                            source_report.scroll_to_selected = true;
+                        }
+                    });
+                    ui.add(
+                        egui::Slider::new(&mut source_report.zoom, 1.0..=8.0)
+                            .text("Zoom")
+                            .show_value(false),
+                    );
+                });
+                ui.separator();
+
+                egui::SidePanel::left("color_report_list")
+                    .resizable(false)
+                    .default_width(170.0)
+                    .show_inside(ui, |ui| {
+                        egui::ScrollArea::vertical().show(ui, |ui| {
+                            if shown.is_empty() {
+                                ui.label("All tiles follow the color rules.");
+                            }
+                            for &i in &shown {
+                                let violation = &report.violations[i];
+                                if ui
+                                    .selectable_label(
+                                        source_report.selected == Some(i),
+                                        violation_label(violation, source_report.check_nes_colors),
+                                    )
+                                    .clicked()
+                                {
+                                    source_report.selected = Some(i);
+                                    source_report.scroll_to_selected = true;
+                                }
+                            }
+                        });
+                    });
+
+                egui::ScrollArea::both().show(ui, |ui| {
+                    let size = source_report.image.size_vec2() * source_report.zoom;
+                    let response = ui.image(source_report.image.texture_id(ctx), size);
+                    let rect = response.rect;
+                    let tile_size = 8.0 * source_report.zoom;
+                    let tile_rect = |violation: &TileColorViolation| {
+                        egui::Rect::from_min_size(
+                            rect.min
+                                + egui::vec2(violation.x as f32, violation.y as f32) * tile_size,
+                            Vec2::splat(tile_size),
+                        )
+                    };
+
+                    let painter = ui.painter_at(rect);
+                    for &i in &shown {
+                        painter.rect_stroke(
+                            tile_rect(&report.violations[i]).shrink(0.5),
+                            0.0,
+                            (1.0, VIOLATION_COLOR),
+                        );
+                    }
+                    if let Some(violation) = source_report
+                        .selected
+                        .filter(|i| shown.contains(i))
+                        .map(|i| &report.violations[i])
+                    {
+                        let selected_rect = tile_rect(violation);
+                        painter.rect_stroke(
+                            selected_rect.expand(1.0),
+                            0.0,
+                            (2.0, SELECTED_VIOLATION_COLOR),
+                        );
+                        if source_report.scroll_to_selected {
+                            ui.scroll_to_rect(selected_rect, Some(egui::Align::Center));
+                        }
+                    }
+
+                    if let Some(pos) = response.hover_pos() {
+                        let tile = ((pos - rect.min) / tile_size).floor();
+                        if let Some(violation) = shown
+                            .iter()
+                            .map(|&i| &report.violations[i])
+                            .find(|x| x.x as f32 == tile.x && x.y as f32 == tile.y)
+                        {
+                            response.on_hover_text(violation_label(
+                                violation,
+                                source_report.check_nes_colors,
+                            ));
+                        }
+                    }
+                });
+                source_report.scroll_to_selected = false;
+            });
+
+        if refresh {
+            self.report_loading = load_report(&source_report.path, source_report.check_nes_colors);
+        }
+        if !open {
+            self.report = None;
+        }
+    }
+}
+
+/// Describe a tile that breaks the color rules for the color report
+fn violation_label(violation: &TileColorViolation, check_nes_colors: bool) -> String {
+    let mut problems = Vec::new();
+    if violation.too_many_colors() {
+        problems.push(format!("{} colors + backdrop", violation.color_count));
+    }
+    if check_nes_colors && violation.non_nes_color_count > 0 {
+        problems.push(format!("{} non-NES colors", violation.non_nes_color_count));
+    }
+    format!(
+        "Tile ({}, {}): {}",
+        violation.x,
+        violation.y,
+        problems.join(", ")
+    )
+}
+
+/// Generate the color report for a source image in the background
+fn load_report(
+    path: &Path,
+    check_nes_colors: bool,
+) -> WatchReceiver<Option<Result<SourceReport, String>>> {
+    let path = path.to_owned();
+    let (sender, receiver) = watch::channel(None);
+
+    std::thread::spawn(move || {
+        let load = || -> anyhow::Result<_> {
+            let image = image::open(&path)?.into_rgba8();
+            let report = color_report(&image);
+            let texture = RetainedImage::from_color_image(
+                "source_color_report",
+                egui::ColorImage::from_rgba_unmultiplied(
+                    [image.width() as usize, image.height() as usize],
+                    image.as_raw(),
+                ),
+            )
+            .with_texture_filter(source_texture::texture_filter());
+
+            Ok(SourceReport {
+                path: path.clone(),
+                report,
+                image: std::sync::Arc::new(texture),
+                selected: None,
+                scroll_to_selected: false,
+                check_nes_colors,
+                zoom: 3.0,
+            })
+        };
+
+        sender.send(Some(load().map_err(|e| format!("{:#}", e))));
+    });
+
+    receiver
+}
+
+/// Load the colors of a source image for the "Replace Color" window in the background, starting
+/// with `to` as the replacement color if it is given
+fn load_color_replace(
+    path: PathBuf,
+    to: Option<[u8; 3]>,
+) -> WatchReceiver<Option<Result<ColorReplace, String>>> {
+    let (sender, receiver) = watch::channel(None);
+
+    std::thread::spawn(move || {
+        let load = || -> anyhow::Result<_> {
+            let image = image::open(&path)?.into_rgba8();
+            let colors = image_colors(&image);
+            let to = to.unwrap_or_else(|| {
+                let color = NES_PALLET[0x0f];
+                [color.r(), color.g(), color.b()]
+            });
+
+            Ok(ColorReplace {
+                path: path.clone(),
+                colors,
+                from: None,
+                to,
+            })
+        };
+
+        sender.send(Some(load().map_err(|e| format!("{:#}", e))));
+    });
+
+    receiver
+}
+
+/// Paint a small square of an RGB color
+fn color_swatch(ui: &mut egui::Ui, [r, g, b]: [u8; 3]) {
+    let (rect, _) = ui.allocate_exact_size(
+        Vec2::splat(ui.spacing().interact_size.y),
+        egui::Sense::hover(),
+    );
+    ui.painter()
+        .rect_filled(rect, 2.0, egui::Color32::from_rgb(r, g, b));
+}
+
+/// Ask the user for a folder, and search it for files with the same names as the missing sources
+/// in the background
+fn find_missing_sources(missing: Vec<(Uid<PathBuf>, PathBuf)>) -> WatchReceiver<Option<Relink>> {
+    let (sender, receiver) = watch::channel(None);
+
+    std::thread::spawn(move || {
+        let _task = BackgroundTask::start("Searching for missing sources");
+        let base_dir = match native_dialog::FileDialog::new()
+            .show_open_single_dir()
+            .expect("File dialog")
+        {
+            Some(dir) => dir,
+            None => return,
+        };
+
+        let mut candidates = HashMap::<OsString, Vec<PathBuf>>::new();
+        for (_, path) in &missing {
+            if let Some(name) = path.file_name() {
+                candidates.insert(name.to_owned(), Vec::new());
+            }
+        }
+        collect_files_named(&base_dir, RELINK_SEARCH_DEPTH, &mut candidates);
+
+        let mut relink = Relink {
+            base_dir,
+            found: Vec::new(),
+            not_found: Vec::new(),
+        };
+        for (id, old_path) in missing {
+            // When more than one file has the same name, pick the one that shares the most
+            // parent folders with the old path
+            let best = old_path
+                .file_name()
+                .and_then(|name| candidates.get(name))
+                .and_then(|paths| {
+                    paths
+                        .iter()
+                        .rev()
+                        .max_by_key(|path| matching_path_suffix(path, &old_path))
+                });
+            match best {
+                Some(new_path) => relink.found.push((id, old_path, new_path.clone())),
+                None => relink.not_found.push(old_path),
+            }
+        }
+
+        sender.send(Some(relink));
+    });
+
+    receiver
+}
+
+/// Recursively find the files in `dir` whose names are keys of `found`, adding their paths to it.
+/// Hidden folders are skipped.
+fn collect_files_named(dir: &Path, depth: usize, found: &mut HashMap<OsString, Vec<PathBuf>>) {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    let mut entries = entries.flatten().map(|x| x.path()).collect::<Vec<_>>();
+    entries.sort();
+
+    for path in entries {
+        let name = match path.file_name() {
+            Some(name) => name.to_owned(),
+            None => continue,
+        };
+        if path.is_dir() {
+            if depth > 0 && !name.to_string_lossy().starts_with('.') {
+                collect_files_named(&path, depth - 1, found);
+            }
+        } else if let Some(paths) = found.get_mut(&name) {
+            paths.push(path);
+        }
+    }
+}
+
+/// Count how many of the trailing file and folder names of two paths are the same
+fn matching_path_suffix(a: &Path, b: &Path) -> usize {
+    let names = |path: &Path| {
+        path.components()
+            .filter_map(|x| match x {
+                Component::Normal(name) => Some(name.to_owned()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+    names(a)
+        .iter()
+        .rev()
+        .zip(names(b).iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count()
+}
+
+/// Ask the user for the folder to move the selected sources to
+fn browse_for_folder() -> WatchReceiver<Option<PathBuf>> {
+    let (sender, receiver) = watch::channel(None);
+
+    std::thread::spawn(move || {
+        let _task = BackgroundTask::start("Choosing a source folder");
+        let dir = native_dialog::FileDialog::new()
+            .show_open_single_dir()
+            .expect("File dialog");
+
+        sender.send(dir);
+    });
+
+    receiver
+}
+
+/// Point the sources `ids` into `dir`, keeping the folders they have below the deepest folder
+/// that contains all of them. The paths are all changed in the same frame, so the move is undone
+/// in one step.
+fn rebase_sources(
+    ctx: &egui::Context,
+    project: &mut ProjectState,
+    ids: &[Uid<PathBuf>],
+    dir: &Path,
+) {
+    let paths = ids
+        .iter()
+        .filter_map(|id| {
+            let path = project.data.sources.get(id)?;
+            Some((*id, Project::source_path(&project.path, path)))
+        })
+        .collect::<Vec<_>>();
+
+    // The deepest folder shared by every source
+    let mut base = match paths.first().and_then(|(_, path)| path.parent()) {
+        Some(parent) => parent.to_path_buf(),
+        None => return,
+    };
+    for (_, path) in &paths {
+        while !path.starts_with(&base) {
+            if !base.pop() {
+                break;
+            }
+        }
+    }
+
+    let mut missing = 0;
+    for (id, path) in &paths {
+        let new_path = dir.join(path.strip_prefix(&base).unwrap_or(path));
+        if !new_path.exists() {
+            missing += 1;
+        }
+        project.update_source(*id, new_path);
+    }
+
+    send_info_notification(
+        ctx,
+        &format!("Moved {} sources to {}", paths.len(), dir.display()),
+    );
+    if missing > 0 {
+        send_error_notification(
+            ctx,
+            format!(
+                "{} of the moved sources aren't in {}, use 🔗 Relink Sources to find them",
+                missing,
+                dir.display()
+            ),
+        );
+    }
 }
 
 fn browse_for_image_path() -> WatchReceiver<Option<PathBuf>> {
     let (path_sender, path_receiver) = watch::channel(None);
 
     std::thread::spawn(move || {
+        let _task = BackgroundTask::start("Choosing a source image");
         let path = native_dialog::FileDialog::new()
-            .add_filter("PNG Image", &["png"])
+            .add_filter("Image", &["png", "bmp", "gif"])
             .show_open_single_file()
             .expect("File dialog");
 
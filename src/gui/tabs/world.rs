@@ -0,0 +1,464 @@
+use std::collections::HashMap;
+
+use egui::DragValue;
+
+use crate::{
+    gui::{
+        accessibility,
+        components::{MetatileGui, MetatileKind},
+        preview_pallet::preview_color,
+        ProjectState,
+    },
+    project::{Level, WorldPlacement},
+    Uid,
+};
+
+use super::{CanvasView, NesimgGuiTab, ProjectLayout};
+
+/// The size of one screen in NES pixels
+const SCREEN_SIZE: egui::Vec2 = egui::Vec2::new(256.0, 240.0);
+
+/// The color used to highlight screens covered by more than one level
+const OVERLAP_COLOR: egui::Color32 = egui::Color32::from_rgb(220, 70, 70);
+
+pub struct WorldTab {
+    zoom: f32,
+    pan: egui::Vec2,
+    current_level: Option<Uid<Level>>,
+    /// The level being dragged, and how far it has been dragged in points
+    dragging: Option<(Uid<Level>, egui::Vec2)>,
+    /// Whether to draw the level tiles, which can be slow when zoomed out on large worlds
+    show_tiles: bool,
+}
+
+impl Default for WorldTab {
+    fn default() -> Self {
+        Self {
+            zoom: 0.25,
+            pan: egui::Vec2::ZERO,
+            current_level: None,
+            dragging: None,
+            show_tiles: true,
+        }
+    }
+}
+
+impl NesimgGuiTab for WorldTab {
+    fn show(&mut self, project: &mut ProjectState, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        // Forget placements of levels that have been deleted
+        let levels = &project.data.levels;
+        project
+            .data
+            .world_layout
+            .retain(|id, _| levels.contains_key(id));
+
+        let central_frame = egui::Frame {
+            fill: ctx.style().visuals.window_fill(),
+            ..Default::default()
+        };
+        egui::CentralPanel::default()
+            .frame(central_frame)
+            .show(ctx, |ui| {
+                let default_spacing = ui.spacing().item_spacing;
+
+                // Avoid putting space between panels
+                ui.spacing_mut().item_spacing = egui::Vec2::default();
+
+                egui::TopBottomPanel::top("world_toolbar").show_inside(ui, |ui| {
+                    ui.spacing_mut().item_spacing = default_spacing;
+
+                    ui.with_layout(egui::Layout::right_to_left(), |ui| {
+                        if ui.button("Reset View").clicked() {
+                            self.zoom = 0.25;
+                            self.pan = egui::Vec2::ZERO;
+                        }
+                        ui.add_space(10.0);
+                        ui.monospace(format!("Zoom: {:>5.2}", self.zoom));
+                        ui.add_space(10.0);
+                        ui.toggle_value(&mut self.show_tiles, "▦ Tiles")
+                            .on_hover_text("Draw the level tiles instead of just their outlines");
+                    });
+                });
+
+                let sidebar_frame = egui::Frame {
+                    inner_margin: egui::style::Margin::symmetric(8.0, 0.0),
+                    fill: ctx.style().visuals.window_fill(),
+                    stroke: ctx.style().visuals.window_stroke(),
+                    ..Default::default()
+                };
+                egui::SidePanel::left("world_sidebar")
+                    .frame(sidebar_frame)
+                    .min_width(150.0)
+                    .max_width(400.0)
+                    .show_inside(ui, |ui| {
+                        ui.spacing_mut().item_spacing = default_spacing;
+                        self.sidebar_gui(project, ui);
+                    });
+
+                // Reset the spacing to the default after adding the panels
+                ui.spacing_mut().item_spacing = default_spacing;
+
+                egui::CentralPanel::default()
+                    .frame(egui::Frame::canvas(&ctx.style()))
+                    .show_inside(ui, |ui| {
+                        self.world_canvas_gui(project, ui, frame);
+                    });
+            });
+    }
+
+    fn help_text(&self) -> &'static str {
+        include_str!("./world_help.txt")
+    }
+
+    fn tooltip(&self) -> &'static str {
+        "Arrange levels into the game world"
+    }
+
+    fn save_layout(&self, layout: &mut ProjectLayout) {
+        layout.world_view = Some(CanvasView {
+            zoom: self.zoom,
+            pan: [self.pan.x, self.pan.y],
+        });
+        layout.world_level = self.current_level;
+    }
+
+    fn load_layout(&mut self, layout: &ProjectLayout) {
+        if let Some(view) = layout.world_view {
+            self.zoom = view.zoom;
+            self.pan = view.pan.into();
+        }
+        self.current_level = layout.world_level;
+    }
+}
+
+impl WorldTab {
+    fn sidebar_gui(&mut self, project: &mut ProjectState, ui: &mut egui::Ui) {
+        ui.add_space(ui.spacing().window_margin.top);
+        ui.horizontal(|ui| {
+            ui.set_height(ui.spacing().interact_size.y);
+            ui.label("Levels");
+        });
+        ui.separator();
+
+        if project.data.levels.is_empty() {
+            ui.label("Create levels in the Maps tab to place them in the world.");
+            return;
+        }
+
+        let data = &mut project.data;
+        for (id, level) in &data.levels {
+            ui.horizontal(|ui| {
+                let mut placed = data.world_layout.contains_key(id);
+                if ui
+                    .checkbox(&mut placed, "")
+                    .on_hover_text("Place the level in the world")
+                    .changed()
+                {
+                    if placed {
+                        // Put the level to the right of everything else
+                        let x = data
+                            .world_layout
+                            .iter()
+                            .filter_map(|(id, placement)| {
+                                Some(placement.x + data.levels.get(id)?.screen_size().0)
+                            })
+                            .max()
+                            .unwrap_or(0);
+                        data.world_layout.insert(*id, WorldPlacement { x, y: 0 });
+                    } else {
+                        data.world_layout.shift_remove(id);
+                    }
+                }
+
+                let name = if level.name.is_empty() {
+                    "Untitled"
+                } else {
+                    &level.name
+                };
+                if ui
+                    .selectable_label(self.current_level == Some(*id), name)
+                    .clicked()
+                {
+                    self.current_level = Some(*id);
+                }
+            });
+        }
+
+        let current = self
+            .current_level
+            .and_then(|id| Some((data.levels.get(&id)?, data.world_layout.get_mut(&id)?)));
+        if let Some((level, placement)) = current {
+            ui.separator();
+            let (screens_wide, screens_high) = level.screen_size();
+            ui.label(format!("Size: {}x{} screens", screens_wide, screens_high));
+            ui.horizontal(|ui| {
+                ui.label("X");
+                ui.add(DragValue::new(&mut placement.x).speed(0.1));
+                ui.label("Y");
+                ui.add(DragValue::new(&mut placement.y).speed(0.1));
+            });
+        }
+    }
+
+    fn world_canvas_gui(
+        &mut self,
+        project: &mut ProjectState,
+        ui: &mut egui::Ui,
+        frame: &mut eframe::Frame,
+    ) {
+        let canvas_rect = ui.max_rect();
+        let canvas_center = canvas_rect.center();
+        ui.set_clip_rect(canvas_rect);
+
+        let mut response = ui.interact(
+            canvas_rect,
+            egui::Id::new("world_canvas"),
+            egui::Sense::click_and_drag(),
+        );
+        accessibility::canvas(&response, || {
+            let level = self
+                .current_level
+                .and_then(|id| project.data.levels.get(&id))
+                .map_or("no level selected", |x| x.name.as_str());
+            format!(
+                "World canvas, {} levels, {}",
+                project.data.world_layout.len(),
+                level
+            )
+        });
+
+        // Handle zoom
+        if response.hovered() {
+            self.zoom *= 1.0 + ui.input().scroll_delta.y / 500.0;
+            self.zoom = self.zoom.clamp(0.02, 4.0);
+        }
+
+        // Handle pan
+        let panning =
+            response.dragged_by(egui::PointerButton::Middle) || ui.input().modifiers.command;
+        if panning {
+            self.pan += response.drag_delta();
+            response = response.on_hover_cursor(egui::CursorIcon::Grabbing);
+        }
+
+        let screen_size = SCREEN_SIZE * self.zoom;
+        let origin = canvas_center + self.pan;
+        let screen_rect = |x: i32, y: i32, width: i32, height: i32| {
+            egui::Rect::from_min_size(
+                origin + egui::Vec2::new(x as f32 * screen_size.x, y as f32 * screen_size.y),
+                egui::Vec2::new(width as f32 * screen_size.x, height as f32 * screen_size.y),
+            )
+        };
+
+        // Move the dragged level, snapping it to the screen grid
+        if let Some((_, offset)) = &mut self.dragging {
+            *offset += response.drag_delta();
+        }
+        let dragged_screens = |offset: egui::Vec2| {
+            (
+                (offset.x / screen_size.x).round() as i32,
+                (offset.y / screen_size.y).round() as i32,
+            )
+        };
+
+        // Paint the screen grid, when the screens are big enough to see it
+        let painter = ui.painter();
+        if screen_size.x > 8.0 {
+            let stroke = egui::Stroke::new(1.0, ui.visuals().faint_bg_color);
+            let first = ((canvas_rect.min - origin) / screen_size).floor();
+            let last = ((canvas_rect.max - origin) / screen_size).ceil();
+            for x in first.x as i32..=last.x as i32 {
+                painter.vline(
+                    origin.x + x as f32 * screen_size.x,
+                    canvas_rect.y_range(),
+                    stroke,
+                );
+            }
+            for y in first.y as i32..=last.y as i32 {
+                painter.hline(
+                    canvas_rect.x_range(),
+                    origin.y + y as f32 * screen_size.y,
+                    stroke,
+                );
+            }
+        }
+
+        let pointer_pos = ui.input().pointer.interact_pos();
+        let placements = project
+            .data
+            .world_layout
+            .iter()
+            .map(|(id, placement)| (*id, *placement))
+            .collect::<Vec<_>>();
+
+        let mut screen_counts = HashMap::<(i32, i32), u32>::new();
+        for (id, placement) in placements {
+            let mut pos = (placement.x, placement.y);
+            if let Some((dragged_id, offset)) = self.dragging {
+                if dragged_id == id {
+                    let (x, y) = dragged_screens(offset);
+                    pos = (pos.0 + x, pos.1 + y);
+                }
+            }
+
+            let level = &project.data.levels[&id];
+            let (screens_wide, screens_high) = level.screen_size();
+            let level_rect = screen_rect(pos.0, pos.1, screens_wide, screens_high);
+            for y in 0..screens_high {
+                for x in 0..screens_wide {
+                    *screen_counts.entry((pos.0 + x, pos.1 + y)).or_default() += 1;
+                }
+            }
+
+            if ui.clip_rect().intersects(level_rect) {
+                self.paint_level(project, ui, frame, id, level_rect);
+            }
+
+            // Select and start dragging levels
+            let pointer_within_level = pointer_pos.map(|x| level_rect.contains(x)).unwrap_or(false);
+            if pointer_within_level && !panning {
+                if response.clicked_by(egui::PointerButton::Primary) {
+                    self.current_level = Some(id);
+                }
+                if response.drag_started() && response.dragged_by(egui::PointerButton::Primary) {
+                    self.current_level = Some(id);
+                    self.dragging = Some((id, egui::Vec2::ZERO));
+                }
+                response = response.on_hover_cursor(egui::CursorIcon::Grab);
+            }
+        }
+
+        // Highlight screens covered by more than one level
+        for ((x, y), count) in screen_counts {
+            if count > 1 {
+                let rect = screen_rect(x, y, 1, 1);
+                ui.painter()
+                    .rect_filled(rect, 0.0, OVERLAP_COLOR.linear_multiply(0.4));
+                ui.painter().rect_stroke(rect, 0.0, (2.0, OVERLAP_COLOR));
+            }
+        }
+
+        accessibility::paint_focus(ui, &response, 0.0);
+
+        // Drop the dragged level when the mouse is released
+        if !response.dragged() {
+            if let Some((id, offset)) = self.dragging.take() {
+                let (x, y) = dragged_screens(offset);
+                if let Some(placement) = project.data.world_layout.get_mut(&id) {
+                    placement.x += x;
+                    placement.y += y;
+                }
+            }
+        }
+    }
+
+    /// Paint a level in the world, with its tiles, screen borders, and name
+    fn paint_level(
+        &self,
+        project: &mut ProjectState,
+        ui: &mut egui::Ui,
+        frame: &mut eframe::Frame,
+        level_id: Uid<Level>,
+        level_rect: egui::Rect,
+    ) {
+        let level = &project.data.levels[&level_id];
+        let margin = level.margin;
+        let metatileset_id = level.metatileset_id;
+        let (screens_wide, screens_high) = level.screen_size();
+
+        let background_color = project
+            .data
+            .metatilesets
+            .get(&metatileset_id)
+            .map(|x| preview_color(x.pallet.colors[0]))
+            .unwrap_or_else(|| ui.visuals().extreme_bg_color);
+        ui.painter().rect_filled(level_rect, 0.0, background_color);
+
+        if self.show_tiles {
+            let tile_size = 16.0 * self.zoom;
+            // Only look at the chunks of the level that are in view
+            let visible = ui.clip_rect().intersect(level_rect);
+            let visible_min = ((visible.min - level_rect.min) / tile_size).floor();
+            let visible_max = ((visible.max - level_rect.min) / tile_size).ceil();
+            let tiles = level
+                .tiles
+                .in_rect(
+                    (
+                        (visible_min.x as i32 - margin.left).max(-margin.left),
+                        (visible_min.y as i32 - margin.top).max(-margin.top),
+                    ),
+                    (
+                        (visible_max.x as i32 - margin.left).min(margin.right),
+                        (visible_max.y as i32 - margin.top).min(margin.bottom),
+                    ),
+                )
+                .map(|((x, y), tile)| {
+                    (
+                        egui::Rect::from_min_size(
+                            level_rect.min
+                                + egui::Vec2::new(
+                                    (x + margin.left) as f32,
+                                    (y + margin.top) as f32,
+                                ) * tile_size,
+                            egui::Vec2::splat(tile_size),
+                        ),
+                        tile.metatileset_tile_id,
+                    )
+                })
+                .filter(|(rect, _)| ui.clip_rect().intersects(*rect))
+                .collect::<Vec<_>>();
+
+            for (tile_rect, metatileset_tile_id) in tiles {
+                MetatileGui::new(
+                    project,
+                    MetatileKind::Metatileset {
+                        metatileset_id,
+                        metatileset_tile_id,
+                    },
+                )
+                .paint_at(tile_rect, ui, frame);
+            }
+        }
+
+        let level = &project.data.levels[&level_id];
+        let painter = ui.painter();
+
+        // Mark the screen boundaries inside of the level
+        let screen_stroke = egui::Stroke::new(1.0, egui::Color32::from_white_alpha(60));
+        for x in 1..screens_wide {
+            painter.vline(
+                level_rect.min.x + level_rect.width() * x as f32 / screens_wide as f32,
+                level_rect.y_range(),
+                screen_stroke,
+            );
+        }
+        for y in 1..screens_high {
+            painter.hline(
+                level_rect.x_range(),
+                level_rect.min.y + level_rect.height() * y as f32 / screens_high as f32,
+                screen_stroke,
+            );
+        }
+
+        painter.rect_stroke(
+            level_rect,
+            0.0,
+            if self.current_level == Some(level_id) {
+                ui.style().visuals.widgets.active.fg_stroke
+            } else {
+                ui.style().visuals.widgets.inactive.fg_stroke
+            },
+        );
+        painter.text(
+            level_rect.left_top() + egui::Vec2::splat(4.0),
+            egui::Align2::LEFT_TOP,
+            if level.name.is_empty() {
+                "Untitled"
+            } else {
+                &level.name
+            },
+            egui::FontId::monospace(14.0),
+            ui.visuals().strong_text_color(),
+        );
+    }
+}
@@ -0,0 +1,401 @@
+//! The guided tour that walks new users through making their first map on an example project
+
+use std::{collections::HashMap, path::PathBuf};
+
+use anyhow::Context;
+use egui::{Align2, Color32, Id, LayerId, Order, Rect, Stroke};
+
+use crate::{
+    mockup::{tile_sheet, TilePixels},
+    project::{
+        Level, LevelMargin, LevelTile, Metatile, Metatileset, MetatilesetTile, Project, Tile,
+    },
+    Uid,
+};
+
+use super::{
+    components::send_error_notification,
+    project_state::{LoadedProject, ProjectState},
+    RootState,
+};
+
+/// The color of the outline drawn around the part of the window the tour is pointing at
+const HIGHLIGHT_COLOR: Color32 = Color32::from_rgb(255, 200, 40);
+
+/// The steps of the tour, in order
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum TourStep {
+    Tabs,
+    AddSource,
+    MakeMetatile,
+    BuildMetatileset,
+    PaintMap,
+    Finished,
+}
+
+impl TourStep {
+    const ALL: [TourStep; 6] = [
+        TourStep::Tabs,
+        TourStep::AddSource,
+        TourStep::MakeMetatile,
+        TourStep::BuildMetatileset,
+        TourStep::PaintMap,
+        TourStep::Finished,
+    ];
+
+    fn title(&self) -> &'static str {
+        match self {
+            TourStep::Tabs => "The Tabs",
+            TourStep::AddSource => "Add a Source Image",
+            TourStep::MakeMetatile => "Make a Metatile",
+            TourStep::BuildMetatileset => "Build a Metatileset",
+            TourStep::PaintMap => "Paint a Map",
+            TourStep::Finished => "All Done!",
+        }
+    }
+
+    fn text(&self) -> &'static str {
+        match self {
+            TourStep::Tabs => {
+                "NESImg is split into tabs, which are worked through from right to left: \
+                source images are cut into metatiles, metatiles are colored in metatilesets, \
+                and metatilesets are used to paint maps. The World tab shows how the maps fit \
+                together."
+            }
+            TourStep::AddSource => {
+                "Source images hold the 8x8 tiles that everything is built from. Open the \
+                Sources tab, click \"➕ Add Source\", and pick `example_tiles.png` from the \
+                tour's project folder."
+            }
+            TourStep::MakeMetatile => {
+                "Metatiles are 16x16 squares of four tiles. Open the Metatiles tab, select the \
+                example source in the sidebar, and drag over the tiles in the image to turn \
+                them into metatiles."
+            }
+            TourStep::BuildMetatileset => {
+                "Metatilesets pick the metatiles and colors used by a level. Open the \
+                Metatilesets tab, create a metatileset with ➕, and double-click metatiles in \
+                the \"Available Metatiles\" sidebar to add them."
+            }
+            TourStep::PaintMap => {
+                "Open the Maps tab and right-click the canvas to create a level. Pick the \
+                metatileset in the sidebar, select a metatile, and click or drag on the level \
+                to paint it."
+            }
+            TourStep::Finished => {
+                "That's the whole workflow! The help panel at the bottom of the window explains \
+                each tab in more detail. When you're ready, create or open your own project \
+                from the File menu. The tour can be started again from the Help menu."
+            }
+        }
+    }
+
+    /// The tab the step takes place in
+    fn tab(&self) -> Option<&'static str> {
+        match self {
+            TourStep::Tabs | TourStep::Finished => None,
+            TourStep::AddSource => Some("Sources"),
+            TourStep::MakeMetatile => Some("Metatiles"),
+            TourStep::BuildMetatileset => Some("Metatilesets"),
+            TourStep::PaintMap => Some("Maps"),
+        }
+    }
+
+    /// Whether the task for the step has been done, so the tour can move on
+    fn is_done(&self, project: &Project) -> bool {
+        match self {
+            TourStep::Tabs | TourStep::Finished => true,
+            TourStep::AddSource => !project.sources.is_empty(),
+            TourStep::MakeMetatile => !project.metatiles.is_empty(),
+            TourStep::BuildMetatileset => {
+                project.metatilesets.values().any(|x| !x.tiles.is_empty())
+            }
+            TourStep::PaintMap => project.levels.values().any(|x| !x.tiles.is_empty()),
+        }
+    }
+}
+
+/// The state of the guided tour
+#[derive(Default)]
+pub struct Tour {
+    /// The step being shown, if the tour is running
+    step: Option<TourStep>,
+    /// The example project created for the tour
+    project_path: Option<PathBuf>,
+}
+
+impl Tour {
+    /// Create the example project and start the tour, closing any open project once it loads
+    pub fn start(&mut self, ctx: &egui::Context, state: &mut RootState) {
+        match create_example_project() {
+            Ok(loaded) => {
+                self.project_path = Some(loaded.path.clone());
+                self.step = Some(TourStep::Tabs);
+                state.loaded_project = watch::channel(Some(loaded)).1;
+            }
+            Err(e) => send_error_notification(ctx, format!("{:#}", e)),
+        }
+    }
+
+    /// Render the tour window and highlight the part of the window it is pointing at
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        state: &mut RootState,
+        current_tab: &str,
+        tab_rects: &HashMap<String, Rect>,
+    ) {
+        let step = match self.step {
+            Some(step) => step,
+            None => return,
+        };
+        let project = match &mut state.project {
+            Some(project) if Some(&project.path) == self.project_path.as_ref() => project,
+            // The example project hasn't loaded yet
+            None => return,
+            // Another project was opened, so the tour is over
+            Some(_) => {
+                self.step = None;
+                return;
+            }
+        };
+        let step_idx = TourStep::ALL
+            .iter()
+            .position(|x| *x == step)
+            .expect("Step in list");
+        let done = step.is_done(&project.data);
+
+        // Point at the tab the step takes place in, or at all of the tabs
+        let highlight = match step.tab() {
+            Some(tab) if tab != current_tab => tab_rects.get(tab).copied(),
+            Some(_) => None,
+            None if step == TourStep::Tabs => tab_rects.values().copied().reduce(Rect::union),
+            None => None,
+        };
+        if let Some(rect) = highlight {
+            let pulse = (ctx.input().time * 4.0).sin() as f32 * 0.5 + 0.5;
+            ctx.layer_painter(LayerId::new(Order::Foreground, Id::new("tour_highlight")))
+                .rect_stroke(
+                    rect.expand(3.0),
+                    3.0,
+                    Stroke::new(2.0 + pulse, HIGHLIGHT_COLOR),
+                );
+            ctx.request_repaint();
+        }
+
+        let mut open = true;
+        let mut next_step = Some(step);
+        egui::Window::new("🎓 Tour")
+            .open(&mut open)
+            .resizable(false)
+            .collapsible(false)
+            .anchor(Align2::RIGHT_TOP, [-10.0, 40.0])
+            .default_width(280.0)
+            .show(ctx, |ui| {
+                ui.label(format!("Step {} of {}", step_idx + 1, TourStep::ALL.len()));
+                ui.heading(step.title());
+                ui.label(step.text());
+                if step == TourStep::AddSource {
+                    if let Some(dir) = project.path.parent() {
+                        ui.label(format!("The project folder is `{}`", dir.display()));
+                    }
+                }
+
+                if step.tab().is_some() {
+                    ui.add_space(4.0);
+                    if done {
+                        ui.colored_label(Color32::GREEN, "✔ Done!");
+                    } else if ui
+                        .button("✨ Do It for Me")
+                        .on_hover_text("Skip ahead by doing this step automatically")
+                        .clicked()
+                    {
+                        do_step(step, project);
+                    }
+                }
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(step_idx > 0, egui::Button::new("⏴ Back"))
+                        .clicked()
+                    {
+                        next_step = Some(TourStep::ALL[step_idx - 1]);
+                    }
+                    if step == TourStep::Finished {
+                        if ui.button("✔ Finish").clicked() {
+                            next_step = None;
+                        }
+                    } else if ui
+                        .add_enabled(done, egui::Button::new("Next ⏵"))
+                        .on_disabled_hover_text("Finish this step to continue")
+                        .clicked()
+                    {
+                        next_step = Some(TourStep::ALL[step_idx + 1]);
+                    }
+                });
+            });
+
+        self.step = next_step.filter(|_| open);
+        if self.step.is_none() {
+            // Close the example project
+            state.close_project();
+        }
+    }
+}
+
+/// Do the task for a step of the tour, for users who want to skip ahead
+fn do_step(step: TourStep, project: &mut ProjectState) {
+    match step {
+        TourStep::Tabs | TourStep::Finished => (),
+        TourStep::AddSource => {
+            let path = project.path.with_file_name(EXAMPLE_SOURCE_NAME);
+            project.add_source(path);
+        }
+        TourStep::MakeMetatile => {
+            let source_id = match project.data.sources.keys().next() {
+                Some(id) => *id,
+                None => {
+                    do_step(TourStep::AddSource, project);
+                    *project.data.sources.keys().next().expect("Source added")
+                }
+            };
+            for tiles in EXAMPLE_METATILES {
+                project.data.metatiles.insert(
+                    Uid::new(),
+                    Metatile {
+                        tiles: tiles.map(|idx| {
+                            Some(Tile {
+                                source_id,
+                                x: idx,
+                                y: 0,
+                            })
+                        }),
+                    },
+                );
+            }
+        }
+        TourStep::BuildMetatileset => {
+            if project.data.metatiles.is_empty() {
+                do_step(TourStep::MakeMetatile, project);
+            }
+            let data = &mut project.data;
+            if data.metatilesets.is_empty() {
+                data.metatilesets.insert(
+                    Uid::new(),
+                    Metatileset {
+                        name: "Example".into(),
+                        ..Default::default()
+                    },
+                );
+            }
+            let metatileset = data.metatilesets.values_mut().next().expect("Metatileset");
+            for metatile_id in data.metatiles.keys() {
+                metatileset.tiles.insert(
+                    Uid::new(),
+                    MetatilesetTile {
+                        metatile_id: *metatile_id,
+                        sub_pallet_idx: 0,
+                        properties: 0,
+                    },
+                );
+            }
+        }
+        TourStep::PaintMap => {
+            if !TourStep::BuildMetatileset.is_done(&project.data) {
+                do_step(TourStep::BuildMetatileset, project);
+            }
+            let data = &mut project.data;
+            let (metatileset_id, metatileset) = data
+                .metatilesets
+                .iter()
+                .find(|(_, x)| !x.tiles.is_empty())
+                .expect("Metatileset with tiles");
+            let tile_ids = metatileset.tiles.keys().copied().collect::<Vec<_>>();
+            let tile = |idx: usize| LevelTile {
+                metatileset_tile_id: tile_ids[idx.min(tile_ids.len() - 1)],
+            };
+
+            let mut level = Level {
+                name: "Example Level".into(),
+                metatileset_id: *metatileset_id,
+                margin: LevelMargin {
+                    top: 0,
+                    right: 16,
+                    bottom: 15,
+                    left: 0,
+                },
+                ..Default::default()
+            };
+            for y in 0..15 {
+                for x in 0..16 {
+                    let idx = match (x, y) {
+                        (_, 12) => 1,
+                        (_, 13..=14) => 3,
+                        (5..=9, 8) => 2,
+                        _ => 0,
+                    };
+                    level.tiles.insert((x, y), tile(idx));
+                }
+            }
+            data.levels.insert(Uid::new(), level);
+        }
+    }
+}
+
+/// The file name of the example tile sheet written next to the tour project
+const EXAMPLE_SOURCE_NAME: &str = "example_tiles.png";
+
+/// The metatiles made from the example tiles by [`do_step`]: sky, ground, bricks, and dirt, as
+/// the indexes of the top-left, top-right, bottom-left, and bottom-right tiles in the example sheet
+const EXAMPLE_METATILES: [[u16; 4]; 4] = [[0, 0, 0, 0], [1, 1, 2, 2], [3, 3, 3, 3], [2, 2, 2, 2]];
+
+/// Draw the tiles of the example tile sheet: sky, grass, dirt, and bricks
+fn example_tiles() -> Vec<TilePixels> {
+    let tile = |pixel: fn(usize, usize) -> u8| {
+        let mut pixels = [0; 64];
+        for (i, x) in pixels.iter_mut().enumerate() {
+            *x = pixel(i % 8, i / 8);
+        }
+        pixels
+    };
+    vec![
+        tile(|_, _| 0),
+        tile(|x, y| match y {
+            0 if x % 3 == 0 => 0,
+            0..=2 => 2,
+            _ => 1,
+        }),
+        tile(|x, y| if (x + y * 3) % 5 == 0 { 3 } else { 1 }),
+        tile(|x, y| {
+            let offset = if y < 4 { 0 } else { 4 };
+            if y % 4 == 3 || (x + offset) % 8 == 0 {
+                1
+            } else {
+                3
+            }
+        }),
+    ]
+}
+
+/// Write the example tile sheet and an empty project to a temporary folder
+fn create_example_project() -> anyhow::Result<LoadedProject> {
+    let dir = std::env::temp_dir().join("nesimg-tour");
+    std::fs::create_dir_all(&dir).context("Create tour project folder")?;
+
+    let (sheet, _) = tile_sheet(&example_tiles());
+    let sheet_path = dir.join(EXAMPLE_SOURCE_NAME);
+    sheet
+        .save(&sheet_path)
+        .with_context(|| format!("Could not save `{}`", sheet_path.display()))?;
+
+    let path = dir.join("tour.nesimg");
+    let data = Project::default();
+    std::fs::write(
+        &path,
+        ron::ser::to_string_pretty(&data, Default::default()).context("Serialize project")?,
+    )
+    .with_context(|| format!("Could not save `{}`", path.display()))?;
+
+    Ok(LoadedProject { data, path })
+}
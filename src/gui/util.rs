@@ -1,13 +1,16 @@
-use std::{collections::HashSet, io::Read, path::Path, sync::Arc};
+use std::{path::Path, sync::Arc};
 
-use egui::Color32;
-use egui_extras::RetainedImage;
-use image::GenericImageView;
 use native_dialog::FileDialog;
 use notify::Watcher;
 use watch::WatchReceiver;
 
-use super::project_state::{SourceImageData, SourceImageStatus};
+use crate::source::IndexedImage;
+
+use super::{
+    background::BackgroundTask,
+    project_state::{SourceImageData, SourceImageStatus},
+    source_texture::SourceTexture,
+};
 
 /// Ask the user to pick a file, and then optionally watch it for changes
 pub fn pick_file<F, R>(filters: &'static [FileFilter], load_fn: F) -> WatchReceiver<R>
@@ -18,6 +21,7 @@ where
     let (sender, receiver) = watch::channel(R::default());
 
     std::thread::spawn(move || {
+        let _task = BackgroundTask::start("Opening a file");
         let mut dialog = FileDialog::new();
 
         for filter in filters {
@@ -42,79 +46,24 @@ pub struct FileFilter {
     pub extensions: &'static [&'static str],
 }
 
-/// The four colors used to represent the different pallets internally in the source image
-static GRAYSCALE_COLORS: [Color32; 4] = [
-    Color32::from_rgb(0, 0, 0),
-    Color32::from_rgb(85, 85, 85),
-    Color32::from_rgb(170, 170, 170),
-    Color32::from_rgb(255, 255, 255),
-];
-
-/// Load an image and watch for changes
-pub fn load_and_watch_image(path: &Path) -> WatchReceiver<SourceImageStatus> {
+/// Load an image and watch for changes, see [`IndexedImage::from_bytes`] for `keep_pallet_order`
+pub fn load_and_watch_image(
+    path: &Path,
+    keep_pallet_order: bool,
+) -> WatchReceiver<SourceImageStatus> {
     let path = path.to_owned();
     let (sender, receiver) = watch::channel(SourceImageStatus::Loading);
 
     std::thread::spawn(move || {
         let load_texture = || -> anyhow::Result<_> {
-            let mut file = std::fs::OpenOptions::new().read(true).open(&path)?;
-            let mut bytes = Vec::new();
-            file.read_to_end(&mut bytes)?;
-
-            let image = image::load_from_memory(&bytes)?;
-
-            if image.width() % 16 != 0 || image.height() % 16 != 0 {
-                anyhow::bail!("Image width and height must be a multiple of 16");
-            }
-
-            let mut colors = HashSet::new();
-
-            for (_, _, pixel) in image.pixels() {
-                colors.insert(pixel);
-            }
-
-            if colors.len() != 4 {
-                anyhow::bail!(
-                    "Image must have only 4 colors, but found {} colors",
-                    colors.len()
-                );
-            }
-
-            // Sort colors by brightness ( or luminocity, I'm not sure what the difference is )
-            let mut colors_sorted = colors.iter().collect::<Vec<_>>();
-            colors_sorted.sort_unstable_by(|x, y| {
-                let x = x[0] as u16 + x[1] as u16 + x[2] as u16;
-                let y = y[0] as u16 + y[1] as u16 + y[2] as u16;
-                x.cmp(&y)
-            });
-
-            let (pixels, indexes) = image
-                .pixels()
-                .map(|(_, _, x)| {
-                    if &x == colors_sorted[0] {
-                        (GRAYSCALE_COLORS[0], 0)
-                    } else if &x == colors_sorted[1] {
-                        (GRAYSCALE_COLORS[1], 1)
-                    } else if &x == colors_sorted[2] {
-                        (GRAYSCALE_COLORS[2], 2)
-                    } else if &x == colors_sorted[3] {
-                        (GRAYSCALE_COLORS[3], 3)
-                    } else {
-                        unreachable!()
-                    }
-                })
-                .unzip();
-
-            let image = egui::ColorImage {
-                size: [image.width() as usize, image.height() as usize],
-                pixels,
-            };
-
-            let texture = RetainedImage::from_color_image("source_image", image)
-                .with_texture_filter(egui::TextureFilter::Nearest);
+            let image = IndexedImage::load(&path, keep_pallet_order)?;
+            let indexes = Arc::new(image.indexes);
 
             Ok(SourceImageData {
-                texture: Arc::new(texture),
+                texture: Arc::new(SourceTexture::new(
+                    [image.width as usize, image.height as usize],
+                    indexes.clone(),
+                )),
                 indexes,
             })
         };
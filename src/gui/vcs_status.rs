@@ -0,0 +1,98 @@
+//! Keeps track of the git status of the open project file
+
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use watch::WatchReceiver;
+
+use crate::vcs::{self, VcsStatus};
+
+/// How often to check the git status of the project file
+const CHECK_INTERVAL: Duration = Duration::from_secs(3);
+
+pub struct VcsIndicator {
+    /// The result of the latest status check
+    receiver: WatchReceiver<VcsStatus>,
+    /// The last status that was received
+    status: VcsStatus,
+    /// When the status was last checked, if it has been checked yet
+    last_check: Option<Instant>,
+}
+
+impl Default for VcsIndicator {
+    fn default() -> Self {
+        Self {
+            receiver: watch::channel(VcsStatus::NotVersioned).1,
+            status: VcsStatus::NotVersioned,
+            last_check: None,
+        }
+    }
+}
+
+impl VcsIndicator {
+    /// Get the latest status, checking it again in the background if it's out of date
+    pub fn status(&mut self, ctx: &egui::Context, project_path: &Path) -> &VcsStatus {
+        if let Some(status) = self.receiver.get_if_new() {
+            self.status = status;
+        }
+
+        let out_of_date = match self.last_check {
+            Some(last_check) => last_check.elapsed() > CHECK_INTERVAL,
+            None => true,
+        };
+        if out_of_date {
+            self.last_check = Some(Instant::now());
+
+            let (sender, receiver) = watch::channel(self.status.clone());
+            self.receiver = receiver;
+
+            let ctx = ctx.clone();
+            let path = project_path.to_owned();
+            std::thread::spawn(move || {
+                sender.send(vcs::file_status(&path));
+                ctx.request_repaint();
+            });
+        }
+
+        &self.status
+    }
+
+    /// Check the status again on the next frame, such as after saving the project
+    pub fn refresh(&mut self) {
+        self.last_check = None;
+    }
+
+    /// Render the branch and status of the project file, if it is in a repository
+    pub fn show(&mut self, ui: &mut egui::Ui, project_path: &Path) {
+        let status = self.status(ui.ctx(), project_path).clone();
+        let branch = if let Some(branch) = status.branch() {
+            branch
+        } else {
+            return;
+        };
+
+        let (text, color, hover) = match &status {
+            VcsStatus::Clean { .. } => (
+                "✔",
+                ui.visuals().text_color(),
+                "The project file is the same as the last commit",
+            ),
+            VcsStatus::Modified { .. } => (
+                "✏",
+                egui::Color32::YELLOW,
+                "The project file has changes that haven't been committed",
+            ),
+            VcsStatus::Untracked { .. } => (
+                "❓",
+                ui.visuals().weak_text_color(),
+                "The project file hasn't been committed to the repository",
+            ),
+            VcsStatus::NotVersioned => return,
+        };
+
+        ui.colored_label(color, format!("⎇ {} {}", branch, text))
+            .on_hover_text(hover);
+    }
+}
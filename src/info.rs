@@ -0,0 +1,131 @@
+//! Summarizing a project for the `info` command, for use in scripts and bug reports
+
+use std::{collections::HashSet, fmt::Write, path::Path};
+
+use serde::Serialize;
+
+use crate::{
+    export::{load_sources, tile_pixels},
+    project::Project,
+};
+
+/// The number of bytes used by each tile in the pattern table
+const CHR_TILE_BYTES: usize = 16;
+
+/// A summary of the contents of a project
+#[derive(Debug, Clone, Serialize)]
+pub struct ProjectInfo {
+    pub sources: usize,
+    pub metatiles: usize,
+    pub metatilesets: usize,
+    pub levels: usize,
+    pub tile_animations: usize,
+    pub export_targets: usize,
+    /// The number of different tiles used by all of the metatiles
+    pub unique_tiles: usize,
+    /// The size of the pattern tables that exporting writes, one for each metatileset
+    pub chr_bytes: usize,
+    /// The details of each metatileset
+    pub metatileset_info: Vec<MetatilesetInfo>,
+    /// The source images that couldn't be loaded, as they are stored in the project
+    pub missing_sources: Vec<String>,
+}
+
+/// A summary of one metatileset
+#[derive(Debug, Clone, Serialize)]
+pub struct MetatilesetInfo {
+    pub name: String,
+    pub metatiles: usize,
+    /// The number of tiles in the metatileset's pattern table
+    pub unique_tiles: usize,
+}
+
+impl ProjectInfo {
+    /// Summarize a project, loading its source images to count the unique tiles
+    pub fn new(project: &Project, project_path: &Path) -> Self {
+        let mut warnings = Vec::new();
+        let sources = load_sources(project, project_path, &mut warnings);
+        let missing_sources = project
+            .sources
+            .iter()
+            .filter(|(id, _)| !sources.contains_key(*id))
+            .map(|(_, path)| path.display().to_string())
+            .collect();
+
+        let mut unique_tiles = HashSet::new();
+        for metatile in project.metatiles.values() {
+            for tile in &metatile.tiles {
+                unique_tiles.insert(tile_pixels(&sources, tile.as_ref()));
+            }
+        }
+
+        let metatileset_info = project
+            .metatilesets
+            .values()
+            .map(|metatileset| {
+                let mut chr = HashSet::new();
+                for metatileset_tile in metatileset.tiles.values() {
+                    let metatile = project.metatiles.get(&metatileset_tile.metatile_id);
+                    for i in 0..4 {
+                        let tile = metatile.and_then(|x| x.tiles[i].as_ref());
+                        chr.insert(tile_pixels(&sources, tile));
+                    }
+                }
+                MetatilesetInfo {
+                    name: metatileset.name.clone(),
+                    metatiles: metatileset.tiles.len(),
+                    unique_tiles: chr.len(),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        Self {
+            sources: project.sources.len(),
+            metatiles: project.metatiles.len(),
+            metatilesets: project.metatilesets.len(),
+            levels: project.levels.len(),
+            tile_animations: project.tile_animations.len(),
+            export_targets: project.export_targets.len(),
+            unique_tiles: unique_tiles.len(),
+            chr_bytes: metatileset_info
+                .iter()
+                .map(|x| x.unique_tiles * CHR_TILE_BYTES)
+                .sum(),
+            metatileset_info,
+            missing_sources,
+        }
+    }
+
+    /// Format the summary as human-readable text
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        let mut line = |label: &str, value: &dyn std::fmt::Display| {
+            writeln!(text, "{:<16} {}", format!("{}:", label), value).unwrap();
+        };
+        line("Sources", &self.sources);
+        line("Metatiles", &self.metatiles);
+        line("Metatilesets", &self.metatilesets);
+        line("Levels", &self.levels);
+        line("Tile animations", &self.tile_animations);
+        line("Export targets", &self.export_targets);
+        line("Unique tiles", &self.unique_tiles);
+        line("CHR bytes", &self.chr_bytes);
+
+        for metatileset in &self.metatileset_info {
+            writeln!(
+                text,
+                "  {}: {} metatiles, {} unique tiles",
+                metatileset.name, metatileset.metatiles, metatileset.unique_tiles
+            )
+            .unwrap();
+        }
+
+        if !self.missing_sources.is_empty() {
+            writeln!(text, "Missing sources:").unwrap();
+            for path in &self.missing_sources {
+                writeln!(text, "  {}", path).unwrap();
+            }
+        }
+        text
+    }
+}
@@ -1,10 +1,26 @@
 #![warn(clippy::all, rust_2018_idioms)]
 #![allow(clippy::reversed_empty_ranges)]
 
+mod asset_pack;
 mod cli;
+mod cluster;
 mod constants;
+mod diff;
+mod dither;
+mod export;
 mod gui;
+mod info;
+mod library;
+mod map_csv;
+mod map_generator;
+mod mockup;
+mod ntsc;
+mod pallet;
 mod project;
+mod savestate;
+mod schema;
+mod source;
+mod vcs;
 
 use std::marker::PhantomData;
 
@@ -0,0 +1,203 @@
+//! Metatile libraries, which save the metatiles of a metatileset along with the tiles they need in
+//! a standalone file, so they can be shared between projects
+//!
+//! Libraries don't refer to any source images. The pixels of each tile are stored in the library,
+//! and importing one lays them out in a new tile sheet next to the project.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use indexmap::IndexSet;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    mockup::TilePixels,
+    project::{Metatile, Metatileset, MetatilesetTile, Pallet, Project, Tile},
+    Uid,
+};
+
+/// The file extension of metatile libraries
+pub const LIBRARY_EXTENSION: &str = "nesimg-tiles";
+
+/// A set of metatiles and the tiles they are made of
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields, default)]
+pub struct MetatileLibrary {
+    /// The name of the metatileset the library was saved from
+    pub name: String,
+    /// The pallet the metatiles are colored with
+    pub pallet: Pallet,
+    /// The sub-pallets beyond the four supported by the NES, like
+    /// [`Metatileset::extra_sub_pallets`]
+    pub extra_sub_pallets: Vec<[u32; 3]>,
+    /// The pixels of each unique tile, as 64 color indexes, `0`-`3`, in row-major order
+    pub tiles: Vec<String>,
+    /// The metatiles, in the order they appear in the metatileset
+    pub metatiles: Vec<LibraryMetatile>,
+}
+
+/// A metatile in a library
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields, default)]
+pub struct LibraryMetatile {
+    /// The indexes into [`MetatileLibrary::tiles`] of the top-left, top-right, bottom-left, and
+    /// bottom-right tiles, or `None` for empty tiles
+    pub tiles: [Option<usize>; 4],
+    /// The sub-pallet used to color the metatile
+    pub sub_pallet_idx: usize,
+}
+
+impl MetatileLibrary {
+    /// Collect the metatiles of a metatileset and the pixels of their tiles, which are read with
+    /// `tile_pixels`
+    pub fn from_metatileset(
+        project: &Project,
+        metatileset: &Metatileset,
+        mut tile_pixels: impl FnMut(&Tile) -> Option<TilePixels>,
+    ) -> anyhow::Result<Self> {
+        let mut tiles = IndexSet::<TilePixels>::new();
+        let mut metatiles = Vec::with_capacity(metatileset.tiles.len());
+        for metatileset_tile in metatileset.tiles.values() {
+            let metatile = project
+                .metatiles
+                .get(&metatileset_tile.metatile_id)
+                .context("Metatileset uses a metatile that isn't in the project")?;
+
+            let mut library_tiles = [None; 4];
+            for (library_tile, tile) in library_tiles.iter_mut().zip(&metatile.tiles) {
+                if let Some(tile) = tile {
+                    let pixels = tile_pixels(tile).with_context(|| {
+                        format!(
+                            "Source image `{}` isn't loaded",
+                            project
+                                .sources
+                                .get(&tile.source_id)
+                                .map(|x| x.display().to_string())
+                                .unwrap_or_default()
+                        )
+                    })?;
+                    *library_tile = Some(tiles.insert_full(pixels).0);
+                }
+            }
+
+            metatiles.push(LibraryMetatile {
+                tiles: library_tiles,
+                sub_pallet_idx: metatileset_tile.sub_pallet_idx,
+            });
+        }
+
+        Ok(Self {
+            name: metatileset.name.clone(),
+            pallet: metatileset.pallet.clone(),
+            extra_sub_pallets: metatileset.extra_sub_pallets.clone(),
+            tiles: tiles.iter().map(encode_tile).collect(),
+            metatiles,
+        })
+    }
+
+    /// Load a library file
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read(path)
+            .with_context(|| format!("Could not read library `{}`", path.display()))?;
+        let library: Self = ron::de::from_bytes(&contents).context("Parse library file")?;
+
+        // Check the tile references up front so importing can't fail halfway through
+        library.tile_pixels()?;
+        for metatile in &library.metatiles {
+            if let Some(idx) = metatile
+                .tiles
+                .iter()
+                .flatten()
+                .find(|&&x| x >= library.tiles.len())
+            {
+                anyhow::bail!(
+                    "Library metatile uses tile {}, but the library only has {} tiles",
+                    idx,
+                    library.tiles.len()
+                );
+            }
+        }
+
+        Ok(library)
+    }
+
+    /// Save the library to a file
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let contents =
+            ron::ser::to_string_pretty(self, Default::default()).context("Serialize library")?;
+
+        std::fs::write(path, contents)
+            .with_context(|| format!("Could not write library `{}`", path.display()))
+    }
+
+    /// Decode the pixels of the library's tiles
+    pub fn tile_pixels(&self) -> anyhow::Result<Vec<TilePixels>> {
+        self.tiles
+            .iter()
+            .enumerate()
+            .map(|(i, tile)| {
+                decode_tile(tile).with_context(|| format!("Library tile {} is invalid", i))
+            })
+            .collect()
+    }
+
+    /// Add the library's metatiles to the project as a new metatileset, given the source image the
+    /// tiles were saved to and the x and y tile index of each tile in it
+    pub fn add_to_project(
+        &self,
+        project: &mut Project,
+        source_id: Uid<PathBuf>,
+        positions: &[(u16, u16)],
+    ) -> Uid<Metatileset> {
+        let mut metatileset = Metatileset {
+            name: self.name.clone(),
+            pallet: self.pallet.clone(),
+            extra_sub_pallets: self.extra_sub_pallets.clone(),
+            ..Default::default()
+        };
+        for library_metatile in &self.metatiles {
+            let metatile_id = Uid::new();
+            project.metatiles.insert(
+                metatile_id,
+                Metatile {
+                    tiles: library_metatile.tiles.map(|idx| {
+                        let (x, y) = positions[idx?];
+                        Some(Tile { source_id, x, y })
+                    }),
+                },
+            );
+            metatileset.tiles.insert(
+                Uid::new(),
+                MetatilesetTile {
+                    metatile_id,
+                    sub_pallet_idx: library_metatile.sub_pallet_idx,
+                    properties: 0,
+                },
+            );
+        }
+
+        let id = Uid::new();
+        project.metatilesets.insert(id, metatileset);
+        id
+    }
+}
+
+/// Write the pixels of a tile as a string of color indexes
+fn encode_tile(pixels: &TilePixels) -> String {
+    pixels.iter().map(|x| char::from(b'0' + x)).collect()
+}
+
+/// Read the pixels of a tile from a string of color indexes
+fn decode_tile(text: &str) -> anyhow::Result<TilePixels> {
+    let mut pixels = [0; 64];
+    if text.len() != pixels.len() {
+        anyhow::bail!("Expected 64 pixels, found {}", text.len());
+    }
+    for (pixel, c) in pixels.iter_mut().zip(text.bytes()) {
+        *pixel = match c {
+            b'0'..=b'3' => c - b'0',
+            _ => anyhow::bail!("Pixels must be color indexes 0-3, found `{}`", c as char),
+        };
+    }
+    Ok(pixels)
+}
@@ -0,0 +1,168 @@
+//! Reading and writing level maps as CSV and TSV files, for bulk edits in spreadsheets and maps
+//! made by scripts
+//!
+//! Each row of the file is a row of the map, from top to bottom, and each cell is the index of a
+//! metatile in the level's metatileset, the same index used in the exported map table, or `-1`
+//! for an empty cell. Blank cells are also read as empty, so cells can be cleared in a spreadsheet
+//! by deleting them.
+
+use std::path::Path;
+
+use anyhow::Context;
+
+use crate::project::{Level, LevelTile, Metatileset};
+
+/// The value written for cells without a metatile
+const EMPTY_CELL: &str = "-1";
+
+/// The delimiter for a map file: tabs for `.tsv` and `.tab` files, and commas for anything else
+pub fn delimiter(path: &Path) -> char {
+    match path.extension().and_then(|x| x.to_str()) {
+        Some(ext) if ext.eq_ignore_ascii_case("tsv") || ext.eq_ignore_ascii_case("tab") => '\t',
+        _ => ',',
+    }
+}
+
+/// Write the metatile indexes of a level's map, one line per row
+pub fn encode_map(level: &Level, metatileset: Option<&Metatileset>, delimiter: char) -> String {
+    let margin = level.margin;
+    let mut out = String::new();
+    for y in -margin.top..margin.bottom {
+        let row = (-margin.left..margin.right)
+            .map(|x| {
+                level
+                    .tiles
+                    .get(&(x, y))
+                    .and_then(|tile| metatileset?.tiles.get_index_of(&tile.metatileset_tile_id))
+                    .map(|idx| idx.to_string())
+                    .unwrap_or_else(|| EMPTY_CELL.into())
+            })
+            .collect::<Vec<_>>();
+        out.push_str(&row.join(&delimiter.to_string()));
+        out.push('\n');
+    }
+    out
+}
+
+/// Read the metatile index of each cell of a map file, or `None` for empty cells
+///
+/// Blank lines at the end of the file are ignored, but rows of `-1` cells are kept, so a map
+/// with empty rows at the bottom keeps its height. Rows shorter than the longest row are padded
+/// with empty cells.
+pub fn decode_map(text: &str, delimiter: char) -> anyhow::Result<Vec<Vec<Option<usize>>>> {
+    let mut lines = text.lines().collect::<Vec<_>>();
+    while lines.last().map(|x| x.trim().is_empty()) == Some(true) {
+        lines.pop();
+    }
+
+    let mut rows = lines
+        .into_iter()
+        .enumerate()
+        .map(|(line, text)| {
+            text.split(delimiter)
+                .enumerate()
+                .map(|(column, cell)| {
+                    let cell = cell.trim().trim_matches('"').trim();
+                    if cell.is_empty() || cell == EMPTY_CELL {
+                        return Ok(None);
+                    }
+                    cell.parse().map(Some).with_context(|| {
+                        format!(
+                            "Row {}, column {}: `{}` isn't a metatile index or {}",
+                            line + 1,
+                            column + 1,
+                            cell,
+                            EMPTY_CELL
+                        )
+                    })
+                })
+                .collect::<anyhow::Result<Vec<_>>>()
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+    for row in &mut rows {
+        row.resize(width, None);
+    }
+
+    Ok(rows)
+}
+
+/// Replace the map of a level with the cells read from a map file, resizing the map to fit them
+///
+/// The map grows or shrinks from its bottom-right corner, so the top-left corner of the file is
+/// the top-left corner of the map.
+pub fn import_map(
+    level: &mut Level,
+    metatileset: &Metatileset,
+    cells: &[Vec<Option<usize>>],
+) -> anyhow::Result<()> {
+    if cells.is_empty() {
+        anyhow::bail!("The map file doesn't have any cells");
+    }
+    let mut tiles = Vec::new();
+    for (y, row) in cells.iter().enumerate() {
+        for (x, idx) in row.iter().enumerate() {
+            if let Some(idx) = *idx {
+                let (id, _) = metatileset.tiles.get_index(idx).with_context(|| {
+                    format!(
+                        "Row {}, column {}: metatileset `{}` only has {} metatiles",
+                        y + 1,
+                        x + 1,
+                        metatileset.name,
+                        metatileset.tiles.len()
+                    )
+                })?;
+                tiles.push(((x as i32, y as i32), *id));
+            }
+        }
+    }
+
+    level.resize(cells[0].len() as i32, cells.len() as i32, (-1, -1));
+    let margin = level.margin;
+    level.tiles.retain(|_, _| false);
+    level.tiles.extend(tiles.into_iter().map(|((x, y), id)| {
+        (
+            (x - margin.left, y - margin.top),
+            LevelTile {
+                metatileset_tile_id: id,
+            },
+        )
+    }));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{project::MetatilesetTile, Uid};
+
+    #[test]
+    fn round_trip_keeps_empty_bottom_rows() {
+        let mut metatileset = Metatileset::default();
+        let tile_id = Uid::new();
+        metatileset
+            .tiles
+            .insert(tile_id, MetatilesetTile::default());
+
+        let mut level = Level::default();
+        level.tiles.insert(
+            (-level.margin.left, -level.margin.top),
+            LevelTile {
+                metatileset_tile_id: tile_id,
+            },
+        );
+        let (width, height) = (level.margin.width(), level.margin.height());
+
+        let text = encode_map(&level, Some(&metatileset), ',');
+        let cells = decode_map(&text, ',').unwrap();
+        assert_eq!(cells.len(), height as usize);
+
+        let mut imported = Level::default();
+        import_map(&mut imported, &metatileset, &cells).unwrap();
+        assert_eq!(imported.margin.width(), width);
+        assert_eq!(imported.margin.height(), height);
+        assert_eq!(encode_map(&imported, Some(&metatileset), ','), text);
+    }
+}
@@ -0,0 +1,316 @@
+//! Procedural generators for filling level maps
+//!
+//! A generator lays out the terrain of an area, where each cell is either floor or wall, and then
+//! each cell is filled with a metatile picked from the weighted building blocks for its terrain.
+//! Generators only decide the terrain, so any of them can be used with any metatiles. New
+//! generators are added by implementing [`MapGenerator`] and listing them in [`generators`].
+
+use std::ops::RangeInclusive;
+
+use rand::{distributions::WeightedIndex, prelude::Distribution, rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    project::{Level, LevelTile, MetatilesetTile},
+    Uid,
+};
+
+/// The kind of terrain in a generated cell
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Terrain {
+    Floor,
+    Wall,
+}
+
+impl Terrain {
+    pub const ALL: [Terrain; 2] = [Terrain::Floor, Terrain::Wall];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Terrain::Floor => "Floor",
+            Terrain::Wall => "Wall",
+        }
+    }
+}
+
+/// A setting of a generator, shown as a slider in the GUI
+pub struct GeneratorParam<'a> {
+    pub name: &'static str,
+    /// What the setting does, shown when hovering over it
+    pub help: &'static str,
+    pub value: &'a mut u32,
+    pub range: RangeInclusive<u32>,
+    pub suffix: &'static str,
+}
+
+/// A way to lay out the terrain of a map
+pub trait MapGenerator {
+    /// The name shown in the generator list
+    fn name(&self) -> &'static str;
+
+    /// A short description of the terrain the generator makes
+    fn description(&self) -> &'static str;
+
+    /// The settings of the generator
+    fn params(&mut self) -> Vec<GeneratorParam<'_>>;
+
+    /// Lay out the terrain for an area of `width` by `height` cells, row by row
+    fn generate(&self, width: usize, height: usize, rng: &mut StdRng) -> Vec<Terrain>;
+}
+
+/// All of the generators, with their default settings
+pub fn generators() -> Vec<Box<dyn MapGenerator>> {
+    vec![
+        Box::new(RandomFill { wall_percent: 30 }),
+        Box::new(Maze { passage_width: 1 }),
+        Box::new(Caves {
+            wall_percent: 45,
+            steps: 4,
+        }),
+    ]
+}
+
+/// Makes each cell a wall by chance
+pub struct RandomFill {
+    pub wall_percent: u32,
+}
+
+impl MapGenerator for RandomFill {
+    fn name(&self) -> &'static str {
+        "Random Fill"
+    }
+
+    fn description(&self) -> &'static str {
+        "Scatter walls over the floor, picking the metatile for each cell by its weight"
+    }
+
+    fn params(&mut self) -> Vec<GeneratorParam<'_>> {
+        vec![GeneratorParam {
+            name: "Walls",
+            help: "The chance of each cell being a wall",
+            value: &mut self.wall_percent,
+            range: 0..=100,
+            suffix: "%",
+        }]
+    }
+
+    fn generate(&self, width: usize, height: usize, rng: &mut StdRng) -> Vec<Terrain> {
+        (0..width * height)
+            .map(|_| random_terrain(rng, self.wall_percent))
+            .collect()
+    }
+}
+
+/// Carves a maze with exactly one path between any two points, surrounded by a wall
+pub struct Maze {
+    /// The width of the passages, in cells. The walls between them are always one cell thick.
+    pub passage_width: u32,
+}
+
+impl MapGenerator for Maze {
+    fn name(&self) -> &'static str {
+        "Maze"
+    }
+
+    fn description(&self) -> &'static str {
+        "Carve a maze out of walls, with exactly one path between any two points"
+    }
+
+    fn params(&mut self) -> Vec<GeneratorParam<'_>> {
+        vec![GeneratorParam {
+            name: "Passage Width",
+            help: "The width of the maze's passages",
+            value: &mut self.passage_width,
+            range: 1..=4,
+            suffix: " metatiles",
+        }]
+    }
+
+    fn generate(&self, width: usize, height: usize, rng: &mut StdRng) -> Vec<Terrain> {
+        let mut terrain = vec![Terrain::Wall; width * height];
+        // Each room of the maze is a square of passage, with a wall to its left and above it
+        let step = self.passage_width as usize + 1;
+        let (rooms_wide, rooms_high) = ((width.max(1) - 1) / step, (height.max(1) - 1) / step);
+        if rooms_wide == 0 || rooms_high == 0 {
+            return terrain;
+        }
+
+        // Clear the cells from (x, y) to (x + w, y + h), in cells
+        let mut carve = |x: usize, y: usize, w: usize, h: usize| {
+            for y in y..y + h {
+                terrain[y * width + x..y * width + x + w].fill(Terrain::Floor);
+            }
+        };
+        let passage = step - 1;
+
+        // A depth-first search that carves a passage to each room it visits
+        let mut visited = vec![false; rooms_wide * rooms_high];
+        let mut stack = vec![(rng.gen_range(0..rooms_wide), rng.gen_range(0..rooms_high))];
+        visited[stack[0].1 * rooms_wide + stack[0].0] = true;
+        carve(
+            stack[0].0 * step + 1,
+            stack[0].1 * step + 1,
+            passage,
+            passage,
+        );
+        while let Some(&(x, y)) = stack.last() {
+            let neighbors = [
+                (x > 0).then(|| (x - 1, y)),
+                (x + 1 < rooms_wide).then(|| (x + 1, y)),
+                (y > 0).then(|| (x, y - 1)),
+                (y + 1 < rooms_high).then(|| (x, y + 1)),
+            ]
+            .into_iter()
+            .flatten()
+            .filter(|(x, y)| !visited[y * rooms_wide + x])
+            .collect::<Vec<_>>();
+            if neighbors.is_empty() {
+                stack.pop();
+                continue;
+            }
+
+            let (next_x, next_y) = neighbors[rng.gen_range(0..neighbors.len())];
+            visited[next_y * rooms_wide + next_x] = true;
+            // Carve the next room and the wall between the two rooms
+            let (left, top) = (x.min(next_x), y.min(next_y));
+            let (w, h) = if next_x != x {
+                (step + passage, passage)
+            } else {
+                (passage, step + passage)
+            };
+            carve(left * step + 1, top * step + 1, w, h);
+            stack.push((next_x, next_y));
+        }
+
+        terrain
+    }
+}
+
+/// Grows caves out of random noise with a cellular automaton
+pub struct Caves {
+    /// The chance of each cell starting out as a wall
+    pub wall_percent: u32,
+    /// The number of times the walls are smoothed
+    pub steps: u32,
+}
+
+impl MapGenerator for Caves {
+    fn name(&self) -> &'static str {
+        "Caves"
+    }
+
+    fn description(&self) -> &'static str {
+        "Grow winding caves by repeatedly smoothing random walls, where each cell becomes a wall \
+        if most of its neighbors are walls"
+    }
+
+    fn params(&mut self) -> Vec<GeneratorParam<'_>> {
+        vec![
+            GeneratorParam {
+                name: "Walls",
+                help: "The chance of each cell starting out as a wall. Higher values give \
+                smaller caves",
+                value: &mut self.wall_percent,
+                range: 0..=100,
+                suffix: "%",
+            },
+            GeneratorParam {
+                name: "Smoothing",
+                help: "The number of times the walls are smoothed. More steps give rounder caves",
+                value: &mut self.steps,
+                range: 0..=10,
+                suffix: " steps",
+            },
+        ]
+    }
+
+    fn generate(&self, width: usize, height: usize, rng: &mut StdRng) -> Vec<Terrain> {
+        let mut terrain = (0..width * height)
+            .map(|_| random_terrain(rng, self.wall_percent))
+            .collect::<Vec<_>>();
+
+        for _ in 0..self.steps {
+            // Cells off the edge count as walls, so the caves are closed in
+            let is_wall = |terrain: &[Terrain], x: isize, y: isize| {
+                x < 0
+                    || y < 0
+                    || x >= width as isize
+                    || y >= height as isize
+                    || terrain[y as usize * width + x as usize] == Terrain::Wall
+            };
+            terrain = (0..width * height)
+                .map(|idx| {
+                    let (x, y) = ((idx % width) as isize, (idx / width) as isize);
+                    let walls = (-1..=1)
+                        .flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+                        .filter(|&(dx, dy)| (dx, dy) != (0, 0))
+                        .filter(|&(dx, dy)| is_wall(&terrain, x + dx, y + dy))
+                        .count();
+                    if walls >= 5 {
+                        Terrain::Wall
+                    } else {
+                        Terrain::Floor
+                    }
+                })
+                .collect();
+        }
+
+        terrain
+    }
+}
+
+/// A wall with a `wall_percent` chance, or a floor otherwise
+fn random_terrain(rng: &mut StdRng, wall_percent: u32) -> Terrain {
+    if rng.gen_range(0..100) < wall_percent {
+        Terrain::Wall
+    } else {
+        Terrain::Floor
+    }
+}
+
+/// A metatile that generated terrain can be filled with, and how often it is picked compared to
+/// the other metatiles for the same terrain
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BuildingBlock {
+    pub tile: Uid<MetatilesetTile>,
+    pub weight: u32,
+}
+
+/// Fill the area of a level from `min` to `max`, inclusive, with terrain from `generator`
+///
+/// `blocks` holds the building blocks for each kind of terrain, indexed like [`Terrain::ALL`].
+/// Cells whose terrain has no building blocks, or only ones with no weight, are left empty. The
+/// same seed always gives the same map.
+pub fn generate_map(
+    level: &mut Level,
+    min: (i32, i32),
+    max: (i32, i32),
+    generator: &dyn MapGenerator,
+    blocks: &[Vec<BuildingBlock>; 2],
+    seed: u64,
+) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let width = (max.0 - min.0 + 1).max(0) as usize;
+    let height = (max.1 - min.1 + 1).max(0) as usize;
+    let terrain = generator.generate(width, height, &mut rng);
+
+    let pickers = [&blocks[0], &blocks[1]]
+        .map(|blocks| WeightedIndex::new(blocks.iter().map(|x| x.weight)).ok());
+    for (idx, terrain) in terrain.into_iter().enumerate() {
+        let pos = (min.0 + (idx % width) as i32, min.1 + (idx / width) as i32);
+        let terrain_idx = terrain as usize;
+        match &pickers[terrain_idx] {
+            Some(picker) => {
+                let tile = blocks[terrain_idx][picker.sample(&mut rng)].tile;
+                level.tiles.insert(
+                    pos,
+                    LevelTile {
+                        metatileset_tile_id: tile,
+                    },
+                );
+            }
+            None => {
+                level.tiles.remove(&pos);
+            }
+        }
+    }
+}
@@ -0,0 +1,198 @@
+//! Converting full-screen mockup images into tiles, metatiles, and a map
+
+use std::path::Path;
+
+use anyhow::Context;
+use indexmap::IndexSet;
+
+use crate::{
+    pallet::{derive_pallet, nearest_nes_color, nes_color_distance},
+    project::Pallet,
+};
+
+/// The number of 8x8 tiles in each row of the generated tile sheet
+const SHEET_WIDTH_TILES: usize = 16;
+
+/// The gray levels used for each color index in the generated tile sheet
+const SHEET_COLORS: [u8; 4] = [0, 85, 170, 255];
+
+/// The colors of an 8x8 tile, as indexes 0-3 into its sub-pallet, in row-major order
+pub type TilePixels = [u8; 64];
+
+/// A 16x16 block of the mockup, which becomes one cell of the map
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MockupBlock {
+    /// The indexes into [`MockupConversion::tiles`] of the top-left, top-right, bottom-left, and
+    /// bottom-right tiles of the block
+    pub tiles: [usize; 4],
+    /// The sub-pallet, 0-3, used to color the block
+    pub sub_pallet: usize,
+}
+
+/// The result of slicing up a mockup image
+#[derive(Debug, Clone)]
+pub struct MockupConversion {
+    /// The pallet suggested for the mockup
+    pub pallet: Pallet,
+    /// The unique 8x8 tiles found in the mockup
+    pub tiles: Vec<TilePixels>,
+    /// The 16x16 blocks of the mockup in row-major order
+    pub blocks: Vec<MockupBlock>,
+    /// The width of the mockup in 16x16 blocks
+    pub width: usize,
+    /// The height of the mockup in 16x16 blocks
+    pub height: usize,
+    /// The number of distinct NES colors found in the mockup after snapping
+    pub color_count: usize,
+    /// The number of blocks that use colors that aren't in their sub-pallet, and so were colored
+    /// with the closest colors available
+    pub unfit_block_count: usize,
+}
+
+impl MockupConversion {
+    /// The distinct blocks of the mockup, each of which becomes a metatile in the metatileset
+    pub fn unique_blocks(&self) -> IndexSet<MockupBlock> {
+        self.blocks.iter().copied().collect()
+    }
+}
+
+/// Slice a mockup image into 8x8 tiles, removing duplicates, and color each 16x16 block of it with
+/// one of the sub-pallets of a suggested pallet.
+pub fn convert_mockup(path: &Path) -> anyhow::Result<MockupConversion> {
+    let image = image::open(path)
+        .with_context(|| format!("Could not open mockup `{}`", path.display()))?
+        .into_rgb8();
+    if image.width() == 0
+        || image.height() == 0
+        || image.width() % 16 != 0
+        || image.height() % 16 != 0
+    {
+        anyhow::bail!(
+            "Mockup is {}x{} pixels, but its width and height must be multiples of 16",
+            image.width(),
+            image.height()
+        );
+    }
+
+    let proposal = derive_pallet(&image);
+    let sub_pallets = proposal.pallet.get_sub_pallets();
+    let width = image.width() as usize / 16;
+    let height = image.height() as usize / 16;
+
+    let mut tiles = IndexSet::<TilePixels>::new();
+    let mut blocks = Vec::with_capacity(width * height);
+    let mut unfit_block_count = 0;
+    for block_y in 0..height {
+        for block_x in 0..width {
+            let colors = (0..16 * 16)
+                .map(|i| {
+                    let pixel = image.get_pixel(
+                        (block_x * 16 + i % 16) as u32,
+                        (block_y * 16 + i / 16) as u32,
+                    );
+                    nearest_nes_color(pixel.0)
+                })
+                .collect::<Vec<_>>();
+
+            // Pick the sub-pallet that can draw the block with the least difference in color
+            let (sub_pallet, error) = sub_pallets
+                .iter()
+                .map(|sub_pallet| {
+                    colors
+                        .iter()
+                        .map(|&color| nearest_slot(sub_pallet, color).1)
+                        .sum::<u32>()
+                })
+                .enumerate()
+                .min_by_key(|(_, error)| *error)
+                .expect("Pallet has sub-pallets");
+            if error > 0 {
+                unfit_block_count += 1;
+            }
+
+            let mut block_tiles = [0; 4];
+            for (i, tile) in block_tiles.iter_mut().enumerate() {
+                let mut pixels = [0; 64];
+                for (j, pixel) in pixels.iter_mut().enumerate() {
+                    let x = (i % 2) * 8 + j % 8;
+                    let y = (i / 2) * 8 + j / 8;
+                    *pixel = nearest_slot(&sub_pallets[sub_pallet], colors[y * 16 + x]).0;
+                }
+                *tile = tiles.insert_full(pixels).0;
+            }
+
+            blocks.push(MockupBlock {
+                tiles: block_tiles,
+                sub_pallet,
+            });
+        }
+    }
+
+    Ok(MockupConversion {
+        pallet: proposal.pallet,
+        tiles: tiles.into_iter().collect(),
+        blocks,
+        width,
+        height,
+        color_count: proposal.color_count,
+        unfit_block_count,
+    })
+}
+
+/// Get the index of the sub-pallet color closest to `color`, along with its distance
+fn nearest_slot(sub_pallet: &[u32; 4], color: u32) -> (u8, u32) {
+    sub_pallet
+        .iter()
+        .enumerate()
+        .map(|(i, &slot)| (i as u8, nes_color_distance(slot, color)))
+        .min_by_key(|(_, distance)| *distance)
+        .expect("Sub-pallet has colors")
+}
+
+/// Lay out tiles in a grayscale tile sheet that can be used as a project source, returning the
+/// sheet and the x and y tile index of each tile in it
+///
+/// Source images must use exactly four colors, so if the tiles don't use all of them, a key tile
+/// with each color is added after the last tile.
+pub fn tile_sheet(tiles: &[TilePixels]) -> (image::RgbImage, Vec<(u16, u16)>) {
+    let mut used = [false; 4];
+    for pixel in tiles.iter().flatten() {
+        used[*pixel as usize] = true;
+    }
+    let key_tile = (!used.iter().all(|x| *x)).then(|| {
+        let mut key = [0; 64];
+        for (i, pixel) in key.iter_mut().enumerate() {
+            *pixel = (i % 8 / 2) as u8;
+        }
+        key
+    });
+
+    let tile_count = tiles.len() + key_tile.is_some() as usize;
+    // Round up to an even number of tile rows so the sheet height is a multiple of 16
+    let rows = (0..tile_count.max(1))
+        .step_by(SHEET_WIDTH_TILES * 2)
+        .count()
+        * 2;
+    let mut sheet = image::RgbImage::new(SHEET_WIDTH_TILES as u32 * 8, rows as u32 * 8);
+
+    let positions = (0..tile_count)
+        .map(|i| {
+            (
+                (i % SHEET_WIDTH_TILES) as u16,
+                (i / SHEET_WIDTH_TILES) as u16,
+            )
+        })
+        .collect::<Vec<_>>();
+    for (pixels, (tile_x, tile_y)) in tiles.iter().chain(key_tile.iter()).zip(&positions) {
+        for (i, pixel) in pixels.iter().enumerate() {
+            let gray = SHEET_COLORS[*pixel as usize];
+            sheet.put_pixel(
+                *tile_x as u32 * 8 + i as u32 % 8,
+                *tile_y as u32 * 8 + i as u32 / 8,
+                image::Rgb([gray; 3]),
+            );
+        }
+    }
+
+    (sheet, positions[..tiles.len()].to_vec())
+}
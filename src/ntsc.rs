@@ -0,0 +1,113 @@
+//! Generating the NES color pallet by simulating the NTSC signal of the PPU, so the colors can be
+//! tuned to match an emulator or TV
+//!
+//! This follows Bisqwit's NES pallet generator: each color is a square wave between two voltage
+//! levels, which is decoded into YIQ the way an ideal TV would, and then converted to RGB.
+
+use egui::Color32;
+use serde::{Deserialize, Serialize};
+
+/// The low and high voltage of the PPU signal for each of the four brightness levels, relative to
+/// the sync voltage
+const SIGNAL_LOW: [f32; 4] = [0.350, 0.518, 0.962, 1.550];
+const SIGNAL_HIGH: [f32; 4] = [1.094, 1.506, 1.962, 1.962];
+
+/// The voltages of black and white, used to normalize the signal
+const BLACK: f32 = 0.518;
+const WHITE: f32 = 1.962;
+
+/// The settings used to decode the NTSC signal into colors, like the picture settings of a TV
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct NtscParams {
+    /// The hue rotation, in degrees
+    pub hue: f32,
+    /// The saturation multiplier
+    pub saturation: f32,
+    /// The contrast multiplier
+    pub contrast: f32,
+    /// The amount added to the brightness, from -1 to 1
+    pub brightness: f32,
+    /// The gamma of the TV that is being matched
+    pub gamma: f32,
+}
+
+impl Default for NtscParams {
+    fn default() -> Self {
+        Self {
+            hue: 0.0,
+            saturation: 1.0,
+            contrast: 1.0,
+            brightness: 0.0,
+            gamma: 1.8,
+        }
+    }
+}
+
+/// Generate the 64 colors of the NES pallet with the given settings
+pub fn generate_pallet(params: &NtscParams) -> [Color32; 64] {
+    let mut pallet = [Color32::BLACK; 64];
+    for (idx, color) in pallet.iter_mut().enumerate() {
+        *color = generate_color(idx as u8, params);
+    }
+    pallet
+}
+
+/// Simulate the signal of one NES color and decode it into an RGB color
+fn generate_color(idx: u8, params: &NtscParams) -> Color32 {
+    let hue = (idx & 0x0F) as usize;
+    // The blacks at the end of each row are always the second brightness level
+    let level = if hue < 0x0E {
+        (idx >> 4 & 0x03) as usize
+    } else {
+        1
+    };
+    // Hue 0 is a solid gray at the high level, and hues 13-15 are solid at the low level
+    let low = if hue == 0 {
+        SIGNAL_HIGH[level]
+    } else {
+        SIGNAL_LOW[level]
+    };
+    let high = if hue < 0x0D {
+        SIGNAL_HIGH[level]
+    } else {
+        SIGNAL_LOW[level]
+    };
+
+    // The PPU outputs 12 samples per pixel, high for half of the color's phase cycle
+    let (mut y, mut i, mut q) = (0.0, 0.0, 0.0);
+    for phase in 0..12 {
+        let signal = if (hue + phase + 8) % 12 < 6 {
+            high
+        } else {
+            low
+        };
+        let v = (signal - BLACK) / (WHITE - BLACK) / 12.0;
+        let angle = std::f32::consts::PI * phase as f32 / 6.0;
+        y += v;
+        i += v * angle.cos();
+        q += v * angle.sin();
+    }
+
+    let (sin, cos) = params.hue.to_radians().sin_cos();
+    let (i, q) = (i * cos - q * sin, i * sin + q * cos);
+    let y = y * params.contrast + params.brightness;
+    let i = i * params.saturation * params.contrast;
+    let q = q * params.saturation * params.contrast;
+
+    // Convert to RGB with the FCC matrix, and correct for the difference in gamma between the TV
+    // and a computer monitor
+    let channel = |value: f32| {
+        let value = if value <= 0.0 {
+            0.0
+        } else {
+            value.powf(2.2 / params.gamma.max(0.1))
+        };
+        (value * 255.95).clamp(0.0, 255.0) as u8
+    };
+    Color32::from_rgb(
+        channel(y + 0.946882 * i + 0.623557 * q),
+        channel(y - 0.274788 * i - 0.635691 * q),
+        channel(y - 1.108545 * i + 1.709007 * q),
+    )
+}
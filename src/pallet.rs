@@ -0,0 +1,479 @@
+//! Tools for picking NES color pallets automatically
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::Path,
+};
+
+use anyhow::Context;
+use egui::Color32;
+
+use crate::{constants::NES_PALLET, project::Pallet};
+
+/// The number of colors in a sub-pallet, not counting the shared background color
+pub const SUB_PALLET_COLORS: usize = 3;
+
+/// The number of sub-pallets in a pallet
+pub const SUB_PALLETS: usize = 4;
+
+/// Whether a NES color index is a good choice when picking colors automatically.
+///
+/// This skips the duplicate blacks at the end of each row, including the "blacker than black"
+/// `$0D`, and keeps `$0F` as the one true black.
+pub fn is_canonical_color(idx: u32) -> bool {
+    idx == 0x0F || (idx < 64 && idx & 0x0F < 0x0D)
+}
+
+/// Get the NES color index closest to an RGB color
+pub fn nearest_nes_color(color: [u8; 3]) -> u32 {
+    (0..NES_PALLET.len() as u32)
+        .filter(|&idx| is_canonical_color(idx))
+        .min_by_key(|&idx| color_distance(NES_PALLET[idx as usize], color))
+        .expect("NES pallet has colors")
+}
+
+/// The squared distance between two colors, weighted by how sensitive the eye is to each channel
+fn color_distance(a: Color32, b: [u8; 3]) -> u32 {
+    let dr = a.r() as i32 - b[0] as i32;
+    let dg = a.g() as i32 - b[1] as i32;
+    let db = a.b() as i32 - b[2] as i32;
+    (2 * dr * dr + 4 * dg * dg + 3 * db * db) as u32
+}
+
+/// The perceived brightness of a NES color, used to order sub-pallet colors the same way source
+/// image colors are ordered.
+pub fn brightness(idx: u32) -> u32 {
+    let color = NES_PALLET[idx.min(63) as usize];
+    color.r() as u32 * 299 + color.g() as u32 * 587 + color.b() as u32 * 114
+}
+
+/// Group sets of colors into at most four sub-pallets of three colors each.
+///
+/// Each set is given with a weight, and the largest and heaviest sets get first pick of the
+/// sub-pallets. Returns the sub-pallets, and for each set, the index of the sub-pallet chosen for
+/// it and whether that sub-pallet contains all of the set's colors.
+pub fn pack_sub_pallets(
+    sets: &[(BTreeSet<u32>, usize)],
+) -> (Vec<BTreeSet<u32>>, Vec<(usize, bool)>) {
+    let mut order = (0..sets.len()).collect::<Vec<_>>();
+    order.sort_by(|&a, &b| {
+        (sets[b].0.len(), sets[b].1)
+            .cmp(&(sets[a].0.len(), sets[a].1))
+            .then(a.cmp(&b))
+    });
+
+    let mut sub_pallets: Vec<BTreeSet<u32>> = Vec::with_capacity(SUB_PALLETS);
+    for &i in &order {
+        let colors = &sets[i].0;
+
+        let best_fit = sub_pallets
+            .iter()
+            .enumerate()
+            .map(|(idx, sub_pallet)| (idx, sub_pallet.union(colors).count()))
+            .filter(|(_, size)| *size <= SUB_PALLET_COLORS)
+            .min_by_key(|(idx, size)| (size - sub_pallets[*idx].len(), *idx));
+
+        if let Some((idx, _)) = best_fit {
+            sub_pallets[idx].extend(colors.iter().copied());
+        } else if sub_pallets.len() < SUB_PALLETS {
+            sub_pallets.push(colors.iter().copied().take(SUB_PALLET_COLORS).collect());
+        }
+    }
+
+    let assignments = sets
+        .iter()
+        .map(|(colors, _)| {
+            sub_pallets
+                .iter()
+                .enumerate()
+                .map(|(idx, sub_pallet)| (idx, sub_pallet.intersection(colors).count()))
+                .max_by_key(|(idx, overlap)| (*overlap, std::cmp::Reverse(*idx)))
+                .map(|(idx, overlap)| (idx, overlap == colors.len()))
+                .unwrap_or((0, colors.is_empty()))
+        })
+        .collect();
+
+    (sub_pallets, assignments)
+}
+
+/// Build a pallet out of a background color and up to four sub-pallets
+pub fn pallet_from_sub_pallets(background: u32, sub_pallets: &[BTreeSet<u32>]) -> Pallet {
+    let mut pallet = Pallet {
+        colors: [background; 13],
+    };
+
+    for (i, sub_pallet) in sub_pallets.iter().take(SUB_PALLETS).enumerate() {
+        let mut colors = sub_pallet.iter().copied().collect::<Vec<_>>();
+        colors.sort_by_key(|&x| brightness(x));
+
+        for (j, color) in colors.into_iter().take(SUB_PALLET_COLORS).enumerate() {
+            pallet.colors[1 + i * SUB_PALLET_COLORS + j] = color;
+        }
+    }
+
+    pallet
+}
+
+/// The length of a `.pal` file with the background and sprite pallets, as they are laid out in PPU
+/// pallet RAM
+pub const PAL_FILE_LEN: usize = 32;
+
+/// Encode a pallet as a `.pal` file: the four background sub-pallets as they are laid out in PPU
+/// pallet RAM, followed by a copy of them for the sprite pallets
+pub fn encode_pal_file(pallet: &Pallet) -> [u8; PAL_FILE_LEN] {
+    let mut bytes = [0; PAL_FILE_LEN];
+    for (i, color) in pallet.get_sub_pallets().iter().flatten().enumerate() {
+        bytes[i] = *color as u8;
+        bytes[i + PAL_FILE_LEN / 2] = *color as u8;
+    }
+    bytes
+}
+
+/// Decode the background pallet from a 16 or 32 byte `.pal` file, ignoring the sprite pallets
+pub fn decode_pal_file(bytes: &[u8]) -> anyhow::Result<Pallet> {
+    if bytes.len() != PAL_FILE_LEN && bytes.len() != PAL_FILE_LEN / 2 {
+        anyhow::bail!(
+            "Pallet file is {} bytes, but should be {} or {} bytes",
+            bytes.len(),
+            PAL_FILE_LEN / 2,
+            PAL_FILE_LEN
+        );
+    }
+    if let Some(color) = bytes.iter().find(|&&x| x as usize >= NES_PALLET.len()) {
+        anyhow::bail!(
+            "Pallet file has color ${:02X}, which isn't an NES color",
+            color
+        );
+    }
+
+    // The first color of every sub-pallet is a mirror of the background color, just like the NES
+    let mut pallet = Pallet {
+        colors: [bytes[0] as u32; 13],
+    };
+    for sub_pallet in 0..SUB_PALLETS {
+        for j in 0..SUB_PALLET_COLORS {
+            pallet.colors[1 + sub_pallet * SUB_PALLET_COLORS + j] =
+                bytes[sub_pallet * 4 + 1 + j] as u32;
+        }
+    }
+    Ok(pallet)
+}
+
+/// Format a pallet as text for the clipboard: the 16 colors of the four sub-pallets in hex, as
+/// they are laid out in PPU pallet RAM, such as `0F 21 11 01 0F 2A 1A 0A ...`
+pub fn format_pallet_text(pallet: &Pallet) -> String {
+    pallet
+        .get_sub_pallets()
+        .iter()
+        .flatten()
+        .map(|color| format!("{:02X}", color))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Parse a pallet copied as text, either in the format written by [`format_pallet_text`] or as
+/// the 13 unique colors with the background color first.
+///
+/// The colors are hex numbers separated by spaces or commas, and may start with `$` or `0x`.
+pub fn parse_pallet_text(text: &str) -> anyhow::Result<Pallet> {
+    let bytes = text
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|x| !x.is_empty())
+        .map(|x| {
+            let hex = x.trim_start_matches('$').trim_start_matches("0x");
+            u8::from_str_radix(hex, 16).with_context(|| format!("`{}` isn't a hex color", x))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    if let Some(color) = bytes.iter().find(|&&x| x as usize >= NES_PALLET.len()) {
+        anyhow::bail!("Color ${:02X} isn't an NES color", color);
+    }
+
+    if bytes.len() == 13 {
+        let mut pallet = Pallet::default();
+        for (color, byte) in pallet.colors.iter_mut().zip(bytes) {
+            *color = byte as u32;
+        }
+        Ok(pallet)
+    } else if bytes.len() == PAL_FILE_LEN / 2 || bytes.len() == PAL_FILE_LEN {
+        decode_pal_file(&bytes)
+    } else {
+        anyhow::bail!(
+            "Found {} colors, but a pallet should have 13 or 16 colors",
+            bytes.len()
+        )
+    }
+}
+
+/// A pallet suggested by [`derive_pallet_from_image`]
+#[derive(Clone, Debug)]
+pub struct PalletProposal {
+    /// The suggested pallet
+    pub pallet: Pallet,
+    /// The number of distinct NES colors found in the image after snapping
+    pub color_count: usize,
+    /// The number of 16x16 blocks in the image
+    pub block_count: usize,
+    /// The number of 16x16 blocks that can't be colored with any of the suggested sub-pallets
+    pub unfit_block_count: usize,
+}
+
+/// Analyze an image, snapping its colors to the NES pallet, and suggest a pallet that can be used
+/// to color it.
+///
+/// Colors are grouped by 16x16 pixel block, because that is the size of a metatile, which must be
+/// colored with a single sub-pallet.
+pub fn derive_pallet_from_image(path: &Path) -> anyhow::Result<PalletProposal> {
+    Ok(derive_pallet(&image::open(path)?.into_rgb8()))
+}
+
+/// Suggest a pallet for an image that has already been loaded, like
+/// [`derive_pallet_from_image`]
+pub fn derive_pallet(image: &image::RgbImage) -> PalletProposal {
+    let mut snapped = HashMap::new();
+    let mut snap = |pixel: &image::Rgb<u8>| {
+        *snapped
+            .entry(pixel.0)
+            .or_insert_with(|| nearest_nes_color(pixel.0))
+    };
+
+    // Count the pixels of each color in each block
+    let mut blocks = HashMap::<(u32, u32), HashMap<u32, usize>>::new();
+    let mut totals = HashMap::<u32, usize>::new();
+    for (x, y, pixel) in image.enumerate_pixels() {
+        let color = snap(pixel);
+        let block = blocks.entry((x / 16, y / 16)).or_default();
+        *block.entry(color).or_default() += 1;
+        *totals.entry(color).or_default() += 1;
+    }
+
+    // The most common color makes the best background color
+    let background = totals
+        .iter()
+        .max_by_key(|(color, count)| (**count, std::cmp::Reverse(**color)))
+        .map(|(color, _)| *color)
+        .unwrap_or(0x0F);
+
+    // Each block may use the background plus its three most common other colors. Blocks with more
+    // colors than that can't be colored exactly whichever sub-pallet they get, so they are counted
+    // before their extra colors are dropped.
+    let mut sets = HashMap::<BTreeSet<u32>, usize>::new();
+    let mut overfull = HashMap::<BTreeSet<u32>, usize>::new();
+    for block in blocks.values() {
+        let mut colors = block
+            .iter()
+            .filter(|(color, _)| **color != background)
+            .collect::<Vec<_>>();
+        colors.sort_by_key(|(color, count)| (std::cmp::Reverse(**count), **color));
+        let too_many_colors = colors.len() > SUB_PALLET_COLORS;
+
+        let set = colors
+            .into_iter()
+            .take(SUB_PALLET_COLORS)
+            .map(|(color, _)| *color)
+            .collect::<BTreeSet<_>>();
+        if too_many_colors {
+            *overfull.entry(set.clone()).or_default() += 1;
+        }
+        *sets.entry(set).or_default() += 1;
+    }
+    let mut sets = sets.into_iter().collect::<Vec<_>>();
+    sets.sort();
+
+    let (sub_pallets, assignments) = pack_sub_pallets(&sets);
+
+    let unfit_block_count = sets
+        .iter()
+        .zip(&assignments)
+        .map(|((set, count), (_, fits))| {
+            if *fits {
+                overfull.get(set).copied().unwrap_or(0)
+            } else {
+                *count
+            }
+        })
+        .sum();
+
+    PalletProposal {
+        pallet: pallet_from_sub_pallets(background, &sub_pallets),
+        color_count: totals.len(),
+        block_count: blocks.len(),
+        unfit_block_count,
+    }
+}
+
+/// The colors a metatile needs in each of the three sub-pallet slots that follow the background
+/// color, with `None` for slots the metatile doesn't use.
+pub type SlotColors = [Option<u32>; SUB_PALLET_COLORS];
+
+/// A color change suggested by [`solve_sub_pallets`] so that a request fits in a sub-pallet
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ColorMerge {
+    /// The index of the request that has to change color
+    pub request: usize,
+    /// The sub-pallet slot, 0-2, not counting the background color
+    pub slot: usize,
+    /// The color that was requested
+    pub from: u32,
+    /// The color it has to be replaced with
+    pub to: u32,
+}
+
+/// The result of [`solve_sub_pallets`]
+#[derive(Clone, Debug, Default)]
+pub struct SubPalletSolution {
+    /// The four sub-pallets, with `None` for slots that no request uses
+    pub sub_pallets: Vec<SlotColors>,
+    /// The index of the sub-pallet assigned to each request
+    pub assignments: Vec<usize>,
+    /// The color changes needed to fit every request into the sub-pallets
+    pub merges: Vec<ColorMerge>,
+}
+
+/// Assign color requests to at most four sub-pallets, sharing sub-pallets between requests where
+/// their colors agree.
+///
+/// Each request is given with a weight, usually the number of metatiles that need those colors.
+/// When the requests can't all fit, the remaining requests are placed in the sub-pallet that needs
+/// the least noticeable color changes, weighted by how many metatiles are affected, and those
+/// changes are reported as merges.
+///
+/// The colors of `locked` sub-pallets are never changed, but requests are still placed in them
+/// when their colors fit, or when they are the closest match.
+pub fn solve_sub_pallets(
+    requests: &[(SlotColors, usize)],
+    locked: &[Option<[u32; SUB_PALLET_COLORS]>; SUB_PALLETS],
+) -> SubPalletSolution {
+    let specified = |colors: &SlotColors| colors.iter().filter(|x| x.is_some()).count();
+
+    let mut order = (0..requests.len()).collect::<Vec<_>>();
+    order.sort_by(|&a, &b| {
+        (specified(&requests[b].0), requests[b].1)
+            .cmp(&(specified(&requests[a].0), requests[a].1))
+            .then(a.cmp(&b))
+    });
+
+    let mut solution = SubPalletSolution {
+        assignments: vec![0; requests.len()],
+        ..Default::default()
+    };
+    let mut unfit = Vec::new();
+
+    // The sub-pallets in use, by their index in the pallet. The locked sub-pallets are always in
+    // use, and the others are started as they are needed.
+    let mut sub_pallets = locked
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, colors)| Some((idx, colors.as_ref()?.map(Some))))
+        .collect::<Vec<_>>();
+    let mut unlocked = (0..SUB_PALLETS).filter(|&idx| locked[idx].is_none());
+
+    for &i in &order {
+        let colors = &requests[i].0;
+
+        // Prefer the compatible sub-pallet that needs the fewest new slots filled in
+        let best_fit = sub_pallets
+            .iter_mut()
+            .filter(|(_, sub_pallet)| {
+                (0..SUB_PALLET_COLORS).all(|s| match (colors[s], sub_pallet[s]) {
+                    (Some(a), Some(b)) => a == b,
+                    _ => true,
+                })
+            })
+            .min_by_key(|(idx, sub_pallet)| {
+                let filled = (0..SUB_PALLET_COLORS)
+                    .filter(|&s| colors[s].is_some() && sub_pallet[s].is_none())
+                    .count();
+                (filled, *idx)
+            });
+
+        if let Some((idx, sub_pallet)) = best_fit {
+            fill_slots(sub_pallet, colors);
+            solution.assignments[i] = *idx;
+        } else if let Some(idx) = unlocked.next() {
+            sub_pallets.push((idx, *colors));
+            solution.assignments[i] = idx;
+        } else {
+            unfit.push(i);
+        }
+    }
+
+    for i in unfit {
+        let (colors, weight) = &requests[i];
+
+        let cost = |sub_pallet: &SlotColors| -> u64 {
+            (0..SUB_PALLET_COLORS)
+                .map(|s| match (colors[s], sub_pallet[s]) {
+                    (Some(a), Some(b)) => nes_color_distance(a, b) as u64 * *weight as u64,
+                    _ => 0,
+                })
+                .sum()
+        };
+        let (idx, sub_pallet) = sub_pallets
+            .iter_mut()
+            .min_by_key(|(idx, sub_pallet)| (cost(sub_pallet), *idx))
+            .expect("Every sub-pallet is in use");
+        let idx = *idx;
+
+        for s in 0..SUB_PALLET_COLORS {
+            if let (Some(from), Some(to)) = (colors[s], sub_pallet[s]) {
+                if from != to {
+                    solution.merges.push(ColorMerge {
+                        request: i,
+                        slot: s,
+                        from,
+                        to,
+                    });
+                }
+            }
+        }
+        fill_slots(sub_pallet, colors);
+        solution.assignments[i] = idx;
+    }
+
+    solution.sub_pallets = vec![[None; SUB_PALLET_COLORS]; SUB_PALLETS];
+    for (idx, colors) in sub_pallets {
+        solution.sub_pallets[idx] = colors;
+    }
+
+    solution
+}
+
+/// Fill in the empty slots of a sub-pallet with the requested colors
+fn fill_slots(sub_pallet: &mut SlotColors, colors: &SlotColors) {
+    for (slot, color) in sub_pallet.iter_mut().zip(colors) {
+        if slot.is_none() {
+            *slot = *color;
+        }
+    }
+}
+
+/// The distance between two NES colors
+pub fn nes_color_distance(a: u32, b: u32) -> u32 {
+    let b = NES_PALLET[b.min(63) as usize];
+    color_distance(NES_PALLET[a.min(63) as usize], [b.r(), b.g(), b.b()])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks_with_too_many_colors_are_unfit() {
+        // One 16x16 block with the background and four other colors, one per row
+        let colors = [0x0F, 0x16, 0x2A, 0x12, 0x30];
+        let image = image::RgbImage::from_fn(16, 16, |_, y| {
+            let color = if y < 12 {
+                colors[0]
+            } else {
+                colors[y as usize - 11]
+            };
+            let color = NES_PALLET[color as usize];
+            image::Rgb([color.r(), color.g(), color.b()])
+        });
+
+        let proposal = derive_pallet(&image);
+        assert_eq!(proposal.block_count, 1);
+        assert_eq!(proposal.unfit_block_count, 1);
+    }
+}
@@ -1,11 +1,26 @@
 //! NESImg project format
 
-use std::path::PathBuf;
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::{Path, PathBuf},
+};
 
-use indexmap::IndexMap;
+use anyhow::Context;
+use indexmap::{IndexMap, IndexSet};
+use once_cell::sync::OnceCell;
+use path_absolutize::Absolutize;
 use serde::{Deserialize, Serialize};
 
-use crate::Uid;
+use crate::{
+    constants::{SCREEN_HEIGHT_METATILES, SCREEN_WIDTH_METATILES},
+    export::PATTERN_TABLE_TILES,
+    schema, Uid,
+};
+
+mod level_tiles;
+mod template;
+pub use level_tiles::LevelTiles;
+pub use template::ProjectTemplate;
 
 /// The actual project structure, as serialized to JSON for the project file
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
@@ -13,12 +28,173 @@ use crate::Uid;
 pub struct Project {
     /// The source images
     pub sources: IndexMap<Uid<PathBuf>, PathBuf>,
+    /// The source images that keep the pallet order of their indexed PNG or GIF file, instead of
+    /// having their colors sorted by brightness
+    pub pallet_order_sources: IndexSet<Uid<PathBuf>>,
     /// The metatiles
     pub metatiles: IndexMap<Uid<Metatile>, Metatile>,
     /// The metatilesets
     pub metatilesets: IndexMap<Uid<Metatileset>, Metatileset>,
     /// The levels that make up the project map
     pub levels: IndexMap<Uid<Level>, Level>,
+    /// The named export configurations for the project
+    pub export_targets: IndexMap<Uid<ExportTarget>, ExportTarget>,
+    /// Bookmarked locations on the map, by their shortcut number, 1-9
+    pub map_bookmarks: BTreeMap<u8, MapBookmark>,
+    /// The levels placed in the world view, and where they are placed
+    pub world_layout: IndexMap<Uid<Level>, WorldPlacement>,
+    /// Background tiles that cycle through other source tiles
+    pub tile_animations: IndexMap<Uid<TileAnimation>, TileAnimation>,
+    /// The edges of the screen that TVs may crop, shown as guides on the map
+    pub overscan: Overscan,
+    /// How the game banks background tiles, which limits the unique tiles on each screen
+    pub chr_banks: ChrBankProfile,
+    /// The names of the bits of [`MetatilesetTile::properties`], from bit 0 to bit 7. Named bits
+    /// get a checkbox in the metatile editor and a constant in exports, and empty names are
+    /// unused bits.
+    pub property_flags: [String; 8],
+}
+
+/// The file extension of binary project files
+pub const BINARY_PROJECT_EXTENSION: &str = "nesimgb";
+
+/// The bytes at the start of every binary project file
+const BINARY_PROJECT_MAGIC: &[u8; 8] = b"NESIMGB\0";
+
+/// The version of the binary project layout, a hash of the shape of [`Project`], so that it changes
+/// whenever a field or variant is added, removed, or moved
+///
+/// Unlike RON, the binary format doesn't record field names, so a binary file can only be read
+/// by a version of NESImg with the same project layout as the one that wrote it.
+fn binary_project_version() -> anyhow::Result<u32> {
+    static VERSION: OnceCell<u32> = OnceCell::new();
+    VERSION
+        .get_or_try_init(schema::layout_hash)
+        .copied()
+        .context("Trace the project format")
+}
+
+/// The ways a project can be stored on disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProjectFormat {
+    /// Human readable RON, which diffs and merges well in version control
+    Ron,
+    /// Compact binary, which loads and saves much faster for very large projects
+    Binary,
+}
+
+impl ProjectFormat {
+    /// Pick the format of a project file by its extension
+    pub fn from_path(path: &Path) -> Self {
+        if path.extension() == Some(std::ffi::OsStr::new(BINARY_PROJECT_EXTENSION)) {
+            Self::Binary
+        } else {
+            Self::Ron
+        }
+    }
+
+    /// Pick the format of a project by its contents, for projects that don't come from a file
+    pub fn from_contents(bytes: &[u8]) -> Self {
+        if bytes.starts_with(BINARY_PROJECT_MAGIC) {
+            Self::Binary
+        } else {
+            Self::Ron
+        }
+    }
+}
+
+impl Project {
+    /// Load a project from a project file
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read(path).context("Read project file")?;
+
+        Self::from_bytes(&contents, ProjectFormat::from_path(path))
+    }
+
+    /// Save the project to a project file
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let contents = self.to_bytes(ProjectFormat::from_path(path))?;
+
+        std::fs::write(path, contents).context("Write project file")
+    }
+
+    /// Parse the contents of a project file
+    pub fn from_bytes(bytes: &[u8], format: ProjectFormat) -> anyhow::Result<Self> {
+        match format {
+            ProjectFormat::Ron => ron::de::from_bytes(bytes).context("Parse project file"),
+            ProjectFormat::Binary => {
+                let data = bytes
+                    .strip_prefix(BINARY_PROJECT_MAGIC.as_slice())
+                    .context("Not a binary NESImg project file")?;
+                let (version, data) = data.split_at(4.min(data.len()));
+                let version = version
+                    .try_into()
+                    .map(u32::from_le_bytes)
+                    .context("Binary project file is truncated")?;
+                let expected_version = binary_project_version()?;
+                if version != expected_version {
+                    anyhow::bail!(
+                        "Binary project file has layout {:08X}, but this version of NESImg reads \
+                        layout {:08X}; open it with the NESImg that wrote it and save it as \
+                        `.nesimg` to upgrade it",
+                        version,
+                        expected_version
+                    );
+                }
+
+                bincode::deserialize(data).context("Parse binary project file")
+            }
+        }
+    }
+
+    /// Serialize the project to the contents of a project file
+    pub fn to_bytes(&self, format: ProjectFormat) -> anyhow::Result<Vec<u8>> {
+        match format {
+            ProjectFormat::Ron => Ok(ron::ser::to_string_pretty(self, Default::default())
+                .context("Serialize project")?
+                .into_bytes()),
+            ProjectFormat::Binary => {
+                let mut contents = BINARY_PROJECT_MAGIC.to_vec();
+                contents.extend_from_slice(&binary_project_version()?.to_le_bytes());
+                bincode::serialize_into(&mut contents, self).context("Serialize project")?;
+
+                Ok(contents)
+            }
+        }
+    }
+
+    /// Get the absolute path to a source image, given the path to the project file.
+    ///
+    /// Source paths are stored relative to the project file.
+    pub fn source_path(project_path: &Path, source: &Path) -> PathBuf {
+        project_path
+            .absolutize()
+            .unwrap()
+            .join(source)
+            .absolutize()
+            .expect("Absolutize")
+            .to_path_buf()
+    }
+
+    /// Find an export target by name
+    pub fn export_target(&self, name: &str) -> Option<&ExportTarget> {
+        self.export_targets.values().find(|x| x.name == name)
+    }
+
+    /// Find the animation that animates a tile, if any
+    pub fn tile_animation(&self, tile: &Tile) -> Option<&TileAnimation> {
+        self.tile_animations
+            .values()
+            .find(|x| x.frames.first() == Some(tile))
+    }
+
+    /// Get the tile that is shown in place of `tile` at a point in time, counted in NES frames
+    pub fn animated_tile<'a>(&'a self, tile: &'a Tile, nes_frame: u64) -> &'a Tile {
+        match self.tile_animation(tile) {
+            Some(animation) => animation.frame_at(nes_frame),
+            None => tile,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default, Hash)]
@@ -31,6 +207,40 @@ pub struct Tile {
     pub y: u16,
 }
 
+/// A background tile that cycles through a list of source tiles
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct TileAnimation {
+    /// A human-readable name for reference purposes
+    pub name: String,
+    /// The source tiles shown for each frame. Metatiles that use the first frame are animated.
+    pub frames: Vec<Tile>,
+    /// How long each frame is shown, in NES frames at 60 frames per second
+    pub frame_duration: u16,
+}
+
+impl Default for TileAnimation {
+    fn default() -> Self {
+        Self {
+            name: "New Animation".into(),
+            frames: Vec::new(),
+            frame_duration: 8,
+        }
+    }
+}
+
+impl TileAnimation {
+    /// Get the frame shown at a point in time, counted in NES frames
+    ///
+    /// # Panics
+    ///
+    /// Panics if the animation has no frames.
+    pub fn frame_at(&self, nes_frame: u64) -> &Tile {
+        let idx = nes_frame / self.frame_duration.max(1) as u64 % self.frames.len() as u64;
+        &self.frames[idx as usize]
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
 #[serde(deny_unknown_fields, default)]
 pub struct Metatile {
@@ -49,6 +259,96 @@ pub struct Metatileset {
 
     /// The metatiles that make up the metatileset
     pub tiles: IndexMap<Uid<MetatilesetTile>, MetatilesetTile>,
+
+    /// Sub-pallets beyond the four supported by the NES, each with the three colors that follow the
+    /// background color.
+    ///
+    /// These can be used to sketch out colors before using the pallet solver to fit them into the
+    /// real pallet.
+    pub extra_sub_pallets: Vec<[u32; 3]>,
+
+    /// The tiles pinned to the top of the tile picker in the maps tab
+    pub favorite_tiles: Vec<Uid<MetatilesetTile>>,
+
+    /// The number of unique tiles the metatileset may use, such as 128 for a game that switches
+    /// half of the pattern table, or `None` to allow the whole pattern table
+    pub tile_budget: Option<usize>,
+
+    /// The sub-pallets whose colors can't be changed, usually because the game's code hardcodes
+    /// them. The background color is locked along with any of them, since it is part of every
+    /// sub-pallet.
+    pub locked_sub_pallets: [bool; 4],
+}
+
+impl Metatileset {
+    /// Get the four colors of a sub-pallet, including the extra sub-pallets, which start at index
+    /// 4. Falls back to the first sub-pallet if there is no sub-pallet with that index.
+    pub fn sub_pallet(&self, idx: usize) -> [u32; 4] {
+        let sub_pallets = self.pallet.get_sub_pallets();
+        match idx.checked_sub(4) {
+            None => sub_pallets[idx],
+            Some(extra_idx) => match self.extra_sub_pallets.get(extra_idx) {
+                Some(c) => [self.pallet.colors[0], c[0], c[1], c[2]],
+                None => sub_pallets[0],
+            },
+        }
+    }
+
+    /// Whether the background color is locked, because one of the sub-pallets is
+    pub fn background_locked(&self) -> bool {
+        self.locked_sub_pallets.contains(&true)
+    }
+
+    /// Replace the pallet, keeping the colors of the locked sub-pallets and the background color if
+    /// it is locked. Returns whether any of the new colors were left out because of the locks.
+    pub fn set_pallet(&mut self, mut pallet: Pallet) -> bool {
+        let old = &self.pallet.colors;
+        let mut kept = Vec::new();
+        if self.background_locked() {
+            kept.push(0);
+        }
+        for (idx, _) in self.locked_sub_pallets.iter().enumerate().filter(|x| *x.1) {
+            kept.extend(1 + idx * 3..4 + idx * 3);
+        }
+
+        let mut changed = false;
+        for i in kept {
+            changed |= pallet.colors[i] != old[i];
+            pallet.colors[i] = old[i];
+        }
+        self.pallet = pallet;
+        changed
+    }
+
+    /// The number of unique tiles used by the metatileset's metatiles
+    pub fn unique_tile_count(&self, metatiles: &IndexMap<Uid<Metatile>, Metatile>) -> usize {
+        self.tiles
+            .values()
+            .filter_map(|x| metatiles.get(&x.metatile_id))
+            .flat_map(|x| x.tiles.iter().flatten())
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    /// The number of unique tiles the metatileset may use: its budget, or the size of a pattern
+    /// table if it doesn't have one
+    pub fn max_tiles(&self) -> usize {
+        self.tile_budget.unwrap_or(PATTERN_TABLE_TILES)
+    }
+
+    /// Remove one of the extra sub-pallets, moving the tiles that use it to the first sub-pallet
+    pub fn remove_extra_sub_pallet(&mut self, extra_idx: usize) {
+        self.extra_sub_pallets.remove(extra_idx);
+
+        let removed_idx = extra_idx + 4;
+        for tile in self.tiles.values_mut() {
+            if tile.sub_pallet_idx == removed_idx {
+                tile.sub_pallet_idx = 0;
+            } else if tile.sub_pallet_idx > removed_idx {
+                tile.sub_pallet_idx -= 1;
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
@@ -56,8 +356,12 @@ pub struct Metatileset {
 pub struct MetatilesetTile {
     /// The id of the metatile.
     pub metatile_id: Uid<Metatile>,
-    /// The index in the range `0..4` of the sub-pallet to use for rendering the metatile.
+    /// The index in the range `0..4` of the sub-pallet to use for rendering the metatile, or a
+    /// higher index to use one of the metatileset's extra sub-pallets.
     pub sub_pallet_idx: usize,
+    /// Flags that the game gives the metatile, such as whether it is solid, exported as the
+    /// property byte of the metatile records
+    pub properties: u8,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -108,9 +412,24 @@ pub struct Level {
     pub name: String,
     pub metatileset_id: Uid<Metatileset>,
     pub margin: LevelMargin,
-    pub tiles: IndexMap<(i32, i32), LevelTile>,
+    pub tiles: LevelTiles,
     /// Used in the GUI to organize the levels
     pub world_offset: egui::Vec2,
+    /// Named areas of the map, such as camera bounds or trigger areas
+    pub zones: IndexMap<Uid<Zone>, Zone>,
+    /// The ways out of the level and into other levels
+    pub exits: IndexMap<Uid<LevelExit>, LevelExit>,
+    /// A mid-frame scroll change, used to preview status bars that stay still while the rest of
+    /// the screen scrolls
+    pub split: Option<ScrollSplit>,
+    /// Pallet swaps part way down the screen, for games that change the pallet with raster tricks
+    pub pallet_regions: Vec<PalletRegion>,
+    /// The rows of the map that make up a static status bar, exported separately from the
+    /// scrolling playfield
+    pub hud: HudRows,
+    /// The flags of each screen of the map by its column and row, for engines that store levels
+    /// as a list of screens. Screens without an entry have the default flags.
+    pub screens: BTreeMap<(i32, i32), ScreenFlags>,
 }
 
 impl Default for Level {
@@ -119,12 +438,232 @@ impl Default for Level {
             name: "New Level".into(),
             metatileset_id: Default::default(),
             margin: Default::default(),
-            tiles: IndexMap::with_capacity(16 * 16),
+            tiles: Default::default(),
             world_offset: Default::default(),
+            zones: Default::default(),
+            exits: Default::default(),
+            split: None,
+            pallet_regions: Default::default(),
+            hud: Default::default(),
+            screens: Default::default(),
         }
     }
 }
 
+impl Level {
+    /// The number of screens covered by the level map horizontally and vertically, counting
+    /// partial screens at the right and bottom edges
+    pub fn screen_size(&self) -> (i32, i32) {
+        let screens =
+            |len: i32, screen_len: usize| (0..len.max(1)).step_by(screen_len).count() as i32;
+        (
+            screens(self.margin.width(), SCREEN_WIDTH_METATILES),
+            screens(self.margin.height(), SCREEN_HEIGHT_METATILES),
+        )
+    }
+
+    /// Resize the map to `width` by `height` metatiles.
+    ///
+    /// The `anchor` is the part of the map that stays in place for each axis: `-1` for the left
+    /// or top edge, `0` for the center, and `1` for the right or bottom edge. Tiles that no longer
+    /// fit are removed, and zones are cropped to the new map, or removed if none of them is left.
+    /// The screen flags move with the map when it grows or shrinks at the left or top by a whole
+    /// number of screens, and are removed otherwise, along with the flags of screens that are no
+    /// longer on the map.
+    pub fn resize(&mut self, width: i32, height: i32, anchor: (i32, i32)) {
+        // How much to grow the start and end of an axis
+        let grow = |len: i32, new_len: i32, anchor: i32| {
+            let diff = new_len - len;
+            match anchor.signum() {
+                -1 => (0, diff),
+                0 => (diff / 2, diff - diff / 2),
+                _ => (diff, 0),
+            }
+        };
+        let (left, right) = grow(self.margin.width(), width, anchor.0);
+        let (top, bottom) = grow(self.margin.height(), height, anchor.1);
+        self.margin.left += left;
+        self.margin.right += right;
+        self.margin.top += top;
+        self.margin.bottom += bottom;
+
+        let margin = self.margin;
+        self.tiles.retain(|pos, _| margin_contains(margin, *pos));
+        self.crop_zones();
+        self.shift_screens(left, top, false);
+    }
+
+    /// Move the screen flags by `dx` and `dy` metatiles, after the map has moved by that much
+    /// relative to its top-left corner.
+    ///
+    /// Flags can only move by whole screens, so they are all removed when the move doesn't line up
+    /// with the screens. Flags that move off of the map are removed, or come back on the opposite
+    /// edge with `wrap`.
+    fn shift_screens(&mut self, dx: i32, dy: i32, wrap: bool) {
+        let (screen_width, screen_height) = (
+            SCREEN_WIDTH_METATILES as i32,
+            SCREEN_HEIGHT_METATILES as i32,
+        );
+        let (screens_wide, screens_high) = self.screen_size();
+        let whole_screens = |len: i32, screen_len: i32| len % screen_len == 0;
+        let lines_up = whole_screens(dx, screen_width)
+            && whole_screens(dy, screen_height)
+            && (!wrap || dx == 0 || whole_screens(self.margin.width(), screen_width))
+            && (!wrap || dy == 0 || whole_screens(self.margin.height(), screen_height));
+        if !lines_up {
+            self.screens.clear();
+            return;
+        }
+
+        let (dx, dy) = (dx / screen_width, dy / screen_height);
+        self.screens = std::mem::take(&mut self.screens)
+            .into_iter()
+            .map(|((x, y), flags)| {
+                let (x, y) = (x + dx, y + dy);
+                if wrap {
+                    (
+                        (x.rem_euclid(screens_wide), y.rem_euclid(screens_high)),
+                        flags,
+                    )
+                } else {
+                    ((x, y), flags)
+                }
+            })
+            .filter(|((x, y), _)| (0..screens_wide).contains(x) && (0..screens_high).contains(y))
+            .collect();
+    }
+
+    /// The flags of the screen at the given column and row
+    pub fn screen_flags(&self, screen: (i32, i32)) -> ScreenFlags {
+        self.screens.get(&screen).copied().unwrap_or_default()
+    }
+
+    /// Set the flags of the screen at the given column and row
+    pub fn set_screen_flags(&mut self, screen: (i32, i32), flags: ScreenFlags) {
+        if flags == ScreenFlags::default() {
+            self.screens.remove(&screen);
+        } else {
+            self.screens.insert(screen, flags);
+        }
+    }
+
+    /// The pallet that the level's pallet regions use at a scanline of the screen, or `None` if
+    /// the metatileset's pallet is used there
+    pub fn region_pallet(&self, scanline: i32) -> Option<&Pallet> {
+        self.pallet_regions
+            .iter()
+            .filter(|x| x.scanline as i32 <= scanline)
+            .max_by_key(|x| x.scanline)
+            .map(|x| &x.pallet)
+    }
+
+    /// Move everything on the map by `dx` and `dy` metatiles.
+    ///
+    /// With `wrap`, tiles that move off of one edge of the map come back on the opposite edge.
+    /// Otherwise they are removed, and the cells that are left behind are set to `fill`, or left
+    /// empty if it is `None`. Zones move with the tiles and are cropped to the map, and screen
+    /// flags move with them when they move by a whole number of screens, see
+    /// [`Self::shift_screens`].
+    pub fn shift(&mut self, dx: i32, dy: i32, wrap: bool, fill: Option<LevelTile>) {
+        let margin = self.margin;
+        let (width, height) = (margin.width().max(1), margin.height().max(1));
+        let move_pos = |(x, y): (i32, i32)| {
+            let (x, y) = (x + dx, y + dy);
+            if wrap {
+                (
+                    (x + margin.left).rem_euclid(width) - margin.left,
+                    (y + margin.top).rem_euclid(height) - margin.top,
+                )
+            } else {
+                (x, y)
+            }
+        };
+
+        self.tiles = std::mem::take(&mut self.tiles)
+            .into_iter()
+            .map(|(pos, tile)| (move_pos(pos), tile))
+            .filter(|(pos, _)| margin_contains(margin, *pos))
+            .collect();
+        if let Some(fill) = fill.filter(|_| !wrap) {
+            for y in -margin.top..margin.bottom {
+                for x in -margin.left..margin.right {
+                    if !margin_contains(margin, (x - dx, y - dy)) {
+                        self.tiles.insert((x, y), fill.clone());
+                    }
+                }
+            }
+        }
+
+        for zone in self.zones.values_mut() {
+            (zone.x, zone.y) = move_pos((zone.x, zone.y));
+        }
+        self.crop_zones();
+        self.shift_screens(dx, dy, wrap);
+    }
+
+    /// Crop the zones to the map, removing the ones that are completely outside of it
+    fn crop_zones(&mut self) {
+        let margin = self.margin;
+        self.zones.retain(|_, zone| {
+            let (x0, x1) = (
+                zone.x.max(-margin.left),
+                (zone.x + zone.width).min(margin.right),
+            );
+            let (y0, y1) = (
+                zone.y.max(-margin.top),
+                (zone.y + zone.height).min(margin.bottom),
+            );
+            zone.x = x0;
+            zone.y = y0;
+            zone.width = x1 - x0;
+            zone.height = y1 - y0;
+            zone.width > 0 && zone.height > 0
+        });
+    }
+}
+
+/// A way of mirroring or rotating part of a map
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum MapTransform {
+    MirrorHorizontal,
+    MirrorVertical,
+    Rotate180,
+}
+
+impl MapTransform {
+    pub const ALL: [MapTransform; 3] = [
+        MapTransform::MirrorHorizontal,
+        MapTransform::MirrorVertical,
+        MapTransform::Rotate180,
+    ];
+
+    /// A human-readable name for the transform
+    pub fn name(&self) -> &'static str {
+        match self {
+            MapTransform::MirrorHorizontal => "Mirror Horizontally",
+            MapTransform::MirrorVertical => "Mirror Vertically",
+            MapTransform::Rotate180 => "Rotate 180°",
+        }
+    }
+
+    /// Get where a position inside of the rectangle from `min` to `max`, inclusive, ends up after
+    /// the transform. This works for metatiles in a map as well as pixels in a metatile.
+    pub fn apply(&self, (x, y): (i32, i32), min: (i32, i32), max: (i32, i32)) -> (i32, i32) {
+        let flip_x = (min.0 + max.0 - x, y);
+        let flip_y = (x, min.1 + max.1 - y);
+        match self {
+            MapTransform::MirrorHorizontal => flip_x,
+            MapTransform::MirrorVertical => flip_y,
+            MapTransform::Rotate180 => (flip_x.0, flip_y.1),
+        }
+    }
+}
+
+/// Whether a metatile position is inside of a level's map
+fn margin_contains(margin: LevelMargin, (x, y): (i32, i32)) -> bool {
+    (-margin.left..margin.right).contains(&x) && (-margin.top..margin.bottom).contains(&y)
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(deny_unknown_fields, default)]
 pub struct LevelMargin {
@@ -160,3 +699,682 @@ impl LevelMargin {
 pub struct LevelTile {
     pub metatileset_tile_id: Uid<MetatilesetTile>,
 }
+
+/// Game-defined flags for one screen of a level, exported as one byte per screen
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields, default)]
+pub struct ScreenFlags {
+    /// Whether the player can leave the screen through each edge, indexed by
+    /// [`MapEdge::export_id`]
+    pub exits: [bool; 4],
+    /// Whether the camera stops scrolling while it is on this screen
+    pub scroll_lock: bool,
+    /// The kind of room the screen is, up to [`ScreenFlags::MAX_ROOM_TYPE`]
+    pub room_type: u8,
+}
+
+impl ScreenFlags {
+    /// The bit set in the exported byte for the scroll lock
+    pub const SCROLL_LOCK_BIT: u8 = 0x10;
+    /// The number of bits the room type is shifted by in the exported byte
+    pub const ROOM_TYPE_SHIFT: u8 = 5;
+    /// The largest room type that fits in the exported byte
+    pub const MAX_ROOM_TYPE: u8 = 7;
+
+    /// The bit set in the exported byte when the screen has an exit through `edge`
+    pub fn exit_bit(edge: MapEdge) -> u8 {
+        1 << edge.export_id()
+    }
+
+    /// The byte exported for the screen: an exit bit for each edge in bits 0-3, the scroll lock
+    /// in bit 4, and the room type in bits 5-7
+    pub fn export_byte(&self) -> u8 {
+        let exits = MapEdge::ALL
+            .iter()
+            .filter(|edge| self.exits[edge.export_id() as usize])
+            .fold(0, |byte, edge| byte | Self::exit_bit(*edge));
+        let scroll_lock = if self.scroll_lock {
+            Self::SCROLL_LOCK_BIT
+        } else {
+            0
+        };
+        exits | scroll_lock | self.room_type.min(Self::MAX_ROOM_TYPE) << Self::ROOM_TYPE_SHIFT
+    }
+}
+
+/// The number of metatile rows at the top and bottom of a level map that are a HUD instead of
+/// part of the playfield
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields, default)]
+pub struct HudRows {
+    pub top: u8,
+    pub bottom: u8,
+}
+
+/// A change of scroll position part way down the screen, usually timed with a sprite zero hit.
+///
+/// The region above the split shows the top-left screen of the level, and the region below it
+/// shows another level, or the same one, at its own scroll position.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct ScrollSplit {
+    /// The first scanline of the lower region
+    pub scanline: u8,
+    /// The level shown in the lower region, or `None` to show this level
+    pub level_id: Option<Uid<Level>>,
+    /// The scroll position of the lower region, in pixels from the top-left of its level
+    pub scroll_x: i32,
+    pub scroll_y: i32,
+}
+
+impl Default for ScrollSplit {
+    fn default() -> Self {
+        Self {
+            scanline: 32,
+            level_id: None,
+            scroll_x: 0,
+            scroll_y: 0,
+        }
+    }
+}
+
+/// A pallet swap part way down the screen, made by rewriting the pallet during rendering.
+///
+/// From its scanline down to the next region, or the bottom of the screen, the background is shown
+/// with the region's pallet instead of the metatileset's. The attribute tables can't describe
+/// this, so the regions are exported as a table of their own for the game's raster code.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct PalletRegion {
+    /// The first scanline that uses the pallet
+    pub scanline: u8,
+    pub pallet: Pallet,
+}
+
+impl Default for PalletRegion {
+    fn default() -> Self {
+        Self {
+            scanline: 120,
+            pallet: Default::default(),
+        }
+    }
+}
+
+/// A named rectangle on a level map.
+///
+/// The position uses the same metatile coordinates as [`Level::tiles`], so zones stay with the
+/// tiles when the level margins are changed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct Zone {
+    /// A human-readable name for reference purposes
+    pub name: String,
+    pub kind: ZoneKind,
+    /// The position of the top-left metatile in the zone
+    pub x: i32,
+    pub y: i32,
+    /// The size of the zone in metatiles
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Default for Zone {
+    fn default() -> Self {
+        Self {
+            name: "New Zone".into(),
+            kind: Default::default(),
+            x: 0,
+            y: 0,
+            width: 1,
+            height: 1,
+        }
+    }
+}
+
+/// What a [`Zone`] is used for in the game
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ZoneKind {
+    /// The area that the camera is allowed to scroll over
+    Camera,
+    /// A room, such as the area the player is kept in until the room is cleared
+    Room,
+    /// An area that triggers an event when the player enters it
+    Trigger,
+}
+
+impl Default for ZoneKind {
+    fn default() -> Self {
+        Self::Room
+    }
+}
+
+impl ZoneKind {
+    pub const ALL: [ZoneKind; 3] = [ZoneKind::Camera, ZoneKind::Room, ZoneKind::Trigger];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ZoneKind::Camera => "Camera Bounds",
+            ZoneKind::Room => "Room",
+            ZoneKind::Trigger => "Trigger",
+        }
+    }
+
+    /// The value used for the zone kind in exported zone tables
+    pub fn export_id(&self) -> u8 {
+        match self {
+            ZoneKind::Camera => 0,
+            ZoneKind::Room => 1,
+            ZoneKind::Trigger => 2,
+        }
+    }
+}
+
+/// A link from a level to another level
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields, default)]
+pub struct LevelExit {
+    /// Where the player leaves the level
+    pub source: ExitSource,
+    /// The level the player goes to
+    pub target_level_id: Uid<Level>,
+    /// The zone in the target level that the player arrives in, or `None` if the game decides,
+    /// such as by using the opposite edge of the map
+    pub target_zone_id: Option<Uid<Zone>>,
+}
+
+/// Where the player leaves a level through a [`LevelExit`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExitSource {
+    /// Walking off of an edge of the map
+    Edge(MapEdge),
+    /// Entering a zone in the level
+    Zone(Uid<Zone>),
+}
+
+impl Default for ExitSource {
+    fn default() -> Self {
+        Self::Edge(MapEdge::Right)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MapEdge {
+    Top,
+    Right,
+    Bottom,
+    Left,
+}
+
+impl MapEdge {
+    pub const ALL: [MapEdge; 4] = [MapEdge::Top, MapEdge::Right, MapEdge::Bottom, MapEdge::Left];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MapEdge::Top => "Top Edge",
+            MapEdge::Right => "Right Edge",
+            MapEdge::Bottom => "Bottom Edge",
+            MapEdge::Left => "Left Edge",
+        }
+    }
+
+    /// The value used for the edge in exported connection tables
+    pub fn export_id(&self) -> u8 {
+        match self {
+            MapEdge::Top => 0,
+            MapEdge::Right => 1,
+            MapEdge::Bottom => 2,
+            MapEdge::Left => 3,
+        }
+    }
+}
+
+/// The position of a level in the world view
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields, default)]
+pub struct WorldPlacement {
+    /// The position of the level's top-left screen, in screens
+    pub x: i32,
+    pub y: i32,
+}
+
+/// The number of pixels at each edge of the 256x240 screen that may be cropped by TVs
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct Overscan {
+    pub top: u8,
+    pub right: u8,
+    pub bottom: u8,
+    pub left: u8,
+}
+
+impl Default for Overscan {
+    fn default() -> Self {
+        Self {
+            top: 8,
+            right: 0,
+            bottom: 8,
+            left: 0,
+        }
+    }
+}
+
+/// How the game maps background tiles into the pattern table, which limits how many unique tiles
+/// one screen of a level can use
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct ChrBankProfile {
+    /// The number of tiles in each CHR bank, such as 64 for 1 KB banks or 256 for a whole pattern
+    /// table
+    pub bank_tiles: u16,
+    /// The number of banks used for the background at the same time
+    pub banks: u8,
+    /// The tiles set aside for things other than the level, such as a font or the HUD
+    pub reserved_tiles: u16,
+}
+
+impl Default for ChrBankProfile {
+    fn default() -> Self {
+        Self {
+            bank_tiles: 256,
+            banks: 1,
+            reserved_tiles: 0,
+        }
+    }
+}
+
+impl ChrBankProfile {
+    /// The bank layouts of common mappers, with the name of the mappers, the tiles in each
+    /// background bank, and the number of background banks
+    pub const MAPPER_PRESETS: &'static [(&'static str, u16, u8)] = &[
+        ("NROM, CNROM, or MMC1", 256, 1),
+        ("MMC3", 128, 2),
+        ("MMC5 or VRC", 64, 4),
+    ];
+
+    /// The name of the mapper preset with the same banks, if there is one
+    pub fn mapper_name(&self) -> Option<&'static str> {
+        Self::MAPPER_PRESETS
+            .iter()
+            .find(|(_, bank_tiles, banks)| *bank_tiles == self.bank_tiles && *banks == self.banks)
+            .map(|(name, ..)| *name)
+    }
+
+    /// The number of unique tiles one screen can use
+    pub fn screen_tile_limit(&self) -> usize {
+        (self.bank_tiles as usize * self.banks as usize)
+            .min(256)
+            .saturating_sub(self.reserved_tiles as usize)
+    }
+}
+
+/// A named location on the map that the GUI can jump to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields, default)]
+pub struct MapBookmark {
+    /// A human-readable name for reference purposes
+    pub name: String,
+    /// The level that was selected when the bookmark was placed
+    pub level_id: Option<Uid<Level>>,
+    /// The map position, in the same coordinates as [`Level::world_offset`], that will be centered
+    /// in the view when jumping to the bookmark
+    pub position: egui::Vec2,
+}
+
+/// The assembler segment that exported tables are placed in by default
+pub const DEFAULT_SEGMENT: &str = "RODATA";
+
+/// A named export configuration, such as "debug" or "release"
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct ExportTarget {
+    /// The name used to select the target in the GUI and on the commandline
+    pub name: String,
+    /// The format the exported tables are written in
+    pub format: ExportFormat,
+    /// Whether to annotate the exported files with comments describing the data
+    pub comments: bool,
+    /// The codec used to compress pattern tables, for games that decompress them into CHR-RAM
+    pub chr_compression: ChrCompression,
+    /// Whether to export the tiles needed by each screen of each level, with a schedule for
+    /// streaming them into CHR-RAM during vblank
+    pub chr_streaming: bool,
+    /// The number of CHR bytes that the game can copy during one NMI, used to split the streaming
+    /// schedule into frames
+    pub nmi_transfer_budget: u16,
+    /// Whether to export each level as the nametable columns or rows that a scrolling engine
+    /// writes to the PPU as the camera moves
+    pub map_streaming: MapStreaming,
+    /// Whether to write named constants for the colors and table values in the text formats
+    pub constants: bool,
+    /// Whether to export tables tracing each metatile and pattern table tile back to the source
+    /// image tiles it came from, for external tools and debuggers
+    pub tile_sources: bool,
+    /// Whether to comment the rows of the assembly tables with the source images, tiles, and
+    /// metatiles that their bytes came from
+    pub provenance: bool,
+    /// Whether to write a CRC-32 constant for each table in the assembly and C exports, so games
+    /// and build systems can check the data
+    pub checksums: bool,
+    /// The directory the exported files are written to, relative to the directory containing the
+    /// project file. Defaults to `export/<target name>`.
+    pub output_dir: Option<PathBuf>,
+    /// Which tables are exported, for teams that only want part of the data
+    pub content: ExportContent,
+    /// The CPU address the exported tables are placed at in PRG-ROM, one after another in the
+    /// order they are exported. Used to check that they stay out of the DPCM sample region, or
+    /// `None` to skip the check.
+    pub data_origin: Option<u16>,
+    /// The number of bytes from $C000 that are kept free for DPCM samples
+    pub dpcm_reserved: u16,
+    /// The assembler segment that tables are placed in when no other segment is assigned to them
+    pub segment: String,
+    /// The assembler segment that the tables of each level are placed in, for games that spread
+    /// their data across PRG banks
+    pub level_segments: IndexMap<Uid<Level>, String>,
+    /// The assembler segment that the tables of each metatileset are placed in
+    pub metatileset_segments: IndexMap<Uid<Metatileset>, String>,
+    /// Prepended to the name of every exported table and constant, so they don't collide with
+    /// the names in the game's own code
+    pub symbol_prefix: String,
+    /// The naming convention of the exported table names
+    pub symbol_case: SymbolCase,
+    /// The bytes that make up each record of the metatile tables
+    pub metatile_layout: MetatileLayout,
+}
+
+impl Default for ExportTarget {
+    fn default() -> Self {
+        Self {
+            name: "default".into(),
+            format: Default::default(),
+            comments: true,
+            chr_compression: Default::default(),
+            chr_streaming: false,
+            nmi_transfer_budget: 128,
+            map_streaming: MapStreaming::None,
+            constants: true,
+            tile_sources: false,
+            provenance: false,
+            checksums: false,
+            output_dir: None,
+            content: ExportContent::Everything,
+            data_origin: None,
+            dpcm_reserved: 0,
+            segment: DEFAULT_SEGMENT.into(),
+            level_segments: Default::default(),
+            metatileset_segments: Default::default(),
+            symbol_prefix: String::new(),
+            symbol_case: Default::default(),
+            metatile_layout: Default::default(),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExportFormat {
+    /// One raw binary file per table
+    Binary,
+    /// A ca65 assembly source file
+    Ca65,
+    /// A C source and header file
+    C,
+    /// A static HTML report documenting the project, with images of the metatiles and maps
+    Html,
+    /// A Python module with a list for each table
+    Python,
+    /// A JavaScript module with a `Uint8Array` for each table
+    JavaScript,
+}
+
+impl Default for ExportFormat {
+    fn default() -> Self {
+        Self::Ca65
+    }
+}
+
+/// The tables written by an export target
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExportContent {
+    /// Every table
+    Everything,
+    /// Only the attribute tables of each level and its HUD, for games that build their own
+    /// nametables
+    Attributes,
+    /// Only the metatileset pallets
+    Pallets,
+}
+
+impl Default for ExportContent {
+    fn default() -> Self {
+        Self::Everything
+    }
+}
+
+impl ExportContent {
+    pub const ALL: [ExportContent; 3] = [
+        ExportContent::Everything,
+        ExportContent::Attributes,
+        ExportContent::Pallets,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExportContent::Everything => "Everything",
+            ExportContent::Attributes => "Attributes Only",
+            ExportContent::Pallets => "Pallets Only",
+        }
+    }
+}
+
+/// How level maps are exported for engines that write them into the nametables while scrolling
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MapStreaming {
+    /// Only the metatile map is exported
+    None,
+    /// The nametable columns of each level, for horizontal scrolling
+    Columns,
+    /// The nametable rows of each level, for vertical scrolling
+    Rows,
+}
+
+impl Default for MapStreaming {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+impl MapStreaming {
+    pub const ALL: [MapStreaming; 3] = [
+        MapStreaming::None,
+        MapStreaming::Columns,
+        MapStreaming::Rows,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MapStreaming::None => "None",
+            MapStreaming::Columns => "Columns (Horizontal)",
+            MapStreaming::Rows => "Rows (Vertical)",
+        }
+    }
+}
+
+/// The naming conventions that exported table names can be written in
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SymbolCase {
+    /// `level_1_map`
+    Snake,
+    /// `level1Map`
+    Camel,
+    /// `LEVEL_1_MAP`
+    Upper,
+}
+
+impl Default for SymbolCase {
+    fn default() -> Self {
+        Self::Snake
+    }
+}
+
+impl SymbolCase {
+    pub const ALL: [SymbolCase; 3] = [SymbolCase::Snake, SymbolCase::Camel, SymbolCase::Upper];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SymbolCase::Snake => "snake_case",
+            SymbolCase::Camel => "camelCase",
+            SymbolCase::Upper => "UPPER_CASE",
+        }
+    }
+}
+
+/// The compression codecs that can be used for exported pattern tables.
+///
+/// See [`crate::export::compression`] for a description of each format.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChrCompression {
+    /// Export the pattern tables uncompressed
+    None,
+    /// Run-length encoding, compatible with `vram_unrle` from neslib
+    Rle,
+    /// Each 8 byte tile plane has a flag byte marking which bytes repeat the previous byte
+    Pb8,
+    /// Byte-oriented LZSS with a 255 byte window
+    Lzss,
+    /// Donut, which packs 64 byte blocks of tile planes, compatible with the Donut decoder
+    Donut,
+    /// Use whichever codec gives the smallest result for each pattern table
+    Smallest,
+}
+
+impl Default for ChrCompression {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+impl ChrCompression {
+    pub const ALL: [ChrCompression; 6] = [
+        ChrCompression::None,
+        ChrCompression::Rle,
+        ChrCompression::Pb8,
+        ChrCompression::Lzss,
+        ChrCompression::Donut,
+        ChrCompression::Smallest,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChrCompression::None => "None",
+            ChrCompression::Rle => "RLE",
+            ChrCompression::Pb8 => "PB8",
+            ChrCompression::Lzss => "LZSS",
+            ChrCompression::Donut => "Donut",
+            ChrCompression::Smallest => "Smallest",
+        }
+    }
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 6] = [
+        ExportFormat::Binary,
+        ExportFormat::Ca65,
+        ExportFormat::C,
+        ExportFormat::Html,
+        ExportFormat::Python,
+        ExportFormat::JavaScript,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExportFormat::Binary => "Binary",
+            ExportFormat::Ca65 => "ca65 Assembly",
+            ExportFormat::C => "C",
+            ExportFormat::Html => "HTML Report",
+            ExportFormat::Python => "Python Module",
+            ExportFormat::JavaScript => "JavaScript Module",
+        }
+    }
+}
+
+/// The layout of the records in the metatile tables, for engines that copy the tables straight
+/// into memory and index them by metatile
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct MetatileLayout {
+    /// The fields of each record, in order
+    pub fields: Vec<MetatileField>,
+    /// The number of bytes each record is padded to with zeros, such as 8 so that records can be
+    /// found with shifts, or `0` to leave records unpadded
+    pub record_size: u8,
+}
+
+impl Default for MetatileLayout {
+    fn default() -> Self {
+        Self {
+            fields: vec![MetatileField::TileIndexes, MetatileField::SubPallet],
+            record_size: 0,
+        }
+    }
+}
+
+impl MetatileLayout {
+    /// The number of bytes taken by the fields, without the padding
+    pub fn fields_size(&self) -> usize {
+        self.fields.iter().map(|x| x.size()).sum()
+    }
+
+    /// The number of bytes in each record
+    pub fn record_size(&self) -> usize {
+        self.fields_size().max(self.record_size as usize)
+    }
+}
+
+/// A field of the metatile records
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MetatileField {
+    /// The pattern table indexes of the top-left, top-right, bottom-left, and bottom-right tiles
+    TileIndexes,
+    /// The pattern table indexes of the top-left, bottom-left, top-right, and bottom-right tiles,
+    /// for engines that draw a column at a time
+    ColumnTileIndexes,
+    /// The sub-pallet index, `0`-`3`
+    SubPallet,
+    /// The sub-pallet index repeated in all four bit pairs of a byte, like `$AA` for sub-pallet
+    /// 2, so the game can mask out the bits of any quadrant of an attribute byte
+    AttributeBits,
+    /// The metatile's [`MetatilesetTile::properties`]
+    Properties,
+    /// A zero byte
+    Padding,
+}
+
+impl MetatileField {
+    pub const ALL: [MetatileField; 6] = [
+        MetatileField::TileIndexes,
+        MetatileField::ColumnTileIndexes,
+        MetatileField::SubPallet,
+        MetatileField::AttributeBits,
+        MetatileField::Properties,
+        MetatileField::Padding,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MetatileField::TileIndexes => "Tile Indexes",
+            MetatileField::ColumnTileIndexes => "Tile Indexes by Column",
+            MetatileField::SubPallet => "Sub-Pallet",
+            MetatileField::AttributeBits => "Attribute Bits",
+            MetatileField::Properties => "Properties",
+            MetatileField::Padding => "Padding",
+        }
+    }
+
+    /// The number of bytes the field takes
+    pub fn size(&self) -> usize {
+        match self {
+            MetatileField::TileIndexes | MetatileField::ColumnTileIndexes => 4,
+            _ => 1,
+        }
+    }
+}
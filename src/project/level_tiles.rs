@@ -0,0 +1,208 @@
+//! Chunked storage for the tiles of a level map
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use indexmap::IndexMap;
+use serde::{ser::SerializeMap, Deserialize, Deserializer, Serialize, Serializer};
+
+use super::LevelTile;
+
+/// The width and height, in metatiles, of the chunks that level tiles are stored in
+pub const CHUNK_SIZE: i32 = 16;
+
+type Chunk = BTreeMap<(i32, i32), LevelTile>;
+
+/// The tiles of a level map, by their position on the map
+///
+/// The tiles are grouped into square chunks that are shared between clones of the map until one
+/// of them changes. This way each undo snapshot only keeps a copy of the chunks that an edit
+/// touched, and drawing part of a huge map only has to look at the chunks that are in view.
+///
+/// Every chunk is still loaded along with the rest of the project. The project file is read in
+/// one piece anyway, and a map of hundreds of screens only takes a few megabytes once it is
+/// loaded, so the chunks aren't loaded lazily.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct LevelTiles {
+    /// The chunks with at least one tile in them, by chunk position
+    chunks: BTreeMap<(i32, i32), Arc<Chunk>>,
+    len: usize,
+}
+
+/// Get the position of the chunk that a tile is stored in
+fn chunk_pos((x, y): (i32, i32)) -> (i32, i32) {
+    (x.div_euclid(CHUNK_SIZE), y.div_euclid(CHUNK_SIZE))
+}
+
+impl LevelTiles {
+    /// The number of tiles on the map
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, pos: &(i32, i32)) -> Option<&LevelTile> {
+        self.chunks.get(&chunk_pos(*pos))?.get(pos)
+    }
+
+    pub fn contains_key(&self, pos: &(i32, i32)) -> bool {
+        self.get(pos).is_some()
+    }
+
+    /// Set the tile at a position, returning the tile that was there before
+    pub fn insert(&mut self, pos: (i32, i32), tile: LevelTile) -> Option<LevelTile> {
+        let chunk = self.chunks.entry(chunk_pos(pos)).or_default();
+        if chunk.get(&pos) == Some(&tile) {
+            // Don't copy a shared chunk just to put back the tile that is already there
+            return Some(tile);
+        }
+
+        let old = Arc::make_mut(chunk).insert(pos, tile);
+        if old.is_none() {
+            self.len += 1;
+        }
+        old
+    }
+
+    /// Clear the tile at a position, returning the tile that was there
+    pub fn remove(&mut self, pos: &(i32, i32)) -> Option<LevelTile> {
+        let chunk_pos = chunk_pos(*pos);
+        let chunk = self.chunks.get_mut(&chunk_pos)?;
+        if !chunk.contains_key(pos) {
+            return None;
+        }
+
+        let old = Arc::make_mut(chunk).remove(pos);
+        if chunk.is_empty() {
+            self.chunks.remove(&chunk_pos);
+        }
+        self.len -= 1;
+        old
+    }
+
+    /// Keep only the tiles that `keep` returns `true` for
+    pub fn retain(&mut self, mut keep: impl FnMut(&(i32, i32), &LevelTile) -> bool) {
+        for chunk in self.chunks.values_mut() {
+            if chunk.iter().all(|(pos, tile)| keep(pos, tile)) {
+                continue;
+            }
+
+            let chunk = Arc::make_mut(chunk);
+            let before = chunk.len();
+            chunk.retain(|pos, tile| keep(pos, tile));
+            self.len -= before - chunk.len();
+        }
+        self.chunks.retain(|_, chunk| !chunk.is_empty());
+    }
+
+    /// Iterate over the tiles on the map, in chunk order
+    pub fn iter(&self) -> impl Iterator<Item = (&(i32, i32), &LevelTile)> + '_ {
+        self.chunks.values().flat_map(|chunk| chunk.iter())
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &(i32, i32)> + '_ {
+        self.iter().map(|(pos, _)| pos)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &LevelTile> + '_ {
+        self.iter().map(|(_, tile)| tile)
+    }
+
+    /// Iterate over the tiles from `min` up to, but not including, `max`, only looking at the
+    /// chunks that overlap that area
+    pub fn in_rect(
+        &self,
+        min: (i32, i32),
+        max: (i32, i32),
+    ) -> impl Iterator<Item = (&(i32, i32), &LevelTile)> + '_ {
+        // An empty area still needs a valid range of chunks to search
+        let last = (max.0.max(min.0 + 1) - 1, max.1.max(min.1 + 1) - 1);
+        let (min_chunk, max_chunk) = (chunk_pos(min), chunk_pos(last));
+        let in_rect = move |(x, y): (i32, i32)| x >= min.0 && x < max.0 && y >= min.1 && y < max.1;
+
+        self.chunks
+            .range((min_chunk.0, i32::MIN)..=(max_chunk.0, i32::MAX))
+            .filter(move |((_, y), _)| *y >= min_chunk.1 && *y <= max_chunk.1)
+            .flat_map(|(_, chunk)| chunk.iter())
+            .filter(move |(pos, _)| in_rect(**pos))
+    }
+}
+
+impl Extend<((i32, i32), LevelTile)> for LevelTiles {
+    fn extend<I: IntoIterator<Item = ((i32, i32), LevelTile)>>(&mut self, iter: I) {
+        for (pos, tile) in iter {
+            self.insert(pos, tile);
+        }
+    }
+}
+
+impl FromIterator<((i32, i32), LevelTile)> for LevelTiles {
+    fn from_iter<I: IntoIterator<Item = ((i32, i32), LevelTile)>>(iter: I) -> Self {
+        let mut tiles = Self::default();
+        tiles.extend(iter);
+        tiles
+    }
+}
+
+impl IntoIterator for LevelTiles {
+    type Item = ((i32, i32), LevelTile);
+    type IntoIter = std::iter::FlatMap<
+        std::collections::btree_map::IntoValues<(i32, i32), Arc<Chunk>>,
+        Chunk,
+        fn(Arc<Chunk>) -> Chunk,
+    >;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.chunks
+            .into_values()
+            .flat_map(|chunk| Arc::try_unwrap(chunk).unwrap_or_else(|chunk| (*chunk).clone()))
+    }
+}
+
+// The tiles are saved as a plain map of positions to tiles in row-major order, so the chunks
+// don't show up in the project file and a change to the chunk size doesn't reorder it
+impl Serialize for LevelTiles {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut tiles = self.iter().collect::<Vec<_>>();
+        tiles.sort_by_key(|((x, y), _)| (*y, *x));
+
+        let mut map = serializer.serialize_map(Some(self.len))?;
+        for (pos, tile) in tiles {
+            map.serialize_entry(pos, tile)?;
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for LevelTiles {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(
+            IndexMap::<(i32, i32), LevelTile>::deserialize(deserializer)?
+                .into_iter()
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiles_are_saved_in_row_major_order() {
+        let positions = [(20, 0), (0, 1), (-3, 0), (17, 1), (0, 0)];
+        let tiles = positions
+            .iter()
+            .map(|pos| (*pos, LevelTile::default()))
+            .collect::<LevelTiles>();
+
+        let ron = ron::to_string(&tiles).unwrap();
+        let saved = ron::from_str::<IndexMap<(i32, i32), LevelTile>>(&ron).unwrap();
+        assert_eq!(
+            saved.keys().copied().collect::<Vec<_>>(),
+            [(-3, 0), (0, 0), (20, 0), (0, 1), (17, 1)]
+        );
+    }
+}
@@ -0,0 +1,154 @@
+//! Starting points for new projects, laid out for common kinds of NES games
+
+use std::{fmt::Display, str::FromStr};
+
+use crate::{
+    constants::{SCREEN_HEIGHT_METATILES, SCREEN_WIDTH_METATILES},
+    Uid,
+};
+
+use super::{
+    ExportFormat, ExportTarget, HudRows, Level, LevelMargin, Metatileset, Pallet, Project,
+    ScreenFlags, WorldPlacement, Zone, ZoneKind,
+};
+
+/// A kind of project to start from
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ProjectTemplate {
+    /// An empty project
+    Blank,
+    /// One horizontally scrolling level with a status bar
+    Platformer,
+    /// A grid of single-screen rooms with exits between them
+    Adventure,
+    /// One screen that doesn't scroll
+    SingleScreen,
+}
+
+impl ProjectTemplate {
+    pub const ALL: [ProjectTemplate; 4] = [
+        ProjectTemplate::Blank,
+        ProjectTemplate::Platformer,
+        ProjectTemplate::Adventure,
+        ProjectTemplate::SingleScreen,
+    ];
+
+    /// The names used to pick the template on the commandline
+    pub const NAMES: [&'static str; 4] = ["blank", "platformer", "adventure", "single-screen"];
+
+    /// The name used to pick the template on the commandline
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProjectTemplate::Blank => Self::NAMES[0],
+            ProjectTemplate::Platformer => Self::NAMES[1],
+            ProjectTemplate::Adventure => Self::NAMES[2],
+            ProjectTemplate::SingleScreen => Self::NAMES[3],
+        }
+    }
+
+    /// Create a project from the template
+    ///
+    /// The templates don't include any source images, so their metatilesets start out empty.
+    pub fn project(&self) -> Project {
+        let metatileset_id = Uid::new();
+        let screen_width = SCREEN_WIDTH_METATILES as i32;
+        let screen_height = SCREEN_HEIGHT_METATILES as i32;
+        let new_level = |name: &str, screens_wide: i32, screens_high: i32| Level {
+            name: name.into(),
+            metatileset_id,
+            margin: LevelMargin {
+                top: 0,
+                right: screens_wide * screen_width,
+                bottom: screens_high * screen_height,
+                left: 0,
+            },
+            ..Default::default()
+        };
+
+        let level = match self {
+            ProjectTemplate::Blank => return Project::default(),
+            ProjectTemplate::Platformer => {
+                let mut level = new_level("Level 1", 8, 1);
+                level.hud = HudRows { top: 2, bottom: 0 };
+                level.zones.insert(
+                    Uid::new(),
+                    Zone {
+                        name: "Camera".into(),
+                        kind: ZoneKind::Camera,
+                        x: 0,
+                        y: 2,
+                        width: 8 * screen_width,
+                        height: screen_height - 2,
+                    },
+                );
+                level
+            }
+            ProjectTemplate::Adventure => {
+                let (width, height) = (4, 4);
+                let mut level = new_level("Overworld", width, height);
+                level.hud = HudRows { top: 3, bottom: 0 };
+                for y in 0..height {
+                    for x in 0..width {
+                        let mut flags = ScreenFlags {
+                            scroll_lock: true,
+                            ..Default::default()
+                        };
+                        flags.exits = [y > 0, x < width - 1, y < height - 1, x > 0];
+                        level.set_screen_flags((x, y), flags);
+                    }
+                }
+                level
+            }
+            ProjectTemplate::SingleScreen => new_level("Screen 1", 1, 1),
+        };
+
+        let mut project = Project::default();
+        project.metatilesets.insert(
+            metatileset_id,
+            Metatileset {
+                name: "Tiles".into(),
+                pallet: Pallet::default(),
+                ..Default::default()
+            },
+        );
+        project.export_targets.insert(
+            Uid::new(),
+            ExportTarget {
+                name: "game".into(),
+                format: ExportFormat::Ca65,
+                ..Default::default()
+            },
+        );
+
+        let level_id = Uid::new();
+        project.levels.insert(level_id, level);
+        project
+            .world_layout
+            .insert(level_id, WorldPlacement { x: 0, y: 0 });
+
+        project
+    }
+}
+
+impl Display for ProjectTemplate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for ProjectTemplate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|x| x.name() == s)
+            .ok_or_else(|| {
+                format!(
+                    "Unknown template `{}`, expected one of: {}",
+                    s,
+                    Self::NAMES.join(", ")
+                )
+            })
+    }
+}
@@ -0,0 +1,144 @@
+//! Reading the PPU pallet and pattern tables out of emulator savestates
+//!
+//! FCEUX savestates ( `.fc0`-`.fc9` and `.fcs` ) are read directly. Mesen compresses its
+//! savestates in a layout that changes between versions, so for Mesen, and any other emulator
+//! with a memory viewer, a 16 KiB dump of PPU memory is read instead.
+
+use std::{io::Read, path::Path};
+
+use anyhow::Context;
+
+use crate::{mockup::TilePixels, pallet::decode_pal_file, project::Pallet};
+
+/// The size of a dump of the PPU address space, from $0000 to $3FFF
+const PPU_DUMP_LEN: usize = 0x4000;
+
+/// Where palette RAM is in the PPU address space
+const PALETTE_RAM_ADDR: usize = 0x3F00;
+
+/// The size of both pattern tables together
+const PATTERN_TABLES_LEN: usize = 0x2000;
+
+/// The PPU state read from a savestate or memory dump
+#[derive(Debug, Clone)]
+pub struct PpuSnapshot {
+    /// The kind of file the snapshot was read from
+    pub kind: &'static str,
+    /// The background pallet from palette RAM
+    pub pallet: Pallet,
+    /// The 512 tiles of both pattern tables, or `None` if the game uses CHR-ROM, which isn't
+    /// stored in savestates
+    pub tiles: Option<Vec<TilePixels>>,
+}
+
+/// Read the PPU state from an FCEUX savestate or a PPU memory dump
+pub fn load_ppu_snapshot(path: &Path) -> anyhow::Result<PpuSnapshot> {
+    let bytes = std::fs::read(path).context("Read savestate")?;
+
+    if bytes.starts_with(b"FCSX") {
+        read_fceux_state(&bytes)
+    } else if bytes.len() == PPU_DUMP_LEN {
+        Ok(PpuSnapshot {
+            kind: "PPU memory dump",
+            pallet: decode_palette_ram(&bytes[PALETTE_RAM_ADDR..PALETTE_RAM_ADDR + 32])?,
+            tiles: Some(decode_pattern_tables(&bytes[..PATTERN_TABLES_LEN])),
+        })
+    } else if bytes.starts_with(b"FCS") {
+        anyhow::bail!("This is an FCEUX savestate from before version 2.0, which isn't supported")
+    } else if bytes.starts_with(b"MST") || bytes.starts_with(b"MSS") {
+        anyhow::bail!(
+            "Mesen savestates aren't supported, save a dump of PPU memory from Mesen's Memory \
+            Viewer and import that instead"
+        )
+    } else {
+        anyhow::bail!(
+            "Not an FCEUX savestate or a {} byte PPU memory dump",
+            PPU_DUMP_LEN
+        )
+    }
+}
+
+/// Read an FCEUX savestate
+///
+/// After a 16 byte header with the `FCSX` magic, the size of the state data, the FCEUX version,
+/// and the compressed size of the state data, or -1 if it isn't compressed, the state is a list of
+/// sections, each with a type byte and a 32-bit size. Each section is a list of chunks with a four
+/// character name, a 32-bit size, and the chunk data. All numbers are little-endian.
+fn read_fceux_state(bytes: &[u8]) -> anyhow::Result<PpuSnapshot> {
+    let header = |offset: usize| {
+        bytes
+            .get(offset..offset + 4)
+            .map(|x| u32::from_le_bytes(x.try_into().unwrap()))
+            .context("Savestate is truncated")
+    };
+    let total_len = header(4)? as usize;
+    let compressed_len = header(12)?;
+
+    let data = if compressed_len == u32::MAX {
+        bytes[16..].to_vec()
+    } else {
+        let mut data = Vec::with_capacity(total_len);
+        flate2::read::ZlibDecoder::new(&bytes[16..])
+            .read_to_end(&mut data)
+            .context("Decompress savestate")?;
+        data
+    };
+
+    let mut palette_ram = None;
+    let mut chr_ram = None;
+    let mut rest = data.as_slice();
+    while rest.len() >= 5 {
+        let section_len = u32::from_le_bytes(rest[1..5].try_into().unwrap()) as usize;
+        let section = rest
+            .get(5..5 + section_len)
+            .context("Savestate section is truncated")?;
+        rest = &rest[5 + section_len..];
+
+        let mut chunks = section;
+        while chunks.len() >= 8 {
+            let name = &chunks[..4];
+            let chunk_len = u32::from_le_bytes(chunks[4..8].try_into().unwrap()) as usize;
+            let chunk = chunks
+                .get(8..8 + chunk_len)
+                .context("Savestate chunk is truncated")?;
+            chunks = &chunks[8 + chunk_len..];
+
+            match name {
+                b"PRAM" => palette_ram = Some(chunk),
+                b"CHRR" => chr_ram = Some(chunk),
+                _ => (),
+            }
+        }
+    }
+
+    Ok(PpuSnapshot {
+        kind: "FCEUX savestate",
+        pallet: decode_palette_ram(palette_ram.context("Savestate doesn't have palette RAM")?)?,
+        tiles: chr_ram
+            .filter(|x| x.len() >= PATTERN_TABLES_LEN)
+            .map(|x| decode_pattern_tables(&x[..PATTERN_TABLES_LEN])),
+    })
+}
+
+/// Get the background pallet out of the 32 bytes of palette RAM
+fn decode_palette_ram(bytes: &[u8]) -> anyhow::Result<Pallet> {
+    // Only the low six bits of palette RAM are used
+    let colors = bytes.iter().map(|x| x & 0x3F).collect::<Vec<_>>();
+
+    decode_pal_file(&colors)
+}
+
+/// Decode the 16 byte, two bit-plane tiles of the pattern tables into color indexes
+fn decode_pattern_tables(bytes: &[u8]) -> Vec<TilePixels> {
+    bytes
+        .chunks_exact(16)
+        .map(|tile| {
+            let mut pixels = [0; 64];
+            for (i, pixel) in pixels.iter_mut().enumerate() {
+                let (row, bit) = (i / 8, 7 - i % 8);
+                *pixel = (tile[row] >> bit & 1) | (tile[row + 8] >> bit & 1) << 1;
+            }
+            pixels
+        })
+        .collect()
+}
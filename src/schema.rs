@@ -0,0 +1,604 @@
+//! A JSON Schema of the project format, for external tools that read or write project files
+//!
+//! The schema is found by tracing the [`Deserialize`] implementation of [`Project`]: instead of
+//! reading a file, a deserializer records what each type asks for and hands back placeholder
+//! values. Each trace can only follow one variant of each enum, so the project is traced until
+//! every variant of every enum has been seen. Since the schema comes from the same code that loads
+//! projects, it can't drift out of date.
+//!
+//! The schema describes project files as they would look in JSON: structs are objects, unit enum
+//! variants are strings, and enum variants with data are objects with the name of the variant as
+//! their only key. RON project files have the same shape. Maps whose keys aren't strings, such as
+//! the tile positions of a level, describe their keys with the non-standard `x-key` keyword.
+
+use std::{cell::RefCell, collections::BTreeMap};
+
+use serde::{
+    de::{
+        value::{Error, StrDeserializer},
+        DeserializeSeed, EnumAccess, MapAccess, SeqAccess, VariantAccess, Visitor,
+    },
+    Deserialize, Deserializer,
+};
+use serde_json::{json, Map, Value};
+
+use crate::project::Project;
+
+/// The most times the project is traced while looking for enum variants that haven't been seen
+const MAX_TRACES: usize = 1000;
+
+/// The placeholder for strings, which is also a valid ULID, so that IDs can be parsed from it
+const PLACEHOLDER_STRING: &str = "00000000000000000000000000";
+
+/// The fields of each struct, by name, in the order they are declared
+type StructFields = BTreeMap<&'static str, &'static [&'static str]>;
+
+/// The types found while tracing
+#[derive(Default)]
+struct Registry {
+    /// The schema of each struct, by name
+    structs: BTreeMap<&'static str, Value>,
+    /// The variant names of each enum, with the schema of each variant that has been seen
+    enums: BTreeMap<&'static str, (&'static [&'static str], Vec<Option<Value>>)>,
+    /// The fields of each struct
+    fields: StructFields,
+    /// The number of the current trace, used to take turns picking each variant of the enums
+    trace: usize,
+    /// Whether the current trace found a type or variant that hadn't been seen before
+    changed: bool,
+}
+
+/// Build the JSON Schema of the project format
+pub fn project_schema() -> anyhow::Result<Value> {
+    let (root, defs, _) = trace_project()?;
+
+    Ok(json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "NESImg project",
+        "description": format!(
+            "The project format of NESImg {}. Project files are written in RON, or in a binary \
+            format when they end in `.nesimgb`",
+            env!("CARGO_PKG_VERSION")
+        ),
+        "$ref": root["$ref"],
+        "$defs": defs,
+    }))
+}
+
+/// A hash of the shape of the project format, which changes whenever a field or enum variant is
+/// added, removed, renamed, or moved. The binary project format stores fields and variants by
+/// their position instead of their name, so this is used as its version.
+pub fn layout_hash() -> anyhow::Result<u32> {
+    let (root, defs, fields) = trace_project()?;
+    let layout = serde_json::to_string(&json!({
+        "root": root,
+        "defs": defs,
+        "fields": fields,
+    }))?;
+
+    // 32-bit FNV-1a, which is the same on every platform and build
+    Ok(layout.bytes().fold(0x811C_9DC5, |hash: u32, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    }))
+}
+
+/// Trace the project format, returning the schema of the project, the schema of every type it
+/// uses by name, and the fields of every struct in the order they are declared
+fn trace_project() -> anyhow::Result<(Value, Map<String, Value>, StructFields)> {
+    let registry = RefCell::new(Registry::default());
+    let mut root = Value::Null;
+    for trace in 0..MAX_TRACES {
+        {
+            let mut registry = registry.borrow_mut();
+            registry.trace = trace;
+            registry.changed = false;
+        }
+        Project::deserialize(Tracer {
+            registry: &registry,
+            out: &mut root,
+        })
+        .map_err(|e| anyhow::format_err!("Trace project format: {}", e))?;
+
+        let registry = registry.borrow();
+        let all_seen = registry
+            .enums
+            .values()
+            .all(|(_, variants)| variants.iter().all(Option::is_some));
+        if all_seen && !registry.changed {
+            break;
+        }
+    }
+
+    let registry = registry.into_inner();
+    let mut defs = Map::new();
+    for (name, schema) in registry.structs {
+        defs.insert(name.into(), schema);
+    }
+    for (name, (variants, schemas)) in registry.enums {
+        let variants = variants
+            .iter()
+            .zip(schemas)
+            .map(|(variant, schema)| schema.unwrap_or_else(|| json!({ "const": variant })))
+            .collect::<Vec<_>>();
+        defs.insert(name.into(), json!({ "oneOf": variants }));
+    }
+
+    Ok((root, defs, registry.fields))
+}
+
+/// A reference to a type in the `$defs` of the schema
+fn def_ref(name: &str) -> Value {
+    json!({ "$ref": format!("#/$defs/{}", name) })
+}
+
+/// The schema of an object with the given properties and no others
+fn object_schema(fields: &[&str], schemas: Vec<Value>) -> Value {
+    let properties = fields
+        .iter()
+        .map(|x| x.to_string())
+        .zip(schemas)
+        .collect::<Map<_, _>>();
+    json!({
+        "type": "object",
+        "properties": properties,
+        "additionalProperties": false,
+    })
+}
+
+/// The schema of a fixed length array
+fn tuple_schema(schemas: Vec<Value>) -> Value {
+    let len = schemas.len();
+    if schemas.windows(2).all(|x| x[0] == x[1]) {
+        json!({
+            "type": "array",
+            "items": schemas.into_iter().next().unwrap_or_else(|| json!({})),
+            "minItems": len,
+            "maxItems": len,
+        })
+    } else {
+        json!({
+            "type": "array",
+            "prefixItems": schemas,
+            "minItems": len,
+            "maxItems": len,
+        })
+    }
+}
+
+/// A deserializer that writes the schema of the type that it is asked for to `out`
+struct Tracer<'a> {
+    registry: &'a RefCell<Registry>,
+    out: &'a mut Value,
+}
+
+impl<'a> Tracer<'a> {
+    /// Trace the type of `seed`, returning its value and its schema
+    fn trace<'de, T: DeserializeSeed<'de>>(
+        registry: &RefCell<Registry>,
+        seed: T,
+    ) -> Result<(T::Value, Value), Error> {
+        let mut schema = Value::Null;
+        let value = seed.deserialize(Tracer {
+            registry,
+            out: &mut schema,
+        })?;
+        Ok((value, schema))
+    }
+}
+
+/// Implement the deserializer methods for integers, with the range of each integer type
+macro_rules! trace_integers {
+    ($($method:ident => $visit:ident($ty:ty),)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                *self.out = json!({
+                    "type": "integer",
+                    "minimum": <$ty>::MIN,
+                    "maximum": <$ty>::MAX,
+                });
+                visitor.$visit(0)
+            }
+        )*
+    };
+}
+
+impl<'de, 'a> Deserializer<'de> for Tracer<'a> {
+    type Error = Error;
+
+    trace_integers! {
+        deserialize_u8 => visit_u8(u8),
+        deserialize_u16 => visit_u16(u16),
+        deserialize_u32 => visit_u32(u32),
+        deserialize_u64 => visit_u64(u64),
+        deserialize_i8 => visit_i8(i8),
+        deserialize_i16 => visit_i16(i16),
+        deserialize_i32 => visit_i32(i32),
+        deserialize_i64 => visit_i64(i64),
+    }
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        *self.out = json!({});
+        visitor.visit_unit()
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        *self.out = json!({ "type": "boolean" });
+        visitor.visit_bool(false)
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        *self.out = json!({ "type": "number" });
+        visitor.visit_f32(0.0)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        *self.out = json!({ "type": "number" });
+        visitor.visit_f64(0.0)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        *self.out = json!({ "type": "string", "minLength": 1, "maxLength": 1 });
+        visitor.visit_char('0')
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        *self.out = json!({ "type": "string" });
+        visitor.visit_str(PLACEHOLDER_STRING)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        *self.out = json!({
+            "type": "array",
+            "items": { "type": "integer", "minimum": 0, "maximum": 255 },
+        });
+        visitor.visit_bytes(&[])
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let mut inner = Value::Null;
+        let value = visitor.visit_some(Tracer {
+            registry: self.registry,
+            out: &mut inner,
+        })?;
+        *self.out = json!({ "anyOf": [inner, { "type": "null" }] });
+        Ok(value)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        *self.out = json!({ "type": "null" });
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let mut seq = SeqTracer {
+            registry: self.registry,
+            len: 1,
+            schemas: Vec::new(),
+        };
+        let value = visitor.visit_seq(&mut seq)?;
+        *self.out = json!({
+            "type": "array",
+            "items": seq.schemas.pop().unwrap_or_else(|| json!({})),
+        });
+        Ok(value)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Error> {
+        let mut seq = SeqTracer {
+            registry: self.registry,
+            len,
+            schemas: Vec::new(),
+        };
+        let value = visitor.visit_seq(&mut seq)?;
+        *self.out = tuple_schema(seq.schemas);
+        Ok(value)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let mut map = MapTracer {
+            registry: self.registry,
+            key: None,
+            value: None,
+        };
+        let value = visitor.visit_map(&mut map)?;
+        let key = map.key.unwrap_or_else(|| json!({}));
+        let mut schema = json!({
+            "type": "object",
+            "additionalProperties": map.value.unwrap_or_else(|| json!({})),
+        });
+        if key != json!({ "type": "string" }) {
+            schema["x-key"] = key;
+        }
+        *self.out = schema;
+        Ok(value)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let mut access = StructTracer {
+            registry: self.registry,
+            fields,
+            schemas: Vec::new(),
+        };
+        let value = visitor.visit_map(&mut access)?;
+
+        let schema = object_schema(fields, access.schemas);
+        let mut registry = self.registry.borrow_mut();
+        registry.fields.insert(name, fields);
+        if registry.structs.insert(name, schema).is_none() {
+            registry.changed = true;
+        }
+        *self.out = def_ref(name);
+        Ok(value)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        // Trace the first variant that hasn't been seen yet, or take turns once all of them have,
+        // to reach the enums inside of each variant
+        let variant = {
+            let mut registry = self.registry.borrow_mut();
+            let trace = registry.trace;
+            let (_, seen) = registry
+                .enums
+                .entry(name)
+                .or_insert_with(|| (variants, vec![None; variants.len()]));
+            seen.iter()
+                .position(Option::is_none)
+                .unwrap_or(trace % variants.len().max(1))
+        };
+
+        let mut schema = Value::Null;
+        let value = visitor.visit_enum(EnumTracer {
+            registry: self.registry,
+            name: variants.get(variant).copied().unwrap_or_default(),
+            out: &mut schema,
+        })?;
+
+        let mut registry = self.registry.borrow_mut();
+        if let Some(seen) = registry
+            .enums
+            .get_mut(name)
+            .and_then(|x| x.1.get_mut(variant))
+        {
+            if seen.is_none() {
+                *seen = Some(schema);
+                registry.changed = true;
+            }
+        }
+        *self.out = def_ref(name);
+        Ok(value)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_any(visitor)
+    }
+}
+
+/// Hands out `len` traced elements
+struct SeqTracer<'a> {
+    registry: &'a RefCell<Registry>,
+    len: usize,
+    schemas: Vec<Value>,
+}
+
+impl<'de, 'a> SeqAccess<'de> for SeqTracer<'a> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        if self.schemas.len() == self.len {
+            return Ok(None);
+        }
+        let (value, schema) = Tracer::trace(self.registry, seed)?;
+        self.schemas.push(schema);
+        Ok(Some(value))
+    }
+}
+
+/// Hands out one traced entry
+struct MapTracer<'a> {
+    registry: &'a RefCell<Registry>,
+    key: Option<Value>,
+    value: Option<Value>,
+}
+
+impl<'de, 'a> MapAccess<'de> for MapTracer<'a> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        if self.key.is_some() {
+            return Ok(None);
+        }
+        let (key, schema) = Tracer::trace(self.registry, seed)?;
+        self.key = Some(schema);
+        Ok(Some(key))
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let (value, schema) = Tracer::trace(self.registry, seed)?;
+        self.value = Some(schema);
+        Ok(value)
+    }
+}
+
+/// Hands out every field of a struct, with a traced value for each
+struct StructTracer<'a> {
+    registry: &'a RefCell<Registry>,
+    fields: &'static [&'static str],
+    schemas: Vec<Value>,
+}
+
+impl<'de, 'a> MapAccess<'de> for StructTracer<'a> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        match self.fields.get(self.schemas.len()) {
+            Some(field) => seed
+                .deserialize(StrDeserializer::<Error>::new(field))
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let (value, schema) = Tracer::trace(self.registry, seed)?;
+        self.schemas.push(schema);
+        Ok(value)
+    }
+}
+
+/// Picks the variant `name` of an enum, and writes the schema of the variant to `out`
+struct EnumTracer<'a> {
+    registry: &'a RefCell<Registry>,
+    name: &'static str,
+    out: &'a mut Value,
+}
+
+impl<'de, 'a> EnumAccess<'de> for EnumTracer<'a> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), Error> {
+        let variant = seed.deserialize(StrDeserializer::<Error>::new(self.name))?;
+        Ok((variant, self))
+    }
+}
+
+impl<'a> EnumTracer<'a> {
+    /// The schema of a variant with data, as an object with the variant name as its only key
+    fn data_variant(&mut self, data: Value) {
+        *self.out = json!({
+            "type": "object",
+            "properties": { self.name: data },
+            "required": [self.name],
+            "additionalProperties": false,
+        });
+    }
+}
+
+impl<'de, 'a> VariantAccess<'de> for EnumTracer<'a> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        *self.out = json!({ "const": self.name });
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(mut self, seed: T) -> Result<T::Value, Error> {
+        let (value, schema) = Tracer::trace(self.registry, seed)?;
+        self.data_variant(schema);
+        Ok(value)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(mut self, len: usize, visitor: V) -> Result<V::Value, Error> {
+        let mut seq = SeqTracer {
+            registry: self.registry,
+            len,
+            schemas: Vec::new(),
+        };
+        let value = visitor.visit_seq(&mut seq)?;
+        self.data_variant(tuple_schema(seq.schemas));
+        Ok(value)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        mut self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let mut access = StructTracer {
+            registry: self.registry,
+            fields,
+            schemas: Vec::new(),
+        };
+        let value = visitor.visit_map(&mut access)?;
+        self.data_variant(object_schema(fields, access.schemas));
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::project::ProjectFormat;
+
+    #[test]
+    fn binary_project_round_trips() {
+        let mut project = Project::default();
+        project
+            .sources
+            .insert(ulid::Ulid::new().into(), "tiles.png".into());
+        project.property_flags[0] = "solid".into();
+
+        let bytes = project.to_bytes(ProjectFormat::Binary).unwrap();
+        let loaded = Project::from_bytes(&bytes, ProjectFormat::Binary).unwrap();
+        assert_eq!(loaded, project);
+    }
+
+    #[test]
+    fn binary_project_with_another_layout_is_rejected() {
+        let mut bytes = Project::default().to_bytes(ProjectFormat::Binary).unwrap();
+        // The layout version follows the 8 byte magic number
+        let version = u32::from_le_bytes(bytes[8..12].try_into().unwrap());
+        bytes[8..12].copy_from_slice(&version.wrapping_add(1).to_le_bytes());
+
+        let error = Project::from_bytes(&bytes, ProjectFormat::Binary).unwrap_err();
+        assert!(error.to_string().contains(&format!("{:08X}", version)));
+        assert_eq!(layout_hash().unwrap(), version);
+    }
+}
@@ -0,0 +1,751 @@
+//! Source image loading, shared by the GUI and the exporter
+
+use std::{
+    collections::{HashMap, HashSet},
+    io::Read,
+    path::Path,
+};
+
+use anyhow::Context;
+use image::GenericImageView;
+
+use crate::constants::NES_PALLET;
+
+/// A source image that has been reduced to the four color indexes used by NES tiles
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IndexedImage {
+    pub width: u32,
+    pub height: u32,
+    /// The color index, 0-3, of every pixel in the image, in row-major order
+    pub indexes: Vec<u8>,
+}
+
+impl IndexedImage {
+    /// Load an image from disk and convert it to color indexes, see [`IndexedImage::from_bytes`]
+    pub fn load(path: &Path, keep_pallet_order: bool) -> anyhow::Result<Self> {
+        let mut file = std::fs::OpenOptions::new().read(true).open(path)?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+
+        Self::from_bytes(&bytes, keep_pallet_order)
+    }
+
+    /// Decode an image and convert it to color indexes.
+    ///
+    /// The colors are sorted by brightness. With `keep_pallet_order`, indexed PNG and GIF images
+    /// keep the color indexes from their pallet instead, so the colors can be put in any order in
+    /// the image editor. This is opt-in for each source, see [`Project::pallet_order_sources`],
+    /// because it changes the color indexes of images that were already in a project.
+    ///
+    /// [`Project::pallet_order_sources`]: crate::project::Project::pallet_order_sources
+    pub fn from_bytes(bytes: &[u8], keep_pallet_order: bool) -> anyhow::Result<Self> {
+        if keep_pallet_order {
+            if let Some(image) = Self::from_indexed_bytes(bytes)? {
+                return Ok(image);
+            }
+        }
+
+        let image = image::load_from_memory(bytes)?;
+
+        if image.width() % 16 != 0 || image.height() % 16 != 0 {
+            anyhow::bail!("Image width and height must be a multiple of 16");
+        }
+
+        let mut colors = HashSet::new();
+
+        for (_, _, pixel) in image.pixels() {
+            colors.insert(pixel);
+        }
+
+        if colors.len() != 4 {
+            anyhow::bail!(
+                "Image must have only 4 colors, but found {} colors",
+                colors.len()
+            );
+        }
+
+        // Sort colors by brightness ( or luminocity, I'm not sure what the difference is )
+        let mut colors_sorted = colors.iter().collect::<Vec<_>>();
+        colors_sorted.sort_unstable_by(|x, y| {
+            let x = x[0] as u16 + x[1] as u16 + x[2] as u16;
+            let y = y[0] as u16 + y[1] as u16 + y[2] as u16;
+            x.cmp(&y)
+        });
+
+        let indexes = image
+            .pixels()
+            .map(|(_, _, x)| {
+                colors_sorted
+                    .iter()
+                    .position(|color| *color == &x)
+                    .expect("Color in palette") as u8
+            })
+            .collect();
+
+        Ok(Self {
+            width: image.width(),
+            height: image.height(),
+            indexes,
+        })
+    }
+
+    /// Decode an indexed PNG or GIF image, keeping its pallet indexes, or return `None` if the image
+    /// isn't indexed
+    fn from_indexed_bytes(bytes: &[u8]) -> anyhow::Result<Option<Self>> {
+        let (width, height, pallet_indexes) = match image::guess_format(bytes) {
+            Ok(image::ImageFormat::Png) => match decode_indexed_png(bytes)? {
+                Some(image) => image,
+                None => return Ok(None),
+            },
+            Ok(image::ImageFormat::Gif) => match decode_indexed_gif(bytes)? {
+                Some(image) => image,
+                None => return Ok(None),
+            },
+            _ => return Ok(None),
+        };
+
+        if width % 16 != 0 || height % 16 != 0 {
+            anyhow::bail!("Image width and height must be a multiple of 16");
+        }
+
+        // Images that only use the first four pallet entries keep their indexes as they are, and
+        // otherwise the used entries are numbered in pallet order
+        let mut used = pallet_indexes.clone();
+        used.sort_unstable();
+        used.dedup();
+        if used.len() > 4 {
+            anyhow::bail!(
+                "Image must have only 4 colors, but uses {} pallet entries",
+                used.len()
+            );
+        }
+        let indexes = if used.iter().all(|&x| x < 4) {
+            pallet_indexes
+        } else {
+            pallet_indexes
+                .iter()
+                .map(|x| used.iter().position(|y| y == x).expect("Used index") as u8)
+                .collect()
+        };
+
+        Ok(Some(Self {
+            width,
+            height,
+            indexes,
+        }))
+    }
+
+    /// Get the 64 color indexes of the 8x8 tile at the given tile coordinate, or `None` if the
+    /// tile is outside of the image.
+    pub fn tile(&self, x: u16, y: u16) -> Option<[u8; 64]> {
+        let (x, y) = (x as u32 * 8, y as u32 * 8);
+        if x + 8 > self.width || y + 8 > self.height {
+            return None;
+        }
+
+        let mut tile = [0; 64];
+        for row in 0..8 {
+            let start = ((y + row) * self.width + x) as usize;
+            tile[row as usize * 8..row as usize * 8 + 8]
+                .copy_from_slice(&self.indexes[start..start + 8]);
+        }
+
+        Some(tile)
+    }
+}
+
+/// Decode the pallet index of every pixel of an indexed PNG, or return `None` if the PNG isn't
+/// indexed
+fn decode_indexed_png(bytes: &[u8]) -> anyhow::Result<Option<(u32, u32, Vec<u8>)>> {
+    let mut decoder = png::Decoder::new(bytes);
+    decoder.set_transformations(png::Transformations::IDENTITY);
+    let mut reader = decoder.read_info()?;
+    if reader.info().color_type != png::ColorType::Indexed {
+        return Ok(None);
+    }
+
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut buffer)?;
+    let bit_depth = frame.bit_depth as usize;
+    let pixels_per_byte = 8 / bit_depth;
+    let mask = ((1u16 << bit_depth) - 1) as u8;
+
+    // Pixels smaller than a byte are packed from the most significant bits, and each row starts
+    // on a new byte
+    let mut indexes = Vec::with_capacity((frame.width * frame.height) as usize);
+    for row in buffer.chunks(frame.line_size).take(frame.height as usize) {
+        for x in 0..frame.width as usize {
+            let byte = row[x / pixels_per_byte];
+            let shift = 8 - bit_depth * (x % pixels_per_byte + 1);
+            indexes.push(byte >> shift & mask);
+        }
+    }
+
+    Ok(Some((frame.width, frame.height, indexes)))
+}
+
+/// Decode the pallet index of every pixel of the first frame of a GIF, or return `None` if the
+/// frame doesn't cover the whole image, such as in optimized animations
+fn decode_indexed_gif(bytes: &[u8]) -> anyhow::Result<Option<(u32, u32, Vec<u8>)>> {
+    let mut options = gif::DecodeOptions::new();
+    options.set_color_output(gif::ColorOutput::Indexed);
+    let mut decoder = options.read_info(bytes)?;
+    let (width, height) = (decoder.width(), decoder.height());
+
+    let frame = match decoder.read_next_frame()? {
+        Some(frame) => frame,
+        None => anyhow::bail!("GIF has no frames"),
+    };
+    if frame.left != 0 || frame.top != 0 || frame.width != width || frame.height != height {
+        return Ok(None);
+    }
+
+    Ok(Some((width as u32, height as u32, frame.buffer.to_vec())))
+}
+
+/// An 8x8 tile of an image that breaks the NES color rules
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TileColorViolation {
+    /// The x tile index in the image
+    pub x: u16,
+    /// The y tile index in the image
+    pub y: u16,
+    /// The number of distinct colors in the tile, not counting the backdrop color
+    pub color_count: usize,
+    /// The number of distinct colors in the tile that aren't in the NES pallet
+    pub non_nes_color_count: usize,
+}
+
+impl TileColorViolation {
+    /// Whether the tile uses more colors than fit in one sub-pallet
+    pub fn too_many_colors(&self) -> bool {
+        self.color_count > 3
+    }
+}
+
+/// The result of checking every 8x8 tile of an image against the NES color rules
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ColorReport {
+    /// The most common color in the image, which is assumed to be the backdrop color
+    pub backdrop: [u8; 4],
+    /// The number of distinct colors in the whole image
+    pub color_count: usize,
+    /// The tiles that use more than three colors besides the backdrop, or that use colors that
+    /// aren't in the NES pallet, in row-major order
+    pub violations: Vec<TileColorViolation>,
+}
+
+/// Check each 8x8 tile of an image for more than three colors besides the backdrop color, and for
+/// colors that aren't exactly one of the colors of the NES pallet.
+///
+/// Unlike [`IndexedImage::load`], this works on images with any number of colors, so it can be
+/// used to find the tiles that keep an image from loading.
+pub fn color_report(image: &image::RgbaImage) -> ColorReport {
+    let mut counts = HashMap::<[u8; 4], usize>::new();
+    for pixel in image.pixels() {
+        *counts.entry(pixel.0).or_default() += 1;
+    }
+    let backdrop = counts
+        .iter()
+        .max_by_key(|(color, count)| (**count, std::cmp::Reverse(**color)))
+        .map(|(color, _)| *color)
+        .unwrap_or_default();
+    let is_nes_color = |color: &[u8; 4]| {
+        NES_PALLET
+            .iter()
+            .any(|x| [x.r(), x.g(), x.b(), 255] == *color)
+    };
+
+    let mut violations = Vec::new();
+    for tile_y in (0..image.height()).step_by(8) {
+        for tile_x in (0..image.width()).step_by(8) {
+            let mut colors = HashSet::new();
+            for y in tile_y..(tile_y + 8).min(image.height()) {
+                for x in tile_x..(tile_x + 8).min(image.width()) {
+                    colors.insert(image.get_pixel(x, y).0);
+                }
+            }
+            let has_backdrop = colors.remove(&backdrop);
+
+            let violation = TileColorViolation {
+                x: (tile_x / 8) as u16,
+                y: (tile_y / 8) as u16,
+                color_count: colors.len(),
+                non_nes_color_count: colors
+                    .iter()
+                    .chain(Some(&backdrop).filter(|_| has_backdrop))
+                    .filter(|color| !is_nes_color(color))
+                    .count(),
+            };
+            if violation.too_many_colors() || violation.non_nes_color_count > 0 {
+                violations.push(violation);
+            }
+        }
+    }
+
+    ColorReport {
+        backdrop,
+        color_count: counts.len(),
+        violations,
+    }
+}
+
+/// The distinct colors of an image and the number of pixels that use each, most used first
+pub fn image_colors(image: &image::RgbaImage) -> Vec<([u8; 4], usize)> {
+    let mut counts = HashMap::<[u8; 4], usize>::new();
+    for pixel in image.pixels() {
+        *counts.entry(pixel.0).or_default() += 1;
+    }
+    let mut colors = counts.into_iter().collect::<Vec<_>>();
+    colors.sort_unstable_by_key(|(color, count)| (std::cmp::Reverse(*count), *color));
+    colors
+}
+
+/// Replace every pixel of exactly the color `from` with the opaque color `to` in the PNG file at
+/// `path`, returning the number of pixels that changed.
+///
+/// Indexed PNGs have the pallet entries with the color changed instead of their pixels, so the
+/// color indexes of every pixel stay the same. The image keeps its color type, bit depth, and
+/// extra chunks, and the file from before the change is kept next to it, see
+/// [`write_changed_png`].
+pub fn replace_color(path: &Path, from: [u8; 4], to: [u8; 3]) -> anyhow::Result<usize> {
+    let bytes = read_png(path)?;
+    let (mut png, mut buffer) = DecodedPng::decode(&bytes)?;
+
+    let mut count = 0;
+    if let Some(pallet) = &mut png.pallet {
+        let mut replaced = Vec::new();
+        for (i, color) in pallet.chunks_exact_mut(3).enumerate() {
+            let alpha = png
+                .alphas
+                .as_ref()
+                .and_then(|x| x.get(i))
+                .copied()
+                .unwrap_or(255);
+            if [color[0], color[1], color[2], alpha] == from {
+                color.copy_from_slice(&to);
+                if let Some(alpha) = png.alphas.as_mut().and_then(|x| x.get_mut(i)) {
+                    *alpha = 255;
+                }
+                replaced.push(i as u8);
+            }
+        }
+        if let Some((_, _, indexes)) = decode_indexed_png(&bytes)? {
+            count = indexes.iter().filter(|x| replaced.contains(x)).count();
+        }
+    } else {
+        for pixel in buffer
+            .chunks_mut(png.line_size)
+            .flat_map(|row| row.chunks_exact_mut(png.channels))
+        {
+            if png.rgba(pixel) == from {
+                pixel[..3].copy_from_slice(&to);
+                if let Some(alpha) = pixel.get_mut(3) {
+                    *alpha = 255;
+                }
+                count += 1;
+            }
+        }
+    }
+
+    if count > 0 {
+        write_changed_png(path, &bytes, &png.encode(&buffer)?)?;
+    }
+
+    Ok(count)
+}
+
+/// Read a PNG that is about to be changed, refusing other formats, which can't be written back
+/// without losing quality or changing how they are stored
+fn read_png(path: &Path) -> anyhow::Result<Vec<u8>> {
+    let bytes = std::fs::read(path)
+        .with_context(|| format!("Could not read image `{}`", path.display()))?;
+    if image::guess_format(&bytes).ok() != Some(image::ImageFormat::Png) {
+        anyhow::bail!(
+            "Only PNG images can be changed, save `{}` as a PNG first",
+            path.display()
+        );
+    }
+
+    Ok(bytes)
+}
+
+/// Overwrite a PNG file with its changed contents
+///
+/// The chunks that only hold extra information, such as color profiles and text, are copied
+/// from the original file. The original file is kept next to the new one, with `.bak` added to
+/// its name, so the last change can be undone by renaming it back.
+fn write_changed_png(path: &Path, original: &[u8], encoded: &[u8]) -> anyhow::Result<()> {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(".bak");
+    std::fs::write(&backup, original).with_context(|| {
+        format!(
+            "Could not back up image `{}` to `{}`",
+            path.display(),
+            Path::new(&backup).display()
+        )
+    })?;
+
+    let contents = keep_png_chunks(original, encoded)?;
+    std::fs::write(path, contents)
+        .with_context(|| format!("Could not write image `{}`", path.display()))
+}
+
+/// The PNG file signature
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+/// Split a PNG into the type and bytes of each of its chunks
+fn png_chunks(bytes: &[u8]) -> anyhow::Result<Vec<([u8; 4], &[u8])>> {
+    let mut chunks = Vec::new();
+    let mut rest = bytes
+        .strip_prefix(&PNG_SIGNATURE[..])
+        .context("Not a PNG file")?;
+    while !rest.is_empty() {
+        if rest.len() < 12 {
+            anyhow::bail!("PNG chunk is cut off");
+        }
+        let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+        let chunk_len = len.checked_add(12).filter(|&x| x <= rest.len());
+        let chunk_len = chunk_len.context("PNG chunk is cut off")?;
+        chunks.push((rest[4..8].try_into().unwrap(), &rest[..chunk_len]));
+        rest = &rest[chunk_len..];
+    }
+
+    Ok(chunks)
+}
+
+/// Put the header, pallet, transparency, and image data chunks of a newly encoded PNG in place of
+/// the ones in the original PNG, keeping the original's other chunks where they were
+fn keep_png_chunks(original: &[u8], encoded: &[u8]) -> anyhow::Result<Vec<u8>> {
+    const REPLACED: [&[u8; 4]; 4] = [b"IHDR", b"PLTE", b"tRNS", b"IDAT"];
+    let encoded = png_chunks(encoded)?;
+    let encoded_chunks = |kind: [u8; 4]| {
+        encoded
+            .iter()
+            .filter(move |(x, _)| *x == kind)
+            .map(|(_, chunk)| *chunk)
+    };
+
+    let mut contents = PNG_SIGNATURE.to_vec();
+    let mut wrote_data = false;
+    for (kind, chunk) in png_chunks(original)? {
+        if !REPLACED.contains(&&kind) {
+            contents.extend_from_slice(chunk);
+        } else if &kind != b"IDAT" {
+            contents.extend(encoded_chunks(kind).flatten());
+        } else if !wrote_data {
+            contents.extend(encoded_chunks(kind).flatten());
+            wrote_data = true;
+        }
+    }
+
+    Ok(contents)
+}
+
+/// The pixels of a PNG decoded exactly as they are stored, with what is needed to encode them
+/// again the same way
+struct DecodedPng {
+    width: u32,
+    height: u32,
+    color_type: png::ColorType,
+    bit_depth: png::BitDepth,
+    /// The pallet of an indexed PNG
+    pallet: Option<Vec<u8>>,
+    /// The alpha of each pallet entry of an indexed PNG, or the transparent color of another PNG
+    alphas: Option<Vec<u8>>,
+    /// The number of bytes of each pixel, for PNGs that aren't indexed
+    channels: usize,
+    /// The number of bytes of each row of pixels
+    line_size: usize,
+}
+
+impl DecodedPng {
+    /// Decode the pixels of an indexed PNG, or an 8-bit RGB or RGBA PNG. Other PNGs are refused,
+    /// since their colors can't be changed without converting them.
+    fn decode(bytes: &[u8]) -> anyhow::Result<(Self, Vec<u8>)> {
+        let mut decoder = png::Decoder::new(bytes);
+        decoder.set_transformations(png::Transformations::IDENTITY);
+        let mut reader = decoder.read_info()?;
+        let info = reader.info();
+        let channels = match (info.color_type, info.bit_depth) {
+            (png::ColorType::Indexed, _) => 0,
+            (png::ColorType::Rgb, png::BitDepth::Eight) => 3,
+            (png::ColorType::Rgba, png::BitDepth::Eight) => 4,
+            (color_type, bit_depth) => anyhow::bail!(
+                "{:?} PNGs with {}-bit channels can't be changed without converting them, save \
+                the image as an indexed, or 8-bit RGB or RGBA PNG first",
+                color_type,
+                bit_depth as u8
+            ),
+        };
+        let pallet = match info.color_type {
+            png::ColorType::Indexed => Some(
+                info.palette
+                    .as_deref()
+                    .context("PNG has no pallet")?
+                    .to_vec(),
+            ),
+            _ => None,
+        };
+        let mut png = Self {
+            width: info.width,
+            height: info.height,
+            color_type: info.color_type,
+            bit_depth: info.bit_depth,
+            pallet,
+            alphas: info.trns.as_deref().map(|x| x.to_vec()),
+            channels,
+            line_size: 0,
+        };
+
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let frame = reader.next_frame(&mut buffer)?;
+        buffer.truncate(frame.buffer_size());
+        png.line_size = frame.line_size;
+
+        Ok((png, buffer))
+    }
+
+    /// The color of a pixel of a PNG that isn't indexed
+    fn rgba(&self, pixel: &[u8]) -> [u8; 4] {
+        [
+            pixel[0],
+            pixel[1],
+            pixel[2],
+            pixel.get(3).copied().unwrap_or(255),
+        ]
+    }
+
+    /// Encode the pixels again, with the same color type and bit depth
+    fn encode(&self, buffer: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let mut contents = Vec::new();
+        let mut encoder = png::Encoder::new(&mut contents, self.width, self.height);
+        encoder.set_color(self.color_type);
+        encoder.set_depth(self.bit_depth);
+        if let Some(pallet) = &self.pallet {
+            encoder.set_palette(pallet.clone());
+        }
+        if let Some(alphas) = &self.alphas {
+            encoder.set_trns(alphas.clone());
+        }
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(buffer)?;
+        writer.finish()?;
+
+        Ok(contents)
+    }
+}
+
+/// A rectangle of pixels in an image
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PixelRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl PixelRegion {
+    /// The region covered by the 8x8 tile at a tile coordinate
+    pub fn tile(x: u16, y: u16) -> Self {
+        Self {
+            x: x as u32 * 8,
+            y: y as u32 * 8,
+            width: 8,
+            height: 8,
+        }
+    }
+
+    /// Shrink the region to fit in an image of the given size
+    pub fn clamp_to(self, width: u32, height: u32) -> Self {
+        let x = self.x.min(width.saturating_sub(1));
+        let y = self.y.min(height.saturating_sub(1));
+        Self {
+            x,
+            y,
+            width: self.width.min(width - x),
+            height: self.height.min(height - y),
+        }
+    }
+
+    /// Grow the region by `margin` pixels on every side, without going past the edges of an image
+    /// of the given size
+    pub fn expand(self, margin: u32, width: u32, height: u32) -> Self {
+        let x = self.x.saturating_sub(margin);
+        let y = self.y.saturating_sub(margin);
+        Self {
+            x,
+            y,
+            width: (self.x + self.width + margin).min(width) - x,
+            height: (self.y + self.height + margin).min(height) - y,
+        }
+    }
+
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        x >= self.x && y >= self.y && x < self.x + self.width && y < self.y + self.height
+    }
+
+    /// Make sure the region is inside of an image of the given size
+    fn check_bounds(self, width: u32, height: u32) -> anyhow::Result<()> {
+        if self.x + self.width > width || self.y + self.height > height {
+            anyhow::bail!(
+                "Region {}x{} at ({}, {}) is outside of the {}x{} image",
+                self.width,
+                self.height,
+                self.x,
+                self.y,
+                width,
+                height
+            );
+        }
+        Ok(())
+    }
+
+    /// The x and y coordinates of every pixel in the region, in row-major order
+    pub fn pixels(self) -> impl Iterator<Item = (u32, u32)> {
+        (self.y..self.y + self.height)
+            .flat_map(move |y| (self.x..self.x + self.width).map(move |x| (x, y)))
+    }
+}
+
+/// Set the color indexes of the pixels of `region` in the PNG file at `path`, given in row-major
+/// order, returning the number of pixels that changed.
+///
+/// Indexed PNGs have the pallet entry for each color index written to their pixels, numbered the
+/// same way as when the image is loaded with `keep_pallet_order`. Other images have the color
+/// already used by each index in the image written to them, so the image keeps its four colors.
+/// The file is written the same way as by [`replace_color`].
+pub fn paint_indexes(
+    path: &Path,
+    region: PixelRegion,
+    indexes: &[u8],
+    keep_pallet_order: bool,
+) -> anyhow::Result<usize> {
+    let bytes = read_png(path)?;
+    let (png, mut buffer) = DecodedPng::decode(&bytes)?;
+    region.check_bounds(png.width, png.height)?;
+
+    let count = if png.pallet.is_some() {
+        paint_indexed_png(
+            &bytes,
+            &png,
+            &mut buffer,
+            region,
+            indexes,
+            keep_pallet_order,
+        )?
+    } else {
+        paint_pixel_indexes(&bytes, &png, &mut buffer, region, indexes)?
+    };
+    if count > 0 {
+        write_changed_png(path, &bytes, &png.encode(&buffer)?)?;
+    }
+
+    Ok(count)
+}
+
+/// Set the pallet indexes of the pixels of `region` in an indexed PNG to the entries used for the
+/// color indexes, numbered like [`IndexedImage::from_bytes`], returning the number of pixels that
+/// changed
+fn paint_indexed_png(
+    bytes: &[u8],
+    png: &DecodedPng,
+    buffer: &mut [u8],
+    region: PixelRegion,
+    indexes: &[u8],
+    keep_pallet_order: bool,
+) -> anyhow::Result<usize> {
+    let pallet_indexes = decode_indexed_png(bytes)?
+        .map(|(_, _, pallet_indexes)| pallet_indexes)
+        .unwrap_or_default();
+
+    // The pallet entry of each color index
+    let mut entries = [None; 4];
+    if keep_pallet_order {
+        let mut used = pallet_indexes;
+        used.sort_unstable();
+        used.dedup();
+        for (idx, entry) in entries.iter_mut().enumerate() {
+            *entry = if used.iter().all(|&x| x < 4) {
+                Some(idx as u8)
+            } else {
+                used.get(idx).copied()
+            };
+        }
+    } else {
+        // Colors sorted by brightness are matched to their entries by where they are used
+        let indexed = IndexedImage::from_bytes(bytes, false)?;
+        for (&idx, &entry) in indexed.indexes.iter().zip(&pallet_indexes) {
+            entries[idx as usize & 3].get_or_insert(entry);
+        }
+    }
+    let pallet_index = |idx: u8| -> anyhow::Result<u8> {
+        entries[idx as usize & 3].with_context(|| {
+            format!(
+                "Image doesn't use color {} yet, add it to the image first",
+                idx
+            )
+        })
+    };
+
+    let bits = png.bit_depth as usize;
+    let pixels_per_byte = 8 / bits;
+    let mask = ((1u16 << bits) - 1) as u8;
+
+    let mut count = 0;
+    for ((x, y), &idx) in region.pixels().zip(indexes) {
+        let value = pallet_index(idx)?;
+        let byte = &mut buffer[y as usize * png.line_size + x as usize / pixels_per_byte];
+        let shift = 8 - bits * (x as usize % pixels_per_byte + 1);
+        if *byte >> shift & mask != value {
+            *byte = *byte & !(mask << shift) | value << shift;
+            count += 1;
+        }
+    }
+
+    Ok(count)
+}
+
+/// Set the pixels of `region` in an RGB or RGBA PNG to the colors used for the color indexes
+/// elsewhere in the image, returning the number of pixels that changed
+fn paint_pixel_indexes(
+    bytes: &[u8],
+    png: &DecodedPng,
+    buffer: &mut [u8],
+    region: PixelRegion,
+    indexes: &[u8],
+) -> anyhow::Result<usize> {
+    let indexed = IndexedImage::from_bytes(bytes, false)?;
+    let pixel_range = |x: u32, y: u32| {
+        let start = y as usize * png.line_size + x as usize * png.channels;
+        start..start + png.channels
+    };
+
+    let mut colors: [Option<Vec<u8>>; 4] = Default::default();
+    let whole_image = PixelRegion {
+        x: 0,
+        y: 0,
+        width: png.width,
+        height: png.height,
+    };
+    for ((x, y), &idx) in whole_image.pixels().zip(&indexed.indexes) {
+        colors[idx as usize & 3].get_or_insert_with(|| buffer[pixel_range(x, y)].to_vec());
+    }
+
+    let mut count = 0;
+    for ((x, y), &idx) in region.pixels().zip(indexes) {
+        let color = colors[idx as usize & 3].as_ref().with_context(|| {
+            format!(
+                "Image doesn't use color {} yet, add it to the image first",
+                idx
+            )
+        })?;
+        let pixel = &mut buffer[pixel_range(x, y)];
+        if pixel[..] != color[..] {
+            pixel.copy_from_slice(color);
+            count += 1;
+        }
+    }
+
+    Ok(count)
+}
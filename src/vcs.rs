@@ -0,0 +1,98 @@
+//! Version control status for project files, using the `git` commandline tool
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::Context;
+use path_absolutize::Absolutize;
+
+/// The git status of a file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VcsStatus {
+    /// The file isn't in a git repository, or git isn't installed
+    NotVersioned,
+    /// The file is in a repository, but hasn't been committed
+    Untracked { branch: String },
+    /// The file is the same as the last commit
+    Clean { branch: String },
+    /// The file has changes that haven't been committed
+    Modified { branch: String },
+}
+
+impl VcsStatus {
+    /// The name of the checked out branch, if the file is in a repository
+    pub fn branch(&self) -> Option<&str> {
+        match self {
+            VcsStatus::NotVersioned => None,
+            VcsStatus::Untracked { branch }
+            | VcsStatus::Clean { branch }
+            | VcsStatus::Modified { branch } => Some(branch),
+        }
+    }
+}
+
+/// Get the git status of a file
+pub fn file_status(path: &Path) -> VcsStatus {
+    let (dir, file_name) = match split_path(path) {
+        Ok(split) => split,
+        Err(_) => return VcsStatus::NotVersioned,
+    };
+
+    if !matches!(git(&dir, &["rev-parse", "--is-inside-work-tree"]), Ok(x) if x == "true") {
+        return VcsStatus::NotVersioned;
+    }
+
+    let branch = match git(&dir, &["branch", "--show-current"]) {
+        Ok(branch) if !branch.is_empty() => branch,
+        _ => "detached".into(),
+    };
+
+    if git(&dir, &["ls-files", "--error-unmatch", "--", &file_name]).is_err() {
+        return VcsStatus::Untracked { branch };
+    }
+
+    match git(&dir, &["status", "--porcelain", "--", &file_name]) {
+        Ok(status) if status.is_empty() => VcsStatus::Clean { branch },
+        Ok(_) => VcsStatus::Modified { branch },
+        Err(_) => VcsStatus::NotVersioned,
+    }
+}
+
+/// Throw away the uncommitted changes to a file, restoring the version from the last commit
+pub fn revert_file(path: &Path) -> anyhow::Result<()> {
+    let (dir, file_name) = split_path(path)?;
+    git(&dir, &["checkout", "HEAD", "--", &file_name])?;
+
+    Ok(())
+}
+
+/// Split a file path into its absolute parent directory and its file name
+fn split_path(path: &Path) -> anyhow::Result<(PathBuf, String)> {
+    let path = path.absolutize()?;
+    let dir = path.parent().context("File has no parent directory")?;
+    let file_name = path.file_name().context("Path has no file name")?;
+
+    Ok((dir.to_owned(), file_name.to_string_lossy().into_owned()))
+}
+
+/// Run a git command in a directory and return its output
+fn git(dir: &Path, args: &[&str]) -> anyhow::Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .context("Run git")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}